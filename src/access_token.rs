@@ -0,0 +1,151 @@
+// Short-lived access tokens exchanged for long-lived credentials.
+//
+// API keys embedded in notebooks and scripts tend to leak and stay valid until
+// someone notices. When enabled, clients can exchange an API key or an
+// identity provider token at `POST /auth/token` for an access token signed by
+// the proxy, and use it as their bearer token until it expires.
+//
+// # Claims
+//
+// An access token carries the identity the exchanged credential established:
+// the client identifier or JWT subject, the security profile selected by the
+// client's groups, and the scopes, model patterns and tenant of the key. It is
+// accepted exactly like the exchanged credential until it expires.
+//
+// # Signing
+//
+// Tokens are JWTs signed with HMAC-SHA256. Without a configured signing
+// secret, a random secret is generated at startup, so issued tokens stop
+// working when the proxy restarts; replicas sharing traffic need the same
+// configured secret.
+//
+// # Revocation
+//
+// Revoking or rotating a key does not invalidate access tokens issued for it
+// before; keep the TTL short.
+use crate::config::AccessTokenConfig;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+
+// Issuer of the access tokens.
+const ISSUER: &str = "panw-api-ollama";
+
+// Number of random bytes in a generated signing secret.
+const SECRET_BYTES: usize = 32;
+
+// Claims of an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessClaims {
+    // Issuer of the token
+    pub iss: String,
+
+    // Client identifier or JWT subject
+    pub sub: String,
+
+    // Issue time in seconds since the epoch
+    pub iat: i64,
+
+    // Expiry in seconds since the epoch
+    pub exp: i64,
+
+    // Credential the token was exchanged for: `api_key` or `jwt`
+    pub auth: String,
+
+    // Security profile selected by the client's groups
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    // Scopes of the exchanged key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+
+    // Model patterns of the exchanged key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+
+    // Tenant of the exchanged key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+}
+
+// Issues and validates access tokens.
+//
+// Cloning is cheap and all clones share the same signing secret.
+#[derive(Clone)]
+pub struct AccessTokens {
+    // Whether tokens are issued and accepted
+    enabled: bool,
+
+    // How long issued tokens are valid in seconds
+    ttl_secs: u64,
+
+    // Key signing issued tokens
+    encoding_key: Arc<EncodingKey>,
+
+    // Key verifying presented tokens
+    decoding_key: Arc<DecodingKey>,
+}
+
+impl AccessTokens {
+    // Creates the issuer from the configuration, generating a signing secret
+    // when none is configured.
+    pub fn new(config: &AccessTokenConfig) -> Self {
+        let secret = if config.signing_secret.is_empty() {
+            let mut secret = vec![0u8; SECRET_BYTES];
+            OsRng.fill_bytes(&mut secret);
+            secret
+        } else {
+            config.signing_secret.as_bytes().to_vec()
+        };
+
+        Self {
+            enabled: config.enabled,
+            ttl_secs: config.ttl_secs,
+            encoding_key: Arc::new(EncodingKey::from_secret(&secret)),
+            decoding_key: Arc::new(DecodingKey::from_secret(&secret)),
+        }
+    }
+
+    // Returns whether access tokens are issued and accepted.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // Returns how long issued tokens are valid in seconds.
+    pub fn ttl_secs(&self) -> u64 {
+        self.ttl_secs
+    }
+
+    // Signs a token with the given claims, setting its issuer and lifetime.
+    //
+    // # Errors
+    //
+    // Returns an error if the token cannot be signed.
+    pub fn issue(&self, mut claims: AccessClaims) -> Result<String, String> {
+        let now = chrono::Utc::now().timestamp();
+        claims.iss = ISSUER.to_string();
+        claims.iat = now;
+        claims.exp = now + self.ttl_secs as i64;
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+            .map_err(|e| e.to_string())
+    }
+
+    // Returns the claims of a valid, unexpired token, if access tokens are
+    // enabled.
+    pub fn verify(&self, token: &str) -> Option<AccessClaims> {
+        if !self.enabled || token.is_empty() {
+            return None;
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[ISSUER]);
+        validation.leeway = 0;
+        jsonwebtoken::decode::<AccessClaims>(token, &self.decoding_key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| debug!("Presented bearer token is no valid access token: {}", e))
+            .ok()
+    }
+}
@@ -0,0 +1,302 @@
+// Time-based access policies.
+//
+// Lab administrators limit when GPUs may be used, for example allowing the
+// large model tier only during office hours. An access policy names tenants,
+// client keys and models, and the weekly time windows in which their requests
+// are accepted:
+//
+// - tenants are matched against the tenant header, which is replaced by the
+//   tenant of keys bound to one
+// - keys are matched by id; only requests authenticated with a client key are
+//   matched by a policy naming keys
+// - models are matched against the models named in request bodies, as in key
+//   scopes; a policy naming models does not apply to requests naming none
+//
+// Windows are evaluated in the policy's time zone, so they follow daylight
+// saving time. A request matched by several policies must be within the
+// windows of each. The health checks and the admin API are never restricted.
+//
+// # Audit
+//
+// Denied requests are rejected with 403, logged as a warning, counted in
+// `panw_access_window_denials_total` by `policy`, and exported as
+// `access_denied` security events.
+use crate::client_auth::ClientIdentity;
+use crate::config::{AccessPolicy, AccessWindowsConfig};
+use crate::handlers::ApiError;
+use crate::key_scopes::{self, KeyGrant, MAX_REQUEST_BODY_BYTES, MODEL_FIELDS, MODEL_PATHS};
+use crate::metrics;
+use crate::security_events::{self, EventType};
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+
+// Paths that are never restricted.
+const EXEMPT_PATHS: [&str; 3] = ["/healthz", "/livez", "/readyz"];
+
+// Weekly window in local time.
+#[derive(Debug)]
+struct Window {
+    // Days the window starts on, indexed from Monday
+    days: [bool; 7],
+
+    // Start and end in minutes after midnight; an end before the start is on the next day
+    start: u32,
+    end: u32,
+}
+
+impl Window {
+    // Returns whether a local day and minute are within the window.
+    fn contains(&self, day: Weekday, minute: u32) -> bool {
+        let today = day.num_days_from_monday() as usize;
+        let yesterday = day.pred().num_days_from_monday() as usize;
+        if self.start < self.end {
+            self.days[today] && minute >= self.start && minute < self.end
+        } else {
+            (self.days[today] && minute >= self.start)
+                || (self.days[yesterday] && minute < self.end)
+        }
+    }
+}
+
+// Policy limiting matching requests to its windows.
+#[derive(Debug)]
+struct Policy {
+    // Name reported in denials
+    name: String,
+
+    // Tenants, keys and models the policy applies to; empty matches all
+    tenants: Vec<String>,
+    keys: Vec<String>,
+    models: Vec<String>,
+
+    // Time zone of the windows
+    timezone: Tz,
+
+    // Windows in which matching requests are accepted
+    windows: Vec<Window>,
+
+    // Windows as configured, for denial messages
+    description: String,
+}
+
+impl Policy {
+    // Compiles a validated policy.
+    fn new(config: &AccessPolicy, default_timezone: &str) -> Option<Self> {
+        let timezone = config.timezone(default_timezone).ok()?;
+        let mut windows = Vec::new();
+        let mut descriptions = Vec::new();
+        for window in &config.windows {
+            let weekdays = window.weekdays().ok()?;
+            let (start, end) = window.minutes().ok()?;
+            let mut days = [weekdays.is_empty(); 7];
+            for day in &weekdays {
+                days[day.num_days_from_monday() as usize] = true;
+            }
+            windows.push(Window { days, start, end });
+            descriptions.push(if weekdays.is_empty() {
+                format!("daily {}-{}", window.start, window.end)
+            } else {
+                let days: Vec<String> = weekdays.iter().map(|day| day.to_string()).collect();
+                format!("{} {}-{}", days.join(","), window.start, window.end)
+            });
+        }
+        Some(Self {
+            name: config.name.clone(),
+            tenants: config.tenants.clone(),
+            keys: config.keys.clone(),
+            models: config.models.clone(),
+            timezone,
+            windows,
+            description: format!("{} {}", descriptions.join("; "), timezone),
+        })
+    }
+
+    // Returns whether the policy applies to a request's tenant and key.
+    fn applies_to(&self, tenant: Option<&str>, key: Option<&str>) -> bool {
+        let listed = |list: &[String], value: Option<&str>| {
+            list.is_empty() || value.is_some_and(|value| list.iter().any(|entry| entry == value))
+        };
+        listed(&self.tenants, tenant) && listed(&self.keys, key)
+    }
+
+    // Returns the first of the models named by a request that the policy applies to.
+    fn matching_model<'a>(&self, models: &[&'a str]) -> Option<&'a str> {
+        models.iter().copied().find(|model| {
+            self.models
+                .iter()
+                .any(|pattern| key_scopes::model_matches(pattern, model))
+        })
+    }
+
+    // Returns whether a time is within one of the policy's windows.
+    fn allows(&self, now: DateTime<Utc>) -> bool {
+        let local = now.with_timezone(&self.timezone);
+        let minute = local.hour() * 60 + local.minute();
+        self.windows
+            .iter()
+            .any(|window| window.contains(local.weekday(), minute))
+    }
+}
+
+// Enforces the configured access policies.
+//
+// Cloning is cheap and all clones share the same policies.
+#[derive(Debug, Clone, Default)]
+pub struct AccessWindows {
+    // Policies in configuration order
+    policies: Arc<Vec<Policy>>,
+
+    // Request header naming the tenant
+    tenant_header: String,
+}
+
+impl AccessWindows {
+    // Creates the policies from the configuration.
+    //
+    // # Arguments
+    //
+    // * `config` - Validated access policies
+    // * `tenant_header` - Request header naming the tenant
+    pub fn new(config: &AccessWindowsConfig, tenant_header: &str) -> Self {
+        Self {
+            policies: Arc::new(
+                config
+                    .policies
+                    .iter()
+                    .filter_map(|policy| Policy::new(policy, &config.timezone))
+                    .collect(),
+            ),
+            tenant_header: tenant_header.to_string(),
+        }
+    }
+}
+
+// Middleware rejecting requests outside the time windows of their access policies.
+pub async fn enforce_access_windows(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let access = &state.access_windows;
+    let path = request.uri().path().to_string();
+    if access.policies.is_empty()
+        || path.starts_with("/admin")
+        || EXEMPT_PATHS.contains(&path.as_str())
+    {
+        return next.run(request).await;
+    }
+
+    let tenant = request
+        .headers()
+        .get(access.tenant_header.as_str())
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|tenant| !tenant.is_empty())
+        .map(str::to_string);
+    let key = request
+        .extensions()
+        .get::<Arc<KeyGrant>>()
+        .map(|grant| grant.id().to_string());
+    let applicable: Vec<&Policy> = access
+        .policies
+        .iter()
+        .filter(|policy| policy.applies_to(tenant.as_deref(), key.as_deref()))
+        .collect();
+    if applicable.is_empty() {
+        return next.run(request).await;
+    }
+
+    // Bodies are only read when a policy depends on the models they name
+    let reads_models = MODEL_PATHS.contains(&path.as_str())
+        && applicable.iter().any(|policy| !policy.models.is_empty());
+    let (request, body) = if reads_models {
+        let (parts, body) = request.into_parts();
+        let bytes = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                    .into_response();
+            }
+        };
+        let body = serde_json::from_slice::<Value>(&bytes).ok();
+        (Request::from_parts(parts, Body::from(bytes)), body)
+    } else {
+        (request, None)
+    };
+    let models: Vec<&str> = body
+        .as_ref()
+        .and_then(Value::as_object)
+        .map(|object| {
+            MODEL_FIELDS
+                .iter()
+                .filter_map(|field| object.get(*field).and_then(Value::as_str))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let now = Utc::now();
+    for policy in applicable {
+        let model = if policy.models.is_empty() {
+            None
+        } else {
+            match policy.matching_model(&models) {
+                Some(model) => Some(model),
+                None => continue,
+            }
+        };
+        if policy.allows(now) {
+            continue;
+        }
+
+        let client = match request.extensions().get::<ClientIdentity>() {
+            Some(ClientIdentity(Some(identity))) => Some(identity.clone()),
+            _ => None,
+        };
+        warn!(
+            "Rejected {} {} from client {} outside the windows of access policy {}",
+            request.method(),
+            path,
+            client.as_deref().unwrap_or("anonymous"),
+            policy.name
+        );
+        metrics::increment_counter(
+            "panw_access_window_denials_total",
+            "Requests rejected outside the time windows of an access policy",
+            &[("policy", policy.name.as_str())],
+        );
+        security_events::publish(
+            EventType::AccessDenied,
+            &format!(
+                "{} rejected outside the windows of access policy {}",
+                path, policy.name
+            ),
+            &[
+                ("policy", json!(policy.name)),
+                ("client", json!(client)),
+                ("tenant", json!(tenant)),
+                ("model", json!(model)),
+                ("path", json!(path)),
+            ],
+        );
+        let subject = match model {
+            Some(model) => format!("Model {}", model),
+            None => "This API".to_string(),
+        };
+        return ApiError::Forbidden(format!(
+            "{} is only available {} (access policy {})",
+            subject, policy.description, policy.name
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
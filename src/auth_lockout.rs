@@ -0,0 +1,283 @@
+// Lockout of sources that repeatedly fail authentication.
+//
+// An internet-exposed proxy is scanned and credential-stuffed as soon as it is
+// reachable. When enabled, requests rejected with 401, whether by client
+// authentication or by the admin API, count as failed attempts of their source
+// IP address and of the credential they presented. A source reaching the
+// configured number of failures within the window is locked out:
+//
+// - further requests from a locked-out IP address, or with a locked-out
+//   credential, are rejected with 429 and a `Retry-After` header before their
+//   credentials are checked, so guessing cannot continue during the lockout
+// - the lockout lasts the base duration and doubles with every further lockout
+//   of the same source, up to the maximum duration
+// - failures expire at the end of the window; successful requests do not
+//   reset them, so a valid key cannot be used to keep guessing others
+//
+// Credentials are tracked by the first 12 hex digits of their SHA-256 hash,
+// as in the request metrics, so they are never kept in memory. Addresses of
+// trusted reverse proxies are never locked out, since every user behind them
+// shares their address.
+//
+// # Audit
+//
+// Every lockout is logged as a warning naming the source, the number of
+// failures and the lockout duration, and counted in
+// `panw_auth_lockouts_total` by `source` (`ip` or `key`). Failed attempts are
+// counted in `panw_auth_failures_total` and rejections of locked-out sources
+// in `panw_auth_lockout_rejections_total`. Failed attempts and lockouts are
+// also exported as `auth_failure` and `lockout` security events; failed
+// attempts are exported even when lockout is disabled.
+//
+// # Memory
+//
+// Sources that are neither locked out nor within a window of failures are
+// dropped once the number of tracked sources exceeds `MAX_SOURCES`, forgetting
+// their earlier lockouts.
+use crate::config::AuthLockoutConfig;
+use crate::handlers::ApiError;
+use crate::metrics;
+use crate::notifications::{self, AlertKind};
+use crate::request_metrics::key_id;
+use crate::security_events::{self, EventType};
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Paths that are never locked out.
+const EXEMPT_PATHS: [&str; 3] = ["/healthz", "/livez", "/readyz"];
+
+// Number of tracked sources above which idle sources are dropped.
+const MAX_SOURCES: usize = 10_000;
+
+// Failed attempts and lockouts of one source.
+#[derive(Default)]
+struct Source {
+    // Failed attempts since the window started
+    failures: u32,
+
+    // When the first failure of the window happened
+    window_start: Option<Instant>,
+
+    // Number of lockouts so far, doubling the next lockout's duration
+    lockouts: u32,
+
+    // End of the current lockout, if any
+    locked_until: Option<Instant>,
+}
+
+impl Source {
+    // Returns the remaining lockout, if the source is locked out.
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.locked_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+}
+
+// Tracks failed authentication attempts and locks out their sources.
+//
+// Cloning the tracker is cheap and all clones share the same sources.
+#[derive(Clone)]
+pub struct AuthLockout {
+    // Failure threshold and lockout durations
+    config: Arc<AuthLockoutConfig>,
+
+    // Addresses that are never locked out
+    exempt_ips: Arc<HashSet<IpAddr>>,
+
+    // Sources keyed by `ip:<address>` or `key:<hash prefix>`
+    sources: Arc<Mutex<HashMap<String, Source>>>,
+}
+
+impl AuthLockout {
+    // Creates the tracker.
+    //
+    // # Arguments
+    //
+    // * `config` - Failure threshold and lockout durations
+    // * `exempt_ips` - Addresses of trusted reverse proxies, never locked out
+    pub fn new(config: &AuthLockoutConfig, exempt_ips: &[String]) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            exempt_ips: Arc::new(
+                exempt_ips
+                    .iter()
+                    .filter_map(|address| address.parse().ok())
+                    .collect(),
+            ),
+            sources: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the longest remaining lockout of the given sources, if any is
+    // locked out.
+    fn locked_out(&self, sources: &[String]) -> Option<Duration> {
+        let now = Instant::now();
+        let tracked = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        sources
+            .iter()
+            .filter_map(|source| tracked.get(source)?.remaining(now))
+            .max()
+    }
+
+    // Records a failed attempt of the given sources, locking out those that
+    // reach the failure threshold.
+    fn record_failure(&self, sources: &[String]) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window_secs);
+        let mut tracked = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        if tracked.len() >= MAX_SOURCES {
+            tracked.retain(|_, source| {
+                source.remaining(now).is_some()
+                    || source
+                        .window_start
+                        .is_some_and(|start| now.duration_since(start) < window)
+            });
+        }
+
+        for key in sources {
+            let source = tracked.entry(key.clone()).or_default();
+            if source
+                .window_start
+                .is_none_or(|start| now.duration_since(start) >= window)
+            {
+                source.failures = 0;
+                source.window_start = Some(now);
+            }
+            source.failures += 1;
+            if source.failures < self.config.max_failures {
+                continue;
+            }
+
+            let factor = 2u64.saturating_pow(source.lockouts.min(32));
+            let secs = self
+                .config
+                .base_lockout_secs
+                .saturating_mul(factor)
+                .min(self.config.max_lockout_secs);
+            source.lockouts += 1;
+            source.locked_until = Some(now + Duration::from_secs(secs));
+            source.failures = 0;
+            source.window_start = None;
+
+            let (kind, name) = key.split_once(':').unwrap_or(("unknown", key));
+            warn!(
+                "Locked out {} {} for {} s after {} failed authentication attempts (lockout {})",
+                kind, name, secs, self.config.max_failures, source.lockouts
+            );
+            metrics::increment_counter(
+                "panw_auth_lockouts_total",
+                "Sources locked out after repeated authentication failures",
+                &[("source", kind)],
+            );
+            security_events::publish(
+                EventType::Lockout,
+                &format!("{} {} locked out for {} s", kind, name, secs),
+                &[("source", json!(name)), ("duration_secs", json!(secs))],
+            );
+            notifications::notify(
+                AlertKind::Lockout,
+                &format!("{} {} locked out for {} s", kind, name, secs),
+                &[
+                    ("source", json!(name)),
+                    ("duration_secs", json!(secs)),
+                    ("lockouts", json!(source.lockouts)),
+                ],
+            );
+        }
+    }
+}
+
+// Middleware locking out sources that repeatedly fail authentication.
+pub async fn enforce_lockout(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let lockout = &state.auth_lockout;
+    let path = request.uri().path().to_string();
+    if EXEMPT_PATHS.contains(&path.as_str()) {
+        return next.run(request).await;
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let mut sources = Vec::new();
+    if lockout.config.enabled {
+        if let Some(ip) = peer.filter(|ip| !lockout.exempt_ips.contains(ip)) {
+            sources.push(format!("ip:{}", ip));
+        }
+        let credential = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .map(|(_, credential)| credential.trim())
+            .filter(|credential| !credential.is_empty());
+        if let Some(credential) = credential {
+            sources.push(format!("key:{}", key_id(credential)));
+        }
+    }
+
+    if let Some(remaining) = lockout.locked_out(&sources) {
+        metrics::increment_counter(
+            "panw_auth_lockout_rejections_total",
+            "Requests rejected because their source is locked out",
+            &[],
+        );
+        let retry_after_secs = remaining.as_secs_f64().ceil() as u64;
+        return ApiError::RateLimited {
+            message: format!(
+                "Too many failed authentication attempts, retry in {} s",
+                retry_after_secs
+            ),
+            retry_after_secs,
+        }
+        .into_response();
+    }
+
+    let response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        publish_failure(peer, &path);
+        if !sources.is_empty() {
+            metrics::increment_counter(
+                "panw_auth_failures_total",
+                "Requests rejected for missing or invalid credentials",
+                &[],
+            );
+            lockout.record_failure(&sources);
+        }
+    }
+    response
+}
+
+// Exports a failed authentication attempt as a security event.
+fn publish_failure(peer: Option<IpAddr>, path: &str) {
+    let realm = if path.starts_with("/admin") {
+        "admin"
+    } else {
+        "api"
+    };
+    security_events::publish(
+        EventType::AuthFailure,
+        &format!("Rejected missing or invalid credentials for {}", path),
+        &[
+            ("realm", json!(realm)),
+            ("ip", json!(peer.map(|peer| peer.to_string()))),
+            ("path", json!(path)),
+        ],
+    );
+}
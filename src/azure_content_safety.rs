@@ -0,0 +1,236 @@
+// Azure AI Content Safety as an alternative security backend.
+//
+// With the `azure_content_safety` security provider, prompts and responses are
+// analyzed by the text analysis API of an Azure AI Content Safety resource
+// instead of the PANW AI Runtime API. Its severities are mapped onto the PANW
+// scan response, so the rest of the security pipeline, including caching, the
+// circuit breaker, the failure mode and security events, works unchanged:
+//
+// - content whose severity in a harm category (`Hate`, `SelfHarm`, `Sexual`,
+//   `Violence`) reaches the category's threshold is blocked as harmful
+//   content, reported as `toxic_content` in the prompt or response findings
+// - content matching one of the configured blocklists is blocked the same way
+// - the severity thresholds become the profile name
+//
+// Severities are requested on the eight-level scale (0 to 7). The API limits
+// the length of analyzed text, so longer content is analyzed in consecutive
+// parts and blocked when any part is. Content Safety has no masking, reports
+// or asynchronous scans, so content is never masked, the admin scan report
+// endpoints cannot fetch its results, and scans of streamed responses scanned
+// asynchronously are sent as regular requests.
+use crate::config::{AzureContentSafetyConfig, AZURE_HARM_CATEGORIES};
+use crate::types::{ScanRequest, ScanResponse};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::debug;
+
+// Longest text analyzed in one request, in UTF-16 code units.
+const MAX_TEXT_LENGTH: usize = 10_000;
+
+// Response of the text analysis API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyzeResponse {
+    // Severity of the text by harm category
+    #[serde(default)]
+    categories_analysis: Vec<CategoryAnalysis>,
+
+    // Blocklist items the text matched
+    #[serde(default)]
+    blocklists_match: Vec<BlocklistMatch>,
+}
+
+// Severity of the text in a harm category.
+#[derive(Debug, Deserialize)]
+struct CategoryAnalysis {
+    category: String,
+    #[serde(default)]
+    severity: u8,
+}
+
+// Blocklist item matched by the text.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlocklistMatch {
+    #[serde(default)]
+    blocklist_name: String,
+}
+
+// Client of the Azure AI Content Safety text analysis API.
+#[derive(Debug, Clone)]
+pub struct ContentSafetyClient {
+    // Endpoint of the Content Safety resource
+    base_url: String,
+
+    // Key of the Content Safety resource
+    api_key: String,
+
+    // Version of the Content Safety API
+    api_version: String,
+
+    // Lowest severity that blocks content, by harm category
+    thresholds: HashMap<String, u8>,
+
+    // Blocklists text is matched against
+    blocklists: Vec<String>,
+
+    // Thresholds as reported in scan responses
+    profile: String,
+}
+
+impl ContentSafetyClient {
+    // Creates a client with the configured resource, thresholds and blocklists.
+    pub fn new(config: &AzureContentSafetyConfig) -> Self {
+        let thresholds: HashMap<String, u8> = AZURE_HARM_CATEGORIES
+            .iter()
+            .map(|category| {
+                let threshold = config
+                    .category_thresholds
+                    .get(*category)
+                    .copied()
+                    .unwrap_or(config.severity_threshold);
+                (category.to_string(), threshold)
+            })
+            .collect();
+        let profile = AZURE_HARM_CATEGORIES
+            .iter()
+            .map(|category| format!("{}>={}", category, thresholds[*category]))
+            .collect::<Vec<_>>()
+            .join(",");
+        Self {
+            base_url: config.endpoint.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            api_version: config.api_version.clone(),
+            thresholds,
+            blocklists: config.blocklists.clone(),
+            profile,
+        }
+    }
+
+    // Returns the endpoint of the Content Safety resource.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Builds the text analysis requests for the content of a scan, one per part
+    // of the text. Content without text needs no request.
+    pub fn requests(&self, client: &Client, payload: &ScanRequest) -> Vec<RequestBuilder> {
+        let text = payload
+            .contents
+            .iter()
+            .flat_map(|content| {
+                [
+                    &content.prompt,
+                    &content.code_prompt,
+                    &content.response,
+                    &content.code_response,
+                ]
+            })
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        split_text(&text)
+            .into_iter()
+            .map(|part| {
+                let mut body = json!({
+                    "text": part,
+                    "categories": AZURE_HARM_CATEGORIES,
+                    "outputType": "EightSeverityLevels",
+                });
+                if !self.blocklists.is_empty() {
+                    body["blocklistNames"] = json!(self.blocklists);
+                    body["haltOnBlocklistHit"] = json!(false);
+                }
+                client
+                    .post(format!("{}/contentsafety/text:analyze", self.base_url))
+                    .query(&[("api-version", self.api_version.as_str())])
+                    .header("Ocp-Apim-Subscription-Key", &self.api_key)
+                    .json(&body)
+            })
+            .collect()
+    }
+
+    // Maps the text analysis responses for the parts of a scan onto a PANW scan
+    // response for the same scan.
+    pub fn scan_response(
+        &self,
+        payload: &ScanRequest,
+        analyses: Vec<AnalyzeResponse>,
+    ) -> ScanResponse {
+        let is_prompt = payload
+            .contents
+            .iter()
+            .any(|content| content.prompt.is_some() || content.code_prompt.is_some());
+        let mut reasons: Vec<String> = Vec::new();
+        for analysis in &analyses {
+            for category in &analysis.categories_analysis {
+                let blocks = self
+                    .thresholds
+                    .get(&category.category)
+                    .is_some_and(|threshold| category.severity >= *threshold);
+                if blocks {
+                    reasons.push(format!(
+                        "{} severity {}",
+                        category.category, category.severity
+                    ));
+                }
+            }
+            for matched in &analysis.blocklists_match {
+                reasons.push(format!("blocklist {}", matched.blocklist_name));
+            }
+        }
+        reasons.sort();
+        reasons.dedup();
+        if !reasons.is_empty() {
+            debug!("Azure Content Safety flagged {}", reasons.join(", "));
+        }
+
+        let mut response = ScanResponse::default_safe_response();
+        response.scan_id = uuid::Uuid::new_v4();
+        response.report_id = response.scan_id.to_string();
+        response.tr_id = Some(payload.tr_id.clone());
+        response.profile_name = Some(self.profile.clone());
+        if !reasons.is_empty() {
+            response.category = "malicious".to_string();
+            response.action = "block".to_string();
+            if is_prompt {
+                response.prompt_detected.toxic_content = true;
+            } else {
+                response.response_detected.toxic_content = true;
+            }
+        }
+        response
+    }
+}
+
+// Splits text into parts short enough to be analyzed, preferring to break
+// after whitespace.
+fn split_text(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut length = 0;
+        let mut end = rest.len();
+        let mut last_break = None;
+        for (index, c) in rest.char_indices() {
+            length += c.len_utf16();
+            if length > MAX_TEXT_LENGTH {
+                end = last_break.unwrap_or(index);
+                break;
+            }
+            if c.is_whitespace() {
+                last_break = Some(index + c.len_utf8());
+            }
+        }
+        let (part, remainder) = rest.split_at(end);
+        if !part.trim().is_empty() {
+            parts.push(part);
+        }
+        rest = remainder;
+    }
+    parts
+}
@@ -0,0 +1,398 @@
+// AWS Bedrock Guardrails as an alternative security backend.
+//
+// With the `bedrock_guardrails` security provider, prompts and responses are
+// assessed by an existing Bedrock guardrail through the ApplyGuardrail API
+// instead of the PANW AI Runtime API. Prompts are applied as `INPUT` and
+// responses as `OUTPUT`. The guardrail's verdict is mapped onto the PANW scan
+// response, so the rest of the security pipeline, including caching, the
+// circuit breaker, the failure mode and security events, works unchanged:
+//
+// - denied topics are reported as topic violations, with the topic names as
+//   blocked topics
+// - content filters and word filters are reported as `toxic_content`, except
+//   prompt attacks, which are reported as `injection`
+// - sensitive information filters are reported as `dlp`; when the guardrail
+//   only anonymized sensitive information, the content is masked with the
+//   guardrail's output instead of blocked
+// - failed contextual grounding checks are reported as `ungrounded`, with the
+//   grounding context of a response sent as its grounding source
+//
+// Requests are signed with AWS Signature Version 4. The guardrail ID and
+// version become the profile name; ApplyGuardrail has no reports or
+// asynchronous scans, so the admin scan report endpoints cannot fetch its
+// results, and scans of streamed responses scanned asynchronously are sent as
+// regular requests.
+use crate::client_auth::to_hex;
+use crate::config::BedrockGuardrailsConfig;
+use crate::types::{ScanRequest, ScanResponse, TopicGuardRails};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+// Service name requests are signed for.
+const SIGNING_SERVICE: &str = "bedrock";
+
+// Response of the ApplyGuardrail API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardrailResponse {
+    // `GUARDRAIL_INTERVENED` when the guardrail blocked or masked content, `NONE` otherwise
+    action: String,
+
+    // Content returned by the guardrail, masked or replaced by its blocked message
+    #[serde(default)]
+    outputs: Vec<GuardrailOutput>,
+
+    // Findings of the guardrail's policies
+    #[serde(default)]
+    assessments: Vec<Value>,
+}
+
+// Content returned by the guardrail.
+#[derive(Debug, Deserialize)]
+struct GuardrailOutput {
+    #[serde(default)]
+    text: String,
+}
+
+// Findings of a guardrail intervention.
+#[derive(Debug, Default)]
+struct Findings {
+    // Denied topics that matched the content
+    topics: Vec<String>,
+
+    // Whether a content filter other than prompt attack, or a word filter, blocked the content
+    toxic: bool,
+
+    // Whether the prompt attack filter blocked the content
+    prompt_attack: bool,
+
+    // Whether a sensitive information filter blocked the content
+    sensitive_blocked: bool,
+
+    // Whether a sensitive information filter anonymized the content
+    sensitive_anonymized: bool,
+
+    // Whether a contextual grounding check blocked the content
+    ungrounded: bool,
+}
+
+impl Findings {
+    // Collects the findings of the policies that acted on the content.
+    fn new(assessments: &[Value]) -> Self {
+        let mut findings = Self::default();
+        for assessment in assessments {
+            for topic in entries(assessment, "/topicPolicy/topics") {
+                if action(topic) == "BLOCKED" {
+                    let name = topic["name"].as_str().unwrap_or_default();
+                    findings.topics.push(name.to_string());
+                }
+            }
+            for filter in entries(assessment, "/contentPolicy/filters") {
+                if action(filter) == "BLOCKED" {
+                    if filter["type"] == "PROMPT_ATTACK" {
+                        findings.prompt_attack = true;
+                    } else {
+                        findings.toxic = true;
+                    }
+                }
+            }
+            let mut words = entries(assessment, "/wordPolicy/customWords")
+                .chain(entries(assessment, "/wordPolicy/managedWordLists"));
+            findings.toxic |= words.any(|word| action(word) == "BLOCKED");
+            let sensitive = entries(assessment, "/sensitiveInformationPolicy/piiEntities")
+                .chain(entries(assessment, "/sensitiveInformationPolicy/regexes"));
+            for entity in sensitive {
+                match action(entity) {
+                    "BLOCKED" => findings.sensitive_blocked = true,
+                    "ANONYMIZED" => findings.sensitive_anonymized = true,
+                    _ => {}
+                }
+            }
+            for check in entries(assessment, "/contextualGroundingPolicy/filters") {
+                if action(check) == "BLOCKED" {
+                    findings.ungrounded = true;
+                }
+            }
+        }
+        findings
+    }
+
+    // Returns whether any policy blocked the content.
+    fn blocked(&self) -> bool {
+        !self.topics.is_empty()
+            || self.toxic
+            || self.prompt_attack
+            || self.sensitive_blocked
+            || self.ungrounded
+    }
+}
+
+// Returns the entries of a policy finding list.
+fn entries<'a>(assessment: &'a Value, pointer: &str) -> impl Iterator<Item = &'a Value> {
+    assessment
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+// Returns the action a policy took on a finding.
+fn action(finding: &Value) -> &str {
+    finding["action"].as_str().unwrap_or_default()
+}
+
+// Client of the Bedrock ApplyGuardrail API.
+#[derive(Debug, Clone)]
+pub struct GuardrailsClient {
+    // URL of the guardrail's ApplyGuardrail endpoint
+    url: String,
+
+    // Base URL of the Bedrock runtime endpoint
+    base_url: String,
+
+    // AWS region of the guardrail
+    region: String,
+
+    // Guardrail ID and version, reported as the profile name
+    profile: String,
+
+    // AWS credentials
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+}
+
+impl GuardrailsClient {
+    // Creates a client for the configured guardrail and credentials.
+    pub fn new(config: &BedrockGuardrailsConfig) -> Self {
+        let base_url = if config.endpoint.is_empty() {
+            format!("https://bedrock-runtime.{}.amazonaws.com", config.region)
+        } else {
+            config.endpoint.trim_end_matches('/').to_string()
+        };
+        // The guardrail may be given as an ARN, whose slashes belong to the path segment
+        let url = format!(
+            "{}/guardrail/{}/version/{}/apply",
+            base_url,
+            uri_encode(&config.guardrail_id),
+            uri_encode(&config.guardrail_version)
+        );
+        Self {
+            url,
+            base_url,
+            region: config.region.clone(),
+            profile: format!("{}:{}", config.guardrail_id, config.guardrail_version),
+            access_key_id: config.access_key_id.clone(),
+            secret_access_key: config.secret_access_key.clone(),
+            session_token: config.session_token.clone(),
+        }
+    }
+
+    // Returns the base URL of the Bedrock runtime endpoint.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Builds the signed ApplyGuardrail request for the content of a scan.
+    pub fn request(&self, client: &Client, payload: &ScanRequest) -> RequestBuilder {
+        let is_prompt = is_prompt(payload);
+        let mut content = Vec::new();
+        for item in &payload.contents {
+            let text = [
+                &item.prompt,
+                &item.code_prompt,
+                &item.response,
+                &item.code_response,
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+            match item.context.as_deref().filter(|_| !is_prompt) {
+                Some(context) => {
+                    content.push(json!({
+                        "text": { "text": context, "qualifiers": ["grounding_source"] }
+                    }));
+                    content.push(json!({
+                        "text": { "text": text, "qualifiers": ["guard_content"] }
+                    }));
+                }
+                None => content.push(json!({ "text": { "text": text } })),
+            }
+        }
+        let body = json!({
+            "source": if is_prompt { "INPUT" } else { "OUTPUT" },
+            "content": content,
+        })
+        .to_string();
+
+        let mut request = client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        // An invalid URL cannot be signed, and fails when the request is sent
+        if let Ok(url) = Url::parse(&self.url) {
+            for (name, value) in self.sign(&url, &body, Utc::now()) {
+                request = request.header(name, value);
+            }
+        }
+        request.body(body)
+    }
+
+    // Maps an ApplyGuardrail response onto a PANW scan response for the same scan.
+    pub fn scan_response(
+        &self,
+        payload: &ScanRequest,
+        guardrail: GuardrailResponse,
+    ) -> ScanResponse {
+        let is_prompt = is_prompt(payload);
+        let mut response = ScanResponse::default_safe_response();
+        response.scan_id = uuid::Uuid::new_v4();
+        response.report_id = response.scan_id.to_string();
+        response.tr_id = Some(payload.tr_id.clone());
+        response.profile_name = Some(self.profile.clone());
+        if guardrail.action != "GUARDRAIL_INTERVENED" {
+            return response;
+        }
+
+        let findings = Findings::new(&guardrail.assessments);
+        debug!("Bedrock guardrail intervened: {:?}", findings);
+        if findings.blocked() {
+            response.category = "malicious".to_string();
+            response.action = "block".to_string();
+        }
+        let topics = (!findings.topics.is_empty()).then(|| TopicGuardRails {
+            allowed_topics: Vec::new(),
+            blocked_topics: findings.topics.clone(),
+        });
+        let dlp = findings.sensitive_blocked || findings.sensitive_anonymized;
+        let masked = guardrail
+            .outputs
+            .iter()
+            .map(|output| output.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if is_prompt {
+            let detected = &mut response.prompt_detected;
+            detected.topic_violation = topics.is_some();
+            detected.toxic_content = findings.toxic;
+            detected.injection = findings.prompt_attack;
+            detected.dlp = dlp;
+            response.prompt_detection_details.topic_guardrails_details = topics;
+            response.prompt_masked_data.data = masked;
+        } else {
+            let detected = &mut response.response_detected;
+            detected.topic_violation = topics.is_some();
+            detected.toxic_content = findings.toxic || findings.prompt_attack;
+            detected.dlp = dlp;
+            detected.ungrounded = findings.ungrounded;
+            response.response_detection_details.topic_guardrails_details = topics;
+            response.response_masked_data.data = masked;
+        }
+        response
+    }
+
+    // Returns the AWS Signature Version 4 headers for a request to a URL with the
+    // given body.
+    fn sign(&self, url: &Url, body: &str, now: DateTime<Utc>) -> Vec<(&'static str, String)> {
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = to_hex(&Sha256::digest(body.as_bytes()));
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if !self.session_token.is_empty() {
+            headers.push(("x-amz-security-token", self.session_token.clone()));
+        }
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        // Path segments are encoded once more for every service but S3
+        let canonical_uri = url
+            .path()
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        let canonical_request = format!(
+            "POST\n{}\n{}\n{}\n{}\n{}",
+            canonical_uri,
+            url.query().unwrap_or_default(),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, SIGNING_SERVICE);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            to_hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = [date.as_str(), &self.region, SIGNING_SERVICE, "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.secret_access_key).into_bytes(),
+                |key, part| hmac_sha256(&key, part),
+            );
+        let signature = to_hex(&hmac_sha256(&key, &string_to_sign));
+
+        headers.retain(|(name, _)| *name != "host");
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key_id, scope, signed_headers, signature
+            ),
+        ));
+        headers
+    }
+}
+
+// Returns whether a scan assesses a prompt rather than a response.
+fn is_prompt(payload: &ScanRequest) -> bool {
+    payload
+        .contents
+        .iter()
+        .any(|content| content.prompt.is_some() || content.code_prompt.is_some())
+}
+
+// Encodes a value the way AWS Signature Version 4 expects, leaving only
+// unreserved characters as they are.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Computes the HMAC-SHA256 of a message.
+pub(crate) fn hmac_sha256(key: &[u8], message: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(message.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
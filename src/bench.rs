@@ -0,0 +1,475 @@
+// Benchmark of the streaming assessment strategies.
+//
+// `panw-api-ollama bench-scan --corpus <path>` replays recorded Ollama streams
+// through the streaming assessment with each strategy and reports how much
+// latency the scans add and how many PANW requests they take, so operators can
+// weigh the strategies against their latency budget before enabling one.
+//
+// # Strategies
+//
+// - `sentence`: each sentence is scanned before it is released, micro-batching
+//   the sentences completed while a scan is in flight (asynchronous streaming
+//   disabled)
+// - `windowed`: content is released while its asynchronous scan is pending, up
+//   to the risk window (`--risk-window`, by default the configured
+//   `security.async_streaming.risk_window_chars`)
+// - `optimistic`: content is released as it arrives and verdicts settle behind
+//   it (asynchronous streaming with an unlimited risk window)
+//
+// # Corpus
+//
+// A corpus is an NDJSON file, or a directory of `.ndjson` files, holding
+// streamed `/api/chat` responses as recorded from Ollama, e.g. with
+// `curl -N http://ollama:11434/api/chat -d '...' > corpus/answer.ndjson`. Each
+// line is replayed as one chunk, `--chunk-interval-ms` apart.
+//
+// # Scans
+//
+// Scans are answered by the built-in mock of the PANW API, which allows all
+// content after `--latency-ms` plus a random jitter of up to `--jitter-ms`. The
+// scan cache, scan budget, circuit breaker and local checks are disabled, so
+// every strategy is measured on the same scans.
+//
+// # Report
+//
+// For each strategy the report shows the delay added to the first content and
+// to the end of the stream compared to the unscanned replay, averaged over the
+// streams, the mean, 95th percentile and largest delay of each character
+// between its arrival and its release, and the scans, polls and failed replays.
+use crate::config::{AsyncStreamingConfig, Config, PostProcessConfig, SecurityConfig};
+use crate::mock_panw::{MockPanw, MockPanwCounts};
+use crate::postprocess::ResponsePostProcessor;
+use crate::security::SecurityClient;
+use crate::stream::SecurityAssessedStream;
+use bytes::Bytes;
+use futures_util::{stream, StreamExt};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Usage of the subcommand.
+const USAGE: &str = "usage: bench-scan --corpus <file or dir> [--latency-ms <ms>] \
+                     [--jitter-ms <ms>] [--chunk-interval-ms <ms>] [--risk-window <chars>] \
+                     [--strategies <sentence,windowed,optimistic>]";
+
+// Streaming assessment strategy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Strategy {
+    Sentence,
+    Windowed,
+    Optimistic,
+}
+
+impl Strategy {
+    // Parses a strategy name.
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.trim() {
+            "sentence" => Ok(Self::Sentence),
+            "windowed" => Ok(Self::Windowed),
+            "optimistic" => Ok(Self::Optimistic),
+            other => Err(format!("unknown strategy {}\n{}", other, USAGE)),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Sentence => "sentence",
+            Self::Windowed => "windowed",
+            Self::Optimistic => "optimistic",
+        }
+    }
+
+    // Returns the asynchronous streaming settings implementing the strategy.
+    fn async_streaming(
+        self,
+        base: &AsyncStreamingConfig,
+        risk_window: usize,
+    ) -> AsyncStreamingConfig {
+        AsyncStreamingConfig {
+            enabled: self != Self::Sentence,
+            risk_window_chars: if self == Self::Optimistic {
+                usize::MAX
+            } else {
+                risk_window
+            },
+            ..base.clone()
+        }
+    }
+}
+
+// Settings of a benchmark run.
+struct Options {
+    corpus: String,
+    latency: Duration,
+    jitter: Duration,
+    chunk_interval: Duration,
+    risk_window: usize,
+    strategies: Vec<Strategy>,
+}
+
+impl Options {
+    // Parses the arguments of the subcommand.
+    fn parse(config: &Config, args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            corpus: String::new(),
+            latency: Duration::from_millis(300),
+            jitter: Duration::ZERO,
+            chunk_interval: Duration::from_millis(20),
+            risk_window: config.security.async_streaming.risk_window_chars,
+            strategies: vec![Strategy::Sentence, Strategy::Windowed, Strategy::Optimistic],
+        };
+        let millis = |value: &str| {
+            value
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| USAGE.to_string())
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or(USAGE)?.clone();
+            match flag.as_str() {
+                "--corpus" => options.corpus = value,
+                "--latency-ms" => options.latency = millis(&value)?,
+                "--jitter-ms" => options.jitter = millis(&value)?,
+                "--chunk-interval-ms" => options.chunk_interval = millis(&value)?,
+                "--risk-window" => {
+                    options.risk_window = value.parse().map_err(|_| USAGE.to_string())?
+                }
+                "--strategies" => {
+                    options.strategies = value
+                        .split(',')
+                        .map(Strategy::parse)
+                        .collect::<Result<_, _>>()?
+                }
+                _ => return Err(USAGE.to_string()),
+            }
+        }
+        if options.corpus.is_empty() || options.strategies.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        Ok(options)
+    }
+}
+
+// A recorded stream.
+struct Recording {
+    name: String,
+
+    // Model named in the stream
+    model: String,
+
+    // Lines of the stream, each replayed as one chunk
+    chunks: Vec<Bytes>,
+
+    // Characters of content in each chunk
+    content_chars: Vec<usize>,
+}
+
+impl Recording {
+    // Returns when each character of content arrives, relative to the start of
+    // the replay.
+    fn arrivals(&self, interval: Duration) -> Vec<Duration> {
+        self.content_chars
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &chars)| std::iter::repeat_n(interval * index as u32, chars))
+            .collect()
+    }
+}
+
+// Timing of one replay through the assessment.
+struct Replay {
+    // Delay added to the first content
+    first_content: Duration,
+
+    // Delay added to the end of the stream
+    completion: Duration,
+
+    // Delay of each character between its arrival and its release
+    char_delays: Vec<Duration>,
+}
+
+// Results of one strategy over the corpus.
+struct StrategyReport {
+    strategy: Strategy,
+    replays: Vec<Replay>,
+    failed: usize,
+    counts: MockPanwCounts,
+}
+
+// Runs the benchmark of the streaming assessment strategies.
+//
+// # Errors
+//
+// Returns an error if the arguments or corpus are invalid, the mock PANW API
+// cannot be started or any replay fails.
+pub async fn run_cli(config: &Config, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let options = Options::parse(config, args)?;
+    let recordings = load_corpus(&options.corpus)?;
+    let mock = MockPanw::start(options.latency, options.jitter).await?;
+
+    println!(
+        "Replaying {} streams ({} chunks, {} characters) at {} ms per chunk, \
+         PANW latency {} ms (+{} ms jitter), risk window {} characters",
+        recordings.len(),
+        recordings
+            .iter()
+            .map(|recording| recording.chunks.len())
+            .sum::<usize>(),
+        recordings
+            .iter()
+            .map(|recording| recording.content_chars.iter().sum::<usize>())
+            .sum::<usize>(),
+        options.chunk_interval.as_millis(),
+        options.latency.as_millis(),
+        options.jitter.as_millis(),
+        options.risk_window
+    );
+
+    let mut reports = Vec::new();
+    for &strategy in &options.strategies {
+        let mut security = bench_security_config(&config.security, mock.base_url());
+        security.async_streaming =
+            strategy.async_streaming(&config.security.async_streaming, options.risk_window);
+        let security_client = SecurityClient::new(security);
+
+        let before = mock.counts();
+        let mut report = StrategyReport {
+            strategy,
+            replays: Vec::new(),
+            failed: 0,
+            counts: MockPanwCounts::default(),
+        };
+        for recording in &recordings {
+            match replay(recording, security_client.clone(), options.chunk_interval).await {
+                Ok(replay) => report.replays.push(replay),
+                Err(e) => {
+                    println!("FAIL {} {}: {}", strategy.name(), recording.name, e);
+                    report.failed += 1;
+                }
+            }
+        }
+        let after = mock.counts();
+        report.counts = MockPanwCounts {
+            sync_scans: after.sync_scans - before.sync_scans,
+            async_scans: after.async_scans - before.async_scans,
+            polls: after.polls - before.polls,
+        };
+        reports.push(report);
+    }
+
+    print_reports(&reports);
+    let failed: usize = reports.iter().map(|report| report.failed).sum();
+    if failed > 0 {
+        return Err(format!("{} replays failed", failed).into());
+    }
+    Ok(())
+}
+
+// Returns the security settings scanning against the mock, with everything
+// that would skip or alter scans disabled.
+fn bench_security_config(base: &SecurityConfig, base_url: &str) -> SecurityConfig {
+    let mut security = base.clone();
+    security.base_url = base_url.to_string();
+    security.api_key = "bench".to_string();
+    security.budget.monthly_cap = 0;
+    security.scan_cache.enabled = false;
+    security.circuit_breaker.enabled = false;
+    security.response_capture.enabled = false;
+    security.secrets.enabled = false;
+    security.language.enabled = false;
+    security.toxicity.enabled = false;
+    security.prefilter_rules = Default::default();
+    security.pii.enabled = false;
+    security.decode_payloads.enabled = false;
+    security.stream_summary = false;
+    security
+}
+
+// Replays a recording through the streaming assessment.
+//
+// # Errors
+//
+// Returns an error if the assessed stream fails or blocks content.
+async fn replay(
+    recording: &Recording,
+    security_client: SecurityClient,
+    interval: Duration,
+) -> Result<Replay, String> {
+    let start = Instant::now();
+    let inner = stream::iter(recording.chunks.clone().into_iter().enumerate()).then(
+        move |(index, chunk)| async move {
+            tokio::time::sleep_until(start + interval * index as u32).await;
+            Ok::<_, reqwest::Error>(chunk)
+        },
+    );
+    let mut assessed = Box::pin(SecurityAssessedStream::new(
+        Box::pin(inner),
+        security_client,
+        ResponsePostProcessor::new(&PostProcessConfig::default()),
+        recording.model.clone(),
+        false,
+        &[],
+    ));
+
+    let mut releases = Vec::new();
+    let mut finished = start.elapsed();
+    while let Some(item) = assessed.next().await {
+        let bytes = item.map_err(|e| e.to_string())?;
+        finished = start.elapsed();
+        for chunk in parse_chunks(&bytes) {
+            if chunk["done_reason"] == "blocked" {
+                return Err("content was blocked".to_string());
+            }
+            let chars = content_chars(&chunk);
+            releases.extend(std::iter::repeat_n(finished, chars));
+        }
+    }
+
+    let arrivals = recording.arrivals(interval);
+    let first_arrival = arrivals.first().copied().unwrap_or_default();
+    let first_release = releases.first().copied().unwrap_or(finished);
+    let last_arrival = interval * recording.chunks.len().saturating_sub(1) as u32;
+    Ok(Replay {
+        first_content: first_release.saturating_sub(first_arrival),
+        completion: finished.saturating_sub(last_arrival),
+        char_delays: releases
+            .iter()
+            .zip(&arrivals)
+            .map(|(release, arrival)| release.saturating_sub(*arrival))
+            .collect(),
+    })
+}
+
+// Prints the results of each strategy.
+fn print_reports(reports: &[StrategyReport]) {
+    println!(
+        "{:<11} {:>13} {:>13} {:>11} {:>11} {:>11} {:>7} {:>7} {:>7}",
+        "strategy",
+        "first content",
+        "completion",
+        "mean delay",
+        "p95 delay",
+        "max delay",
+        "scans",
+        "polls",
+        "failed"
+    );
+    for report in reports {
+        let mean_of = |delays: Vec<Duration>| {
+            let count = delays.len().max(1) as u32;
+            delays.into_iter().sum::<Duration>() / count
+        };
+        let first_content = mean_of(
+            report
+                .replays
+                .iter()
+                .map(|replay| replay.first_content)
+                .collect(),
+        );
+        let completion = mean_of(
+            report
+                .replays
+                .iter()
+                .map(|replay| replay.completion)
+                .collect(),
+        );
+        let mut char_delays: Vec<Duration> = report
+            .replays
+            .iter()
+            .flat_map(|replay| replay.char_delays.iter().copied())
+            .collect();
+        char_delays.sort();
+        let percentile = |fraction: f64| {
+            let index = ((char_delays.len().saturating_sub(1)) as f64 * fraction).round() as usize;
+            char_delays.get(index).copied().unwrap_or_default()
+        };
+
+        println!(
+            "{:<11} {:>10} ms {:>10} ms {:>8} ms {:>8} ms {:>8} ms {:>7} {:>7} {:>7}",
+            report.strategy.name(),
+            format!("+{}", first_content.as_millis()),
+            format!("+{}", completion.as_millis()),
+            mean_of(char_delays.clone()).as_millis(),
+            percentile(0.95).as_millis(),
+            percentile(1.0).as_millis(),
+            report.counts.sync_scans + report.counts.async_scans,
+            report.counts.polls,
+            report.failed
+        );
+    }
+}
+
+// Loads the recorded streams of a file or directory.
+fn load_corpus(path: &str) -> Result<Vec<Recording>, String> {
+    let paths = if Path::new(path).is_dir() {
+        let mut paths: Vec<_> = fs::read_dir(path)
+            .map_err(|e| format!("{}: {}", path, e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "ndjson")
+            })
+            .collect();
+        paths.sort();
+        paths
+    } else {
+        vec![Path::new(path).to_path_buf()]
+    };
+
+    let recordings: Vec<Recording> = paths
+        .iter()
+        .map(|path| {
+            let name = path.display().to_string();
+            let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+            load_recording(name, &contents)
+        })
+        .collect::<Result<_, _>>()?;
+    if recordings.is_empty() {
+        return Err(format!("no recorded streams found in {}", path));
+    }
+    Ok(recordings)
+}
+
+// Parses a recorded stream.
+fn load_recording(name: String, contents: &str) -> Result<Recording, String> {
+    let mut recording = Recording {
+        name,
+        model: String::new(),
+        chunks: Vec::new(),
+        content_chars: Vec::new(),
+    };
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let chunk: Value = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: {}", recording.name, number + 1, e))?;
+        if recording.model.is_empty() {
+            recording.model = chunk["model"].as_str().unwrap_or_default().to_string();
+        }
+        recording.chunks.push(Bytes::from(format!("{}\n", line)));
+        recording.content_chars.push(content_chars(&chunk));
+    }
+    if recording.chunks.is_empty() {
+        return Err(format!("{}: empty recording", recording.name));
+    }
+    Ok(recording)
+}
+
+// Parses the NDJSON chunks released by the assessed stream.
+fn parse_chunks(bytes: &[u8]) -> Vec<Value> {
+    String::from_utf8_lossy(bytes)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+// Returns the number of characters of content in a chat chunk.
+fn content_chars(chunk: &Value) -> usize {
+    chunk["message"]["content"]
+        .as_str()
+        .map_or(0, |content| content.chars().count())
+}
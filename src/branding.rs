@@ -0,0 +1,264 @@
+// Per-tenant branding of the content injected into blocked responses.
+//
+// When content is blocked the proxy answers with a message of its own: the
+// violation message in place of the model's output, a synthetic model name
+// on the chunk that ends a blocked stream, and optionally an HTML block page.
+// Deployments shared by several teams can brand all of them per tenant,
+// resolved at request time from the tenant header.
+//
+// # Templates
+//
+// Block messages are rendered from a template in which `{category}`,
+// `{action}`, `{reasons}` and `{topics}` are replaced with the details of the
+// violation. Block pages additionally accept `{message}`, the rendered block
+// message; all values are HTML-escaped.
+//
+// # Localization
+//
+// Violation categories, actions, reasons and topic headings are looked up in
+// the configured translations and replaced when a translation exists, so
+// together with a translated template the whole message can be localized.
+use crate::config::{BrandingConfig, BrandingSettings};
+use crate::security::Assessment;
+use axum::{
+    extract::Request,
+    http::{
+        header::{ACCEPT, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Template of block messages when none is configured.
+const DEFAULT_MESSAGE_TEMPLATE: &str =
+    "\n\n⚠️ This content was blocked due to security policy violations:\n\n\
+     • Category: {category}\n\
+     • Action: {action}\n\
+     • Reasons: \n\
+      - {reasons}{topics}\n\
+     \n\nPlease reformulate your request to comply with security policies.\n\n";
+
+// Model name of the chunk ending a blocked stream when none is configured.
+const DEFAULT_MODEL_NAME: &str = "security-filter";
+
+// Reason reported when the assessment names none.
+const UNSPECIFIED_REASON: &str = "Unspecified security concern";
+
+// Block page rendered for a blocked response, attached as a response extension.
+#[derive(Debug, Clone)]
+pub struct BlockPage(pub String);
+
+// Branding of a single tenant, or of all tenants without their own.
+#[derive(Debug, Clone)]
+pub struct Branding {
+    // Template of block messages
+    message_template: String,
+
+    // Model name of the chunk ending a blocked stream
+    model_name: String,
+
+    // Template of the HTML block page, if any
+    block_page_html: Option<String>,
+
+    // Replacements of categories, actions, reasons and topic headings
+    translations: HashMap<String, String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self::new(&BrandingSettings::default(), None)
+    }
+}
+
+impl Branding {
+    // Creates the branding of the shared settings, overridden by a tenant's.
+    fn new(defaults: &BrandingSettings, tenant: Option<&BrandingSettings>) -> Self {
+        let pick = |field: fn(&BrandingSettings) -> &Option<String>| {
+            tenant
+                .and_then(|tenant| field(tenant).clone())
+                .or_else(|| field(defaults).clone())
+        };
+
+        let mut translations = defaults.translations.clone();
+        if let Some(tenant) = tenant {
+            translations.extend(tenant.translations.clone());
+        }
+
+        Self {
+            message_template: pick(|s| &s.message_template)
+                .unwrap_or_else(|| DEFAULT_MESSAGE_TEMPLATE.to_string()),
+            model_name: pick(|s| &s.model_name).unwrap_or_else(|| DEFAULT_MODEL_NAME.to_string()),
+            block_page_html: pick(|s| &s.block_page_html),
+            translations,
+        }
+    }
+
+    // Returns the model name of the chunk ending a blocked stream.
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    // Formats the block message for an assessment, including the detection
+    // reasons and topic guardrails.
+    pub fn violation_message(&self, assessment: &Assessment) -> String {
+        let details = &assessment.details;
+        let mut topics = String::new();
+        for (guardrails, suffix) in [
+            (
+                &details.prompt_detection_details.topic_guardrails_details,
+                "",
+            ),
+            (
+                &details.response_detection_details.topic_guardrails_details,
+                " (Response)",
+            ),
+        ] {
+            let Some(guardrails) = guardrails else {
+                continue;
+            };
+            for (heading, list) in [
+                ("Allowed Topics", &guardrails.allowed_topics),
+                ("Blocked Topics", &guardrails.blocked_topics),
+            ] {
+                if list.is_empty() {
+                    continue;
+                }
+                let heading = format!("{}{}", heading, suffix);
+                topics.push_str(&format!("\n• {}:\n", self.localize(&heading)));
+                for topic in list {
+                    topics.push_str(&format!("  - {}\n", topic));
+                }
+            }
+        }
+
+        self.message_template
+            .replace("{category}", &self.localize(&assessment.category))
+            .replace("{action}", &self.localize(&assessment.action))
+            .replace("{reasons}", &self.reasons(assessment).join("\n - "))
+            .replace("{topics}", &topics)
+    }
+
+    // Renders the block page for an assessment, if one is configured.
+    pub fn block_page(&self, assessment: &Assessment) -> Option<BlockPage> {
+        let template = self.block_page_html.as_ref()?;
+        Some(BlockPage(
+            template
+                .replace(
+                    "{message}",
+                    &escape_html(&self.violation_message(assessment)),
+                )
+                .replace(
+                    "{category}",
+                    &escape_html(&self.localize(&assessment.category)),
+                )
+                .replace("{action}", &escape_html(&self.localize(&assessment.action)))
+                .replace(
+                    "{reasons}",
+                    &escape_html(&self.reasons(assessment).join(", ")),
+                ),
+        ))
+    }
+
+    // Returns the localized detection reasons of an assessment.
+    fn reasons(&self, assessment: &Assessment) -> Vec<String> {
+        let reasons = assessment.reasons();
+        if reasons.is_empty() {
+            return vec![self.localize(UNSPECIFIED_REASON)];
+        }
+        reasons.iter().map(|reason| self.localize(reason)).collect()
+    }
+
+    // Returns the translation of a text, or the text itself.
+    fn localize(&self, text: &str) -> String {
+        self.translations
+            .get(text)
+            .cloned()
+            .unwrap_or_else(|| text.to_string())
+    }
+}
+
+// Brandings of all tenants, resolved per request.
+//
+// Cloning the brandings is cheap and all clones share the same settings.
+#[derive(Debug, Clone, Default)]
+pub struct TenantBrandings {
+    // Branding of tenants without their own
+    default: Arc<Branding>,
+
+    // Brandings keyed by tenant
+    tenants: Arc<HashMap<String, Arc<Branding>>>,
+}
+
+impl TenantBrandings {
+    // Creates the brandings of the configured tenants.
+    pub fn new(config: &BrandingConfig) -> Self {
+        Self {
+            default: Arc::new(Branding::new(&config.defaults, None)),
+            tenants: Arc::new(
+                config
+                    .tenants
+                    .iter()
+                    .map(|(tenant, settings)| {
+                        (
+                            tenant.clone(),
+                            Arc::new(Branding::new(&config.defaults, Some(settings))),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    // Returns the branding of a tenant, falling back to the shared branding.
+    pub fn resolve(&self, tenant: Option<&str>) -> Arc<Branding> {
+        tenant
+            .and_then(|tenant| self.tenants.get(tenant))
+            .unwrap_or(&self.default)
+            .clone()
+    }
+}
+
+// Escapes text for inclusion in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Returns whether the client prefers HTML over JSON.
+fn prefers_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|accept| accept.split(',').next())
+        .is_some_and(|preferred| preferred.trim().starts_with("text/html"))
+}
+
+// Middleware serving the block page in place of blocked responses to clients
+// that prefer HTML.
+pub async fn serve_block_page(request: Request, next: Next) -> Response {
+    let wants_html = prefers_html(request.headers());
+    let mut response = next.run(request).await;
+
+    match response.extensions_mut().remove::<BlockPage>() {
+        Some(BlockPage(page)) if wants_html => (
+            StatusCode::FORBIDDEN,
+            [(CONTENT_TYPE, "text/html; charset=utf-8")],
+            page,
+        )
+            .into_response(),
+        _ => response,
+    }
+}
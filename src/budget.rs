@@ -0,0 +1,212 @@
+// Monthly PANW scan budget tracking.
+//
+// Every scan sent to the PANW AI Runtime API is billed, so a misbehaving client
+// loop can cause a surprise overage bill. This module counts scans per calendar
+// month (UTC) against a configurable hard cap and, once the cap is reached,
+// tells the security client which degraded mode to fall back to.
+//
+// # Persistence
+//
+// The running count is written to a small JSON state file after every scan so
+// that restarting the proxy does not reset the budget. The count starts over
+// automatically when a new month begins.
+//
+// # Alerting
+//
+// The first time the cap is reached in a month an error is logged and a
+// `scan_budget_exhausted` alert is notified.
+use crate::config::{DegradedMode, ScanBudgetConfig};
+use crate::encryption;
+use crate::migrations;
+use crate::notifications::{self, AlertKind};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, info, warn};
+
+// Scan usage for a single calendar month.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct BudgetUsage {
+    // Month the count applies to, formatted as YYYY-MM
+    month: String,
+
+    // Number of scans sent this month
+    scans: u64,
+
+    // Whether the cap alert has already fired this month
+    alerted: bool,
+}
+
+// Outcome of reserving a scan against the budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetDecision {
+    // Budget available, the scan may be sent
+    Scan,
+    // Budget exhausted, fall back to the given degraded mode
+    Degraded(DegradedMode),
+}
+
+// Tracks PANW scans against the configured monthly cap.
+//
+// Cloning the budget is cheap and all clones share the same usage counter.
+#[derive(Clone)]
+pub struct ScanBudget {
+    // Cap, degraded mode, alert webhook and state file
+    config: ScanBudgetConfig,
+
+    // Usage for the current month
+    usage: Arc<Mutex<BudgetUsage>>,
+}
+
+impl ScanBudget {
+    // Creates a budget, restoring the usage from the state file when present.
+    pub fn new(config: ScanBudgetConfig) -> Self {
+        let usage = if config.monthly_cap > 0 {
+            load_usage(&config.state_file)
+        } else {
+            BudgetUsage::default()
+        };
+
+        if config.monthly_cap > 0 {
+            info!(
+                "PANW scan budget: {} of {} scans used in {}",
+                usage.scans,
+                config.monthly_cap,
+                if usage.month.is_empty() { current_month() } else { usage.month.clone() }
+            );
+        }
+
+        Self {
+            config,
+            usage: Arc::new(Mutex::new(usage)),
+        }
+    }
+
+    // Reserves one scan against the monthly budget.
+    //
+    // # Returns
+    //
+    // `BudgetDecision::Scan` when the scan may be sent, or the configured degraded
+    // mode once the cap has been reached.
+    pub fn reserve(&self) -> BudgetDecision {
+        if self.config.monthly_cap == 0 {
+            return BudgetDecision::Scan;
+        }
+
+        let month = current_month();
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+
+        if usage.month != month {
+            if !usage.month.is_empty() {
+                info!("New billing month {}, resetting PANW scan budget", month);
+            }
+            *usage = BudgetUsage {
+                month,
+                scans: 0,
+                alerted: false,
+            };
+        }
+
+        if usage.scans < self.config.monthly_cap {
+            usage.scans += 1;
+            debug!(
+                "PANW scan {} of {} this month",
+                usage.scans, self.config.monthly_cap
+            );
+            save_usage(&self.config.state_file, &usage);
+            return BudgetDecision::Scan;
+        }
+
+        if !usage.alerted {
+            usage.alerted = true;
+            save_usage(&self.config.state_file, &usage);
+            self.fire_alert(&usage);
+        }
+
+        BudgetDecision::Degraded(self.config.degraded_mode)
+    }
+
+    // Logs and notifies the alert for a month whose cap has been reached.
+    fn fire_alert(&self, usage: &BudgetUsage) {
+        error!(
+            "PANW scan budget of {} scans exhausted for {}, switching to {:?} mode",
+            self.config.monthly_cap, usage.month, self.config.degraded_mode
+        );
+
+        notifications::notify(
+            AlertKind::ScanBudgetExhausted,
+            &format!(
+                "PANW scan budget of {} scans exhausted for {}",
+                self.config.monthly_cap, usage.month
+            ),
+            &[
+                ("month", json!(usage.month)),
+                ("monthly_cap", json!(self.config.monthly_cap)),
+                ("degraded_mode", json!(self.config.degraded_mode)),
+            ],
+        );
+    }
+}
+
+// Returns the current month in UTC, formatted as YYYY-MM.
+fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+// Loads the persisted usage, starting fresh if the file is missing or unreadable.
+fn load_usage(path: &str) -> BudgetUsage {
+    match fs::read_to_string(path) {
+        Ok(contents) => encryption::open(&contents)
+            .and_then(|contents| migrations::decode("budget", &contents))
+            .and_then(|data| serde_json::from_value(data).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Ignoring invalid scan budget state file {}: {}", path, e);
+                BudgetUsage::default()
+            }),
+        Err(_) => BudgetUsage::default(),
+    }
+}
+
+// Checks that the state file holds a valid usage record.
+//
+// # Returns
+//
+// The number of records in the file, or `None` if no state file exists
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or is not a valid usage record.
+pub fn verify_state_file(path: &str) -> Result<Option<usize>, String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    verify_contents(&contents).map(Some)
+}
+
+// Checks that state file contents hold a valid usage record.
+//
+// # Returns
+//
+// The number of records, which is always one
+//
+// # Errors
+//
+// Returns an error if the contents are not a valid usage record.
+pub fn verify_contents(contents: &str) -> Result<usize, String> {
+    let data = migrations::decode("budget", &encryption::open(contents)?)?;
+    serde_json::from_value::<BudgetUsage>(data).map_err(|e| e.to_string())?;
+    Ok(1)
+}
+
+// Persists the usage so the budget survives restarts.
+fn save_usage(path: &str, usage: &BudgetUsage) {
+    let result = migrations::encode("budget", usage)
+        .and_then(|contents| encryption::write(path, &contents));
+
+    if let Err(e) = result {
+        warn!("Failed to persist scan budget state to {}: {}", path, e);
+    }
+}
@@ -0,0 +1,172 @@
+// Cache of model capabilities learned from Ollama.
+//
+// Capabilities such as the context length, the embedding dimension and support
+// for images or tools are read from `/api/show` the first time a model is
+// needed and cached per model name for the configured TTL. They are exposed at
+// `/api/capabilities` and used to validate requests before they are scanned or
+// forwarded, for example to reject images sent to a model without vision
+// support with a helpful error instead of an opaque Ollama failure.
+//
+// # Detection
+//
+// Recent Ollama versions list the capabilities of a model in `/api/show`. For
+// older versions, vision support is detected from the projector info and tool
+// support from the prompt template.
+use crate::config::CapabilitiesConfig;
+use crate::handlers::ApiError;
+use crate::ollama::OllamaClient;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+// Capabilities of a single model.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelCapabilities {
+    // Name of the model
+    pub model: String,
+
+    // Maximum context length the model was trained for
+    pub context_length: Option<u64>,
+
+    // Context window configured through the model's `num_ctx` parameter
+    pub num_ctx: Option<u64>,
+
+    // Dimension of the embeddings the model produces
+    pub embedding_length: Option<u64>,
+
+    // Whether the model accepts images
+    pub vision: bool,
+
+    // Whether the model supports tool calling
+    pub tools: bool,
+
+    // Whether the model produces embeddings
+    pub embedding: bool,
+}
+
+impl ModelCapabilities {
+    // Returns the context window the model runs with, preferring `num_ctx`.
+    pub fn context_window(&self) -> Option<u64> {
+        self.num_ctx.or(self.context_length)
+    }
+}
+
+// A cached capability lookup and when it was made.
+type CachedCapabilities = (Instant, ModelCapabilities);
+
+// Caches model capabilities learned from `/api/show`.
+//
+// Cloning the cache is cheap and all clones share the same entries.
+#[derive(Clone)]
+pub struct CapabilityCache {
+    // How long learned capabilities are reused
+    ttl: Duration,
+
+    // Capabilities keyed by model name
+    entries: Arc<Mutex<HashMap<String, CachedCapabilities>>>,
+}
+
+impl CapabilityCache {
+    // Creates an empty cache.
+    pub fn new(config: &CapabilitiesConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.cache_ttl_secs),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the capabilities of a model, learning them from `/api/show` when not cached.
+    //
+    // Returns `None` if the model is unknown to Ollama or its metadata cannot be read.
+    pub async fn get(&self, ollama: &OllamaClient, model: &str) -> Option<ModelCapabilities> {
+        {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((learned_at, capabilities)) = entries.get(model) {
+                if learned_at.elapsed() < self.ttl {
+                    return Some(capabilities.clone());
+                }
+            }
+        }
+
+        let show = match ollama.forward("/api/show", &json!({ "name": model })).await {
+            Ok(response) => response.json::<Value>().await.ok()?,
+            Err(e) => {
+                debug!("Could not learn capabilities of {}: {}", model, e);
+                return None;
+            }
+        };
+        let capabilities = parse_capabilities(model, &show);
+        debug!("Learned capabilities of {}: {:?}", model, capabilities);
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(model.to_string(), (Instant::now(), capabilities.clone()));
+        Some(capabilities)
+    }
+
+    // Rejects images sent to a model that is known not to support them.
+    //
+    // # Errors
+    //
+    // Returns a bad request error if the model's capabilities are known and lack vision.
+    pub async fn require_vision(&self, ollama: &OllamaClient, model: &str) -> Result<(), ApiError> {
+        match self.get(ollama, model).await {
+            Some(capabilities) if !capabilities.vision => Err(ApiError::BadRequest(format!(
+                "Model {} does not support images; send the request to a vision model instead",
+                model
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Extracts the capabilities of a model from its `/api/show` response.
+fn parse_capabilities(model: &str, show: &Value) -> ModelCapabilities {
+    let listed: Option<Vec<&str>> = show["capabilities"]
+        .as_array()
+        .map(|capabilities| capabilities.iter().filter_map(Value::as_str).collect());
+    let model_info = |suffix: &str| {
+        show["model_info"].as_object().and_then(|info| {
+            info.iter()
+                .find(|(key, _)| key.ends_with(suffix))
+                .and_then(|(_, value)| value.as_u64())
+        })
+    };
+
+    let num_ctx = show["parameters"].as_str().and_then(|parameters| {
+        parameters.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse().ok(),
+                _ => None,
+            }
+        })
+    });
+
+    let (vision, tools, embedding) = match &listed {
+        Some(listed) => (
+            listed.contains(&"vision"),
+            listed.contains(&"tools"),
+            listed.contains(&"embedding"),
+        ),
+        None => (
+            !show["projector_info"].is_null(),
+            show["template"]
+                .as_str()
+                .is_some_and(|template| template.contains(".Tools")),
+            false,
+        ),
+    };
+
+    ModelCapabilities {
+        model: model.to_string(),
+        context_length: model_info(".context_length"),
+        num_ctx,
+        embedding_length: model_info(".embedding_length"),
+        vision,
+        tools,
+        embedding,
+    }
+}
@@ -0,0 +1,156 @@
+// Circuit breaker around the PANW API.
+//
+// When the PANW API is unreachable or failing, every request would otherwise
+// wait for its scan to time out and then fail, tying up clients and hammering
+// the API as it recovers. When enabled, the breaker opens after the configured
+// number of consecutive failed scans:
+//
+// - while open, scans are not attempted and content is handled by the open
+//   mode: rejected (`block`, fail closed) or passed through without a PANW
+//   verdict (`monitor` or `local_only`, fail open) after the local checks,
+//   like content whose scan failed under the failure mode; failing open
+//   requires a failure mode that fails open too, and `fail_open_log` exports
+//   a `fail_open` security event for every pass-through
+// - once the cooldown has passed, a single scan probes the API while other
+//   scans are still handled by the open mode
+// - a probe that reaches the API closes the breaker; a failed probe opens it
+//   for another cooldown
+//
+// Timeouts, connection errors, server errors and rate limiting count as
+// failures. Any other answer, including other client errors and unparseable
+// responses, shows that the API is reachable and resets the count; errors of
+// the proxy itself, such as an asynchronous scan that did not settle in time,
+// are not counted either way.
+//
+// # Metrics
+//
+// The state is reported in `panw_circuit_breaker_open`, openings are counted
+// in `panw_circuit_breaker_trips_total` and scans skipped while open in
+// `panw_circuit_breaker_short_circuits_total`.
+use crate::config::{CircuitBreakerConfig, DegradedMode};
+use crate::metrics;
+use crate::security::SecurityError;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// Failure count and open state.
+#[derive(Default)]
+struct BreakerState {
+    // Failed scans since the last scan that reached the API
+    consecutive_failures: u32,
+
+    // End of the cooldown while the breaker is open
+    open_until: Option<Instant>,
+
+    // When the pending probe was admitted, while one is pending
+    probe_started: Option<Instant>,
+}
+
+// Suspends scans while the PANW API keeps failing.
+//
+// Cloning the breaker is cheap and all clones share the same state.
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    // Number of consecutive failures that opens the breaker
+    failure_threshold: u32,
+
+    // How long the breaker stays open before a probe
+    cooldown: Duration,
+
+    // Behavior while the breaker is open
+    open_mode: DegradedMode,
+
+    // Shared failure count and open state
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreaker {
+    // Creates a closed breaker from the configuration.
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        record_state(false);
+        Self {
+            failure_threshold: config.failure_threshold,
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            open_mode: config.open_mode,
+            state: Arc::new(Mutex::new(BreakerState::default())),
+        }
+    }
+
+    // Decides whether a scan may be attempted.
+    //
+    // # Returns
+    //
+    // `None` if the scan may go ahead, possibly as the probe of an open
+    // breaker, or the open mode if the scan must be skipped
+    pub fn check(&self) -> Option<DegradedMode> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let open_until = state.open_until?;
+
+        // A probe that never reported back, e.g. because its client went away,
+        // is replaced after another cooldown
+        let probe_pending = state
+            .probe_started
+            .is_some_and(|started| now.duration_since(started) < self.cooldown);
+        if now >= open_until && !probe_pending {
+            state.probe_started = Some(now);
+            info!("PANW circuit breaker cooldown over, probing the API");
+            return None;
+        }
+
+        metrics::increment_counter(
+            "panw_circuit_breaker_short_circuits_total",
+            "Scans skipped because the PANW circuit breaker was open",
+            &[],
+        );
+        Some(self.open_mode)
+    }
+
+    // Records the outcome of an attempted scan.
+    pub fn record<T>(&self, result: &Result<T, SecurityError>) {
+        let failed = result.as_ref().is_err_and(SecurityError::is_outage);
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if !failed {
+            state.consecutive_failures = 0;
+            state.probe_started = None;
+            if state.open_until.take().is_some() {
+                info!("PANW API reachable again, circuit breaker closed");
+                record_state(false);
+            }
+            return;
+        }
+
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        let probe_failed = state.probe_started.take().is_some();
+        if probe_failed
+            || (state.open_until.is_none() && state.consecutive_failures >= self.failure_threshold)
+        {
+            warn!(
+                "PANW circuit breaker open for {} s after {} consecutive failed scans",
+                self.cooldown.as_secs(),
+                state.consecutive_failures
+            );
+            if state.open_until.is_none() {
+                metrics::increment_counter(
+                    "panw_circuit_breaker_trips_total",
+                    "Times the PANW circuit breaker opened",
+                    &[],
+                );
+                record_state(true);
+            }
+            state.open_until = Some(now + self.cooldown);
+        }
+    }
+}
+
+// Reports whether the breaker is open.
+fn record_state(open: bool) {
+    metrics::set_gauge(
+        "panw_circuit_breaker_open",
+        "Whether the PANW circuit breaker is open",
+        &[],
+        if open { 1.0 } else { 0.0 },
+    );
+}
@@ -0,0 +1,738 @@
+// API key and JWT authentication of proxy clients.
+//
+// Without authentication, anyone who can reach the port can use the Ollama
+// backend. When enabled, every request must carry one of the configured client
+// keys as a bearer token in the `Authorization` header. Keys may be configured
+// in plain text or as their SHA-256 hash; incoming keys are hashed and compared
+// in constant time either way. With JWT validation enabled, bearer tokens that
+// match no key are validated as JWTs issued by the configured identity provider.
+//
+// # Basic Authentication and Reverse Proxies
+//
+// For clients that cannot send bearer tokens, HTTP Basic credentials can be
+// accepted instead, with the id of a client key as the user name and the key
+// as the password. Deployments that authenticate users at a reverse proxy can
+// instead trust the proxy's identity header (`X-Forwarded-User` by default)
+// from the configured proxy addresses; the named user becomes the request's
+// identity without further credentials. The header is rejected when it comes
+// from any other address.
+//
+// # Identity
+//
+// The identifier of the matching key, or the subject of the JWT, becomes the
+// request's `ClientIdentity`, which is reported to PANW as the app user and
+// written to the audit log. A request authenticated by key or JWT takes that
+// identity even if the connection presented a client certificate. A JWT may
+// also select the request's `SecurityProfile` through the client's groups.
+//
+// # Access Tokens
+//
+// With access tokens enabled, keys and JWTs can be exchanged at `/auth/token`
+// for a short-lived token signed by the proxy (see `access_token`), which is
+// accepted as a bearer token with the identity, profile and grant of the
+// exchanged credential.
+//
+// # Scopes
+//
+// Keys may be limited to some endpoints and models. The grant of the matching
+// key is attached to the request and enforced by `key_scopes`; keys holding
+// the `admin` scope are also accepted by the admin API. Keys created through
+// the admin API (see `key_store`) are accepted like configured keys, and those
+// bound to a tenant replace the request's tenant header.
+//
+// # Impersonation
+//
+// An operator holding the admin token can send a request as a specific client
+// by naming it in the `X-Act-As-User` header, to reproduce reports that
+// content is blocked only for that client. The request takes the client's
+// identity, and with it the client's rate limits, concurrency and usage
+// counts, and the security profile its groups select when they are listed in
+// `X-Act-As-Groups`. A client holding a configured or managed key is also
+// limited to the key's scopes and models and processed as the key's tenant;
+// other clients take the tenant from the tenant header as usual, and expired
+// or revoked keys cannot be impersonated. Every impersonated request and its
+// outcome is logged and exported as an `impersonation` security event, and
+// the response names the impersonated client in `X-Acted-As-User`.
+// Impersonation headers without a valid admin token are rejected.
+//
+// # End Users of Frontends
+//
+// Frontends serving many users authenticate as a single client. Clients listed
+// as trusted may name the end user in the app user header (`X-App-User` by
+// default); the end user then takes the frontend's place as the request's
+// identity, so PANW audit records, usage counts and rate limits are kept per
+// end user. The header is rejected from any other client, so it cannot be used
+// to pose as someone else.
+//
+// # Exemptions
+//
+// The health checks stay open for orchestrators, and the admin API keeps its
+// own token.
+use crate::config::ClientAuthConfig;
+use crate::handlers::admin::{admin_actor, constant_time_eq, require_admin};
+use crate::handlers::ApiError;
+use crate::key_scopes::{KeyGrant, ADMIN_SCOPE};
+use crate::key_store::KeyStore;
+use crate::security_events::{self, EventType};
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+// Paths that never require a client key.
+const EXEMPT_PATHS: [&str; 3] = ["/healthz", "/livez", "/readyz"];
+
+// Request header naming the client an admin acts as.
+const ACT_AS_USER_HEADER: &str = "x-act-as-user";
+
+// Request header listing the groups of the impersonated client.
+const ACT_AS_GROUPS_HEADER: &str = "x-act-as-groups";
+
+// Response header naming the impersonated client.
+const ACTED_AS_USER_HEADER: &str = "x-acted-as-user";
+
+// Challenge sent with rejections when Basic credentials are accepted.
+const BASIC_CHALLENGE: &str = "Basic realm=\"panw-api-ollama\"";
+
+// Identity of the client, taken from its API key or validated certificate.
+//
+// Extracting the identity never fails; unauthenticated requests have no identity.
+#[derive(Debug, Clone, Default)]
+pub struct ClientIdentity(pub Option<String>);
+
+impl FromRequestParts<AppState> for ClientIdentity {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ClientIdentity>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// Credential a request was authenticated with.
+#[derive(Debug, Clone)]
+pub struct Credential {
+    // `api_key`, `jwt` or `access_token`
+    pub kind: &'static str,
+
+    // Client identity established by the credential
+    pub subject: String,
+
+    // Security profile selected by the credential (optional)
+    pub profile: Option<String>,
+}
+
+// Security profile selected for the request, overriding the default profile.
+//
+// A profile named in the security profile header takes precedence over the one
+// selected by the client's groups. Extraction fails if the header names a
+// profile that is not allowed; requests without a profile use the model's
+// profile or the default.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityProfile(pub Option<String>);
+
+impl FromRequestParts<AppState> for SecurityProfile {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(profile) = state
+            .security_client
+            .requested_profile(&parts.headers)
+            .map_err(ApiError::Forbidden)?
+        {
+            return Ok(SecurityProfile(Some(profile)));
+        }
+        Ok(parts
+            .extensions
+            .get::<SecurityProfile>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// Accepted client keys, stored as their SHA-256 hashes.
+//
+// Cloning the key set is cheap and all clones share the same keys.
+#[derive(Clone)]
+pub struct ClientKeys {
+    // Whether requests must carry a client key
+    enabled: bool,
+
+    // Client identifiers and the hashes of their keys
+    keys: Arc<Vec<(String, Vec<u8>)>>,
+
+    // Endpoints and models of each key, by client identifier
+    grants: Arc<HashMap<String, Arc<KeyGrant>>>,
+
+    // Keys managed through the admin API
+    store: KeyStore,
+
+    // Request header naming the tenant
+    tenant_header: Option<HeaderName>,
+
+    // Header naming the end user, or `None` when it is not accepted
+    app_user_header: Option<String>,
+
+    // Clients allowed to name the end user
+    trusted_clients: Arc<HashSet<String>>,
+
+    // Whether HTTP Basic credentials are accepted
+    basic_auth: bool,
+
+    // Header carrying the user authenticated by a trusted reverse proxy, or
+    // `None` when it is not accepted
+    proxy_user_header: Option<String>,
+
+    // Addresses of the reverse proxies allowed to send the header
+    trusted_proxies: Arc<Vec<IpAddr>>,
+}
+
+impl ClientKeys {
+    // Hashes the configured keys and loads the managed keys.
+    //
+    // Invalid hashes are rejected by config validation and never match here.
+    //
+    // # Arguments
+    //
+    // * `config` - Client authentication settings
+    // * `tenant_header` - Request header naming the tenant
+    pub fn new(config: &ClientAuthConfig, tenant_header: &str) -> Self {
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| {
+                let hash = if key.key_sha256.is_empty() {
+                    Sha256::digest(key.key.as_bytes()).to_vec()
+                } else {
+                    decode_hex(&key.key_sha256).unwrap_or_default()
+                };
+                (key.id.clone(), hash)
+            })
+            .collect();
+
+        let grants = config
+            .keys
+            .iter()
+            .map(|key| {
+                let grant = KeyGrant::new(&key.id, &key.scopes, &key.models, None);
+                (key.id.clone(), Arc::new(grant))
+            })
+            .collect();
+
+        let app_user_header = &config.app_user_header;
+        Self {
+            enabled: config.enabled,
+            keys: Arc::new(keys),
+            grants: Arc::new(grants),
+            store: KeyStore::new(&config.key_state_file),
+            tenant_header: HeaderName::try_from(tenant_header).ok(),
+            app_user_header: app_user_header
+                .enabled
+                .then(|| app_user_header.name.clone()),
+            trusted_clients: Arc::new(app_user_header.trusted_clients.iter().cloned().collect()),
+            basic_auth: config.basic_auth,
+            proxy_user_header: config
+                .trusted_proxy
+                .enabled
+                .then(|| config.trusted_proxy.header.clone()),
+            trusted_proxies: Arc::new(
+                config
+                    .trusted_proxy
+                    .addresses
+                    .iter()
+                    .filter_map(|address| address.parse().ok())
+                    .collect(),
+            ),
+        }
+    }
+
+    // Returns the keys managed through the admin API.
+    pub fn store(&self) -> &KeyStore {
+        &self.store
+    }
+
+    // Returns the grants of the configured keys ordered by client identifier.
+    pub fn configured_grants(&self) -> Vec<Arc<KeyGrant>> {
+        let mut grants: Vec<Arc<KeyGrant>> = self.grants.values().cloned().collect();
+        grants.sort_by(|a, b| a.id().cmp(b.id()));
+        grants
+    }
+
+    // Returns whether a configured key has the given client identifier.
+    pub fn is_configured(&self, id: &str) -> bool {
+        self.grants.contains_key(id)
+    }
+
+    // Returns the grant of the configured or managed key with the given client
+    // identifier.
+    //
+    // # Returns
+    //
+    // `None` if no key has the identifier, otherwise the key's grant, or its
+    // status if it is expired or revoked
+    fn grant_of(&self, id: &str) -> Option<Result<Arc<KeyGrant>, &'static str>> {
+        match self.grants.get(id) {
+            Some(grant) => Some(Ok(grant.clone())),
+            None => self.store.grant(id).map(|grant| grant.map(Arc::new)),
+        }
+    }
+
+    // Attaches a key's grant to a request, replacing the tenant header with the
+    // key's tenant.
+    fn apply_grant(&self, request: &mut Request, grant: Arc<KeyGrant>) {
+        if let (Some(tenant), Some(name)) = (grant.tenant(), &self.tenant_header) {
+            if let Ok(tenant) = HeaderValue::from_str(tenant) {
+                request.headers_mut().insert(name.clone(), tenant);
+            }
+        }
+        request.extensions_mut().insert(grant);
+    }
+
+    // Returns the grant of the key, if it is accepted.
+    fn authenticate(&self, key: &str) -> Option<Arc<KeyGrant>> {
+        if key.is_empty() {
+            return None;
+        }
+        let hash = Sha256::digest(key.as_bytes());
+        // Compare against every key so the time taken does not reveal which matched
+        let configured = self.keys.iter().fold(None, |found, (id, expected)| {
+            if constant_time_eq(&hash, expected) {
+                Some(id.as_str())
+            } else {
+                found
+            }
+        });
+        match configured {
+            Some(id) => self.grants.get(id).cloned(),
+            None => self.store.authenticate(&hash).map(Arc::new),
+        }
+    }
+
+    // Returns whether the key is accepted and holds the `admin` scope.
+    pub fn grants_admin(&self, key: &str) -> bool {
+        self.admin_id(key).is_some()
+    }
+
+    // Returns the identifier of the client whose key is given, if it is
+    // accepted and holds the `admin` scope.
+    pub fn admin_id(&self, key: &str) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        self.authenticate(key)
+            .filter(|grant| grant.allows_scope(ADMIN_SCOPE))
+            .map(|grant| grant.id().to_string())
+    }
+
+    // Returns the grant of the client whose id and key are given as HTTP Basic
+    // credentials, if they are accepted.
+    fn authenticate_basic(&self, credentials: &str) -> Option<Arc<KeyGrant>> {
+        let decoded = STANDARD.decode(credentials.trim()).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (user, key) = decoded.split_once(':')?;
+        self.authenticate(key)
+            .filter(|grant| constant_time_eq(grant.id().as_bytes(), user.as_bytes()))
+    }
+}
+
+// Middleware rejecting requests without a valid client key or JWT.
+//
+// Authenticated requests carry the key's identifier or the JWT subject as their
+// `ClientIdentity`, and the profile selected by the JWT as their `SecurityProfile`.
+pub async fn require_client_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if path.starts_with("/admin") || EXEMPT_PATHS.contains(&path) {
+        return next.run(request).await;
+    }
+    if request.headers().contains_key(ACT_AS_USER_HEADER) {
+        return impersonate(state, request, next).await;
+    }
+    if let Some(name) = state.client_keys.proxy_user_header.as_deref() {
+        if request.headers().contains_key(name) {
+            return trust_proxy_user(state, request, next).await;
+        }
+    }
+    if !state.client_keys.enabled {
+        return next.run(request).await;
+    }
+
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let supplied = authorization
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .unwrap_or_default();
+    let basic = authorization
+        .strip_prefix("Basic ")
+        .filter(|_| state.client_keys.basic_auth);
+
+    // Identities from keys are limited to the key's grant
+    let keys = &state.client_keys;
+    let from_key = |grant: Arc<KeyGrant>| (grant.id().to_string(), None, Some(grant), "api_key");
+    let identity = match keys.authenticate(supplied) {
+        Some(grant) => Some(from_key(grant)),
+        None if basic.is_some() => basic
+            .and_then(|credentials| keys.authenticate_basic(credentials))
+            .map(from_key),
+        None => match state.access_tokens.verify(supplied) {
+            Some(claims) => {
+                // Tokens exchanged for keys carry the key's grant
+                let grant = (claims.auth == "api_key").then(|| {
+                    let grant =
+                        KeyGrant::new(&claims.sub, &claims.scopes, &claims.models, claims.tenant);
+                    Arc::new(grant)
+                });
+                Some((claims.sub, claims.profile, grant, "access_token"))
+            }
+            None if state.jwt.enabled() && !supplied.is_empty() => {
+                match state.jwt.validate(supplied).await {
+                    Ok(identity) => Some((identity.subject, identity.profile, None, "jwt")),
+                    Err(e) => {
+                        warn!("Rejected JWT: {}", e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        },
+    };
+
+    let Some((id, profile, grant, kind)) = identity else {
+        warn!(
+            "Rejected unauthenticated request: {} {}",
+            request.method(),
+            request.uri().path()
+        );
+        let mut response =
+            ApiError::Unauthorized("Missing or invalid API key".to_string()).into_response();
+        if state.client_keys.basic_auth {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(BASIC_CHALLENGE),
+            );
+        }
+        return response;
+    };
+
+    info!(
+        "Client {} authenticated for {} {}",
+        id,
+        request.method(),
+        request.uri().path()
+    );
+    request.extensions_mut().insert(Credential {
+        kind,
+        subject: id.clone(),
+        profile: profile.clone(),
+    });
+    request.extensions_mut().insert(ClientIdentity(Some(id)));
+    if let Some(profile) = profile {
+        request.extensions_mut().insert(SecurityProfile(Some(profile)));
+    }
+    if let Some(grant) = grant {
+        keys.apply_grant(&mut request, grant);
+    }
+    next.run(request).await
+}
+
+// Middleware letting trusted clients name the end user of a request.
+//
+// Requests from trusted clients carrying the app user header take the end user
+// as their `ClientIdentity`; the header from any other client is rejected.
+pub async fn apply_app_user(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(name) = state.client_keys.app_user_header.as_deref() else {
+        return next.run(request).await;
+    };
+    let user = header_str(request.headers(), name);
+    if user.is_empty() {
+        return next.run(request).await;
+    }
+
+    let client = request
+        .extensions()
+        .get::<ClientIdentity>()
+        .and_then(|identity| identity.0.clone());
+    match client {
+        Some(client) if state.client_keys.trusted_clients.contains(&client) => {
+            debug!("Client {} acting for app user {}", client, user);
+            request.extensions_mut().insert(ClientIdentity(Some(user)));
+            next.run(request).await
+        }
+        client => {
+            warn!(
+                "Rejected {} header from untrusted client {} for {} {}",
+                name,
+                client.as_deref().unwrap_or("anonymous"),
+                request.method(),
+                request.uri().path()
+            );
+            ApiError::Forbidden(format!("{} is only accepted from trusted clients", name))
+                .into_response()
+        }
+    }
+}
+
+// Runs a request as the user named by a trusted reverse proxy.
+//
+// The identity header is only accepted from the configured proxy addresses.
+async fn trust_proxy_user(state: AppState, mut request: Request, next: Next) -> Response {
+    let name = state
+        .client_keys
+        .proxy_user_header
+        .clone()
+        .unwrap_or_default();
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip());
+    let Some(peer) = peer.filter(|peer| state.client_keys.trusted_proxies.contains(peer)) else {
+        warn!(
+            "Rejected {} header from untrusted address {} for {} {}",
+            name,
+            peer.map_or_else(|| "unknown".to_string(), |peer| peer.to_string()),
+            request.method(),
+            request.uri().path()
+        );
+        return ApiError::Forbidden(format!("{} is only accepted from trusted proxies", name))
+            .into_response();
+    };
+
+    let user = header_str(request.headers(), &name);
+    if user.is_empty() {
+        return ApiError::BadRequest(format!("{} must name a user", name)).into_response();
+    }
+    info!(
+        "User {} authenticated by proxy {} for {} {}",
+        user,
+        peer,
+        request.method(),
+        request.uri().path()
+    );
+    request.extensions_mut().insert(ClientIdentity(Some(user)));
+    next.run(request).await
+}
+
+// Runs a request as the client named in the `X-Act-As-User` header.
+//
+// The request must carry the admin token instead of client credentials.
+async fn impersonate(state: AppState, mut request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    if let Err(e) = require_admin(&state, request.headers()) {
+        warn!("Rejected impersonation of a client for {} {}", method, path);
+        return e.into_response();
+    }
+
+    let user = header_str(request.headers(), ACT_AS_USER_HEADER);
+    let groups = header_str(request.headers(), ACT_AS_GROUPS_HEADER);
+    if user.is_empty() {
+        return ApiError::BadRequest(format!("{} must name a client", ACT_AS_USER_HEADER))
+            .into_response();
+    }
+    let groups: Vec<&str> = groups
+        .split(',')
+        .map(str::trim)
+        .filter(|group| !group.is_empty())
+        .collect();
+    let profile = state.jwt.profile_for_groups(&groups);
+
+    // Clients holding a key are limited to its grant, as their own requests are
+    let keys = &state.client_keys;
+    match keys.grant_of(&user) {
+        Some(Ok(grant)) => keys.apply_grant(&mut request, grant),
+        Some(Err(status)) => {
+            warn!(
+                "Rejected impersonation of client {} with {} key for {} {}",
+                user, status, method, path
+            );
+            return ApiError::BadRequest(format!("API key of client {} is {}", user, status))
+                .into_response();
+        }
+        None => {}
+    }
+    let tenant = keys
+        .tenant_header
+        .as_ref()
+        .map(|name| header_str(request.headers(), name.as_str()))
+        .filter(|tenant| !tenant.is_empty());
+
+    let actor = admin_actor(&state, request.headers());
+    info!(
+        "Admin {} acting as client {} (profile: {}) for {} {}",
+        actor,
+        user,
+        profile.as_deref().unwrap_or("default"),
+        method,
+        path
+    );
+    security_events::publish(
+        EventType::Impersonation,
+        &format!("Admin {} acting as client {} for {}", actor, user, path),
+        &[
+            ("actor", json!(actor)),
+            ("client", json!(user)),
+            ("profile", json!(profile)),
+            ("tenant", json!(tenant)),
+            ("path", json!(path)),
+        ],
+    );
+    request
+        .extensions_mut()
+        .insert(ClientIdentity(Some(user.clone())));
+    if let Some(profile) = profile {
+        request.extensions_mut().insert(SecurityProfile(Some(profile)));
+    }
+
+    let mut response = next.run(request).await;
+    info!(
+        "Admin request as client {} for {} {} completed with {}",
+        user,
+        method,
+        path,
+        response.status()
+    );
+    if let Ok(value) = HeaderValue::from_str(&user) {
+        response.headers_mut().insert(ACTED_AS_USER_HEADER, value);
+    }
+    response
+}
+
+// Returns the trimmed value of a request header, or an empty string.
+fn header_str(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Encodes bytes as lowercase hex.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Decodes a hex string into bytes.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_scopes::enforce_scopes;
+    use crate::vault::SharedSecret;
+    use axum::{
+        body::Body,
+        http::StatusCode,
+        middleware::{from_fn, from_fn_with_state},
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    // Admin token of the test proxy.
+    const ADMIN_TOKEN: &str = "test-admin-token";
+
+    // Configuration with a client key limited to chatting with llama3.
+    const CONFIG: &str = r#"
+server: {host: 127.0.0.1, port: 0, debug_level: INFO}
+ollama: {provider: mock}
+security: {base_url: "http://127.0.0.1:9", api_key: "", profile_name: default,
+  app_name: test, app_user: test, budget: {state_file: ""}}
+admin: {token: test-admin-token}
+kill_switch: {state_file: ""}
+templates: {state_file: ""}
+client_auth:
+  enabled: true
+  key_state_file: ""
+  keys: [{id: ci, key: ci-key, scopes: [chat], models: [llama3]}]
+"#;
+
+    // Routes a request through client authentication and key scopes.
+    async fn send(path: &str, admin_token: Option<&str>, model: &str) -> StatusCode {
+        let config: crate::config::Config = serde_yml::from_str(CONFIG).unwrap();
+        let state = crate::build_app_state(&config, "", SharedSecret::new("")).unwrap();
+        let app = Router::new()
+            .route("/api/chat", post(|| async { "ok" }))
+            .route("/api/embeddings", post(|| async { "ok" }))
+            .layer(from_fn(enforce_scopes))
+            .layer(from_fn_with_state(state.clone(), require_client_key))
+            .with_state(state);
+
+        let mut request = Request::post(path)
+            .header("content-type", "application/json")
+            .header(ACT_AS_USER_HEADER, "ci");
+        if let Some(token) = admin_token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = Body::from(format!(r#"{{"model":"{}"}}"#, model));
+        app.oneshot(request.body(body).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn impersonation_runs_within_the_client_grant() {
+        let status = send("/api/chat", Some(ADMIN_TOKEN), "llama3").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn impersonation_rejects_models_outside_the_client_grant() {
+        let status = send("/api/chat", Some(ADMIN_TOKEN), "mistral").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn impersonation_rejects_scopes_outside_the_client_grant() {
+        let status = send("/api/embeddings", Some(ADMIN_TOKEN), "llama3").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn impersonation_requires_the_admin_token() {
+        let status = send("/api/chat", None, "llama3").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let status = send("/api/chat", Some("ci-key"), "llama3").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
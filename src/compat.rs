@@ -0,0 +1,179 @@
+// Normalization of request bodies sent by older clients.
+//
+// Fleets of chat UIs, scripts and SDKs are rarely upgraded together with the
+// proxy, and older clients send shapes of the generate and chat requests that
+// the current handlers reject. Before such requests are processed, their
+// bodies are rewritten into the current shape by a set of shims:
+//
+// - `missing_content_type`: JSON bodies sent without a JSON `Content-Type`
+//   are marked as JSON
+// - `chat_prompt`: chat requests carrying a `prompt` (and optional `system`)
+//   instead of `messages` are turned into a conversation
+// - `string_messages`: chat messages given as plain strings become user
+//   messages
+// - `string_stream`: `stream` flags given as `"true"`, `"false"`, `1` or `0`
+//   become booleans
+// - `missing_stream`: requests without a `stream` flag are streamed, as Ollama
+//   does
+//
+// Every applied shim is counted in `panw_compat_shims_total` by shim and
+// route, so operators can see which clients still need upgrading. Bodies that
+// need no shim are passed through unchanged.
+use crate::handlers::ApiError;
+use crate::metrics;
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderValue,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+// Largest request body considered for normalization.
+const MAX_REQUEST_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+// Routes whose request bodies are normalized.
+const SHIMMED_ROUTES: [&str; 2] = ["/api/generate", "/api/chat"];
+
+// Rewrites chat requests carrying a prompt instead of messages.
+fn chat_prompt(route: &str, body: &mut Map<String, Value>) -> bool {
+    if route != "/api/chat" || body.contains_key("messages") {
+        return false;
+    }
+    let Some(Value::String(prompt)) = body.remove("prompt") else {
+        return false;
+    };
+
+    let mut messages = Vec::new();
+    if let Some(Value::String(system)) = body.remove("system") {
+        messages.push(json!({ "role": "system", "content": system }));
+    }
+    messages.push(json!({ "role": "user", "content": prompt }));
+    body.insert("messages".to_string(), Value::Array(messages));
+    true
+}
+
+// Turns chat messages given as plain strings into user messages.
+fn string_messages(route: &str, body: &mut Map<String, Value>) -> bool {
+    if route != "/api/chat" {
+        return false;
+    }
+    let Some(Value::Array(messages)) = body.get_mut("messages") else {
+        return false;
+    };
+
+    let mut applied = false;
+    for message in messages.iter_mut() {
+        if let Value::String(content) = message {
+            *message = json!({ "role": "user", "content": content });
+            applied = true;
+        }
+    }
+    applied
+}
+
+// Turns stream flags given as strings or numbers into booleans.
+fn string_stream(_route: &str, body: &mut Map<String, Value>) -> bool {
+    let stream = match body.get("stream") {
+        Some(Value::String(value)) => match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => return false,
+        },
+        Some(Value::Number(value)) => match value.as_u64() {
+            Some(1) => true,
+            Some(0) => false,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    body.insert("stream".to_string(), Value::Bool(stream));
+    true
+}
+
+// Streams requests without a stream flag, as Ollama does.
+fn missing_stream(_route: &str, body: &mut Map<String, Value>) -> bool {
+    if body.get("stream").is_some_and(|stream| !stream.is_null()) {
+        return false;
+    }
+    body.insert("stream".to_string(), Value::Bool(true));
+    true
+}
+
+// Body shims by name, in the order they are applied.
+type Shim = fn(&str, &mut Map<String, Value>) -> bool;
+const SHIMS: [(&str, Shim); 4] = [
+    ("chat_prompt", chat_prompt),
+    ("string_messages", string_messages),
+    ("string_stream", string_stream),
+    ("missing_stream", missing_stream),
+];
+
+// Records that a shim was applied to a request.
+fn record(shim: &str, route: &str) {
+    debug!("Applied compatibility shim {} to {} request", shim, route);
+    metrics::increment_counter(
+        "panw_compat_shims_total",
+        "Requests of older clients rewritten into the current shape, by shim",
+        &[("shim", shim), ("route", route)],
+    );
+}
+
+// Middleware rewriting request bodies of older clients into the current shape.
+pub async fn apply_shims(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request.uri().path().to_string();
+    if !state.compat_config.enabled || !SHIMMED_ROUTES.contains(&route.as_str()) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                .into_response();
+        }
+    };
+
+    // Bodies that are not JSON objects are left for the handler to reject
+    let Ok(Value::Object(mut object)) = serde_json::from_slice::<Value>(&bytes) else {
+        return next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+    };
+
+    let is_json = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().to_ascii_lowercase().contains("json"));
+    if !is_json {
+        parts
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        record("missing_content_type", &route);
+    }
+
+    let mut rewritten = false;
+    for (name, shim) in SHIMS {
+        if shim(&route, &mut object) {
+            record(name, &route);
+            rewritten = true;
+        }
+    }
+    if !rewritten {
+        return next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await;
+    }
+
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = Body::from(Value::Object(object).to_string());
+    next.run(Request::from_parts(parts, body)).await
+}
@@ -0,0 +1,258 @@
+// Caps on concurrent generations with bounded queueing.
+//
+// A single Ollama host stalls badly when many generations run in parallel, so
+// the number of generations in flight across all clients can be capped.
+// Requests arriving while the cap is reached wait in a bounded queue for a
+// free slot; requests that find the queue full, or that wait longer than the
+// queue timeout, are shed with 503 and a `Retry-After` header.
+//
+// # Per-Model Limits
+//
+// Ollama unloads and reloads models when generations for different models
+// compete for memory, so the generations of each model can be capped as well.
+// A request naming a model first waits for a slot of its model and then for a
+// global slot, sharing the same queue and timeout, so requests for a busy
+// model do not hold global slots that other models could use.
+//
+// # Streaming
+//
+// A slot is held until the response body has been sent completely, so a
+// streaming generation occupies its slot for the whole stream rather than only
+// until the first chunk.
+use crate::config::ConcurrencyConfig;
+use crate::handlers::ApiError;
+use crate::metrics;
+use crate::ollama::with_tag;
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+// Limits the number of generations in flight.
+//
+// Cloning the limiter is cheap and all clones share the same slots and queue.
+#[derive(Clone)]
+pub struct GenerationLimiter {
+    // Maximum number of generations in flight; 0 disables the limit
+    max_in_flight: usize,
+
+    // Maximum number of requests waiting for a slot
+    max_queued: usize,
+
+    // Longest time a request waits for a slot
+    queue_timeout: Duration,
+
+    // Free generation slots
+    slots: Arc<Semaphore>,
+
+    // Number of requests currently waiting for a slot
+    queued: Arc<AtomicUsize>,
+
+    // Maximum number of generations in flight per model; 0 disables the limit
+    max_in_flight_per_model: usize,
+
+    // Limits of specific models by tagged name, overriding the one above
+    model_limits: Arc<HashMap<String, usize>>,
+
+    // Free generation slots of each limited model, created on first use
+    model_slots: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+// Largest request body read for the model it names.
+const MAX_REQUEST_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+impl GenerationLimiter {
+    // Creates a limiter with all slots free.
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        Self {
+            max_in_flight: config.max_in_flight,
+            max_queued: config.max_queued,
+            queue_timeout: Duration::from_secs(config.queue_timeout_secs),
+            slots: Arc::new(Semaphore::new(config.max_in_flight)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            max_in_flight_per_model: config.max_in_flight_per_model,
+            model_limits: Arc::new(
+                config
+                    .model_limits
+                    .iter()
+                    .map(|limit| (with_tag(&limit.model), limit.max_in_flight))
+                    .collect(),
+            ),
+            model_slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns whether the generations of any model are limited.
+    fn limits_models(&self) -> bool {
+        self.max_in_flight_per_model > 0 || self.model_limits.values().any(|&limit| limit > 0)
+    }
+
+    // Returns the slots of a model, or `None` if its generations are not limited.
+    fn model_slots(&self, model: &str) -> Option<Arc<Semaphore>> {
+        let model = with_tag(model);
+        let limit = self
+            .model_limits
+            .get(&model)
+            .copied()
+            .unwrap_or(self.max_in_flight_per_model);
+        if limit == 0 {
+            return None;
+        }
+
+        let mut model_slots = self.model_slots.lock().unwrap_or_else(|e| e.into_inner());
+        if !model_slots.contains_key(&model) {
+            // Drop the slots of idle models so that requests naming arbitrary
+            // models cannot grow the map without bound
+            model_slots.retain(|_, slots| Arc::strong_count(slots) > 1);
+        }
+        Some(
+            model_slots
+                .entry(model)
+                .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+                .clone(),
+        )
+    }
+
+    // Waits for a free slot.
+    //
+    // # Arguments
+    //
+    // * `slots` - The global slots or the slots of a model
+    // * `timeout` - Longest time to wait in the queue
+    //
+    // # Returns
+    //
+    // The slot, or the reason the request was shed.
+    async fn acquire(
+        &self,
+        slots: &Arc<Semaphore>,
+        timeout: Duration,
+    ) -> Result<OwnedSemaphorePermit, &'static str> {
+        if let Ok(permit) = slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        // Reserve a place in the queue unless it is full
+        let reserved = self
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued < self.max_queued).then_some(queued + 1)
+            });
+        if reserved.is_err() {
+            return Err("queue_full");
+        }
+
+        debug!("All generation slots busy, queueing request");
+        let result = tokio::time::timeout(timeout, slots.clone().acquire_owned()).await;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+
+        match result {
+            Ok(Ok(permit)) => Ok(permit),
+            _ => Err("queue_timeout"),
+        }
+    }
+}
+
+// Middleware holding each generation to a free slot, queueing or shedding excess requests.
+pub async fn limit_generations(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limiter = &state.generation_limiter;
+    if limiter.max_in_flight == 0 && !limiter.limits_models() {
+        return next.run(request).await;
+    }
+    let queued_at = Instant::now();
+
+    // Wait for a slot of the request's model first
+    let mut request = request;
+    let mut model_permit = None;
+    if limiter.limits_models() {
+        let (parts, body) = request.into_parts();
+        let bytes = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                    .into_response();
+            }
+        };
+        let model = serde_json::from_slice::<Value>(&bytes)
+            .ok()
+            .and_then(|body| body.get("model")?.as_str().map(str::to_string));
+        request = Request::from_parts(parts, Body::from(bytes));
+
+        let slots = model
+            .as_deref()
+            .and_then(|model| limiter.model_slots(model));
+        if let (Some(model), Some(slots)) = (model, slots) {
+            match limiter.acquire(&slots, limiter.queue_timeout).await {
+                Ok(permit) => model_permit = Some(permit),
+                Err(reason) => {
+                    let reason = match reason {
+                        "queue_full" => "model_queue_full",
+                        _ => "model_queue_timeout",
+                    };
+                    return shed(limiter, &request, reason, Some(&model));
+                }
+            }
+        }
+    }
+
+    let mut permit = None;
+    if limiter.max_in_flight > 0 {
+        let remaining = limiter.queue_timeout.saturating_sub(queued_at.elapsed());
+        match limiter.acquire(&limiter.slots, remaining).await {
+            Ok(global) => permit = Some(global),
+            Err(reason) => return shed(limiter, &request, reason, None),
+        }
+    }
+
+    let response = next.run(request).await;
+
+    // Keep the slots until the body has been sent or dropped
+    let (parts, body) = response.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = (&permit, &model_permit);
+        chunk
+    }));
+    Response::from_parts(parts, body)
+}
+
+// Rejects a request that found no free slot.
+fn shed(
+    limiter: &GenerationLimiter,
+    request: &Request,
+    reason: &'static str,
+    model: Option<&str>,
+) -> Response {
+    warn!(
+        "Shedding {} request{}: generation capacity exhausted ({})",
+        request.uri().path(),
+        model
+            .map(|model| format!(" for {}", model))
+            .unwrap_or_default(),
+        reason
+    );
+    metrics::increment_counter(
+        "panw_generations_shed_total",
+        "Generation requests shed because the server was at capacity",
+        &[("reason", reason)],
+    );
+    ApiError::Overloaded {
+        message: "The server is at capacity, please retry later".to_string(),
+        retry_after_secs: limiter.queue_timeout.as_secs().max(1),
+    }
+    .into_response()
+}
@@ -7,14 +7,17 @@
 ///
 /// # Configuration Flow
 ///
-/// 1. Load configuration from YAML file or environment variables
+/// 1. Find the configuration file given with `--config` or in the search path,
+///    and load it, or load configuration from environment variables
 /// 2. Parse into structured types
 /// 3. Validate all required settings
 /// 4. Make configuration available to application components
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use tracing::{debug, info};
 
@@ -53,6 +56,142 @@ pub struct Config {
 
     /// Security and content filtering settings
     pub security: SecurityConfig,
+
+    /// Model management settings
+    #[serde(default)]
+    pub models: ModelsConfig,
+
+    /// Administrative API settings
+    #[serde(default)]
+    pub admin: AdminConfig,
+
+    /// Read-only and maintenance mode settings
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    /// Emergency kill switch settings
+    #[serde(default)]
+    pub kill_switch: KillSwitchConfig,
+
+    /// Duplicate-request suppression settings
+    #[serde(default)]
+    pub dedup: DedupConfig,
+
+    /// Idempotency-Key header settings
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+
+    /// Signed response manifest settings
+    #[serde(default)]
+    pub provenance: ProvenanceConfig,
+
+    /// Tamper-evident conversation transcript settings
+    #[serde(default)]
+    pub transcripts: TranscriptsConfig,
+
+    /// Response post-processing settings
+    #[serde(default)]
+    pub postprocess: PostProcessConfig,
+
+    /// Summarization endpoint settings
+    #[serde(default)]
+    pub summarize: SummarizeConfig,
+
+    /// Batch generation endpoint settings
+    #[serde(default)]
+    pub batch: BatchConfig,
+
+    /// Asynchronous generation job settings
+    #[serde(default)]
+    pub jobs: JobsConfig,
+
+    /// Scheduled prompt settings
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+
+    /// Prompt template library settings
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+
+    /// Health check settings
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    /// Request metrics settings
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Context window check settings
+    #[serde(default)]
+    pub context: ContextConfig,
+
+    /// Model capability cache settings
+    #[serde(default)]
+    pub capabilities: CapabilitiesConfig,
+
+    /// Automatic model pull settings
+    #[serde(default)]
+    pub auto_pull: AutoPullConfig,
+
+    /// API key authentication of proxy clients
+    #[serde(default)]
+    pub client_auth: ClientAuthConfig,
+
+    /// Per-client usage report settings
+    #[serde(default)]
+    pub usage: UsageConfig,
+
+    /// Per-client request rate limits
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// Time windows outside which tenants, keys or models may not be used
+    #[serde(default)]
+    pub access_windows: AccessWindowsConfig,
+
+    /// Global generation concurrency limits
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+
+    /// Encryption at rest of the local state files
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+
+    /// HashiCorp Vault server secrets are read from
+    #[serde(default)]
+    pub vault: VaultConfig,
+
+    /// Request and stream timeouts
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+
+    /// Feature flags guarding risky behaviors
+    #[serde(default)]
+    pub feature_flags: Vec<FeatureFlagConfig>,
+
+    /// Branding of block messages and pages
+    #[serde(default)]
+    pub branding: BrandingConfig,
+
+    /// Scripted latencies for demos
+    #[serde(default)]
+    pub demo: DemoConfig,
+
+    /// Normalization of request bodies sent by older clients
+    #[serde(default)]
+    pub compat: CompatConfig,
+
+    /// Headers passed between clients and Ollama
+    #[serde(default)]
+    pub passthrough: PassthroughConfig,
+
+    /// Export of security events to SIEM systems
+    #[serde(default)]
+    pub security_events: SecurityEventsConfig,
+
+    /// Notification of operational alerts through email, PagerDuty or webhooks
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
 }
 
 /// Server configuration settings.
@@ -68,6 +207,27 @@ pub struct ServerConfig {
 
     /// Logging level (e.g., "INFO", "DEBUG", "ERROR")
     pub debug_level: String,
+
+    /// PEM certificate chain for serving HTTPS; plain HTTP is served when unset
+    #[serde(default)]
+    pub tls_cert_file: Option<String>,
+
+    /// PEM private key matching the certificate
+    #[serde(default)]
+    pub tls_key_file: Option<String>,
+
+    /// How often the certificate and key files are checked for changes
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub tls_reload_interval_secs: u64,
+
+    /// PEM bundle of CAs for client certificates; when set, only clients
+    /// presenting a certificate signed by one of them can connect
+    #[serde(default)]
+    pub tls_client_ca_file: Option<String>,
+}
+
+fn default_tls_reload_interval_secs() -> u64 {
+    30
 }
 
 /// Ollama API integration settings.
@@ -76,7 +236,312 @@ pub struct ServerConfig {
 #[derive(Debug, Clone, Deserialize)]
 pub struct OllamaConfig {
     /// Base URL of the Ollama API service
+    #[serde(default = "default_ollama_base_url")]
     pub base_url: String,
+
+    /// Base URLs of further Ollama servers sharing the requests in rotation
+    #[serde(default)]
+    pub backends: Vec<String>,
+
+    /// Interval between background probes of the Ollama servers when several
+    /// are configured; unhealthy servers are taken out of rotation until they
+    /// recover. 0 disables the probes
+    #[serde(default = "default_ollama_probe_interval_secs")]
+    pub probe_interval_secs: u64,
+
+    /// Interval between checks of the Ollama version, which adjust requests to
+    /// known differences between versions. The version is always checked at
+    /// startup; 0 disables the periodic checks
+    #[serde(default = "default_ollama_version_check_interval_secs")]
+    pub version_check_interval_secs: u64,
+
+    /// Servers answering requests for specific models instead of the servers
+    /// above, by first matching route
+    #[serde(default)]
+    pub model_routes: Vec<ModelRoute>,
+
+    /// Extra headers sent with every request to the Ollama servers, for
+    /// servers behind an authenticating gateway
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Bearer token sent in the `Authorization` header of every request to the
+    /// Ollama servers
+    #[serde(default)]
+    pub bearer_token: String,
+
+    /// User name and password sent as basic authentication with every request
+    /// to the Ollama servers
+    #[serde(default)]
+    pub basic_auth_user: String,
+    #[serde(default)]
+    pub basic_auth_password: String,
+
+    /// Retries of requests that fail transiently, such as during an Ollama restart
+    #[serde(default)]
+    pub retry: OllamaRetryConfig,
+
+    /// Timeouts and connection pooling of requests to the Ollama servers
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+
+    /// Backend answering model requests
+    #[serde(default)]
+    pub provider: OllamaProvider,
+
+    /// Built-in mock backend settings
+    #[serde(default)]
+    pub mock: MockOllamaConfig,
+}
+
+fn default_ollama_base_url() -> String {
+    "http://localhost:11434".to_string()
+}
+
+fn default_ollama_probe_interval_secs() -> u64 {
+    10
+}
+
+fn default_ollama_version_check_interval_secs() -> u64 {
+    300
+}
+
+/// Ollama servers answering requests for the models matching a pattern.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelRoute {
+    /// Model name; a trailing `*` matches any suffix, and names without a tag
+    /// also match the `:latest` tag
+    pub model: String,
+
+    /// Base URLs of the servers sharing the model's requests in rotation
+    pub backends: Vec<String>,
+}
+
+/// Parses a comma-separated list of `model=url` routes.
+///
+/// Entries naming the same model are merged into one route.
+fn parse_model_routes(value: &str) -> Vec<ModelRoute> {
+    let mut routes: Vec<ModelRoute> = Vec::new();
+    for (model, url) in parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+    {
+        let (model, url) = (model.trim(), url.trim().to_string());
+        match routes.iter_mut().find(|route| route.model == model) {
+            Some(route) => route.backends.push(url),
+            None => routes.push(ModelRoute {
+                model: model.to_string(),
+                backends: vec![url],
+            }),
+        }
+    }
+    routes
+}
+
+/// Retry settings for transient Ollama failures.
+///
+/// Requests whose connection fails or is reset, or that are answered with one
+/// of the listed statuses, are retried with exponential backoff.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OllamaRetryConfig {
+    /// Retries after the first attempt (0 = no retries)
+    #[serde(default = "default_ollama_retry_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay before the first retry in milliseconds; doubled for every further retry
+    #[serde(default = "default_ollama_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Longest delay between retries in milliseconds
+    #[serde(default = "default_ollama_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Response statuses that are retried
+    #[serde(default = "default_ollama_retry_on_status")]
+    pub retry_on_status: Vec<u16>,
+}
+
+impl Default for OllamaRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: default_ollama_retry_max_retries(),
+            initial_backoff_ms: default_ollama_retry_initial_backoff_ms(),
+            max_backoff_ms: default_ollama_retry_max_backoff_ms(),
+            retry_on_status: default_ollama_retry_on_status(),
+        }
+    }
+}
+
+fn default_ollama_retry_max_retries() -> u32 {
+    2
+}
+
+fn default_ollama_retry_initial_backoff_ms() -> u64 {
+    250
+}
+
+fn default_ollama_retry_max_backoff_ms() -> u64 {
+    2000
+}
+
+fn default_ollama_retry_on_status() -> Vec<u16> {
+    vec![502, 503]
+}
+
+/// Timeouts and connection pooling of an HTTP client for an upstream service.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpClientConfig {
+    /// Longest time in seconds to establish a connection (0 = no timeout)
+    #[serde(default = "default_http_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// Longest time in seconds for a request, including reading the response
+    /// (0 = no timeout); when not set, requests to Ollama have no timeout and
+    /// requests to other services time out after the default of the service
+    #[serde(default)]
+    pub request_timeout_secs: Option<u64>,
+
+    /// Time in seconds after which idle pooled connections are closed
+    /// (0 = kept until the server closes them)
+    #[serde(default = "default_http_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+
+    /// Most idle connections kept per host; unlimited when not set
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// Interval of TCP keepalive probes in seconds (0 = disabled)
+    #[serde(default = "default_http_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: default_http_connect_timeout_secs(),
+            request_timeout_secs: None,
+            pool_idle_timeout_secs: default_http_pool_idle_timeout_secs(),
+            pool_max_idle_per_host: None,
+            tcp_keepalive_secs: default_http_tcp_keepalive_secs(),
+        }
+    }
+}
+
+fn default_http_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_http_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_http_tcp_keepalive_secs() -> u64 {
+    60
+}
+
+/// Parses a comma-separated list of HTTP status codes, skipping invalid entries.
+fn parse_statuses(value: &str) -> Vec<u16> {
+    parse_list(value)
+        .iter()
+        .filter_map(|status| status.parse().ok())
+        .collect()
+}
+
+/// Parses a comma-separated list of `Name=value` headers.
+fn parse_headers(value: &str) -> HashMap<String, String> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Backend answering model requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OllamaProvider {
+    /// Ollama server at the configured base URL
+    #[default]
+    Http,
+    /// Built-in backend generating deterministic responses, for demos and tests
+    Mock,
+}
+
+impl std::str::FromStr for OllamaProvider {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "http" => Ok(Self::Http),
+            "mock" => Ok(Self::Mock),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown Ollama provider: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Built-in mock backend settings.
+///
+/// Responses are generated from a seeded word list, so the same request always
+/// produces the same tokens.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockOllamaConfig {
+    /// Seed mixed into every generated response
+    #[serde(default)]
+    pub seed: u64,
+
+    /// Tokens streamed per second (0 = no delay)
+    #[serde(default = "default_mock_tokens_per_second")]
+    pub tokens_per_second: f64,
+
+    /// Number of tokens in a generated response
+    #[serde(default = "default_mock_response_tokens")]
+    pub response_tokens: usize,
+
+    /// Models reported as installed
+    #[serde(default = "default_mock_models")]
+    pub models: Vec<String>,
+
+    /// Phrases that embed a fixed text in the response when a prompt contains them
+    #[serde(default)]
+    pub triggers: Vec<MockTrigger>,
+}
+
+impl Default for MockOllamaConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            tokens_per_second: default_mock_tokens_per_second(),
+            response_tokens: default_mock_response_tokens(),
+            models: default_mock_models(),
+            triggers: Vec::new(),
+        }
+    }
+}
+
+fn default_mock_tokens_per_second() -> f64 {
+    20.0
+}
+
+fn default_mock_response_tokens() -> usize {
+    40
+}
+
+fn default_mock_models() -> Vec<String> {
+    vec!["mock".to_string()]
+}
+
+/// A trigger phrase of the mock backend.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockTrigger {
+    /// Phrase looked for in the prompt, ignoring case
+    pub phrase: String,
+
+    /// Text embedded in the middle of the response, e.g. content a security
+    /// profile blocks
+    pub response: String,
 }
 
 /// Security and content filtering settings.
@@ -94,6 +559,19 @@ pub struct SecurityConfig {
     /// Security profile name to use for assessments
     pub profile_name: String,
 
+    /// Request header through which clients select one of the allowed profiles;
+    /// disabled when empty
+    #[serde(default)]
+    pub profile_header: String,
+
+    /// Profiles clients may select through the profile header
+    #[serde(default)]
+    pub allowed_profiles: Vec<String>,
+
+    /// Profiles used for requests to specific models
+    #[serde(default)]
+    pub model_profiles: Vec<ModelProfile>,
+
     /// Application name for telemetry and audit
     pub app_name: String,
 
@@ -103,147 +581,5579 @@ pub struct SecurityConfig {
     /// Context for grounding LLM responses. When not empty, contextual grounding is enabled.
     #[serde(default)]
     pub contextual_grounding: String,
-}
 
-/// Loads configuration from environment variables.
-///
-/// This function reads configuration values from environment variables,
-/// falling back to default values where appropriate.
-///
-/// # Returns
-///
-/// * `Config` - Configuration object populated from environment variables
-fn load_from_env() -> Config {
-    info!("Loading configuration from environment variables");
+    /// Longest time in seconds a scan may wait out PANW rate limiting before it fails
+    #[serde(default = "default_rate_limit_max_wait_secs")]
+    pub rate_limit_max_wait_secs: u64,
 
-    let server = ServerConfig {
-        host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
-        port: env::var("SERVER_PORT")
-            .ok()
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(11435),
-        debug_level: env::var("SERVER_DEBUG_LEVEL").unwrap_or_else(|_| "INFO".to_string()),
-    };
+    /// Monthly PANW scan budget
+    #[serde(default)]
+    pub budget: ScanBudgetConfig,
 
-    let ollama = OllamaConfig {
-        base_url: env::var("OLLAMA_BASE_URL")
-            .unwrap_or_else(|_| "http://localhost:11434".to_string()),
-    };
+    /// Timeouts and connection pooling of requests to PANW
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
 
-    let security = SecurityConfig {
-        base_url: env::var("SECURITY_BASE_URL")
-            .unwrap_or_else(|_| "https://service.api.aisecurity.paloaltonetworks.com".to_string()),
-        api_key: env::var("SECURITY_API_KEY").unwrap_or_default(),
-        profile_name: env::var("SECURITY_PROFILE_NAME").unwrap_or_default(),
-        app_name: env::var("SECURITY_APP_NAME").unwrap_or_else(|_| "panw-api-ollama".to_string()),
-        app_user: env::var("SECURITY_APP_USER").unwrap_or_else(|_| "default".to_string()),
-        contextual_grounding: env::var("SECURITY_CONTEXTUAL_GROUNDING_CONTEXT").unwrap_or_default(),
-    };
+    /// Local secret scanning of model responses
+    #[serde(default)]
+    pub secrets: SecretScanConfig,
 
-    Config {
-        server,
-        ollama,
-        security,
-    }
-}
+    /// Language detection and policy
+    #[serde(default)]
+    pub language: LanguageConfig,
 
-/// Loads configuration from a YAML file or environment variables.
-///
-/// This function first attempts to load configuration from the specified file path.
-/// If the file doesn't exist or can't be read, it falls back to using environment variables.
-/// In either case, the resulting configuration is validated before being returned.
-///
-/// # Arguments
-///
-/// * `path` - Path to the YAML configuration file (optional, will use env vars if file not found)
-///
-/// # Returns
-///
-/// * `Ok(Config)` - Validated configuration object
-/// * `Err(ConfigError)` - If loading or validation fails
-///
-/// # Example
-///
-/// ```
-/// let config = config::load_config("config.yaml")?;
-/// println!("Server will listen on {}:{}", config.server.host, config.server.port);
-/// ```
-pub fn load_config(path: &str) -> Result<Config, ConfigError> {
-    // Check if file exists
-    if Path::new(path).exists() {
-        info!("Loading configuration from file: {}", path);
+    /// Local profanity and toxicity pre-filter
+    #[serde(default)]
+    pub toxicity: ToxicityConfig,
+
+    /// Local regex and keyword rules deciding content before it is scanned
+    #[serde(default)]
+    pub prefilter_rules: PrefilterRulesConfig,
+
+    /// Local masking of personal data in prompts
+    #[serde(default)]
+    pub pii: PiiConfig,
+
+    /// Decoding of encoded payloads in prompts for scanning
+    #[serde(default)]
+    pub decode_payloads: DecodePayloadsConfig,
+
+    /// Reuse of verdicts for repeated identical content
+    #[serde(default)]
+    pub scan_cache: ScanCacheConfig,
+
+    /// Scanning of streamed responses through the asynchronous scan API
+    #[serde(default)]
+    pub async_streaming: AsyncStreamingConfig,
+
+    /// Whether streamed responses end with a summary of their scans and verdict
+    #[serde(default)]
+    pub stream_summary: bool,
+
+    /// Capture of redacted PANW responses for troubleshooting
+    #[serde(default)]
+    pub response_capture: ResponseCaptureConfig,
+
+    /// Circuit breaker suspending scans while the PANW API keeps failing
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+
+    /// Behavior when a scan fails because the PANW API is unreachable, times
+    /// out, returns a server error or rate limits the proxy
+    #[serde(default)]
+    pub failure_mode: FailureMode,
+
+    /// Trusted clients whose requests skip PANW scanning
+    #[serde(default)]
+    pub scan_bypass: ScanBypassConfig,
+
+    /// Backend assessing prompts and responses
+    #[serde(default)]
+    pub provider: SecurityProvider,
+
+    /// Backends assessing the same content in parallel, in place of `provider`
+    #[serde(default)]
+    pub fan_out: FanOutConfig,
+
+    /// OpenAI Moderation API settings, used by the `openai_moderation` provider
+    #[serde(default)]
+    pub openai_moderation: OpenAiModerationConfig,
+
+    /// AWS Bedrock Guardrails settings, used by the `bedrock_guardrails` provider
+    #[serde(default)]
+    pub bedrock_guardrails: BedrockGuardrailsConfig,
+
+    /// Azure AI Content Safety settings, used by the `azure_content_safety` provider
+    #[serde(default)]
+    pub azure_content_safety: AzureContentSafetyConfig,
+
+    /// Llama Guard settings, used by the `llama_guard` provider
+    #[serde(default)]
+    pub llama_guard: LlamaGuardConfig,
+}
+
+impl SecurityConfig {
+    /// Returns whether a provider assesses content, alone or in the fan-out.
+    pub fn uses(&self, provider: SecurityProvider) -> bool {
+        if self.fan_out.providers.is_empty() {
+            self.provider == provider
+        } else {
+            self.fan_out.providers.contains(&provider)
+        }
+    }
+}
+
+/// Backend assessing prompts and responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityProvider {
+    /// PANW AI Runtime API
+    #[default]
+    Panw,
+    /// OpenAI Moderation API, without PANW's masking, reports or asynchronous scans
+    OpenaiModeration,
+    /// AWS Bedrock Guardrails, without PANW's reports or asynchronous scans
+    BedrockGuardrails,
+    /// Azure AI Content Safety, without PANW's masking, reports or asynchronous scans
+    AzureContentSafety,
+    /// Llama Guard model served by Ollama, without PANW's masking, reports or
+    /// asynchronous scans
+    LlamaGuard,
+}
+
+impl std::str::FromStr for SecurityProvider {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "panw" => Ok(Self::Panw),
+            "openai_moderation" => Ok(Self::OpenaiModeration),
+            "bedrock_guardrails" => Ok(Self::BedrockGuardrails),
+            "azure_content_safety" => Ok(Self::AzureContentSafety),
+            "llama_guard" => Ok(Self::LlamaGuard),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown security provider: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl SecurityProvider {
+    /// Returns the name of the provider as configured.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Panw => "panw",
+            Self::OpenaiModeration => "openai_moderation",
+            Self::BedrockGuardrails => "bedrock_guardrails",
+            Self::AzureContentSafety => "azure_content_safety",
+            Self::LlamaGuard => "llama_guard",
+        }
+    }
+}
+
+/// Settings of several providers scanning the same content in parallel.
+///
+/// Every prompt and response is scanned by all listed providers at once, and
+/// the policy combines their verdicts into the final verdict. A scan fails
+/// when any provider fails, which the failure mode then handles.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FanOutConfig {
+    /// Providers scanning in parallel (empty = `provider` alone)
+    #[serde(default)]
+    pub providers: Vec<SecurityProvider>,
+
+    /// Policy deciding the final verdict from the providers' verdicts
+    #[serde(default)]
+    pub policy: VerdictPolicy,
+
+    /// Weights of the providers by name for the `weighted` policy; providers
+    /// not listed weigh 1
+    #[serde(default)]
+    pub weights: HashMap<String, f64>,
+
+    /// Share of the total weight that must block for the `weighted` policy to
+    /// block, from above 0 to 1
+    #[serde(default = "default_fan_out_threshold")]
+    pub threshold: f64,
+}
+
+impl Default for FanOutConfig {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            policy: VerdictPolicy::default(),
+            weights: HashMap::new(),
+            threshold: default_fan_out_threshold(),
+        }
+    }
+}
+
+fn default_fan_out_threshold() -> f64 {
+    0.5
+}
+
+/// Policy combining the verdicts of providers scanning in parallel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerdictPolicy {
+    /// Block when any provider blocks
+    #[default]
+    AnyBlock,
+    /// Block only when every provider blocks
+    AllBlock,
+    /// Block when the providers blocking carry at least the threshold share
+    /// of the total weight
+    Weighted,
+}
+
+impl VerdictPolicy {
+    /// Returns the name of the policy as configured.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AnyBlock => "any_block",
+            Self::AllBlock => "all_block",
+            Self::Weighted => "weighted",
+        }
+    }
+}
+
+impl std::str::FromStr for VerdictPolicy {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "any_block" => Ok(Self::AnyBlock),
+            "all_block" => Ok(Self::AllBlock),
+            "weighted" => Ok(Self::Weighted),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown verdict policy: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Parses a comma-separated list of security providers, skipping invalid
+/// entries.
+fn parse_providers(value: &str) -> Vec<SecurityProvider> {
+    parse_list(value)
+        .iter()
+        .filter_map(|provider| provider.parse().ok())
+        .collect()
+}
+
+/// Parses a comma-separated list of `provider=weight` mappings, skipping
+/// invalid entries.
+fn parse_provider_weights(value: &str) -> HashMap<String, f64> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .filter_map(|(provider, weight)| {
+            Some((provider.trim().to_string(), weight.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// OpenAI Moderation API settings.
+///
+/// Content flagged in one of the blocking categories is blocked as harmful
+/// content, the way PANW reports toxic content.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAiModerationConfig {
+    /// Base URL of the OpenAI API
+    #[serde(default = "default_openai_moderation_base_url")]
+    pub base_url: String,
+
+    /// API key for authenticating with the OpenAI API
+    #[serde(default)]
+    pub api_key: String,
+
+    /// Moderation model
+    #[serde(default = "default_openai_moderation_model")]
+    pub model: String,
+
+    /// Categories that block content, such as `hate` or `violence/graphic`; a
+    /// category also covers its subcategories. Empty blocks content flagged in
+    /// any category
+    #[serde(default)]
+    pub block_categories: Vec<String>,
+}
+
+impl Default for OpenAiModerationConfig {
+    fn default() -> Self {
+        Self {
+            base_url: default_openai_moderation_base_url(),
+            api_key: String::new(),
+            model: default_openai_moderation_model(),
+            block_categories: Vec::new(),
+        }
+    }
+}
+
+fn default_openai_moderation_base_url() -> String {
+    "https://api.openai.com".to_string()
+}
+
+fn default_openai_moderation_model() -> String {
+    "omni-moderation-latest".to_string()
+}
+
+/// AWS Bedrock Guardrails settings.
+///
+/// Content is assessed with the ApplyGuardrail API of an existing guardrail.
+/// Requests are signed with AWS Signature Version 4 using static credentials.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BedrockGuardrailsConfig {
+    /// AWS region of the guardrail, such as `us-east-1`
+    #[serde(default)]
+    pub region: String,
+
+    /// ID or ARN of the guardrail
+    #[serde(default)]
+    pub guardrail_id: String,
+
+    /// Version of the guardrail, or `DRAFT` for its working draft
+    #[serde(default = "default_bedrock_guardrail_version")]
+    pub guardrail_version: String,
+
+    /// Bedrock runtime endpoint, such as a VPC endpoint (empty = the regional
+    /// endpoint)
+    #[serde(default)]
+    pub endpoint: String,
+
+    /// AWS access key ID
+    #[serde(default)]
+    pub access_key_id: String,
+
+    /// AWS secret access key
+    #[serde(default)]
+    pub secret_access_key: String,
+
+    /// Session token of temporary credentials (empty = long-term credentials)
+    #[serde(default)]
+    pub session_token: String,
+}
+
+impl Default for BedrockGuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            region: String::new(),
+            guardrail_id: String::new(),
+            guardrail_version: default_bedrock_guardrail_version(),
+            endpoint: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            session_token: String::new(),
+        }
+    }
+}
+
+fn default_bedrock_guardrail_version() -> String {
+    "DRAFT".to_string()
+}
+
+/// Reads the AWS region from `BEDROCK_GUARDRAILS_REGION`, `AWS_REGION` or
+/// `AWS_DEFAULT_REGION`, in that order.
+fn env_aws_region() -> Option<String> {
+    ["BEDROCK_GUARDRAILS_REGION", "AWS_REGION", "AWS_DEFAULT_REGION"]
+        .iter()
+        .find_map(|name| env::var(name).ok().filter(|region| !region.is_empty()))
+}
+
+/// Harm categories analyzed by Azure AI Content Safety.
+pub const AZURE_HARM_CATEGORIES: [&str; 4] = ["Hate", "SelfHarm", "Sexual", "Violence"];
+
+/// Azure AI Content Safety settings.
+///
+/// Content whose severity in a harm category reaches the category's threshold,
+/// on the scale of 0 (safe) to 7, is blocked as harmful content, the way PANW
+/// reports toxic content.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AzureContentSafetyConfig {
+    /// Endpoint of the Content Safety resource, such as
+    /// `https://<resource>.cognitiveservices.azure.com`
+    #[serde(default)]
+    pub endpoint: String,
+
+    /// Key of the Content Safety resource
+    #[serde(default)]
+    pub api_key: String,
+
+    /// Version of the Content Safety API
+    #[serde(default = "default_azure_content_safety_api_version")]
+    pub api_version: String,
+
+    /// Lowest severity that blocks content in a harm category; above 7 never
+    /// blocks
+    #[serde(default = "default_azure_content_safety_severity_threshold")]
+    pub severity_threshold: u8,
+
+    /// Thresholds by harm category (`Hate`, `SelfHarm`, `Sexual` or
+    /// `Violence`), overriding `severity_threshold`
+    #[serde(default)]
+    pub category_thresholds: HashMap<String, u8>,
+
+    /// Names of custom blocklists of the resource; content matching any of
+    /// their items is blocked
+    #[serde(default)]
+    pub blocklists: Vec<String>,
+}
+
+impl Default for AzureContentSafetyConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            api_key: String::new(),
+            api_version: default_azure_content_safety_api_version(),
+            severity_threshold: default_azure_content_safety_severity_threshold(),
+            category_thresholds: HashMap::new(),
+            blocklists: Vec::new(),
+        }
+    }
+}
+
+fn default_azure_content_safety_api_version() -> String {
+    "2024-09-01".to_string()
+}
+
+fn default_azure_content_safety_severity_threshold() -> u8 {
+    4
+}
+
+/// Llama Guard settings.
+///
+/// Content the model classifies as unsafe in one of the blocking hazard
+/// categories is blocked; privacy violations are reported as data loss
+/// prevention, code interpreter abuse as malicious code and every other
+/// category as harmful content.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LlamaGuardConfig {
+    /// Classifier model served by Ollama, answering in the Llama Guard format
+    #[serde(default = "default_llama_guard_model")]
+    pub model: String,
+
+    /// Hazard categories that block content, such as `S1` or `S10` (empty =
+    /// all categories)
+    #[serde(default)]
+    pub block_categories: Vec<String>,
+}
+
+impl Default for LlamaGuardConfig {
+    fn default() -> Self {
+        Self {
+            model: default_llama_guard_model(),
+            block_categories: Vec::new(),
+        }
+    }
+}
+
+fn default_llama_guard_model() -> String {
+    "llama-guard3".to_string()
+}
+
+/// Parses a comma-separated list of `Category=threshold` mappings, skipping
+/// invalid entries.
+fn parse_category_thresholds(value: &str) -> HashMap<String, u8> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .filter_map(|(category, threshold)| {
+            Some((category.trim().to_string(), threshold.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Security profile used for requests to a model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelProfile {
+    /// Model name; names without a tag also match the `:latest` tag
+    pub model: String,
+
+    /// PANW security profile used for the model's requests
+    pub profile: String,
+}
+
+/// Parses a comma-separated list of `model=profile` mappings.
+fn parse_model_profiles(value: &str) -> Vec<ModelProfile> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(model, profile)| ModelProfile {
+            model: model.trim().to_string(),
+            profile: profile.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Action taken when the local scanner finds a secret in a response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretAction {
+    /// Replace the secret with a redaction marker
+    #[default]
+    Mask,
+    /// Block the response
+    Block,
+}
+
+impl std::str::FromStr for SecretAction {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "mask" => Ok(Self::Mask),
+            "block" => Ok(Self::Block),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown secret action: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Local secret scanning settings.
+///
+/// Responses are checked for AWS keys, private key blocks and JWTs in addition
+/// to the PANW DLP assessment.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SecretScanConfig {
+    /// Enable the local secret scanner
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Action taken when a secret is found
+    #[serde(default)]
+    pub action: SecretAction,
+}
+
+/// Action taken when content is in a language that is not allowed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageAction {
+    /// Log and count the content but let it pass
+    #[default]
+    Flag,
+    /// Block the content before it is scanned
+    Block,
+}
+
+impl std::str::FromStr for LanguageAction {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "flag" => Ok(Self::Flag),
+            "block" => Ok(Self::Block),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown language action: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Language detection settings.
+///
+/// Prompts and responses are tagged with their detected language in logs and
+/// metrics, and content in other than the allowed languages can be flagged or
+/// blocked.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LanguageConfig {
+    /// Enable language detection
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// ISO 639-3 codes of the allowed languages (e.g. "eng", "deu"); all
+    /// languages are allowed when empty
+    #[serde(default)]
+    pub allowed_languages: Vec<String>,
+
+    /// Action taken for content in a language that is not allowed
+    #[serde(default)]
+    pub action: LanguageAction,
+
+    /// Minimum detection confidence (0.0 to 1.0) before the policy applies
+    #[serde(default = "default_language_min_confidence")]
+    pub min_confidence: f64,
+}
+
+fn default_language_min_confidence() -> f64 {
+    0.5
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_languages: Vec::new(),
+            action: LanguageAction::default(),
+            min_confidence: default_language_min_confidence(),
+        }
+    }
+}
+
+/// Severity of a term in the toxicity word lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToxicitySeverity {
+    /// Mild profanity
+    Low,
+    /// Strong profanity and insults
+    #[default]
+    Medium,
+    /// Threats and abuse
+    High,
+}
+
+impl ToxicitySeverity {
+    /// Returns the name used in logs and metric labels.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+impl std::str::FromStr for ToxicitySeverity {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown toxicity severity: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Action taken when the toxicity pre-filter matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToxicityAction {
+    /// Log and count the match but still scan the content with PANW
+    Flag,
+    /// Block the content without a PANW scan
+    #[default]
+    Block,
+}
+
+impl std::str::FromStr for ToxicityAction {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "flag" => Ok(Self::Flag),
+            "block" => Ok(Self::Block),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown toxicity action: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// A term in a toxicity word list.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToxicTerm {
+    /// Word or phrase, matched case-insensitively on word boundaries
+    pub term: String,
+
+    /// Severity of the term
+    #[serde(default)]
+    pub severity: ToxicitySeverity,
+}
+
+/// Word list of a single tenant.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TenantToxicityConfig {
+    /// Terms added for the tenant
+    #[serde(default)]
+    pub terms: Vec<ToxicTerm>,
+
+    /// Use only the tenant's terms instead of adding them to the shared lists
+    #[serde(default)]
+    pub replace_defaults: bool,
+}
+
+/// Local profanity and toxicity pre-filter settings.
+///
+/// Content is matched against an embedded word list, the configured terms and
+/// the terms of the request's tenant before PANW is consulted, so obviously
+/// abusive traffic does not use scan quota.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ToxicityConfig {
+    /// Enable the pre-filter
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Action taken when a term at or above `min_severity` matches
+    #[serde(default)]
+    pub action: ToxicityAction,
+
+    /// Lowest severity that triggers the action
+    #[serde(default)]
+    pub min_severity: ToxicitySeverity,
+
+    /// Terms added to the embedded word list for all tenants
+    #[serde(default)]
+    pub terms: Vec<ToxicTerm>,
+
+    /// Word lists keyed by tenant, as named by the metrics tenant header
+    #[serde(default)]
+    pub tenants: HashMap<String, TenantToxicityConfig>,
+}
+
+/// Local pre-filter rules.
+///
+/// Rules are matched against every prompt and response before it is scanned.
+/// Content matched by a `block` rule is blocked and content matched only by an
+/// `allow` rule is allowed, both without a scan; content matched by a
+/// `deep_scan` rule is always scanned afresh. A block beats a deep scan, which
+/// beats an allow.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PrefilterRulesConfig {
+    /// Rules in configuration order
+    #[serde(default)]
+    pub rules: Vec<PrefilterRule>,
+}
+
+/// A local pre-filter rule.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrefilterRule {
+    /// Unique name of the rule, reported in logs and metrics
+    pub name: String,
+
+    /// What happens to matching content
+    pub action: PrefilterAction,
+
+    /// Regular expressions matching the content; for `allow` rules, a pattern
+    /// must match the whole content
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    /// Words or phrases matching the content case-insensitively on word
+    /// boundaries; for `allow` rules, a keyword must be the whole content
+    #[serde(default)]
+    pub keywords: Vec<String>,
+
+    /// Content the rule applies to
+    #[serde(default)]
+    pub applies_to: PrefilterStage,
+}
+
+/// Action of a local pre-filter rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefilterAction {
+    /// Allow the content without a scan
+    Allow,
+    /// Scan the content afresh, skipping the scan cache and scan bypass
+    DeepScan,
+    /// Block the content without a scan
+    Block,
+}
+
+/// Content a local pre-filter rule applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefilterStage {
+    /// Prompts only
+    Prompts,
+    /// Responses only
+    Responses,
+    /// Prompts and responses
+    #[default]
+    Both,
+}
+
+/// Type of personal data masked by the local PII stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiEntity {
+    /// Email addresses
+    Email,
+    /// Phone numbers in international or North American notation
+    Phone,
+    /// US social security numbers
+    Ssn,
+    /// Payment card numbers passing the Luhn check
+    CreditCard,
+    /// International bank account numbers passing the mod-97 check
+    Iban,
+}
+
+impl std::str::FromStr for PiiEntity {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "email" => Ok(Self::Email),
+            "phone" => Ok(Self::Phone),
+            "ssn" => Ok(Self::Ssn),
+            "credit_card" => Ok(Self::CreditCard),
+            "iban" => Ok(Self::Iban),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown PII entity type: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl PiiEntity {
+    /// Returns the name of the entity type as configured.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Phone => "phone",
+            Self::Ssn => "ssn",
+            Self::CreditCard => "credit_card",
+            Self::Iban => "iban",
+        }
+    }
+}
+
+/// Local PII masking settings.
+///
+/// Personal data of the configured types is masked in prompts before they are
+/// scanned or sent to Ollama, so it never leaves the host.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PiiConfig {
+    /// Enable local PII masking
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Types of personal data to mask
+    #[serde(default = "default_pii_entities")]
+    pub entities: Vec<PiiEntity>,
+}
+
+impl Default for PiiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            entities: default_pii_entities(),
+        }
+    }
+}
+
+fn default_pii_entities() -> Vec<PiiEntity> {
+    vec![
+        PiiEntity::Email,
+        PiiEntity::Phone,
+        PiiEntity::Ssn,
+        PiiEntity::CreditCard,
+        PiiEntity::Iban,
+    ]
+}
+
+/// Encoded payload decoding settings.
+///
+/// Base64, hex and URL-encoded blobs in prompts are decoded and the decoded
+/// text is scanned along with the prompt, so instructions or secrets smuggled
+/// inside an encoding are assessed too.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DecodePayloadsConfig {
+    /// Enable decoding of encoded payloads
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Shortest encoded blob decoded, in characters
+    #[serde(default = "default_decode_payloads_min_length")]
+    pub min_length: usize,
+}
+
+impl Default for DecodePayloadsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_length: default_decode_payloads_min_length(),
+        }
+    }
+}
+
+fn default_decode_payloads_min_length() -> usize {
+    16
+}
+
+/// Parses a comma-separated list of PII entity types, skipping invalid
+/// entries.
+fn parse_pii_entities(value: &str) -> Vec<PiiEntity> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entity| entity.parse().ok())
+        .collect()
+}
+
+/// Behavior while content cannot be scanned by PANW, once the monthly scan
+/// budget is exhausted or while the circuit breaker is open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DegradedMode {
+    /// Reject requests that would need a PANW scan
+    #[default]
+    Block,
+    /// Pass content through unscanned and log it
+    Monitor,
+    /// Skip PANW and rely on the local checks only
+    LocalOnly,
+}
+
+impl std::str::FromStr for DegradedMode {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "block" => Ok(Self::Block),
+            "monitor" => Ok(Self::Monitor),
+            "local_only" => Ok(Self::LocalOnly),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown degraded mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Behavior when a PANW scan fails during an outage of the API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FailureMode {
+    /// Reject the request
+    #[default]
+    #[serde(rename = "fail_closed")]
+    Closed,
+    /// Pass content through unscanned and log a warning
+    #[serde(rename = "fail_open")]
+    Open,
+    /// Pass content through unscanned and export a `fail_open` security event
+    #[serde(rename = "fail_open_log")]
+    OpenLog,
+}
+
+impl std::str::FromStr for FailureMode {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fail_closed" => Ok(Self::Closed),
+            "fail_open" => Ok(Self::Open),
+            "fail_open_log" => Ok(Self::OpenLog),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown failure mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Trusted client settings.
+///
+/// Requests authenticated with one of the listed client keys, or coming from
+/// one of the listed networks, are not scanned by PANW. The local checks still
+/// run, and every such request is logged and exported as a `scan_bypass`
+/// security event.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScanBypassConfig {
+    /// Ids of client keys whose requests skip PANW scanning
+    #[serde(default)]
+    pub keys: Vec<String>,
+
+    /// Source networks in CIDR notation, or single addresses, whose requests
+    /// skip PANW scanning
+    #[serde(default)]
+    pub networks: Vec<String>,
+}
+
+/// Scan result cache settings.
+///
+/// Repeated identical content, such as system prompts and canned greetings, is
+/// scanned once and its verdict reused until it expires.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanCacheConfig {
+    /// Enable the cache
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Largest number of cached verdicts; the least recently used are evicted
+    #[serde(default = "default_scan_cache_max_entries")]
+    pub max_entries: usize,
+
+    /// How long a verdict is reused in seconds
+    #[serde(default = "default_scan_cache_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for ScanCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_entries: default_scan_cache_max_entries(),
+            ttl_secs: default_scan_cache_ttl_secs(),
+        }
+    }
+}
+
+fn default_scan_cache_max_entries() -> usize {
+    10_000
+}
+
+fn default_scan_cache_ttl_secs() -> u64 {
+    300
+}
+
+/// Asynchronous scanning of streamed responses.
+///
+/// Streamed content is submitted to the PANW asynchronous scan API and
+/// forwarded while its verdict is pending, as long as no more than the risk
+/// window of content is waiting for a verdict. A blocking verdict ends the
+/// stream, but content forwarded before it has already reached the client.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsyncStreamingConfig {
+    /// Enable asynchronous scanning of streamed responses
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Largest number of characters forwarded before their verdict is known
+    #[serde(default = "default_async_risk_window_chars")]
+    pub risk_window_chars: usize,
+
+    /// Interval between polls for pending scan results in milliseconds
+    #[serde(default = "default_async_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+
+    /// How long to wait for the result of a submitted scan in seconds
+    #[serde(default = "default_async_settle_timeout_secs")]
+    pub settle_timeout_secs: u64,
+}
+
+impl Default for AsyncStreamingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            risk_window_chars: default_async_risk_window_chars(),
+            poll_interval_ms: default_async_poll_interval_ms(),
+            settle_timeout_secs: default_async_settle_timeout_secs(),
+        }
+    }
+}
+
+fn default_async_risk_window_chars() -> usize {
+    2_000
+}
+
+fn default_async_poll_interval_ms() -> u64 {
+    250
+}
+
+fn default_async_settle_timeout_secs() -> u64 {
+    30
+}
+
+/// Capture of PANW responses for troubleshooting.
+///
+/// Raw PANW responses are not logged, since they can carry scanned content.
+/// Instead, responses with an error status or a body that cannot be parsed,
+/// and a sample of the others, are kept redacted in memory and listed at
+/// `GET /admin/panw-responses`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResponseCaptureConfig {
+    /// Enable capturing PANW responses
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Number of most recent captures kept
+    #[serde(default = "default_capture_capacity")]
+    pub capacity: usize,
+
+    /// Largest number of responses captured per minute
+    #[serde(default = "default_capture_max_per_minute")]
+    pub max_per_minute: u32,
+
+    /// Percentage of successfully parsed responses captured
+    #[serde(default)]
+    pub sample_percent: u8,
+
+    /// Largest number of bytes of a response body kept
+    #[serde(default = "default_capture_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+impl Default for ResponseCaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: default_capture_capacity(),
+            max_per_minute: default_capture_max_per_minute(),
+            sample_percent: 0,
+            max_body_bytes: default_capture_max_body_bytes(),
+        }
+    }
+}
+
+fn default_capture_capacity() -> usize {
+    50
+}
+
+fn default_capture_max_per_minute() -> u32 {
+    10
+}
+
+fn default_capture_max_body_bytes() -> usize {
+    4_096
+}
+
+/// Circuit breaker around the PANW API.
+///
+/// After the configured number of consecutive failed scans, such as timeouts,
+/// connection errors or server errors, scans are suspended for the cooldown
+/// and content is handled by the open mode instead of waiting on an
+/// unavailable service. After the cooldown, a single scan probes the API and
+/// closes the breaker if it succeeds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Enable the circuit breaker
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Number of consecutive failed scans that opens the breaker
+    #[serde(default = "default_breaker_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// How long the breaker stays open before probing the API again in seconds
+    #[serde(default = "default_breaker_cooldown_secs")]
+    pub cooldown_secs: u64,
+
+    /// Behavior while the breaker is open; `monitor` and `local_only` fail
+    /// open and require `failure_mode` `fail_open` or `fail_open_log`
+    #[serde(default)]
+    pub open_mode: DegradedMode,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            failure_threshold: default_breaker_failure_threshold(),
+            cooldown_secs: default_breaker_cooldown_secs(),
+            open_mode: DegradedMode::default(),
+        }
+    }
+}
+
+fn default_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_breaker_cooldown_secs() -> u64 {
+    30
+}
+
+/// Monthly PANW scan budget settings.
+///
+/// Guards against surprise overage bills from a misbehaving client loop.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanBudgetConfig {
+    /// Maximum number of PANW scans per calendar month (UTC); 0 disables the cap
+    #[serde(default)]
+    pub monthly_cap: u64,
+
+    /// Behavior once the cap is reached
+    #[serde(default)]
+    pub degraded_mode: DegradedMode,
+
+    /// Webhook URL notified once when the cap is reached
+    #[serde(default)]
+    pub alert_webhook: String,
+
+    /// File used to persist the scan count across restarts
+    #[serde(default = "default_budget_state_file")]
+    pub state_file: String,
+}
+
+impl Default for ScanBudgetConfig {
+    fn default() -> Self {
+        Self {
+            monthly_cap: 0,
+            degraded_mode: DegradedMode::default(),
+            alert_webhook: String::new(),
+            state_file: default_budget_state_file(),
+        }
+    }
+}
+
+fn default_budget_state_file() -> String {
+    "scan-budget.json".to_string()
+}
+
+fn default_rate_limit_max_wait_secs() -> u64 {
+    30
+}
+
+/// Model management settings.
+///
+/// Controls how responses from the model management endpoints are handled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelsConfig {
+    /// Filtering applied to /api/show responses
+    #[serde(default)]
+    pub show_filter: ShowFilterConfig,
+
+    /// Require a confirmation token for deletes and copies over existing models
+    #[serde(default = "default_true")]
+    pub confirm_destructive: bool,
+
+    /// Lifetime of confirmation tokens in seconds
+    #[serde(default = "default_confirmation_ttl_secs")]
+    pub confirmation_ttl_secs: u64,
+
+    /// Streaming assessment strategies of individual models
+    #[serde(default)]
+    pub streaming: Vec<ModelStreaming>,
+}
+
+impl Default for ModelsConfig {
+    fn default() -> Self {
+        Self {
+            show_filter: ShowFilterConfig::default(),
+            confirm_destructive: true,
+            confirmation_ttl_secs: default_confirmation_ttl_secs(),
+            streaming: Vec::new(),
+        }
+    }
+}
+
+/// How streamed responses are batched for security assessment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamingStrategy {
+    /// Each line is scanned before it is released
+    #[default]
+    Sentence,
+    /// Text is scanned by paragraph and code blocks as a whole before release
+    Code,
+    /// Content is released within the risk window before its asynchronous verdict
+    Windowed,
+}
+
+impl std::str::FromStr for StreamingStrategy {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sentence" => Ok(Self::Sentence),
+            "code" => Ok(Self::Code),
+            "windowed" => Ok(Self::Windowed),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown streaming strategy: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Streaming assessment strategy used for a model's streamed responses.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelStreaming {
+    /// Model name; names without a tag also match the `:latest` tag
+    pub model: String,
+
+    /// How the model's streamed responses are batched for assessment
+    pub strategy: StreamingStrategy,
+
+    /// Characters accumulated before they are scanned regardless of boundaries
+    /// (defaults to 100000)
+    #[serde(default)]
+    pub assessment_window_chars: Option<usize>,
+
+    /// Risk window of the windowed strategy (defaults to
+    /// `security.async_streaming.risk_window_chars`)
+    #[serde(default)]
+    pub risk_window_chars: Option<usize>,
+}
+
+/// Parses a comma-separated list of `model=strategy` mappings, skipping unknown strategies.
+fn parse_model_streaming(value: &str) -> Vec<ModelStreaming> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .filter_map(|(model, strategy)| {
+            Some(ModelStreaming {
+                model: model.trim().to_string(),
+                strategy: strategy.parse().ok()?,
+                assessment_window_chars: None,
+                risk_window_chars: None,
+            })
+        })
+        .collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_confirmation_ttl_secs() -> u64 {
+    60
+}
+
+/// Filtering settings for /api/show responses.
+///
+/// The `system`, `template`, `parameters` and `modelfile` fields can carry
+/// baked-in prompts or credentials, so they can be scanned or removed entirely.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ShowFilterConfig {
+    /// Scan the sensitive fields and redact any that fail the security assessment
+    #[serde(default)]
+    pub scan: bool,
+
+    /// Remove the sensitive fields from the response entirely
+    #[serde(default)]
+    pub strip: bool,
+}
+
+/// Administrative API settings.
+///
+/// The admin endpoints are disabled unless a token is configured.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AdminConfig {
+    /// Bearer token required by the /admin endpoints
+    #[serde(default)]
+    pub token: String,
+}
+
+/// Operating mode of the proxy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperatingMode {
+    /// All endpoints are available
+    #[default]
+    Normal,
+    /// Model management endpoints that modify models are rejected
+    ReadOnly,
+    /// All API endpoints are rejected with 503; in-flight streams are allowed to finish
+    Maintenance,
+}
+
+impl std::str::FromStr for OperatingMode {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Ok(Self::Normal),
+            "read_only" | "readonly" => Ok(Self::ReadOnly),
+            "maintenance" => Ok(Self::Maintenance),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown operating mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Read-only and maintenance mode settings.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MaintenanceConfig {
+    /// Mode applied outside of scheduled windows
+    #[serde(default)]
+    pub mode: OperatingMode,
+
+    /// Message returned to clients while a restricted mode is active
+    #[serde(default)]
+    pub message: String,
+
+    /// Scheduled windows during which a restricted mode applies
+    #[serde(default)]
+    pub windows: Vec<MaintenanceWindow>,
+}
+
+/// A scheduled time window with a restricted operating mode.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MaintenanceWindow {
+    /// Mode applied during the window
+    pub mode: OperatingMode,
+
+    /// Start of the window (RFC 3339)
+    pub start: DateTime<Utc>,
+
+    /// End of the window (RFC 3339)
+    pub end: DateTime<Utc>,
+
+    /// Message overriding the default maintenance message during this window
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Emergency kill switch settings.
+///
+/// While the kill switch is activated through the admin API, every generation
+/// request is rejected and streams in flight are cut off. The activation is
+/// persisted until it is lifted.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KillSwitchConfig {
+    /// Message returned to clients while the kill switch is active (empty = the
+    /// default message)
+    #[serde(default)]
+    pub message: String,
+
+    /// File persisting the activation across restarts
+    #[serde(default = "default_kill_switch_state_file")]
+    pub state_file: String,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        Self {
+            message: String::new(),
+            state_file: default_kill_switch_state_file(),
+        }
+    }
+}
+
+fn default_kill_switch_state_file() -> String {
+    "kill-switch.json".to_string()
+}
+
+/// Duplicate-request suppression settings.
+///
+/// Identical generation requests from the same client within the window are
+/// answered with the result of the first request instead of being scanned and
+/// generated again.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DedupConfig {
+    /// Enable duplicate-request suppression
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How long a completed result is replayed to duplicates, in seconds
+    #[serde(default = "default_dedup_window_secs")]
+    pub window_secs: u64,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_secs: default_dedup_window_secs(),
+        }
+    }
+}
+
+fn default_dedup_window_secs() -> u64 {
+    10
+}
+
+/// Idempotency-Key header settings.
+///
+/// Generation requests carrying an `Idempotency-Key` header are processed at most
+/// once per client and key; replays receive the stored response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdempotencyConfig {
+    /// Honour the Idempotency-Key header on generation endpoints
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// How long stored responses are kept for replays, in seconds
+    #[serde(default = "default_idempotency_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: default_idempotency_ttl_secs(),
+        }
+    }
+}
+
+fn default_idempotency_ttl_secs() -> u64 {
+    300
+}
+
+/// Signed response manifest settings.
+///
+/// When enabled, a detached Ed25519-signed manifest is produced for every
+/// generation response and can be retrieved by request ID.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProvenanceConfig {
+    /// Produce signed manifests for generation responses
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// PKCS#8 PEM file holding the Ed25519 signing key; an ephemeral key is used when empty
+    #[serde(default)]
+    pub signing_key_file: String,
+
+    /// How long manifests remain retrievable, in seconds
+    #[serde(default = "default_provenance_retention_secs")]
+    pub retention_secs: u64,
+}
+
+impl Default for ProvenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            signing_key_file: String::new(),
+            retention_secs: default_provenance_retention_secs(),
+        }
+    }
+}
+
+fn default_provenance_retention_secs() -> u64 {
+    3600
+}
+
+/// Tamper-evident conversation transcript settings.
+///
+/// When enabled, every assessed prompt and response is appended to the
+/// transcript file with its verdict. The records of a session are chained by
+/// hash, so a record that was altered, removed or reordered is detected by
+/// `transcripts verify`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TranscriptsConfig {
+    /// Record assessed prompts and responses
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// File the transcript records are appended to
+    #[serde(default = "default_transcripts_file")]
+    pub file: String,
+
+    /// Request header naming the session of a request; requests without it
+    /// form a session of their own, named by their transaction ID
+    #[serde(default = "default_transcripts_session_header")]
+    pub session_header: String,
+
+    /// Days a session is kept after its last record before it is pruned;
+    /// 0 keeps sessions forever
+    #[serde(default)]
+    pub retention_days: u64,
+
+    /// Key of the HMAC-SHA256 record hashes, at least 32 bytes, or a Vault
+    /// reference; plain SHA-256 hashes are used when empty
+    #[serde(default)]
+    pub hmac_key: String,
+}
+
+impl Default for TranscriptsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            file: default_transcripts_file(),
+            session_header: default_transcripts_session_header(),
+            retention_days: 0,
+            hmac_key: String::new(),
+        }
+    }
+}
+
+fn default_transcripts_file() -> String {
+    "transcripts.jsonl".to_string()
+}
+
+fn default_transcripts_session_header() -> String {
+    "x-session-id".to_string()
+}
+
+/// How URLs in model responses are rewritten.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlRewriteMode {
+    /// Leave URLs untouched
+    #[default]
+    None,
+    /// Rewrite URLs to defanged form, e.g. hxxps://example[.]com
+    Defang,
+    /// Wrap URLs in the configured safe-redirect service
+    Redirect,
+}
+
+impl std::str::FromStr for UrlRewriteMode {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "defang" => Ok(Self::Defang),
+            "redirect" => Ok(Self::Redirect),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown URL rewrite mode: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Built-in filter applied to response text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFilter {
+    /// Remove markdown images such as `![alt](https://example.com/a.png)`
+    StripMarkdownImages,
+    /// Remove HTML tags and comments, and script and style blocks with their contents
+    StripHtml,
+    /// Collapse runs of spaces and tabs, and more than two consecutive newlines
+    CollapseWhitespace,
+    /// Remove leaked system prompts, such as echoed chat template system turns
+    StripLeakage,
+}
+
+impl std::str::FromStr for ResponseFilter {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "strip_markdown_images" => Ok(Self::StripMarkdownImages),
+            "strip_html" => Ok(Self::StripHtml),
+            "collapse_whitespace" => Ok(Self::CollapseWhitespace),
+            "strip_leakage" => Ok(Self::StripLeakage),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown response filter: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Response post-processing settings.
+///
+/// Post-processing runs on assessed content just before it is returned to the
+/// client, in both streaming and non-streaming responses. The filters run in the
+/// configured order, followed by URL rewriting and the length limit.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostProcessConfig {
+    /// Filters applied to response text, in order
+    #[serde(default)]
+    pub filters: Vec<ResponseFilter>,
+
+    /// How URLs in responses are rewritten
+    #[serde(default)]
+    pub url_mode: UrlRewriteMode,
+
+    /// Safe-redirect URL template for redirect mode; `{url}` is replaced with the
+    /// percent-encoded URL, which is appended when the placeholder is missing
+    #[serde(default)]
+    pub url_redirect_template: String,
+
+    /// Maximum number of characters returned per response (0 for unlimited)
+    #[serde(default)]
+    pub max_length: usize,
+
+    /// Notice appended to responses cut off at the maximum length
+    #[serde(default = "default_truncation_notice")]
+    pub truncation_notice: String,
+
+    /// Regular expressions matching leaked system prompts, removed by the
+    /// `strip_leakage` filter (empty = the built-in patterns)
+    #[serde(default)]
+    pub leakage_patterns: Vec<String>,
+}
+
+impl Default for PostProcessConfig {
+    fn default() -> Self {
+        Self {
+            filters: Vec::new(),
+            url_mode: UrlRewriteMode::default(),
+            url_redirect_template: String::new(),
+            max_length: 0,
+            truncation_notice: default_truncation_notice(),
+            leakage_patterns: Vec::new(),
+        }
+    }
+}
+
+fn default_truncation_notice() -> String {
+    "\n\n[Response truncated]".to_string()
+}
+
+/// Parses a comma-separated list of response filters, skipping unknown names.
+fn parse_filters(value: &str) -> Vec<ResponseFilter> {
+    value
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .filter_map(|name| name.parse().ok())
+        .collect()
+}
+
+/// Summarization endpoint settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SummarizeConfig {
+    /// Ollama model used for summaries when the request names none
+    #[serde(default)]
+    pub model: String,
+
+    /// System prompt instructing the model how to summarize
+    #[serde(default = "default_summarize_prompt")]
+    pub prompt: String,
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            prompt: default_summarize_prompt(),
+        }
+    }
+}
+
+fn default_summarize_prompt() -> String {
+    "Summarize the following content concisely. Keep the key facts, decisions and open questions, and do not add information that is not in the content.".to_string()
+}
+
+/// Batch generation endpoint settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchConfig {
+    /// Maximum number of prompts accepted in a single batch
+    #[serde(default = "default_batch_max_prompts")]
+    pub max_prompts: usize,
+
+    /// Number of prompts of a batch processed at the same time
+    #[serde(default = "default_batch_concurrency")]
+    pub concurrency: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_prompts: default_batch_max_prompts(),
+            concurrency: default_batch_concurrency(),
+        }
+    }
+}
+
+fn default_batch_max_prompts() -> usize {
+    100
+}
+
+fn default_batch_concurrency() -> usize {
+    4
+}
+
+/// Asynchronous generation job settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobsConfig {
+    /// How long finished job results are kept when they are not fetched
+    #[serde(default = "default_jobs_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Maximum number of unfetched jobs per client address
+    #[serde(default = "default_jobs_max_per_client")]
+    pub max_per_client: usize,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_jobs_ttl_secs(),
+            max_per_client: default_jobs_max_per_client(),
+        }
+    }
+}
+
+fn default_jobs_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_jobs_max_per_client() -> usize {
+    5
+}
+
+/// A prompt executed on a cron schedule through the full security pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledPrompt {
+    /// Unique name of the schedule
+    #[serde(default)]
+    pub name: String,
+
+    /// Cron expression evaluated in UTC, either in five-field crontab syntax or
+    /// with a leading seconds field
+    pub cron: String,
+
+    /// Ollama model used for generation
+    pub model: String,
+
+    /// The prompt to run
+    pub prompt: String,
+
+    /// Optional system message to guide model behavior
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// Webhook receiving the result of every run
+    pub webhook: String,
+}
+
+impl ScheduledPrompt {
+    /// Parses the cron expression of the schedule.
+    ///
+    /// Five-field crontab expressions are run at second zero of each matching minute.
+    pub fn schedule(&self) -> Result<cron::Schedule, ConfigError> {
+        let expression = self.cron.trim();
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {}", expression)
+        } else {
+            expression.to_string()
+        };
+
+        expression.parse().map_err(|e| {
+            ConfigError::ValidationError(format!(
+                "Invalid cron expression for schedule {}: {}",
+                self.name, e
+            ))
+        })
+    }
+
+    /// Checks that the schedule is complete and its cron expression is valid.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.name.trim().is_empty() {
+            return Err(ConfigError::ValidationError(
+                "Scheduled prompts must have a name".into(),
+            ));
+        }
+        if self.model.is_empty() || self.prompt.is_empty() {
+            return Err(ConfigError::ValidationError(format!(
+                "Schedule {} must define a model and a prompt",
+                self.name
+            )));
+        }
+        if !self.webhook.starts_with("http") {
+            return Err(ConfigError::ValidationError(format!(
+                "Schedule {} must define a webhook starting with http:// or https://",
+                self.name
+            )));
+        }
+        self.schedule().map(|_| ())
+    }
+}
+
+/// Prompt template library settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplatesConfig {
+    /// File persisting saved templates across restarts; templates are kept in
+    /// memory only when empty
+    #[serde(default = "default_templates_state_file")]
+    pub state_file: String,
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        Self {
+            state_file: default_templates_state_file(),
+        }
+    }
+}
+
+fn default_templates_state_file() -> String {
+    "prompt-templates.json".to_string()
+}
+
+/// Health check settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HealthConfig {
+    /// How long each upstream probe may take before the dependency is reported down
+    #[serde(default = "default_health_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// How long a health check result is reused before the upstreams are probed again
+    #[serde(default = "default_health_cache_secs")]
+    pub cache_secs: u64,
+
+    /// Interval between background probes of the PANW security channel; 0
+    /// disables the background probe
+    #[serde(default = "default_health_probe_interval_secs")]
+    pub probe_interval_secs: u64,
+
+    /// How long readiness reports not ready after a shutdown signal before the
+    /// server stops accepting connections
+    #[serde(default = "default_health_shutdown_delay_secs")]
+    pub shutdown_delay_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_health_timeout_secs(),
+            cache_secs: default_health_cache_secs(),
+            probe_interval_secs: default_health_probe_interval_secs(),
+            shutdown_delay_secs: default_health_shutdown_delay_secs(),
+        }
+    }
+}
+
+fn default_health_timeout_secs() -> u64 {
+    3
+}
+
+fn default_health_cache_secs() -> u64 {
+    10
+}
+
+fn default_health_probe_interval_secs() -> u64 {
+    30
+}
+
+fn default_health_shutdown_delay_secs() -> u64 {
+    5
+}
+
+/// Request metrics settings.
+///
+/// Request counters and latency histograms are labeled with the tenant and a
+/// hashed identifier of the API key so per-customer dashboards are possible.
+/// Both labels are capped to keep the number of series bounded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricsConfig {
+    /// Request header naming the tenant
+    #[serde(default = "default_metrics_tenant_header")]
+    pub tenant_header: String,
+
+    /// Maximum number of distinct tenant labels; further tenants are reported as `other`
+    #[serde(default = "default_metrics_max_label_values")]
+    pub max_tenants: usize,
+
+    /// Maximum number of distinct key labels; further keys are reported as `other`
+    #[serde(default = "default_metrics_max_label_values")]
+    pub max_keys: usize,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            tenant_header: default_metrics_tenant_header(),
+            max_tenants: default_metrics_max_label_values(),
+            max_keys: default_metrics_max_label_values(),
+        }
+    }
+}
+
+fn default_metrics_tenant_header() -> String {
+    "x-tenant-id".to_string()
+}
+
+fn default_metrics_max_label_values() -> usize {
+    100
+}
+
+/// Context window check settings.
+///
+/// Prompts that are estimated to exceed the target model's context window are
+/// rejected before they are scanned or forwarded, instead of being silently
+/// truncated by Ollama.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContextConfig {
+    /// Enable the context window check
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Characters counted as one token when estimating the prompt size
+    #[serde(default = "default_context_chars_per_token")]
+    pub chars_per_token: usize,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            chars_per_token: default_context_chars_per_token(),
+        }
+    }
+}
+
+fn default_context_chars_per_token() -> usize {
+    4
+}
+
+/// Model capability cache settings.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CapabilitiesConfig {
+    /// How long the capabilities learned from `/api/show` are cached per model
+    #[serde(default = "default_capabilities_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            cache_ttl_secs: default_capabilities_cache_ttl_secs(),
+        }
+    }
+}
+
+fn default_capabilities_cache_ttl_secs() -> u64 {
+    600
+}
+
+/// Automatic model pull settings.
+///
+/// When enabled, a request for an allowed model that is not installed starts a
+/// pull in the background and is answered with 425 and a `Retry-After` header
+/// until the model is available.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoPullConfig {
+    /// Enable automatic pulls of missing models
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Models that may be pulled automatically; a trailing `*` matches any suffix
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+
+    /// Seconds clients are asked to wait before retrying while a pull is running
+    #[serde(default = "default_auto_pull_retry_after_secs")]
+    pub retry_after_secs: u64,
+}
+
+impl Default for AutoPullConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_models: Vec::new(),
+            retry_after_secs: default_auto_pull_retry_after_secs(),
+        }
+    }
+}
+
+fn default_auto_pull_retry_after_secs() -> u64 {
+    10
+}
+
+/// An API key of a proxy client.
+///
+/// Either the key itself or its SHA-256 hash is configured, so keys do not have
+/// to be stored in plain text.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientKey {
+    /// Identifier of the client, reported to PANW as the app user and logged
+    pub id: String,
+
+    /// The key in plain text
+    #[serde(default)]
+    pub key: String,
+
+    /// Hex-encoded SHA-256 hash of the key, used instead of `key`
+    #[serde(default)]
+    pub key_sha256: String,
+
+    /// Endpoints the key may be used for, out of `KEY_SCOPES`; every scope
+    /// except `admin` when empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    /// Models the key may be used with; a trailing `*` matches any suffix, and
+    /// names without a tag also match the `:latest` tag. Every model when empty
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
+/// Scopes that can be granted to client keys.
+pub const KEY_SCOPES: [&str; 6] = [
+    "generate",
+    "chat",
+    "embeddings",
+    "models:read",
+    "models:write",
+    "admin",
+];
+
+/// API key authentication settings for proxy clients.
+///
+/// When enabled, every request except the health checks and the admin API must
+/// carry one of the configured keys, a key created through the admin API, or a
+/// valid JWT as a bearer token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientAuthConfig {
+    /// Require an API key from clients
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Accepted client keys
+    #[serde(default)]
+    pub keys: Vec<ClientKey>,
+
+    /// File persisting the keys managed through the admin API, stored as their
+    /// hashes; managed keys are kept in memory only when empty
+    #[serde(default = "default_client_auth_key_state_file")]
+    pub key_state_file: String,
+
+    /// JWT bearer token validation
+    #[serde(default)]
+    pub jwt: JwtConfig,
+
+    /// End user named by a trusted frontend
+    #[serde(default)]
+    pub app_user_header: AppUserHeaderConfig,
+
+    /// Accept HTTP Basic credentials, with a client key's id as the user name
+    /// and the key as the password
+    #[serde(default)]
+    pub basic_auth: bool,
+
+    /// Identity asserted by a trusted reverse proxy
+    #[serde(default)]
+    pub trusted_proxy: TrustedProxyConfig,
+
+    /// Short-lived access tokens issued in exchange for keys and JWTs
+    #[serde(default)]
+    pub access_tokens: AccessTokenConfig,
+
+    /// Lockout of sources that repeatedly fail authentication
+    #[serde(default)]
+    pub lockout: AuthLockoutConfig,
+}
+
+/// Brute-force protection settings.
+///
+/// Source IP addresses and credentials failing authentication `max_failures`
+/// times within `window_secs` are locked out for `base_lockout_secs`, doubling
+/// with every further lockout up to `max_lockout_secs`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthLockoutConfig {
+    /// Lock out sources that repeatedly fail authentication
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Failed attempts within the window that lock a source out
+    #[serde(default = "default_auth_lockout_max_failures")]
+    pub max_failures: u32,
+
+    /// Window in seconds in which failed attempts are counted
+    #[serde(default = "default_auth_lockout_window_secs")]
+    pub window_secs: u64,
+
+    /// Duration of the first lockout in seconds
+    #[serde(default = "default_auth_lockout_base_secs")]
+    pub base_lockout_secs: u64,
+
+    /// Longest lockout in seconds
+    #[serde(default = "default_auth_lockout_max_secs")]
+    pub max_lockout_secs: u64,
+}
+
+impl Default for AuthLockoutConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_failures: default_auth_lockout_max_failures(),
+            window_secs: default_auth_lockout_window_secs(),
+            base_lockout_secs: default_auth_lockout_base_secs(),
+            max_lockout_secs: default_auth_lockout_max_secs(),
+        }
+    }
+}
+
+fn default_auth_lockout_max_failures() -> u32 {
+    10
+}
+
+fn default_auth_lockout_window_secs() -> u64 {
+    300
+}
+
+fn default_auth_lockout_base_secs() -> u64 {
+    60
+}
+
+fn default_auth_lockout_max_secs() -> u64 {
+    3600
+}
+
+/// Settings of the short-lived access tokens issued at `/auth/token`.
+///
+/// Clients exchange a client key or a JWT from the identity provider for an
+/// access token signed by the proxy, so scripts and notebooks need not embed
+/// long-lived secrets.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessTokenConfig {
+    /// Issue access tokens and accept them as credentials
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// How long issued tokens are valid in seconds
+    #[serde(default = "default_access_token_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Secret signing the tokens, at least 32 bytes; a random secret is
+    /// generated at startup when empty, so tokens do not survive restarts
+    #[serde(default)]
+    pub signing_secret: String,
+}
+
+impl Default for AccessTokenConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_access_token_ttl_secs(),
+            signing_secret: String::new(),
+        }
+    }
+}
+
+fn default_access_token_ttl_secs() -> u64 {
+    900
+}
+
+/// Settings of the identity header set by a trusted reverse proxy.
+///
+/// Small deployments often authenticate users at a reverse proxy in front of
+/// the proxy. When enabled, requests from the listed proxy addresses carrying
+/// the header take its value as the client identity without further
+/// credentials. The header is rejected from any other address, so it cannot be
+/// spoofed by clients reaching the proxy directly.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrustedProxyConfig {
+    /// Accept the identity header from the listed proxies
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Name of the header carrying the authenticated user
+    #[serde(default = "default_trusted_proxy_header")]
+    pub header: String,
+
+    /// IP addresses of the reverse proxies allowed to send the header
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+impl Default for TrustedProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            header: default_trusted_proxy_header(),
+            addresses: Vec::new(),
+        }
+    }
+}
+
+impl Default for ClientAuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: Vec::new(),
+            key_state_file: default_client_auth_key_state_file(),
+            jwt: JwtConfig::default(),
+            app_user_header: AppUserHeaderConfig::default(),
+            basic_auth: false,
+            trusted_proxy: TrustedProxyConfig::default(),
+            access_tokens: AccessTokenConfig::default(),
+            lockout: AuthLockoutConfig::default(),
+        }
+    }
+}
+
+fn default_client_auth_key_state_file() -> String {
+    "client-keys.json".to_string()
+}
+
+fn default_trusted_proxy_header() -> String {
+    "X-Forwarded-User".to_string()
+}
+
+/// Settings of the header through which a trusted frontend names the end user.
+///
+/// Frontends such as Open WebUI authenticate as a single client but serve many
+/// users. When enabled, clients listed as trusted may name the end user in the
+/// header, and the end user is reported to PANW as the app user in place of the
+/// frontend. Other clients sending the header are rejected, so it cannot be
+/// spoofed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppUserHeaderConfig {
+    /// Accept the header from trusted clients
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Name of the header
+    #[serde(default = "default_app_user_header_name")]
+    pub name: String,
+
+    /// Identities of the clients allowed to send the header, as authenticated
+    /// by key, JWT subject or client certificate
+    #[serde(default)]
+    pub trusted_clients: Vec<String>,
+}
+
+impl Default for AppUserHeaderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: default_app_user_header_name(),
+            trusted_clients: Vec::new(),
+        }
+    }
+}
+
+fn default_app_user_header_name() -> String {
+    "X-App-User".to_string()
+}
+
+/// Security profile used for members of a group.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupProfile {
+    /// Group as named in the groups claim
+    pub group: String,
+
+    /// PANW security profile used for the group's requests
+    pub profile: String,
+}
+
+/// JWT bearer token validation settings.
+///
+/// Tokens are validated against the signing keys published at the JWKS URL of
+/// the identity provider. The subject becomes the client identity and the first
+/// configured group the client belongs to selects the security profile.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JwtConfig {
+    /// Accept JWTs as client credentials
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Required issuer (`iss` claim)
+    #[serde(default)]
+    pub issuer: String,
+
+    /// Required audience (`aud` claim); not checked when empty
+    #[serde(default)]
+    pub audience: String,
+
+    /// URL of the issuer's JSON Web Key Set
+    #[serde(default)]
+    pub jwks_url: String,
+
+    /// How long fetched signing keys are cached in seconds
+    #[serde(default = "default_jwt_jwks_refresh_secs")]
+    pub jwks_refresh_secs: u64,
+
+    /// Claim holding the client identity
+    #[serde(default = "default_jwt_subject_claim")]
+    pub subject_claim: String,
+
+    /// Claim holding the client's groups
+    #[serde(default = "default_jwt_groups_claim")]
+    pub groups_claim: String,
+
+    /// Security profiles by group, in order of precedence
+    #[serde(default)]
+    pub group_profiles: Vec<GroupProfile>,
+
+    /// Timeouts and connection pooling of the client fetching signing keys;
+    /// requests time out after 10 seconds unless configured otherwise
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            issuer: String::new(),
+            audience: String::new(),
+            jwks_url: String::new(),
+            jwks_refresh_secs: default_jwt_jwks_refresh_secs(),
+            subject_claim: default_jwt_subject_claim(),
+            groups_claim: default_jwt_groups_claim(),
+            group_profiles: Vec::new(),
+            http_client: HttpClientConfig::default(),
+        }
+    }
+}
+
+fn default_jwt_jwks_refresh_secs() -> u64 {
+    3600
+}
+
+fn default_jwt_subject_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_jwt_groups_claim() -> String {
+    "groups".to_string()
+}
+
+/// Parses a comma-separated list of `group:profile` mappings.
+fn parse_group_profiles(value: &str) -> Vec<GroupProfile> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(group, profile)| GroupProfile {
+            group: group.trim().to_string(),
+            profile: profile.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Per-client usage report settings.
+///
+/// Usage is counted for every identified client. These settings only control
+/// the privacy protections applied when reports are exported.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UsageConfig {
+    /// Clients with fewer requests are left out of reports (0 = no threshold)
+    #[serde(default)]
+    pub report_min_count: u64,
+
+    /// Epsilon of the Laplace noise added to reported counts (0 = no noise)
+    #[serde(default)]
+    pub report_noise_epsilon: f64,
+}
+
+/// Request rate limit of a single route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteRateLimit {
+    /// Route template the limit applies to (e.g. `/api/chat`)
+    pub route: String,
+
+    /// Sustained number of requests allowed per minute
+    pub requests_per_minute: u32,
+
+    /// Number of requests allowed in a burst
+    pub burst: u32,
+}
+
+/// Per-client request rate limit settings.
+///
+/// Every client gets a token bucket per route, keyed by its authenticated
+/// identity or, for anonymous clients, its IP address. Routes without their own
+/// limit use the default limit.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimitConfig {
+    /// Enforce request rate limits
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Default sustained number of requests allowed per minute
+    #[serde(default = "default_rate_limit_requests_per_minute")]
+    pub requests_per_minute: u32,
+
+    /// Default number of requests allowed in a burst
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+
+    /// Limits of individual routes, overriding the default
+    #[serde(default)]
+    pub routes: Vec<RouteRateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests_per_minute: default_rate_limit_requests_per_minute(),
+            burst: default_rate_limit_burst(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+fn default_rate_limit_requests_per_minute() -> u32 {
+    60
+}
+
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+
+/// Time-based access policies.
+///
+/// Requests of the tenants and client keys a policy names, for the models it
+/// names, are only accepted within the policy's time windows. Selectors left
+/// empty match every tenant, key or model; a request matched by several
+/// policies must be within the windows of each.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessWindowsConfig {
+    /// IANA time zone of policies without their own, such as `Europe/Berlin`
+    #[serde(default = "default_access_windows_timezone")]
+    pub timezone: String,
+
+    /// Policies restricting access to time windows
+    #[serde(default)]
+    pub policies: Vec<AccessPolicy>,
+}
+
+impl Default for AccessWindowsConfig {
+    fn default() -> Self {
+        Self {
+            timezone: default_access_windows_timezone(),
+            policies: Vec::new(),
+        }
+    }
+}
+
+fn default_access_windows_timezone() -> String {
+    "UTC".to_string()
+}
+
+/// Policy limiting matching requests to time windows.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessPolicy {
+    /// Unique name of the policy, reported in denials
+    pub name: String,
+
+    /// Tenants the policy applies to (empty = every tenant)
+    #[serde(default)]
+    pub tenants: Vec<String>,
+
+    /// Ids of the client keys the policy applies to (empty = every client)
+    #[serde(default)]
+    pub keys: Vec<String>,
+
+    /// Models the policy applies to, with a trailing `*` matching any suffix
+    /// (empty = every request, including those naming no model)
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    /// IANA time zone of the windows (empty = the default time zone)
+    #[serde(default)]
+    pub timezone: String,
+
+    /// Windows in which matching requests are accepted
+    pub windows: Vec<AccessWindow>,
+}
+
+impl AccessPolicy {
+    /// Parses the time zone of the policy, falling back to the default time zone.
+    pub fn timezone(&self, default: &str) -> Result<chrono_tz::Tz, ConfigError> {
+        let name = if self.timezone.is_empty() {
+            default
+        } else {
+            self.timezone.as_str()
+        };
+        name.parse().map_err(|_| {
+            ConfigError::ValidationError(format!(
+                "Unknown time zone {} in access policy {}",
+                name, self.name
+            ))
+        })
+    }
+}
+
+/// Weekly time window, in local time of its policy.
+///
+/// A window ending before it starts runs past midnight into the next day.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccessWindow {
+    /// Days the window starts on, such as `mon` or `friday` (empty = every day)
+    #[serde(default)]
+    pub days: Vec<String>,
+
+    /// Start of the window as `HH:MM`
+    pub start: String,
+
+    /// End of the window as `HH:MM`, up to `24:00`
+    pub end: String,
+}
+
+impl AccessWindow {
+    /// Parses the days of the window.
+    pub fn weekdays(&self) -> Result<Vec<chrono::Weekday>, ConfigError> {
+        self.days
+            .iter()
+            .map(|day| {
+                day.trim().parse().map_err(|_| {
+                    ConfigError::ValidationError(format!("Invalid access window day: {}", day))
+                })
+            })
+            .collect()
+    }
+
+    /// Parses the start and end of the window as minutes after midnight.
+    pub fn minutes(&self) -> Result<(u32, u32), ConfigError> {
+        let parse = |time: &str| {
+            time.trim()
+                .split_once(':')
+                .and_then(|(hours, minutes)| {
+                    let hours: u32 = hours.parse().ok()?;
+                    let minutes: u32 = minutes.parse().ok()?;
+                    (minutes < 60 && hours * 60 + minutes <= 24 * 60)
+                        .then_some(hours * 60 + minutes)
+                })
+                .ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "Invalid access window time {}; expected HH:MM",
+                        time
+                    ))
+                })
+        };
+        let (start, end) = (parse(&self.start)?, parse(&self.end)?);
+        if start == end || start == 24 * 60 {
+            return Err(ConfigError::ValidationError(format!(
+                "Access window {}-{} is empty",
+                self.start, self.end
+            )));
+        }
+        Ok((start, end))
+    }
+}
+
+/// Generation concurrency settings.
+///
+/// Generations beyond `max_in_flight`, or beyond the limit of their model,
+/// wait for a free slot in a queue of at most `max_queued` requests; requests
+/// that find the queue full or time out are rejected with 503.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConcurrencyConfig {
+    /// Maximum number of generations in flight (0 = unlimited)
+    #[serde(default)]
+    pub max_in_flight: usize,
+
+    /// Maximum number of generations in flight for any single model
+    /// (0 = unlimited)
+    #[serde(default)]
+    pub max_in_flight_per_model: usize,
+
+    /// Limits of specific models overriding `max_in_flight_per_model`
+    #[serde(default)]
+    pub model_limits: Vec<ModelConcurrency>,
+
+    /// Maximum number of generations waiting for a free slot
+    #[serde(default = "default_concurrency_max_queued")]
+    pub max_queued: usize,
+
+    /// Longest time a generation waits for a free slot in seconds
+    #[serde(default = "default_concurrency_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 0,
+            max_in_flight_per_model: 0,
+            model_limits: Vec::new(),
+            max_queued: default_concurrency_max_queued(),
+            queue_timeout_secs: default_concurrency_queue_timeout_secs(),
+        }
+    }
+}
+
+fn default_concurrency_max_queued() -> usize {
+    32
+}
+
+fn default_concurrency_queue_timeout_secs() -> u64 {
+    30
+}
+
+/// Concurrency limit of a single model.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelConcurrency {
+    /// Model name; names without a tag also match the `:latest` tag
+    pub model: String,
+
+    /// Maximum number of the model's generations in flight (0 = unlimited)
+    pub max_in_flight: usize,
+}
+
+/// Parses a comma-separated list of `model=max_in_flight` limits, skipping
+/// invalid entries.
+fn parse_model_concurrency(value: &str) -> Vec<ModelConcurrency> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .filter_map(|(model, limit)| {
+            Some(ModelConcurrency {
+                model: model.trim().to_string(),
+                max_in_flight: limit.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Encryption at rest of the local state files.
+///
+/// State files are encrypted with the active key when at least one key is
+/// configured. All configured keys can decrypt, so a key is rotated by adding
+/// a new active key and keeping the old one until `store rekey` has run.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EncryptionConfig {
+    /// Encryption keys
+    #[serde(default)]
+    pub keys: Vec<EncryptionKey>,
+
+    /// Identifier of the key used for writing (defaults to the first key)
+    #[serde(default)]
+    pub active_key: String,
+}
+
+/// A single state file encryption key.
+///
+/// Exactly one of `key` and `key_file` must be set.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncryptionKey {
+    /// Identifier stored with every file encrypted with the key
+    pub id: String,
+
+    /// Base64 encoded 32-byte key
+    #[serde(default)]
+    pub key: String,
+
+    /// File holding the base64 encoded 32-byte key
+    #[serde(default)]
+    pub key_file: String,
+}
+
+/// A feature flag guarding a risky behavior.
+///
+/// Listed tenants always get an enabled flag; other clients get it when their
+/// stable rollout bucket falls below the rollout percentage.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeatureFlagConfig {
+    /// Name the flag is consulted by
+    pub name: String,
+
+    /// Whether the flag is enabled at all
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Percentage of clients outside the listed tenants that get the flag
+    #[serde(default = "default_rollout_percent")]
+    pub rollout_percent: u8,
+
+    /// Tenants that always get the flag
+    #[serde(default)]
+    pub tenants: Vec<String>,
+}
+
+fn default_rollout_percent() -> u8 {
+    100
+}
+
+/// Parses a comma-separated list of `name` or `name:rollout_percent` feature flags.
+fn parse_feature_flags(value: &str) -> Vec<FeatureFlagConfig> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| {
+            let (name, rollout_percent) = match entry.split_once(':') {
+                Some((name, percent)) => (name, percent.trim().parse().ok()?),
+                None => (entry.as_str(), default_rollout_percent()),
+            };
+            Some(FeatureFlagConfig {
+                name: name.trim().to_string(),
+                enabled: true,
+                rollout_percent,
+                tenants: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Branding of the content the proxy injects when it blocks a request.
+///
+/// The settings at the top level apply to all tenants; each tenant can override
+/// any of them. Translations of tenants are added to the shared translations.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BrandingConfig {
+    /// Branding shared by all tenants
+    #[serde(flatten)]
+    pub defaults: BrandingSettings,
+
+    /// Overrides keyed by tenant, as named by the metrics tenant header
+    #[serde(default)]
+    pub tenants: HashMap<String, BrandingSettings>,
+}
+
+/// Branding settings of all tenants or of a single tenant.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BrandingSettings {
+    /// Template of block messages; `{category}`, `{action}`, `{reasons}` and
+    /// `{topics}` are replaced with the details of the violation
+    #[serde(default)]
+    pub message_template: Option<String>,
+
+    /// Model name reported in the chunk that ends a blocked stream
+    #[serde(default)]
+    pub model_name: Option<String>,
+
+    /// HTML page returned instead of a blocked JSON response to clients that
+    /// prefer HTML; `{message}`, `{category}`, `{action}` and `{reasons}` are replaced
+    #[serde(default)]
+    pub block_page_html: Option<String>,
+
+    /// Replacements of violation categories, actions, reasons and topic headings
+    #[serde(default)]
+    pub translations: HashMap<String, String>,
+}
+
+/// Demo mode settings.
+///
+/// A scenario file scripts the inter-token delays of the mock Ollama backend
+/// and delays of PANW verdicts, so demos of streaming block and mask behavior
+/// play out the same way every time.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DemoConfig {
+    /// YAML scenario file; no latencies are injected when empty
+    #[serde(default)]
+    pub scenario_file: String,
+}
+
+/// Compatibility shim settings.
+///
+/// Request bodies of older clients to the generate and chat endpoints are
+/// rewritten into the current shape before they are processed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompatConfig {
+    /// Rewrite request bodies of older clients
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl Default for CompatConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Header passthrough settings.
+///
+/// The proxy builds its own requests to Ollama and its own responses to
+/// clients, so headers are only passed on when listed here.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PassthroughConfig {
+    /// Client request headers forwarded to Ollama
+    #[serde(default)]
+    pub request_headers: Vec<String>,
+
+    /// Ollama response headers returned to clients
+    #[serde(default)]
+    pub response_headers: Vec<String>,
+}
+
+/// Headers the proxy manages itself, which may not be passed through.
+const MANAGED_HEADERS: [&str; 7] = [
+    "authorization",
+    "connection",
+    "content-length",
+    "content-type",
+    "host",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Types of exported security events.
+pub const SECURITY_EVENT_TYPES: [&str; 11] = [
+    "block",
+    "mask",
+    "fail_open",
+    "scan_bypass",
+    "auth_failure",
+    "lockout",
+    "failover",
+    "policy_change",
+    "access_denied",
+    "kill_switch",
+    "impersonation",
+];
+
+/// Fields of exported security events, which can be renamed.
+pub const SECURITY_EVENT_FIELDS: [&str; 27] = [
+    "timestamp",
+    "event_type",
+    "severity",
+    "message",
+    "direction",
+    "category",
+    "action",
+    "decided_by",
+    "report_id",
+    "transaction_id",
+    "client",
+    "tenant",
+    "error",
+    "realm",
+    "ip",
+    "path",
+    "source",
+    "duration_secs",
+    "backend",
+    "in_rotation",
+    "change",
+    "target",
+    "policy",
+    "model",
+    "actor",
+    "reason",
+    "profile",
+];
+
+/// Severity of an exported security event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventSeverity {
+    /// Routine events worth recording
+    Info,
+    /// Expected events of little concern
+    Low,
+    /// Events worth a look
+    Medium,
+    /// Events needing attention
+    High,
+    /// Events needing immediate attention
+    Critical,
+}
+
+impl EventSeverity {
+    /// Returns the name used in exported events and metric labels.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for EventSeverity {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "info" => Ok(Self::Info),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            other => Err(ConfigError::ValidationError(format!(
+                "Unknown event severity: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Security event export settings.
+///
+/// Blocks, masks, authentication failures, lockouts, Ollama failovers and
+/// policy changes made through the admin API are exported as JSON events to a
+/// webhook and a syslog collector. Severities and field names can be mapped to
+/// the SIEM's schema so no parsing glue is needed downstream.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SecurityEventsConfig {
+    /// Webhook receiving every event as a JSON object (empty = disabled)
+    #[serde(default)]
+    pub webhook_url: String,
+
+    /// `host:port` of a syslog collector receiving every event over UDP
+    /// (empty = disabled)
+    #[serde(default)]
+    pub syslog_address: String,
+
+    /// Severities by event type, overriding the defaults
+    #[serde(default)]
+    pub severities: HashMap<String, EventSeverity>,
+
+    /// Names of exported fields by field, for fields the SIEM names differently
+    #[serde(default)]
+    pub field_names: HashMap<String, String>,
+
+    /// Timeouts and connection pooling of the client posting events to the
+    /// webhook; requests time out after 10 seconds unless configured otherwise
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+/// Parses a comma-separated list of `event_type=severity` mappings, skipping
+/// invalid entries.
+fn parse_event_severities(value: &str) -> HashMap<String, EventSeverity> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once('='))
+        .filter_map(|(event, severity)| Some((event.trim().to_string(), severity.parse().ok()?)))
+        .collect()
+}
+
+/// Alerts that can be notified.
+pub const NOTIFICATION_ALERTS: [&str; 6] = [
+    "scan_budget_exhausted",
+    "scheduled_prompt_failed",
+    "lockout",
+    "failover",
+    "policy_change",
+    "kill_switch",
+];
+
+/// Notification settings.
+///
+/// Operational alerts, such as an exhausted scan budget, a failed scheduled
+/// prompt, a lockout, an Ollama failover, a policy change or the kill switch,
+/// are sent to the channels of every rule matching them. The alert webhooks of the scan budget
+/// and the scheduler are notified as webhook channels of their alert.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationsConfig {
+    /// Channels alerts can be sent to
+    #[serde(default)]
+    pub channels: Vec<NotificationChannelConfig>,
+
+    /// Rules selecting the channels of alerts
+    #[serde(default)]
+    pub rules: Vec<AlertRule>,
+
+    /// Timeouts and connection pooling of the client posting to webhook and
+    /// PagerDuty channels; requests time out after 10 seconds unless configured
+    /// otherwise
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+/// Kind of a notification channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannelKind {
+    /// JSON object posted to a URL
+    Webhook,
+    /// Email sent through an SMTP server
+    Smtp,
+    /// Event triggered through the PagerDuty Events API v2
+    Pagerduty,
+}
+
+/// Channel alerts can be sent to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationChannelConfig {
+    /// Name rules refer to the channel by
+    pub name: String,
+
+    /// Kind of the channel
+    pub kind: NotificationChannelKind,
+
+    /// URL of a webhook channel, or of the PagerDuty Events API when it is not
+    /// the default
+    #[serde(default)]
+    pub url: String,
+
+    /// Integration key of a PagerDuty channel
+    #[serde(default)]
+    pub routing_key: String,
+
+    /// Server and addresses of an SMTP channel
+    #[serde(default)]
+    pub smtp: SmtpConfig,
+}
+
+/// Security of the connection to an SMTP server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Plain connection, for relays on a trusted network
+    None,
+    /// Plain connection upgraded with STARTTLS
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+}
+
+/// SMTP server and addresses of an email channel.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    /// Host name of the SMTP server
+    #[serde(default)]
+    pub host: String,
+
+    /// Port of the SMTP server
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+
+    /// Security of the connection
+    #[serde(default)]
+    pub security: SmtpSecurity,
+
+    /// PEM file of the certificate authorities trusted for the server's
+    /// certificate (empty = the system bundle)
+    #[serde(default)]
+    pub ca_file: String,
+
+    /// User name for authentication (empty = no authentication)
+    #[serde(default)]
+    pub username: String,
+
+    /// Password for authentication
+    #[serde(default)]
+    pub password: String,
+
+    /// Sender address
+    #[serde(default)]
+    pub from: String,
+
+    /// Recipient addresses
+    #[serde(default)]
+    pub to: Vec<String>,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: default_smtp_port(),
+            security: SmtpSecurity::default(),
+            ca_file: String::new(),
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            to: Vec::new(),
+        }
+    }
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// Rule sending matching alerts to channels.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRule {
+    /// Alerts the rule applies to (empty = every alert)
+    #[serde(default)]
+    pub alerts: Vec<String>,
+
+    /// Lowest severity of alerts the rule applies to
+    #[serde(default = "default_alert_rule_min_severity")]
+    pub min_severity: EventSeverity,
+
+    /// Names of the channels the alerts are sent to
+    pub channels: Vec<String>,
+}
+
+fn default_alert_rule_min_severity() -> EventSeverity {
+    EventSeverity::Info
+}
+
+/// Request and stream timeouts.
+///
+/// The request timeout bounds the time until a response starts; the stream
+/// timeout bounds the whole exchange of a streaming response.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TimeoutConfig {
+    /// Request timeout in seconds (0 = no timeout)
+    #[serde(default)]
+    pub request_timeout_secs: u64,
+
+    /// Stream timeout in seconds (0 = no timeout)
+    #[serde(default)]
+    pub stream_timeout_secs: u64,
+
+    /// Per-route overrides of the timeouts
+    #[serde(default)]
+    pub routes: Vec<RouteTimeout>,
+}
+
+/// Timeouts of a single route.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteTimeout {
+    /// Route template the timeouts apply to (e.g. `/api/pull`)
+    pub route: String,
+
+    /// Request timeout in seconds (0 = no timeout)
+    #[serde(default)]
+    pub request_timeout_secs: u64,
+
+    /// Stream timeout in seconds (0 = no timeout)
+    #[serde(default)]
+    pub stream_timeout_secs: u64,
+}
+
+/// Parses a comma-separated list of `route:request_timeout:stream_timeout` overrides.
+fn parse_route_timeouts(value: &str) -> Vec<RouteTimeout> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| {
+            let mut parts = entry.rsplitn(3, ':');
+            let stream_timeout_secs = parts.next()?.trim().parse().ok()?;
+            let request_timeout_secs = parts.next()?.trim().parse().ok()?;
+            Some(RouteTimeout {
+                route: parts.next()?.trim().to_string(),
+                request_timeout_secs,
+                stream_timeout_secs,
+            })
+        })
+        .collect()
+}
+
+/// HashiCorp Vault connection settings.
+///
+/// Settings holding secrets may reference a field of a KV version 2 secret as
+/// `vault:<path>#<field>` instead of holding the secret itself. The proxy
+/// authenticates with `token` when set and otherwise with AppRole.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultConfig {
+    /// Vault server address (e.g. `https://vault.example.com:8200`)
+    #[serde(default)]
+    pub address: String,
+
+    /// Vault Enterprise namespace (optional)
+    #[serde(default)]
+    pub namespace: String,
+
+    /// Vault token
+    #[serde(default)]
+    pub token: String,
+
+    /// AppRole role ID
+    #[serde(default)]
+    pub role_id: String,
+
+    /// AppRole secret ID
+    #[serde(default)]
+    pub secret_id: String,
+
+    /// Mount path of the AppRole auth method
+    #[serde(default = "default_vault_approle_mount")]
+    pub approle_mount: String,
+
+    /// Mount path of the KV version 2 secrets engine
+    #[serde(default = "default_vault_kv_mount")]
+    pub kv_mount: String,
+
+    /// How often referenced secrets are read again in seconds (0 = never)
+    #[serde(default = "default_vault_refresh_secs")]
+    pub refresh_secs: u64,
+
+    /// Timeouts and connection pooling of the client talking to Vault;
+    /// requests time out after 10 seconds unless configured otherwise
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+impl Default for VaultConfig {
+    fn default() -> Self {
+        Self {
+            address: String::new(),
+            namespace: String::new(),
+            token: String::new(),
+            role_id: String::new(),
+            secret_id: String::new(),
+            approle_mount: default_vault_approle_mount(),
+            kv_mount: default_vault_kv_mount(),
+            refresh_secs: default_vault_refresh_secs(),
+            http_client: HttpClientConfig::default(),
+        }
+    }
+}
+
+fn default_vault_approle_mount() -> String {
+    "approle".to_string()
+}
+
+fn default_vault_kv_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_vault_refresh_secs() -> u64 {
+    300
+}
+
+/// Parses a comma-separated list of `id:base64` encryption keys.
+fn parse_encryption_keys(value: &str) -> Vec<EncryptionKey> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(id, key)| EncryptionKey {
+            id: id.trim().to_string(),
+            key: key.trim().to_string(),
+            key_file: String::new(),
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of `route:requests_per_minute:burst` limits.
+fn parse_route_rate_limits(value: &str) -> Vec<RouteRateLimit> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| {
+            let mut parts = entry.rsplitn(3, ':');
+            let burst = parts.next()?.trim().parse().ok()?;
+            let requests_per_minute = parts.next()?.trim().parse().ok()?;
+            Some(RouteRateLimit {
+                route: parts.next()?.trim().to_string(),
+                requests_per_minute,
+                burst,
+            })
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of `id:value` client keys.
+fn parse_client_keys(value: &str, hashed: bool) -> Vec<ClientKey> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once(':'))
+        .map(|(id, value)| ClientKey {
+            id: id.trim().to_string(),
+            key: if hashed { String::new() } else { value.trim().to_string() },
+            key_sha256: if hashed { value.trim().to_string() } else { String::new() },
+            scopes: Vec::new(),
+            models: Vec::new(),
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of `id:value` pairs into the values of each id.
+fn parse_key_grants(value: &str) -> HashMap<String, Vec<String>> {
+    let mut grants: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, value) in parse_list(value)
+        .iter()
+        .filter_map(|entry| entry.split_once(':'))
+    {
+        grants
+            .entry(id.trim().to_string())
+            .or_default()
+            .push(value.trim().to_string());
+    }
+    grants
+}
+
+/// Parses a comma-separated list, skipping empty entries.
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Scheduled prompt settings.
+///
+/// Schedules defined here are loaded at startup; schedules added through the
+/// admin API are kept in memory only.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SchedulerConfig {
+    /// Webhook receiving a JSON notification whenever a scheduled run fails
+    #[serde(default)]
+    pub alert_webhook: String,
+
+    /// Prompts executed on a schedule
+    #[serde(default)]
+    pub schedules: Vec<ScheduledPrompt>,
+
+    /// Election of the replica running the schedules
+    #[serde(default)]
+    pub leader_election: LeaderElectionConfig,
+
+    /// Timeouts and connection pooling of the client delivering results to the
+    /// schedules' webhooks; requests time out after 10 seconds unless configured
+    /// otherwise
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+/// Leader election settings for replicas sharing the same schedules.
+///
+/// Replicas elect a leader through a lease file on storage they all mount, and
+/// only the leader runs scheduled prompts. When the leader stops renewing the
+/// lease, another replica takes over once the lease has expired.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LeaderElectionConfig {
+    /// Enable leader election
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Lease file on storage shared by all replicas; the lease of every term
+    /// is stored next to it as `<lease_file>.<term>`
+    #[serde(default = "default_leader_lease_file")]
+    pub lease_file: String,
+
+    /// How long a lease is valid without being renewed in seconds
+    #[serde(default = "default_leader_lease_ttl_secs")]
+    pub lease_ttl_secs: u64,
+}
+
+impl Default for LeaderElectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            lease_file: default_leader_lease_file(),
+            lease_ttl_secs: default_leader_lease_ttl_secs(),
+        }
+    }
+}
+
+fn default_leader_lease_file() -> String {
+    "scheduler-leader.json".to_string()
+}
+
+fn default_leader_lease_ttl_secs() -> u64 {
+    30
+}
+
+/// Reads a boolean flag from an environment variable.
+///
+/// Accepts "true"/"false", "1"/"0", "yes"/"no" and "on"/"off" (case-insensitive).
+fn env_flag(name: &str) -> Option<bool> {
+    let value = env::var(name).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Loads configuration from environment variables.
+///
+/// This function reads configuration values from environment variables,
+/// falling back to default values where appropriate.
+///
+/// # Returns
+///
+/// * `Config` - Configuration object populated from environment variables
+fn load_from_env() -> Config {
+    info!("Loading configuration from environment variables");
+
+    let server = ServerConfig {
+        host: env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string()),
+        port: env::var("SERVER_PORT")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(11435),
+        debug_level: env::var("SERVER_DEBUG_LEVEL").unwrap_or_else(|_| "INFO".to_string()),
+        tls_cert_file: env::var("SERVER_TLS_CERT_FILE").ok(),
+        tls_key_file: env::var("SERVER_TLS_KEY_FILE").ok(),
+        tls_reload_interval_secs: env::var("SERVER_TLS_RELOAD_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(default_tls_reload_interval_secs),
+        tls_client_ca_file: env::var("SERVER_TLS_CLIENT_CA_FILE").ok(),
+    };
+
+    let ollama = OllamaConfig {
+        base_url: env::var("OLLAMA_BASE_URL").unwrap_or_else(|_| default_ollama_base_url()),
+        backends: env::var("OLLAMA_BACKENDS")
+            .map(|b| parse_list(&b))
+            .unwrap_or_default(),
+        probe_interval_secs: env::var("OLLAMA_PROBE_INTERVAL_SECS")
+            .ok()
+            .and_then(|i| i.parse().ok())
+            .unwrap_or_else(default_ollama_probe_interval_secs),
+        version_check_interval_secs: env::var("OLLAMA_VERSION_CHECK_INTERVAL_SECS")
+            .ok()
+            .and_then(|i| i.parse().ok())
+            .unwrap_or_else(default_ollama_version_check_interval_secs),
+        model_routes: env::var("OLLAMA_MODEL_ROUTES")
+            .map(|r| parse_model_routes(&r))
+            .unwrap_or_default(),
+        headers: env::var("OLLAMA_HEADERS")
+            .map(|h| parse_headers(&h))
+            .unwrap_or_default(),
+        bearer_token: env::var("OLLAMA_BEARER_TOKEN").unwrap_or_default(),
+        basic_auth_user: env::var("OLLAMA_BASIC_AUTH_USER").unwrap_or_default(),
+        basic_auth_password: env::var("OLLAMA_BASIC_AUTH_PASSWORD").unwrap_or_default(),
+        retry: OllamaRetryConfig {
+            max_retries: env::var("OLLAMA_RETRY_MAX_RETRIES")
+                .ok()
+                .and_then(|r| r.parse().ok())
+                .unwrap_or_else(default_ollama_retry_max_retries),
+            initial_backoff_ms: env::var("OLLAMA_RETRY_INITIAL_BACKOFF_MS")
+                .ok()
+                .and_then(|b| b.parse().ok())
+                .unwrap_or_else(default_ollama_retry_initial_backoff_ms),
+            max_backoff_ms: env::var("OLLAMA_RETRY_MAX_BACKOFF_MS")
+                .ok()
+                .and_then(|b| b.parse().ok())
+                .unwrap_or_else(default_ollama_retry_max_backoff_ms),
+            retry_on_status: env::var("OLLAMA_RETRY_ON_STATUS")
+                .map(|s| parse_statuses(&s))
+                .unwrap_or_else(|_| default_ollama_retry_on_status()),
+        },
+        http_client: HttpClientConfig {
+            connect_timeout_secs: env::var("OLLAMA_HTTP_CONNECT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_connect_timeout_secs),
+            request_timeout_secs: env::var("OLLAMA_HTTP_REQUEST_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok()),
+            pool_idle_timeout_secs: env::var("OLLAMA_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_pool_idle_timeout_secs),
+            pool_max_idle_per_host: env::var("OLLAMA_HTTP_POOL_MAX_IDLE_PER_HOST")
+                .ok()
+                .and_then(|m| m.parse().ok()),
+            tcp_keepalive_secs: env::var("OLLAMA_HTTP_TCP_KEEPALIVE_SECS")
+                .ok()
+                .and_then(|k| k.parse().ok())
+                .unwrap_or_else(default_http_tcp_keepalive_secs),
+        },
+        provider: env::var("OLLAMA_PROVIDER")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or_default(),
+        mock: MockOllamaConfig {
+            seed: env::var("OLLAMA_MOCK_SEED")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default(),
+            tokens_per_second: env::var("OLLAMA_MOCK_TOKENS_PER_SECOND")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_mock_tokens_per_second),
+            response_tokens: env::var("OLLAMA_MOCK_RESPONSE_TOKENS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_mock_response_tokens),
+            models: env::var("OLLAMA_MOCK_MODELS")
+                .map(|m| parse_list(&m))
+                .unwrap_or_else(|_| default_mock_models()),
+            triggers: Vec::new(),
+        },
+    };
+
+    let security = SecurityConfig {
+        base_url: env::var("SECURITY_BASE_URL")
+            .unwrap_or_else(|_| "https://service.api.aisecurity.paloaltonetworks.com".to_string()),
+        api_key: env::var("SECURITY_API_KEY").unwrap_or_default(),
+        profile_name: env::var("SECURITY_PROFILE_NAME").unwrap_or_default(),
+        profile_header: env::var("SECURITY_PROFILE_HEADER").unwrap_or_default(),
+        allowed_profiles: env::var("SECURITY_ALLOWED_PROFILES")
+            .map(|p| parse_list(&p))
+            .unwrap_or_default(),
+        model_profiles: env::var("SECURITY_MODEL_PROFILES")
+            .map(|p| parse_model_profiles(&p))
+            .unwrap_or_default(),
+        app_name: env::var("SECURITY_APP_NAME").unwrap_or_else(|_| "panw-api-ollama".to_string()),
+        app_user: env::var("SECURITY_APP_USER").unwrap_or_else(|_| "default".to_string()),
+        contextual_grounding: env::var("SECURITY_CONTEXTUAL_GROUNDING_CONTEXT").unwrap_or_default(),
+        rate_limit_max_wait_secs: env::var("SECURITY_RATE_LIMIT_MAX_WAIT_SECS")
+            .ok()
+            .and_then(|w| w.parse().ok())
+            .unwrap_or_else(default_rate_limit_max_wait_secs),
+        budget: ScanBudgetConfig {
+            monthly_cap: env::var("SECURITY_BUDGET_MONTHLY_CAP")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or(0),
+            degraded_mode: env::var("SECURITY_BUDGET_DEGRADED_MODE")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_default(),
+            alert_webhook: env::var("SECURITY_BUDGET_ALERT_WEBHOOK").unwrap_or_default(),
+            state_file: env::var("SECURITY_BUDGET_STATE_FILE")
+                .unwrap_or_else(|_| default_budget_state_file()),
+        },
+        http_client: HttpClientConfig {
+            connect_timeout_secs: env::var("SECURITY_HTTP_CONNECT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_connect_timeout_secs),
+            request_timeout_secs: env::var("SECURITY_HTTP_REQUEST_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok()),
+            pool_idle_timeout_secs: env::var("SECURITY_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_pool_idle_timeout_secs),
+            pool_max_idle_per_host: env::var("SECURITY_HTTP_POOL_MAX_IDLE_PER_HOST")
+                .ok()
+                .and_then(|m| m.parse().ok()),
+            tcp_keepalive_secs: env::var("SECURITY_HTTP_TCP_KEEPALIVE_SECS")
+                .ok()
+                .and_then(|k| k.parse().ok())
+                .unwrap_or_else(default_http_tcp_keepalive_secs),
+        },
+        secrets: SecretScanConfig {
+            enabled: env_flag("SECURITY_SECRETS_ENABLED").unwrap_or(false),
+            action: env::var("SECURITY_SECRETS_ACTION")
+                .ok()
+                .and_then(|a| a.parse().ok())
+                .unwrap_or_default(),
+        },
+        language: LanguageConfig {
+            enabled: env_flag("SECURITY_LANGUAGE_ENABLED").unwrap_or(false),
+            allowed_languages: env::var("SECURITY_LANGUAGE_ALLOWED")
+                .map(|languages| parse_list(&languages))
+                .unwrap_or_default(),
+            action: env::var("SECURITY_LANGUAGE_ACTION")
+                .ok()
+                .and_then(|a| a.parse().ok())
+                .unwrap_or_default(),
+            min_confidence: env::var("SECURITY_LANGUAGE_MIN_CONFIDENCE")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_language_min_confidence),
+        },
+        toxicity: ToxicityConfig {
+            enabled: env_flag("SECURITY_TOXICITY_ENABLED").unwrap_or(false),
+            action: env::var("SECURITY_TOXICITY_ACTION")
+                .ok()
+                .and_then(|a| a.parse().ok())
+                .unwrap_or_default(),
+            min_severity: env::var("SECURITY_TOXICITY_MIN_SEVERITY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default(),
+            terms: Vec::new(),
+            tenants: Default::default(),
+        },
+        prefilter_rules: PrefilterRulesConfig::default(),
+        pii: PiiConfig {
+            enabled: env_flag("SECURITY_PII_ENABLED").unwrap_or(false),
+            entities: env::var("SECURITY_PII_ENTITIES")
+                .map(|entities| parse_pii_entities(&entities))
+                .unwrap_or_else(|_| default_pii_entities()),
+        },
+        decode_payloads: DecodePayloadsConfig {
+            enabled: env_flag("SECURITY_DECODE_PAYLOADS_ENABLED").unwrap_or(false),
+            min_length: env::var("SECURITY_DECODE_PAYLOADS_MIN_LENGTH")
+                .ok()
+                .and_then(|l| l.parse().ok())
+                .unwrap_or_else(default_decode_payloads_min_length),
+        },
+        scan_cache: ScanCacheConfig {
+            enabled: env_flag("SECURITY_SCAN_CACHE_ENABLED").unwrap_or(false),
+            max_entries: env::var("SECURITY_SCAN_CACHE_MAX_ENTRIES")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_scan_cache_max_entries),
+            ttl_secs: env::var("SECURITY_SCAN_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_scan_cache_ttl_secs),
+        },
+        async_streaming: AsyncStreamingConfig {
+            enabled: env_flag("SECURITY_ASYNC_STREAMING_ENABLED").unwrap_or(false),
+            risk_window_chars: env::var("SECURITY_ASYNC_STREAMING_RISK_WINDOW_CHARS")
+                .ok()
+                .and_then(|w| w.parse().ok())
+                .unwrap_or_else(default_async_risk_window_chars),
+            poll_interval_ms: env::var("SECURITY_ASYNC_STREAMING_POLL_INTERVAL_MS")
+                .ok()
+                .and_then(|i| i.parse().ok())
+                .unwrap_or_else(default_async_poll_interval_ms),
+            settle_timeout_secs: env::var("SECURITY_ASYNC_STREAMING_SETTLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_async_settle_timeout_secs),
+        },
+        stream_summary: env_flag("SECURITY_STREAM_SUMMARY").unwrap_or(false),
+        response_capture: ResponseCaptureConfig {
+            enabled: env_flag("SECURITY_RESPONSE_CAPTURE_ENABLED").unwrap_or(false),
+            capacity: env::var("SECURITY_RESPONSE_CAPTURE_CAPACITY")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_capture_capacity),
+            max_per_minute: env::var("SECURITY_RESPONSE_CAPTURE_MAX_PER_MINUTE")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_capture_max_per_minute),
+            sample_percent: env::var("SECURITY_RESPONSE_CAPTURE_SAMPLE_PERCENT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or_default(),
+            max_body_bytes: env::var("SECURITY_RESPONSE_CAPTURE_MAX_BODY_BYTES")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_capture_max_body_bytes),
+        },
+        circuit_breaker: CircuitBreakerConfig {
+            enabled: env_flag("SECURITY_CIRCUIT_BREAKER_ENABLED").unwrap_or(false),
+            failure_threshold: env::var("SECURITY_CIRCUIT_BREAKER_FAILURE_THRESHOLD")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_breaker_failure_threshold),
+            cooldown_secs: env::var("SECURITY_CIRCUIT_BREAKER_COOLDOWN_SECS")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_breaker_cooldown_secs),
+            open_mode: env::var("SECURITY_CIRCUIT_BREAKER_OPEN_MODE")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_default(),
+        },
+        failure_mode: env::var("SECURITY_FAILURE_MODE")
+            .ok()
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default(),
+        scan_bypass: ScanBypassConfig {
+            keys: env::var("SECURITY_SCAN_BYPASS_KEYS")
+                .map(|k| parse_list(&k))
+                .unwrap_or_default(),
+            networks: env::var("SECURITY_SCAN_BYPASS_NETWORKS")
+                .map(|n| parse_list(&n))
+                .unwrap_or_default(),
+        },
+        provider: env::var("SECURITY_PROVIDER")
+            .ok()
+            .and_then(|p| p.parse().ok())
+            .unwrap_or_default(),
+        fan_out: FanOutConfig {
+            providers: env::var("SECURITY_FAN_OUT_PROVIDERS")
+                .map(|p| parse_providers(&p))
+                .unwrap_or_default(),
+            policy: env::var("SECURITY_FAN_OUT_POLICY")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or_default(),
+            weights: env::var("SECURITY_FAN_OUT_WEIGHTS")
+                .map(|w| parse_provider_weights(&w))
+                .unwrap_or_default(),
+            threshold: env::var("SECURITY_FAN_OUT_THRESHOLD")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_fan_out_threshold),
+        },
+        openai_moderation: OpenAiModerationConfig {
+            base_url: env::var("OPENAI_MODERATION_BASE_URL")
+                .unwrap_or_else(|_| default_openai_moderation_base_url()),
+            api_key: env::var("OPENAI_MODERATION_API_KEY").unwrap_or_default(),
+            model: env::var("OPENAI_MODERATION_MODEL")
+                .unwrap_or_else(|_| default_openai_moderation_model()),
+            block_categories: env::var("OPENAI_MODERATION_BLOCK_CATEGORIES")
+                .map(|c| parse_list(&c))
+                .unwrap_or_default(),
+        },
+        bedrock_guardrails: BedrockGuardrailsConfig {
+            region: env_aws_region().unwrap_or_default(),
+            guardrail_id: env::var("BEDROCK_GUARDRAILS_ID").unwrap_or_default(),
+            guardrail_version: env::var("BEDROCK_GUARDRAILS_VERSION")
+                .unwrap_or_else(|_| default_bedrock_guardrail_version()),
+            endpoint: env::var("BEDROCK_GUARDRAILS_ENDPOINT").unwrap_or_default(),
+            access_key_id: env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+            session_token: env::var("AWS_SESSION_TOKEN").unwrap_or_default(),
+        },
+        azure_content_safety: AzureContentSafetyConfig {
+            endpoint: env::var("AZURE_CONTENT_SAFETY_ENDPOINT").unwrap_or_default(),
+            api_key: env::var("AZURE_CONTENT_SAFETY_API_KEY").unwrap_or_default(),
+            api_version: env::var("AZURE_CONTENT_SAFETY_API_VERSION")
+                .unwrap_or_else(|_| default_azure_content_safety_api_version()),
+            severity_threshold: env::var("AZURE_CONTENT_SAFETY_SEVERITY_THRESHOLD")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_azure_content_safety_severity_threshold),
+            category_thresholds: env::var("AZURE_CONTENT_SAFETY_CATEGORY_THRESHOLDS")
+                .map(|t| parse_category_thresholds(&t))
+                .unwrap_or_default(),
+            blocklists: env::var("AZURE_CONTENT_SAFETY_BLOCKLISTS")
+                .map(|b| parse_list(&b))
+                .unwrap_or_default(),
+        },
+        llama_guard: LlamaGuardConfig {
+            model: env::var("LLAMA_GUARD_MODEL").unwrap_or_else(|_| default_llama_guard_model()),
+            block_categories: env::var("LLAMA_GUARD_BLOCK_CATEGORIES")
+                .map(|c| parse_list(&c))
+                .unwrap_or_default(),
+        },
+    };
+
+    let models = ModelsConfig {
+        show_filter: ShowFilterConfig {
+            scan: env_flag("MODELS_SHOW_SCAN").unwrap_or(false),
+            strip: env_flag("MODELS_SHOW_STRIP").unwrap_or(false),
+        },
+        confirm_destructive: env_flag("MODELS_CONFIRM_DESTRUCTIVE").unwrap_or(true),
+        confirmation_ttl_secs: env::var("MODELS_CONFIRMATION_TTL_SECS")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or_else(default_confirmation_ttl_secs),
+        streaming: env::var("MODELS_STREAMING_STRATEGIES")
+            .map(|s| parse_model_streaming(&s))
+            .unwrap_or_default(),
+    };
+
+    let admin = AdminConfig {
+        token: env::var("ADMIN_TOKEN").unwrap_or_default(),
+    };
+
+    let maintenance = MaintenanceConfig {
+        mode: env::var("MAINTENANCE_MODE")
+            .ok()
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default(),
+        message: env::var("MAINTENANCE_MESSAGE").unwrap_or_default(),
+        windows: Vec::new(),
+    };
+
+    let kill_switch = KillSwitchConfig {
+        message: env::var("KILL_SWITCH_MESSAGE").unwrap_or_default(),
+        state_file: env::var("KILL_SWITCH_STATE_FILE")
+            .unwrap_or_else(|_| default_kill_switch_state_file()),
+    };
+
+    let dedup = DedupConfig {
+        enabled: env_flag("DEDUP_ENABLED").unwrap_or(false),
+        window_secs: env::var("DEDUP_WINDOW_SECS")
+            .ok()
+            .and_then(|w| w.parse().ok())
+            .unwrap_or_else(default_dedup_window_secs),
+    };
+
+    let idempotency = IdempotencyConfig {
+        enabled: env_flag("IDEMPOTENCY_ENABLED").unwrap_or(true),
+        ttl_secs: env::var("IDEMPOTENCY_TTL_SECS")
+            .ok()
+            .and_then(|t| t.parse().ok())
+            .unwrap_or_else(default_idempotency_ttl_secs),
+    };
+
+    let provenance = ProvenanceConfig {
+        enabled: env_flag("PROVENANCE_ENABLED").unwrap_or(false),
+        signing_key_file: env::var("PROVENANCE_SIGNING_KEY_FILE").unwrap_or_default(),
+        retention_secs: env::var("PROVENANCE_RETENTION_SECS")
+            .ok()
+            .and_then(|r| r.parse().ok())
+            .unwrap_or_else(default_provenance_retention_secs),
+    };
+
+    let transcripts = TranscriptsConfig {
+        enabled: env_flag("TRANSCRIPTS_ENABLED").unwrap_or(false),
+        file: env::var("TRANSCRIPTS_FILE").unwrap_or_else(|_| default_transcripts_file()),
+        session_header: env::var("TRANSCRIPTS_SESSION_HEADER")
+            .unwrap_or_else(|_| default_transcripts_session_header()),
+        retention_days: env::var("TRANSCRIPTS_RETENTION_DAYS")
+            .ok()
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(0),
+        hmac_key: env::var("TRANSCRIPTS_HMAC_KEY").unwrap_or_default(),
+    };
+
+    let postprocess = PostProcessConfig {
+        filters: env::var("POSTPROCESS_FILTERS")
+            .map(|f| parse_filters(&f))
+            .unwrap_or_default(),
+        url_mode: env::var("POSTPROCESS_URL_MODE")
+            .ok()
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default(),
+        url_redirect_template: env::var("POSTPROCESS_URL_REDIRECT_TEMPLATE").unwrap_or_default(),
+        max_length: env::var("POSTPROCESS_MAX_LENGTH")
+            .ok()
+            .and_then(|l| l.parse().ok())
+            .unwrap_or(0),
+        truncation_notice: env::var("POSTPROCESS_TRUNCATION_NOTICE")
+            .unwrap_or_else(|_| default_truncation_notice()),
+        leakage_patterns: env::var("POSTPROCESS_LEAKAGE_PATTERNS")
+            .map(|p| parse_list(&p))
+            .unwrap_or_default(),
+    };
+
+    Config {
+        server,
+        ollama,
+        security,
+        models,
+        admin,
+        maintenance,
+        kill_switch,
+        dedup,
+        idempotency,
+        provenance,
+        transcripts,
+        postprocess,
+        summarize: SummarizeConfig {
+            model: env::var("SUMMARIZE_MODEL").unwrap_or_default(),
+            prompt: env::var("SUMMARIZE_PROMPT").unwrap_or_else(|_| default_summarize_prompt()),
+        },
+        batch: BatchConfig {
+            max_prompts: env::var("BATCH_MAX_PROMPTS")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_batch_max_prompts),
+            concurrency: env::var("BATCH_CONCURRENCY")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_batch_concurrency),
+        },
+        jobs: JobsConfig {
+            ttl_secs: env::var("JOBS_TTL_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_jobs_ttl_secs),
+            max_per_client: env::var("JOBS_MAX_PER_CLIENT")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_jobs_max_per_client),
+        },
+        scheduler: SchedulerConfig {
+            alert_webhook: env::var("SCHEDULER_ALERT_WEBHOOK").unwrap_or_default(),
+            schedules: Vec::new(),
+            leader_election: LeaderElectionConfig {
+                enabled: env_flag("SCHEDULER_LEADER_ELECTION_ENABLED").unwrap_or(false),
+                lease_file: env::var("SCHEDULER_LEADER_LEASE_FILE")
+                    .unwrap_or_else(|_| default_leader_lease_file()),
+                lease_ttl_secs: env::var("SCHEDULER_LEADER_LEASE_TTL_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_leader_lease_ttl_secs),
+            },
+        http_client: HttpClientConfig {
+            connect_timeout_secs: env::var("SCHEDULER_HTTP_CONNECT_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_connect_timeout_secs),
+            request_timeout_secs: env::var("SCHEDULER_HTTP_REQUEST_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok()),
+            pool_idle_timeout_secs: env::var("SCHEDULER_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_http_pool_idle_timeout_secs),
+            pool_max_idle_per_host: env::var("SCHEDULER_HTTP_POOL_MAX_IDLE_PER_HOST")
+                .ok()
+                .and_then(|m| m.parse().ok()),
+            tcp_keepalive_secs: env::var("SCHEDULER_HTTP_TCP_KEEPALIVE_SECS")
+                .ok()
+                .and_then(|k| k.parse().ok())
+                .unwrap_or_else(default_http_tcp_keepalive_secs),
+        },
+        },
+        templates: TemplatesConfig {
+            state_file: env::var("TEMPLATES_STATE_FILE")
+                .unwrap_or_else(|_| default_templates_state_file()),
+        },
+        health: HealthConfig {
+            timeout_secs: env::var("HEALTH_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_health_timeout_secs),
+            cache_secs: env::var("HEALTH_CACHE_SECS")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_health_cache_secs),
+            probe_interval_secs: env::var("HEALTH_PROBE_INTERVAL_SECS")
+                .ok()
+                .and_then(|i| i.parse().ok())
+                .unwrap_or_else(default_health_probe_interval_secs),
+            shutdown_delay_secs: env::var("HEALTH_SHUTDOWN_DELAY_SECS")
+                .ok()
+                .and_then(|d| d.parse().ok())
+                .unwrap_or_else(default_health_shutdown_delay_secs),
+        },
+        metrics: MetricsConfig {
+            tenant_header: env::var("METRICS_TENANT_HEADER")
+                .unwrap_or_else(|_| default_metrics_tenant_header()),
+            max_tenants: env::var("METRICS_MAX_TENANTS")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_metrics_max_label_values),
+            max_keys: env::var("METRICS_MAX_KEYS")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_metrics_max_label_values),
+        },
+        context: ContextConfig {
+            enabled: env_flag("CONTEXT_CHECK_ENABLED").unwrap_or(true),
+            chars_per_token: env::var("CONTEXT_CHARS_PER_TOKEN")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_else(default_context_chars_per_token),
+        },
+        capabilities: CapabilitiesConfig {
+            cache_ttl_secs: env::var("CAPABILITIES_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_capabilities_cache_ttl_secs),
+        },
+        auto_pull: AutoPullConfig {
+            enabled: env_flag("AUTO_PULL_ENABLED").unwrap_or(false),
+            allowed_models: env::var("AUTO_PULL_ALLOWED_MODELS")
+                .map(|m| parse_list(&m))
+                .unwrap_or_default(),
+            retry_after_secs: env::var("AUTO_PULL_RETRY_AFTER_SECS")
+                .ok()
+                .and_then(|r| r.parse().ok())
+                .unwrap_or_else(default_auto_pull_retry_after_secs),
+        },
+        client_auth: ClientAuthConfig {
+            enabled: env_flag("CLIENT_AUTH_ENABLED").unwrap_or(false),
+            keys: client_keys_from_env().unwrap_or_default(),
+            key_state_file: env::var("CLIENT_AUTH_KEY_STATE_FILE")
+                .unwrap_or_else(|_| default_client_auth_key_state_file()),
+            jwt: JwtConfig {
+                enabled: env_flag("CLIENT_AUTH_JWT_ENABLED").unwrap_or(false),
+                issuer: env::var("CLIENT_AUTH_JWT_ISSUER").unwrap_or_default(),
+                audience: env::var("CLIENT_AUTH_JWT_AUDIENCE").unwrap_or_default(),
+                jwks_url: env::var("CLIENT_AUTH_JWT_JWKS_URL").unwrap_or_default(),
+                jwks_refresh_secs: env::var("CLIENT_AUTH_JWT_JWKS_REFRESH_SECS")
+                    .ok()
+                    .and_then(|r| r.parse().ok())
+                    .unwrap_or_else(default_jwt_jwks_refresh_secs),
+                subject_claim: env::var("CLIENT_AUTH_JWT_SUBJECT_CLAIM")
+                    .unwrap_or_else(|_| default_jwt_subject_claim()),
+                groups_claim: env::var("CLIENT_AUTH_JWT_GROUPS_CLAIM")
+                    .unwrap_or_else(|_| default_jwt_groups_claim()),
+                group_profiles: env::var("CLIENT_AUTH_JWT_GROUP_PROFILES")
+                    .map(|p| parse_group_profiles(&p))
+                    .unwrap_or_default(),
+                http_client: HttpClientConfig {
+                    connect_timeout_secs: env::var("CLIENT_AUTH_JWT_HTTP_CONNECT_TIMEOUT_SECS")
+                        .ok()
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or_else(default_http_connect_timeout_secs),
+                    request_timeout_secs: env::var("CLIENT_AUTH_JWT_HTTP_REQUEST_TIMEOUT_SECS")
+                        .ok()
+                        .and_then(|t| t.parse().ok()),
+                    pool_idle_timeout_secs: env::var("CLIENT_AUTH_JWT_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                        .ok()
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or_else(default_http_pool_idle_timeout_secs),
+                    pool_max_idle_per_host: env::var("CLIENT_AUTH_JWT_HTTP_POOL_MAX_IDLE_PER_HOST")
+                        .ok()
+                        .and_then(|m| m.parse().ok()),
+                    tcp_keepalive_secs: env::var("CLIENT_AUTH_JWT_HTTP_TCP_KEEPALIVE_SECS")
+                        .ok()
+                        .and_then(|k| k.parse().ok())
+                        .unwrap_or_else(default_http_tcp_keepalive_secs),
+                },
+            },
+            app_user_header: AppUserHeaderConfig {
+                enabled: env_flag("CLIENT_AUTH_APP_USER_HEADER_ENABLED").unwrap_or(false),
+                name: env::var("CLIENT_AUTH_APP_USER_HEADER_NAME")
+                    .unwrap_or_else(|_| default_app_user_header_name()),
+                trusted_clients: env::var("CLIENT_AUTH_APP_USER_HEADER_TRUSTED_CLIENTS")
+                    .map(|c| parse_list(&c))
+                    .unwrap_or_default(),
+            },
+            basic_auth: env_flag("CLIENT_AUTH_BASIC_ENABLED").unwrap_or(false),
+            trusted_proxy: TrustedProxyConfig {
+                enabled: env_flag("CLIENT_AUTH_TRUSTED_PROXY_ENABLED").unwrap_or(false),
+                header: env::var("CLIENT_AUTH_TRUSTED_PROXY_HEADER")
+                    .unwrap_or_else(|_| default_trusted_proxy_header()),
+                addresses: env::var("CLIENT_AUTH_TRUSTED_PROXY_ADDRESSES")
+                    .map(|a| parse_list(&a))
+                    .unwrap_or_default(),
+            },
+            access_tokens: AccessTokenConfig {
+                enabled: env_flag("CLIENT_AUTH_ACCESS_TOKENS_ENABLED").unwrap_or(false),
+                ttl_secs: env::var("CLIENT_AUTH_ACCESS_TOKEN_TTL_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_access_token_ttl_secs),
+                signing_secret: env::var("CLIENT_AUTH_ACCESS_TOKEN_SIGNING_SECRET")
+                    .unwrap_or_default(),
+            },
+            lockout: AuthLockoutConfig {
+                enabled: env_flag("CLIENT_AUTH_LOCKOUT_ENABLED").unwrap_or(false),
+                max_failures: env::var("CLIENT_AUTH_LOCKOUT_MAX_FAILURES")
+                    .ok()
+                    .and_then(|m| m.parse().ok())
+                    .unwrap_or_else(default_auth_lockout_max_failures),
+                window_secs: env::var("CLIENT_AUTH_LOCKOUT_WINDOW_SECS")
+                    .ok()
+                    .and_then(|w| w.parse().ok())
+                    .unwrap_or_else(default_auth_lockout_window_secs),
+                base_lockout_secs: env::var("CLIENT_AUTH_LOCKOUT_BASE_SECS")
+                    .ok()
+                    .and_then(|b| b.parse().ok())
+                    .unwrap_or_else(default_auth_lockout_base_secs),
+                max_lockout_secs: env::var("CLIENT_AUTH_LOCKOUT_MAX_SECS")
+                    .ok()
+                    .and_then(|m| m.parse().ok())
+                    .unwrap_or_else(default_auth_lockout_max_secs),
+            },
+        },
+        usage: UsageConfig {
+            report_min_count: env::var("USAGE_REPORT_MIN_COUNT")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .unwrap_or_default(),
+            report_noise_epsilon: env::var("USAGE_REPORT_NOISE_EPSILON")
+                .ok()
+                .and_then(|e| e.parse().ok())
+                .unwrap_or_default(),
+        },
+        rate_limit: RateLimitConfig {
+            enabled: env_flag("RATE_LIMIT_ENABLED").unwrap_or(false),
+            requests_per_minute: env::var("RATE_LIMIT_REQUESTS_PER_MINUTE")
+                .ok()
+                .and_then(|r| r.parse().ok())
+                .unwrap_or_else(default_rate_limit_requests_per_minute),
+            burst: env::var("RATE_LIMIT_BURST")
+                .ok()
+                .and_then(|b| b.parse().ok())
+                .unwrap_or_else(default_rate_limit_burst),
+            routes: env::var("RATE_LIMIT_ROUTES")
+                .map(|r| parse_route_rate_limits(&r))
+                .unwrap_or_default(),
+        },
+        access_windows: AccessWindowsConfig::default(),
+        concurrency: ConcurrencyConfig {
+            max_in_flight: env::var("CONCURRENCY_MAX_IN_FLIGHT")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_default(),
+            max_in_flight_per_model: env::var("CONCURRENCY_MAX_IN_FLIGHT_PER_MODEL")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_default(),
+            model_limits: env::var("CONCURRENCY_MODEL_LIMITS")
+                .map(|l| parse_model_concurrency(&l))
+                .unwrap_or_default(),
+            max_queued: env::var("CONCURRENCY_MAX_QUEUED")
+                .ok()
+                .and_then(|m| m.parse().ok())
+                .unwrap_or_else(default_concurrency_max_queued),
+            queue_timeout_secs: env::var("CONCURRENCY_QUEUE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_else(default_concurrency_queue_timeout_secs),
+        },
+        encryption: EncryptionConfig {
+            keys: env::var("STORAGE_ENCRYPTION_KEYS")
+                .map(|k| parse_encryption_keys(&k))
+                .unwrap_or_default(),
+            active_key: env::var("STORAGE_ENCRYPTION_ACTIVE_KEY").unwrap_or_default(),
+        },
+        vault: VaultConfig {
+            address: env::var("VAULT_ADDR").unwrap_or_default(),
+            namespace: env::var("VAULT_NAMESPACE").unwrap_or_default(),
+            token: env::var("VAULT_TOKEN").unwrap_or_default(),
+            role_id: env::var("VAULT_ROLE_ID").unwrap_or_default(),
+            secret_id: env::var("VAULT_SECRET_ID").unwrap_or_default(),
+            approle_mount: env::var("VAULT_APPROLE_MOUNT")
+                .unwrap_or_else(|_| default_vault_approle_mount()),
+            kv_mount: env::var("VAULT_KV_MOUNT").unwrap_or_else(|_| default_vault_kv_mount()),
+            refresh_secs: env::var("VAULT_REFRESH_SECS")
+                .ok()
+                .and_then(|r| r.parse().ok())
+                .unwrap_or_else(default_vault_refresh_secs),
+            http_client: HttpClientConfig {
+                connect_timeout_secs: env::var("VAULT_HTTP_CONNECT_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_connect_timeout_secs),
+                request_timeout_secs: env::var("VAULT_HTTP_REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok()),
+                pool_idle_timeout_secs: env::var("VAULT_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_pool_idle_timeout_secs),
+                pool_max_idle_per_host: env::var("VAULT_HTTP_POOL_MAX_IDLE_PER_HOST")
+                    .ok()
+                    .and_then(|m| m.parse().ok()),
+                tcp_keepalive_secs: env::var("VAULT_HTTP_TCP_KEEPALIVE_SECS")
+                    .ok()
+                    .and_then(|k| k.parse().ok())
+                    .unwrap_or_else(default_http_tcp_keepalive_secs),
+            },
+        },
+        timeouts: TimeoutConfig {
+            request_timeout_secs: env::var("TIMEOUT_REQUEST_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+            stream_timeout_secs: env::var("TIMEOUT_STREAM_SECS")
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+            routes: env::var("TIMEOUT_ROUTES")
+                .map(|r| parse_route_timeouts(&r))
+                .unwrap_or_default(),
+        },
+        feature_flags: env::var("FEATURE_FLAGS")
+            .map(|f| parse_feature_flags(&f))
+            .unwrap_or_default(),
+        branding: BrandingConfig {
+            defaults: BrandingSettings {
+                message_template: env::var("BRANDING_MESSAGE_TEMPLATE").ok(),
+                model_name: env::var("BRANDING_MODEL_NAME").ok(),
+                block_page_html: None,
+                translations: HashMap::new(),
+            },
+            tenants: HashMap::new(),
+        },
+        demo: DemoConfig {
+            scenario_file: env::var("DEMO_SCENARIO_FILE").unwrap_or_default(),
+        },
+        compat: CompatConfig {
+            enabled: env_flag("COMPAT_ENABLED").unwrap_or(true),
+        },
+        passthrough: PassthroughConfig {
+            request_headers: env::var("PASSTHROUGH_REQUEST_HEADERS")
+                .map(|h| parse_list(&h))
+                .unwrap_or_default(),
+            response_headers: env::var("PASSTHROUGH_RESPONSE_HEADERS")
+                .map(|h| parse_list(&h))
+                .unwrap_or_default(),
+        },
+        security_events: SecurityEventsConfig {
+            webhook_url: env::var("SECURITY_EVENTS_WEBHOOK_URL").unwrap_or_default(),
+            syslog_address: env::var("SECURITY_EVENTS_SYSLOG_ADDRESS").unwrap_or_default(),
+            severities: env::var("SECURITY_EVENTS_SEVERITIES")
+                .map(|s| parse_event_severities(&s))
+                .unwrap_or_default(),
+            field_names: env::var("SECURITY_EVENTS_FIELD_NAMES")
+                .map(|f| parse_headers(&f))
+                .unwrap_or_default(),
+            http_client: HttpClientConfig {
+                connect_timeout_secs: env::var("SECURITY_EVENTS_HTTP_CONNECT_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_connect_timeout_secs),
+                request_timeout_secs: env::var("SECURITY_EVENTS_HTTP_REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok()),
+                pool_idle_timeout_secs: env::var("SECURITY_EVENTS_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_pool_idle_timeout_secs),
+                pool_max_idle_per_host: env::var("SECURITY_EVENTS_HTTP_POOL_MAX_IDLE_PER_HOST")
+                    .ok()
+                    .and_then(|m| m.parse().ok()),
+                tcp_keepalive_secs: env::var("SECURITY_EVENTS_HTTP_TCP_KEEPALIVE_SECS")
+                    .ok()
+                    .and_then(|k| k.parse().ok())
+                    .unwrap_or_else(default_http_tcp_keepalive_secs),
+            },
+        },
+        notifications: NotificationsConfig {
+            channels: Vec::new(),
+            rules: Vec::new(),
+            http_client: HttpClientConfig {
+                connect_timeout_secs: env::var("NOTIFICATIONS_HTTP_CONNECT_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_connect_timeout_secs),
+                request_timeout_secs: env::var("NOTIFICATIONS_HTTP_REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok()),
+                pool_idle_timeout_secs: env::var("NOTIFICATIONS_HTTP_POOL_IDLE_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or_else(default_http_pool_idle_timeout_secs),
+                pool_max_idle_per_host: env::var("NOTIFICATIONS_HTTP_POOL_MAX_IDLE_PER_HOST")
+                    .ok()
+                    .and_then(|m| m.parse().ok()),
+                tcp_keepalive_secs: env::var("NOTIFICATIONS_HTTP_TCP_KEEPALIVE_SECS")
+                    .ok()
+                    .and_then(|k| k.parse().ok())
+                    .unwrap_or_else(default_http_tcp_keepalive_secs),
+            },
+        },
+    }
+}
+
+/// Reads client keys from `CLIENT_AUTH_KEYS` and `CLIENT_AUTH_KEY_HASHES`.
+///
+/// Returns `None` if neither variable is set.
+fn client_keys_from_env() -> Option<Vec<ClientKey>> {
+    let keys = env::var("CLIENT_AUTH_KEYS").ok();
+    let hashes = env::var("CLIENT_AUTH_KEY_HASHES").ok();
+    if keys.is_none() && hashes.is_none() {
+        return None;
+    }
+
+    let mut parsed = keys.map(|k| parse_client_keys(&k, false)).unwrap_or_default();
+    parsed.extend(hashes.map(|h| parse_client_keys(&h, true)).unwrap_or_default());
+    apply_key_grants_from_env(&mut parsed);
+    Some(parsed)
+}
+
+/// Applies the scopes and models of `CLIENT_AUTH_KEY_SCOPES` and
+/// `CLIENT_AUTH_KEY_MODELS` to the keys they name.
+fn apply_key_grants_from_env(keys: &mut [ClientKey]) {
+    let scopes = env::var("CLIENT_AUTH_KEY_SCOPES")
+        .map(|s| parse_key_grants(&s))
+        .unwrap_or_default();
+    let models = env::var("CLIENT_AUTH_KEY_MODELS")
+        .map(|m| parse_key_grants(&m))
+        .unwrap_or_default();
+    for key in keys.iter_mut() {
+        if let Some(scopes) = scopes.get(&key.id) {
+            key.scopes = scopes.clone();
+        }
+        if let Some(models) = models.get(&key.id) {
+            key.models = models.clone();
+        }
+    }
+}
+
+/// Name of the configuration file.
+pub const CONFIG_FILE_NAME: &str = "config.yaml";
+
+/// Directory holding the configuration file below the user and system
+/// configuration directories.
+const CONFIG_DIR_NAME: &str = "panw-api-ollama";
+
+/// Where the configuration file was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Given with `--config`
+    Argument,
+    /// First existing file of the search path
+    SearchPath,
+}
+
+/// Returns the locations searched for the configuration file, in order:
+/// the working directory, the user configuration directory
+/// (`$XDG_CONFIG_HOME`, or `~/.config` when unset) and `/etc`.
+pub fn config_search_path() -> Vec<PathBuf> {
+    let user_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    let mut path = vec![PathBuf::from(CONFIG_FILE_NAME)];
+    if let Some(dir) = user_dir {
+        path.push(dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME));
+    }
+    path.push(Path::new("/etc").join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME));
+    path
+}
+
+/// Finds the configuration file.
+///
+/// A file given with `--config` takes precedence and must exist; otherwise the
+/// first existing file of the search path is used.
+///
+/// # Returns
+///
+/// * `Ok(Some((path, source)))` - The configuration file and where it was found
+/// * `Ok(None)` - If no file was given and none exists in the search path
+/// * `Err(ConfigError)` - If the file given with `--config` does not exist
+pub fn find_config_file(
+    explicit: Option<&str>,
+) -> Result<Option<(String, ConfigSource)>, ConfigError> {
+    if let Some(path) = explicit {
+        if !Path::new(path).is_file() {
+            return Err(ConfigError::ValidationError(format!(
+                "Configuration file {} given with --config does not exist",
+                path
+            )));
+        }
+        return Ok(Some((path.to_string(), ConfigSource::Argument)));
+    }
+    Ok(config_search_path()
+        .into_iter()
+        .find(|path| path.is_file())
+        .map(|path| (path.to_string_lossy().into_owned(), ConfigSource::SearchPath)))
+}
+
+/// Loads configuration from a YAML file or environment variables.
+///
+/// This function loads configuration from the given file, overridden by
+/// environment variables. Without a file, it uses environment variables only.
+/// In either case, the resulting configuration is validated before being returned.
+///
+/// # Arguments
+///
+/// * `path` - Path to the YAML configuration file, as found by `find_config_file`
+///
+/// # Returns
+///
+/// * `Ok(Config)` - Validated configuration object
+/// * `Err(ConfigError)` - If loading or validation fails
+///
+/// # Example
+///
+/// ```
+/// let config = config::load_config(Some("config.yaml"))?;
+/// println!("Server will listen on {}:{}", config.server.host, config.server.port);
+/// ```
+pub fn load_config(path: Option<&str>) -> Result<Config, ConfigError> {
+    if let Some(path) = path {
+        info!("Loading configuration from file: {}", path);
+
+        // Read file content
+        let content = fs::read_to_string(path)?;
+        debug!("Successfully read configuration file");
+
+        // Parse YAML
+        let mut config: Config = serde_yml::from_str(&content)?;
+        debug!("Successfully parsed YAML configuration");
+
+        // Override with environment variables if present
+        override_with_env(&mut config);
+
+        // Validate configuration
+        config.validate()?;
+        info!("Configuration validated successfully");
+
+        Ok(config)
+    } else {
+        info!("Configuration file not found. Using environment variables.");
+        let config = load_from_env();
+        config.validate()?;
+        info!("Configuration from environment variables validated successfully");
+        Ok(config)
+    }
+}
+
+/// Override configuration values with environment variables if present
+fn override_with_env(config: &mut Config) {
+    if let Ok(host) = env::var("SERVER_HOST") {
+        config.server.host = host;
+    }
+
+    if let Ok(port) = env::var("SERVER_PORT") {
+        if let Ok(port) = port.parse() {
+            config.server.port = port;
+        }
+    }
+
+    if let Ok(debug_level) = env::var("SERVER_DEBUG_LEVEL") {
+        config.server.debug_level = debug_level;
+    }
+
+    if let Ok(cert_file) = env::var("SERVER_TLS_CERT_FILE") {
+        config.server.tls_cert_file = Some(cert_file);
+    }
+
+    if let Ok(key_file) = env::var("SERVER_TLS_KEY_FILE") {
+        config.server.tls_key_file = Some(key_file);
+    }
+
+    if let Ok(interval) = env::var("SERVER_TLS_RELOAD_INTERVAL_SECS") {
+        if let Ok(interval) = interval.parse() {
+            config.server.tls_reload_interval_secs = interval;
+        }
+    }
+
+    if let Ok(ca_file) = env::var("SERVER_TLS_CLIENT_CA_FILE") {
+        config.server.tls_client_ca_file = Some(ca_file);
+    }
+
+    if let Ok(base_url) = env::var("OLLAMA_BASE_URL") {
+        config.ollama.base_url = base_url;
+    }
+
+    if let Ok(backends) = env::var("OLLAMA_BACKENDS") {
+        config.ollama.backends = parse_list(&backends);
+    }
+
+    if let Ok(interval) = env::var("OLLAMA_PROBE_INTERVAL_SECS") {
+        if let Ok(interval) = interval.parse() {
+            config.ollama.probe_interval_secs = interval;
+        }
+    }
+
+    if let Ok(interval) = env::var("OLLAMA_VERSION_CHECK_INTERVAL_SECS") {
+        if let Ok(interval) = interval.parse() {
+            config.ollama.version_check_interval_secs = interval;
+        }
+    }
+
+    if let Ok(routes) = env::var("OLLAMA_MODEL_ROUTES") {
+        config.ollama.model_routes = parse_model_routes(&routes);
+    }
+
+    if let Ok(headers) = env::var("OLLAMA_HEADERS") {
+        config.ollama.headers = parse_headers(&headers);
+    }
+
+    if let Ok(token) = env::var("OLLAMA_BEARER_TOKEN") {
+        config.ollama.bearer_token = token;
+    }
+
+    if let Ok(user) = env::var("OLLAMA_BASIC_AUTH_USER") {
+        config.ollama.basic_auth_user = user;
+    }
+
+    if let Ok(password) = env::var("OLLAMA_BASIC_AUTH_PASSWORD") {
+        config.ollama.basic_auth_password = password;
+    }
+
+    if let Ok(retries) = env::var("OLLAMA_RETRY_MAX_RETRIES") {
+        if let Ok(retries) = retries.parse() {
+            config.ollama.retry.max_retries = retries;
+        }
+    }
+
+    if let Ok(backoff) = env::var("OLLAMA_RETRY_INITIAL_BACKOFF_MS") {
+        if let Ok(backoff) = backoff.parse() {
+            config.ollama.retry.initial_backoff_ms = backoff;
+        }
+    }
+
+    if let Ok(backoff) = env::var("OLLAMA_RETRY_MAX_BACKOFF_MS") {
+        if let Ok(backoff) = backoff.parse() {
+            config.ollama.retry.max_backoff_ms = backoff;
+        }
+    }
+
+    if let Ok(statuses) = env::var("OLLAMA_RETRY_ON_STATUS") {
+        config.ollama.retry.retry_on_status = parse_statuses(&statuses);
+    }
+
+    if let Ok(timeout) = env::var("OLLAMA_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.ollama.http_client.connect_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("OLLAMA_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.ollama.http_client.request_timeout_secs = Some(timeout);
+        }
+    }
+
+    if let Ok(timeout) = env::var("OLLAMA_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.ollama.http_client.pool_idle_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(max_idle) = env::var("OLLAMA_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.ollama.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("OLLAMA_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.ollama.http_client.tcp_keepalive_secs = keepalive;
+        }
+    }
+
+    if let Ok(provider) = env::var("OLLAMA_PROVIDER") {
+        if let Ok(provider) = provider.parse() {
+            config.ollama.provider = provider;
+        }
+    }
+
+    if let Ok(seed) = env::var("OLLAMA_MOCK_SEED") {
+        if let Ok(seed) = seed.parse() {
+            config.ollama.mock.seed = seed;
+        }
+    }
+
+    if let Ok(tokens_per_second) = env::var("OLLAMA_MOCK_TOKENS_PER_SECOND") {
+        if let Ok(tokens_per_second) = tokens_per_second.parse() {
+            config.ollama.mock.tokens_per_second = tokens_per_second;
+        }
+    }
+
+    if let Ok(response_tokens) = env::var("OLLAMA_MOCK_RESPONSE_TOKENS") {
+        if let Ok(response_tokens) = response_tokens.parse() {
+            config.ollama.mock.response_tokens = response_tokens;
+        }
+    }
+
+    if let Ok(models) = env::var("OLLAMA_MOCK_MODELS") {
+        config.ollama.mock.models = parse_list(&models);
+    }
+
+    if let Ok(base_url) = env::var("SECURITY_BASE_URL") {
+        config.security.base_url = base_url;
+    }
+
+    if let Ok(api_key) = env::var("SECURITY_API_KEY") {
+        config.security.api_key = api_key;
+    }
+
+    if let Ok(profile_name) = env::var("SECURITY_PROFILE_NAME") {
+        config.security.profile_name = profile_name;
+    }
+
+    if let Ok(header) = env::var("SECURITY_PROFILE_HEADER") {
+        config.security.profile_header = header;
+    }
+
+    if let Ok(profiles) = env::var("SECURITY_ALLOWED_PROFILES") {
+        config.security.allowed_profiles = parse_list(&profiles);
+    }
+
+    if let Ok(profiles) = env::var("SECURITY_MODEL_PROFILES") {
+        config.security.model_profiles = parse_model_profiles(&profiles);
+    }
+
+    if let Ok(app_name) = env::var("SECURITY_APP_NAME") {
+        config.security.app_name = app_name;
+    }
+
+    if let Ok(app_user) = env::var("SECURITY_APP_USER") {
+        config.security.app_user = app_user;
+    }
+
+    // Load contextual grounding configuration
+    if let Ok(contextual_grounding) = env::var("SECURITY_CONTEXTUAL_GROUNDING_CONTEXT") {
+        config.security.contextual_grounding = contextual_grounding;
+    }
+
+    if let Ok(max_wait) = env::var("SECURITY_RATE_LIMIT_MAX_WAIT_SECS") {
+        if let Ok(max_wait) = max_wait.parse() {
+            config.security.rate_limit_max_wait_secs = max_wait;
+        }
+    }
+
+    if let Ok(cap) = env::var("SECURITY_BUDGET_MONTHLY_CAP") {
+        if let Ok(cap) = cap.parse() {
+            config.security.budget.monthly_cap = cap;
+        }
+    }
+
+    if let Ok(mode) = env::var("SECURITY_BUDGET_DEGRADED_MODE") {
+        if let Ok(mode) = mode.parse() {
+            config.security.budget.degraded_mode = mode;
+        }
+    }
+
+    if let Ok(webhook) = env::var("SECURITY_BUDGET_ALERT_WEBHOOK") {
+        config.security.budget.alert_webhook = webhook;
+    }
+
+    if let Ok(state_file) = env::var("SECURITY_BUDGET_STATE_FILE") {
+        config.security.budget.state_file = state_file;
+    }
+
+    if let Ok(timeout) = env::var("SECURITY_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security.http_client.connect_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("SECURITY_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security.http_client.request_timeout_secs = Some(timeout);
+        }
+    }
+
+    if let Ok(timeout) = env::var("SECURITY_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security.http_client.pool_idle_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(max_idle) = env::var("SECURITY_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.security.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("SECURITY_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.security.http_client.tcp_keepalive_secs = keepalive;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_SECRETS_ENABLED") {
+        config.security.secrets.enabled = enabled;
+    }
+
+    if let Ok(action) = env::var("SECURITY_SECRETS_ACTION") {
+        if let Ok(action) = action.parse() {
+            config.security.secrets.action = action;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_LANGUAGE_ENABLED") {
+        config.security.language.enabled = enabled;
+    }
+
+    if let Ok(languages) = env::var("SECURITY_LANGUAGE_ALLOWED") {
+        config.security.language.allowed_languages = parse_list(&languages);
+    }
+
+    if let Ok(action) = env::var("SECURITY_LANGUAGE_ACTION") {
+        if let Ok(action) = action.parse() {
+            config.security.language.action = action;
+        }
+    }
+
+    if let Ok(confidence) = env::var("SECURITY_LANGUAGE_MIN_CONFIDENCE") {
+        if let Ok(confidence) = confidence.parse() {
+            config.security.language.min_confidence = confidence;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_TOXICITY_ENABLED") {
+        config.security.toxicity.enabled = enabled;
+    }
+
+    if let Ok(action) = env::var("SECURITY_TOXICITY_ACTION") {
+        if let Ok(action) = action.parse() {
+            config.security.toxicity.action = action;
+        }
+    }
+
+    if let Ok(severity) = env::var("SECURITY_TOXICITY_MIN_SEVERITY") {
+        if let Ok(severity) = severity.parse() {
+            config.security.toxicity.min_severity = severity;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_PII_ENABLED") {
+        config.security.pii.enabled = enabled;
+    }
+
+    if let Ok(entities) = env::var("SECURITY_PII_ENTITIES") {
+        config.security.pii.entities = parse_pii_entities(&entities);
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_DECODE_PAYLOADS_ENABLED") {
+        config.security.decode_payloads.enabled = enabled;
+    }
+
+    if let Ok(min_length) = env::var("SECURITY_DECODE_PAYLOADS_MIN_LENGTH") {
+        if let Ok(min_length) = min_length.parse() {
+            config.security.decode_payloads.min_length = min_length;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_SCAN_CACHE_ENABLED") {
+        config.security.scan_cache.enabled = enabled;
+    }
+
+    if let Ok(max_entries) = env::var("SECURITY_SCAN_CACHE_MAX_ENTRIES") {
+        if let Ok(max_entries) = max_entries.parse() {
+            config.security.scan_cache.max_entries = max_entries;
+        }
+    }
+
+    if let Ok(ttl) = env::var("SECURITY_SCAN_CACHE_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.security.scan_cache.ttl_secs = ttl;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_ASYNC_STREAMING_ENABLED") {
+        config.security.async_streaming.enabled = enabled;
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_STREAM_SUMMARY") {
+        config.security.stream_summary = enabled;
+    }
+
+    if let Ok(window) = env::var("SECURITY_ASYNC_STREAMING_RISK_WINDOW_CHARS") {
+        if let Ok(window) = window.parse() {
+            config.security.async_streaming.risk_window_chars = window;
+        }
+    }
+
+    if let Ok(interval) = env::var("SECURITY_ASYNC_STREAMING_POLL_INTERVAL_MS") {
+        if let Ok(interval) = interval.parse() {
+            config.security.async_streaming.poll_interval_ms = interval;
+        }
+    }
+
+    if let Ok(timeout) = env::var("SECURITY_ASYNC_STREAMING_SETTLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security.async_streaming.settle_timeout_secs = timeout;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_RESPONSE_CAPTURE_ENABLED") {
+        config.security.response_capture.enabled = enabled;
+    }
+
+    if let Ok(capacity) = env::var("SECURITY_RESPONSE_CAPTURE_CAPACITY") {
+        if let Ok(capacity) = capacity.parse() {
+            config.security.response_capture.capacity = capacity;
+        }
+    }
+
+    if let Ok(max) = env::var("SECURITY_RESPONSE_CAPTURE_MAX_PER_MINUTE") {
+        if let Ok(max) = max.parse() {
+            config.security.response_capture.max_per_minute = max;
+        }
+    }
+
+    if let Ok(percent) = env::var("SECURITY_RESPONSE_CAPTURE_SAMPLE_PERCENT") {
+        if let Ok(percent) = percent.parse() {
+            config.security.response_capture.sample_percent = percent;
+        }
+    }
+
+    if let Ok(max) = env::var("SECURITY_RESPONSE_CAPTURE_MAX_BODY_BYTES") {
+        if let Ok(max) = max.parse() {
+            config.security.response_capture.max_body_bytes = max;
+        }
+    }
+
+    if let Some(enabled) = env_flag("SECURITY_CIRCUIT_BREAKER_ENABLED") {
+        config.security.circuit_breaker.enabled = enabled;
+    }
+
+    if let Ok(threshold) = env::var("SECURITY_CIRCUIT_BREAKER_FAILURE_THRESHOLD") {
+        if let Ok(threshold) = threshold.parse() {
+            config.security.circuit_breaker.failure_threshold = threshold;
+        }
+    }
+
+    if let Ok(cooldown) = env::var("SECURITY_CIRCUIT_BREAKER_COOLDOWN_SECS") {
+        if let Ok(cooldown) = cooldown.parse() {
+            config.security.circuit_breaker.cooldown_secs = cooldown;
+        }
+    }
+
+    if let Ok(mode) = env::var("SECURITY_CIRCUIT_BREAKER_OPEN_MODE") {
+        if let Ok(mode) = mode.parse() {
+            config.security.circuit_breaker.open_mode = mode;
+        }
+    }
+
+    if let Ok(mode) = env::var("SECURITY_FAILURE_MODE") {
+        if let Ok(mode) = mode.parse() {
+            config.security.failure_mode = mode;
+        }
+    }
+
+    if let Ok(keys) = env::var("SECURITY_SCAN_BYPASS_KEYS") {
+        config.security.scan_bypass.keys = parse_list(&keys);
+    }
+
+    if let Ok(networks) = env::var("SECURITY_SCAN_BYPASS_NETWORKS") {
+        config.security.scan_bypass.networks = parse_list(&networks);
+    }
+
+    if let Ok(provider) = env::var("SECURITY_PROVIDER") {
+        if let Ok(provider) = provider.parse() {
+            config.security.provider = provider;
+        }
+    }
+
+    if let Ok(providers) = env::var("SECURITY_FAN_OUT_PROVIDERS") {
+        config.security.fan_out.providers = parse_providers(&providers);
+    }
+
+    if let Ok(policy) = env::var("SECURITY_FAN_OUT_POLICY") {
+        if let Ok(policy) = policy.parse() {
+            config.security.fan_out.policy = policy;
+        }
+    }
+
+    if let Ok(weights) = env::var("SECURITY_FAN_OUT_WEIGHTS") {
+        config.security.fan_out.weights = parse_provider_weights(&weights);
+    }
+
+    if let Ok(threshold) = env::var("SECURITY_FAN_OUT_THRESHOLD") {
+        if let Ok(threshold) = threshold.parse() {
+            config.security.fan_out.threshold = threshold;
+        }
+    }
+
+    if let Ok(base_url) = env::var("OPENAI_MODERATION_BASE_URL") {
+        config.security.openai_moderation.base_url = base_url;
+    }
+
+    if let Ok(api_key) = env::var("OPENAI_MODERATION_API_KEY") {
+        config.security.openai_moderation.api_key = api_key;
+    }
+
+    if let Ok(model) = env::var("OPENAI_MODERATION_MODEL") {
+        config.security.openai_moderation.model = model;
+    }
+
+    if let Ok(categories) = env::var("OPENAI_MODERATION_BLOCK_CATEGORIES") {
+        config.security.openai_moderation.block_categories = parse_list(&categories);
+    }
+
+    if let Some(region) = env_aws_region() {
+        config.security.bedrock_guardrails.region = region;
+    }
+
+    if let Ok(guardrail_id) = env::var("BEDROCK_GUARDRAILS_ID") {
+        config.security.bedrock_guardrails.guardrail_id = guardrail_id;
+    }
+
+    if let Ok(version) = env::var("BEDROCK_GUARDRAILS_VERSION") {
+        config.security.bedrock_guardrails.guardrail_version = version;
+    }
+
+    if let Ok(endpoint) = env::var("BEDROCK_GUARDRAILS_ENDPOINT") {
+        config.security.bedrock_guardrails.endpoint = endpoint;
+    }
+
+    if let Ok(access_key_id) = env::var("AWS_ACCESS_KEY_ID") {
+        config.security.bedrock_guardrails.access_key_id = access_key_id;
+    }
+
+    if let Ok(secret_access_key) = env::var("AWS_SECRET_ACCESS_KEY") {
+        config.security.bedrock_guardrails.secret_access_key = secret_access_key;
+    }
+
+    if let Ok(session_token) = env::var("AWS_SESSION_TOKEN") {
+        config.security.bedrock_guardrails.session_token = session_token;
+    }
+
+    if let Ok(endpoint) = env::var("AZURE_CONTENT_SAFETY_ENDPOINT") {
+        config.security.azure_content_safety.endpoint = endpoint;
+    }
+
+    if let Ok(api_key) = env::var("AZURE_CONTENT_SAFETY_API_KEY") {
+        config.security.azure_content_safety.api_key = api_key;
+    }
+
+    if let Ok(api_version) = env::var("AZURE_CONTENT_SAFETY_API_VERSION") {
+        config.security.azure_content_safety.api_version = api_version;
+    }
+
+    if let Ok(threshold) = env::var("AZURE_CONTENT_SAFETY_SEVERITY_THRESHOLD") {
+        if let Ok(threshold) = threshold.parse() {
+            config.security.azure_content_safety.severity_threshold = threshold;
+        }
+    }
+
+    if let Ok(thresholds) = env::var("AZURE_CONTENT_SAFETY_CATEGORY_THRESHOLDS") {
+        config.security.azure_content_safety.category_thresholds =
+            parse_category_thresholds(&thresholds);
+    }
+
+    if let Ok(blocklists) = env::var("AZURE_CONTENT_SAFETY_BLOCKLISTS") {
+        config.security.azure_content_safety.blocklists = parse_list(&blocklists);
+    }
+
+    if let Ok(model) = env::var("LLAMA_GUARD_MODEL") {
+        config.security.llama_guard.model = model;
+    }
+
+    if let Ok(categories) = env::var("LLAMA_GUARD_BLOCK_CATEGORIES") {
+        config.security.llama_guard.block_categories = parse_list(&categories);
+    }
+
+    if let Some(scan) = env_flag("MODELS_SHOW_SCAN") {
+        config.models.show_filter.scan = scan;
+    }
+
+    if let Some(strip) = env_flag("MODELS_SHOW_STRIP") {
+        config.models.show_filter.strip = strip;
+    }
+
+    if let Some(confirm) = env_flag("MODELS_CONFIRM_DESTRUCTIVE") {
+        config.models.confirm_destructive = confirm;
+    }
+
+    if let Ok(ttl) = env::var("MODELS_CONFIRMATION_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.models.confirmation_ttl_secs = ttl;
+        }
+    }
+
+    if let Ok(strategies) = env::var("MODELS_STREAMING_STRATEGIES") {
+        config.models.streaming = parse_model_streaming(&strategies);
+    }
+
+    if let Ok(token) = env::var("ADMIN_TOKEN") {
+        config.admin.token = token;
+    }
+
+    if let Ok(mode) = env::var("MAINTENANCE_MODE") {
+        if let Ok(mode) = mode.parse() {
+            config.maintenance.mode = mode;
+        }
+    }
+
+    if let Ok(message) = env::var("MAINTENANCE_MESSAGE") {
+        config.maintenance.message = message;
+    }
+
+    if let Ok(message) = env::var("KILL_SWITCH_MESSAGE") {
+        config.kill_switch.message = message;
+    }
+
+    if let Ok(state_file) = env::var("KILL_SWITCH_STATE_FILE") {
+        config.kill_switch.state_file = state_file;
+    }
+
+    if let Some(enabled) = env_flag("DEDUP_ENABLED") {
+        config.dedup.enabled = enabled;
+    }
+
+    if let Ok(window) = env::var("DEDUP_WINDOW_SECS") {
+        if let Ok(window) = window.parse() {
+            config.dedup.window_secs = window;
+        }
+    }
+
+    if let Some(enabled) = env_flag("IDEMPOTENCY_ENABLED") {
+        config.idempotency.enabled = enabled;
+    }
+
+    if let Ok(ttl) = env::var("IDEMPOTENCY_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.idempotency.ttl_secs = ttl;
+        }
+    }
+
+    if let Some(enabled) = env_flag("PROVENANCE_ENABLED") {
+        config.provenance.enabled = enabled;
+    }
+
+    if let Ok(key_file) = env::var("PROVENANCE_SIGNING_KEY_FILE") {
+        config.provenance.signing_key_file = key_file;
+    }
+
+    if let Ok(retention) = env::var("PROVENANCE_RETENTION_SECS") {
+        if let Ok(retention) = retention.parse() {
+            config.provenance.retention_secs = retention;
+        }
+    }
+
+    if let Some(enabled) = env_flag("TRANSCRIPTS_ENABLED") {
+        config.transcripts.enabled = enabled;
+    }
+
+    if let Ok(file) = env::var("TRANSCRIPTS_FILE") {
+        config.transcripts.file = file;
+    }
+
+    if let Ok(header) = env::var("TRANSCRIPTS_SESSION_HEADER") {
+        config.transcripts.session_header = header;
+    }
+
+    if let Ok(retention) = env::var("TRANSCRIPTS_RETENTION_DAYS") {
+        if let Ok(retention) = retention.parse() {
+            config.transcripts.retention_days = retention;
+        }
+    }
+
+    if let Ok(key) = env::var("TRANSCRIPTS_HMAC_KEY") {
+        config.transcripts.hmac_key = key;
+    }
+
+    if let Ok(mode) = env::var("POSTPROCESS_URL_MODE") {
+        if let Ok(mode) = mode.parse() {
+            config.postprocess.url_mode = mode;
+        }
+    }
+
+    if let Ok(template) = env::var("POSTPROCESS_URL_REDIRECT_TEMPLATE") {
+        config.postprocess.url_redirect_template = template;
+    }
+
+    if let Ok(filters) = env::var("POSTPROCESS_FILTERS") {
+        config.postprocess.filters = parse_filters(&filters);
+    }
+
+    if let Ok(max_length) = env::var("POSTPROCESS_MAX_LENGTH") {
+        if let Ok(max_length) = max_length.parse() {
+            config.postprocess.max_length = max_length;
+        }
+    }
+
+    if let Ok(notice) = env::var("POSTPROCESS_TRUNCATION_NOTICE") {
+        config.postprocess.truncation_notice = notice;
+    }
+
+    if let Ok(patterns) = env::var("POSTPROCESS_LEAKAGE_PATTERNS") {
+        config.postprocess.leakage_patterns = parse_list(&patterns);
+    }
+
+    if let Ok(model) = env::var("SUMMARIZE_MODEL") {
+        config.summarize.model = model;
+    }
+
+    if let Ok(prompt) = env::var("SUMMARIZE_PROMPT") {
+        config.summarize.prompt = prompt;
+    }
+
+    if let Ok(max_prompts) = env::var("BATCH_MAX_PROMPTS") {
+        if let Ok(max_prompts) = max_prompts.parse() {
+            config.batch.max_prompts = max_prompts;
+        }
+    }
+
+    if let Ok(concurrency) = env::var("BATCH_CONCURRENCY") {
+        if let Ok(concurrency) = concurrency.parse() {
+            config.batch.concurrency = concurrency;
+        }
+    }
+
+    if let Ok(ttl) = env::var("JOBS_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.jobs.ttl_secs = ttl;
+        }
+    }
+
+    if let Ok(max_per_client) = env::var("JOBS_MAX_PER_CLIENT") {
+        if let Ok(max_per_client) = max_per_client.parse() {
+            config.jobs.max_per_client = max_per_client;
+        }
+    }
+
+    if let Ok(webhook) = env::var("SCHEDULER_ALERT_WEBHOOK") {
+        config.scheduler.alert_webhook = webhook;
+    }
+
+    if let Some(enabled) = env_flag("SCHEDULER_LEADER_ELECTION_ENABLED") {
+        config.scheduler.leader_election.enabled = enabled;
+    }
+
+    if let Ok(lease_file) = env::var("SCHEDULER_LEADER_LEASE_FILE") {
+        config.scheduler.leader_election.lease_file = lease_file;
+    }
+
+    if let Ok(ttl) = env::var("SCHEDULER_LEADER_LEASE_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.scheduler.leader_election.lease_ttl_secs = ttl;
+        }
+    }
+
+    if let Ok(timeout) = env::var("SCHEDULER_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.scheduler.http_client.connect_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("SCHEDULER_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.scheduler.http_client.request_timeout_secs = Some(timeout);
+        }
+    }
+
+    if let Ok(timeout) = env::var("SCHEDULER_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.scheduler.http_client.pool_idle_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(max_idle) = env::var("SCHEDULER_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.scheduler.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("SCHEDULER_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.scheduler.http_client.tcp_keepalive_secs = keepalive;
+        }
+    }
+
+    if let Ok(state_file) = env::var("TEMPLATES_STATE_FILE") {
+        config.templates.state_file = state_file;
+    }
+
+    if let Ok(timeout) = env::var("HEALTH_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.health.timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(cache) = env::var("HEALTH_CACHE_SECS") {
+        if let Ok(cache) = cache.parse() {
+            config.health.cache_secs = cache;
+        }
+    }
+
+    if let Ok(interval) = env::var("HEALTH_PROBE_INTERVAL_SECS") {
+        if let Ok(interval) = interval.parse() {
+            config.health.probe_interval_secs = interval;
+        }
+    }
+
+    if let Ok(delay) = env::var("HEALTH_SHUTDOWN_DELAY_SECS") {
+        if let Ok(delay) = delay.parse() {
+            config.health.shutdown_delay_secs = delay;
+        }
+    }
+
+    if let Ok(header) = env::var("METRICS_TENANT_HEADER") {
+        config.metrics.tenant_header = header;
+    }
+
+    if let Ok(max_tenants) = env::var("METRICS_MAX_TENANTS") {
+        if let Ok(max_tenants) = max_tenants.parse() {
+            config.metrics.max_tenants = max_tenants;
+        }
+    }
+
+    if let Ok(max_keys) = env::var("METRICS_MAX_KEYS") {
+        if let Ok(max_keys) = max_keys.parse() {
+            config.metrics.max_keys = max_keys;
+        }
+    }
+
+    if let Some(enabled) = env_flag("CONTEXT_CHECK_ENABLED") {
+        config.context.enabled = enabled;
+    }
+
+    if let Ok(chars_per_token) = env::var("CONTEXT_CHARS_PER_TOKEN") {
+        if let Ok(chars_per_token) = chars_per_token.parse() {
+            config.context.chars_per_token = chars_per_token;
+        }
+    }
+
+    if let Ok(ttl) = env::var("CAPABILITIES_CACHE_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.capabilities.cache_ttl_secs = ttl;
+        }
+    }
+
+    if let Some(enabled) = env_flag("AUTO_PULL_ENABLED") {
+        config.auto_pull.enabled = enabled;
+    }
+
+    if let Ok(models) = env::var("AUTO_PULL_ALLOWED_MODELS") {
+        config.auto_pull.allowed_models = parse_list(&models);
+    }
+
+    if let Ok(retry_after) = env::var("AUTO_PULL_RETRY_AFTER_SECS") {
+        if let Ok(retry_after) = retry_after.parse() {
+            config.auto_pull.retry_after_secs = retry_after;
+        }
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_ENABLED") {
+        config.client_auth.enabled = enabled;
+    }
+
+    if let Some(keys) = client_keys_from_env() {
+        config.client_auth.keys = keys;
+    }
+    apply_key_grants_from_env(&mut config.client_auth.keys);
+
+    if let Ok(state_file) = env::var("CLIENT_AUTH_KEY_STATE_FILE") {
+        config.client_auth.key_state_file = state_file;
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_JWT_ENABLED") {
+        config.client_auth.jwt.enabled = enabled;
+    }
+
+    if let Ok(issuer) = env::var("CLIENT_AUTH_JWT_ISSUER") {
+        config.client_auth.jwt.issuer = issuer;
+    }
+
+    if let Ok(audience) = env::var("CLIENT_AUTH_JWT_AUDIENCE") {
+        config.client_auth.jwt.audience = audience;
+    }
+
+    if let Ok(jwks_url) = env::var("CLIENT_AUTH_JWT_JWKS_URL") {
+        config.client_auth.jwt.jwks_url = jwks_url;
+    }
+
+    if let Ok(refresh) = env::var("CLIENT_AUTH_JWT_JWKS_REFRESH_SECS") {
+        if let Ok(refresh) = refresh.parse() {
+            config.client_auth.jwt.jwks_refresh_secs = refresh;
+        }
+    }
+
+    if let Ok(claim) = env::var("CLIENT_AUTH_JWT_SUBJECT_CLAIM") {
+        config.client_auth.jwt.subject_claim = claim;
+    }
+
+    if let Ok(claim) = env::var("CLIENT_AUTH_JWT_GROUPS_CLAIM") {
+        config.client_auth.jwt.groups_claim = claim;
+    }
+
+    if let Ok(profiles) = env::var("CLIENT_AUTH_JWT_GROUP_PROFILES") {
+        config.client_auth.jwt.group_profiles = parse_group_profiles(&profiles);
+    }
+
+    if let Ok(timeout) = env::var("CLIENT_AUTH_JWT_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.client_auth.jwt.http_client.connect_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("CLIENT_AUTH_JWT_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.client_auth.jwt.http_client.request_timeout_secs = Some(timeout);
+        }
+    }
+
+    if let Ok(timeout) = env::var("CLIENT_AUTH_JWT_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.client_auth.jwt.http_client.pool_idle_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(max_idle) = env::var("CLIENT_AUTH_JWT_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.client_auth.jwt.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("CLIENT_AUTH_JWT_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.client_auth.jwt.http_client.tcp_keepalive_secs = keepalive;
+        }
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_APP_USER_HEADER_ENABLED") {
+        config.client_auth.app_user_header.enabled = enabled;
+    }
+
+    if let Ok(name) = env::var("CLIENT_AUTH_APP_USER_HEADER_NAME") {
+        config.client_auth.app_user_header.name = name;
+    }
+
+    if let Ok(clients) = env::var("CLIENT_AUTH_APP_USER_HEADER_TRUSTED_CLIENTS") {
+        config.client_auth.app_user_header.trusted_clients = parse_list(&clients);
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_BASIC_ENABLED") {
+        config.client_auth.basic_auth = enabled;
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_TRUSTED_PROXY_ENABLED") {
+        config.client_auth.trusted_proxy.enabled = enabled;
+    }
+
+    if let Ok(header) = env::var("CLIENT_AUTH_TRUSTED_PROXY_HEADER") {
+        config.client_auth.trusted_proxy.header = header;
+    }
+
+    if let Ok(addresses) = env::var("CLIENT_AUTH_TRUSTED_PROXY_ADDRESSES") {
+        config.client_auth.trusted_proxy.addresses = parse_list(&addresses);
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_ACCESS_TOKENS_ENABLED") {
+        config.client_auth.access_tokens.enabled = enabled;
+    }
+
+    if let Ok(ttl) = env::var("CLIENT_AUTH_ACCESS_TOKEN_TTL_SECS") {
+        if let Ok(ttl) = ttl.parse() {
+            config.client_auth.access_tokens.ttl_secs = ttl;
+        }
+    }
+
+    if let Ok(secret) = env::var("CLIENT_AUTH_ACCESS_TOKEN_SIGNING_SECRET") {
+        config.client_auth.access_tokens.signing_secret = secret;
+    }
+
+    if let Some(enabled) = env_flag("CLIENT_AUTH_LOCKOUT_ENABLED") {
+        config.client_auth.lockout.enabled = enabled;
+    }
+
+    if let Ok(max_failures) = env::var("CLIENT_AUTH_LOCKOUT_MAX_FAILURES") {
+        if let Ok(max_failures) = max_failures.parse() {
+            config.client_auth.lockout.max_failures = max_failures;
+        }
+    }
+
+    if let Ok(window) = env::var("CLIENT_AUTH_LOCKOUT_WINDOW_SECS") {
+        if let Ok(window) = window.parse() {
+            config.client_auth.lockout.window_secs = window;
+        }
+    }
+
+    if let Ok(base) = env::var("CLIENT_AUTH_LOCKOUT_BASE_SECS") {
+        if let Ok(base) = base.parse() {
+            config.client_auth.lockout.base_lockout_secs = base;
+        }
+    }
+
+    if let Ok(max) = env::var("CLIENT_AUTH_LOCKOUT_MAX_SECS") {
+        if let Ok(max) = max.parse() {
+            config.client_auth.lockout.max_lockout_secs = max;
+        }
+    }
+
+    if let Ok(min_count) = env::var("USAGE_REPORT_MIN_COUNT") {
+        if let Ok(min_count) = min_count.parse() {
+            config.usage.report_min_count = min_count;
+        }
+    }
+
+    if let Ok(epsilon) = env::var("USAGE_REPORT_NOISE_EPSILON") {
+        if let Ok(epsilon) = epsilon.parse() {
+            config.usage.report_noise_epsilon = epsilon;
+        }
+    }
+
+    if let Some(enabled) = env_flag("RATE_LIMIT_ENABLED") {
+        config.rate_limit.enabled = enabled;
+    }
+
+    if let Ok(requests) = env::var("RATE_LIMIT_REQUESTS_PER_MINUTE") {
+        if let Ok(requests) = requests.parse() {
+            config.rate_limit.requests_per_minute = requests;
+        }
+    }
+
+    if let Ok(burst) = env::var("RATE_LIMIT_BURST") {
+        if let Ok(burst) = burst.parse() {
+            config.rate_limit.burst = burst;
+        }
+    }
+
+    if let Ok(routes) = env::var("RATE_LIMIT_ROUTES") {
+        config.rate_limit.routes = parse_route_rate_limits(&routes);
+    }
+
+    if let Ok(max_in_flight) = env::var("CONCURRENCY_MAX_IN_FLIGHT") {
+        if let Ok(max_in_flight) = max_in_flight.parse() {
+            config.concurrency.max_in_flight = max_in_flight;
+        }
+    }
+
+    if let Ok(max_in_flight) = env::var("CONCURRENCY_MAX_IN_FLIGHT_PER_MODEL") {
+        if let Ok(max_in_flight) = max_in_flight.parse() {
+            config.concurrency.max_in_flight_per_model = max_in_flight;
+        }
+    }
+
+    if let Ok(limits) = env::var("CONCURRENCY_MODEL_LIMITS") {
+        config.concurrency.model_limits = parse_model_concurrency(&limits);
+    }
+
+    if let Ok(max_queued) = env::var("CONCURRENCY_MAX_QUEUED") {
+        if let Ok(max_queued) = max_queued.parse() {
+            config.concurrency.max_queued = max_queued;
+        }
+    }
+
+    if let Ok(timeout) = env::var("CONCURRENCY_QUEUE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.concurrency.queue_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(keys) = env::var("STORAGE_ENCRYPTION_KEYS") {
+        config.encryption.keys = parse_encryption_keys(&keys);
+    }
+
+    if let Ok(active_key) = env::var("STORAGE_ENCRYPTION_ACTIVE_KEY") {
+        config.encryption.active_key = active_key;
+    }
+
+    if let Ok(address) = env::var("VAULT_ADDR") {
+        config.vault.address = address;
+    }
+
+    if let Ok(namespace) = env::var("VAULT_NAMESPACE") {
+        config.vault.namespace = namespace;
+    }
+
+    if let Ok(token) = env::var("VAULT_TOKEN") {
+        config.vault.token = token;
+    }
+
+    if let Ok(role_id) = env::var("VAULT_ROLE_ID") {
+        config.vault.role_id = role_id;
+    }
+
+    if let Ok(secret_id) = env::var("VAULT_SECRET_ID") {
+        config.vault.secret_id = secret_id;
+    }
+
+    if let Ok(mount) = env::var("VAULT_APPROLE_MOUNT") {
+        config.vault.approle_mount = mount;
+    }
+
+    if let Ok(mount) = env::var("VAULT_KV_MOUNT") {
+        config.vault.kv_mount = mount;
+    }
+
+    if let Ok(refresh) = env::var("VAULT_REFRESH_SECS") {
+        if let Ok(refresh) = refresh.parse() {
+            config.vault.refresh_secs = refresh;
+        }
+    }
+
+    if let Ok(timeout) = env::var("VAULT_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.vault.http_client.connect_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("VAULT_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.vault.http_client.request_timeout_secs = Some(timeout);
+        }
+    }
+
+    if let Ok(timeout) = env::var("VAULT_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.vault.http_client.pool_idle_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(max_idle) = env::var("VAULT_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.vault.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("VAULT_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.vault.http_client.tcp_keepalive_secs = keepalive;
+        }
+    }
+
+    if let Ok(timeout) = env::var("TIMEOUT_REQUEST_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.timeouts.request_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(timeout) = env::var("TIMEOUT_STREAM_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.timeouts.stream_timeout_secs = timeout;
+        }
+    }
+
+    if let Ok(routes) = env::var("TIMEOUT_ROUTES") {
+        config.timeouts.routes = parse_route_timeouts(&routes);
+    }
+
+    if let Ok(flags) = env::var("FEATURE_FLAGS") {
+        config.feature_flags = parse_feature_flags(&flags);
+    }
+
+    if let Ok(template) = env::var("BRANDING_MESSAGE_TEMPLATE") {
+        config.branding.defaults.message_template = Some(template);
+    }
+
+    if let Ok(model_name) = env::var("BRANDING_MODEL_NAME") {
+        config.branding.defaults.model_name = Some(model_name);
+    }
 
-        // Read file content
-        let content = fs::read_to_string(path)?;
-        debug!("Successfully read configuration file");
+    if let Ok(scenario_file) = env::var("DEMO_SCENARIO_FILE") {
+        config.demo.scenario_file = scenario_file;
+    }
 
-        // Parse YAML
-        let mut config: Config = serde_yml::from_str(&content)?;
-        debug!("Successfully parsed YAML configuration");
+    if let Some(enabled) = env_flag("COMPAT_ENABLED") {
+        config.compat.enabled = enabled;
+    }
 
-        // Override with environment variables if present
-        override_with_env(&mut config);
+    if let Ok(headers) = env::var("PASSTHROUGH_REQUEST_HEADERS") {
+        config.passthrough.request_headers = parse_list(&headers);
+    }
 
-        // Validate configuration
-        config.validate()?;
-        info!("Configuration validated successfully");
+    if let Ok(headers) = env::var("PASSTHROUGH_RESPONSE_HEADERS") {
+        config.passthrough.response_headers = parse_list(&headers);
+    }
 
-        Ok(config)
-    } else {
-        info!(
-            "Configuration file not found: {}. Using environment variables.",
-            path
-        );
-        let config = load_from_env();
-        config.validate()?;
-        info!("Configuration from environment variables validated successfully");
-        Ok(config)
+    if let Ok(url) = env::var("SECURITY_EVENTS_WEBHOOK_URL") {
+        config.security_events.webhook_url = url;
     }
-}
 
-/// Override configuration values with environment variables if present
-fn override_with_env(config: &mut Config) {
-    if let Ok(host) = env::var("SERVER_HOST") {
-        config.server.host = host;
+    if let Ok(address) = env::var("SECURITY_EVENTS_SYSLOG_ADDRESS") {
+        config.security_events.syslog_address = address;
     }
 
-    if let Ok(port) = env::var("SERVER_PORT") {
-        if let Ok(port) = port.parse() {
-            config.server.port = port;
+    if let Ok(severities) = env::var("SECURITY_EVENTS_SEVERITIES") {
+        config.security_events.severities = parse_event_severities(&severities);
+    }
+
+    if let Ok(names) = env::var("SECURITY_EVENTS_FIELD_NAMES") {
+        config.security_events.field_names = parse_headers(&names);
+    }
+
+    if let Ok(timeout) = env::var("NOTIFICATIONS_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.notifications.http_client.connect_timeout_secs = timeout;
         }
     }
 
-    if let Ok(debug_level) = env::var("SERVER_DEBUG_LEVEL") {
-        config.server.debug_level = debug_level;
+    if let Ok(timeout) = env::var("NOTIFICATIONS_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.notifications.http_client.request_timeout_secs = Some(timeout);
+        }
     }
 
-    if let Ok(base_url) = env::var("OLLAMA_BASE_URL") {
-        config.ollama.base_url = base_url;
+    if let Ok(timeout) = env::var("NOTIFICATIONS_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.notifications.http_client.pool_idle_timeout_secs = timeout;
+        }
     }
 
-    if let Ok(base_url) = env::var("SECURITY_BASE_URL") {
-        config.security.base_url = base_url;
+    if let Ok(max_idle) = env::var("NOTIFICATIONS_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.notifications.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
     }
 
-    if let Ok(api_key) = env::var("SECURITY_API_KEY") {
-        config.security.api_key = api_key;
+    if let Ok(keepalive) = env::var("NOTIFICATIONS_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.notifications.http_client.tcp_keepalive_secs = keepalive;
+        }
     }
 
-    if let Ok(profile_name) = env::var("SECURITY_PROFILE_NAME") {
-        config.security.profile_name = profile_name;
+    if let Ok(timeout) = env::var("SECURITY_EVENTS_HTTP_CONNECT_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security_events.http_client.connect_timeout_secs = timeout;
+        }
     }
 
-    if let Ok(app_name) = env::var("SECURITY_APP_NAME") {
-        config.security.app_name = app_name;
+    if let Ok(timeout) = env::var("SECURITY_EVENTS_HTTP_REQUEST_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security_events.http_client.request_timeout_secs = Some(timeout);
+        }
     }
 
-    if let Ok(app_user) = env::var("SECURITY_APP_USER") {
-        config.security.app_user = app_user;
+    if let Ok(timeout) = env::var("SECURITY_EVENTS_HTTP_POOL_IDLE_TIMEOUT_SECS") {
+        if let Ok(timeout) = timeout.parse() {
+            config.security_events.http_client.pool_idle_timeout_secs = timeout;
+        }
     }
 
-    // Load contextual grounding configuration
-    if let Ok(contextual_grounding) = env::var("SECURITY_CONTEXTUAL_GROUNDING_CONTEXT") {
-        config.security.contextual_grounding = contextual_grounding;
+    if let Ok(max_idle) = env::var("SECURITY_EVENTS_HTTP_POOL_MAX_IDLE_PER_HOST") {
+        if let Ok(max_idle) = max_idle.parse() {
+            config.security_events.http_client.pool_max_idle_per_host = Some(max_idle);
+        }
+    }
+
+    if let Ok(keepalive) = env::var("SECURITY_EVENTS_HTTP_TCP_KEEPALIVE_SECS") {
+        if let Ok(keepalive) = keepalive.parse() {
+            config.security_events.http_client.tcp_keepalive_secs = keepalive;
+        }
     }
 }
 
@@ -265,6 +6175,23 @@ impl Config {
             ));
         }
 
+        // TLS needs both a certificate and its key
+        if self.server.tls_cert_file.is_some() != self.server.tls_key_file.is_some() {
+            return Err(ConfigError::ValidationError(
+                "TLS requires both a certificate file and a key file".into(),
+            ));
+        }
+        if self.server.tls_client_ca_file.is_some() && self.server.tls_cert_file.is_none() {
+            return Err(ConfigError::ValidationError(
+                "Client certificate authentication requires TLS to be configured".into(),
+            ));
+        }
+        if self.server.tls_cert_file.is_some() && self.server.tls_reload_interval_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "TLS reload interval must be greater than 0".into(),
+            ));
+        }
+
         // Validate ollama config
         if self.ollama.base_url.is_empty() {
             return Err(ConfigError::ValidationError(
@@ -278,27 +6205,301 @@ impl Config {
                 "Ollama base URL must start with http:// or https://".into(),
             ));
         }
-
-        // Validate security config - API credentials
-        if self.security.base_url.is_empty() || self.security.api_key.is_empty() {
+        if let Some(backend) = self
+            .ollama
+            .backends
+            .iter()
+            .find(|backend| !backend.starts_with("http"))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Ollama backend URL must start with http:// or https://: {}",
+                backend
+            )));
+        }
+        for route in &self.ollama.model_routes {
+            if route.model.trim().is_empty() || route.backends.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Ollama model routes need a model and at least one backend".into(),
+                ));
+            }
+            if let Some(backend) = route
+                .backends
+                .iter()
+                .find(|backend| !backend.starts_with("http"))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Ollama backend URL of model route {} must start with http:// or https://: {}",
+                    route.model, backend
+                )));
+            }
+        }
+        for (name, value) in &self.ollama.headers {
+            if axum::http::HeaderName::try_from(name.as_str()).is_err()
+                || axum::http::HeaderValue::try_from(value.as_str()).is_err()
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Ollama header {} is not a valid header",
+                    name
+                )));
+            }
+        }
+        let basic_auth = !self.ollama.basic_auth_user.is_empty();
+        if !basic_auth && !self.ollama.basic_auth_password.is_empty() {
             return Err(ConfigError::ValidationError(
-                "Security credentials missing (base_url or api_key)".into(),
+                "Ollama basic_auth_password requires basic_auth_user".into(),
             ));
         }
-
-        // Ensure security URL is properly formatted
-        if !self.security.base_url.starts_with("http") {
+        let authorization_header = self
+            .ollama
+            .headers
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case("authorization"));
+        let credentials = [
+            basic_auth,
+            !self.ollama.bearer_token.is_empty(),
+            authorization_header,
+        ];
+        if credentials.into_iter().filter(|set| *set).count() > 1 {
             return Err(ConfigError::ValidationError(
-                "Security base URL must start with http:// or https://".into(),
+                "Set only one of Ollama bearer_token, basic auth or an Authorization header".into(),
             ));
         }
+        let retry = &self.ollama.retry;
+        if retry.max_backoff_ms < retry.initial_backoff_ms {
+            return Err(ConfigError::ValidationError(
+                "Ollama retry max_backoff_ms must not be less than initial_backoff_ms".into(),
+            ));
+        }
+        if let Some(status) = retry
+            .retry_on_status
+            .iter()
+            .find(|status| !(400..=599).contains(*status))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Ollama retry_on_status must list error statuses, got {}",
+                status
+            )));
+        }
 
-        // Validate PANW AI profile config
-        if self.security.profile_name.is_empty() {
+        // Validate mock backend config
+        let mock = &self.ollama.mock;
+        if !mock.tokens_per_second.is_finite() || mock.tokens_per_second < 0.0 {
             return Err(ConfigError::ValidationError(
-                "Security profile_name is required".into(),
+                "Mock tokens_per_second must be a non-negative number".into(),
             ));
         }
+        if mock.models.is_empty() {
+            return Err(ConfigError::ValidationError(
+                "Mock backend needs at least one model".into(),
+            ));
+        }
+        if mock.triggers.iter().any(|trigger| trigger.phrase.trim().is_empty()) {
+            return Err(ConfigError::ValidationError(
+                "Mock trigger phrases must not be empty".into(),
+            ));
+        }
+
+        // Validate the providers scanning in parallel
+        let fan_out = &self.security.fan_out;
+        if !fan_out.providers.is_empty() {
+            if fan_out.providers.len() < 2 {
+                return Err(ConfigError::ValidationError(
+                    "Security fan-out requires at least two providers".into(),
+                ));
+            }
+            for (index, provider) in fan_out.providers.iter().enumerate() {
+                if fan_out.providers[..index].contains(provider) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Security fan-out lists provider {} more than once",
+                        provider.as_str()
+                    )));
+                }
+            }
+            for (provider, weight) in &fan_out.weights {
+                if !fan_out
+                    .providers
+                    .iter()
+                    .any(|listed| listed.as_str() == provider)
+                {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Security fan-out weight given for provider {} outside the fan-out",
+                        provider
+                    )));
+                }
+                if !weight.is_finite() || *weight < 0.0 {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Security fan-out weight of {} must not be negative",
+                        provider
+                    )));
+                }
+            }
+            if fan_out.policy == VerdictPolicy::Weighted
+                && !(fan_out.threshold > 0.0 && fan_out.threshold <= 1.0)
+            {
+                return Err(ConfigError::ValidationError(
+                    "Security fan-out threshold must be above 0 and at most 1".into(),
+                ));
+            }
+        }
+
+        // Validate security config - API credentials of the selected providers
+        if self.security.uses(SecurityProvider::OpenaiModeration) {
+            let moderation = &self.security.openai_moderation;
+            if moderation.api_key.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "OpenAI moderation provider requires an api_key".into(),
+                ));
+            }
+            if !moderation.base_url.starts_with("http") {
+                return Err(ConfigError::ValidationError(
+                    "OpenAI moderation base URL must start with http:// or https://".into(),
+                ));
+            }
+            if moderation.model.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "OpenAI moderation model must not be empty".into(),
+                ));
+            }
+        }
+        if self.security.uses(SecurityProvider::BedrockGuardrails) {
+            let guardrails = &self.security.bedrock_guardrails;
+            if guardrails.region.is_empty() || guardrails.guardrail_id.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Bedrock guardrails provider requires a region and guardrail_id".into(),
+                ));
+            }
+            if guardrails.guardrail_version.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Bedrock guardrail version must not be empty".into(),
+                ));
+            }
+            if guardrails.access_key_id.is_empty() || guardrails.secret_access_key.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Bedrock guardrails provider requires AWS credentials \
+                     (access_key_id and secret_access_key)"
+                        .into(),
+                ));
+            }
+            if !guardrails.endpoint.is_empty() && !guardrails.endpoint.starts_with("http") {
+                return Err(ConfigError::ValidationError(
+                    "Bedrock guardrails endpoint must start with http:// or https://".into(),
+                ));
+            }
+        }
+        if self.security.uses(SecurityProvider::AzureContentSafety) {
+            let safety = &self.security.azure_content_safety;
+            if safety.endpoint.is_empty() || safety.api_key.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Azure Content Safety provider requires an endpoint and api_key".into(),
+                ));
+            }
+            if !safety.endpoint.starts_with("http") {
+                return Err(ConfigError::ValidationError(
+                    "Azure Content Safety endpoint must start with http:// or https://".into(),
+                ));
+            }
+            if safety.api_version.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Azure Content Safety API version must not be empty".into(),
+                ));
+            }
+            if safety.severity_threshold == 0 {
+                return Err(ConfigError::ValidationError(
+                    "Azure Content Safety severity threshold must be greater than 0".into(),
+                ));
+            }
+            for (category, threshold) in &safety.category_thresholds {
+                if !AZURE_HARM_CATEGORIES.contains(&category.as_str()) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Unknown Azure Content Safety harm category: {} (expected one of {})",
+                        category,
+                        AZURE_HARM_CATEGORIES.join(", ")
+                    )));
+                }
+                if *threshold == 0 {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Azure Content Safety threshold of {} must be greater than 0",
+                        category
+                    )));
+                }
+            }
+        }
+        if self.security.uses(SecurityProvider::LlamaGuard) {
+            let llama_guard = &self.security.llama_guard;
+            if llama_guard.model.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Llama Guard model must not be empty".into(),
+                ));
+            }
+            for category in &llama_guard.block_categories {
+                let valid = category
+                    .trim()
+                    .strip_prefix(['S', 's'])
+                    .is_some_and(|number| number.parse::<u8>().is_ok());
+                if !valid {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Invalid Llama Guard hazard category: {} (expected a code such as S1)",
+                        category
+                    )));
+                }
+            }
+            if self.ollama.provider == OllamaProvider::Mock {
+                return Err(ConfigError::ValidationError(
+                    "Llama Guard provider requires a real Ollama backend, not the mock".into(),
+                ));
+            }
+        }
+        if self.security.uses(SecurityProvider::Panw) {
+            if self.security.base_url.is_empty() || self.security.api_key.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Security credentials missing (base_url or api_key)".into(),
+                ));
+            }
+
+            // Ensure security URL is properly formatted
+            if !self.security.base_url.starts_with("http") {
+                return Err(ConfigError::ValidationError(
+                    "Security base URL must start with http:// or https://".into(),
+                ));
+            }
+
+            // Validate PANW AI profile config
+            if self.security.profile_name.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Security profile_name is required".into(),
+                ));
+            }
+        }
+
+        if !self.security.profile_header.is_empty() {
+            if axum::http::HeaderName::try_from(self.security.profile_header.as_str()).is_err() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Security profile_header is not a valid header name: {}",
+                    self.security.profile_header
+                )));
+            }
+            if self.security.allowed_profiles.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Security profile_header requires at least one allowed profile".into(),
+                ));
+            }
+        }
+        for (index, mapping) in self.security.model_profiles.iter().enumerate() {
+            if mapping.model.trim().is_empty() || mapping.profile.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Security model profiles must name a model and a profile".into(),
+                ));
+            }
+            if self.security.model_profiles[..index]
+                .iter()
+                .any(|other| other.model == mapping.model)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate security model profile: {}",
+                    mapping.model
+                )));
+            }
+        }
 
         if self.security.app_name.is_empty() {
             return Err(ConfigError::ValidationError(
@@ -312,6 +6513,833 @@ impl Config {
             ));
         }
 
+        // Validate model management config
+        if self.models.confirm_destructive && self.models.confirmation_ttl_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Models confirmation_ttl_secs must be greater than zero".into(),
+            ));
+        }
+
+        for (index, streaming) in self.models.streaming.iter().enumerate() {
+            if streaming.model.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Model streaming strategies must name a model".into(),
+                ));
+            }
+            if self.models.streaming[..index]
+                .iter()
+                .any(|other| other.model == streaming.model)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate model streaming strategy: {}",
+                    streaming.model
+                )));
+            }
+            if streaming.assessment_window_chars == Some(0)
+                || streaming.risk_window_chars == Some(0)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Streaming windows of model {} must be greater than 0",
+                    streaming.model
+                )));
+            }
+        }
+
+        // Validate maintenance windows
+        if let Some(window) = self
+            .maintenance
+            .windows
+            .iter()
+            .find(|window| window.end <= window.start)
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Maintenance window starting at {} must end after it starts",
+                window.start
+            )));
+        }
+
+        // Validate duplicate-request suppression config
+        if self.dedup.enabled && self.dedup.window_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Dedup window_secs must be greater than zero".into(),
+            ));
+        }
+
+        if self.idempotency.enabled && self.idempotency.ttl_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Idempotency ttl_secs must be greater than zero".into(),
+            ));
+        }
+
+        if self.provenance.enabled && self.provenance.retention_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Provenance retention_secs must be greater than zero".into(),
+            ));
+        }
+
+        // Validate transcript config
+        if self.transcripts.enabled {
+            if self.transcripts.file.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Transcripts file must not be empty".into(),
+                ));
+            }
+            if axum::http::HeaderName::try_from(self.transcripts.session_header.as_str()).is_err()
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid transcripts session header: {}",
+                    self.transcripts.session_header
+                )));
+            }
+            let hmac_key = &self.transcripts.hmac_key;
+            if !hmac_key.is_empty() && !crate::vault::is_reference(hmac_key) && hmac_key.len() < 32
+            {
+                return Err(ConfigError::ValidationError(
+                    "Transcripts HMAC key must be at least 32 bytes".into(),
+                ));
+            }
+        }
+
+        // Validate batch generation config
+        if self.batch.max_prompts == 0 || self.batch.concurrency == 0 {
+            return Err(ConfigError::ValidationError(
+                "Batch max_prompts and concurrency must be greater than zero".into(),
+            ));
+        }
+
+        // Validate generation job config
+        if self.jobs.ttl_secs == 0 || self.jobs.max_per_client == 0 {
+            return Err(ConfigError::ValidationError(
+                "Jobs ttl_secs and max_per_client must be greater than zero".into(),
+            ));
+        }
+
+        // Validate health check config
+        if self.health.timeout_secs == 0 {
+            return Err(ConfigError::ValidationError(
+                "Health timeout_secs must be greater than zero".into(),
+            ));
+        }
+
+        // Validate request metrics config
+        if axum::http::HeaderName::try_from(self.metrics.tenant_header.as_str()).is_err() {
+            return Err(ConfigError::ValidationError(format!(
+                "Metrics tenant_header is not a valid header name: {}",
+                self.metrics.tenant_header
+            )));
+        }
+
+        // Validate context window check config
+        if self.context.enabled && self.context.chars_per_token == 0 {
+            return Err(ConfigError::ValidationError(
+                "Context chars_per_token must be greater than zero".into(),
+            ));
+        }
+
+        // Validate language policy config
+        if !(0.0..=1.0).contains(&self.security.language.min_confidence) {
+            return Err(ConfigError::ValidationError(
+                "Language min_confidence must be between 0.0 and 1.0".into(),
+            ));
+        }
+        if let Some(code) = self
+            .security
+            .language
+            .allowed_languages
+            .iter()
+            .find(|code| whatlang::Lang::from_code(code.as_str()).is_none())
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Unknown language code: {} (expected ISO 639-3, e.g. eng)",
+                code
+            )));
+        }
+
+        // Validate toxicity pre-filter config
+        let toxicity = &self.security.toxicity;
+        let tenant_terms = toxicity.tenants.values().flat_map(|tenant| &tenant.terms);
+        if toxicity
+            .terms
+            .iter()
+            .chain(tenant_terms)
+            .any(|term| term.term.trim().is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "Toxicity terms must not be empty".into(),
+            ));
+        }
+
+        // Validate local pre-filter rules
+        let rules = &self.security.prefilter_rules.rules;
+        for (index, rule) in rules.iter().enumerate() {
+            if rule.name.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Pre-filter rules require a name".into(),
+                ));
+            }
+            if rules[..index].iter().any(|other| other.name == rule.name) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Pre-filter rule {} is defined more than once",
+                    rule.name
+                )));
+            }
+            if rule.patterns.is_empty() && rule.keywords.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Pre-filter rule {} requires patterns or keywords",
+                    rule.name
+                )));
+            }
+            if rule.keywords.iter().any(|keyword| keyword.trim().is_empty()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Keywords of pre-filter rule {} must not be empty",
+                    rule.name
+                )));
+            }
+            for pattern in &rule.patterns {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "Invalid pattern {:?} in pre-filter rule {}: {}",
+                        pattern, rule.name, e
+                    )));
+                }
+            }
+        }
+
+        // Validate local PII masking
+        let pii = &self.security.pii;
+        if pii.enabled && pii.entities.is_empty() {
+            return Err(ConfigError::ValidationError(
+                "PII masking is enabled but no entity types are configured".into(),
+            ));
+        }
+
+        // Validate encoded payload decoding
+        let decode_payloads = &self.security.decode_payloads;
+        if decode_payloads.enabled && decode_payloads.min_length < 4 {
+            return Err(ConfigError::ValidationError(
+                "Encoded payload min_length must be at least 4".into(),
+            ));
+        }
+
+        // Validate scan cache config
+        let scan_cache = &self.security.scan_cache;
+        if scan_cache.enabled && (scan_cache.max_entries == 0 || scan_cache.ttl_secs == 0) {
+            return Err(ConfigError::ValidationError(
+                "Scan cache max_entries and ttl_secs must be greater than 0".into(),
+            ));
+        }
+
+        // Validate asynchronous streaming config
+        let async_streaming = &self.security.async_streaming;
+        let windowed_models = self
+            .models
+            .streaming
+            .iter()
+            .any(|streaming| streaming.strategy == StreamingStrategy::Windowed);
+        if (async_streaming.enabled || windowed_models)
+            && (async_streaming.poll_interval_ms == 0 || async_streaming.settle_timeout_secs == 0)
+        {
+            return Err(ConfigError::ValidationError(
+                "Async streaming poll_interval_ms and settle_timeout_secs must be greater than 0"
+                    .into(),
+            ));
+        }
+
+        let capture = &self.security.response_capture;
+        if capture.enabled && capture.capacity == 0 {
+            return Err(ConfigError::ValidationError(
+                "Response capture capacity must be greater than 0".into(),
+            ));
+        }
+        if capture.sample_percent > 100 {
+            return Err(ConfigError::ValidationError(
+                "Response capture sample_percent must be between 0 and 100".into(),
+            ));
+        }
+
+        let breaker = &self.security.circuit_breaker;
+        if breaker.enabled && (breaker.failure_threshold == 0 || breaker.cooldown_secs == 0) {
+            return Err(ConfigError::ValidationError(
+                "Circuit breaker failure_threshold and cooldown_secs must be greater than 0"
+                    .into(),
+            ));
+        }
+
+        if self.security.failure_mode == FailureMode::OpenLog
+            && self.security_events.webhook_url.is_empty()
+            && self.security_events.syslog_address.is_empty()
+        {
+            return Err(ConfigError::ValidationError(
+                "Failure mode fail_open_log requires a security events webhook or syslog address"
+                    .into(),
+            ));
+        }
+
+        if breaker.enabled
+            && breaker.open_mode != DegradedMode::Block
+            && self.security.failure_mode == FailureMode::Closed
+        {
+            return Err(ConfigError::ValidationError(
+                "Circuit breaker open_mode monitor and local_only pass content through \
+                 unscanned and require failure_mode fail_open or fail_open_log"
+                    .into(),
+            ));
+        }
+
+        if let Some(network) = self
+            .security
+            .scan_bypass
+            .networks
+            .iter()
+            .find(|network| crate::scan_bypass::Network::parse(network).is_none())
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Scan bypass network is not a CIDR network or IP address: {}",
+                network
+            )));
+        }
+
+        // Validate automatic model pull config
+        if self.auto_pull.enabled && self.auto_pull.allowed_models.is_empty() {
+            return Err(ConfigError::ValidationError(
+                "Auto pull requires at least one entry in allowed_models".into(),
+            ));
+        }
+
+        // Validate client authentication config
+        let jwt = &self.client_auth.jwt;
+        // Keys may also be created through the admin API when they are persisted
+        let has_keys =
+            !self.client_auth.keys.is_empty() || !self.client_auth.key_state_file.is_empty();
+        if self.client_auth.enabled && !has_keys && !jwt.enabled {
+            return Err(ConfigError::ValidationError(
+                "Client authentication requires at least one key, a key state file or JWT \
+                 validation"
+                    .into(),
+            ));
+        }
+        if jwt.enabled {
+            if !self.client_auth.enabled {
+                return Err(ConfigError::ValidationError(
+                    "JWT validation requires client authentication to be enabled".into(),
+                ));
+            }
+            if jwt.issuer.is_empty() || jwt.jwks_url.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "JWT validation requires an issuer and a JWKS URL".into(),
+                ));
+            }
+            if jwt.subject_claim.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "JWT subject claim cannot be empty".into(),
+                ));
+            }
+            if jwt
+                .group_profiles
+                .iter()
+                .any(|mapping| mapping.group.is_empty() || mapping.profile.is_empty())
+            {
+                return Err(ConfigError::ValidationError(
+                    "JWT group profiles must name a group and a profile".into(),
+                ));
+            }
+        }
+        for (index, key) in self.client_auth.keys.iter().enumerate() {
+            if key.id.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Client keys must have an id".into(),
+                ));
+            }
+            if key.key.is_empty() == key.key_sha256.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Client key {} must define exactly one of key and key_sha256",
+                    key.id
+                )));
+            }
+            if !key.key_sha256.is_empty()
+                && (key.key_sha256.len() != 64
+                    || !key.key_sha256.chars().all(|c| c.is_ascii_hexdigit()))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Client key {} has an invalid key_sha256; expected 64 hex digits",
+                    key.id
+                )));
+            }
+            if let Some(scope) = key
+                .scopes
+                .iter()
+                .find(|scope| !KEY_SCOPES.contains(&scope.as_str()))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Client key {} has an unknown scope {}; expected one of {}",
+                    key.id,
+                    scope,
+                    KEY_SCOPES.join(", ")
+                )));
+            }
+            if key.models.iter().any(|model| model.trim().is_empty()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Client key {} has an empty model pattern",
+                    key.id
+                )));
+            }
+            if self.client_auth.keys[..index]
+                .iter()
+                .any(|other| other.id == key.id)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate client key id: {}",
+                    key.id
+                )));
+            }
+        }
+        let app_user_header = &self.client_auth.app_user_header;
+        if app_user_header.enabled {
+            if axum::http::HeaderName::try_from(app_user_header.name.as_str()).is_err() {
+                return Err(ConfigError::ValidationError(format!(
+                    "App user header name is not a valid header name: {}",
+                    app_user_header.name
+                )));
+            }
+            if app_user_header.trusted_clients.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "App user header requires at least one trusted client".into(),
+                ));
+            }
+            if !self.client_auth.enabled && self.server.tls_client_ca_file.is_none() {
+                return Err(ConfigError::ValidationError(
+                    "App user header requires client authentication or client certificates \
+                     to identify trusted clients"
+                        .into(),
+                ));
+            }
+        }
+        if self.client_auth.basic_auth && (!self.client_auth.enabled || !has_keys) {
+            return Err(ConfigError::ValidationError(
+                "Basic authentication requires client authentication with at least one key".into(),
+            ));
+        }
+        let trusted_proxy = &self.client_auth.trusted_proxy;
+        if trusted_proxy.enabled {
+            if axum::http::HeaderName::try_from(trusted_proxy.header.as_str()).is_err() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Trusted proxy header is not a valid header name: {}",
+                    trusted_proxy.header
+                )));
+            }
+            if trusted_proxy.addresses.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Trusted proxy requires at least one proxy address".into(),
+                ));
+            }
+            if let Some(address) = trusted_proxy
+                .addresses
+                .iter()
+                .find(|address| address.parse::<std::net::IpAddr>().is_err())
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Trusted proxy address is not an IP address: {}",
+                    address
+                )));
+            }
+        }
+        let access_tokens = &self.client_auth.access_tokens;
+        if access_tokens.enabled {
+            if !self.client_auth.enabled {
+                return Err(ConfigError::ValidationError(
+                    "Access tokens require client authentication to be enabled".into(),
+                ));
+            }
+            if access_tokens.ttl_secs == 0 {
+                return Err(ConfigError::ValidationError(
+                    "Access token ttl_secs must be greater than 0".into(),
+                ));
+            }
+            if !access_tokens.signing_secret.is_empty() && access_tokens.signing_secret.len() < 32
+            {
+                return Err(ConfigError::ValidationError(
+                    "Access token signing secret must be at least 32 bytes".into(),
+                ));
+            }
+        }
+        let lockout = &self.client_auth.lockout;
+        if lockout.enabled {
+            if lockout.max_failures == 0 || lockout.window_secs == 0 {
+                return Err(ConfigError::ValidationError(
+                    "Auth lockout max_failures and window_secs must be greater than 0".into(),
+                ));
+            }
+            if lockout.base_lockout_secs == 0
+                || lockout.max_lockout_secs < lockout.base_lockout_secs
+            {
+                return Err(ConfigError::ValidationError(
+                    "Auth lockout base_lockout_secs must be greater than 0 and at most \
+                     max_lockout_secs"
+                        .into(),
+                ));
+            }
+        }
+
+        // Validate usage report config
+        let epsilon = self.usage.report_noise_epsilon;
+        if !epsilon.is_finite() || epsilon < 0.0 {
+            return Err(ConfigError::ValidationError(
+                "Usage report noise epsilon must be a non-negative number".into(),
+            ));
+        }
+
+        // Validate rate limit config
+        let rate_limit = &self.rate_limit;
+        if rate_limit.requests_per_minute == 0 || rate_limit.burst == 0 {
+            return Err(ConfigError::ValidationError(
+                "Rate limit requests per minute and burst must be greater than 0".into(),
+            ));
+        }
+        for limit in &rate_limit.routes {
+            if !limit.route.starts_with('/') {
+                return Err(ConfigError::ValidationError(format!(
+                    "Rate limit route must start with '/': {}",
+                    limit.route
+                )));
+            }
+            if limit.requests_per_minute == 0 || limit.burst == 0 {
+                return Err(ConfigError::ValidationError(format!(
+                    "Rate limit of {} must allow at least one request",
+                    limit.route
+                )));
+            }
+        }
+
+        // Validate access window config
+        let access_windows = &self.access_windows;
+        for (index, policy) in access_windows.policies.iter().enumerate() {
+            if policy.name.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Access policies must have a name".into(),
+                ));
+            }
+            if access_windows.policies[..index]
+                .iter()
+                .any(|other| other.name == policy.name)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate access policy: {}",
+                    policy.name
+                )));
+            }
+            policy.timezone(&access_windows.timezone)?;
+            if policy.windows.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Access policy {} must define at least one window",
+                    policy.name
+                )));
+            }
+            for window in &policy.windows {
+                window.weekdays()?;
+                window.minutes()?;
+            }
+        }
+
+        // Validate state file encryption config
+        let encryption = &self.encryption;
+        for (index, key) in encryption.keys.iter().enumerate() {
+            if key.id.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Encryption keys must have an id".into(),
+                ));
+            }
+            if key.key.is_empty() == key.key_file.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Encryption key {} must define exactly one of key and key_file",
+                    key.id
+                )));
+            }
+            if encryption.keys[..index].iter().any(|other| other.id == key.id) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate encryption key id: {}",
+                    key.id
+                )));
+            }
+        }
+        if !encryption.active_key.is_empty()
+            && !encryption.keys.iter().any(|key| key.id == encryption.active_key)
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Active encryption key {} is not configured",
+                encryption.active_key
+            )));
+        }
+
+        // Validate timeout config
+        if let Some(timeout) = self
+            .timeouts
+            .routes
+            .iter()
+            .find(|timeout| !timeout.route.starts_with('/'))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Timeout route must start with '/': {}",
+                timeout.route
+            )));
+        }
+
+        // Validate branding config
+        let branding = &self.branding;
+        for settings in std::iter::once(&branding.defaults).chain(branding.tenants.values()) {
+            if settings
+                .model_name
+                .as_ref()
+                .is_some_and(|name| name.trim().is_empty())
+            {
+                return Err(ConfigError::ValidationError(
+                    "Branding model_name must not be empty".into(),
+                ));
+            }
+        }
+
+        // Validate feature flags
+        for (index, flag) in self.feature_flags.iter().enumerate() {
+            if flag.name.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Feature flags must have a name".into(),
+                ));
+            }
+            if flag.rollout_percent > 100 {
+                return Err(ConfigError::ValidationError(format!(
+                    "Rollout percent of feature flag {} must be between 0 and 100",
+                    flag.name
+                )));
+            }
+            if self.feature_flags[..index]
+                .iter()
+                .any(|other| other.name == flag.name)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate feature flag: {}",
+                    flag.name
+                )));
+            }
+        }
+
+        // Validate header passthrough config
+        let passthrough = &self.passthrough;
+        for name in passthrough
+            .request_headers
+            .iter()
+            .chain(&passthrough.response_headers)
+        {
+            if axum::http::HeaderName::try_from(name.as_str()).is_err() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Passthrough header is not a valid header name: {}",
+                    name
+                )));
+            }
+            if MANAGED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Passthrough header {} is managed by the proxy and cannot be passed through",
+                    name
+                )));
+            }
+        }
+
+        // Validate security event export config
+        let events = &self.security_events;
+        if !events.webhook_url.is_empty() && !events.webhook_url.starts_with("http") {
+            return Err(ConfigError::ValidationError(
+                "Security events webhook_url must start with http:// or https://".into(),
+            ));
+        }
+        if !events.syslog_address.is_empty() && events.syslog_address.rsplit_once(':').is_none() {
+            return Err(ConfigError::ValidationError(
+                "Security events syslog_address must be given as host:port".into(),
+            ));
+        }
+        if let Some(event) = events
+            .severities
+            .keys()
+            .find(|event| !SECURITY_EVENT_TYPES.contains(&event.as_str()))
+        {
+            return Err(ConfigError::ValidationError(format!(
+                "Unknown security event type {}; expected one of {}",
+                event,
+                SECURITY_EVENT_TYPES.join(", ")
+            )));
+        }
+        for (field, name) in &events.field_names {
+            if !SECURITY_EVENT_FIELDS.contains(&field.as_str()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Unknown security event field {}; expected one of {}",
+                    field,
+                    SECURITY_EVENT_FIELDS.join(", ")
+                )));
+            }
+            if name.trim().is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "Security event field {} must not be renamed to an empty name",
+                    field
+                )));
+            }
+        }
+
+        // Validate notification config
+        let notifications = &self.notifications;
+        for (index, channel) in notifications.channels.iter().enumerate() {
+            if channel.name.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Notification channels must have a name".into(),
+                ));
+            }
+            if notifications.channels[..index]
+                .iter()
+                .any(|other| other.name == channel.name)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate notification channel: {}",
+                    channel.name
+                )));
+            }
+            if !channel.url.is_empty() && !channel.url.starts_with("http") {
+                return Err(ConfigError::ValidationError(format!(
+                    "Notification channel {} url must start with http:// or https://",
+                    channel.name
+                )));
+            }
+            let missing = match channel.kind {
+                NotificationChannelKind::Webhook => channel.url.is_empty().then_some("url"),
+                NotificationChannelKind::Pagerduty => {
+                    channel.routing_key.is_empty().then_some("routing_key")
+                }
+                NotificationChannelKind::Smtp => {
+                    if channel.smtp.host.is_empty() {
+                        Some("smtp host")
+                    } else if channel.smtp.from.is_empty() {
+                        Some("smtp from address")
+                    } else if channel.smtp.to.is_empty() {
+                        Some("smtp to address")
+                    } else {
+                        None
+                    }
+                }
+            };
+            if let Some(missing) = missing {
+                return Err(ConfigError::ValidationError(format!(
+                    "Notification channel {} requires a {}",
+                    channel.name, missing
+                )));
+            }
+        }
+        for rule in &notifications.rules {
+            if rule.channels.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Alert rules must name at least one channel".into(),
+                ));
+            }
+            if let Some(channel) = rule
+                .channels
+                .iter()
+                .find(|name| !notifications.channels.iter().any(|c| &c.name == *name))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Alert rule refers to unknown notification channel {}",
+                    channel
+                )));
+            }
+            if let Some(alert) = rule
+                .alerts
+                .iter()
+                .find(|alert| !NOTIFICATION_ALERTS.contains(&alert.as_str()))
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Unknown alert {}; expected one of {}",
+                    alert,
+                    NOTIFICATION_ALERTS.join(", ")
+                )));
+            }
+        }
+
+        // Validate concurrency config
+        for (index, limit) in self.concurrency.model_limits.iter().enumerate() {
+            if limit.model.trim().is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Concurrency model limits must name a model".into(),
+                ));
+            }
+            if self.concurrency.model_limits[..index]
+                .iter()
+                .any(|other| other.model == limit.model)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate concurrency limit for model {}",
+                    limit.model
+                )));
+            }
+        }
+
+        // Validate Vault config
+        let vault = &self.vault;
+        let references_vault = std::iter::once(&self.security.api_key)
+            .chain(&self.server.tls_key_file)
+            .chain(self.encryption.keys.iter().map(|key| &key.key))
+            .any(|value| value.starts_with("vault:"));
+        if references_vault && vault.address.is_empty() {
+            return Err(ConfigError::ValidationError(
+                "Secrets reference Vault but no Vault address is configured".into(),
+            ));
+        }
+        if !vault.address.is_empty()
+            && vault.token.is_empty()
+            && (vault.role_id.is_empty() || vault.secret_id.is_empty())
+        {
+            return Err(ConfigError::ValidationError(
+                "Vault requires a token or an AppRole role_id and secret_id".into(),
+            ));
+        }
+
+        // Validate scheduled prompts
+        for (index, schedule) in self.scheduler.schedules.iter().enumerate() {
+            schedule.validate()?;
+            if self.scheduler.schedules[..index]
+                .iter()
+                .any(|other| other.name == schedule.name)
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "Duplicate schedule name: {}",
+                    schedule.name
+                )));
+            }
+        }
+
+        let leader_election = &self.scheduler.leader_election;
+        if leader_election.enabled {
+            if leader_election.lease_file.is_empty() {
+                return Err(ConfigError::ValidationError(
+                    "Leader election requires a lease file".into(),
+                ));
+            }
+            if leader_election.lease_ttl_secs < 3 {
+                return Err(ConfigError::ValidationError(
+                    "Leader lease TTL must be at least 3 seconds".into(),
+                ));
+            }
+        }
+
+        // Validate response post-processing config
+        if self.postprocess.url_mode == UrlRewriteMode::Redirect
+            && !self.postprocess.url_redirect_template.starts_with("http")
+        {
+            return Err(ConfigError::ValidationError(
+                "Postprocess url_redirect_template must start with http:// or https:// in redirect mode".into(),
+            ));
+        }
+        for pattern in &self.postprocess.leakage_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid postprocess leakage pattern {:?}: {}",
+                    pattern, e
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,98 @@
+// Short-lived confirmation tokens for destructive model operations.
+//
+// Destructive calls such as deleting a model or copying over an existing name
+// follow a two-step flow: the first call is answered with a token bound to the
+// exact operation, and only a second call echoing that token is forwarded.
+//
+// # Usage
+//
+// ```rust
+// let store = ConfirmationStore::new(Duration::from_secs(60));
+//
+// let token = store.issue("delete:llama3:latest");
+// assert!(store.redeem(&token, "delete:llama3:latest"));
+// assert!(!store.redeem(&token, "delete:llama3:latest")); // tokens are single-use
+// ```
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+// A confirmation token waiting to be redeemed.
+#[derive(Debug)]
+struct PendingConfirmation {
+    // Operation fingerprint the token was issued for
+    operation: String,
+
+    // Point in time after which the token is no longer accepted
+    expires_at: Instant,
+}
+
+// Store of outstanding confirmation tokens shared across requests.
+//
+// Cloning the store is cheap and all clones share the same tokens.
+#[derive(Clone)]
+pub struct ConfirmationStore {
+    // How long an issued token remains valid
+    ttl: Duration,
+
+    // Outstanding tokens keyed by token value
+    pending: Arc<Mutex<HashMap<String, PendingConfirmation>>>,
+}
+
+impl ConfirmationStore {
+    // Creates an empty store issuing tokens valid for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns how long issued tokens remain valid.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Issues a new token bound to the given operation fingerprint.
+    //
+    // Expired tokens are pruned on every call so the store stays bounded by the
+    // number of operations requested within one TTL.
+    pub fn issue(&self, operation: &str) -> String {
+        let token = Uuid::new_v4().simple().to_string();
+        let now = Instant::now();
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, confirmation| confirmation.expires_at > now);
+        pending.insert(
+            token.clone(),
+            PendingConfirmation {
+                operation: operation.to_string(),
+                expires_at: now + self.ttl,
+            },
+        );
+
+        debug!("Issued confirmation token for operation: {}", operation);
+        token
+    }
+
+    // Redeems a token for the given operation fingerprint.
+    //
+    // # Returns
+    //
+    // `true` if the token exists, has not expired and was issued for the same
+    // operation. A matching token is consumed and cannot be redeemed again.
+    pub fn redeem(&self, token: &str, operation: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+
+        let valid = pending.get(token).is_some_and(|confirmation| {
+            confirmation.operation == operation && confirmation.expires_at > Instant::now()
+        });
+        if valid {
+            pending.remove(token);
+        }
+
+        valid
+    }
+}
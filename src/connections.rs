@@ -0,0 +1,537 @@
+// Per-connection statistics and termination of client connections.
+//
+// Operators occasionally need to find and stop a runaway stream without
+// restarting the proxy. Every accepted connection is registered while it is
+// open, with its peer address, the bytes read and written and the most recent
+// request, and listed at `GET /admin/connections` together with the number of
+// open connections per client IP.
+//
+// `DELETE /admin/connections/{id}` terminates a connection: its pending and
+// next reads and writes fail, which closes the socket and ends every request
+// on it, including responses still being streamed. The Ollama request behind
+// a terminated stream is dropped along with it.
+//
+// Bytes are counted on the socket, so over TLS they include the TLS overhead.
+//
+// # Metrics
+//
+// Open connections are reported in `panw_connections_active` and terminated
+// connections are counted in `panw_connections_terminated_total`.
+use crate::metrics;
+use crate::AppState;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::{AddExtension, Next};
+use axum::response::Response;
+use axum::serve::{IncomingStream, Listener};
+use axum::{Extension, Router};
+use axum_server::accept::Accept;
+use chrono::{DateTime, Utc};
+use futures_util::task::AtomicWaker;
+use pin_project::pin_project;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::io::{self, IoSlice};
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tower::{Layer, Service};
+use tracing::error;
+
+// The most recent request received on a connection.
+#[derive(Debug, Clone, Serialize)]
+pub struct LastRequest {
+    // HTTP method of the request
+    pub method: String,
+
+    // Path of the request, without the query
+    pub path: String,
+
+    // When the request was received
+    pub received_at: DateTime<Utc>,
+}
+
+// A connection as reported by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    // Identifies the connection for termination
+    pub id: u64,
+
+    // Address of the client
+    pub peer: SocketAddr,
+
+    // When the connection was accepted
+    pub opened_at: DateTime<Utc>,
+
+    // Bytes received from the client
+    pub bytes_in: u64,
+
+    // Bytes sent to the client
+    pub bytes_out: u64,
+
+    // Requests received on the connection
+    pub requests: u64,
+
+    // The most recent request, if any
+    pub last_request: Option<LastRequest>,
+}
+
+// Open connections of a client IP.
+#[derive(Debug, Clone, Serialize)]
+pub struct IpStatus {
+    // Address of the client
+    pub ip: IpAddr,
+
+    // Open connections from the address
+    pub connections: usize,
+
+    // Bytes received over the open connections
+    pub bytes_in: u64,
+
+    // Bytes sent over the open connections
+    pub bytes_out: u64,
+}
+
+// Open connections as reported by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionReport {
+    // Number of open connections
+    pub active: usize,
+
+    // Open connections per client IP, most connections first
+    pub per_ip: Vec<IpStatus>,
+
+    // Open connections, oldest first
+    pub connections: Vec<ConnectionStatus>,
+}
+
+// An open connection.
+struct Connection {
+    // Identifies the connection for termination
+    id: u64,
+
+    // Address of the client
+    peer: SocketAddr,
+
+    // When the connection was accepted
+    opened_at: DateTime<Utc>,
+
+    // Bytes received from the client
+    bytes_in: AtomicU64,
+
+    // Bytes sent to the client
+    bytes_out: AtomicU64,
+
+    // Requests received on the connection
+    requests: AtomicU64,
+
+    // The most recent request, if any
+    last_request: Mutex<Option<LastRequest>>,
+
+    // Whether an operator terminated the connection
+    terminated: AtomicBool,
+
+    // Wakes the pending read once the connection is terminated
+    read_waker: AtomicWaker,
+
+    // Wakes the pending write once the connection is terminated
+    write_waker: AtomicWaker,
+}
+
+impl Connection {
+    // Returns the connection as reported by the admin API.
+    fn status(&self) -> ConnectionStatus {
+        ConnectionStatus {
+            id: self.id,
+            peer: self.peer,
+            opened_at: self.opened_at,
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            last_request: self
+                .last_request
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+
+    // Fails the connection's IO if it has been terminated, or registers the
+    // waker to be woken on termination.
+    fn check(&self, waker: &AtomicWaker, cx: &Context<'_>) -> io::Result<()> {
+        waker.register(cx.waker());
+        if self.terminated.load(Ordering::Relaxed) {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection terminated by an administrator",
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Registry of the open connections.
+//
+// Cloning the registry is cheap and all clones share the same connections.
+#[derive(Clone, Default)]
+pub struct ConnectionRegistry {
+    // Identifier of the next accepted connection
+    next_id: Arc<AtomicU64>,
+
+    // Open connections keyed by identifier
+    connections: Arc<Mutex<BTreeMap<u64, Arc<Connection>>>>,
+}
+
+impl ConnectionRegistry {
+    // Registers an accepted connection and wraps its IO to count its bytes.
+    pub fn track<I>(&self, io: I, peer: SocketAddr) -> TrackedStream<I> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let connection = Arc::new(Connection {
+            id,
+            peer,
+            opened_at: Utc::now(),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            last_request: Mutex::new(None),
+            terminated: AtomicBool::new(false),
+            read_waker: AtomicWaker::new(),
+            write_waker: AtomicWaker::new(),
+        });
+
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        connections.insert(id, connection.clone());
+        record_active(connections.len());
+
+        TrackedStream {
+            inner: io,
+            connection,
+            _registration: Registration {
+                registry: self.clone(),
+                id,
+            },
+        }
+    }
+
+    // Returns the open connections and their totals per client IP.
+    pub fn report(&self) -> ConnectionReport {
+        let connections: Vec<ConnectionStatus> = {
+            let connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+            connections
+                .values()
+                .map(|connection| connection.status())
+                .collect()
+        };
+
+        let mut per_ip: HashMap<IpAddr, IpStatus> = HashMap::new();
+        for connection in &connections {
+            let ip = connection.peer.ip();
+            let status = per_ip.entry(ip).or_insert(IpStatus {
+                ip,
+                connections: 0,
+                bytes_in: 0,
+                bytes_out: 0,
+            });
+            status.connections += 1;
+            status.bytes_in += connection.bytes_in;
+            status.bytes_out += connection.bytes_out;
+        }
+        let mut per_ip: Vec<IpStatus> = per_ip.into_values().collect();
+        per_ip.sort_by(|a, b| b.connections.cmp(&a.connections).then(a.ip.cmp(&b.ip)));
+
+        ConnectionReport {
+            active: connections.len(),
+            per_ip,
+            connections,
+        }
+    }
+
+    // Terminates an open connection.
+    //
+    // # Returns
+    //
+    // The terminated connection, or `None` if no connection has the identifier
+    pub fn terminate(&self, id: u64) -> Option<ConnectionStatus> {
+        let connection = {
+            let connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+            connections.get(&id)?.clone()
+        };
+
+        if !connection.terminated.swap(true, Ordering::Relaxed) {
+            metrics::increment_counter(
+                "panw_connections_terminated_total",
+                "Client connections terminated through the admin API",
+                &[],
+            );
+        }
+        connection.read_waker.wake();
+        connection.write_waker.wake();
+        Some(connection.status())
+    }
+
+    // Records a request received from a peer.
+    fn record_request(&self, peer: SocketAddr, request: LastRequest) {
+        let connection = {
+            let connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+            connections
+                .values()
+                .find(|connection| connection.peer == peer)
+                .cloned()
+        };
+        if let Some(connection) = connection {
+            connection.requests.fetch_add(1, Ordering::Relaxed);
+            *connection
+                .last_request
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()) = Some(request);
+        }
+    }
+
+    // Removes a closed connection.
+    fn remove(&self, id: u64) {
+        let mut connections = self.connections.lock().unwrap_or_else(|e| e.into_inner());
+        connections.remove(&id);
+        record_active(connections.len());
+    }
+}
+
+// Removes a connection from the registry once its IO is dropped.
+struct Registration {
+    // Registry holding the connection
+    registry: ConnectionRegistry,
+
+    // Identifier of the connection
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.registry.remove(self.id);
+    }
+}
+
+// IO of a registered connection, counting its bytes and failing once the
+// connection is terminated.
+#[pin_project]
+pub struct TrackedStream<I> {
+    #[pin]
+    inner: I,
+    connection: Arc<Connection>,
+    _registration: Registration,
+}
+
+impl<I: AsyncRead> AsyncRead for TrackedStream<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.connection.check(&this.connection.read_waker, cx)?;
+
+        let filled = buf.filled().len();
+        let result = this.inner.poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = (buf.filled().len() - filled) as u64;
+            this.connection.bytes_in.fetch_add(read, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<I: AsyncWrite> AsyncWrite for TrackedStream<I> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        this.connection.check(&this.connection.write_waker, cx)?;
+
+        let result = this.inner.poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            this.connection
+                .bytes_out
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        this.connection.check(&this.connection.write_waker, cx)?;
+
+        let result = this.inner.poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(written)) = result {
+            this.connection
+                .bytes_out
+                .fetch_add(written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.connection.check(&this.connection.write_waker, cx)?;
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+// TCP listener registering every accepted connection.
+pub struct TrackedListener {
+    // Listener accepting the connections
+    inner: TcpListener,
+
+    // Registry receiving the connections
+    registry: ConnectionRegistry,
+}
+
+impl TrackedListener {
+    // Wraps a bound listener.
+    pub fn new(inner: TcpListener, registry: ConnectionRegistry) -> Self {
+        Self { inner, registry }
+    }
+}
+
+impl Listener for TrackedListener {
+    type Io = TrackedStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            match self.inner.accept().await {
+                Ok((stream, peer)) => return (self.registry.track(stream, peer), peer),
+                // Errors of a single connection do not affect the listener
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionRefused
+                            | io::ErrorKind::ConnectionAborted
+                            | io::ErrorKind::ConnectionReset
+                    ) => {}
+                // Other errors, such as running out of file descriptors, are
+                // retried after a pause
+                Err(e) => {
+                    error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
+
+// Hands the connections of a `TrackedListener` to the router, attaching the
+// peer address as `ConnectInfo` like `into_make_service_with_connect_info`.
+#[derive(Clone)]
+pub struct TrackedMakeService {
+    // Router serving the connections
+    app: Router,
+}
+
+impl TrackedMakeService {
+    // Creates the service for the router.
+    pub fn new(app: Router) -> Self {
+        Self { app }
+    }
+}
+
+impl<'a> Service<IncomingStream<'a, TrackedListener>> for TrackedMakeService {
+    type Response = AddExtension<Router, ConnectInfo<SocketAddr>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, stream: IncomingStream<'a, TrackedListener>) -> Self::Future {
+        let peer = *stream.remote_addr();
+        ready(Ok(Extension(ConnectInfo(peer)).layer(self.app.clone())))
+    }
+}
+
+// Acceptor registering every accepted connection before the inner acceptor,
+// such as the TLS handshake, takes over.
+#[derive(Clone)]
+pub struct TrackingAcceptor<A> {
+    // Acceptor handling the registered connections
+    inner: A,
+
+    // Registry receiving the connections
+    registry: ConnectionRegistry,
+}
+
+impl<A> TrackingAcceptor<A> {
+    // Wraps an acceptor.
+    pub fn new(inner: A, registry: ConnectionRegistry) -> Self {
+        Self { inner, registry }
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for TrackingAcceptor<A>
+where
+    A: Accept<TrackedStream<TcpStream>, S>,
+{
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = A::Future;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let peer = stream
+            .peer_addr()
+            .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0)));
+        self.inner
+            .accept(self.registry.track(stream, peer), service)
+    }
+}
+
+// Middleware recording every request on its connection.
+pub async fn record_connection_request(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        state.connections.record_request(
+            *peer,
+            LastRequest {
+                method: request.method().to_string(),
+                path: request.uri().path().to_string(),
+                received_at: Utc::now(),
+            },
+        );
+    }
+    next.run(request).await
+}
+
+// Reports the number of open connections.
+fn record_active(active: usize) {
+    metrics::set_gauge(
+        "panw_connections_active",
+        "Open client connections",
+        &[],
+        active as f64,
+    );
+}
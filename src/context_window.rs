@@ -0,0 +1,103 @@
+// Early rejection of prompts that exceed the target model's context window.
+//
+// Ollama silently truncates prompts that do not fit the context window, so the
+// model answers a different question than the one that was asked and scanned.
+// Instead, the prompt size is estimated before the request is scanned or
+// forwarded, and prompts that do not fit are rejected with a clear 400.
+//
+// # Context Window
+//
+// The window of a request is, in order of precedence:
+//
+// 1. The `num_ctx` option of the request
+// 2. The `num_ctx` parameter of the model
+// 3. The `<architecture>.context_length` reported in the model info
+//
+// Model windows come from the capability cache. When a model's capabilities
+// cannot be learned or carry no window, the request is let through and Ollama
+// reports any problem as usual.
+//
+// # Estimation
+//
+// The prompt size is estimated from its character count with the configured
+// number of characters per token, which errs towards letting prompts through.
+use crate::capabilities::CapabilityCache;
+use crate::config::ContextConfig;
+use crate::handlers::ApiError;
+use crate::ollama::OllamaClient;
+use serde_json::Value;
+use tracing::warn;
+
+// Checks prompts against the context windows of their models.
+//
+// Cloning the checker is cheap and all clones share the same capability cache.
+#[derive(Clone)]
+pub struct ContextWindows {
+    // Whether prompts are checked at all
+    enabled: bool,
+
+    // Characters counted as one token
+    chars_per_token: usize,
+
+    // Source of the model context windows
+    capabilities: CapabilityCache,
+}
+
+impl ContextWindows {
+    // Creates the checker from the configuration.
+    pub fn new(config: &ContextConfig, capabilities: CapabilityCache) -> Self {
+        Self {
+            enabled: config.enabled,
+            chars_per_token: config.chars_per_token.max(1),
+            capabilities,
+        }
+    }
+
+    // Rejects prompts that are estimated to exceed the model's context window.
+    //
+    // # Arguments
+    //
+    // * `ollama` - Client used to learn the model's context window
+    // * `model` - Name of the target model
+    // * `prompt` - Text sent to the model, including system and history
+    // * `options` - Model options of the request, which may set `num_ctx`
+    //
+    // # Errors
+    //
+    // Returns a bad request error if the estimated prompt size exceeds the window.
+    pub async fn check(
+        &self,
+        ollama: &OllamaClient,
+        model: &str,
+        prompt: &str,
+        options: Option<&Value>,
+    ) -> Result<(), ApiError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let estimated = prompt.chars().count().div_ceil(self.chars_per_token) as u64;
+        let window = match options.and_then(|options| options["num_ctx"].as_u64()) {
+            Some(num_ctx) => Some(num_ctx),
+            None => self
+                .capabilities
+                .get(ollama, model)
+                .await
+                .and_then(|capabilities| capabilities.context_window()),
+        };
+
+        match window {
+            Some(window) if estimated > window => {
+                warn!(
+                    "Rejected prompt for {}: estimated {} tokens exceed context window of {}",
+                    model, estimated, window
+                );
+                Err(ApiError::BadRequest(format!(
+                    "Prompt exceeds model context: estimated {} tokens, but {} has a context window of {} tokens",
+                    estimated, model, window
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Cutting off streaming responses.
+//
+// Once a streaming response has started, its status code has already been
+// sent, so a stream that must end early (because it ran into its timeout or
+// the kill switch was activated) is cut off with a final error chunk instead.
+// The chunk is framed like the rest of the response, as an NDJSON line or as a
+// server-sent event, and marked as `done` so clients stop waiting. Dropping the
+// response body aborts the upstream request behind it.
+use crate::sse::SSE_CONTENT_TYPE;
+use axum::{
+    body::{Body, HttpBody},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::Response,
+};
+use bytes::Bytes;
+use futures_util::{Future, StreamExt};
+use serde_json::json;
+
+// Builds the chunk ending a stream that was cut off.
+//
+// # Arguments
+//
+// * `message` - Why the stream was cut off
+// * `status` - Status reported in the chunk
+// * `sse` - Whether the stream is framed as server-sent events
+fn error_chunk(message: &str, status: StatusCode, sse: bool) -> Bytes {
+    let error = json!({
+        "error": message,
+        "status": status.as_u16(),
+        "done": true,
+    });
+    if sse {
+        Bytes::from(format!("data: {}\n\n", error))
+    } else {
+        Bytes::from(format!("{}\n", error))
+    }
+}
+
+// Cuts off a streaming response once `cut` resolves.
+//
+// The body is forwarded until `cut` resolves with the message and status of the
+// error ending the stream. Responses of known length are returned unchanged.
+//
+// # Arguments
+//
+// * `response` - The response to forward
+// * `cut` - Resolves when the stream must end
+pub fn cut_off_stream<F>(response: Response, cut: F) -> Response
+where
+    F: Future<Output = (String, StatusCode)> + Send + 'static,
+{
+    if response.body().size_hint().exact().is_some() {
+        return response;
+    }
+
+    let sse = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(SSE_CONTENT_TYPE.as_bytes()));
+    let (parts, body) = response.into_parts();
+    let state = Some((body.into_data_stream(), Box::pin(cut)));
+    let body = futures_util::stream::unfold(state, move |state| async move {
+        let (mut inner, mut cut) = state?;
+        tokio::select! {
+            biased;
+            (message, status) = &mut cut => Some((Ok(error_chunk(&message, status, sse)), None)),
+            chunk = inner.next() => chunk.map(|chunk| (chunk, Some((inner, cut)))),
+        }
+    });
+    Response::from_parts(parts, Body::from_stream(body))
+}
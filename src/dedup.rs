@@ -0,0 +1,544 @@
+// Duplicate-request suppression for accidental client retries.
+//
+// Chat UIs that retry on a perceived timeout send the exact same request again
+// while the first one is still being scanned or generated, doubling both the
+// PANW scans and the model work. This middleware recognises such duplicates and
+// answers them with the result of the original request.
+//
+// # Matching
+//
+// Two requests are duplicates when they come from the same client address and
+// carry the same method, path and body. Requests are identified by a SHA-256
+// hash of those components, so request bodies are not kept in memory.
+//
+// # Behavior
+//
+// - While the original request is in flight, duplicates wait for it to finish
+//   and then receive a copy of its response. Streaming responses are passed
+//   through to the original client unchanged and delivered to duplicates in one
+//   piece once the stream has ended.
+// - Successful responses are replayed to duplicates for the configured window.
+// - Failed responses are shared with waiting duplicates but not retained, so a
+//   deliberate retry after an error goes through.
+// - If the original client disconnects before the response completes, waiting
+//   duplicates are processed as regular requests.
+//
+// # Idempotency Keys
+//
+// Clients that need at-most-once semantics send an `Idempotency-Key` header.
+// Requests carrying the same key from the same client are matched even when
+// duplicate suppression is disabled, and the stored response, including any
+// security verdict it carries, is replayed for the idempotency TTL whatever its
+// status. Reusing a key with a different request body is rejected with 422.
+//
+// # Forcing Fresh Verdicts
+//
+// A replayed response carries the verdict PANW gave the original request, so
+// after a security profile change policy testers may see stale verdicts.
+// Requests sent with the admin token and an `X-Scan-Bypass-Cache: true` header
+// are never answered from the store: any retained response for them is dropped
+// and the request is scanned and generated again. Retained responses can also
+// be dropped without resending the request through
+// `DELETE /admin/cache/{hash}`, where the hash is the hex-encoded SHA-256 of
+// the request body.
+//
+// The same applies to the PANW verdicts of the scan cache: requests are tagged
+// with the hash of their body and the bypass flag (see `CachedVerdicts`), so
+// bypassing requests are scanned afresh and invalidating a hash drops the
+// verdicts cached for it as well.
+use crate::config::{DedupConfig, IdempotencyConfig};
+use crate::handlers::admin::require_admin;
+use crate::handlers::ApiError;
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body, BodyDataStream},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures_util::Stream;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+// Largest request body considered for duplicate detection.
+const MAX_REQUEST_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+// Header marking responses replayed from an earlier identical request.
+pub const DUPLICATE_HEADER: &str = "x-duplicate-request";
+
+// Request header carrying a client-chosen idempotency key.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+// Header marking responses replayed for a reused idempotency key.
+pub const IDEMPOTENT_REPLAY_HEADER: &str = "idempotent-replayed";
+
+// Request header asking for a fresh response instead of a replay.
+const SCAN_BYPASS_CACHE_HEADER: &str = "x-scan-bypass-cache";
+
+// Longest idempotency key accepted.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+// Hash of the request body and the cache bypass flag, attached to requests
+// for the scan cache of the security client.
+#[derive(Debug, Clone)]
+pub struct CachedVerdicts {
+    // Hex-encoded SHA-256 of the request body
+    pub body_hash: String,
+
+    // Whether cached verdicts must not be reused for the request
+    pub bypass: bool,
+}
+
+// A complete response kept for replaying to duplicates.
+#[derive(Debug)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl CachedResponse {
+    // Builds a response replaying the cached one, marked with the given header.
+    fn replay(&self, marker: &'static str) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response.headers_mut().remove(header::CONTENT_LENGTH);
+        response
+            .headers_mut()
+            .insert(marker, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+// Completion channel for an in-flight request; `None` until the response is complete.
+type Completion = watch::Receiver<Option<Arc<CachedResponse>>>;
+
+// How a request is matched against earlier requests and how long its result is kept.
+struct RequestKey {
+    // Fingerprint the request is stored under
+    key: String,
+
+    // Hash of the request body, used to detect reused idempotency keys and
+    // to invalidate retained responses
+    body_hash: String,
+
+    // How long the completed response is replayed
+    ttl: Duration,
+
+    // Whether failed responses are replayed as well
+    retain_failures: bool,
+
+    // Header marking replayed responses
+    marker: &'static str,
+}
+
+// State of a request fingerprint in the store.
+enum Entry {
+    // The original request is still being processed
+    InFlight {
+        completion: Completion,
+        body_hash: String,
+    },
+    // The original request completed and may be replayed
+    Completed {
+        response: Arc<CachedResponse>,
+        body_hash: String,
+        expires_at: Instant,
+    },
+}
+
+impl Entry {
+    // Returns the hash of the request body the entry was created for.
+    fn body_hash(&self) -> &str {
+        match self {
+            Entry::InFlight { body_hash, .. } | Entry::Completed { body_hash, .. } => body_hash,
+        }
+    }
+}
+
+// Result of looking up a request fingerprint.
+enum Lookup {
+    // First request with this fingerprint; the caller processes it
+    Original(DedupGuard),
+    // An identical request is in flight
+    InFlight(Completion),
+    // An identical request completed within the window
+    Completed(Arc<CachedResponse>),
+    // The idempotency key was already used for a different request body
+    Mismatch,
+}
+
+// Store of recent and in-flight requests shared across requests.
+//
+// Cloning the store is cheap and all clones share the same entries.
+#[derive(Clone)]
+pub struct DedupStore {
+    // Whether duplicate-request suppression is active
+    enabled: bool,
+
+    // How long completed responses are replayed to duplicates
+    window: Duration,
+
+    // Whether the Idempotency-Key header is honoured
+    idempotency_enabled: bool,
+
+    // How long responses are replayed for a reused idempotency key
+    idempotency_ttl: Duration,
+
+    // Entries keyed by request fingerprint
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl DedupStore {
+    // Creates an empty store for the given configuration.
+    pub fn new(config: &DedupConfig, idempotency: &IdempotencyConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            window: Duration::from_secs(config.window_secs),
+            idempotency_enabled: idempotency.enabled,
+            idempotency_ttl: Duration::from_secs(idempotency.ttl_secs),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Looks up a fingerprint, registering it as in flight when it is new.
+    //
+    // Expired entries are pruned on every call.
+    fn begin(&self, request_key: RequestKey) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| match entry {
+            Entry::InFlight { .. } => true,
+            Entry::Completed { expires_at, .. } => *expires_at > now,
+        });
+
+        if let Some(entry) = entries.get(&request_key.key) {
+            if entry.body_hash() != request_key.body_hash {
+                return Lookup::Mismatch;
+            }
+            return match entry {
+                Entry::InFlight { completion, .. } => Lookup::InFlight(completion.clone()),
+                Entry::Completed { response, .. } => Lookup::Completed(response.clone()),
+            };
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        entries.insert(
+            request_key.key.clone(),
+            Entry::InFlight {
+                completion: receiver,
+                body_hash: request_key.body_hash.clone(),
+            },
+        );
+
+        Lookup::Original(DedupGuard {
+            store: self.clone(),
+            request_key,
+            sender,
+            finished: false,
+        })
+    }
+
+    // Drops the completed response retained for a fingerprint, if any.
+    //
+    // In-flight entries are kept so the original request can still complete.
+    fn forget(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if matches!(entries.get(key), Some(Entry::Completed { .. })) {
+            entries.remove(key);
+        }
+    }
+
+    // Drops all completed responses retained for requests with the given body hash.
+    //
+    // # Returns
+    //
+    // The number of responses dropped
+    pub fn invalidate(&self, body_hash: &str) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let before = entries.len();
+        entries.retain(|_, entry| {
+            !matches!(entry, Entry::Completed { .. }) || entry.body_hash() != body_hash
+        });
+        before - entries.len()
+    }
+}
+
+// Ownership of an in-flight fingerprint held by the original request.
+//
+// Dropping the guard without completing it releases the fingerprint so that
+// waiting duplicates are processed on their own.
+struct DedupGuard {
+    store: DedupStore,
+    request_key: RequestKey,
+    sender: watch::Sender<Option<Arc<CachedResponse>>>,
+    finished: bool,
+}
+
+impl DedupGuard {
+    // Publishes the complete response to waiting duplicates and retains it for replays.
+    fn complete(mut self, response: CachedResponse) {
+        let response = Arc::new(response);
+        let request_key = &self.request_key;
+        let mut entries = self.store.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        if response.status.is_success() || request_key.retain_failures {
+            entries.insert(
+                request_key.key.clone(),
+                Entry::Completed {
+                    response: response.clone(),
+                    body_hash: request_key.body_hash.clone(),
+                    expires_at: Instant::now() + request_key.ttl,
+                },
+            );
+        } else {
+            entries.remove(&request_key.key);
+        }
+
+        self.sender.send_replace(Some(response));
+        self.finished = true;
+    }
+}
+
+impl Drop for DedupGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            debug!("Request abandoned before completion, releasing duplicate fingerprint");
+            self.store
+                .entries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&self.request_key.key);
+        }
+    }
+}
+
+// Response body that forwards chunks unchanged while recording a copy.
+//
+// Once the inner body ends, the recorded response is handed to the guard.
+struct RecordingBody {
+    inner: BodyDataStream,
+    status: StatusCode,
+    headers: HeaderMap,
+    recorded: Vec<u8>,
+    guard: Option<DedupGuard>,
+}
+
+impl Stream for RecordingBody {
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => this.recorded.extend_from_slice(chunk),
+            Poll::Ready(Some(Err(_))) => {
+                // Never replay a truncated response
+                this.guard = None;
+            }
+            Poll::Ready(None) => {
+                if let Some(guard) = this.guard.take() {
+                    guard.complete(CachedResponse {
+                        status: this.status,
+                        headers: this.headers.clone(),
+                        body: Bytes::from(std::mem::take(&mut this.recorded)),
+                    });
+                }
+            }
+            Poll::Pending => {}
+        }
+
+        poll
+    }
+}
+
+// Hashes the given components into a hex-encoded SHA-256 digest.
+fn hash_parts(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// Hashes a request body into the hex-encoded SHA-256 digest used to invalidate it.
+fn hash_body(body: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(body))
+}
+
+// Extracts the idempotency key from the request headers.
+//
+// # Returns
+//
+// * `Ok(None)` - If the request carries no idempotency key
+// * `Ok(Some(key))` - The trimmed idempotency key
+// * `Err(ApiError)` - If the key is empty, too long or not valid text
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+
+    let key = value
+        .to_str()
+        .map_err(|_| ApiError::BadRequest("Idempotency-Key must be valid ASCII".to_string()))?
+        .trim();
+
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(ApiError::BadRequest(format!(
+            "Idempotency-Key must be between 1 and {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        )));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+// Middleware answering duplicate generation requests with the original result.
+pub async fn dedup_guard(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let store = &state.dedup;
+    let bypass = request
+        .headers()
+        .get(SCAN_BYPASS_CACHE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"));
+    if bypass && require_admin(&state, request.headers()).is_err() {
+        return ApiError::Unauthorized(format!(
+            "{} requires the admin token",
+            SCAN_BYPASS_CACHE_HEADER
+        ))
+        .into_response();
+    }
+
+    let idempotency_key = if store.idempotency_enabled {
+        match idempotency_key(request.headers()) {
+            Ok(key) => key,
+            Err(e) => return e.into_response(),
+        }
+    } else {
+        None
+    };
+
+    let caches_verdicts = state.security_client.caches_verdicts();
+    if idempotency_key.is_none() && !store.enabled && !caches_verdicts {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                .into_response();
+        }
+    };
+    let mut request = Request::from_parts(parts, Body::from(body.clone()));
+    let body_hash = hash_body(&body);
+    request.extensions_mut().insert(CachedVerdicts {
+        body_hash: body_hash.clone(),
+        bypass,
+    });
+    if idempotency_key.is_none() && !store.enabled {
+        return next.run(request).await;
+    }
+
+    let client = addr.ip().to_string();
+    let method = request.method().as_str();
+    let path = request.uri().path();
+    let request_key = match &idempotency_key {
+        Some(key) => RequestKey {
+            key: hash_parts(&[client.as_bytes(), path.as_bytes(), key.as_bytes()]),
+            body_hash,
+            ttl: store.idempotency_ttl,
+            retain_failures: true,
+            marker: IDEMPOTENT_REPLAY_HEADER,
+        },
+        None => RequestKey {
+            key: hash_parts(&[client.as_bytes(), method.as_bytes(), path.as_bytes(), &body]),
+            body_hash,
+            ttl: store.window,
+            retain_failures: false,
+            marker: DUPLICATE_HEADER,
+        },
+    };
+    let marker = request_key.marker;
+    if bypass {
+        info!(
+            "Bypassing retained responses for {} request from {}",
+            request.uri().path(),
+            addr.ip()
+        );
+        store.forget(&request_key.key);
+    }
+
+    let guard = match store.begin(request_key) {
+        Lookup::Original(guard) => guard,
+        Lookup::Mismatch => {
+            warn!(
+                "Idempotency key reused with a different {} request from {}",
+                request.uri().path(),
+                addr.ip()
+            );
+            return ApiError::UnprocessableEntity(
+                "Idempotency-Key was already used for a different request".to_string(),
+            )
+            .into_response();
+        }
+        Lookup::Completed(response) => {
+            info!(
+                "Replaying recent result for duplicate {} request from {}",
+                request.uri().path(),
+                addr.ip()
+            );
+            return response.replay(marker);
+        }
+        Lookup::InFlight(_) if bypass => return next.run(request).await,
+        Lookup::InFlight(mut completion) => {
+            info!(
+                "Duplicate {} request from {} is waiting for the in-flight original",
+                request.uri().path(),
+                addr.ip()
+            );
+            let completed = completion
+                .wait_for(Option::is_some)
+                .await
+                .ok()
+                .and_then(|response| response.clone());
+            return match completed {
+                Some(response) => response.replay(marker),
+                None => {
+                    debug!("Original request was abandoned, processing duplicate on its own");
+                    next.run(request).await
+                }
+            };
+        }
+    };
+
+    let response = next.run(request).await;
+    let (parts, body) = response.into_parts();
+    let recording = RecordingBody {
+        inner: body.into_data_stream(),
+        status: parts.status,
+        headers: parts.headers.clone(),
+        recorded: Vec::new(),
+        guard: Some(guard),
+    };
+
+    Response::from_parts(parts, Body::from_stream(recording))
+}
@@ -0,0 +1,207 @@
+// Detection and decoding of encoded payloads in prompts.
+//
+// Instructions or secrets smuggled inside an encoding pass a scan of the
+// prompt text unnoticed. With payload decoding enabled, prompts are searched
+// for encoded blobs of at least the configured length:
+//
+// - `base64`: standard or URL-safe alphabet, with or without padding
+// - `hex`: runs of hex digit pairs, optionally prefixed with `0x`
+// - `url`: tokens holding at least three percent-escapes
+//
+// Blobs that decode to readable UTF-8 text are scanned along with the prompt
+// as code content, so the prompt forwarded to Ollama is unchanged. Decoded
+// text is searched again, so payloads encoded twice are found as well. Decoded
+// payloads are counted in `panw_decoded_payloads_total` by `encoding`.
+use crate::client_auth::decode_hex;
+use crate::config::DecodePayloadsConfig;
+use crate::metrics;
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
+use regex::Regex;
+use tracing::debug;
+
+// How many times decoded text is searched again for payloads.
+const MAX_DEPTH: usize = 2;
+
+// Largest number of payloads decoded from one prompt.
+const MAX_PAYLOADS: usize = 16;
+
+// Fewest percent-escapes in a URL-encoded token.
+const MIN_URL_ESCAPES: usize = 3;
+
+// Share of characters of decoded text that must be printable.
+const MIN_PRINTABLE: f64 = 0.9;
+
+// Encoding of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Base64,
+    Hex,
+    Url,
+}
+
+impl Encoding {
+    // Returns the name used in logs and metric labels.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Base64 => "base64",
+            Encoding::Hex => "hex",
+            Encoding::Url => "url",
+        }
+    }
+}
+
+// Finds and decodes encoded payloads in text.
+pub struct PayloadDecoder {
+    // Pattern of base64 blobs
+    base64: Regex,
+
+    // Pattern of hex blobs
+    hex: Regex,
+
+    // Pattern of tokens holding percent-escapes
+    url: Regex,
+
+    // Shortest blob decoded, in characters
+    min_length: usize,
+}
+
+impl PayloadDecoder {
+    // Compiles the patterns for blobs of the configured length.
+    pub fn new(config: &DecodePayloadsConfig) -> Self {
+        let min_length = config.min_length;
+        Self {
+            base64: Regex::new(&format!(r"[A-Za-z0-9+/_-]{{{},}}={{0,2}}", min_length))
+                .expect("valid payload pattern"),
+            hex: Regex::new(&format!(
+                r"\b(?:0x)?((?:[0-9A-Fa-f]{{2}}){{{},}})\b",
+                min_length.div_ceil(2)
+            ))
+            .expect("valid payload pattern"),
+            url: Regex::new(r"\S*%[0-9A-Fa-f]{2}\S*").expect("valid payload pattern"),
+            min_length,
+        }
+    }
+
+    // Decodes the encoded payloads found in text, including payloads found in
+    // decoded text.
+    //
+    // # Returns
+    //
+    // The encoding and decoded text of every payload, in the order found
+    pub fn decode(&self, text: &str) -> Vec<(Encoding, String)> {
+        let mut payloads = Vec::new();
+        self.decode_into(text, 0, &mut payloads);
+        for (encoding, decoded) in &payloads {
+            debug!(
+                "Decoded {} payload of {} characters for scanning",
+                encoding.as_str(),
+                decoded.chars().count()
+            );
+            metrics::increment_counter(
+                "panw_decoded_payloads_total",
+                "Encoded payloads in prompts decoded for scanning",
+                &[("encoding", encoding.as_str())],
+            );
+        }
+        payloads
+    }
+
+    // Appends the payloads found in text, searching decoded text up to the
+    // largest depth.
+    fn decode_into(&self, text: &str, depth: usize, payloads: &mut Vec<(Encoding, String)>) {
+        if depth >= MAX_DEPTH {
+            return;
+        }
+        let mut found = Vec::new();
+        for captures in self.hex.captures_iter(text) {
+            if let Some(decoded) = decode_hex(&captures[1]).and_then(readable) {
+                found.push((Encoding::Hex, decoded));
+            }
+        }
+        for blob in self.base64.find_iter(text).map(|m| m.as_str()) {
+            // Hex blobs are base64 too; they are decoded as hex
+            if is_hex(blob) {
+                continue;
+            }
+            if let Some(decoded) = decode_base64(blob) {
+                found.push((Encoding::Base64, decoded));
+            }
+        }
+        for token in self.url.find_iter(text).map(|m| m.as_str()) {
+            if token.len() < self.min_length || token.matches('%').count() < MIN_URL_ESCAPES {
+                continue;
+            }
+            if let Some(decoded) = decode_url(token) {
+                found.push((Encoding::Url, decoded));
+            }
+        }
+
+        for (encoding, decoded) in found {
+            if payloads.len() >= MAX_PAYLOADS {
+                return;
+            }
+            if payloads.iter().any(|(_, existing)| *existing == decoded) {
+                continue;
+            }
+            payloads.push((encoding, decoded.clone()));
+            self.decode_into(&decoded, depth + 1, payloads);
+        }
+    }
+}
+
+// Returns whether a blob consists of hex digit pairs.
+fn is_hex(blob: &str) -> bool {
+    blob.len().is_multiple_of(2) && blob.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Decodes a base64 blob in either alphabet to readable text.
+fn decode_base64(blob: &str) -> Option<String> {
+    let bytes = if blob.contains(['-', '_']) {
+        URL_SAFE
+            .decode(blob)
+            .or_else(|_| URL_SAFE_NO_PAD.decode(blob))
+    } else {
+        STANDARD
+            .decode(blob)
+            .or_else(|_| STANDARD_NO_PAD.decode(blob))
+    };
+    readable(bytes.ok()?)
+}
+
+// Decodes the percent-escapes of a URL-encoded token to readable text.
+fn decode_url(token: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(token.len());
+    let mut rest = token.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    readable(bytes)
+}
+
+// Returns decoded bytes as text if they are UTF-8 and mostly printable.
+fn readable(bytes: Vec<u8>) -> Option<String> {
+    let text = String::from_utf8(bytes).ok()?;
+    let total = text.chars().count();
+    let printable = text
+        .chars()
+        .filter(|c| !c.is_control() || c.is_whitespace())
+        .count();
+    let has_letters = text.chars().any(char::is_alphabetic);
+    (total > 0 && has_letters && printable as f64 / total as f64 >= MIN_PRINTABLE).then_some(text)
+}
@@ -0,0 +1,234 @@
+// Encryption at rest for the local state files.
+//
+// When encryption keys are configured, state files are written as AES-256-GCM
+// envelopes instead of plain JSON. The GCM tag authenticates every file, so a
+// file that was tampered with or corrupted fails to open instead of being
+// read as garbage.
+//
+// # Keys and Rotation
+//
+// Keys are 32 random bytes, base64 encoded, each with an identifier that is
+// stored in the envelope. Files are always written with the active key and
+// can be read with any configured key, so a key is rotated by adding a new
+// active key while keeping the old one until `store rekey` has rewritten all
+// files, after which the old key can be removed.
+//
+// # Migration
+//
+// Once keys are configured, unencrypted contents are rejected, since anyone
+// able to write a state file could otherwise replace an encrypted file with
+// unauthenticated contents. After enabling encryption on an existing
+// installation, run `store rekey` to encrypt the existing files before
+// starting the proxy; `store rekey` and `store restore` are the only paths
+// that accept unencrypted contents, and they encrypt them with the active key.
+//
+// Files are written to a temporary file, synced and renamed into place, so a
+// crash never leaves a truncated envelope behind.
+use crate::config::EncryptionConfig;
+use crate::store;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::OnceLock;
+
+// Algorithm marker of encrypted envelopes.
+const ALGORITHM: &str = "aes-256-gcm";
+
+// Keys installed at startup; state files are plain JSON when not installed.
+static CIPHER: OnceLock<StateCipher> = OnceLock::new();
+
+// Encrypted state file contents.
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    // Encryption algorithm, always `aes-256-gcm`
+    encrypted: String,
+    // Identifier of the key the file was encrypted with
+    key_id: String,
+    // Base64 nonce
+    nonce: String,
+    // Base64 ciphertext including the authentication tag
+    ciphertext: String,
+}
+
+// Configured encryption keys.
+struct StateCipher {
+    // Identifier of the key used for writing
+    active: String,
+
+    // Ciphers by key identifier
+    keys: Vec<(String, Aes256Gcm)>,
+}
+
+impl StateCipher {
+    // Returns the cipher of the given key.
+    fn key(&self, id: &str) -> Option<&Aes256Gcm> {
+        self.keys
+            .iter()
+            .find(|(key_id, _)| key_id == id)
+            .map(|(_, cipher)| cipher)
+    }
+}
+
+// Installs the configured keys for all subsequent reads and writes.
+//
+// Does nothing when no keys are configured.
+//
+// # Errors
+//
+// Returns an error if a key cannot be read or is not 32 base64-encoded bytes.
+pub fn init(config: &EncryptionConfig) -> Result<(), String> {
+    if config.keys.is_empty() {
+        return Ok(());
+    }
+
+    let mut keys = Vec::new();
+    for key in &config.keys {
+        let encoded = if key.key_file.is_empty() {
+            key.key.clone()
+        } else {
+            fs::read_to_string(&key.key_file)
+                .map_err(|e| format!("encryption key file {}: {}", key.key_file, e))?
+        };
+        let bytes = BASE64
+            .decode(encoded.trim())
+            .ok()
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or_else(|| format!("encryption key {} must be 32 base64-encoded bytes", key.id))?;
+        keys.push((
+            key.id.clone(),
+            Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)),
+        ));
+    }
+
+    let active = if config.active_key.is_empty() {
+        config.keys[0].id.clone()
+    } else {
+        config.active_key.clone()
+    };
+    let _ = CIPHER.set(StateCipher { active, keys });
+    Ok(())
+}
+
+// Encrypts state file contents with the active key.
+//
+// Returns the contents unchanged when no keys are installed.
+//
+// # Errors
+//
+// Returns an error if encryption fails.
+pub fn seal(plaintext: &str) -> Result<String, String> {
+    let Some(cipher) = CIPHER.get() else {
+        return Ok(plaintext.to_string());
+    };
+    let key = cipher
+        .key(&cipher.active)
+        .ok_or_else(|| format!("active encryption key {} is not configured", cipher.active))?;
+
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = key
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: cipher.active.as_bytes(),
+            },
+        )
+        .map_err(|_| "encryption failed".to_string())?;
+
+    serde_json::to_string(&Envelope {
+        encrypted: ALGORITHM.to_string(),
+        key_id: cipher.active.clone(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+    .map_err(|e| e.to_string())
+}
+
+// Decrypts and verifies state file contents.
+//
+// Contents that are not an encrypted envelope are returned unchanged when no
+// keys are installed.
+//
+// # Errors
+//
+// Returns an error if the file is encrypted with an unknown key, fails the
+// integrity check, or is not encrypted although keys are installed.
+pub fn open(contents: &str) -> Result<String, String> {
+    match serde_json::from_str::<Envelope>(contents) {
+        Ok(envelope) => decrypt(&envelope),
+        Err(_) if CIPHER.get().is_some() => {
+            Err("contents are not encrypted; run `store rekey` to encrypt them".to_string())
+        }
+        Err(_) => Ok(contents.to_string()),
+    }
+}
+
+// Encrypts state file contents with the active key, decrypting them first if
+// they are encrypted and accepting unencrypted contents.
+//
+// Only for rewriting files on purpose, as `store rekey` and `store restore`
+// do; state is read with `open`.
+//
+// # Errors
+//
+// Returns an error if the contents are encrypted with an unknown key, fail
+// the integrity check, or cannot be sealed.
+pub fn reseal(contents: &str) -> Result<String, String> {
+    let plaintext = match serde_json::from_str::<Envelope>(contents) {
+        Ok(envelope) => decrypt(&envelope)?,
+        Err(_) => contents.to_string(),
+    };
+    seal(&plaintext)
+}
+
+// Decrypts and verifies an envelope.
+fn decrypt(envelope: &Envelope) -> Result<String, String> {
+    if envelope.encrypted != ALGORITHM {
+        return Err(format!("unsupported encryption {}", envelope.encrypted));
+    }
+
+    let key = CIPHER
+        .get()
+        .and_then(|cipher| cipher.key(&envelope.key_id))
+        .ok_or_else(|| format!("encryption key {} is not configured", envelope.key_id))?;
+    let nonce = BASE64
+        .decode(&envelope.nonce)
+        .ok()
+        .filter(|nonce| nonce.len() == 12)
+        .ok_or("invalid nonce")?;
+    let ciphertext = BASE64
+        .decode(&envelope.ciphertext)
+        .map_err(|_| "invalid ciphertext")?;
+
+    let plaintext = key
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: envelope.key_id.as_bytes(),
+            },
+        )
+        .map_err(|_| "integrity check failed".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+// Reads and decrypts a state file.
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or opened.
+pub fn read(path: &str) -> Result<String, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    open(&contents)
+}
+
+// Encrypts and writes a state file, replacing it atomically.
+//
+// # Errors
+//
+// Returns an error if the contents cannot be sealed or written.
+pub fn write(path: &str, plaintext: &str) -> Result<(), String> {
+    store::write_atomically(path, &seal(plaintext)?).map_err(|e| e.to_string())
+}
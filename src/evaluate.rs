@@ -0,0 +1,344 @@
+// Evaluation of the guardrails against a labeled prompt dataset.
+//
+// `panw-api-ollama evaluate --dataset <path>` sends every prompt of a labeled
+// dataset through the live security pipeline, exactly as a generation request
+// would be assessed, and reports how well the detections match the labels, so
+// security teams can measure what the configured profile catches through this
+// proxy before relying on it.
+//
+// # Dataset
+//
+// A dataset is a JSONL file with one labeled prompt per line:
+//
+// ```text
+// {"prompt": "What is the capital of France?", "label": "benign"}
+// {"prompt": "Ignore all previous instructions and ...", "label": "injection"}
+// {"prompt": "...", "labels": ["dlp", "malicious_code"]}
+// ```
+//
+// Labels name the detection categories expected to fire: `injection`, `dlp`,
+// `url_cats`, `toxic_content`, `malicious_code`, `agent` and
+// `topic_violation`. `benign` (or no labels) marks a prompt that should pass.
+//
+// # Report
+//
+// For every category the report counts the prompts labeled with it that were
+// detected (true positives), the prompts detected but not labeled with it
+// (false positives) and the prompts labeled but not detected (false
+// negatives), with the resulting precision and recall. The `blocked` row
+// compares the verdicts with the labels: attack prompts should be blocked and
+// benign ones allowed. Misclassified prompts are listed by line number, without
+// their content.
+//
+// Scans count against the scan quota and budget like any other scan. Security
+// events and transcripts are not written.
+use crate::security::{Assessment, SecurityClient};
+use crate::types::PromptDetected;
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use std::fs;
+
+// Usage of the subcommand.
+const USAGE: &str = "usage: evaluate --dataset <file> [--model <name>] [--concurrency <n>]";
+
+// Label of prompts that should pass.
+const BENIGN: &str = "benign";
+
+// Tells whether a detection category fired in the prompt findings.
+type Detector = fn(&PromptDetected) -> bool;
+
+// Detection categories, as labeled in datasets.
+const CATEGORIES: [(&str, Detector); 7] = [
+    ("injection", |detected| detected.injection),
+    ("dlp", |detected| detected.dlp),
+    ("url_cats", |detected| detected.url_cats),
+    ("toxic_content", |detected| detected.toxic_content),
+    ("malicious_code", |detected| detected.malicious_code),
+    ("agent", |detected| detected.agent),
+    ("topic_violation", |detected| detected.topic_violation),
+];
+
+// Settings of an evaluation run.
+struct Options {
+    dataset: String,
+    model: String,
+    concurrency: usize,
+}
+
+impl Options {
+    // Parses the arguments of the subcommand.
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            dataset: String::new(),
+            model: "evaluation".to_string(),
+            concurrency: 4,
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args.next().ok_or(USAGE)?.clone();
+            match flag.as_str() {
+                "--dataset" => options.dataset = value,
+                "--model" => options.model = value,
+                "--concurrency" => {
+                    options.concurrency = value
+                        .parse()
+                        .ok()
+                        .filter(|concurrency| *concurrency > 0)
+                        .ok_or(USAGE)?
+                }
+                _ => return Err(USAGE.to_string()),
+            }
+        }
+        if options.dataset.is_empty() {
+            return Err(USAGE.to_string());
+        }
+        Ok(options)
+    }
+}
+
+// A line of the dataset.
+#[derive(Debug, Deserialize)]
+struct Sample {
+    prompt: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+// A labeled prompt.
+struct Item {
+    // Line of the dataset holding the prompt
+    line: usize,
+
+    prompt: String,
+
+    // Detection categories expected to fire; empty for benign prompts
+    labels: Vec<&'static str>,
+}
+
+// Outcome of assessing a labeled prompt.
+struct Outcome {
+    line: usize,
+    labels: Vec<&'static str>,
+
+    // Detection categories that fired, and whether the prompt was blocked, or
+    // the error of a failed assessment
+    result: Result<(Vec<&'static str>, bool), String>,
+}
+
+// Counts of one report row.
+#[derive(Default)]
+struct Counts {
+    true_positives: usize,
+    false_positives: usize,
+    false_negatives: usize,
+}
+
+impl Counts {
+    fn add(&mut self, labeled: bool, detected: bool) {
+        match (labeled, detected) {
+            (true, true) => self.true_positives += 1,
+            (false, true) => self.false_positives += 1,
+            (true, false) => self.false_negatives += 1,
+            (false, false) => {}
+        }
+    }
+
+    fn precision(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    fn recall(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+}
+
+// Runs the evaluation of the guardrails against a labeled dataset.
+//
+// # Errors
+//
+// Returns an error if the arguments or dataset are invalid, or any prompt
+// could not be assessed.
+pub async fn run_cli(
+    security_client: &SecurityClient,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = Options::parse(args)?;
+    let contents = fs::read_to_string(&options.dataset)
+        .map_err(|e| format!("cannot read dataset {}: {}", options.dataset, e))?;
+    let items = parse_dataset(&options.dataset, &contents)?;
+    let attacks = items.iter().filter(|item| !item.labels.is_empty()).count();
+    println!(
+        "Evaluating {} prompts ({} attacks, {} benign) from {} with model {}",
+        items.len(),
+        attacks,
+        items.len() - attacks,
+        options.dataset,
+        options.model
+    );
+
+    // `buffered` keeps the outcomes in dataset order
+    let outcomes: Vec<Outcome> = stream::iter(items)
+        .map(|item| {
+            let model = options.model.as_str();
+            async move {
+                let result = security_client
+                    .assess_content(&item.prompt, model, true)
+                    .await
+                    .map(|assessment| (detections(&assessment), !assessment.is_safe))
+                    .map_err(|e| e.to_string());
+                Outcome {
+                    line: item.line,
+                    labels: item.labels,
+                    result,
+                }
+            }
+        })
+        .buffered(options.concurrency)
+        .collect()
+        .await;
+
+    print_report(&outcomes);
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.result.is_err())
+        .count();
+    if failed > 0 {
+        return Err(format!("{} prompts could not be assessed", failed).into());
+    }
+    Ok(())
+}
+
+// Parses the labeled prompts of a dataset.
+fn parse_dataset(name: &str, contents: &str) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = index + 1;
+        let sample: Sample =
+            serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", name, number, e))?;
+        let mut labels = Vec::new();
+        for label in sample.label.iter().chain(&sample.labels) {
+            let label = label.trim().to_ascii_lowercase();
+            if label == BENIGN {
+                continue;
+            }
+            let category = CATEGORIES
+                .iter()
+                .map(|(category, _)| *category)
+                .find(|category| *category == label)
+                .ok_or_else(|| format!("{}:{}: unknown label {}", name, number, label))?;
+            if !labels.contains(&category) {
+                labels.push(category);
+            }
+        }
+        items.push(Item {
+            line: number,
+            prompt: sample.prompt,
+            labels,
+        });
+    }
+    if items.is_empty() {
+        return Err(format!("no prompts found in {}", name));
+    }
+    Ok(items)
+}
+
+// Returns the detection categories that fired for a prompt.
+fn detections(assessment: &Assessment) -> Vec<&'static str> {
+    CATEGORIES
+        .iter()
+        .filter(|(_, detected)| detected(&assessment.details.prompt_detected))
+        .map(|(category, _)| *category)
+        .collect()
+}
+
+// Prints the counts, precision and recall of every category and the verdicts,
+// followed by the misclassified prompts.
+fn print_report(outcomes: &[Outcome]) {
+    let mut rows: Vec<(&str, Counts)> = CATEGORIES
+        .iter()
+        .map(|(category, _)| (*category, Counts::default()))
+        .collect();
+    let mut blocked_counts = Counts::default();
+    let mut misclassified = Vec::new();
+    for outcome in outcomes {
+        let Ok((detected, blocked)) = &outcome.result else {
+            continue;
+        };
+        for (category, counts) in rows.iter_mut() {
+            counts.add(
+                outcome.labels.contains(category),
+                detected.contains(category),
+            );
+        }
+        blocked_counts.add(!outcome.labels.is_empty(), *blocked);
+        let mut expected = outcome.labels.clone();
+        let mut actual = detected.clone();
+        expected.sort();
+        actual.sort();
+        if expected != actual || outcome.labels.is_empty() == *blocked {
+            misclassified.push((outcome, detected, *blocked));
+        }
+    }
+
+    println!(
+        "{:<16} {:>6} {:>6} {:>6} {:>10} {:>8}",
+        "category", "tp", "fp", "fn", "precision", "recall"
+    );
+    rows.push(("blocked", blocked_counts));
+    for (category, counts) in &rows {
+        println!(
+            "{:<16} {:>6} {:>6} {:>6} {:>10} {:>8}",
+            category,
+            counts.true_positives,
+            counts.false_positives,
+            counts.false_negatives,
+            percent(counts.precision()),
+            percent(counts.recall())
+        );
+    }
+
+    for (outcome, detected, blocked) in misclassified {
+        println!(
+            "MISMATCH line {}: labeled {}, detected {}, {}",
+            outcome.line,
+            names(&outcome.labels, BENIGN),
+            names(detected, "none"),
+            if blocked { "blocked" } else { "allowed" }
+        );
+    }
+    for outcome in outcomes {
+        if let Err(e) = &outcome.result {
+            println!("FAIL line {}: {}", outcome.line, e);
+        }
+    }
+}
+
+// Returns a ratio, or `None` if it is undefined.
+fn ratio(numerator: usize, denominator: usize) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+// Formats a ratio as a percentage.
+fn percent(value: Option<f64>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| format!("{:.1}%", value * 100.0))
+}
+
+// Formats a list of categories, or the given name when it is empty.
+fn names(categories: &[&str], empty: &str) -> String {
+    if categories.is_empty() {
+        empty.to_string()
+    } else {
+        categories.join(",")
+    }
+}
@@ -0,0 +1,194 @@
+// Feature flags with per-tenant and percentage rollout.
+//
+// Risky behaviors are guarded by named flags defined in the configuration, so
+// they can be enabled gradually and disabled instantly without a deploy.
+//
+// # Evaluation
+//
+// A flag is evaluated for a request's tenant and client:
+//
+// - A disabled flag is off for everyone.
+// - Tenants listed on the flag always get it.
+// - Everyone else gets it if their bucket, a stable hash of the flag name and
+//   the client identity (or the tenant when there is none), falls below the
+//   rollout percentage. The same client therefore keeps the same answer while
+//   the percentage is raised, and different flags roll out to different
+//   clients.
+//
+// Flags that are not defined are off.
+//
+// # Admin Toggles
+//
+// The admin API can override whether a flag is enabled and its rollout
+// percentage at runtime. Overrides are kept in memory and replace the
+// configured values until they are cleared or the proxy restarts.
+use crate::config::FeatureFlagConfig;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+// Runtime override of a flag set through the admin API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlagOverride {
+    // Whether the flag is enabled
+    pub enabled: bool,
+
+    // Rollout percentage; the configured percentage is kept when not given
+    #[serde(default)]
+    pub rollout_percent: Option<u8>,
+}
+
+// Effective state of a flag as reported by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagStatus {
+    // Name of the flag
+    pub name: String,
+    // Whether the flag is enabled
+    pub enabled: bool,
+    // Percentage of clients outside the listed tenants that get the flag
+    pub rollout_percent: u8,
+    // Tenants that always get the flag
+    pub tenants: Vec<String>,
+    // `config` or `override`
+    pub source: &'static str,
+}
+
+// Evaluates feature flags for requests.
+//
+// Cloning the flags is cheap and all clones share the same overrides.
+#[derive(Clone)]
+pub struct FeatureFlags {
+    // Flags defined in the configuration, by name
+    flags: Arc<HashMap<String, FeatureFlagConfig>>,
+
+    // Overrides set through the admin API, by flag name
+    overrides: Arc<RwLock<HashMap<String, FlagOverride>>>,
+}
+
+impl FeatureFlags {
+    // Creates the flags defined in the configuration, without overrides.
+    pub fn new(flags: &[FeatureFlagConfig]) -> Self {
+        Self {
+            flags: Arc::new(
+                flags
+                    .iter()
+                    .map(|flag| (flag.name.clone(), flag.clone()))
+                    .collect(),
+            ),
+            overrides: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    // Returns whether a flag is on for a request.
+    //
+    // # Arguments
+    //
+    // * `name` - Name of the flag
+    // * `tenant` - Tenant of the request, if any
+    // * `client` - Identity of the client, if any
+    pub fn is_enabled(&self, name: &str, tenant: Option<&str>, client: Option<&str>) -> bool {
+        let Some(status) = self.status(name) else {
+            return false;
+        };
+        if !status.enabled {
+            return false;
+        }
+        if tenant.is_some_and(|tenant| status.tenants.iter().any(|t| t == tenant)) {
+            return true;
+        }
+
+        let subject = client.or(tenant).unwrap_or_default();
+        bucket(name, subject) < status.rollout_percent
+    }
+
+    // Returns the names of the flags that are on for a request, ordered by name.
+    pub fn enabled_for(&self, tenant: Option<&str>, client: Option<&str>) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .flags
+            .keys()
+            .filter(|name| self.is_enabled(name, tenant, client))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    // Returns the effective state of a flag, or `None` if it is not defined.
+    pub fn status(&self, name: &str) -> Option<FlagStatus> {
+        let flag = self.flags.get(name)?;
+        let overrides = self.overrides.read().unwrap_or_else(|e| e.into_inner());
+        let flag_override = overrides.get(name);
+
+        Some(FlagStatus {
+            name: flag.name.clone(),
+            enabled: flag_override.map_or(flag.enabled, |o| o.enabled),
+            rollout_percent: flag_override
+                .and_then(|o| o.rollout_percent)
+                .unwrap_or(flag.rollout_percent)
+                .min(100),
+            tenants: flag.tenants.clone(),
+            source: if flag_override.is_some() {
+                "override"
+            } else {
+                "config"
+            },
+        })
+    }
+
+    // Returns the effective state of every flag, ordered by name.
+    pub fn list(&self) -> Vec<FlagStatus> {
+        let mut statuses: Vec<FlagStatus> = self
+            .flags
+            .keys()
+            .filter_map(|name| self.status(name))
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    // Overrides a flag at runtime.
+    //
+    // # Returns
+    //
+    // The new state of the flag, or `None` if it is not defined.
+    pub fn set_override(&self, name: &str, flag_override: FlagOverride) -> Option<FlagStatus> {
+        if !self.flags.contains_key(name) {
+            return None;
+        }
+        info!(
+            "Feature flag {} overridden: enabled={}, rollout_percent={:?}",
+            name, flag_override.enabled, flag_override.rollout_percent
+        );
+        self.overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), flag_override);
+        self.status(name)
+    }
+
+    // Clears the runtime override of a flag, returning to the configuration.
+    //
+    // # Returns
+    //
+    // The state of the flag, or `None` if it is not defined.
+    pub fn clear_override(&self, name: &str) -> Option<FlagStatus> {
+        if self
+            .overrides
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(name)
+            .is_some()
+        {
+            info!("Feature flag {} override cleared", name);
+        }
+        self.status(name)
+    }
+}
+
+// Returns the rollout bucket (0-99) of a subject for a flag.
+fn bucket(name: &str, subject: &str) -> u8 {
+    let digest = Sha256::digest(format!("{}:{}", name, subject).as_bytes());
+    (u16::from_be_bytes([digest[0], digest[1]]) % 100) as u8
+}
@@ -0,0 +1,703 @@
+// Administrative endpoints for operating the proxy at runtime.
+//
+// All endpoints in this module require the configured admin token, or a client
+// key holding the `admin` scope, as a bearer token. Without either they are
+// disabled entirely.
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use tracing::{info, warn};
+
+use crate::config::{OperatingMode, ScheduledPrompt};
+use crate::feature_flags::FlagOverride;
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::key_store::{KeyStoreError, ManagedKey, NewKey};
+use crate::kill_switch::Activation;
+use crate::notifications::{self, AlertKind};
+use crate::security::SecurityError;
+use crate::security_events::{self, EventType};
+use crate::usage::ClientUsage;
+use crate::AppState;
+
+// Request body for changing the operating mode.
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    // Mode to switch to
+    pub mode: OperatingMode,
+    // Optional message returned to rejected clients
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+// Request body for activating the kill switch.
+#[derive(Debug, Default, Deserialize)]
+pub struct KillSwitchRequest {
+    // Reason for the activation, recorded for the audit
+    #[serde(default)]
+    pub reason: Option<String>,
+    // Optional message returned to rejected clients
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+// Client key as listed by the key management endpoints.
+#[derive(Debug, Serialize)]
+pub struct KeySummary {
+    // Identifier of the client
+    pub id: String,
+    // `config` for keys from the configuration, `managed` for keys created
+    // through the admin API
+    pub source: &'static str,
+    // `active`, `expired` or `revoked`
+    pub status: &'static str,
+    // Granted scopes; every scope except `admin` when empty
+    pub scopes: Vec<String>,
+    // Allowed model patterns; every model when empty
+    pub models: Vec<String>,
+    // Tenant the key's requests are processed as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    // Requests, scans and violations counted for the client since startup
+    pub usage: ClientUsage,
+}
+
+impl KeySummary {
+    // Summarizes a managed key with its client's usage.
+    fn managed(state: &AppState, key: ManagedKey) -> Self {
+        Self {
+            status: key.status(Utc::now()),
+            usage: state.usage.usage_of(&key.id),
+            id: key.id,
+            source: "managed",
+            scopes: key.scopes,
+            models: key.models,
+            tenant: key.tenant,
+            created_at: Some(key.created_at),
+            expires_at: key.expires_at,
+            rotated_at: key.rotated_at,
+            revoked_at: key.revoked_at,
+        }
+    }
+}
+
+// Response of creating or rotating a key, carrying the key itself.
+#[derive(Debug, Serialize)]
+pub struct IssuedKey {
+    // The key; it is not stored and cannot be retrieved again
+    pub key: String,
+    #[serde(flatten)]
+    pub summary: KeySummary,
+}
+
+// Query parameters of the usage report endpoint.
+#[derive(Debug, Deserialize)]
+pub struct UsageReportQuery {
+    // Report exact counts, skipping the configured privacy protections
+    #[serde(default)]
+    pub exact: bool,
+}
+
+// Verifies that the request carries the configured admin token, or a client
+// key holding the `admin` scope.
+//
+// # Returns
+//
+// * `Ok(())` - If the bearer token matches the configured admin token or an admin key
+// * `Err(ApiError)` - If the admin API is disabled or the token is missing or wrong
+pub fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let supplied = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if state.client_keys.grants_admin(supplied.trim()) {
+        return Ok(());
+    }
+
+    if state.admin_config.token.is_empty() {
+        return Err(ApiError::Unauthorized(
+            "Admin API is disabled; configure admin.token to enable it".to_string(),
+        ));
+    }
+
+    if constant_time_eq(supplied.as_bytes(), state.admin_config.token.as_bytes()) {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("Invalid admin token".to_string()))
+    }
+}
+
+// Returns who is making an admin request: the client of an admin key, or
+// `admin_token`.
+pub fn admin_actor(state: &AppState, headers: &HeaderMap) -> String {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|key| state.client_keys.admin_id(key.trim()))
+        .unwrap_or_else(|| "admin_token".to_string())
+}
+
+// Compares two byte strings without short-circuiting on the first difference.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Handler for reading the effective operating mode (GET /admin/maintenance)
+pub async fn handle_get_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.maintenance.effective_mode())
+}
+
+// Handler for overriding the operating mode (PUT /admin/maintenance)
+pub async fn handle_set_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    info!("Admin requested operating mode {:?}", request.mode);
+    let mode = json!(request.mode);
+    publish_policy_change("operating_mode_set", mode.as_str().unwrap_or_default());
+    state.maintenance.set_override(request.mode, request.message);
+    build_serialized_response(state.maintenance.effective_mode())
+}
+
+// Handler for clearing the operating mode override (DELETE /admin/maintenance)
+pub async fn handle_clear_maintenance(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    state.maintenance.clear_override();
+    publish_policy_change("operating_mode_cleared", "maintenance");
+    build_serialized_response(state.maintenance.effective_mode())
+}
+
+// Handler for reading the kill switch (GET /admin/kill-switch)
+pub async fn handle_get_kill_switch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.kill_switch.status())
+}
+
+// Handler for activating the kill switch (PUT /admin/kill-switch)
+//
+// Stops all generation at once; the body with a reason and message is optional.
+// Activating an active kill switch keeps its original activation.
+pub async fn handle_activate_kill_switch(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    request: Option<Json<KillSwitchRequest>>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let Json(request) = request.unwrap_or_default();
+    let activation = Activation {
+        activated_by: admin_actor(&state, &headers),
+        activated_at: Utc::now(),
+        ip: Some(addr.ip().to_string()),
+        reason: request.reason.filter(|reason| !reason.is_empty()),
+        message: request.message.filter(|message| !message.is_empty()),
+    };
+    let summary = format!(
+        "Kill switch activated by {} from {}{}",
+        activation.activated_by,
+        addr.ip(),
+        activation
+            .reason
+            .as_ref()
+            .map(|reason| format!(": {}", reason))
+            .unwrap_or_default()
+    );
+    let details = [
+        ("action", json!("activated")),
+        ("actor", json!(activation.activated_by)),
+        ("ip", json!(activation.ip)),
+        ("reason", json!(activation.reason)),
+    ];
+    if state.kill_switch.activate(activation) {
+        warn!("{}", summary);
+        security_events::publish(EventType::KillSwitch, &summary, &details);
+        notifications::notify(AlertKind::KillSwitch, &summary, &details);
+    }
+    build_serialized_response(state.kill_switch.status())
+}
+
+// Handler for lifting the kill switch (DELETE /admin/kill-switch)
+pub async fn handle_lift_kill_switch(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if let Some(activation) = state.kill_switch.lift() {
+        let actor = admin_actor(&state, &headers);
+        let summary = format!(
+            "Kill switch lifted by {} from {} (activated by {} at {})",
+            actor,
+            addr.ip(),
+            activation.activated_by,
+            activation.activated_at
+        );
+        let details = [
+            ("action", json!("lifted")),
+            ("actor", json!(actor)),
+            ("ip", json!(addr.ip().to_string())),
+        ];
+        warn!("{}", summary);
+        security_events::publish(EventType::KillSwitch, &summary, &details);
+        notifications::notify(AlertKind::KillSwitch, &summary, &details);
+    }
+    build_serialized_response(state.kill_switch.status())
+}
+
+// Handler for listing feature flags (GET /admin/flags)
+pub async fn handle_list_flags(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.feature_flags.list())
+}
+
+// Handler for overriding a feature flag (PUT /admin/flags/{name})
+//
+// Overrides are kept in memory only.
+pub async fn handle_set_flag(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(request): Json<FlagOverride>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if request.rollout_percent.is_some_and(|percent| percent > 100) {
+        return Err(ApiError::BadRequest(
+            "`rollout_percent` must be between 0 and 100".to_string(),
+        ));
+    }
+    let status = state
+        .feature_flags
+        .set_override(&name, request)
+        .ok_or_else(|| ApiError::NotFound(format!("No feature flag named {}", name)))?;
+    publish_policy_change("flag_set", &name);
+    build_serialized_response(status)
+}
+
+// Handler for clearing a feature flag override (DELETE /admin/flags/{name})
+pub async fn handle_clear_flag(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let status = state
+        .feature_flags
+        .clear_override(&name)
+        .ok_or_else(|| ApiError::NotFound(format!("No feature flag named {}", name)))?;
+    publish_policy_change("flag_cleared", &name);
+    build_serialized_response(status)
+}
+
+// Handler for listing scheduled prompts (GET /admin/schedules)
+pub async fn handle_list_schedules(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.scheduler.list())
+}
+
+// Handler for adding or replacing a scheduled prompt (PUT /admin/schedules/{name})
+//
+// Schedules added at runtime are kept in memory only.
+pub async fn handle_put_schedule(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(mut definition): Json<ScheduledPrompt>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    definition.name = name;
+    info!("Admin requested schedule {}", definition.name);
+    let name = definition.name.clone();
+    let status = state
+        .scheduler
+        .upsert(definition)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    publish_policy_change("schedule_set", &name);
+    build_serialized_response(status)
+}
+
+// Handler for removing a scheduled prompt (DELETE /admin/schedules/{name})
+pub async fn handle_delete_schedule(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if !state.scheduler.remove(&name) {
+        return Err(ApiError::NotFound(format!("No schedule named {}", name)));
+    }
+    info!("Admin removed schedule {}", name);
+    publish_policy_change("schedule_removed", &name);
+    build_serialized_response(state.scheduler.list())
+}
+
+// Handler for exporting per-client usage (GET /admin/usage)
+//
+// Applies the configured minimum count and noise unless `?exact=true` is given.
+pub async fn handle_usage_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<UsageReportQuery>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.usage.report(query.exact))
+}
+
+// Handler for verifying the local state files (GET /admin/stores)
+pub async fn handle_verify_stores(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.store_files.verify())
+}
+
+// Handler for backing up the configuration and state files (GET /admin/backup)
+//
+// Secrets in the configuration file are redacted. Restoring a backup requires
+// the server to be stopped, see `store restore`.
+pub async fn handle_backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let backup = state
+        .store_files
+        .backup()
+        .map_err(|e| ApiError::InternalError(format!("Failed to create backup: {}", e)))?;
+    info!("Admin created a backup of {} files", backup.files.len());
+    build_serialized_response(backup)
+}
+
+// Handler for dropping retained responses for a request body (DELETE /admin/cache/{hash})
+//
+// The hash is the hex-encoded SHA-256 of the request body. Later identical
+// requests are scanned and generated again instead of being replayed, and the
+// PANW verdicts cached for the request are dropped as well.
+pub async fn handle_invalidate_cache(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(hash): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let hash = hash.to_ascii_lowercase();
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::BadRequest(
+            "Cache key must be a hex-encoded SHA-256 of the request body".to_string(),
+        ));
+    }
+    let invalidated = state.dedup.invalidate(&hash);
+    let verdicts = state.security_client.invalidate_cached_verdicts(&hash);
+    info!(
+        "Admin dropped {} retained responses and {} cached verdicts for {}",
+        invalidated, verdicts, hash
+    );
+    build_serialized_response(serde_json::json!({
+        "content_hash": hash,
+        "invalidated": invalidated,
+        "invalidated_verdicts": verdicts,
+    }))
+}
+
+// Handler for listing captured PANW responses (GET /admin/panw-responses)
+//
+// Lists the redacted responses kept for troubleshooting, most recent first.
+pub async fn handle_list_panw_responses(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let capture = state.security_client.response_capture().ok_or_else(|| {
+        ApiError::NotFound(
+            "Response capture is disabled; set security.response_capture.enabled".to_string(),
+        )
+    })?;
+    build_serialized_response(capture.list())
+}
+
+// Handler for listing open client connections (GET /admin/connections)
+//
+// Reports the bytes and most recent request of every connection, and the
+// number of open connections per client IP.
+pub async fn handle_list_connections(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    build_serialized_response(state.connections.report())
+}
+
+// Handler for terminating a client connection (DELETE /admin/connections/{id})
+//
+// Ends every request on the connection, including streamed responses.
+pub async fn handle_terminate_connection(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let Some(connection) = state.connections.terminate(id) else {
+        return Err(ApiError::NotFound(format!("No open connection with id {}", id)));
+    };
+    info!(
+        "Admin terminated connection {} from {} after {} requests",
+        connection.id, connection.peer, connection.requests
+    );
+    build_serialized_response(connection)
+}
+
+// Handler for fetching a threat report from PANW (GET /admin/scan-reports/{report_id})
+//
+// Returns the report PANW keeps for a scan, so a block can be investigated by
+// the report ID from its verdict or logs without separate PANW tooling.
+pub async fn handle_get_scan_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(report_id): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    validate_scan_id("Report", &report_id)?;
+    let report = state
+        .security_client
+        .fetch_report(&report_id)
+        .await
+        .map_err(|e| lookup_error("report", &report_id, e))?;
+    info!("Admin fetched PANW scan report {}", report_id);
+    build_serialized_response(report)
+}
+
+// Handler for fetching a scan result from PANW (GET /admin/scan-results/{scan_id})
+pub async fn handle_get_scan_result(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(scan_id): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    validate_scan_id("Scan", &scan_id)?;
+    let result = state
+        .security_client
+        .fetch_result(&scan_id)
+        .await
+        .map_err(|e| lookup_error("scan", &scan_id, e))?;
+    info!("Admin fetched PANW scan result {}", scan_id);
+    build_serialized_response(result)
+}
+
+// Rejects IDs that cannot be PANW report or scan IDs.
+fn validate_scan_id(kind: &str, id: &str) -> Result<(), ApiError> {
+    let valid = !id.is_empty()
+        && id.len() <= 128
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ApiError::BadRequest(format!(
+            "{} IDs must be 1 to 128 letters, digits, `-` or `_`",
+            kind
+        )))
+    }
+}
+
+// Maps a failed PANW lookup to an error response.
+//
+// PANW rejecting the proxy's own API key is reported as an internal error
+// rather than 401 or 403, so it is not mistaken for the admin's credentials
+// failing.
+fn lookup_error(kind: &str, id: &str, error: SecurityError) -> ApiError {
+    match error {
+        SecurityError::NotFound => ApiError::NotFound(format!("PANW has no {} {}", kind, id)),
+        SecurityError::Unauthenticated | SecurityError::Forbidden => ApiError::InternalError(
+            "PANW rejected the configured API key for the lookup".to_string(),
+        ),
+        e => ApiError::SecurityError(e),
+    }
+}
+
+// Handler for listing client keys with their usage (GET /admin/keys)
+//
+// Lists the configured keys followed by the keys managed through the admin API.
+pub async fn handle_list_keys(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    let configured = state
+        .client_keys
+        .configured_grants()
+        .into_iter()
+        .map(|grant| KeySummary {
+            id: grant.id().to_string(),
+            source: "config",
+            status: "active",
+            scopes: grant.scopes().to_vec(),
+            models: grant.models().to_vec(),
+            tenant: None,
+            created_at: None,
+            expires_at: None,
+            rotated_at: None,
+            revoked_at: None,
+            usage: state.usage.usage_of(grant.id()),
+        });
+    let managed = state
+        .client_keys
+        .store()
+        .list()
+        .into_iter()
+        .map(|key| KeySummary::managed(&state, key));
+    build_serialized_response(configured.chain(managed).collect::<Vec<_>>())
+}
+
+// Handler for creating a client key (POST /admin/keys)
+//
+// The generated key is only returned in this response.
+pub async fn handle_create_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new): Json<NewKey>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if state.client_keys.is_configured(&new.id) {
+        return Err(ApiError::UnprocessableEntity(format!(
+            "A configured key with id {} already exists",
+            new.id
+        )));
+    }
+    let (key, secret) = state
+        .client_keys
+        .store()
+        .create(new)
+        .map_err(key_store_error)?;
+    info!("Admin created client key {}", key.id);
+    publish_policy_change("key_created", &key.id);
+    let mut response = build_serialized_response(IssuedKey {
+        key: secret,
+        summary: KeySummary::managed(&state, key),
+    })?;
+    *response.status_mut() = StatusCode::CREATED;
+    Ok(response)
+}
+
+// Handler for replacing a client key with a new one (POST /admin/keys/{id}/rotate)
+//
+// The previous key stops working immediately.
+pub async fn handle_rotate_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if state.client_keys.is_configured(&id) {
+        return Err(ApiError::BadRequest(format!(
+            "Key {} is configured in the configuration file and cannot be rotated here",
+            id
+        )));
+    }
+    let Some((key, secret)) = state
+        .client_keys
+        .store()
+        .rotate(&id)
+        .map_err(key_store_error)?
+    else {
+        return Err(ApiError::NotFound(format!(
+            "No active managed key with id {}",
+            id
+        )));
+    };
+    info!("Admin rotated client key {}", key.id);
+    publish_policy_change("key_rotated", &key.id);
+    build_serialized_response(IssuedKey {
+        key: secret,
+        summary: KeySummary::managed(&state, key),
+    })
+}
+
+// Handler for revoking a client key (DELETE /admin/keys/{id})
+pub async fn handle_revoke_key(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if state.client_keys.is_configured(&id) {
+        return Err(ApiError::BadRequest(format!(
+            "Key {} is configured in the configuration file and cannot be revoked here",
+            id
+        )));
+    }
+    let Some(key) = state
+        .client_keys
+        .store()
+        .revoke(&id)
+        .map_err(key_store_error)?
+    else {
+        return Err(ApiError::NotFound(format!("No managed key with id {}", id)));
+    };
+    info!("Admin revoked client key {}", key.id);
+    publish_policy_change("key_revoked", &key.id);
+    build_serialized_response(KeySummary::managed(&state, key))
+}
+
+// Maps a failed change of the managed keys to the response status.
+fn key_store_error(e: KeyStoreError) -> ApiError {
+    match e {
+        KeyStoreError::Invalid(message) => ApiError::BadRequest(message),
+        KeyStoreError::NotSaved(_) => ApiError::InternalError(e.to_string()),
+    }
+}
+
+// Exports a change made through the admin API as a security event and notifies
+// it as a `policy_change` alert.
+//
+// # Arguments
+//
+// * `change` - What was changed, e.g. `key_revoked`
+// * `target` - The mode, flag, schedule or key that was changed
+fn publish_policy_change(change: &str, target: &str) {
+    security_events::publish(
+        EventType::PolicyChange,
+        &format!("Admin change {} of {}", change, target),
+        &[("change", json!(change)), ("target", json!(target))],
+    );
+    notifications::notify(
+        AlertKind::PolicyChange,
+        &format!("Admin change {} of {}", change, target),
+        &[("change", json!(change)), ("target", json!(target))],
+    );
+}
@@ -0,0 +1,88 @@
+// Exchange of long-lived credentials for short-lived access tokens.
+//
+// Clients authenticated by a client key or a JWT from the identity provider
+// receive an access token carrying the same identity, profile and grant. The
+// token cannot itself be exchanged for a new one, so it stops working once it
+// expires however often it is used.
+use axum::{extract::State, response::Response, Extension};
+use serde::Serialize;
+use tracing::info;
+
+use crate::access_token::AccessClaims;
+use crate::client_auth::Credential;
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::key_scopes::KeyGrant;
+use crate::AppState;
+use std::sync::Arc;
+
+// Response of a token exchange, in the format of an OAuth 2.0 token response.
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    // The signed access token
+    pub access_token: String,
+    // Always `Bearer`
+    pub token_type: &'static str,
+    // Seconds until the token expires
+    pub expires_in: u64,
+}
+
+// Handler for exchanging a key or JWT for an access token (POST /auth/token)
+//
+// The credential to exchange is the request's own bearer token or Basic
+// credentials.
+pub async fn handle_issue_token(
+    State(state): State<AppState>,
+    credential: Option<Extension<Credential>>,
+    grant: Option<Extension<Arc<KeyGrant>>>,
+) -> Result<Response, ApiError> {
+    if !state.access_tokens.enabled() {
+        return Err(ApiError::NotFound(
+            "Access tokens are not enabled".to_string(),
+        ));
+    }
+    let Some(Extension(credential)) = credential else {
+        return Err(ApiError::Unauthorized(
+            "Access tokens are only issued for client keys and identity provider tokens"
+                .to_string(),
+        ));
+    };
+    if credential.kind == "access_token" {
+        return Err(ApiError::Forbidden(
+            "Access tokens cannot be exchanged for new tokens".to_string(),
+        ));
+    }
+
+    let grant = grant.map(|Extension(grant)| grant);
+    let claims = AccessClaims {
+        iss: String::new(),
+        sub: credential.subject.clone(),
+        iat: 0,
+        exp: 0,
+        auth: credential.kind.to_string(),
+        profile: credential.profile,
+        scopes: grant
+            .as_ref()
+            .map(|g| g.scopes().to_vec())
+            .unwrap_or_default(),
+        models: grant
+            .as_ref()
+            .map(|g| g.models().to_vec())
+            .unwrap_or_default(),
+        tenant: grant.as_ref().and_then(|g| g.tenant().map(str::to_string)),
+    };
+    let access_token = state
+        .access_tokens
+        .issue(claims)
+        .map_err(|e| ApiError::InternalError(format!("Failed to sign access token: {}", e)))?;
+    info!(
+        "Issued access token to client {} for its {}",
+        credential.subject, credential.kind
+    );
+
+    build_serialized_response(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: state.access_tokens.ttl_secs(),
+    })
+}
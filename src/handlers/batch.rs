@@ -0,0 +1,155 @@
+// Handler for batch generation requests.
+//
+// Each prompt of a batch goes through the standard non-streaming pipeline: the
+// prompt is scanned, a response is generated by Ollama, and the response is
+// scanned before it is included in the results. Prompts are processed with
+// bounded concurrency and every prompt gets its own result and verdict, so a
+// blocked or failed prompt never fails the whole batch.
+use axum::{extract::State, response::Response, Json};
+use futures_util::stream::{self, StreamExt};
+use tracing::{debug, info, warn};
+
+use crate::handlers::generate::{blocked_prompt, generate_assessed, GenerationResult};
+use crate::handlers::utils::{build_serialized_response, scan_verdict, RequestSecurity};
+use crate::handlers::ApiError;
+use crate::security::SecurityClient;
+use crate::types::{
+    BatchGenerateRequest, BatchGenerateResponse, BatchGenerateResult, BatchItemStatus,
+    GenerateRequest,
+};
+use crate::AppState;
+
+// Handles batch generation requests with security assessment.
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `Json(request)` - The batch generation request from the client
+//
+// # Returns
+//
+// * `Ok(Response)` - The per-prompt results
+// * `Err(ApiError)` - If the batch is empty or exceeds the configured size
+pub async fn handle_generate_batch(
+    State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(request): Json<BatchGenerateRequest>,
+) -> Result<Response, ApiError> {
+    let max_prompts = state.batch_config.max_prompts;
+    if request.prompts.is_empty() {
+        return Err(ApiError::BadRequest("`prompts` must not be empty".to_string()));
+    }
+    if request.prompts.len() > max_prompts {
+        return Err(ApiError::BadRequest(format!(
+            "Batch of {} prompts exceeds the maximum of {}",
+            request.prompts.len(),
+            max_prompts
+        )));
+    }
+
+    debug!(
+        "Received batch generate request for model: {} ({} prompts)",
+        request.model,
+        request.prompts.len()
+    );
+
+    let requests: Vec<GenerateRequest> = request
+        .prompts
+        .iter()
+        .map(|prompt| GenerateRequest {
+            model: request.model.clone(),
+            prompt: prompt.clone(),
+            system: request.system.clone(),
+            template: None,
+            context: None,
+            stream: Some(false),
+            raw: None,
+            format: None,
+            options: request.options.clone(),
+            images: None,
+        })
+        .collect();
+
+    // `buffered` keeps the results in prompt order
+    let results: Vec<BatchGenerateResult> = stream::iter(requests.into_iter().enumerate())
+        .map(|(index, mut item)| {
+            let state = state.clone();
+            let security_client = security_client.clone();
+            async move { process_item(&state, &security_client, index, &mut item).await }
+        })
+        .buffered(state.batch_config.concurrency)
+        .collect()
+        .await;
+
+    let blocked = results
+        .iter()
+        .filter(|result| result.status == BatchItemStatus::Blocked)
+        .count();
+    let failed = results
+        .iter()
+        .filter(|result| result.status == BatchItemStatus::Failed)
+        .count();
+    info!(
+        "Batch of {} prompts completed ({} blocked, {} failed)",
+        results.len(),
+        blocked,
+        failed
+    );
+
+    build_serialized_response(BatchGenerateResponse {
+        model: request.model,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        results,
+    })
+}
+
+// Runs a single prompt of a batch through the scan→generate→scan pipeline.
+async fn process_item(
+    state: &AppState,
+    security_client: &SecurityClient,
+    index: usize,
+    request: &mut GenerateRequest,
+) -> BatchGenerateResult {
+    let outcome = match blocked_prompt(security_client, request).await {
+        Ok(Some(assessment)) => Ok(Err(("prompt", assessment))),
+        Ok(None) => generate_assessed(state, security_client, request)
+            .await
+            .map(|result| match result {
+                GenerationResult::Blocked(_, assessment) => Err(("response", assessment)),
+                GenerationResult::Passed { response, .. } => Ok(response),
+            }),
+        Err(e) => Err(e),
+    };
+
+    match outcome {
+        Ok(Ok(response)) => BatchGenerateResult {
+            index,
+            status: BatchItemStatus::Allowed,
+            response: Some(response),
+            verdict: None,
+            error: None,
+        },
+        Ok(Err((stage, assessment))) => BatchGenerateResult {
+            index,
+            status: BatchItemStatus::Blocked,
+            response: None,
+            verdict: Some(scan_verdict(
+                stage,
+                &assessment,
+                &security_client.branding(),
+            )),
+            error: None,
+        },
+        Err(e) => {
+            warn!("Batch prompt {} failed: {}", index, e);
+            BatchGenerateResult {
+                index,
+                status: BatchItemStatus::Failed,
+                response: None,
+                verdict: None,
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// Handler for the model capability endpoint.
+//
+// Reports the capabilities learned from Ollama, such as the context length and
+// support for images or tools, so clients can pick a suitable model up front.
+use axum::{
+    extract::{Query, State},
+    response::Response,
+};
+use serde::Deserialize;
+use tracing::{debug, error};
+
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::AppState;
+
+// Query parameters of the capability endpoint.
+#[derive(Debug, Deserialize)]
+pub struct CapabilitiesQuery {
+    // Only report this model
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+// Handler for model capabilities (GET /api/capabilities)
+//
+// Reports the capabilities of every installed model, or of a single model when
+// `?model=` is given.
+pub async fn handle_capabilities(
+    State(state): State<AppState>,
+    Query(query): Query<CapabilitiesQuery>,
+) -> Result<Response, ApiError> {
+    if let Some(model) = query.model.filter(|model| !model.is_empty()) {
+        let capabilities = state
+            .capabilities
+            .get(&state.ollama_client, &model)
+            .await
+            .ok_or_else(|| ApiError::NotFound(format!("No model named {}", model)))?;
+        return build_serialized_response(capabilities);
+    }
+
+    let response = state.ollama_client.forward_get("/api/tags").await?;
+    let tags: serde_json::Value = response.json().await.map_err(|e| {
+        error!("Failed to parse model list: {}", e);
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+    let models: Vec<&str> = tags["models"]
+        .as_array()
+        .map(|models| models.iter().filter_map(|model| model["name"].as_str()).collect())
+        .unwrap_or_default();
+    debug!("Reporting capabilities of {} models", models.len());
+
+    let mut capabilities = Vec::with_capacity(models.len());
+    for model in models {
+        if let Some(model_capabilities) = state.capabilities.get(&state.ollama_client, model).await
+        {
+            capabilities.push(model_capabilities);
+        }
+    }
+    build_serialized_response(capabilities)
+}
@@ -25,10 +25,11 @@ use std::net::SocketAddr;
 use tracing::{debug, error, info};
 
 use crate::handlers::utils::{
-    build_json_response, build_violation_response, format_security_violation_message,
-    handle_streaming_request, log_llm_metrics,
+    build_json_response, build_violation_response, handle_streaming_request, log_llm_metrics,
+    RequestSecurity,
 };
 use crate::handlers::ApiError;
+use crate::postprocess::PostProcessState;
 use crate::security::SecurityClient;
 use crate::types::{ChatRequest, ChatResponse, Message};
 use crate::AppState;
@@ -40,7 +41,8 @@ use crate::AppState;
 // Handles chat completion requests with security assessment.
 //
 // This handler:
-// 1. Performs security checks on incoming chat messages
+// 1. Rejects images to non-vision models and conversations that exceed the
+//    model's context window, then performs security checks on incoming chat messages
 // 2. Routes the request to Ollama if messages pass security checks
 // 3. Scans the response for security issues before returning to client
 // 4. Handles both streaming and non-streaming responses
@@ -48,6 +50,7 @@ use crate::AppState;
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
 // * `Json(request)` - The chat completion request from the client
 //
 // # Returns
@@ -57,6 +60,7 @@ use crate::AppState;
 pub async fn handle_chat(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
     Json(mut request): Json<ChatRequest>,
 ) -> Result<Response, ApiError> {
     // Ensure stream parameter is always set
@@ -70,9 +74,36 @@ pub async fn handle_chat(
         addr.ip()
     );
 
-    // Clone security client and configure with user's IP
-    let mut security_client = state.security_client.clone();
-    security_client.with_user_ip(addr.ip().to_string());
+    // Pull the model first if it is allowed but missing
+    state.ensure_model_available(&request.model).await?;
+
+    // Reject images the model cannot process
+    if request
+        .messages
+        .iter()
+        .any(|message| message.images.as_ref().is_some_and(|images| !images.is_empty()))
+    {
+        state
+            .capabilities
+            .require_vision(&state.ollama_client, &request.model)
+            .await?;
+    }
+
+    // Reject conversations that would be truncated to fit the model's context window
+    let conversation: Vec<&str> = request
+        .messages
+        .iter()
+        .map(|message| message.content.as_str())
+        .collect();
+    state
+        .context_windows
+        .check(
+            &state.ollama_client,
+            &request.model,
+            &conversation.join("\n"),
+            request.options.as_ref(),
+        )
+        .await?;
 
     // Security assessment: check all input messages for policy violations
     // and potentially replace with masked content
@@ -83,10 +114,10 @@ pub async fn handle_chat(
     // Route based on streaming or non-streaming mode
     if request.stream.unwrap() {
         debug!("Handling streaming chat request");
-        handle_streaming_chat(State(state), Json(request)).await
+        handle_streaming_chat(State(state), security_client, Json(request)).await
     } else {
         debug!("Handling non-streaming chat request");
-        handle_non_streaming_chat(State(state), Json(request)).await
+        handle_non_streaming_chat(State(state), &security_client, Json(request)).await
     }
 }
 
@@ -127,17 +158,22 @@ async fn assess_chat_messages(
             .await?;
 
         if !assessment.is_safe {
-            let blocked_message = format_security_violation_message(&assessment);
+            let branding = security_client.branding();
+            let blocked_message = branding.violation_message(&assessment);
             let response = ChatResponse {
                 model: request.model.clone(),
                 created_at: chrono::Utc::now().to_rfc3339(),
                 message: Message {
                     role: "assistant".to_string(),
                     content: blocked_message,
+                    images: None,
                 },
                 done: true,
             };
-            return Ok(Err(build_violation_response(response)?));
+            return Ok(Err(build_violation_response(
+                response,
+                branding.block_page(&assessment),
+            )?));
         }
 
         // If we have masked content use it
@@ -161,6 +197,7 @@ async fn assess_chat_messages(
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `security_client` - Security client configured for this request
 // * `Json(request)` - The chat completion request from the client
 //
 // # Returns
@@ -169,6 +206,7 @@ async fn assess_chat_messages(
 // * `Err(ApiError)` - If an error occurs during processing
 async fn handle_non_streaming_chat(
     State(state): State<AppState>,
+    security_client: &SecurityClient,
     Json(request): Json<ChatRequest>,
 ) -> Result<Response, ApiError> {
     // Forward request to Ollama
@@ -196,20 +234,39 @@ async fn handle_non_streaming_chat(
     })?;
 
     // Security assessment on response content
-    let assessment = state
-        .security_client
+    let assessment = security_client
         .assess_content(&response_body.message.content, &request.model, false)
         .await?;
 
     if !assessment.is_safe {
         // Replace content with security violation message
-        response_body.message.content = format_security_violation_message(&assessment);
-        return build_violation_response(response_body);
+        let branding = security_client.branding();
+        response_body.message.content = branding.violation_message(&assessment);
+        return build_violation_response(response_body, branding.block_page(&assessment));
     }
 
     // If we have masked content, use it
-    let response = if assessment.is_masked {
+    let mut modified = false;
+    if assessment.is_masked {
         response_body.message.content = assessment.final_content;
+        modified = true;
+    }
+
+    // Mask secrets found by the local secret scanner
+    if let Some(masked) = security_client.mask_secrets(&response_body.message.content, &mut false) {
+        response_body.message.content = masked;
+        modified = true;
+    }
+
+    // Apply the configured post-processing to the assessed content
+    if let Some(processed) = state
+        .postprocess
+        .process(&response_body.message.content, &mut PostProcessState::default()) {
+        response_body.message.content = processed;
+        modified = true;
+    }
+
+    let response = if modified {
         info!("Chat response passed security checks (with masked content), returning to client");
 
         let json_bytes = serde_json::to_vec(&response_body).map_err(|e| {
@@ -232,6 +289,7 @@ async fn handle_non_streaming_chat(
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `security_client` - Security client configured for this request
 // * `Json(request)` - The chat completion request from the client
 //
 // # Returns
@@ -240,11 +298,20 @@ async fn handle_non_streaming_chat(
 // * `Err(ApiError)` - If an error occurs during processing
 async fn handle_streaming_chat(
     State(state): State<AppState>,
+    security_client: SecurityClient,
     Json(request): Json<ChatRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Processing streaming chat request");
 
     let model = request.model.clone();
     // For streaming chat, we're dealing with responses from the LLM, so is_prompt should be false
-    handle_streaming_request::<ChatRequest>(&state, request, "/api/chat", &model, false).await
+    handle_streaming_request::<ChatRequest>(
+        &state,
+        security_client,
+        request,
+        "/api/chat",
+        &model,
+        false,
+    )
+    .await
 }
@@ -1,19 +1,62 @@
-use crate::handlers::utils::{build_json_response, build_violation_response};
+use crate::handlers::utils::{build_json_response, build_violation_response, RequestSecurity};
 use crate::handlers::ApiError;
+use crate::security::{Assessment, SecurityClient};
 use crate::types::EmbeddingsRequest;
 use crate::types::EmbeddingsResponse;
 use crate::AppState;
 use axum::{extract::State, response::Response, Json};
+use bytes::Bytes;
 use tracing::debug;
 
+// Outcome of an embeddings request after security assessment.
+pub enum EmbeddingsOutcome {
+    // The prompt passed assessment; carries the raw Ollama response body
+    Embedded(Bytes),
+    // The prompt was blocked by the security assessment
+    Blocked(Box<Assessment>),
+}
+
 pub async fn handle_embeddings(
     State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
     Json(request): Json<EmbeddingsRequest>,
 ) -> Result<Response, ApiError> {
+    match assess_and_embed(&state, &security_client, &request).await? {
+        EmbeddingsOutcome::Embedded(body_bytes) => build_json_response(body_bytes),
+        EmbeddingsOutcome::Blocked(_) => {
+            // Return a mock embedding response with zeros
+            let response = EmbeddingsResponse {
+                embedding: vec![0.0; 10], // A small vector of zeros as placeholder
+            };
+
+            build_violation_response(response, None)
+        }
+    }
+}
+
+// Assesses an embeddings prompt and forwards it to Ollama if it is safe.
+//
+// # Arguments
+//
+// * `state` - Application state containing client connections
+// * `security_client` - Security client configured for the request
+// * `request` - The embeddings request to assess and forward
+//
+// # Returns
+//
+// * `Ok(EmbeddingsOutcome)` - The Ollama response body, or the blocking assessment
+// * `Err(ApiError)` - If an error occurs during assessment or forwarding
+pub async fn assess_and_embed(
+    state: &AppState,
+    security_client: &SecurityClient,
+    request: &EmbeddingsRequest,
+) -> Result<EmbeddingsOutcome, ApiError> {
     debug!("Received embeddings request for model: {}", request.model);
 
-    let assessment = state
-        .security_client
+    // Pull the model first if it is allowed but missing
+    state.ensure_model_available(&request.model).await?;
+
+    let assessment = security_client
         .assess_content(
             &request.prompt,
             &request.model,
@@ -22,22 +65,27 @@ pub async fn handle_embeddings(
         .await?;
 
     if !assessment.is_safe {
-        // Return a mock embedding response with zeros
-        let response = EmbeddingsResponse {
-            embedding: vec![0.0; 10], // A small vector of zeros as placeholder
-        };
-
-        return build_violation_response(response);
+        return Ok(EmbeddingsOutcome::Blocked(Box::new(assessment)));
     }
 
-    // Forward to Ollama
+    // Forward to Ollama, with the masked prompt if masked
+    let masked;
+    let request = if assessment.is_masked {
+        masked = EmbeddingsRequest {
+            prompt: assessment.final_content,
+            ..request.clone()
+        };
+        &masked
+    } else {
+        request
+    };
     let response = state
         .ollama_client
-        .forward("/api/embeddings", &request)
+        .forward("/api/embeddings", request)
         .await?;
     let body_bytes = response
         .bytes()
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
-    build_json_response(body_bytes)
+    Ok(EmbeddingsOutcome::Embedded(body_bytes))
 }
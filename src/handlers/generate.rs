@@ -3,20 +3,24 @@
 // This module provides security-enhanced handlers for text generation
 // requests, scanning both prompts and responses for policy violations.
 use axum::{extract::State, response::Response, Json};
+use bytes::Bytes;
 use tracing::{debug, error};
 
 use crate::handlers::utils::{
-    build_json_response, build_violation_response, format_security_violation_message,
-    handle_streaming_request, log_llm_metrics,
+    build_json_response, build_serialized_response, build_violation_response,
+    handle_streaming_request, log_llm_metrics, RequestSecurity,
 };
 use crate::handlers::ApiError;
+use crate::postprocess::PostProcessState;
+use crate::security::{Assessment, SecurityClient};
 use crate::types::{GenerateRequest, GenerateResponse};
 use crate::AppState;
 
 // Handles text generation requests with security assessment.
 //
 // This handler:
-// 1. Performs security checks on the input prompt
+// 1. Rejects images to non-vision models and prompts that exceed the model's
+//    context window, then performs security checks on the input prompt
 // 2. Routes the request to Ollama if the prompt passes security checks
 // 3. Scans the generated response for security issues before returning to client
 // 4. Handles both streaming and non-streaming requests
@@ -24,6 +28,7 @@ use crate::AppState;
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
 // * `Json(request)` - The generation request from the client
 //
 // # Returns
@@ -32,25 +37,47 @@ use crate::AppState;
 // * `Err(ApiError)` - If an error occurs during processing
 pub async fn handle_generate(
     State(state): State<AppState>,
-    Json(request): Json<GenerateRequest>,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(mut request): Json<GenerateRequest>,
 ) -> Result<Response, ApiError> {
     // Ensure stream parameter is explicitly set
     // request.stream = Some(false);
 
     debug!("Received generate request for model: {}", request.model);
 
+    // Pull the model first if it is allowed but missing
+    state.ensure_model_available(&request.model).await?;
+
+    // Reject images the model cannot process
+    if request.images.as_ref().is_some_and(|images| !images.is_empty()) {
+        state
+            .capabilities
+            .require_vision(&state.ollama_client, &request.model)
+            .await?;
+    }
+
+    // Reject prompts that would be truncated to fit the model's context window
+    let prompt = match &request.system {
+        Some(system) => format!("{}\n{}", system, request.prompt),
+        None => request.prompt.clone(),
+    };
+    state
+        .context_windows
+        .check(&state.ollama_client, &request.model, &prompt, request.options.as_ref())
+        .await?;
+
     // Check the input prompt for security violations
-    if let Err(response) = assess_generate_prompt(&state, &request).await? {
+    if let Err(response) = assess_generate_prompt(&security_client, &mut request).await? {
         return Ok(response);
     }
 
     // Route based on streaming or non-streaming mode
     if request.stream.unwrap() {
         debug!("Handling streaming generate request");
-        handle_streaming_generate(State(state), Json(request)).await
+        handle_streaming_generate(State(state), security_client, Json(request)).await
     } else {
         debug!("Handling non-streaming generate request");
-        handle_non_streaming_generate(State(state), Json(request)).await
+        handle_non_streaming_generate(State(state), &security_client, Json(request)).await
     }
 }
 
@@ -58,7 +85,7 @@ pub async fn handle_generate(
 //
 // # Arguments
 //
-// * `state` - Application state containing security client
+// * `security_client` - Security client configured for this request
 // * `request` - The generation request containing the prompt to assess
 //
 // # Returns
@@ -67,18 +94,13 @@ pub async fn handle_generate(
 // * `Ok(Err(Response))` - If security violation is detected, with appropriate response
 // * `Err(ApiError)` - If an error occurs during security assessment
 async fn assess_generate_prompt(
-    state: &AppState,
-    request: &GenerateRequest,
+    security_client: &SecurityClient,
+    request: &mut GenerateRequest,
 ) -> Result<Result<(), Response>, ApiError> {
-    // Check input prompt
-    let assessment = state
-        .security_client
-        .assess_content(&request.prompt, &request.model, true)
-        .await?;
-
     // If the content is not safe, create a blocked response
-    if !assessment.is_safe {
-        let blocked_message = format_security_violation_message(&assessment);
+    if let Some(assessment) = blocked_prompt(security_client, request).await? {
+        let branding = security_client.branding();
+        let blocked_message = branding.violation_message(&assessment);
 
         let response = GenerateResponse {
             model: request.model.clone(),
@@ -88,17 +110,65 @@ async fn assess_generate_prompt(
             done: true,
         };
 
-        return Ok(Err(build_violation_response(response)?));
+        return Ok(Err(build_violation_response(
+            response,
+            branding.block_page(&assessment),
+        )?));
     }
 
     Ok(Ok(()))
 }
 
+// Checks a generation prompt, returning the assessment that blocked it, if any.
+// A prompt that passes is replaced with its masked version, if masked.
+//
+// # Arguments
+//
+// * `security_client` - Security client configured for this request
+// * `request` - The generation request containing the prompt to assess
+//
+// # Returns
+//
+// * `Ok(None)` - If the prompt passes security checks
+// * `Ok(Some(Assessment))` - The assessment of a blocked prompt
+// * `Err(ApiError)` - If an error occurs during security assessment
+pub(crate) async fn blocked_prompt(
+    security_client: &SecurityClient,
+    request: &mut GenerateRequest,
+) -> Result<Option<Box<Assessment>>, ApiError> {
+    let assessment = security_client
+        .assess_content(&request.prompt, &request.model, true)
+        .await?;
+
+    if !assessment.is_safe {
+        return Ok(Some(Box::new(assessment)));
+    }
+    if assessment.is_masked {
+        debug!("Using masked content for prompt with sensitive data");
+        request.prompt = assessment.final_content;
+    }
+    Ok(None)
+}
+
+// Result of a non-streaming generation whose prompt already passed assessment.
+pub(crate) enum GenerationResult {
+    // The response was blocked; carries the Ollama response and the blocking assessment
+    Blocked(GenerateResponse, Box<Assessment>),
+    // The response passed; carries the raw Ollama body, the response text after
+    // secret masking and post-processing, and whether that text was changed
+    Passed {
+        body: Bytes,
+        response: String,
+        rewritten: bool,
+    },
+}
+
 // Handles non-streaming generate requests.
 //
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `security_client` - Security client configured for this request
 // * `Json(request)` - The generation request from the client
 //
 // # Returns
@@ -107,14 +177,62 @@ async fn assess_generate_prompt(
 // * `Err(ApiError)` - If an error occurs during processing
 async fn handle_non_streaming_generate(
     State(state): State<AppState>,
+    security_client: &SecurityClient,
     Json(request): Json<GenerateRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Processing non-streaming generate request");
 
+    match generate_assessed(&state, security_client, &request).await? {
+        GenerationResult::Blocked(mut response_body, assessment) => {
+            // Replace the content with security message
+            let branding = security_client.branding();
+            response_body.response = branding.violation_message(&assessment);
+
+            build_violation_response(response_body, branding.block_page(&assessment))
+        }
+        GenerationResult::Passed {
+            body,
+            response,
+            rewritten: true,
+        } => {
+            // Keep all other response fields
+            let mut json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| {
+                error!("Failed to parse response: {}", e);
+                ApiError::InternalError("Failed to parse response".to_string())
+            })?;
+            json["response"] = serde_json::Value::String(response);
+            build_serialized_response(json)
+        }
+        // Return safe response
+        GenerationResult::Passed { body, .. } => build_json_response(body),
+    }
+}
+
+// Generates a non-streaming response and assesses it.
+//
+// The prompt must already have passed assessment. Secrets found by the local
+// secret scanner are masked and the configured post-processing is applied to
+// responses that pass.
+//
+// # Arguments
+//
+// * `state` - Application state containing client connections
+// * `security_client` - Security client configured for this request
+// * `request` - The generation request, with streaming disabled
+//
+// # Returns
+//
+// * `Ok(GenerationResult)` - The assessed generation
+// * `Err(ApiError)` - If an error occurs during generation or assessment
+pub(crate) async fn generate_assessed(
+    state: &AppState,
+    security_client: &SecurityClient,
+    request: &GenerateRequest,
+) -> Result<GenerationResult, ApiError> {
     // Forward request to Ollama
     let response = state
         .ollama_client
-        .forward("/api/generate", &request)
+        .forward("/api/generate", request)
         .await?;
 
     // Read response body
@@ -129,27 +247,40 @@ async fn handle_non_streaming_generate(
     }
 
     // Parse response
-    let mut response_body: GenerateResponse = serde_json::from_slice(&body_bytes).map_err(|e| {
+    let response_body: GenerateResponse = serde_json::from_slice(&body_bytes).map_err(|e| {
         error!("Failed to parse response: {}", e);
         ApiError::InternalError("Failed to parse response".to_string())
     })?;
 
     // Check model output for security issues
-    let assessment = state
-        .security_client
+    let assessment = security_client
         .assess_content(&response_body.response, &request.model, false)
         .await?;
 
-    // If response is not safe, replace content with security message
     if !assessment.is_safe {
-        // Replace the content with security message
-        response_body.response = format_security_violation_message(&assessment);
-
-        return build_violation_response(response_body);
+        return Ok(GenerationResult::Blocked(response_body, Box::new(assessment)));
     }
 
-    // Return safe response
-    build_json_response(body_bytes)
+    // Mask secrets found by the local secret scanner, then apply the configured
+    // post-processing
+    let masked = security_client.mask_secrets(&response_body.response, &mut false);
+    let current = masked.as_deref().unwrap_or(&response_body.response);
+    let processed = state
+        .postprocess
+        .process(current, &mut PostProcessState::default());
+
+    Ok(match processed.or(masked) {
+        Some(response) => GenerationResult::Passed {
+            body: body_bytes,
+            response,
+            rewritten: true,
+        },
+        None => GenerationResult::Passed {
+            body: body_bytes,
+            response: response_body.response,
+            rewritten: false,
+        },
+    })
 }
 
 // Handles streaming generate requests.
@@ -157,6 +288,7 @@ async fn handle_non_streaming_generate(
 // # Arguments
 //
 // * `State(state)` - Application state containing client connections
+// * `security_client` - Security client configured for this request
 // * `Json(request)` - The generation request from the client
 //
 // # Returns
@@ -165,6 +297,7 @@ async fn handle_non_streaming_generate(
 // * `Err(ApiError)` - If an error occurs during processing
 async fn handle_streaming_generate(
     State(state): State<AppState>,
+    security_client: SecurityClient,
     Json(request): Json<GenerateRequest>,
 ) -> Result<Response, ApiError> {
     debug!("Setting up streaming generate request");
@@ -173,6 +306,7 @@ async fn handle_streaming_generate(
     // For streaming generate, we're dealing with responses from the LLM, so is_prompt should be false
     handle_streaming_request::<GenerateRequest>(
         &state,
+        security_client,
         request,
         "/api/generate",
         &model,
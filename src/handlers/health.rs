@@ -0,0 +1,47 @@
+// Handlers for the health check endpoints used by load balancers and orchestrators.
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::health::{HealthStatus, ReadinessStatus};
+use crate::AppState;
+
+// Handler for health checks (GET /healthz)
+//
+// Responds with 200 when all upstreams are reachable and 503 when the proxy is
+// degraded; the body reports the state of each dependency either way.
+pub async fn handle_healthz(State(state): State<AppState>) -> Response {
+    let report = state.health.check().await;
+    let status = match report.status {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Degraded => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report)).into_response()
+}
+
+// Handler for liveness checks (GET /livez)
+//
+// Always responds with 200 while the process is able to serve requests; upstream
+// outages do not affect liveness.
+pub async fn handle_livez() -> Response {
+    Json(json!({ "status": "alive" })).into_response()
+}
+
+// Handler for readiness checks (GET /readyz)
+//
+// Responds with 200 when both upstreams are reachable and the proxy is not
+// shutting down, and 503 otherwise; the body reports the individual checks, the
+// state of the PANW security channel and the Ollama version compatibility either
+// way.
+pub async fn handle_readyz(State(state): State<AppState>) -> Response {
+    let report = state.health.readiness().await;
+    let status = match report.status {
+        ReadinessStatus::Ready => StatusCode::OK,
+        ReadinessStatus::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(report)).into_response()
+}
@@ -0,0 +1,147 @@
+// Handlers for asynchronous generation jobs.
+//
+// `POST /api/jobs` accepts a generate request and returns immediately with the
+// job ID; the generation runs in the background through the regular security
+// pipeline. `GET /api/jobs/{id}` reports the job state and, once the job has
+// finished, returns its result a single time.
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::net::SocketAddr;
+use tracing::{debug, error, info, warn};
+
+use crate::client_auth::ClientIdentity;
+use crate::handlers::generate::{blocked_prompt, generate_assessed, GenerationResult};
+use crate::handlers::utils::{build_serialized_response, scan_verdict, RequestSecurity};
+use crate::handlers::ApiError;
+use crate::jobs::JobOutcome;
+use crate::security::SecurityClient;
+use crate::types::GenerateRequest;
+use crate::AppState;
+
+// Handles job submissions by queueing a generation in the background.
+//
+// The generation is cancelled if the kill switch is activated before it finishes.
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `ConnectInfo(addr)` - Address of the client, which owns the job if it has no identity
+// * `ClientIdentity(identity)` - Authenticated identity of the client, which owns the job
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `Json(request)` - The generate request to run; streaming is always disabled
+//
+// # Returns
+//
+// * `Ok(Response)` - 202 Accepted with the queued job
+// * `Err(ApiError)` - If the client already holds too many jobs
+pub async fn handle_create_job(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIdentity(identity): ClientIdentity,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(mut request): Json<GenerateRequest>,
+) -> Result<Response, ApiError> {
+    request.stream = Some(false);
+
+    let owner = job_owner(identity, addr);
+    let job = state.jobs.create(&owner)?;
+    info!("Accepted job {} for model {}", job.id, request.model);
+
+    let id = job.id.clone();
+    tokio::spawn(async move {
+        state.jobs.start(&id);
+        // Activating the kill switch cancels the job, dropping its Ollama request
+        let outcome = tokio::select! {
+            outcome = run_job(&state, &security_client, &mut request) => outcome,
+            message = state.kill_switch.engaged() => {
+                warn!("Cancelled job {} on kill switch activation", id);
+                JobOutcome::Failed(message)
+            }
+        };
+        state.jobs.finish(&id, outcome);
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+// Handles job lookups.
+//
+// Finished jobs are removed once they have been returned.
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing the job store
+// * `ConnectInfo(addr)` - Address of the client, which must own the job if it has no identity
+// * `ClientIdentity(identity)` - Authenticated identity of the client, which must own the job
+// * `Path(id)` - ID of the job
+//
+// # Returns
+//
+// * `Ok(Response)` - The job state and, if finished, its result
+// * `Err(ApiError)` - Not found for unknown, expired, fetched or foreign jobs
+pub async fn handle_get_job(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIdentity(identity): ClientIdentity,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    debug!("Fetching job {}", id);
+
+    let job = state
+        .jobs
+        .fetch(&id, &job_owner(identity, addr))
+        .ok_or_else(|| ApiError::NotFound(format!("No job found with ID {}", id)))?;
+    build_serialized_response(job)
+}
+
+// Returns the owner of a client's jobs: its authenticated identity, or its address
+// if the request carried no identity.
+fn job_owner(identity: Option<String>, addr: SocketAddr) -> String {
+    identity.unwrap_or_else(|| addr.ip().to_string())
+}
+
+// Runs a job through the scan→generate→scan pipeline.
+async fn run_job(
+    state: &AppState,
+    security_client: &SecurityClient,
+    request: &mut GenerateRequest,
+) -> JobOutcome {
+    match blocked_prompt(security_client, request).await {
+        Ok(Some(assessment)) => {
+            return JobOutcome::Blocked(scan_verdict(
+                "prompt",
+                &assessment,
+                &security_client.branding(),
+            ))
+        }
+        Ok(None) => {}
+        Err(e) => return JobOutcome::Failed(e.to_string()),
+    }
+
+    match generate_assessed(state, security_client, request).await {
+        Ok(GenerationResult::Blocked(_, assessment)) => {
+            JobOutcome::Blocked(scan_verdict(
+                "response",
+                &assessment,
+                &security_client.branding(),
+            ))
+        }
+        Ok(GenerationResult::Passed { body, response, .. }) => {
+            match serde_json::from_slice::<serde_json::Value>(&body) {
+                Ok(mut json) => {
+                    json["response"] = serde_json::Value::String(response);
+                    JobOutcome::Completed(json)
+                }
+                Err(e) => {
+                    error!("Failed to parse response: {}", e);
+                    JobOutcome::Failed("Failed to parse response".to_string())
+                }
+            }
+        }
+        Err(e) => JobOutcome::Failed(e.to_string()),
+    }
+}
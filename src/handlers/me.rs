@@ -0,0 +1,40 @@
+// Self-service endpoints for authenticated clients.
+//
+// Clients can read their own usage and the sanitized reasons of their recent
+// violations. The endpoints only ever report on the caller's own identity and
+// require the request to be authenticated by an API key, JWT or client
+// certificate.
+use axum::{extract::State, response::Response};
+
+use crate::client_auth::ClientIdentity;
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::AppState;
+
+// Returns the identity of the caller, rejecting anonymous requests.
+fn require_identity(identity: ClientIdentity) -> Result<String, ApiError> {
+    identity.0.ok_or_else(|| {
+        ApiError::Unauthorized("This endpoint requires client authentication".to_string())
+    })
+}
+
+// Handler for the caller's own usage (GET /api/me/usage)
+pub async fn handle_my_usage(
+    State(state): State<AppState>,
+    identity: ClientIdentity,
+) -> Result<Response, ApiError> {
+    let client = require_identity(identity)?;
+    build_serialized_response(state.usage.usage_of(&client))
+}
+
+// Handler for the caller's recent violations (GET /api/me/violations)
+//
+// Returns the most recent blocked prompts and responses, newest first, with
+// their category and findings but without the blocked content.
+pub async fn handle_my_violations(
+    State(state): State<AppState>,
+    identity: ClientIdentity,
+) -> Result<Response, ApiError> {
+    let client = require_identity(identity)?;
+    build_serialized_response(state.usage.violations_of(&client))
+}
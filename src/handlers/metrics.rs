@@ -0,0 +1,16 @@
+// Prometheus metrics endpoint.
+//
+// Renders the process-wide metrics recorded by the proxy in the Prometheus
+// text exposition format.
+use axum::{body::Body, response::Response};
+
+use crate::handlers::ApiError;
+use crate::metrics;
+
+// Returns all recorded metrics in the Prometheus text format.
+pub async fn handle_metrics() -> Result<Response, ApiError> {
+    Response::builder()
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(Body::from(metrics::render()))
+        .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))
+}
@@ -4,12 +4,25 @@ use axum::{
     Json,
 };
 use serde_json::json;
-use tracing::error;
+use tracing::{debug, error, warn};
 
+pub mod admin;
+pub mod auth;
+pub mod batch;
+pub mod capabilities;
 pub mod chat;
 pub mod embeddings;
 pub mod generate;
+pub mod health;
+pub mod jobs;
+pub mod me;
+pub mod metrics;
 pub mod models;
+pub mod openai;
+pub mod provenance;
+pub mod proxy;
+pub mod summarize;
+pub mod templates;
 pub mod utils;
 pub mod version;
 
@@ -42,6 +55,85 @@ pub enum ApiError {
     // not directly related to external services.
     #[error("Internal error: {0}")]
     InternalError(String),
+
+    // Authentication failures for protected endpoints.
+    //
+    // Returned when a request lacks valid credentials for an endpoint
+    // that requires them, such as the admin API.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    // Authenticated clients attempting something they are not allowed to.
+    //
+    // Returned when a client sends a header only trusted clients may
+    // send, such as the app user header.
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    // Malformed or invalid client requests.
+    //
+    // Returned when a request is well-formed JSON but carries values
+    // the proxy cannot act on.
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    // Requests that conflict with an earlier request.
+    //
+    // Returned when an idempotency key is reused for a request with
+    // a different body.
+    #[error("Unprocessable entity: {0}")]
+    UnprocessableEntity(String),
+
+    // Clients exceeding a per-client limit.
+    //
+    // Returned when a client already holds the maximum number of
+    // outstanding resources, such as generation jobs.
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
+    // Requested resources that do not exist.
+    //
+    // Returned when a lookup by ID, such as a response manifest,
+    // finds nothing.
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    // Requests for a model that is still being pulled.
+    //
+    // Returned with a `Retry-After` header while an automatic pull of
+    // the requested model is running.
+    #[error("Model not ready: {message}")]
+    ModelPulling {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    // Clients exceeding their request rate limit.
+    //
+    // Returned with a `Retry-After` header telling the client when its
+    // next request will be accepted.
+    #[error("Rate limit exceeded: {message}")]
+    RateLimited {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    // Requests shed while the server is at capacity.
+    //
+    // Returned with a `Retry-After` header when all generation slots
+    // and queue places are taken.
+    #[error("Server at capacity: {message}")]
+    Overloaded {
+        message: String,
+        retry_after_secs: u64,
+    },
+
+    // Requests that exceeded their timeout.
+    //
+    // Returned when the upstream request or a pending scan was aborted
+    // because the route's request timeout passed.
+    #[error("Timeout: {0}")]
+    Timeout(String),
 }
 
 impl IntoResponse for ApiError {
@@ -50,6 +142,39 @@ impl IntoResponse for ApiError {
     // Maps each error type to an appropriate HTTP status code and
     // formats the error message for the response body.
     fn into_response(self) -> Response {
+        // Model pulls and rate limits additionally tell the client when to retry
+        let retry = match &self {
+            ApiError::ModelPulling {
+                message,
+                retry_after_secs,
+            } => {
+                debug!("Model not ready: {}", message);
+                Some((StatusCode::TOO_EARLY, message.clone(), *retry_after_secs))
+            }
+            ApiError::RateLimited {
+                message,
+                retry_after_secs,
+            } => Some((StatusCode::TOO_MANY_REQUESTS, message.clone(), *retry_after_secs)),
+            ApiError::Overloaded {
+                message,
+                retry_after_secs,
+            } => Some((StatusCode::SERVICE_UNAVAILABLE, message.clone(), *retry_after_secs)),
+            _ => None,
+        };
+        if let Some((status, message, retry_after_secs)) = retry {
+            let body = Json(json!({
+                "error": message,
+                "status": status.as_u16(),
+            }));
+            let mut response = (status, body).into_response();
+            if let Ok(value) = retry_after_secs.to_string().parse() {
+                response
+                    .headers_mut()
+                    .insert(axum::http::header::RETRY_AFTER, value);
+            }
+            return response;
+        }
+
         // Map error types to appropriate status codes and messages
         let (status, error_message) = match self {
             ApiError::OllamaError(e) => {
@@ -71,6 +196,14 @@ impl IntoResponse for ApiError {
                         StatusCode::TOO_MANY_REQUESTS,
                         format!("Rate limit exceeded. Please retry after {} {}.", interval, unit)
                     ),
+                    crate::security::SecurityError::BudgetExhausted => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "The monthly security scan budget has been exhausted. Please contact your administrator.".to_string()
+                    ),
+                    crate::security::SecurityError::CircuitOpen => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "The security scanning service is currently unavailable. Please try again later.".to_string()
+                    ),
                     crate::security::SecurityError::BlockedContent(msg) => (
                         StatusCode::FORBIDDEN,
                         format!("Content blocked: {}", msg)
@@ -85,6 +218,37 @@ impl IntoResponse for ApiError {
                 error!("Internal server error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, msg)
             },
+            ApiError::Unauthorized(msg) => {
+                warn!("Unauthorized request: {}", msg);
+                (StatusCode::UNAUTHORIZED, msg)
+            },
+            ApiError::Forbidden(msg) => {
+                warn!("Forbidden request: {}", msg);
+                (StatusCode::FORBIDDEN, msg)
+            },
+            ApiError::BadRequest(msg) => {
+                warn!("Bad request: {}", msg);
+                (StatusCode::BAD_REQUEST, msg)
+            },
+            ApiError::UnprocessableEntity(msg) => {
+                warn!("Unprocessable request: {}", msg);
+                (StatusCode::UNPROCESSABLE_ENTITY, msg)
+            },
+            ApiError::TooManyRequests(msg) => {
+                warn!("Too many requests: {}", msg);
+                (StatusCode::TOO_MANY_REQUESTS, msg)
+            },
+            ApiError::NotFound(msg) => {
+                debug!("Not found: {}", msg);
+                (StatusCode::NOT_FOUND, msg)
+            },
+            ApiError::ModelPulling { message, .. } => (StatusCode::TOO_EARLY, message),
+            ApiError::RateLimited { message, .. } => (StatusCode::TOO_MANY_REQUESTS, message),
+            ApiError::Overloaded { message, .. } => (StatusCode::SERVICE_UNAVAILABLE, message),
+            ApiError::Timeout(msg) => {
+                warn!("Request timed out: {}", msg);
+                (StatusCode::GATEWAY_TIMEOUT, msg)
+            },
         };
 
         // Create a JSON response with the error message
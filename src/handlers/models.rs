@@ -1,19 +1,46 @@
-use axum::{extract::State, http::Method, response::Response, Json};
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use serde_json::{json, Value};
+use tracing::{debug, error, info, warn};
 
 use crate::{
     handlers::{utils::build_json_response, ApiError},
+    ollama::with_tag,
     AppState,
 };
 
+// Fields of an /api/show response that may carry baked-in prompts or credentials.
+const SENSITIVE_SHOW_FIELDS: [&str; 4] = ["system", "template", "parameters", "modelfile"];
+
+// Replacement text for sensitive fields that fail the security assessment.
+const REDACTED_FIELD: &str = "[redacted by security policy]";
+
+// Header used to echo confirmation tokens for destructive operations.
+const CONFIRMATION_HEADER: &str = "x-confirmation-token";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ModelRequest {
+    #[serde(default, alias = "model", skip_serializing_if = "String::is_empty")]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
+    // Source model for copy requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    // Destination model for copy requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    // Confirmation token echoed back for destructive operations, never forwarded
+    #[serde(default, skip_serializing)]
+    pub confirmation_token: Option<String>,
 }
 
 #[derive(Debug)]
@@ -75,6 +102,17 @@ async fn forward_to_ollama<T: Serialize>(
     body: Option<&T>,
     model_name: Option<&str>,
 ) -> Result<Response, ApiError> {
+    let body_bytes = fetch_from_ollama(state, endpoint, body, model_name).await?;
+    build_json_response(body_bytes)
+}
+
+// Forwards a request to the Ollama service and returns the raw response body.
+async fn fetch_from_ollama<T: Serialize>(
+    state: &AppState,
+    endpoint: OllamaEndpoint,
+    body: Option<&T>,
+    model_name: Option<&str>,
+) -> Result<Bytes, ApiError> {
     // Create log message
     let log_message = if endpoint.includes_model_name_in_logs() {
         if let Some(name) = model_name {
@@ -99,11 +137,141 @@ async fn forward_to_ollama<T: Serialize>(
     };
 
     // Process the response
-    let body_bytes = response
+    response
         .bytes()
         .await
-        .map_err(|e| ApiError::InternalError(e.to_string()))?;
-    build_json_response(body_bytes)
+        .map_err(|e| ApiError::InternalError(e.to_string()))
+}
+
+// Applies the configured show filter to the sensitive fields of an /api/show response.
+//
+// Fields are removed entirely when stripping is enabled. Otherwise, when scanning is
+// enabled, each field is assessed as model output and replaced with its masked version
+// or a redaction notice if the assessment requires it.
+async fn filter_show_response(
+    state: &AppState,
+    model_name: &str,
+    body_bytes: Bytes,
+) -> Result<Bytes, ApiError> {
+    let filter = &state.models_config.show_filter;
+    if !filter.strip && !filter.scan {
+        return Ok(body_bytes);
+    }
+
+    let mut json: Value = serde_json::from_slice(&body_bytes).map_err(|e| {
+        error!("Failed to parse show response: {}", e);
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+    let Some(fields) = json.as_object_mut() else {
+        return Ok(body_bytes);
+    };
+
+    for field in SENSITIVE_SHOW_FIELDS {
+        if filter.strip {
+            if fields.remove(field).is_some() {
+                debug!("Stripped '{}' from show response for {}", field, model_name);
+            }
+            continue;
+        }
+
+        let Some(Value::String(content)) = fields.get(field) else {
+            continue;
+        };
+
+        let assessment = state
+            .security_client
+            .assess_content(content, model_name, false)
+            .await?;
+
+        if !assessment.is_safe {
+            warn!(
+                "Redacted '{}' from show response for {}: category={}",
+                field, model_name, assessment.category
+            );
+            fields.insert(field.to_string(), Value::String(REDACTED_FIELD.to_string()));
+        } else if assessment.is_masked {
+            info!("Masked '{}' in show response for {}", field, model_name);
+            fields.insert(field.to_string(), Value::String(assessment.final_content));
+        }
+    }
+
+    let json_bytes = serde_json::to_vec(&json).map_err(|e| {
+        error!("Failed to serialize filtered show response: {}", e);
+        ApiError::InternalError("Failed to serialize response".to_string())
+    })?;
+    Ok(Bytes::from(json_bytes))
+}
+
+// Enforces the two-step confirmation flow for a destructive operation.
+//
+// The token may be echoed either in the `X-Confirmation-Token` header or in the
+// `confirmation_token` body field.
+//
+// # Returns
+//
+// * `None` - If confirmation is disabled or a valid token for this operation was supplied
+// * `Some(Response)` - A 428 response carrying a fresh token for this operation
+fn require_confirmation(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: &ModelRequest,
+    operation: &str,
+) -> Option<Response> {
+    if !state.models_config.confirm_destructive {
+        return None;
+    }
+
+    let supplied = headers
+        .get(CONFIRMATION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or_else(|| request.confirmation_token.clone());
+
+    if let Some(token) = supplied {
+        if state.confirmations.redeem(&token, operation) {
+            info!("Confirmed destructive operation: {}", operation);
+            return None;
+        }
+        warn!(
+            "Rejected invalid or expired confirmation token for: {}",
+            operation
+        );
+    }
+
+    let token = state.confirmations.issue(operation);
+    let ttl = state.confirmations.ttl().as_secs();
+    let status = StatusCode::PRECONDITION_REQUIRED;
+    let body = Json(json!({
+        "error": format!(
+            "Confirmation required for {}. Repeat the request with the confirmation token within {} seconds.",
+            operation, ttl
+        ),
+        "status": status.as_u16(),
+        "confirmation_token": token,
+        "expires_in": ttl,
+    }));
+
+    Some((status, body).into_response())
+}
+
+// Checks whether a model with the given name is present in Ollama.
+async fn model_exists(state: &AppState, name: &str) -> Result<bool, ApiError> {
+    let body_bytes = fetch_from_ollama::<()>(state, OllamaEndpoint::Tags, None, None).await?;
+    let tags: Value = serde_json::from_slice(&body_bytes).map_err(|e| {
+        error!("Failed to parse model list: {}", e);
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+
+    let wanted = with_tag(name);
+    let exists = tags["models"].as_array().is_some_and(|models| {
+        models.iter().any(|model| {
+            model["name"]
+                .as_str()
+                .is_some_and(|existing| with_tag(existing) == wanted)
+        })
+    });
+
+    Ok(exists)
 }
 
 // Handler for listing models (GET /api/tags)
@@ -116,13 +284,15 @@ pub async fn handle_show_model(
     State(state): State<AppState>,
     Json(request): Json<ModelRequest>,
 ) -> Result<Response, ApiError> {
-    forward_to_ollama(
+    let body_bytes = fetch_from_ollama(
         &state,
         OllamaEndpoint::Show,
         Some(&request),
         Some(&request.name),
     )
-    .await
+    .await?;
+    let body_bytes = filter_show_response(&state, &request.name, body_bytes).await?;
+    build_json_response(body_bytes)
 }
 
 // Handler for creating a model (POST /api/create)
@@ -134,18 +304,42 @@ pub async fn handle_create_model(
 }
 
 // Handler for copying a model (POST /api/copy)
+//
+// Copies over an existing destination replace that model and therefore require confirmation.
 pub async fn handle_copy_model(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<ModelRequest>,
 ) -> Result<Response, ApiError> {
+    if let Some(destination) = request.destination.as_deref() {
+        if state.models_config.confirm_destructive && model_exists(&state, destination).await? {
+            let operation = format!(
+                "copy:{}->{}",
+                request.source.as_deref().unwrap_or_default(),
+                destination
+            );
+            if let Some(response) = require_confirmation(&state, &headers, &request, &operation) {
+                return Ok(response);
+            }
+        }
+    }
+
     forward_to_ollama(&state, OllamaEndpoint::Copy, Some(&request), None).await
 }
 
 // Handler for deleting a model (POST /api/delete)
+//
+// Deletes always require confirmation when confirmation is enabled.
 pub async fn handle_delete_model(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<ModelRequest>,
 ) -> Result<Response, ApiError> {
+    let operation = format!("delete:{}", with_tag(&request.name));
+    if let Some(response) = require_confirmation(&state, &headers, &request, &operation) {
+        return Ok(response);
+    }
+
     forward_to_ollama(
         &state,
         OllamaEndpoint::Delete,
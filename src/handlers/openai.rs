@@ -0,0 +1,248 @@
+// Handlers for the OpenAI-compatible API.
+//
+// The legacy OpenAI completions and embeddings endpoints are translated onto the
+// native generate and embeddings handlers, so requests in the OpenAI wire format
+// pass through exactly the same security assessment before they are forwarded
+// to Ollama. Responses are translated back into the OpenAI format.
+use axum::{
+    body::{to_bytes, Body},
+    extract::State,
+    response::Response,
+    Json,
+};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::handlers::embeddings::{assess_and_embed, EmbeddingsOutcome};
+use crate::handlers::generate::handle_generate;
+use crate::handlers::utils::{build_serialized_response, RequestSecurity};
+use crate::handlers::ApiError;
+use crate::security::SecurityError;
+use crate::sse::{SseStream, SSE_CONTENT_TYPE};
+use crate::stream::SUMMARY_FIELD;
+use crate::types::{
+    CompletionChoice, CompletionRequest, CompletionResponse, CompletionUsage, EmbeddingsRequest,
+    EmbeddingsResponse, GenerateRequest, OpenAiEmbedding, OpenAiEmbeddingsRequest,
+    OpenAiEmbeddingsResponse,
+};
+use crate::AppState;
+
+// Largest generate response body read when translating a completion.
+const MAX_RESPONSE_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+// Handles OpenAI completion requests by mapping them onto the generate handler.
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `Json(request)` - The OpenAI completion request from the client
+//
+// # Returns
+//
+// * `Ok(Response)` - The completion, or a server-sent event stream of completion chunks
+// * `Err(ApiError)` - If the request is invalid or an error occurs during processing
+pub async fn handle_completions(
+    State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(request): Json<CompletionRequest>,
+) -> Result<Response, ApiError> {
+    debug!("Received OpenAI completion request for model: {}", request.model);
+
+    let streaming = request.stream.unwrap_or(false);
+    let model = request.model.clone();
+    let generate_request = to_generate_request(request)?;
+
+    let response = handle_generate(
+        State(state),
+        RequestSecurity(security_client),
+        Json(generate_request),
+    )
+    .await?;
+
+    let id = format!("cmpl-{}", Uuid::new_v4().simple());
+    let created = chrono::Utc::now().timestamp();
+    if streaming {
+        stream_completion(response, CompletionContext { id, created, model })
+    } else {
+        let body = to_bytes(response.into_body(), MAX_RESPONSE_BODY_BYTES)
+            .await
+            .map_err(|e| {
+                error!("Failed to read generate response: {}", e);
+                ApiError::InternalError("Failed to read response body".to_string())
+            })?;
+        let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| {
+            error!("Failed to parse generate response: {}", e);
+            ApiError::InternalError("Failed to parse response".to_string())
+        })?;
+
+        let context = CompletionContext { id, created, model };
+        let mut completion = context.completion(&json);
+        completion.usage = Some(CompletionUsage {
+            prompt_tokens: json["prompt_eval_count"].as_u64().unwrap_or(0),
+            completion_tokens: json["eval_count"].as_u64().unwrap_or(0),
+            total_tokens: json["prompt_eval_count"].as_u64().unwrap_or(0)
+                + json["eval_count"].as_u64().unwrap_or(0),
+        });
+        build_serialized_response(completion)
+    }
+}
+
+// Handles OpenAI embeddings requests by assessing and embedding each input.
+//
+// Every input is assessed separately; if any of them is blocked, the whole
+// request is rejected.
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `Json(request)` - The OpenAI embeddings request from the client
+//
+// # Returns
+//
+// * `Ok(Response)` - The embeddings in OpenAI format
+// * `Err(ApiError)` - If an input is blocked or an error occurs during processing
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(request): Json<OpenAiEmbeddingsRequest>,
+) -> Result<Response, ApiError> {
+    let inputs = request.input.into_vec();
+    if inputs.is_empty() {
+        return Err(ApiError::BadRequest("`input` must not be empty".to_string()));
+    }
+
+    let mut data = Vec::with_capacity(inputs.len());
+    for (index, prompt) in inputs.into_iter().enumerate() {
+        let embeddings_request = EmbeddingsRequest {
+            model: request.model.clone(),
+            prompt,
+            options: None,
+        };
+
+        match assess_and_embed(&state, &security_client, &embeddings_request).await? {
+            EmbeddingsOutcome::Embedded(body) => {
+                let response: EmbeddingsResponse = serde_json::from_slice(&body).map_err(|e| {
+                    error!("Failed to parse embeddings response: {}", e);
+                    ApiError::InternalError("Failed to parse response".to_string())
+                })?;
+                data.push(OpenAiEmbedding {
+                    object: "embedding",
+                    index,
+                    embedding: response.embedding,
+                });
+            }
+            EmbeddingsOutcome::Blocked(assessment) => {
+                return Err(ApiError::SecurityError(SecurityError::BlockedContent(
+                    security_client.branding().violation_message(&assessment),
+                )));
+            }
+        }
+    }
+
+    build_serialized_response(OpenAiEmbeddingsResponse {
+        object: "list",
+        data,
+        model: request.model,
+        usage: CompletionUsage::default(),
+    })
+}
+
+// Translates an OpenAI completion request into a native generate request.
+//
+// # Errors
+//
+// Returns a bad request error unless the request carries exactly one prompt.
+fn to_generate_request(request: CompletionRequest) -> Result<GenerateRequest, ApiError> {
+    let mut prompts = request.prompt.into_vec();
+    if prompts.len() != 1 {
+        return Err(ApiError::BadRequest(
+            "Exactly one prompt per completion request is supported".to_string(),
+        ));
+    }
+
+    let mut options = serde_json::Map::new();
+    if let Some(max_tokens) = request.max_tokens {
+        options.insert("num_predict".into(), max_tokens.into());
+    }
+    if let Some(temperature) = request.temperature {
+        options.insert("temperature".into(), temperature.into());
+    }
+    if let Some(top_p) = request.top_p {
+        options.insert("top_p".into(), top_p.into());
+    }
+    if let Some(stop) = request.stop {
+        options.insert("stop".into(), stop.into_vec().into());
+    }
+    if let Some(seed) = request.seed {
+        options.insert("seed".into(), seed.into());
+    }
+
+    Ok(GenerateRequest {
+        model: request.model,
+        prompt: prompts.remove(0),
+        system: None,
+        template: None,
+        context: None,
+        stream: Some(request.stream.unwrap_or(false)),
+        raw: None,
+        format: None,
+        options: (!options.is_empty()).then_some(serde_json::Value::Object(options)),
+        images: None,
+    })
+}
+
+// Identifies the completion that translated chunks belong to.
+struct CompletionContext {
+    id: String,
+    created: i64,
+    model: String,
+}
+
+impl CompletionContext {
+    // Translates a native generate response or chunk into a completion.
+    fn completion(&self, json: &serde_json::Value) -> CompletionResponse {
+        let finish_reason = json["done"].as_bool().unwrap_or(false).then(|| {
+            match json["done_reason"].as_str() {
+                Some("length") => "length",
+                _ => "stop",
+            }
+            .to_string()
+        });
+
+        CompletionResponse {
+            id: self.id.clone(),
+            object: "text_completion",
+            created: self.created,
+            model: self.model.clone(),
+            choices: vec![CompletionChoice {
+                text: json["response"].as_str().unwrap_or_default().to_string(),
+                index: 0,
+                logprobs: None,
+                finish_reason,
+            }],
+            usage: None,
+        }
+    }
+}
+
+// Translates a streaming generate response into an OpenAI server-sent event stream.
+//
+// The assessed NDJSON stream produced by the generate handler is re-framed as
+// server-sent events, with every chunk translated into a completion chunk. The
+// verdict summary, when enabled, is sent as its own event before the final chunk.
+fn stream_completion(response: Response, context: CompletionContext) -> Result<Response, ApiError> {
+    let events = SseStream::new(response.into_body().into_data_stream(), move |json| {
+        if let Some(summary) = json.get(SUMMARY_FIELD) {
+            return Some(serde_json::json!({ SUMMARY_FIELD: summary }));
+        }
+        serde_json::to_value(context.completion(json)).ok()
+    });
+
+    Response::builder()
+        .header("Content-Type", SSE_CONTENT_TYPE)
+        .header("Cache-Control", "no-cache")
+        .body(Body::from_stream(events))
+        .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))
+}
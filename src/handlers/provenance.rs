@@ -0,0 +1,38 @@
+// Retrieval of signed response manifests.
+//
+// Manifests are produced by the provenance middleware for generation responses
+// and looked up here by the request ID returned in the `X-Request-Id` header.
+use axum::{
+    extract::{Path, State},
+    response::Response,
+};
+use tracing::debug;
+
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::AppState;
+
+// Returns the signed manifest for a request.
+//
+// # Returns
+//
+// * `Ok(Response)` - The signed manifest as JSON
+// * `Err(ApiError)` - If manifests are disabled or none is retained for the request ID
+pub async fn handle_get_manifest(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+) -> Result<Response, ApiError> {
+    debug!("Looking up response manifest for request {}", request_id);
+
+    if !state.provenance.is_enabled() {
+        return Err(ApiError::NotFound(
+            "Response manifests are disabled; set provenance.enabled to enable them".to_string(),
+        ));
+    }
+
+    let manifest = state.provenance.get(&request_id).ok_or_else(|| {
+        ApiError::NotFound(format!("No manifest found for request {}", request_id))
+    })?;
+
+    build_serialized_response(manifest)
+}
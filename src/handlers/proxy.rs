@@ -0,0 +1,39 @@
+// Self-description endpoint of the proxy.
+//
+// Reports which optional subsystems this deployment has enabled, so client
+// tooling can adapt to it. Like every other API endpoint it requires client
+// authentication when that is enabled. The feature flags reported are the ones
+// that are on for the calling client, so they may differ between clients.
+use axum::{extract::State, response::Response};
+use serde::Serialize;
+
+use crate::client_auth::ClientIdentity;
+use crate::handlers::utils::build_serialized_response;
+use crate::handlers::ApiError;
+use crate::proxy_info::ProxyCapabilities;
+use crate::request_metrics::Tenant;
+use crate::AppState;
+
+// Capabilities of the proxy as seen by the calling client.
+#[derive(Debug, Serialize)]
+struct CapabilitiesResponse<'a> {
+    // Enabled subsystems of the deployment
+    #[serde(flatten)]
+    capabilities: &'a ProxyCapabilities,
+    // Feature flags that are on for the client
+    feature_flags: Vec<String>,
+}
+
+// Handler for the proxy's enabled subsystems (GET /api/proxy/capabilities)
+pub async fn handle_capabilities(
+    State(state): State<AppState>,
+    Tenant(tenant): Tenant,
+    ClientIdentity(identity): ClientIdentity,
+) -> Result<Response, ApiError> {
+    build_serialized_response(CapabilitiesResponse {
+        capabilities: &state.proxy_capabilities,
+        feature_flags: state
+            .feature_flags
+            .enabled_for(tenant.as_deref(), identity.as_deref()),
+    })
+}
@@ -0,0 +1,172 @@
+// Handler for conversation and document summarization requests.
+//
+// Summaries are produced by the configured summarization model through the
+// same pipeline as regular generation: the content is scanned as a prompt,
+// summarized by Ollama, and the summary is scanned as a response before it is
+// returned.
+use axum::{extract::State, response::Response, Json};
+use tracing::{debug, error, info};
+
+use crate::handlers::utils::{
+    build_serialized_response, build_violation_response, log_llm_metrics, RequestSecurity,
+};
+use crate::handlers::ApiError;
+use crate::postprocess::PostProcessState;
+use crate::types::{GenerateRequest, GenerateResponse, SummarizeRequest, SummarizeResponse};
+use crate::AppState;
+
+// Handles summarization requests with security assessment.
+//
+// This handler:
+// 1. Builds the content to summarize from the conversation or document
+// 2. Performs security checks on the content as a prompt
+// 3. Asks the summarization model for a summary via Ollama
+// 4. Scans the summary for security issues before returning it to the client
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `Json(request)` - The summarization request from the client
+//
+// # Returns
+//
+// * `Ok(Response)` - The summarization response
+// * `Err(ApiError)` - If the request is invalid or an error occurs during processing
+pub async fn handle_summarize(
+    State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
+    Json(request): Json<SummarizeRequest>,
+) -> Result<Response, ApiError> {
+    let model = request
+        .model
+        .clone()
+        .filter(|model| !model.is_empty())
+        .unwrap_or_else(|| state.summarize_config.model.clone());
+    if model.is_empty() {
+        return Err(ApiError::BadRequest(
+            "No summarization model configured; set `model` in the request".to_string(),
+        ));
+    }
+
+    let content = summarization_input(&request)?;
+    debug!(
+        "Received summarize request for model: {} ({} characters)",
+        model,
+        content.len()
+    );
+
+    // Check the content to summarize for security violations
+    let assessment = security_client.assess_content(&content, &model, true).await?;
+    if !assessment.is_safe {
+        let branding = security_client.branding();
+        return build_violation_response(
+            summarize_response(&model, branding.violation_message(&assessment)),
+            branding.block_page(&assessment),
+        );
+    }
+    let content = if assessment.is_masked {
+        assessment.final_content
+    } else {
+        content
+    };
+
+    // Ask the summarization model for a summary
+    let generate_request = GenerateRequest {
+        model: model.clone(),
+        prompt: content,
+        system: Some(state.summarize_config.prompt.clone()),
+        template: None,
+        context: None,
+        stream: Some(false),
+        raw: None,
+        format: None,
+        options: request.options,
+        images: None,
+    };
+    let response = state
+        .ollama_client
+        .forward("/api/generate", &generate_request)
+        .await?;
+    let body_bytes = response.bytes().await.map_err(|e| {
+        error!("Failed to read response body: {}", e);
+        ApiError::InternalError("Failed to read response body".to_string())
+    })?;
+
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&body_bytes) {
+        log_llm_metrics(&json, false);
+    }
+
+    let generated: GenerateResponse = serde_json::from_slice(&body_bytes).map_err(|e| {
+        error!("Failed to parse response: {}", e);
+        ApiError::InternalError("Failed to parse response".to_string())
+    })?;
+
+    // Check the summary for security issues
+    let assessment = security_client
+        .assess_content(&generated.response, &model, false)
+        .await?;
+    if !assessment.is_safe {
+        let branding = security_client.branding();
+        return build_violation_response(
+            summarize_response(&model, branding.violation_message(&assessment)),
+            branding.block_page(&assessment),
+        );
+    }
+
+    let mut summary = if assessment.is_masked {
+        assessment.final_content
+    } else {
+        generated.response
+    };
+
+    // Mask secrets found by the local secret scanner, then apply the configured post-processing
+    if let Some(masked) = security_client.mask_secrets(&summary, &mut false) {
+        summary = masked;
+    }
+    if let Some(processed) = state
+        .postprocess
+        .process(&summary, &mut PostProcessState::default())
+    {
+        summary = processed;
+    }
+
+    info!("Summary passed security checks, returning to client");
+    build_serialized_response(summarize_response(&model, summary))
+}
+
+// Builds the text to summarize from a summarization request.
+//
+// Conversations are rendered one message per paragraph, prefixed with the role.
+//
+// # Errors
+//
+// Returns a bad request error if the request carries no content.
+fn summarization_input(request: &SummarizeRequest) -> Result<String, ApiError> {
+    let content = match (&request.messages, &request.text) {
+        (Some(messages), _) if !messages.is_empty() => messages
+            .iter()
+            .map(|message| format!("{}: {}", message.role, message.content))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        (_, Some(text)) => text.clone(),
+        _ => String::new(),
+    };
+
+    if content.trim().is_empty() {
+        return Err(ApiError::BadRequest(
+            "Either `messages` or `text` must be provided".to_string(),
+        ));
+    }
+
+    Ok(content)
+}
+
+// Creates a summarization response for the given model and summary.
+fn summarize_response(model: &str, summary: String) -> SummarizeResponse {
+    SummarizeResponse {
+        model: model.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        summary,
+    }
+}
@@ -0,0 +1,293 @@
+// Handlers for the prompt template library.
+//
+// Anyone can list and run templates; saving and deleting them requires the
+// admin token. Templates are scanned when they are saved, so running one only
+// needs to scan the substituted values before the rendered prompt is generated
+// and the response scanned as usual.
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    response::Response,
+    Extension, Json,
+};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+use crate::handlers::admin::require_admin;
+use crate::handlers::generate::{generate_assessed, GenerationResult};
+use crate::handlers::utils::{
+    build_serialized_response, build_violation_response, RequestSecurity,
+};
+use crate::handlers::ApiError;
+use crate::key_scopes::KeyGrant;
+use crate::types::{
+    GenerateRequest, GenerateResponse, PromptTemplate, RunTemplateRequest, SaveTemplateRequest,
+};
+use crate::AppState;
+
+// Model name reported to the security API for templates without a default model.
+const UNSPECIFIED_MODEL: &str = "template";
+
+// Handler for listing prompt templates (GET /api/templates)
+pub async fn handle_list_templates(State(state): State<AppState>) -> Result<Response, ApiError> {
+    build_serialized_response(state.templates.list())
+}
+
+// Handler for reading a prompt template (GET /api/templates/{name})
+pub async fn handle_get_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let template = state.templates.get(&name).ok_or_else(|| template_not_found(&name))?;
+    build_serialized_response(template)
+}
+
+// Handler for saving a prompt template (PUT /api/templates/{name})
+//
+// The template text and system message are scanned before the template is
+// saved; templates that fail the scan are rejected.
+pub async fn handle_put_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(mut request): Json<SaveTemplateRequest>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if request.template.trim().is_empty() {
+        return Err(ApiError::BadRequest("`template` must not be empty".to_string()));
+    }
+
+    // Personal data is masked before the template is scanned or saved
+    let texts = std::iter::once(&mut request.template).chain(request.system.as_mut());
+    for text in texts {
+        if let Some(masked) = state.security_client.mask_pii(text) {
+            *text = masked;
+        }
+    }
+
+    let model = request.model.as_deref().unwrap_or(UNSPECIFIED_MODEL);
+    let content = match &request.system {
+        Some(system) => format!("{}\n{}", system, request.template),
+        None => request.template.clone(),
+    };
+    let assessment = state
+        .security_client
+        .assess_content(&content, model, true)
+        .await?;
+    if !assessment.is_safe {
+        return Err(ApiError::UnprocessableEntity(
+            state.security_client.branding().violation_message(&assessment),
+        ));
+    }
+
+    let template = PromptTemplate {
+        variables: state.templates.variables(&request.template),
+        name,
+        description: request.description,
+        model: request.model,
+        system: request.system,
+        template: request.template,
+        updated_at: chrono::Utc::now(),
+    };
+    info!("Admin saved prompt template {}", template.name);
+    state.templates.save(template.clone());
+    build_serialized_response(template)
+}
+
+// Handler for removing a prompt template (DELETE /api/templates/{name})
+pub async fn handle_delete_template(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    require_admin(&state, &headers)?;
+    if !state.templates.remove(&name) {
+        return Err(template_not_found(&name));
+    }
+    info!("Admin removed prompt template {}", name);
+    build_serialized_response(state.templates.list())
+}
+
+// Handles template runs by substituting the variables and generating a response.
+//
+// This handler:
+// 1. Substitutes the supplied values into the saved template
+// 2. Performs security checks on the substituted values as a prompt
+// 3. Generates a response for the rendered prompt via Ollama
+// 4. Scans the response for security issues before returning it to the client
+//
+// # Arguments
+//
+// * `State(state)` - Application state containing client connections
+// * `RequestSecurity(security_client)` - Security client configured for the request
+// * `grant` - Scopes and models of the client key, if the request carries one
+// * `Path(name)` - Name of the template to run
+// * `Json(request)` - The variable values and optional model override
+//
+// # Returns
+//
+// * `Ok(Response)` - The generate response, or a violation response if blocked
+// * `Err(ApiError)` - If the template is unknown, a variable is missing, the key may not
+//   use the model or an error occurs
+pub async fn handle_run_template(
+    State(state): State<AppState>,
+    RequestSecurity(security_client): RequestSecurity,
+    grant: Option<Extension<Arc<KeyGrant>>>,
+    Path(name): Path<String>,
+    Json(mut request): Json<RunTemplateRequest>,
+) -> Result<Response, ApiError> {
+    let template = state.templates.get(&name).ok_or_else(|| template_not_found(&name))?;
+
+    // Personal data in the values is masked before they are scanned or rendered
+    for value in request.variables.values_mut() {
+        if let Some(masked) = state.security_client.mask_pii(value) {
+            *value = masked;
+        }
+    }
+
+    let grant = grant.as_ref().map(|Extension(grant)| grant.as_ref());
+    let model = run_model(&template, request.model, grant)?;
+
+    let prompt = state
+        .templates
+        .render(&template, &request.variables)
+        .map_err(|missing| {
+            ApiError::BadRequest(format!("Missing template variables: {}", missing.join(", ")))
+        })?;
+    debug!("Running prompt template {} with model {}", name, model);
+
+    // The template itself was scanned when it was saved; only the values are new
+    let values: Vec<&str> = template
+        .variables
+        .iter()
+        .filter_map(|variable| request.variables.get(variable).map(String::as_str))
+        .collect();
+    let assessment = security_client
+        .assess_content(&values.join("\n"), &model, true)
+        .await?;
+    if !assessment.is_safe {
+        let branding = security_client.branding();
+        return build_violation_response(
+            GenerateResponse {
+                model,
+                created_at: chrono::Utc::now().to_rfc3339(),
+                response: branding.violation_message(&assessment),
+                context: None,
+                done: true,
+            },
+            branding.block_page(&assessment),
+        );
+    }
+
+    let generate_request = GenerateRequest {
+        model,
+        prompt,
+        system: template.system,
+        template: None,
+        context: None,
+        stream: Some(false),
+        raw: None,
+        format: None,
+        options: request.options,
+        images: None,
+    };
+
+    match generate_assessed(&state, &security_client, &generate_request).await? {
+        GenerationResult::Blocked(mut response_body, assessment) => {
+            let branding = security_client.branding();
+            response_body.response = branding.violation_message(&assessment);
+            build_violation_response(response_body, branding.block_page(&assessment))
+        }
+        GenerationResult::Passed { body, response, .. } => {
+            let mut json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| {
+                error!("Failed to parse response: {}", e);
+                ApiError::InternalError("Failed to parse response".to_string())
+            })?;
+            json["response"] = serde_json::Value::String(response);
+            build_serialized_response(json)
+        }
+    }
+}
+
+// Resolves the model a template runs with, the model in the request or else
+// the template's default, and checks it against the models of the client key.
+fn run_model(
+    template: &PromptTemplate,
+    requested: Option<String>,
+    grant: Option<&KeyGrant>,
+) -> Result<String, ApiError> {
+    let model = requested
+        .filter(|model| !model.is_empty())
+        .or_else(|| template.model.clone())
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "Template {} has no default model; set `model` in the request",
+                template.name
+            ))
+        })?;
+    if let Some(grant) = grant {
+        grant.check_model(&model).inspect_err(|_| {
+            warn!(
+                "Rejected run of template {} from client {} for model {}",
+                template.name,
+                grant.id(),
+                model
+            )
+        })?;
+    }
+    Ok(model)
+}
+
+// Builds the error returned for unknown templates.
+fn template_not_found(name: &str) -> ApiError {
+    ApiError::NotFound(format!("No prompt template named {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(model: Option<&str>) -> PromptTemplate {
+        PromptTemplate {
+            name: "review".to_string(),
+            description: None,
+            model: model.map(str::to_string),
+            system: None,
+            template: "Review {{code}}".to_string(),
+            variables: vec!["code".to_string()],
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    fn restricted_key() -> KeyGrant {
+        KeyGrant::new("ci", &[], &["llama3".to_string()], None)
+    }
+
+    #[test]
+    fn restricted_key_is_rejected_for_template_default_model() {
+        let result = run_model(&template(Some("mistral")), None, Some(&restricted_key()));
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+    }
+
+    #[test]
+    fn restricted_key_is_rejected_for_model_override() {
+        let result = run_model(
+            &template(Some("llama3")),
+            Some("mistral".to_string()),
+            Some(&restricted_key()),
+        );
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+    }
+
+    #[test]
+    fn restricted_key_runs_granted_model() {
+        let result = run_model(&template(Some("llama3:latest")), None, Some(&restricted_key()));
+        assert_eq!(result.unwrap(), "llama3:latest");
+    }
+
+    #[test]
+    fn requests_without_key_run_any_model() {
+        let result = run_model(&template(None), Some("mistral".to_string()), None);
+        assert_eq!(result.unwrap(), "mistral");
+    }
+}
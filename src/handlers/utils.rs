@@ -1,12 +1,96 @@
-use crate::{handlers::ApiError, stream::SecurityAssessedStream, AppState};
+use crate::{
+    branding::{BlockPage, Branding},
+    client_auth::{ClientIdentity, SecurityProfile},
+    dedup::CachedVerdicts,
+    handlers::ApiError,
+    provenance::ScanTrail,
+    request_metrics::Tenant,
+    scan_bypass::ScanBypass,
+    security::SecurityClient,
+    stream::SecurityAssessedStream,
+    transaction::TransactionId,
+    transcripts::Session,
+    types::ScanVerdict,
+    verdict_debug::VerdictTrail,
+    AppState,
+};
 
-use axum::{body::Body, response::Response};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderValue},
+    response::Response,
+};
 use bytes::Bytes;
 use futures_util::stream::StreamExt;
 use http_body_util::StreamBody;
 use serde::Serialize;
+use std::net::SocketAddr;
 use tracing::{error, info};
 
+// Header marking responses whose content was replaced by a block message.
+pub const BLOCKED_HEADER: &str = "x-content-blocked";
+
+// Security client configured for the request being handled.
+//
+// Every scan of the request is attributed and routed like the request itself:
+// the client carries its tenant, client identity and address, scan bypass,
+// security profile, transaction ID and transcript session, its scan and
+// verdict trails when they are recorded, and the body hash linking its cached
+// verdicts. Extraction fails only if the request selects a security profile it
+// may not use.
+pub struct RequestSecurity(pub SecurityClient);
+
+impl FromRequestParts<AppState> for RequestSecurity {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Ok(Tenant(tenant)) = Tenant::from_request_parts(parts, state).await;
+        let Ok(ClientIdentity(identity)) = ClientIdentity::from_request_parts(parts, state).await;
+        let Ok(ScanBypass(bypass)) = ScanBypass::from_request_parts(parts, state).await;
+        let SecurityProfile(profile) = SecurityProfile::from_request_parts(parts, state).await?;
+        let Ok(TransactionId(transaction)) =
+            TransactionId::from_request_parts(parts, state).await;
+        let Ok(Session(session)) = Session::from_request_parts(parts, state).await;
+
+        let mut security_client = state.security_client.clone();
+        if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+            security_client.with_user_ip(addr.ip().to_string());
+        }
+        if let Some(tenant) = tenant {
+            security_client.with_tenant(tenant);
+        }
+        if let Some(user) = identity {
+            security_client.with_app_user(user);
+        }
+        if let Some(client) = bypass {
+            security_client.with_scan_bypass(client);
+        }
+        if let Some(profile) = profile {
+            security_client.with_profile_name(profile);
+        }
+        if let Some(id) = transaction {
+            security_client.with_transaction_id(id);
+        }
+        if let Some(session) = session {
+            security_client.with_session(session);
+        }
+        if let Some(trail) = parts.extensions.get::<ScanTrail>() {
+            security_client.with_scan_trail(trail.clone());
+        }
+        if let Some(trail) = parts.extensions.get::<VerdictTrail>() {
+            security_client.with_verdict_trail(trail.clone());
+        }
+        if let Some(cached) = parts.extensions.get::<CachedVerdicts>() {
+            security_client.with_request_hash(cached.body_hash.clone(), cached.bypass);
+        }
+        Ok(RequestSecurity(security_client))
+    }
+}
+
 // Builds an HTTP response with JSON content type from the provided bytes.
 pub fn build_json_response(bytes: Bytes) -> Result<Response<Body>, ApiError> {
     Response::builder()
@@ -23,6 +107,7 @@ fn convert_stream_error(err: reqwest::Error) -> reqwest::Error {
 // Handles streaming requests to API endpoints, applying security assessment to the streamed responses.
 pub async fn handle_streaming_request<T>(
     state: &AppState,
+    security_client: SecurityClient,
     request: T,
     endpoint: &str,
     model: &str,
@@ -40,9 +125,11 @@ where
     // Create the security-assessed stream
     let assessed_stream = SecurityAssessedStream::new(
         converted_stream,
-        state.security_client.clone(),
+        security_client,
+        state.postprocess.clone(),
         model.to_string(),
         is_prompt,
+        &state.models_config.streaming,
     );
 
     // Clone the model string for use in the closure
@@ -60,8 +147,10 @@ where
                 "error": ERROR_MESSAGE,
                 "done": true
             });
-            let error_bytes = serde_json::to_vec(&error_json)
+            // Errors end the assessed stream, so this doubles as its terminal chunk
+            let mut error_bytes = serde_json::to_vec(&error_json)
                 .unwrap_or_else(|_| ERROR_MESSAGE.as_bytes().to_vec());
+            error_bytes.push(b'\n');
             Ok(Bytes::from(error_bytes))
         }
     });
@@ -76,121 +165,49 @@ where
         .map_err(|e| ApiError::InternalError(format!("Failed to create response: {}", e)))
 }
 
-// Formats a comprehensive security violation message with detailed detection reasons.
-pub fn format_security_violation_message(assessment: &crate::security::Assessment) -> String {
-    let mut reasons = Vec::new();
-
-    // Check prompt detection reasons
-    if assessment.details.prompt_detected.url_cats {
-        reasons.push("Prompt contains malicious URLs");
-    }
-    if assessment.details.prompt_detected.dlp {
-        reasons.push("Prompt contains sensitive information");
-    }
-    if assessment.details.prompt_detected.injection {
-        reasons.push("Prompt contains injection threats");
-    }
-    if assessment.details.prompt_detected.toxic_content {
-        reasons.push("Prompt contains harmful content");
-    }
-    if assessment.details.prompt_detected.malicious_code {
-        reasons.push("Prompt contains malicious code");
-    }
-    if assessment.details.prompt_detected.agent {
-        reasons.push("Prompt contains any Agent related threats");
-    }
-    if assessment.details.prompt_detected.topic_violation {
-        reasons.push("Prompt contains any content violates topic guardrails");
-    }
-
-    // Check response detection reasons
-    if assessment.details.response_detected.url_cats {
-        reasons.push("Response contains malicious URLs");
-    }
-    if assessment.details.response_detected.dlp {
-        reasons.push("Response contains sensitive information");
-    }
-    if assessment.details.response_detected.db_security {
-        reasons.push("Response contains database security threats");
-    }
-    if assessment.details.response_detected.toxic_content {
-        reasons.push("Response contains harmful content");
-    }
-    if assessment.details.response_detected.malicious_code {
-        reasons.push("Response contains malicious code");
-    }
-    if assessment.details.response_detected.agent {
-        reasons.push("Response contains any Agent related threats");
-    }
-    if assessment.details.response_detected.ungrounded {
-        reasons.push("Response contains any ungrounded content");
-    }
-    if assessment.details.response_detected.topic_violation {
-        reasons.push("Response contains any content violates topic guardrails");
-    }
-
-    let reasons_text = if reasons.is_empty() {
-        "Unspecified security concern".to_string()
-    } else {
-        reasons.join("\n - ")
-    };
-
-    // Format topic guardrails information if available
-    let mut topic_info = String::new();
-
-    // Check prompt topic guardrails
-    if let Some(ref details) = assessment
-        .details
-        .prompt_detection_details
-        .topic_guardrails_details
-    {
-        if !details.allowed_topics.is_empty() {
-            topic_info.push_str("\n• Allowed Topics:\n");
-            for topic in &details.allowed_topics {
-                topic_info.push_str(&format!("  - {}\n", topic));
-            }
-        }
-        if !details.blocked_topics.is_empty() {
-            topic_info.push_str("\n• Blocked Topics:\n");
-            for topic in &details.blocked_topics {
-                topic_info.push_str(&format!("  - {}\n", topic));
-            }
-        }
+// Creates the verdict describing why content was blocked.
+//
+// # Arguments
+//
+// * `stage` - Whether the prompt or the response was blocked ("prompt" or "response")
+// * `assessment` - The assessment that blocked the content
+// * `branding` - Branding of the block message
+pub fn scan_verdict(
+    stage: &str,
+    assessment: &crate::security::Assessment,
+    branding: &Branding,
+) -> ScanVerdict {
+    ScanVerdict {
+        stage: stage.to_string(),
+        category: assessment.category.clone(),
+        action: assessment.action.clone(),
+        message: branding.violation_message(assessment),
     }
+}
 
-    // Check response topic guardrails
-    if let Some(ref details) = assessment
-        .details
-        .response_detection_details
-        .topic_guardrails_details
-    {
-        if !details.allowed_topics.is_empty() {
-            topic_info.push_str("\n• Allowed Topics (Response):\n");
-            for topic in &details.allowed_topics {
-                topic_info.push_str(&format!("  - {}\n", topic));
-            }
-        }
-        if !details.blocked_topics.is_empty() {
-            topic_info.push_str("\n• Blocked Topics (Response):\n");
-            for topic in &details.blocked_topics {
-                topic_info.push_str(&format!("  - {}\n", topic));
-            }
-        }
+// Builds a response with serialized data for a security violation.
+//
+// The response is marked with the blocked header, and the block page, if any,
+// is attached for clients that prefer HTML.
+pub fn build_violation_response<T>(
+    data: T,
+    block_page: Option<BlockPage>,
+) -> Result<Response<Body>, ApiError>
+where
+    T: Serialize,
+{
+    let mut response = build_serialized_response(data)?;
+    response
+        .headers_mut()
+        .insert(BLOCKED_HEADER, HeaderValue::from_static("true"));
+    if let Some(block_page) = block_page {
+        response.extensions_mut().insert(block_page);
     }
-
-    format!(
-        "\n\n⚠️ This content was blocked due to security policy violations:\n\n\
-         • Category: {}\n\
-         • Action: {}\n\
-         • Reasons: \n\
-          - {}{}\n\
-         \n\nPlease reformulate your request to comply with security policies.\n\n",
-        assessment.category, assessment.action, reasons_text, topic_info
-    )
+    Ok(response)
 }
 
-// Builds a response with serialized data for a security violation.
-pub fn build_violation_response<T>(data: T) -> Result<Response<Body>, ApiError>
+// Builds a JSON response by serializing the provided data.
+pub fn build_serialized_response<T>(data: T) -> Result<Response<Body>, ApiError>
 where
     T: Serialize,
 {
@@ -0,0 +1,389 @@
+// Health checks of the upstream services the proxy depends on.
+//
+// `/healthz` lets a load balancer tell a healthy proxy from a degraded one. A
+// check actively probes both upstreams in parallel:
+//
+// - Ollama is probed with `GET /api/version` and is up when it answers with a
+//   success status.
+// - The PANW AI Runtime API is probed with a plain `GET` of its base URL and is
+//   up when it answers with any status below 500. A scan request would consume
+//   scan budget, so only connectivity is checked.
+//
+// Each probe is bounded by the configured timeout, and the result is cached so
+// frequent health checks do not put load on the upstreams.
+//
+// # Security Channel
+//
+// Independent of traffic, a background task probes the PANW AI Runtime API on
+// the configured interval so operators can see the health of the enforcement
+// path. The outcome of the latest probe, including those made by `/healthz`, is
+// exposed as the `panw_security_channel_up` gauge together with
+// `panw_security_channel_last_success_timestamp_seconds`, and is reported by
+// `/readyz`.
+//
+// # Liveness and Readiness
+//
+// `/livez` only reports that the process is alive and serving requests, so an
+// orchestrator restarts the proxy only when it is really stuck. `/readyz`
+// reports whether the proxy should receive traffic: the configuration is loaded
+// and the clients are constructed before the server starts listening, so it
+// checks that both upstreams are reachable and that the proxy is not shutting
+// down. Once shutdown begins, readiness flips to not ready while in-flight
+// requests drain. `/readyz` also reports how the Ollama version relates to the
+// versions the proxy was tested with, without affecting readiness.
+use crate::config::HealthConfig;
+use crate::metrics;
+use crate::ollama_compat::{Compatibility, OllamaCompat};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// Overall health of the proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    // All dependencies are reachable
+    Ok,
+    // At least one dependency is unreachable
+    Degraded,
+}
+
+// Reachability of a single dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyStatus {
+    Up,
+    Down,
+    // Not probed yet
+    Unknown,
+}
+
+// Probe result of a single dependency.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyHealth {
+    // Whether the dependency is reachable
+    pub status: DependencyStatus,
+
+    // URL that was probed
+    pub url: String,
+
+    // How long the probe took
+    pub latency_ms: u64,
+
+    // Why the dependency is considered down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Probe results of all dependencies.
+#[derive(Debug, Clone, Serialize)]
+pub struct Dependencies {
+    pub ollama: DependencyHealth,
+    pub panw: DependencyHealth,
+}
+
+// Result of a health check.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    // Overall health of the proxy
+    pub status: HealthStatus,
+
+    // When the upstreams were probed
+    pub checked_at: DateTime<Utc>,
+
+    // Probe results of the individual dependencies
+    pub dependencies: Dependencies,
+}
+
+// State of the PANW security channel according to the latest probe.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityChannel {
+    // Whether the latest probe reached the API
+    pub status: DependencyStatus,
+
+    // When the API was last probed
+    pub last_checked: Option<DateTime<Utc>>,
+
+    // When the API was last reached
+    pub last_success: Option<DateTime<Utc>>,
+
+    // Why the latest probe failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Whether the proxy is ready to serve traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessStatus {
+    Ready,
+    NotReady,
+}
+
+// Result of a readiness check.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    // Whether the proxy is ready to serve traffic
+    pub status: ReadinessStatus,
+
+    // Whether graceful shutdown has begun
+    pub shutting_down: bool,
+
+    // Probe results of the upstreams
+    pub dependencies: Dependencies,
+
+    // State of the PANW security channel
+    pub security_channel: SecurityChannel,
+
+    // Compatibility with the Ollama version
+    pub ollama_compatibility: Compatibility,
+}
+
+// Probes the upstream services and caches the result.
+//
+// Cloning the checker is cheap and all clones share the same cached result.
+#[derive(Clone)]
+pub struct HealthChecker {
+    // HTTP client with the probe timeout applied
+    client: Client,
+
+    // URL probed to check Ollama, or `None` when the mock backend answers in its place
+    ollama_url: Option<String>,
+
+    // URL probed to check the PANW AI Runtime API
+    panw_url: String,
+
+    // How long a result is reused
+    cache_ttl: Duration,
+
+    // Most recent result and when it was produced
+    cached: Arc<Mutex<Option<(Instant, HealthReport)>>>,
+
+    // Interval between background probes of the security channel
+    probe_interval: Duration,
+
+    // State of the security channel according to the latest probe
+    channel: Arc<Mutex<SecurityChannel>>,
+
+    // Whether graceful shutdown has begun
+    shutting_down: Arc<AtomicBool>,
+
+    // Compatibility with the Ollama version, checked by the Ollama client
+    ollama_compat: OllamaCompat,
+}
+
+impl HealthChecker {
+    // Creates a checker probing the given Ollama and PANW base URLs.
+    //
+    // Without an Ollama base URL the mock backend is in use and always reported up.
+    //
+    // # Errors
+    //
+    // Returns an error if the HTTP client cannot be created, rather than
+    // probing without a timeout.
+    pub fn new(
+        config: &HealthConfig,
+        ollama_base_url: Option<&str>,
+        panw_base_url: &str,
+    ) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            client,
+            ollama_url: ollama_base_url
+                .map(|base_url| format!("{}/api/version", base_url.trim_end_matches('/'))),
+            panw_url: panw_base_url.to_string(),
+            cache_ttl: Duration::from_secs(config.cache_secs),
+            cached: Arc::new(Mutex::new(None)),
+            probe_interval: Duration::from_secs(config.probe_interval_secs),
+            channel: Arc::new(Mutex::new(SecurityChannel {
+                status: DependencyStatus::Unknown,
+                last_checked: None,
+                last_success: None,
+                error: None,
+            })),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            ollama_compat: OllamaCompat::default(),
+        })
+    }
+
+    // Reports the compatibility with the Ollama version recorded by the Ollama client.
+    pub fn with_ollama_compat(mut self, compat: OllamaCompat) -> Self {
+        self.ollama_compat = compat;
+        self
+    }
+
+    // Returns the state of the security channel according to the latest probe.
+    pub fn security_channel(&self) -> SecurityChannel {
+        self.channel
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // Marks the proxy as shutting down so readiness checks fail from now on.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    // Returns whether the proxy is ready to serve traffic.
+    //
+    // The proxy is ready while both upstreams are reachable and shutdown has not begun.
+    pub async fn readiness(&self) -> ReadinessReport {
+        let health = self.check().await;
+        let shutting_down = self.shutting_down.load(Ordering::SeqCst);
+        let status = if health.status == HealthStatus::Ok && !shutting_down {
+            ReadinessStatus::Ready
+        } else {
+            ReadinessStatus::NotReady
+        };
+
+        ReadinessReport {
+            status,
+            shutting_down,
+            dependencies: health.dependencies,
+            security_channel: self.security_channel(),
+            ollama_compatibility: self.ollama_compat.compatibility(),
+        }
+    }
+
+    // Returns the current health, probing the upstreams unless a cached result is fresh.
+    pub async fn check(&self) -> HealthReport {
+        {
+            let cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((at, report)) = cached.as_ref() {
+                if at.elapsed() < self.cache_ttl {
+                    return report.clone();
+                }
+            }
+        }
+
+        let (ollama, panw) = tokio::join!(
+            self.probe_ollama(),
+            self.probe_security_channel(),
+        );
+
+        let status = if ollama.status == DependencyStatus::Up && panw.status == DependencyStatus::Up
+        {
+            HealthStatus::Ok
+        } else {
+            HealthStatus::Degraded
+        };
+        if status == HealthStatus::Degraded {
+            warn!(
+                "Health check degraded: ollama {:?}, panw {:?}",
+                ollama.status, panw.status
+            );
+        }
+
+        let report = HealthReport {
+            status,
+            checked_at: Utc::now(),
+            dependencies: Dependencies { ollama, panw },
+        };
+
+        let mut cached = self.cached.lock().unwrap_or_else(|e| e.into_inner());
+        *cached = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    // Probes Ollama, unless the mock backend answers in its place.
+    async fn probe_ollama(&self) -> DependencyHealth {
+        match &self.ollama_url {
+            Some(url) => self.probe(url, |status| status.is_success()).await,
+            None => DependencyHealth {
+                status: DependencyStatus::Up,
+                url: "mock".to_string(),
+                latency_ms: 0,
+                error: None,
+            },
+        }
+    }
+
+    // Probes the PANW AI Runtime API and records the outcome as the security channel state.
+    async fn probe_security_channel(&self) -> DependencyHealth {
+        let health = self
+            .probe(&self.panw_url, |status| !status.is_server_error())
+            .await;
+
+        let now = Utc::now();
+        let mut channel = self.channel.lock().unwrap_or_else(|e| e.into_inner());
+        if channel.status != health.status {
+            info!("PANW security channel is {:?}", health.status);
+        }
+        channel.status = health.status;
+        channel.last_checked = Some(now);
+        channel.error = health.error.clone();
+        if health.status == DependencyStatus::Up {
+            channel.last_success = Some(now);
+        }
+
+        metrics::set_gauge(
+            "panw_security_channel_up",
+            "Whether the latest probe reached the PANW AI Runtime API",
+            &[],
+            if health.status == DependencyStatus::Up { 1.0 } else { 0.0 },
+        );
+        if let Some(last_success) = channel.last_success {
+            metrics::set_gauge(
+                "panw_security_channel_last_success_timestamp_seconds",
+                "Unix time of the latest probe that reached the PANW AI Runtime API",
+                &[],
+                last_success.timestamp() as f64,
+            );
+        }
+
+        health
+    }
+
+    // Sends a GET to the URL and judges the response status with `is_up`.
+    async fn probe(
+        &self,
+        url: &str,
+        is_up: impl Fn(reqwest::StatusCode) -> bool,
+    ) -> DependencyHealth {
+        let start = Instant::now();
+        let result = self.client.get(url).send().await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let error = match result {
+            Ok(response) if is_up(response.status()) => None,
+            Ok(response) => Some(format!("Unexpected status {}", response.status())),
+            Err(e) if e.is_timeout() => Some("Timed out".to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+
+        DependencyHealth {
+            status: if error.is_none() {
+                DependencyStatus::Up
+            } else {
+                DependencyStatus::Down
+            },
+            url: url.to_string(),
+            latency_ms,
+            error,
+        }
+    }
+}
+
+// Starts the background task probing the security channel on the configured interval.
+pub fn spawn_security_probe(checker: HealthChecker) {
+    if checker.probe_interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(checker.probe_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            checker.probe_security_channel().await;
+        }
+    });
+}
@@ -0,0 +1,67 @@
+// Tuning of the HTTP clients for the upstream services.
+//
+// The clients for Ollama, PANW, the JWKS endpoint of the identity provider,
+// Vault, the result webhooks of scheduled prompts, the security event webhook
+// and the notification channels are built from their own settings for connect
+// and request timeouts, connection pooling and TCP keepalive. Without a
+// connect timeout, requests to an unreachable server hang until the operating
+// system gives up, which can take minutes.
+//
+// # Request Timeouts
+//
+// Requests to Ollama have no timeout by default, since generations may stream
+// for as long as the model produces tokens; the proxy's own request and stream
+// timeouts bound them instead. Requests to PANW time out after 30 seconds, and
+// requests for signing keys, secrets and webhook deliveries after 10 seconds,
+// unless configured otherwise, so a slow identity provider cannot hold up
+// authentication indefinitely, an unresponsive Vault cannot hold up startup
+// and unresponsive webhook receivers cannot pile up pending deliveries.
+use crate::config::HttpClientConfig;
+use reqwest::{Client, ClientBuilder};
+use std::time::Duration;
+
+// Request timeout of PANW requests when none is configured.
+pub const DEFAULT_SECURITY_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Request timeout of JWKS requests when none is configured.
+pub const DEFAULT_JWKS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Request timeout of Vault requests when none is configured.
+pub const DEFAULT_VAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Request timeout of webhook deliveries when none is configured.
+pub const DEFAULT_WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Returns the duration of a setting given in seconds, or `None` for 0.
+fn secs(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
+
+// Creates a client builder with the given settings.
+//
+// # Arguments
+//
+// * `config` - Timeouts and connection pooling of the client
+// * `default_request_timeout` - Request timeout used when none is configured
+pub fn builder(
+    config: &HttpClientConfig,
+    default_request_timeout: Option<Duration>,
+) -> ClientBuilder {
+    let mut builder = Client::builder()
+        .pool_idle_timeout(secs(config.pool_idle_timeout_secs))
+        .tcp_keepalive(secs(config.tcp_keepalive_secs));
+    if let Some(timeout) = secs(config.connect_timeout_secs) {
+        builder = builder.connect_timeout(timeout);
+    }
+    let request_timeout = match config.request_timeout_secs {
+        Some(timeout) => secs(timeout),
+        None => default_request_timeout,
+    };
+    if let Some(timeout) = request_timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(max_idle) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    builder
+}
@@ -0,0 +1,171 @@
+// Asynchronous generation jobs.
+//
+// Long generations over unreliable links are submitted as jobs: the request is
+// processed server-side through the regular scan→generate→scan pipeline while
+// the client polls for the result, so no connection has to stay open for the
+// duration of the generation.
+//
+// # Lifecycle
+//
+// Jobs are queued, run and finish as completed, blocked or failed. A finished
+// job is removed once its owner has fetched it, or after the configured TTL if
+// it is never fetched.
+//
+// # Limits
+//
+// Jobs belong to the authenticated client that submitted them, or to its
+// address if the request carried no client identity, and can only be fetched
+// by that owner. Each client may hold a limited number of jobs that have not
+// been fetched yet.
+use crate::config::JobsConfig;
+use crate::handlers::ApiError;
+use crate::types::{JobResponse, JobStatus, ScanVerdict};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+// Final outcome of a job.
+pub enum JobOutcome {
+    // The generate response, after secret masking and post-processing
+    Completed(serde_json::Value),
+    // The verdict of the blocked prompt or response
+    Blocked(ScanVerdict),
+    // The error that stopped the job
+    Failed(String),
+}
+
+// A job and its bookkeeping.
+struct Job {
+    // Identity, or address if it has none, of the client that submitted the job
+    owner: String,
+
+    // State and result returned to the client
+    state: JobResponse,
+
+    // When the finished job is discarded; `None` while it is still processing
+    expires_at: Option<Instant>,
+}
+
+// Tracks generation jobs and their results.
+//
+// Cloning the store is cheap and all clones share the same jobs.
+#[derive(Clone)]
+pub struct JobStore {
+    // How long finished jobs are kept
+    ttl: Duration,
+
+    // Maximum number of unfetched jobs per client
+    max_per_client: usize,
+
+    // Jobs keyed by ID
+    jobs: Arc<Mutex<HashMap<String, Job>>>,
+}
+
+impl JobStore {
+    // Creates an empty store for the given configuration.
+    pub fn new(config: &JobsConfig) -> Self {
+        Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_per_client: config.max_per_client,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Registers a new queued job for a client.
+    //
+    // Expired jobs are pruned on every call.
+    //
+    // # Errors
+    //
+    // Returns a too-many-requests error if the client already holds the
+    // maximum number of unfetched jobs.
+    pub fn create(&self, owner: &str) -> Result<JobResponse, ApiError> {
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.retain(|_, job| job.expires_at.is_none_or(|expires_at| expires_at > now));
+
+        let held = jobs.values().filter(|job| job.owner == owner).count();
+        if held >= self.max_per_client {
+            return Err(ApiError::TooManyRequests(format!(
+                "Client already holds {} unfetched jobs; fetch finished jobs before submitting more",
+                held
+            )));
+        }
+
+        let state = JobResponse {
+            id: Uuid::new_v4().to_string(),
+            status: JobStatus::Queued,
+            created_at: Utc::now(),
+            completed_at: None,
+            result: None,
+            verdict: None,
+            error: None,
+        };
+        jobs.insert(
+            state.id.clone(),
+            Job {
+                owner: owner.to_string(),
+                state: state.clone(),
+                expires_at: None,
+            },
+        );
+
+        debug!("Queued job {} for {}", state.id, owner);
+        Ok(state)
+    }
+
+    // Marks a job as running.
+    pub fn start(&self, id: &str) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = jobs.get_mut(id) {
+            job.state.status = JobStatus::Running;
+        }
+    }
+
+    // Stores the outcome of a job and starts its TTL.
+    pub fn finish(&self, id: &str, outcome: JobOutcome) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+
+        match outcome {
+            JobOutcome::Completed(result) => {
+                job.state.status = JobStatus::Completed;
+                job.state.result = Some(result);
+            }
+            JobOutcome::Blocked(verdict) => {
+                job.state.status = JobStatus::Blocked;
+                job.state.verdict = Some(verdict);
+            }
+            JobOutcome::Failed(error) => {
+                job.state.status = JobStatus::Failed;
+                job.state.error = Some(error);
+            }
+        }
+        job.state.completed_at = Some(Utc::now());
+        job.expires_at = Some(Instant::now() + self.ttl);
+
+        debug!("Job {} finished with status {:?}", id, job.state.status);
+    }
+
+    // Returns a client's job, removing it once it has finished.
+    //
+    // Jobs of other clients and expired jobs are reported as missing.
+    pub fn fetch(&self, id: &str, owner: &str) -> Option<JobResponse> {
+        let now = Instant::now();
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs.get(id).filter(|job| {
+            job.owner == owner && job.expires_at.is_none_or(|expires_at| expires_at > now)
+        })?;
+
+        if job.state.status.is_finished() {
+            jobs.remove(id).map(|job| job.state)
+        } else {
+            Some(job.state.clone())
+        }
+    }
+}
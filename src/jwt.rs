@@ -0,0 +1,230 @@
+// JWT bearer token validation for SSO-integrated deployments.
+//
+// Clients authenticated by an identity provider present their access token as
+// a bearer token. The token's signature is checked against the signing keys
+// published at the provider's JWKS endpoint, and its issuer, audience and
+// expiry are validated.
+//
+// # Claims
+//
+// The subject claim becomes the request's client identity. The groups claim,
+// either a list of strings or a single string, selects the PANW security
+// profile: the first configured group mapping the client is a member of wins,
+// and clients in none of the mapped groups use the default profile.
+//
+// # Signing Keys
+//
+// Keys are fetched on first use and cached for the configured refresh
+// interval. A token signed with an unknown key ID triggers an early refresh,
+// so rotated keys are picked up immediately, but at most once per
+// `MIN_REFRESH_INTERVAL` so forged key IDs cannot be used to flood the
+// identity provider.
+use crate::config::{GroupProfile, JwtConfig};
+use crate::http_client;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+// Shortest interval between two fetches of the signing keys.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Errors that can occur while validating a token.
+#[derive(Debug, Error)]
+pub enum JwtError {
+    // Token is malformed, expired, or fails signature or claim validation
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+
+    // Signing keys could not be fetched from the identity provider
+    #[error("Failed to fetch signing keys: {0}")]
+    KeysUnavailable(String),
+}
+
+// Identity established by a validated token.
+#[derive(Debug, Clone)]
+pub struct JwtIdentity {
+    // Value of the subject claim
+    pub subject: String,
+
+    // Security profile selected by the client's groups (optional)
+    pub profile: Option<String>,
+}
+
+// Signing keys fetched from the JWKS endpoint.
+#[derive(Default)]
+struct CachedKeys {
+    // Key set, if it has been fetched
+    keys: Option<JwkSet>,
+
+    // When the key set was last fetched
+    fetched_at: Option<Instant>,
+}
+
+// Validates JWTs against the keys of the configured issuer.
+//
+// Cloning the validator is cheap and all clones share the same cached keys.
+#[derive(Clone)]
+pub struct JwtValidator {
+    // Issuer, audience, JWKS endpoint and claim mappings
+    config: Arc<JwtConfig>,
+
+    // HTTP client used to fetch the signing keys
+    client: Client,
+
+    // Signing keys fetched so far
+    cache: Arc<Mutex<CachedKeys>>,
+}
+
+impl JwtValidator {
+    // Creates a validator for the configured issuer.
+    //
+    // # Errors
+    //
+    // Returns an error if the HTTP client cannot be created
+    pub fn new(config: &JwtConfig) -> Result<Self, reqwest::Error> {
+        let client = http_client::builder(
+            &config.http_client,
+            Some(http_client::DEFAULT_JWKS_REQUEST_TIMEOUT),
+        )
+        .build()?;
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            client,
+            cache: Arc::new(Mutex::new(CachedKeys::default())),
+        })
+    }
+
+    // Returns whether JWTs are accepted as client credentials.
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    // Returns the profile selected for members of the given groups, if any.
+    pub fn profile_for_groups(&self, groups: &[&str]) -> Option<String> {
+        profile_for(&self.config.group_profiles, groups)
+    }
+
+    // Validates a token and extracts the client's identity.
+    //
+    // # Errors
+    //
+    // Returns an error if the token is invalid or the signing keys cannot be fetched.
+    pub async fn validate(&self, token: &str) -> Result<JwtIdentity, JwtError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| JwtError::InvalidToken(e.to_string()))?;
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(JwtError::InvalidToken(
+                "symmetric signatures are not accepted".to_string(),
+            ));
+        }
+        let kid = header
+            .kid
+            .ok_or_else(|| JwtError::InvalidToken("missing key ID".to_string()))?;
+
+        let key = self.decoding_key(&kid).await?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        if self.config.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&[&self.config.audience]);
+        }
+
+        let claims = jsonwebtoken::decode::<Value>(token, &key, &validation)
+            .map_err(|e| JwtError::InvalidToken(e.to_string()))?
+            .claims;
+
+        let subject = claims
+            .get(&self.config.subject_claim)
+            .and_then(Value::as_str)
+            .filter(|subject| !subject.is_empty())
+            .ok_or_else(|| {
+                JwtError::InvalidToken(format!("missing {} claim", self.config.subject_claim))
+            })?
+            .to_string();
+
+        let groups: Vec<&str> = match claims.get(&self.config.groups_claim) {
+            Some(Value::Array(groups)) => groups.iter().filter_map(Value::as_str).collect(),
+            Some(Value::String(group)) => vec![group.as_str()],
+            _ => Vec::new(),
+        };
+
+        Ok(JwtIdentity {
+            subject,
+            profile: profile_for(&self.config.group_profiles, &groups),
+        })
+    }
+
+    // Returns the decoding key with the given ID, refreshing the keys when needed.
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, JwtError> {
+        let (cached, refresh) = {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            let age = cache.fetched_at.map(|at| at.elapsed());
+            let key = cache.keys.as_ref().and_then(|keys| keys.find(kid)).cloned();
+            let expired =
+                age.is_none_or(|age| age >= Duration::from_secs(self.config.jwks_refresh_secs));
+            let unknown = key.is_none() && age.is_none_or(|age| age >= MIN_REFRESH_INTERVAL);
+            (key, expired || unknown)
+        };
+
+        let jwk = match (refresh, cached) {
+            (false, cached) => cached,
+            (true, cached) => match self.fetch_keys().await {
+                Ok(keys) => {
+                    let jwk = keys.find(kid).cloned();
+                    let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+                    cache.keys = Some(keys);
+                    cache.fetched_at = Some(Instant::now());
+                    jwk
+                }
+                // Keep validating with the cached key while the provider is unreachable
+                Err(e) if cached.is_some() => {
+                    warn!("{}, using cached signing key", e);
+                    cached
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        let jwk = jwk.ok_or_else(|| JwtError::InvalidToken(format!("unknown key ID {}", kid)))?;
+        DecodingKey::from_jwk(&jwk).map_err(|e| JwtError::InvalidToken(e.to_string()))
+    }
+
+    // Fetches the key set from the JWKS endpoint.
+    async fn fetch_keys(&self) -> Result<JwkSet, JwtError> {
+        debug!("Fetching JWT signing keys from {}", self.config.jwks_url);
+        let keys: JwkSet = self
+            .client
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| JwtError::KeysUnavailable(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| JwtError::KeysUnavailable(e.to_string()))?;
+        info!(
+            "Loaded {} JWT signing keys from {}",
+            keys.keys.len(),
+            self.config.jwks_url
+        );
+        Ok(keys)
+    }
+}
+
+// Returns the profile of the first mapped group the client is a member of.
+fn profile_for(mappings: &[GroupProfile], groups: &[&str]) -> Option<String> {
+    mappings
+        .iter()
+        .find(|mapping| groups.contains(&mapping.group.as_str()))
+        .map(|mapping| mapping.profile.clone())
+}
@@ -0,0 +1,230 @@
+// Scopes limiting the endpoints and models of client keys.
+//
+// A client key may be granted a subset of the scopes below, so that a CI job
+// can hold an embeddings-only key and students chat-only keys:
+//
+// - `generate`: `/api/generate`, `/api/generate/batch`, `/api/summarize`,
+//   `/v1/completions`, asynchronous jobs and prompt templates
+// - `chat`: `/api/chat`
+// - `embeddings`: `/api/embeddings` and `/v1/embeddings`
+// - `models:read`: `/api/tags`, `/api/show` and `/api/capabilities`
+// - `models:write`: `/api/create`, `/api/copy`, `/api/delete`, `/api/pull`
+//   and `/api/push`
+// - `admin`: the admin API, with the key in place of the admin token
+//
+// Keys without scopes hold every scope except `admin`. Endpoints describing
+// the proxy or the client itself, such as `/api/version` and `/api/me/usage`,
+// need no scope.
+//
+// # Models
+//
+// Keys may also be limited to models matching a list of patterns. Requests
+// naming another model in their body are rejected before they are processed.
+// Template runs are checked against the model they resolve to, the model in
+// the request body or else the template's default model.
+//
+// # Other Identities
+//
+// Scopes only apply to requests authenticated by a client key, or sent by an
+// admin as the key's client. Requests authenticated by JWT, client
+// certificate or a trusted reverse proxy are not limited.
+use crate::handlers::ApiError;
+use crate::ollama::with_tag;
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::warn;
+
+// Largest request body inspected for the models it names.
+pub const MAX_REQUEST_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+// Scope that lets a key use the admin API.
+pub const ADMIN_SCOPE: &str = "admin";
+
+// Routes whose request bodies name models.
+pub const MODEL_PATHS: [&str; 14] = [
+    "/api/generate",
+    "/api/generate/batch",
+    "/api/chat",
+    "/api/summarize",
+    "/v1/completions",
+    "/api/embeddings",
+    "/v1/embeddings",
+    "/api/jobs",
+    "/api/show",
+    "/api/create",
+    "/api/copy",
+    "/api/delete",
+    "/api/pull",
+    "/api/push",
+];
+
+// Fields of request bodies naming models.
+pub const MODEL_FIELDS: [&str; 4] = ["model", "name", "source", "destination"];
+
+// Endpoints and models a client key may be used for.
+#[derive(Debug)]
+pub struct KeyGrant {
+    // Identifier of the key's client
+    id: String,
+
+    // Granted scopes; every scope except `admin` when empty
+    scopes: Vec<String>,
+
+    // Allowed model patterns; every model when empty
+    models: Vec<String>,
+
+    // Tenant the key's requests are processed as, if any
+    tenant: Option<String>,
+}
+
+impl KeyGrant {
+    // Creates the grant of a key.
+    pub fn new(id: &str, scopes: &[String], models: &[String], tenant: Option<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            scopes: scopes.to_vec(),
+            models: models.to_vec(),
+            tenant,
+        }
+    }
+
+    // Returns the identifier of the key's client.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // Returns the granted scopes.
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    // Returns the allowed model patterns.
+    pub fn models(&self) -> &[String] {
+        &self.models
+    }
+
+    // Returns the tenant the key's requests are processed as, if any.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    // Returns whether the key holds a scope.
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        if self.scopes.is_empty() {
+            return scope != ADMIN_SCOPE;
+        }
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+
+    // Returns whether the key may be used with a model.
+    fn allows_model(&self, model: &str) -> bool {
+        self.models.is_empty()
+            || self
+                .models
+                .iter()
+                .any(|pattern| model_matches(pattern, model))
+    }
+
+    // Rejects models the key may not be used with.
+    pub fn check_model(&self, model: &str) -> Result<(), ApiError> {
+        if self.allows_model(model) {
+            return Ok(());
+        }
+        Err(ApiError::Forbidden(format!(
+            "API key may not be used with model {}",
+            model
+        )))
+    }
+}
+
+// Returns the scope needed for a request, or `None` if it needs none.
+fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    match path {
+        "/api/generate" | "/api/generate/batch" | "/api/summarize" | "/v1/completions" => {
+            Some("generate")
+        }
+        "/api/chat" => Some("chat"),
+        "/api/embeddings" | "/v1/embeddings" => Some("embeddings"),
+        "/api/tags" | "/api/show" | "/api/capabilities" => Some("models:read"),
+        "/api/create" | "/api/copy" | "/api/delete" | "/api/pull" | "/api/push" => {
+            Some("models:write")
+        }
+        "/api/jobs" => Some("generate"),
+        _ if path.starts_with("/api/jobs/") => Some("generate"),
+        _ if path.starts_with("/api/templates") => {
+            if method == Method::GET || path.ends_with("/generate") {
+                Some("generate")
+            } else {
+                Some(ADMIN_SCOPE)
+            }
+        }
+        _ => None,
+    }
+}
+
+// Returns whether a model matches a pattern, which matches any suffix when it
+// ends in `*` and otherwise names a model with or without its tag.
+pub fn model_matches(pattern: &str, model: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => with_tag(pattern) == with_tag(model),
+    }
+}
+
+// Middleware rejecting requests outside the scopes and models of their key.
+pub async fn enforce_scopes(request: Request, next: Next) -> Response {
+    let Some(grant) = request.extensions().get::<Arc<KeyGrant>>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let path = request.uri().path().to_string();
+    if let Some(scope) = required_scope(request.method(), &path) {
+        if !grant.allows_scope(scope) {
+            warn!(
+                "Rejected {} {} from client {} without the {} scope",
+                request.method(),
+                path,
+                grant.id,
+                scope
+            );
+            return ApiError::Forbidden(format!("API key lacks the {} scope", scope))
+                .into_response();
+        }
+    }
+    if grant.models.is_empty() || !MODEL_PATHS.contains(&path.as_str()) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                .into_response();
+        }
+    };
+
+    // Bodies that are not JSON objects are left for the handler to reject
+    if let Ok(Value::Object(object)) = serde_json::from_slice::<Value>(&bytes) {
+        let denied = MODEL_FIELDS
+            .iter()
+            .filter_map(|field| object.get(*field).and_then(Value::as_str))
+            .find_map(|model| grant.check_model(model).err().map(|e| (model, e)));
+        if let Some((model, e)) = denied {
+            warn!(
+                "Rejected {} {} from client {} for model {}",
+                parts.method, path, grant.id, model
+            );
+            return e.into_response();
+        }
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
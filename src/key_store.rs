@@ -0,0 +1,431 @@
+// Client keys managed at runtime through the admin API.
+//
+// Onboarding a user should not require editing the configuration file and
+// restarting the proxy, so operators can create, rotate and revoke client keys
+// through `/admin/keys`. Managed keys are accepted alongside the configured
+// keys and can carry the same scopes and model patterns, an expiry and a
+// tenant:
+//
+// - the key itself is generated by the proxy and returned only once, when the
+//   key is created or rotated
+// - a rotated key stops working immediately and is replaced by a new one
+// - revoked keys stay listed so their usage remains attributable, but are no
+//   longer accepted, and their ids cannot be reused
+// - requests authenticated by a key with a tenant are processed as that
+//   tenant, whatever tenant header they carry
+//
+// # Persistence
+//
+// Keys are stored as their SHA-256 hashes in the configured state file,
+// encrypted when state file encryption is configured, and loaded again at
+// startup. A change is only reported as done once it has been saved; a key
+// that could not be created or rotated is rolled back, and a key that could not
+// be revoked stays revoked until the next restart. When the state file exists
+// but cannot be loaded, no changes are saved, so that they cannot overwrite the
+// keys in it.
+use crate::client_auth::{decode_hex, to_hex};
+use crate::config::KEY_SCOPES;
+use crate::encryption;
+use crate::handlers::admin::constant_time_eq;
+use crate::key_scopes::KeyGrant;
+use crate::migrations;
+use chrono::{DateTime, Utc};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tracing::{error, info};
+
+// Prefix of generated keys, so leaked keys are easy to recognize.
+const KEY_PREFIX: &str = "pao_";
+
+// Number of random bytes in a generated key.
+const KEY_BYTES: usize = 32;
+
+// A client key created through the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagedKey {
+    // Identifier of the client
+    pub id: String,
+
+    // Hex-encoded SHA-256 hash of the key
+    pub key_sha256: String,
+
+    // Granted scopes; every scope except `admin` when empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    // Allowed model patterns; every model when empty
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    // Tenant the key's requests are processed as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+
+    // When the key was created
+    pub created_at: DateTime<Utc>,
+
+    // When the key stops being accepted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    // When the key was last rotated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<DateTime<Utc>>,
+
+    // When the key was revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ManagedKey {
+    // Returns `active`, `expired` or `revoked`.
+    pub fn status(&self, now: DateTime<Utc>) -> &'static str {
+        if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            "expired"
+        } else {
+            "active"
+        }
+    }
+}
+
+// Errors that can occur while changing the managed keys.
+#[derive(Debug, Error)]
+pub enum KeyStoreError {
+    // Settings of the key are invalid or its id is taken
+    #[error("{0}")]
+    Invalid(String),
+
+    // Change could not be saved to the state file
+    #[error("Failed to save client keys: {0}")]
+    NotSaved(String),
+}
+
+// Settings of a key to create.
+#[derive(Debug, Deserialize)]
+pub struct NewKey {
+    // Identifier of the client
+    pub id: String,
+
+    // Granted scopes; every scope except `admin` when empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    // Allowed model patterns; every model when empty
+    #[serde(default)]
+    pub models: Vec<String>,
+
+    // Tenant the key's requests are processed as
+    #[serde(default)]
+    pub tenant: Option<String>,
+
+    // When the key stops being accepted; never when not set
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// Stores the managed keys.
+//
+// Cloning the store is cheap and all clones share the same keys.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    // File persisting the keys; empty to keep them in memory only
+    state_file: String,
+
+    // Keys by client identifier
+    keys: Arc<Mutex<BTreeMap<String, ManagedKey>>>,
+
+    // Why the existing state file could not be loaded; nothing is saved then
+    load_error: Option<String>,
+}
+
+impl KeyStore {
+    // Creates the store, loading persisted keys from the state file.
+    pub fn new(state_file: &str) -> Self {
+        let (keys, load_error) = match load_keys(state_file) {
+            Ok(keys) => (keys, None),
+            Err(e) => {
+                error!(
+                    "Failed to load client keys from {}, refusing to change them: {}",
+                    state_file, e
+                );
+                (BTreeMap::new(), Some(e))
+            }
+        };
+        if !keys.is_empty() {
+            info!("Loaded {} managed client keys", keys.len());
+        }
+
+        Self {
+            state_file: state_file.to_string(),
+            keys: Arc::new(Mutex::new(keys)),
+            load_error,
+        }
+    }
+
+    // Returns all keys ordered by identifier.
+    pub fn list(&self) -> Vec<ManagedKey> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        keys.values().cloned().collect()
+    }
+
+    // Returns the grant of the active key with the given hash, if any.
+    pub fn authenticate(&self, hash: &[u8]) -> Option<KeyGrant> {
+        let now = Utc::now();
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        // Compare against every key so the time taken does not reveal which matched
+        let found = keys.values().fold(None, |found, key| {
+            let expected = decode_hex(&key.key_sha256).unwrap_or_default();
+            if constant_time_eq(hash, &expected) {
+                Some(key)
+            } else {
+                found
+            }
+        })?;
+        (found.status(now) == "active").then(|| {
+            KeyGrant::new(
+                &found.id,
+                &found.scopes,
+                &found.models,
+                found.tenant.clone(),
+            )
+        })
+    }
+
+    // Returns the grant of the key with the given client identifier.
+    //
+    // # Returns
+    //
+    // `None` if no key has the identifier, otherwise the key's grant, or its
+    // status if it is expired or revoked
+    pub fn grant(&self, id: &str) -> Option<Result<KeyGrant, &'static str>> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let key = keys.get(id)?;
+        Some(match key.status(Utc::now()) {
+            "active" => Ok(KeyGrant::new(
+                &key.id,
+                &key.scopes,
+                &key.models,
+                key.tenant.clone(),
+            )),
+            status => Err(status),
+        })
+    }
+
+    // Creates a key.
+    //
+    // # Returns
+    //
+    // The stored key and the key itself, which is not kept
+    //
+    // # Errors
+    //
+    // Returns an error if the settings are invalid, the id is taken or the key
+    // cannot be saved.
+    pub fn create(&self, new: NewKey) -> Result<(ManagedKey, String), KeyStoreError> {
+        validate(&new).map_err(KeyStoreError::Invalid)?;
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if keys.contains_key(&new.id) {
+            return Err(KeyStoreError::Invalid(format!(
+                "A managed key with id {} already exists",
+                new.id
+            )));
+        }
+
+        let (secret, key_sha256) = generate_key();
+        let key = ManagedKey {
+            id: new.id,
+            key_sha256,
+            scopes: new.scopes,
+            models: new.models,
+            tenant: new.tenant.filter(|tenant| !tenant.trim().is_empty()),
+            created_at: Utc::now(),
+            expires_at: new.expires_at,
+            rotated_at: None,
+            revoked_at: None,
+        };
+        keys.insert(key.id.clone(), key.clone());
+        if let Err(e) = self.save(&keys) {
+            keys.remove(&key.id);
+            return Err(e);
+        }
+        Ok((key, secret))
+    }
+
+    // Replaces the key of a client with a new one.
+    //
+    // # Returns
+    //
+    // The stored key and the new key itself, or `None` if no active key has
+    // the id
+    //
+    // # Errors
+    //
+    // Returns an error if the new key cannot be saved; the previous key is
+    // kept then.
+    pub fn rotate(&self, id: &str) -> Result<Option<(ManagedKey, String)>, KeyStoreError> {
+        let now = Utc::now();
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(key) = keys.get_mut(id).filter(|key| key.revoked_at.is_none()) else {
+            return Ok(None);
+        };
+        let previous = key.clone();
+        let (secret, key_sha256) = generate_key();
+        key.key_sha256 = key_sha256;
+        key.rotated_at = Some(now);
+        let key = key.clone();
+        if let Err(e) = self.save(&keys) {
+            keys.insert(previous.id.clone(), previous);
+            return Err(e);
+        }
+        Ok(Some((key, secret)))
+    }
+
+    // Revokes the key of a client.
+    //
+    // # Returns
+    //
+    // The revoked key, or `None` if no key has the id
+    //
+    // # Errors
+    //
+    // Returns an error if the revocation cannot be saved; the key stays
+    // revoked until the next restart then.
+    pub fn revoke(&self, id: &str) -> Result<Option<ManagedKey>, KeyStoreError> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(key) = keys.get_mut(id) else {
+            return Ok(None);
+        };
+        key.revoked_at.get_or_insert_with(Utc::now);
+        let key = key.clone();
+        self.save(&keys)?;
+        Ok(Some(key))
+    }
+
+    // Persists the keys so they survive restarts.
+    //
+    // # Errors
+    //
+    // Returns an error if the existing state file could not be loaded or the
+    // keys cannot be written.
+    fn save(&self, keys: &BTreeMap<String, ManagedKey>) -> Result<(), KeyStoreError> {
+        if self.state_file.is_empty() {
+            return Ok(());
+        }
+        if let Some(load_error) = &self.load_error {
+            return Err(KeyStoreError::NotSaved(format!(
+                "{} could not be loaded at startup and is not overwritten: {}",
+                self.state_file, load_error
+            )));
+        }
+
+        migrations::encode("keys", keys)
+            .and_then(|contents| encryption::write(&self.state_file, &contents))
+            .map_err(|e| {
+                error!("Failed to save client keys to {}: {}", self.state_file, e);
+                KeyStoreError::NotSaved(e)
+            })
+    }
+}
+
+// Checks the settings of a key to create.
+fn validate(new: &NewKey) -> Result<(), String> {
+    let valid_id = !new.id.is_empty()
+        && new.id.len() <= 128
+        && new
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.@".contains(c));
+    if !valid_id {
+        return Err("Key ids must be 1 to 128 letters, digits, `-`, `_`, `.` or `@`".to_string());
+    }
+    if let Some(scope) = new
+        .scopes
+        .iter()
+        .find(|scope| !KEY_SCOPES.contains(&scope.as_str()))
+    {
+        return Err(format!(
+            "Unknown scope {}; expected one of {}",
+            scope,
+            KEY_SCOPES.join(", ")
+        ));
+    }
+    if new.models.iter().any(|model| model.trim().is_empty()) {
+        return Err("Model patterns must not be empty".to_string());
+    }
+    if new
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= Utc::now())
+    {
+        return Err("expires_at must be in the future".to_string());
+    }
+    Ok(())
+}
+
+// Generates a key and the hex-encoded SHA-256 hash it is stored as.
+fn generate_key() -> (String, String) {
+    let mut bytes = [0u8; KEY_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = format!("{}{}", KEY_PREFIX, to_hex(&bytes));
+    let hash = to_hex(&Sha256::digest(secret.as_bytes()));
+    (secret, hash)
+}
+
+// Loads the persisted keys, starting empty if there is no state file.
+//
+// # Errors
+//
+// Returns an error if the state file exists but cannot be read or does not
+// hold valid keys.
+fn load_keys(path: &str) -> Result<BTreeMap<String, ManagedKey>, String> {
+    if path.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+
+    match fs::read_to_string(path) {
+        Ok(contents) => encryption::open(&contents)
+            .and_then(|contents| migrations::decode("keys", &contents))
+            .and_then(|data| serde_json::from_value(data).map_err(|e| e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Checks that the state file holds valid keys.
+//
+// # Returns
+//
+// The number of stored keys, or `None` if no state file exists
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or does not hold valid keys.
+pub fn verify_state_file(path: &str) -> Result<Option<usize>, String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    verify_contents(&contents).map(Some)
+}
+
+// Checks that state file contents hold valid keys.
+//
+// # Returns
+//
+// The number of keys
+//
+// # Errors
+//
+// Returns an error if the contents do not hold valid keys.
+pub fn verify_contents(contents: &str) -> Result<usize, String> {
+    let data = migrations::decode("keys", &encryption::open(contents)?)?;
+    let keys: BTreeMap<String, ManagedKey> =
+        serde_json::from_value(data).map_err(|e| e.to_string())?;
+    Ok(keys.len())
+}
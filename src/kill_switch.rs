@@ -0,0 +1,363 @@
+// Emergency kill switch.
+//
+// Incident responders need to stop all generation with a single call, without
+// editing the configuration or restarting the proxy. `PUT /admin/kill-switch`
+// activates the kill switch and `DELETE /admin/kill-switch` lifts it. While it
+// is active:
+//
+// - new generation requests (generate, batch, chat, summarize, OpenAI
+//   completions, template generations and jobs) are rejected with 503 and the
+//   configured message
+// - streams in flight are cut off and end with a final error chunk, which
+//   drops the Ollama requests behind them
+// - jobs still queued or running are cancelled and fail with the configured
+//   message
+// - scheduled prompts are skipped
+//
+// Model management, embeddings, health checks and the admin API stay
+// available.
+//
+// # Persistence
+//
+// The activation is written to a state file, so a restarted proxy comes back
+// with the kill switch still active until it is lifted. A state file that
+// cannot be read keeps the kill switch active rather than silently lifting it.
+//
+// # Audit
+//
+// Every activation records who activated it (the admin key's client, or the
+// admin token), from which address, when and why, as reported by
+// `GET /admin/kill-switch`. Activations and lifts are logged, exported as
+// `kill_switch` security events and notified as `kill_switch` alerts.
+// Rejected requests and cut streams are counted in
+// `panw_kill_switch_rejections_total` and `panw_kill_switch_streams_cut_total`.
+use crate::config::KillSwitchConfig;
+use crate::cutoff::cut_off_stream;
+use crate::metrics;
+use crate::AppState;
+use crate::{encryption, migrations};
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+// Default message returned while the kill switch is active.
+const DEFAULT_MESSAGE: &str =
+    "Generation has been stopped by the operators of this service. Please try again later.";
+
+// Generation endpoints stopped by the kill switch.
+const GENERATION_PATHS: [&str; 6] = [
+    "/api/generate",
+    "/api/generate/batch",
+    "/api/chat",
+    "/api/summarize",
+    "/v1/completions",
+    "/api/jobs",
+];
+
+// An activation of the kill switch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activation {
+    // Client of the admin key that activated the kill switch, or `admin_token`
+    pub activated_by: String,
+
+    // When the kill switch was activated
+    pub activated_at: DateTime<Utc>,
+
+    // Address the activation came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+
+    // Reason given for the activation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    // Message returned to clients, overriding the configured message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+// State of the kill switch as reported by the admin API.
+#[derive(Debug, Serialize)]
+pub struct KillSwitchStatus {
+    // Whether generation is stopped
+    pub active: bool,
+
+    // Message returned to clients while active
+    pub message: String,
+
+    // The current activation
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub activation: Option<Activation>,
+}
+
+// Stops generation while activated.
+//
+// Cloning is cheap and all clones share the same activation.
+#[derive(Clone)]
+pub struct KillSwitch {
+    // The current activation; none while generation is allowed
+    activation: Arc<RwLock<Option<Activation>>>,
+
+    // Whether the kill switch is active, watched by streams and jobs in flight
+    active: Arc<watch::Sender<bool>>,
+
+    // Configured message
+    message: String,
+
+    // File persisting the activation
+    state_file: String,
+}
+
+impl KillSwitch {
+    // Creates the kill switch, restoring an activation persisted before a restart.
+    pub fn new(config: &KillSwitchConfig) -> Self {
+        let activation = load_activation(&config.state_file);
+        if let Some(activation) = &activation {
+            warn!(
+                "Kill switch is active since {} (activated by {}); generation stays stopped \
+                 until it is lifted",
+                activation.activated_at, activation.activated_by
+            );
+        }
+        let (active, _) = watch::channel(activation.is_some());
+        record_active(activation.is_some());
+        Self {
+            activation: Arc::new(RwLock::new(activation)),
+            active: Arc::new(active),
+            message: config.message.clone(),
+            state_file: config.state_file.clone(),
+        }
+    }
+
+    // Returns whether generation is stopped.
+    pub fn is_active(&self) -> bool {
+        *self.active.borrow()
+    }
+
+    // Waits until the kill switch is active.
+    //
+    // # Returns
+    //
+    // The message returned to clients while it is active
+    pub async fn engaged(&self) -> String {
+        let mut active = self.active.subscribe();
+        if active.wait_for(|active| *active).await.is_err() {
+            // The kill switch is never dropped while requests are served
+            future::pending::<()>().await;
+        }
+        self.status().message
+    }
+
+    // Returns the state of the kill switch.
+    pub fn status(&self) -> KillSwitchStatus {
+        let activation = self
+            .activation
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        KillSwitchStatus {
+            active: activation.is_some(),
+            message: self.message_for(activation.as_ref()),
+            activation,
+        }
+    }
+
+    // Activates the kill switch, cutting off the streams and jobs in flight, and
+    // persists the activation. An active kill switch keeps its original activation.
+    //
+    // # Returns
+    //
+    // Whether the kill switch was newly activated
+    pub fn activate(&self, activation: Activation) -> bool {
+        {
+            let mut current = self.activation.write().unwrap_or_else(|e| e.into_inner());
+            if current.is_some() {
+                return false;
+            }
+            self.persist(&activation);
+            *current = Some(activation);
+        }
+        self.active.send_replace(true);
+        record_active(true);
+        true
+    }
+
+    // Lifts the kill switch and removes the persisted activation.
+    //
+    // # Returns
+    //
+    // The lifted activation, or `None` if the kill switch was not active
+    pub fn lift(&self) -> Option<Activation> {
+        let lifted = {
+            let mut current = self.activation.write().unwrap_or_else(|e| e.into_inner());
+            let lifted = current.take()?;
+            if let Err(e) = fs::remove_file(&self.state_file) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    error!(
+                        "Failed to remove kill switch state file {}; the kill switch will be \
+                         active again after a restart: {}",
+                        self.state_file, e
+                    );
+                }
+            }
+            lifted
+        };
+        self.active.send_replace(false);
+        record_active(false);
+        Some(lifted)
+    }
+
+    // Returns the message for clients, preferring the activation's own message.
+    fn message_for(&self, activation: Option<&Activation>) -> String {
+        activation
+            .and_then(|activation| activation.message.as_deref())
+            .filter(|message| !message.is_empty())
+            .or(Some(self.message.as_str()).filter(|message| !message.is_empty()))
+            .unwrap_or(DEFAULT_MESSAGE)
+            .to_string()
+    }
+
+    // Writes the activation to the state file.
+    fn persist(&self, activation: &Activation) {
+        let result = migrations::encode("kill_switch", activation)
+            .and_then(|contents| encryption::write(&self.state_file, &contents));
+        if let Err(e) = result {
+            error!(
+                "Failed to persist kill switch state to {}; the kill switch will not survive \
+                 a restart: {}",
+                self.state_file, e
+            );
+        }
+    }
+}
+
+// Reports whether the kill switch is active in `panw_kill_switch_active`.
+fn record_active(active: bool) {
+    metrics::set_gauge(
+        "panw_kill_switch_active",
+        "Whether the kill switch is stopping generation",
+        &[],
+        if active { 1.0 } else { 0.0 },
+    );
+}
+
+// Loads a persisted activation; an unreadable state file counts as active.
+fn load_activation(path: &str) -> Option<Activation> {
+    let contents = fs::read_to_string(path).ok()?;
+    let activation = encryption::open(&contents)
+        .and_then(|contents| migrations::decode("kill_switch", &contents))
+        .and_then(|data| serde_json::from_value(data).map_err(|e| e.to_string()));
+    match activation {
+        Ok(activation) => Some(activation),
+        Err(e) => {
+            error!(
+                "Kill switch state file {} is invalid, keeping the kill switch active: {}",
+                path, e
+            );
+            Some(Activation {
+                activated_by: "unknown".to_string(),
+                activated_at: Utc::now(),
+                ip: None,
+                reason: Some(format!("invalid state file {}", path)),
+                message: None,
+            })
+        }
+    }
+}
+
+// Checks that the state file holds a valid activation.
+//
+// # Returns
+//
+// The number of records in the file, or `None` if no state file exists
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or is not a valid activation.
+pub fn verify_state_file(path: &str) -> Result<Option<usize>, String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    verify_contents(&contents).map(Some)
+}
+
+// Checks that state file contents hold a valid activation.
+//
+// # Returns
+//
+// The number of records, which is always one
+//
+// # Errors
+//
+// Returns an error if the contents are not a valid activation.
+pub fn verify_contents(contents: &str) -> Result<usize, String> {
+    let data = migrations::decode("kill_switch", &encryption::open(contents)?)?;
+    serde_json::from_value::<Activation>(data).map_err(|e| e.to_string())?;
+    Ok(1)
+}
+
+// Returns whether a request starts a generation.
+fn is_generation(method: &Method, path: &str) -> bool {
+    *method == Method::POST
+        && (GENERATION_PATHS.contains(&path)
+            || path
+                .strip_prefix("/api/templates/")
+                .is_some_and(|rest| rest.ends_with("/generate")))
+}
+
+// Middleware rejecting generation requests while the kill switch is active and
+// cutting off their streams when it is activated.
+pub async fn kill_switch_guard(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    if !is_generation(request.method(), &path) {
+        return next.run(request).await;
+    }
+
+    let kill_switch = state.kill_switch.clone();
+    if kill_switch.is_active() {
+        warn!("Rejected {} while the kill switch is active", path);
+        metrics::increment_counter(
+            "panw_kill_switch_rejections_total",
+            "Generation requests rejected while the kill switch was active",
+            &[],
+        );
+        let status = StatusCode::SERVICE_UNAVAILABLE;
+        let body = Json(json!({
+            "error": kill_switch.status().message,
+            "status": status.as_u16(),
+            "kill_switch": true,
+        }));
+        return (status, body).into_response();
+    }
+
+    let response = next.run(request).await;
+
+    // Cut the stream off on activation
+    cut_off_stream(response, async move {
+        let message = kill_switch.engaged().await;
+        warn!("Cut off stream of {} on kill switch activation", path);
+        metrics::increment_counter(
+            "panw_kill_switch_streams_cut_total",
+            "Streams cut off by the activation of the kill switch",
+            &[],
+        );
+        (message, StatusCode::SERVICE_UNAVAILABLE)
+    })
+}
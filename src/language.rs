@@ -0,0 +1,98 @@
+// Local language detection and policy for prompts and responses.
+//
+// Some compliance regimes restrict which languages support staff can review, so
+// the language of every scanned prompt and response is detected locally and
+// counted in `panw_language_detections_total`. When a list of allowed languages
+// is configured, content in any other language is either flagged in the logs
+// or blocked before it is sent to PANW, which also saves scan quota.
+//
+// # Confidence
+//
+// Detection on short or mixed-language text is unreliable. Content detected
+// with less than the configured confidence is counted as `unknown` and never
+// flagged or blocked.
+use crate::config::{LanguageAction, LanguageConfig};
+use crate::metrics;
+use tracing::{debug, warn};
+use whatlang::Lang;
+
+// Detects the language of content and applies the configured policy.
+pub struct LanguagePolicy {
+    // Allowed languages; all languages are allowed when empty
+    allowed: Vec<Lang>,
+
+    // Action taken for content in a language that is not allowed
+    action: LanguageAction,
+
+    // Minimum detection confidence before the policy applies
+    min_confidence: f64,
+}
+
+impl LanguagePolicy {
+    // Creates the policy from the configuration.
+    //
+    // Unknown language codes are rejected by config validation and ignored here.
+    pub fn new(config: &LanguageConfig) -> Self {
+        Self {
+            allowed: config
+                .allowed_languages
+                .iter()
+                .filter_map(|code| Lang::from_code(code.as_str()))
+                .collect(),
+            action: config.action,
+            min_confidence: config.min_confidence,
+        }
+    }
+
+    // Detects the language of the content, counts it and checks it against the policy.
+    //
+    // # Arguments
+    //
+    // * `content` - Text of the prompt or response
+    // * `is_prompt` - Whether the content is a prompt rather than a response
+    //
+    // # Returns
+    //
+    // Whether the content should be blocked
+    pub fn check(&self, content: &str, is_prompt: bool) -> bool {
+        let direction = if is_prompt { "prompt" } else { "response" };
+        let detected = whatlang::detect(content)
+            .filter(|info| info.confidence() >= self.min_confidence)
+            .map(|info| info.lang());
+
+        let code = detected.map_or("unknown", |lang| lang.code());
+        debug!("Detected {} language: {}", direction, code);
+        metrics::increment_counter(
+            "panw_language_detections_total",
+            "Prompts and responses by detected language",
+            &[("direction", direction), ("language", code)],
+        );
+
+        let lang = match detected {
+            Some(lang) if !self.allowed.is_empty() && !self.allowed.contains(&lang) => lang,
+            _ => return false,
+        };
+
+        let action = match self.action {
+            LanguageAction::Flag => "flag",
+            LanguageAction::Block => "block",
+        };
+        warn!(
+            "Detected {} in disallowed language {} ({}), action={}",
+            direction,
+            lang.eng_name(),
+            lang.code(),
+            action
+        );
+        metrics::increment_counter(
+            "panw_language_violations_total",
+            "Prompts and responses in a language that is not allowed",
+            &[
+                ("direction", direction),
+                ("language", lang.code()),
+                ("action", action),
+            ],
+        );
+        self.action == LanguageAction::Block
+    }
+}
@@ -0,0 +1,379 @@
+// Leader election between replicas running the same schedules.
+//
+// Every replica registers the scheduled prompts from the shared configuration,
+// so without coordination each run would execute once per replica and post
+// duplicate results. When enabled, replicas elect a leader through lease
+// files on storage they all mount, such as the volume holding the state files,
+// and only the leader runs due schedules:
+//
+// - every lease is a numbered term, stored next to the configured lease file
+//   as `<lease file>.<term>`, holding the instance id of the leader and when
+//   the term expires; the highest term is the current lease
+// - every replica checks the lease a few times per lease TTL; a replica takes
+//   the lease when it is missing or expired, and the leader renews it, both by
+//   claiming the next term
+// - a term is claimed by hard-linking a complete lease file to its name, which
+//   fails if the name exists, so when two replicas claim the same term only
+//   one succeeds; older terms are removed by the winner
+// - before running due schedules, the leader reads the lease again and skips
+//   the runs if another replica has taken over in the meantime
+//
+// When the leader stops, another replica takes over once the lease has
+// expired; runs that fall due before then are skipped. Expiry is compared
+// across replicas, so their clocks must be kept in sync. A leader that stalls
+// for longer than the lease TTL after checking the lease, for example while
+// the host is suspended, may still run schedules that the new leader runs too.
+//
+// # Metrics
+//
+// Whether this replica is the leader is reported in `panw_scheduler_leader`.
+use crate::config::LeaderElectionConfig;
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// Contents of the lease file.
+#[derive(Debug, Deserialize, Serialize)]
+struct Lease {
+    // Instance id of the replica holding the lease
+    holder: String,
+
+    // When the lease expires unless it is renewed
+    expires_at: DateTime<Utc>,
+}
+
+// Lease deciding which replica runs scheduled prompts.
+//
+// Cloning the lease is cheap and all clones share the same leadership state.
+#[derive(Clone)]
+pub struct LeaderLease {
+    // Identifies this replica in the lease file
+    instance_id: String,
+
+    // Path of the lease file the terms are numbered after
+    path: String,
+
+    // How long the lease is valid without being renewed
+    ttl: Duration,
+
+    // Whether this replica held the lease when last checked
+    leader: Arc<AtomicBool>,
+}
+
+impl LeaderLease {
+    // Creates the lease from the configuration without taking it.
+    pub fn new(config: &LeaderElectionConfig) -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "replica".to_string());
+        let instance_id = format!("{}-{:08x}", hostname, rand::random::<u32>());
+        info!("Leader election enabled, instance id {}", instance_id);
+        record_state(false);
+
+        Self {
+            instance_id,
+            path: config.lease_file.clone(),
+            ttl: Duration::from_secs(config.lease_ttl_secs),
+            leader: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Starts the background task taking and renewing the lease.
+    pub fn spawn(&self) {
+        let lease = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(lease.ttl / 3);
+            loop {
+                interval.tick().await;
+                lease.refresh();
+            }
+        });
+    }
+
+    // Reports whether this replica holds the lease, reading the lease file.
+    pub fn is_leader(&self) -> bool {
+        let leader = match self.read() {
+            Ok((_, Some(lease))) => self.holds(&lease),
+            Ok((_, None)) => false,
+            Err(e) => {
+                warn!("Failed to read leader lease {}: {}", self.path, e);
+                false
+            }
+        };
+        self.set_leader(leader);
+        leader
+    }
+
+    // Takes the lease if it is missing or expired, or renews it if this
+    // replica holds it.
+    fn refresh(&self) {
+        let (term, current) = match self.read() {
+            Ok(current) => current,
+            Err(e) => {
+                warn!("Failed to read leader lease {}: {}", self.path, e);
+                self.set_leader(false);
+                return;
+            }
+        };
+        let available = current
+            .as_ref()
+            .is_none_or(|lease| lease.holder == self.instance_id || lease.expires_at <= Utc::now());
+        if available {
+            match self.claim(term + 1) {
+                Ok(true) => self.remove_terms_before(term + 1),
+                Ok(false) => debug!(
+                    "Another replica claimed term {} of leader lease {} first",
+                    term + 1,
+                    self.path
+                ),
+                Err(e) => warn!("Failed to write leader lease {}: {}", self.path, e),
+            }
+        }
+
+        // Read the lease back in case another replica claimed a later term
+        self.is_leader();
+    }
+
+    // Returns whether the lease is held by this replica and has not expired.
+    fn holds(&self, lease: &Lease) -> bool {
+        lease.holder == self.instance_id && lease.expires_at > Utc::now()
+    }
+
+    // Returns the path of the lease file of a term.
+    fn term_path(&self, term: u64) -> String {
+        format!("{}.{}", self.path, term)
+    }
+
+    // Lists the terms that have lease files.
+    //
+    // # Errors
+    //
+    // Returns an error if the directory of the lease files cannot be read.
+    fn terms(&self) -> Result<Vec<u64>, String> {
+        let path = Path::new(&self.path);
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let prefix = format!(
+            "{}.",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_str()?
+                    .strip_prefix(&prefix)?
+                    .parse()
+                    .ok()
+            })
+            .collect())
+    }
+
+    // Reads the lease of the highest term.
+    //
+    // # Returns
+    //
+    // The highest term, 0 if there is none, and its lease, or `None` if there
+    // is no lease file or it cannot be parsed
+    //
+    // # Errors
+    //
+    // Returns an error if the lease files exist but cannot be read.
+    fn read(&self) -> Result<(u64, Option<Lease>), String> {
+        let Some(term) = self.terms()?.into_iter().max() else {
+            return Ok((0, None));
+        };
+        let contents = match fs::read_to_string(self.term_path(term)) {
+            Ok(contents) => contents,
+            // Removed by a replica that claimed a later term since listing
+            Err(e) if e.kind() == ErrorKind::NotFound => return self.read(),
+            Err(e) => return Err(e.to_string()),
+        };
+        match serde_json::from_str(&contents) {
+            Ok(lease) => Ok((term, Some(lease))),
+            Err(e) => {
+                warn!(
+                    "Ignoring unparseable leader lease {}: {}",
+                    self.term_path(term),
+                    e
+                );
+                Ok((term, None))
+            }
+        }
+    }
+
+    // Claims a term with a lease held by this replica.
+    //
+    // The lease is written to a temporary file first and then hard-linked to
+    // the file of the term, which fails if another replica claimed the term.
+    //
+    // # Returns
+    //
+    // Whether this replica claimed the term
+    //
+    // # Errors
+    //
+    // Returns an error if the lease file cannot be written.
+    fn claim(&self, term: u64) -> Result<bool, String> {
+        let ttl = chrono::Duration::from_std(self.ttl).map_err(|e| e.to_string())?;
+        let lease = Lease {
+            holder: self.instance_id.clone(),
+            expires_at: Utc::now() + ttl,
+        };
+        let contents = serde_json::to_string(&lease).map_err(|e| e.to_string())?;
+
+        let temp_path = format!("{}.{}.tmp", self.path, self.instance_id);
+        fs::write(&temp_path, contents).map_err(|e| e.to_string())?;
+        let result = fs::hard_link(&temp_path, self.term_path(term));
+        let _ = fs::remove_file(&temp_path);
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // Removes the lease files of the terms before the given one.
+    fn remove_terms_before(&self, term: u64) {
+        for older in self.terms().unwrap_or_default() {
+            if older < term {
+                let _ = fs::remove_file(self.term_path(older));
+            }
+        }
+    }
+
+    // Records whether this replica is the leader, logging changes.
+    fn set_leader(&self, leader: bool) {
+        if self.leader.swap(leader, Ordering::Relaxed) != leader {
+            if leader {
+                info!("This replica is now the scheduler leader");
+            } else {
+                info!("This replica is no longer the scheduler leader");
+            }
+            record_state(leader);
+        }
+    }
+}
+
+// Reports whether this replica is the leader.
+fn record_state(leader: bool) {
+    metrics::set_gauge(
+        "panw_scheduler_leader",
+        "Whether this replica is the leader running scheduled prompts",
+        &[],
+        if leader { 1.0 } else { 0.0 },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // Creates an empty directory for the lease files of a test.
+    fn lease_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("leader-{:08x}", rand::random::<u32>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Creates a replica's lease on the lease file in the directory.
+    fn replica(dir: &Path, lease_ttl_secs: u64) -> LeaderLease {
+        LeaderLease::new(&LeaderElectionConfig {
+            enabled: true,
+            lease_file: dir.join("leader.json").to_string_lossy().into_owned(),
+            lease_ttl_secs,
+        })
+    }
+
+    #[test]
+    fn only_one_replica_claims_a_term() {
+        let dir = lease_dir();
+        let (a, b) = (replica(&dir, 30), replica(&dir, 30));
+
+        // Both replicas found the lease missing before either claimed it
+        assert_eq!(a.read().unwrap().0, 0);
+        assert_eq!(b.read().unwrap().0, 0);
+        assert!(a.claim(1).unwrap());
+        assert!(!b.claim(1).unwrap());
+
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn leader_renews_its_lease() {
+        let dir = lease_dir();
+        let (a, b) = (replica(&dir, 30), replica(&dir, 30));
+
+        a.refresh();
+        b.refresh();
+        a.refresh();
+
+        assert!(a.is_leader());
+        assert!(!b.is_leader());
+        assert_eq!(a.terms().unwrap(), vec![2]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expired_lease_is_taken_over() {
+        let dir = lease_dir();
+        let (a, b) = (replica(&dir, 0), replica(&dir, 30));
+
+        a.refresh();
+        b.refresh();
+
+        assert!(!a.is_leader());
+        assert!(b.is_leader());
+        assert_eq!(b.read().unwrap().0, 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_refreshes_elect_one_leader() {
+        let dir = lease_dir();
+        let replicas = [replica(&dir, 0), replica(&dir, 0)];
+
+        // Both replicas read the same term before either claims the next one
+        for _ in 0..50 {
+            let barrier = std::sync::Barrier::new(replicas.len());
+            let claimed = std::thread::scope(|scope| {
+                let handles: Vec<_> = replicas
+                    .iter()
+                    .map(|lease| {
+                        let barrier = &barrier;
+                        scope.spawn(move || {
+                            let (term, _) = lease.read().unwrap();
+                            barrier.wait();
+                            lease.claim(term + 1).unwrap()
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect::<Vec<_>>()
+            });
+            assert_eq!(claimed.iter().filter(|&&claimed| claimed).count(), 1);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+// Local Llama Guard classification as an alternative security backend.
+//
+// With the `llama_guard` security provider, prompts and responses are
+// classified by a Llama Guard model (or a classifier with the same output
+// format) served by the upstream Ollama, so a lab can run without any external
+// scan API. Requests go through the proxy's Ollama client, following its
+// backends, model routes and retries.
+//
+// Prompts are classified as the user turn of a conversation, responses as the
+// assistant turn following an empty user turn. The model answers `safe`, or
+// `unsafe` followed by the codes of the violated hazard categories (`S1` to
+// `S14` for Llama Guard 3). Its verdict is mapped onto the PANW scan response,
+// so the rest of the security pipeline, including caching, the circuit
+// breaker, the failure mode and security events, works unchanged:
+//
+// - `S7` (privacy) is reported as `dlp`
+// - `S14` (code interpreter abuse) is reported as `malicious_code`
+// - every other category is reported as `toxic_content`
+// - the model becomes the profile name
+//
+// Only the configured categories block content, or all of them when none are
+// configured; an unsafe verdict naming no category always blocks. An answer
+// that is not a verdict counts as a failed scan.
+use crate::config::LlamaGuardConfig;
+use crate::ollama::{OllamaClient, OllamaError};
+use crate::security::SecurityError;
+use crate::types::{ScanRequest, ScanResponse};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::debug;
+
+// Hazard category reported as data loss prevention.
+const PRIVACY_CATEGORY: &str = "S7";
+
+// Hazard category reported as malicious code.
+const CODE_CATEGORY: &str = "S14";
+
+// Category of an unsafe verdict naming no categories, which always blocks.
+const UNCATEGORIZED: &str = "unsafe";
+
+// Non-streamed answer of the Ollama chat API.
+#[derive(Debug, Deserialize)]
+struct ChatAnswer {
+    message: AnswerMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnswerMessage {
+    #[serde(default)]
+    content: String,
+}
+
+// Client classifying content with a Llama Guard model served by Ollama.
+#[derive(Clone)]
+pub struct LlamaGuardClient {
+    // Ollama client the classification requests are sent through
+    ollama: OllamaClient,
+
+    // Classifier model
+    model: String,
+
+    // Hazard categories that block content; empty blocks all
+    block_categories: Vec<String>,
+}
+
+impl LlamaGuardClient {
+    // Creates a client classifying with the configured model through an Ollama client.
+    pub fn new(config: &LlamaGuardConfig, ollama: OllamaClient) -> Self {
+        Self {
+            ollama,
+            model: config.model.clone(),
+            block_categories: config
+                .block_categories
+                .iter()
+                .map(|category| category.trim().to_ascii_uppercase())
+                .collect(),
+        }
+    }
+
+    // Returns the base URL of the Ollama server classifying content.
+    pub fn base_url(&self) -> &str {
+        self.ollama.base_url()
+    }
+
+    // Classifies the content of a scan and maps the verdict onto a PANW scan
+    // response for the same scan.
+    //
+    // # Errors
+    //
+    // Returns an error if Ollama cannot be reached, fails, or answers with
+    // something other than a verdict.
+    pub async fn classify(&self, payload: &ScanRequest) -> Result<ScanResponse, SecurityError> {
+        let is_prompt = payload
+            .contents
+            .iter()
+            .any(|content| content.prompt.is_some() || content.code_prompt.is_some());
+        let text = payload
+            .contents
+            .iter()
+            .flat_map(|content| {
+                [
+                    &content.prompt,
+                    &content.code_prompt,
+                    &content.response,
+                    &content.code_response,
+                ]
+            })
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let messages = if is_prompt {
+            json!([{ "role": "user", "content": text }])
+        } else {
+            json!([
+                { "role": "user", "content": "" },
+                { "role": "assistant", "content": text },
+            ])
+        };
+        let body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": false,
+            "options": { "temperature": 0 },
+        });
+
+        let answer: ChatAnswer = self
+            .ollama
+            .forward("/api/chat", &body)
+            .await
+            .map_err(|e| match e {
+                OllamaError::RequestError(e) => SecurityError::RequestError(e),
+                OllamaError::ApiError { status, message }
+                    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    SecurityError::ProviderUnavailable(status, message)
+                }
+                e => SecurityError::AssessmentError(format!("Llama Guard request failed: {}", e)),
+            })?
+            .json()
+            .await?;
+        let categories = parse_verdict(&answer.message.content).ok_or_else(|| {
+            SecurityError::AssessmentError(format!(
+                "Unexpected Llama Guard verdict from {}: {:?}",
+                self.model,
+                answer.message.content.chars().take(100).collect::<String>()
+            ))
+        })?;
+        Ok(self.scan_response(payload, is_prompt, &categories))
+    }
+
+    // Maps the violated hazard categories onto a PANW scan response.
+    fn scan_response(
+        &self,
+        payload: &ScanRequest,
+        is_prompt: bool,
+        categories: &[String],
+    ) -> ScanResponse {
+        let blocked: Vec<&str> = categories
+            .iter()
+            .map(String::as_str)
+            .filter(|category| {
+                *category == UNCATEGORIZED
+                    || self.block_categories.is_empty()
+                    || self
+                        .block_categories
+                        .iter()
+                        .any(|blocks| blocks == category)
+            })
+            .collect();
+        if !categories.is_empty() {
+            debug!(
+                "Llama Guard flagged {}; blocking {}",
+                categories.join(", "),
+                if blocked.is_empty() {
+                    "none".to_string()
+                } else {
+                    blocked.join(", ")
+                }
+            );
+        }
+
+        let mut response = ScanResponse::default_safe_response();
+        response.scan_id = uuid::Uuid::new_v4();
+        response.report_id = response.scan_id.to_string();
+        response.tr_id = Some(payload.tr_id.clone());
+        response.profile_name = Some(self.model.clone());
+        if blocked.is_empty() {
+            return response;
+        }
+        response.category = "malicious".to_string();
+        response.action = "block".to_string();
+        for category in blocked {
+            let (dlp, code) = (category == PRIVACY_CATEGORY, category == CODE_CATEGORY);
+            if is_prompt {
+                let detected = &mut response.prompt_detected;
+                detected.dlp |= dlp;
+                detected.malicious_code |= code;
+                detected.toxic_content |= !dlp && !code;
+            } else {
+                let detected = &mut response.response_detected;
+                detected.dlp |= dlp;
+                detected.malicious_code |= code;
+                detected.toxic_content |= !dlp && !code;
+            }
+        }
+        response
+    }
+}
+
+// Parses the answer of the classifier.
+//
+// # Returns
+//
+// The codes of the violated categories, empty for safe content, or `None` if
+// the answer is not a verdict
+fn parse_verdict(answer: &str) -> Option<Vec<String>> {
+    let answer = answer.trim();
+    let (verdict, rest) = answer
+        .split_once(char::is_whitespace)
+        .unwrap_or((answer, ""));
+    match verdict.to_ascii_lowercase().as_str() {
+        "safe" => Some(Vec::new()),
+        "unsafe" => {
+            let mut categories: Vec<String> = rest
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .map(|code| code.trim().to_ascii_uppercase())
+                .filter(|code| {
+                    code.strip_prefix('S')
+                        .is_some_and(|number| number.parse::<u8>().is_ok())
+                })
+                .collect();
+            categories.dedup();
+            if categories.is_empty() {
+                categories.push(UNCATEGORIZED.to_string());
+            }
+            Some(categories)
+        }
+        _ => None,
+    }
+}
@@ -22,37 +22,214 @@
 // Module declarations
 //------------------------------------------------------------------------------
 
+// Short-lived access tokens exchanged for long-lived credentials.
+mod access_token;
+// Time-based access policies for tenants, keys and models.
+mod access_windows;
+// Lockout of sources that repeatedly fail authentication.
+mod auth_lockout;
+// Azure AI Content Safety as an alternative security backend.
+mod azure_content_safety;
+// Benchmark of the streaming assessment strategies.
+mod bench;
+// AWS Bedrock Guardrails as an alternative security backend.
+mod bedrock_guardrails;
+// Per-tenant branding of the content injected into blocked responses.
+mod branding;
+// Monthly PANW scan budget tracking.
+mod budget;
+// Cache of model capabilities learned from Ollama.
+mod capabilities;
+// Circuit breaker suspending scans while the PANW API keeps failing.
+mod circuit_breaker;
+// API key authentication of proxy clients.
+mod client_auth;
+// Normalization of request bodies sent by older clients.
+mod compat;
+// Global cap on concurrent generations with bounded queueing.
+mod concurrency;
 // Configuration loading and management.
 mod config;
+// End-to-end conformance suite run against a deployed proxy.
+mod conformance;
+// Confirmation tokens for destructive model operations.
+mod confirmation;
+// Per-connection statistics and termination of client connections.
+mod connections;
+// Early rejection of prompts that exceed the model's context window.
+mod context_window;
+// Cutting off streaming responses.
+mod cutoff;
+// Duplicate-request suppression for accidental client retries.
+mod dedup;
+// Detection and decoding of encoded payloads in prompts.
+mod encoded_payloads;
+// Encryption at rest for the local state files.
+mod encryption;
+// Evaluation of the guardrails against a labeled prompt dataset.
+mod evaluate;
+// Feature flags with per-tenant and percentage rollout.
+mod feature_flags;
 // HTTP request handlers for API endpoints.
 mod handlers;
+// Health checks of the upstream services.
+mod health;
+// Tuning of the HTTP clients for the upstream services.
+mod http_client;
+// Asynchronous generation jobs.
+mod jobs;
+// JWT bearer token validation for SSO-integrated deployments.
+mod jwt;
+// Scopes limiting the endpoints and models of client keys.
+mod key_scopes;
+// Client keys managed at runtime through the admin API.
+mod key_store;
+// Emergency kill switch stopping all generation.
+mod kill_switch;
+// Leader election between replicas running the same schedules.
+mod leader;
+// Local language detection and policy for prompts and responses.
+mod language;
+// Local Llama Guard classification as an alternative security backend.
+mod llama_guard;
+// Read-only and maintenance mode handling.
+mod maintenance;
+// Process-wide metrics in the Prometheus text format.
+mod metrics;
+// Schema versions and migrations of the local state files.
+mod migrations;
+// Notification of operational alerts through pluggable channels.
+mod notifications;
+// Built-in mock of the Ollama API for demos and tests.
+mod mock_ollama;
+// Built-in mock of the PANW API for benchmarks.
+mod mock_panw;
+// Automatic pulls of allowed models on first use.
+mod model_pull;
 // Client for interacting with Ollama API services.
 mod ollama;
+// Compatibility with the version of the connected Ollama servers.
+mod ollama_compat;
+// OpenAI Moderation API as an alternative security backend.
+mod openai_moderation;
+// Passing of selected headers between clients and Ollama.
+mod passthrough;
+// Local masking of personal data in prompts.
+mod pii;
+// Post-processing of model responses after security assessment.
+mod postprocess;
+// Local regex and keyword pre-filter rules deciding content before scans.
+mod prefilter_rules;
+// Signed provenance manifests for generated responses.
+mod provenance;
+// Self-description of the proxy's enabled subsystems.
+mod proxy_info;
+// Per-client request rate limiting.
+mod rate_limit;
+// Per-route request metrics labeled by tenant and API key.
+mod request_metrics;
+// Capture of redacted PANW responses for troubleshooting.
+mod response_capture;
+// Scan bypass for trusted internal clients.
+mod scan_bypass;
+// Reuse of PANW verdicts for repeated identical content.
+mod scan_cache;
+// Scripted latencies for reproducible demos.
+mod scenario;
+// Scheduled prompt execution.
+mod scheduler;
 // Security assessment and content filtering using PANW AI Runtime API.
 mod security;
+// Security events exported to SIEM systems.
+mod security_events;
+// Local scanner for secrets in model responses.
+mod secrets;
+// Minimal SMTP client for email notifications.
+mod smtp;
+// Maintenance of the local state files.
+mod store;
+// Server-sent event framing for OpenAI-compatible streaming.
+mod sse;
 // Utilities for handling streaming responses.
 mod stream;
+// Library of approved prompt templates.
+mod templates;
+// Per-route request timeouts.
+mod timeout;
+// Native TLS termination for the listener.
+mod tls;
+// Transaction IDs correlating client requests with PANW scans.
+mod transaction;
+// Local profanity and toxicity pre-filter.
+mod toxicity;
+// Tamper-evident conversation transcripts.
+mod transcripts;
 // Common type definitions used throughout the application.
 mod types;
+// Per-client usage accounting and privacy-preserving usage reports.
+mod usage;
+// Secret resolution from HashiCorp Vault.
+mod vault;
+// Explanations of assessment decisions for diagnosing verdicts.
+mod verdict_debug;
 
 //------------------------------------------------------------------------------
 // Import declarations
 //------------------------------------------------------------------------------
 
 // Internal crate imports
+use crate::access_token::AccessTokens;
+use crate::access_windows::AccessWindows;
+use crate::auth_lockout::AuthLockout;
+use crate::branding::TenantBrandings;
+use crate::capabilities::CapabilityCache;
+use crate::client_auth::ClientKeys;
+use crate::concurrency::GenerationLimiter;
+use crate::confirmation::ConfirmationStore;
+use crate::connections::{
+    ConnectionRegistry, TrackedListener, TrackedMakeService, TrackingAcceptor,
+};
+use crate::context_window::ContextWindows;
+use crate::dedup::DedupStore;
+use crate::feature_flags::FeatureFlags;
 use crate::handlers::*;
+use crate::health::HealthChecker;
+use crate::jobs::JobStore;
+use crate::jwt::JwtValidator;
+use crate::kill_switch::KillSwitch;
+use crate::llama_guard::LlamaGuardClient;
+use crate::maintenance::MaintenanceController;
+use crate::mock_ollama::MockOllama;
+use crate::model_pull::ModelPuller;
 use crate::ollama::OllamaClient;
+use crate::passthrough::HeaderPassthrough;
+use crate::postprocess::ResponsePostProcessor;
+use crate::provenance::ProvenanceService;
+use crate::proxy_info::ProxyCapabilities;
+use crate::rate_limit::RateLimiter;
+use crate::request_metrics::RequestLabeler;
+use crate::scenario::Scenario;
+use crate::scheduler::Scheduler;
 use crate::security::SecurityClient;
+use crate::store::StoreFiles;
+use crate::templates::TemplateStore;
+use crate::tls::{ClientIdentityAcceptor, TlsFiles};
+use crate::transcripts::Transcripts;
+use crate::usage::UsageLedger;
+use crate::vault::{SharedSecret, VaultClient};
 
 // Web framework imports
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{delete, get, post, put},
     Router,
 };
 
 // Standard library imports
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 // Middleware and utility imports
 use tower_http::trace::TraceLayer;
@@ -72,6 +249,74 @@ pub struct AppState {
     pub(crate) ollama_client: OllamaClient,
     // Client for performing security assessments
     pub(crate) security_client: SecurityClient,
+    // Model management settings
+    pub(crate) models_config: config::ModelsConfig,
+    // Outstanding confirmation tokens for destructive model operations
+    pub(crate) confirmations: ConfirmationStore,
+    // Administrative API settings
+    pub(crate) admin_config: config::AdminConfig,
+    // Resolves read-only and maintenance modes
+    pub(crate) maintenance: MaintenanceController,
+    // Emergency stop of all generation
+    pub(crate) kill_switch: KillSwitch,
+    // Recent and in-flight generation requests for duplicate and idempotent replays
+    pub(crate) dedup: DedupStore,
+    // Signs and retains response manifests
+    pub(crate) provenance: ProvenanceService,
+    // Rewrites assessed response content before it is returned
+    pub(crate) postprocess: ResponsePostProcessor,
+    // Summarization endpoint settings
+    pub(crate) summarize_config: config::SummarizeConfig,
+    // Batch generation endpoint settings
+    pub(crate) batch_config: config::BatchConfig,
+    // Asynchronous generation jobs and their results
+    pub(crate) jobs: JobStore,
+    // Prompts executed on a schedule
+    pub(crate) scheduler: Scheduler,
+    // Approved prompt templates
+    pub(crate) templates: TemplateStore,
+    // Probes the upstream services for the health check endpoint
+    pub(crate) health: HealthChecker,
+    // Derives the tenant and key labels of request metrics
+    pub(crate) request_labeler: RequestLabeler,
+    // Model context windows learned from Ollama
+    pub(crate) context_windows: ContextWindows,
+    // Model capabilities learned from Ollama
+    pub(crate) capabilities: CapabilityCache,
+    // Pulls allowed models that are missing on first use
+    pub(crate) model_puller: ModelPuller,
+    // API keys accepted from proxy clients
+    pub(crate) client_keys: ClientKeys,
+    // Validates JWTs presented by proxy clients
+    pub(crate) jwt: JwtValidator,
+    // Issues and validates short-lived access tokens
+    pub(crate) access_tokens: AccessTokens,
+    // Locks out sources that repeatedly fail authentication
+    pub(crate) auth_lockout: AuthLockout,
+    // Per-client usage counts
+    pub(crate) usage: UsageLedger,
+    // Tamper-evident records of assessed content (optional)
+    pub(crate) transcripts: Option<Transcripts>,
+    // State files verified by the admin API
+    pub(crate) store_files: StoreFiles,
+    // Per-client request rate limits
+    pub(crate) rate_limiter: RateLimiter,
+    // Time windows of tenants, keys and models
+    pub(crate) access_windows: AccessWindows,
+    // Caps the number of generations in flight
+    pub(crate) generation_limiter: GenerationLimiter,
+    // Request and stream timeouts
+    pub(crate) timeout_config: config::TimeoutConfig,
+    // Feature flags guarding risky behaviors
+    pub(crate) feature_flags: FeatureFlags,
+    // Enabled subsystems reported to clients
+    pub(crate) proxy_capabilities: Arc<ProxyCapabilities>,
+    // Normalization of request bodies sent by older clients
+    pub(crate) compat_config: config::CompatConfig,
+    // Headers passed between clients and Ollama
+    pub(crate) header_passthrough: HeaderPassthrough,
+    // Open client connections and their statistics
+    pub(crate) connections: ConnectionRegistry,
 }
 
 impl AppState {
@@ -79,6 +324,19 @@ impl AppState {
     pub fn builder() -> AppStateBuilder {
         AppStateBuilder::default()
     }
+
+    // Ensures that a requested model is installed, pulling it when allowed.
+    //
+    // # Errors
+    //
+    // Returns a model pulling error while an automatic pull of the model is running.
+    pub(crate) async fn ensure_model_available(&self, model: &str) -> Result<(), ApiError> {
+        // Pulls are model writes, which only the normal operating mode allows
+        let may_pull = self.maintenance.effective_mode().mode == config::OperatingMode::Normal;
+        self.model_puller
+            .ensure_available(&self.ollama_client, &self.capabilities, model, may_pull)
+            .await
+    }
 }
 
 // Builder for creating AppState instances with a fluent API.
@@ -91,6 +349,66 @@ pub struct AppStateBuilder {
     ollama_client: Option<OllamaClient>,
     // Optional security client to be set before building
     security_client: Option<SecurityClient>,
+    // Model management settings, defaulted when not set
+    models_config: Option<config::ModelsConfig>,
+    // Administrative API settings, defaulted (disabled) when not set
+    admin_config: Option<config::AdminConfig>,
+    // Maintenance settings, defaulted (normal mode) when not set
+    maintenance_config: Option<config::MaintenanceConfig>,
+    // Kill switch settings, defaulted when not set
+    kill_switch_config: Option<config::KillSwitchConfig>,
+    // Duplicate-request suppression settings, defaulted (disabled) when not set
+    dedup_config: Option<config::DedupConfig>,
+    // Idempotency-Key settings, defaulted (enabled) when not set
+    idempotency_config: Option<config::IdempotencyConfig>,
+    // Manifest signing service, defaulted (disabled) when not set
+    provenance: Option<ProvenanceService>,
+    // Response post-processing settings, defaulted (no rewrites) when not set
+    postprocess_config: Option<config::PostProcessConfig>,
+    // Summarization endpoint settings, defaulted when not set
+    summarize_config: Option<config::SummarizeConfig>,
+    // Batch generation endpoint settings, defaulted when not set
+    batch_config: Option<config::BatchConfig>,
+    // Generation job settings, defaulted when not set
+    jobs_config: Option<config::JobsConfig>,
+    // Scheduled prompt settings, defaulted (no schedules) when not set
+    scheduler: Option<Scheduler>,
+    // Prompt template settings, defaulted when not set
+    templates_config: Option<config::TemplatesConfig>,
+    // Health check settings, defaulted when not set
+    health_config: Option<config::HealthConfig>,
+    // Request metrics settings, defaulted when not set
+    metrics_config: Option<config::MetricsConfig>,
+    // Context window check settings, defaulted when not set
+    context_config: Option<config::ContextConfig>,
+    // Model capability cache settings, defaulted when not set
+    capabilities_config: Option<config::CapabilitiesConfig>,
+    // Automatic model pull settings, defaulted (disabled) when not set
+    auto_pull_config: Option<config::AutoPullConfig>,
+    // Client authentication settings, defaulted (disabled) when not set
+    client_auth_config: Option<config::ClientAuthConfig>,
+    // Usage report settings, defaulted (no privacy protections) when not set
+    usage_config: Option<config::UsageConfig>,
+    // Transcript settings, defaulted (disabled) when not set
+    transcripts: Option<Transcripts>,
+    // State files of the local stores, defaulted (none) when not set
+    store_files: Option<StoreFiles>,
+    // Rate limit settings, defaulted (disabled) when not set
+    rate_limit_config: Option<config::RateLimitConfig>,
+    // Access policies, defaulted (unrestricted) when not set
+    access_windows_config: Option<config::AccessWindowsConfig>,
+    // Generation concurrency settings, defaulted (unlimited) when not set
+    concurrency_config: Option<config::ConcurrencyConfig>,
+    // Timeout settings, defaulted (no timeouts) when not set
+    timeout_config: Option<config::TimeoutConfig>,
+    // Feature flag definitions, defaulted (no flags) when not set
+    feature_flags_config: Option<Vec<config::FeatureFlagConfig>>,
+    // Description of the enabled subsystems to be set before building
+    proxy_capabilities: Option<ProxyCapabilities>,
+    // Compatibility shim settings, defaulted (enabled) when not set
+    compat_config: Option<config::CompatConfig>,
+    // Header passthrough settings, defaulted (no headers) when not set
+    passthrough_config: Option<config::PassthroughConfig>,
 }
 
 impl AppStateBuilder {
@@ -106,6 +424,195 @@ impl AppStateBuilder {
         self
     }
 
+    // Sets the model management settings for the application state.
+    pub fn with_models_config(mut self, models_config: config::ModelsConfig) -> Self {
+        self.models_config = Some(models_config);
+        self
+    }
+
+    // Sets the administrative API settings for the application state.
+    pub fn with_admin_config(mut self, admin_config: config::AdminConfig) -> Self {
+        self.admin_config = Some(admin_config);
+        self
+    }
+
+    // Sets the maintenance settings for the application state.
+    pub fn with_maintenance_config(mut self, maintenance_config: config::MaintenanceConfig) -> Self {
+        self.maintenance_config = Some(maintenance_config);
+        self
+    }
+
+    // Sets the kill switch settings for the application state.
+    pub fn with_kill_switch_config(mut self, kill_switch_config: config::KillSwitchConfig) -> Self {
+        self.kill_switch_config = Some(kill_switch_config);
+        self
+    }
+
+    // Sets the duplicate-request suppression settings for the application state.
+    pub fn with_dedup_config(mut self, dedup_config: config::DedupConfig) -> Self {
+        self.dedup_config = Some(dedup_config);
+        self
+    }
+
+    // Sets the Idempotency-Key settings for the application state.
+    pub fn with_idempotency_config(mut self, idempotency_config: config::IdempotencyConfig) -> Self {
+        self.idempotency_config = Some(idempotency_config);
+        self
+    }
+
+    // Sets the manifest signing service for the application state.
+    pub fn with_provenance(mut self, provenance: ProvenanceService) -> Self {
+        self.provenance = Some(provenance);
+        self
+    }
+
+    // Sets the response post-processing settings for the application state.
+    pub fn with_postprocess_config(mut self, postprocess_config: config::PostProcessConfig) -> Self {
+        self.postprocess_config = Some(postprocess_config);
+        self
+    }
+
+    // Sets the summarization endpoint settings for the application state.
+    pub fn with_summarize_config(mut self, summarize_config: config::SummarizeConfig) -> Self {
+        self.summarize_config = Some(summarize_config);
+        self
+    }
+
+    // Sets the batch generation endpoint settings for the application state.
+    pub fn with_batch_config(mut self, batch_config: config::BatchConfig) -> Self {
+        self.batch_config = Some(batch_config);
+        self
+    }
+
+    // Sets the generation job settings for the application state.
+    pub fn with_jobs_config(mut self, jobs_config: config::JobsConfig) -> Self {
+        self.jobs_config = Some(jobs_config);
+        self
+    }
+
+    // Sets the scheduled prompt registry for the application state.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    // Sets the prompt template settings for the application state.
+    pub fn with_templates_config(mut self, templates_config: config::TemplatesConfig) -> Self {
+        self.templates_config = Some(templates_config);
+        self
+    }
+
+    // Sets the health check settings for the application state.
+    pub fn with_health_config(mut self, health_config: config::HealthConfig) -> Self {
+        self.health_config = Some(health_config);
+        self
+    }
+
+    // Sets the request metrics settings for the application state.
+    pub fn with_metrics_config(mut self, metrics_config: config::MetricsConfig) -> Self {
+        self.metrics_config = Some(metrics_config);
+        self
+    }
+
+    // Sets the context window check settings for the application state.
+    pub fn with_context_config(mut self, context_config: config::ContextConfig) -> Self {
+        self.context_config = Some(context_config);
+        self
+    }
+
+    // Sets the model capability cache settings for the application state.
+    pub fn with_capabilities_config(
+        mut self,
+        capabilities_config: config::CapabilitiesConfig,
+    ) -> Self {
+        self.capabilities_config = Some(capabilities_config);
+        self
+    }
+
+    // Sets the automatic model pull settings for the application state.
+    pub fn with_auto_pull_config(mut self, auto_pull_config: config::AutoPullConfig) -> Self {
+        self.auto_pull_config = Some(auto_pull_config);
+        self
+    }
+
+    // Sets the client authentication settings for the application state.
+    pub fn with_client_auth_config(mut self, client_auth_config: config::ClientAuthConfig) -> Self {
+        self.client_auth_config = Some(client_auth_config);
+        self
+    }
+
+    // Sets the usage report settings for the application state.
+    pub fn with_usage_config(mut self, usage_config: config::UsageConfig) -> Self {
+        self.usage_config = Some(usage_config);
+        self
+    }
+
+    // Sets the transcripts for the application state, if enabled.
+    pub fn with_transcripts(mut self, transcripts: Option<Transcripts>) -> Self {
+        self.transcripts = transcripts;
+        self
+    }
+
+    // Sets the state files of the local stores for the application state.
+    pub fn with_store_files(mut self, store_files: StoreFiles) -> Self {
+        self.store_files = Some(store_files);
+        self
+    }
+
+    // Sets the rate limit settings for the application state.
+    pub fn with_rate_limit_config(mut self, rate_limit_config: config::RateLimitConfig) -> Self {
+        self.rate_limit_config = Some(rate_limit_config);
+        self
+    }
+
+    // Sets the time-based access policies for the application state.
+    pub fn with_access_windows_config(
+        mut self,
+        access_windows_config: config::AccessWindowsConfig,
+    ) -> Self {
+        self.access_windows_config = Some(access_windows_config);
+        self
+    }
+
+    // Sets the generation concurrency settings for the application state.
+    pub fn with_concurrency_config(mut self, concurrency_config: config::ConcurrencyConfig) -> Self {
+        self.concurrency_config = Some(concurrency_config);
+        self
+    }
+
+    // Sets the request timeout settings for the application state.
+    pub fn with_timeout_config(mut self, timeout_config: config::TimeoutConfig) -> Self {
+        self.timeout_config = Some(timeout_config);
+        self
+    }
+
+    // Sets the feature flag definitions for the application state.
+    pub fn with_feature_flags_config(
+        mut self,
+        feature_flags_config: Vec<config::FeatureFlagConfig>,
+    ) -> Self {
+        self.feature_flags_config = Some(feature_flags_config);
+        self
+    }
+
+    // Sets the description of the enabled subsystems for the application state.
+    pub fn with_proxy_capabilities(mut self, proxy_capabilities: ProxyCapabilities) -> Self {
+        self.proxy_capabilities = Some(proxy_capabilities);
+        self
+    }
+
+    // Sets the compatibility shim settings for the application state.
+    pub fn with_compat_config(mut self, compat_config: config::CompatConfig) -> Self {
+        self.compat_config = Some(compat_config);
+        self
+    }
+
+    // Sets the header passthrough settings for the application state.
+    pub fn with_passthrough_config(mut self, passthrough_config: config::PassthroughConfig) -> Self {
+        self.passthrough_config = Some(passthrough_config);
+        self
+    }
+
     // Builds the AppState from the configured components.
     //
     // # Errors
@@ -113,11 +620,100 @@ impl AppStateBuilder {
     // Returns an error if any required component is missing.
     pub fn build(self) -> Result<AppState, &'static str> {
         let ollama_client = self.ollama_client.ok_or("OllamaClient is required")?;
-        let security_client = self.security_client.ok_or("SecurityClient is required")?;
+        let mut security_client = self.security_client.ok_or("SecurityClient is required")?;
+        let proxy_capabilities = self
+            .proxy_capabilities
+            .ok_or("ProxyCapabilities is required")?;
 
+        let client_auth_config = self.client_auth_config.unwrap_or_default();
+        let metrics_config = self.metrics_config.unwrap_or_default();
+        let usage = UsageLedger::new(&self.usage_config.unwrap_or_default());
+        security_client.with_usage_ledger(usage.clone());
+        let transcripts = self.transcripts;
+        if let Some(transcripts) = &transcripts {
+            security_client.with_transcripts(transcripts.clone());
+        }
+
+        let models_config = self.models_config.unwrap_or_default();
+        let confirmations =
+            ConfirmationStore::new(Duration::from_secs(models_config.confirmation_ttl_secs));
+
+        let health = HealthChecker::new(
+            &self.health_config.unwrap_or_default(),
+            (!ollama_client.is_mock()).then(|| ollama_client.base_url()),
+            security_client.base_url(),
+        )
+        .map_err(|e| {
+            error!("Failed to create the health check client: {}", e);
+            "Failed to create the health check client"
+        })?
+        .with_ollama_compat(ollama_client.compat().clone());
+
+        let capabilities = CapabilityCache::new(&self.capabilities_config.unwrap_or_default());
+        let context_windows = ContextWindows::new(
+            &self.context_config.unwrap_or_default(),
+            capabilities.clone(),
+        );
+
+        let maintenance = MaintenanceController::new(self.maintenance_config.unwrap_or_default());
+
+        let dedup = DedupStore::new(
+            &self.dedup_config.unwrap_or_default(),
+            &self.idempotency_config.unwrap_or_default(),
+        );
+
+        let summarize_config = self.summarize_config.unwrap_or_default();
         Ok(AppState {
             ollama_client,
             security_client,
+            models_config,
+            confirmations,
+            admin_config: self.admin_config.unwrap_or_default(),
+            maintenance,
+            kill_switch: KillSwitch::new(&self.kill_switch_config.unwrap_or_default()),
+            dedup,
+            provenance: self
+                .provenance
+                .unwrap_or_else(ProvenanceService::disabled),
+            postprocess: ResponsePostProcessor::new(&self.postprocess_config.unwrap_or_default())
+                .with_system_prompts(&[summarize_config.prompt.as_str()]),
+            summarize_config,
+            batch_config: self.batch_config.unwrap_or_default(),
+            jobs: JobStore::new(&self.jobs_config.unwrap_or_default()),
+            scheduler: self.scheduler.ok_or("Scheduler is required")?,
+            templates: TemplateStore::new(&self.templates_config.unwrap_or_default()),
+            health,
+            request_labeler: RequestLabeler::new(&metrics_config),
+            context_windows,
+            capabilities,
+            model_puller: ModelPuller::new(&self.auto_pull_config.unwrap_or_default()),
+            client_keys: ClientKeys::new(&client_auth_config, &metrics_config.tenant_header),
+            jwt: JwtValidator::new(&client_auth_config.jwt)
+                .map_err(|_| "Failed to create the JWKS HTTP client")?,
+            access_tokens: AccessTokens::new(&client_auth_config.access_tokens),
+            auth_lockout: AuthLockout::new(
+                &client_auth_config.lockout,
+                &client_auth_config.trusted_proxy.addresses,
+            ),
+            usage,
+            transcripts,
+            store_files: self.store_files.unwrap_or_default(),
+            rate_limiter: RateLimiter::new(&self.rate_limit_config.unwrap_or_default()),
+            access_windows: AccessWindows::new(
+                &self.access_windows_config.unwrap_or_default(),
+                &metrics_config.tenant_header,
+            ),
+            generation_limiter: GenerationLimiter::new(
+                &self.concurrency_config.unwrap_or_default(),
+            ),
+            timeout_config: self.timeout_config.unwrap_or_default(),
+            feature_flags: FeatureFlags::new(&self.feature_flags_config.unwrap_or_default()),
+            proxy_capabilities: Arc::new(proxy_capabilities),
+            compat_config: self.compat_config.unwrap_or_default(),
+            header_passthrough: HeaderPassthrough::new(
+                &self.passthrough_config.unwrap_or_default(),
+            ),
+            connections: ConnectionRegistry::default(),
         })
     }
 }
@@ -129,26 +725,141 @@ impl AppStateBuilder {
 // Application entry point that initializes and runs the server.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration
-    let config = config::load_config("config.yaml")?;
+    // Find and load the configuration, `--config` taking precedence over the search path
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let config_file = config::find_config_file(take_config_arg(&mut args)?.as_deref())?;
+    let mut config = config::load_config(config_file.as_ref().map(|(path, _)| path.as_str()))?;
+    let policy_file = config_file
+        .as_ref()
+        .map_or(config::CONFIG_FILE_NAME, |(path, _)| path.as_str());
+
+    // Resolve the state file encryption keys referenced in Vault
+    let vault = VaultClient::new(&config.vault)?;
+    let mut encryption_config = config.encryption.clone();
+    for key in &mut encryption_config.keys {
+        key.key = vault.resolve(&key.key).await?;
+    }
+
+    // Resolve the transcript HMAC key referenced in Vault
+    config.transcripts.hmac_key = vault.resolve(&config.transcripts.hmac_key).await?;
+
+    // Install the state file encryption keys before any state file is touched
+    encryption::init(&encryption_config)?;
+
+    // Run store maintenance instead of the server when requested
+    if args.first().map(String::as_str) == Some("store") {
+        return store::run_cli(&StoreFiles::new(policy_file, &config), &args[1..]);
+    }
+
+    // Run the conformance suite against a running proxy when requested
+    if args.first().map(String::as_str) == Some("conformance") {
+        return conformance::run_cli(&config, &args[1..]).await;
+    }
+
+    // Verify the transcript hash chains when requested
+    if args.first().map(String::as_str) == Some("transcripts") {
+        return transcripts::run_cli(&config.transcripts, &args[1..]);
+    }
+
+    // Benchmark the streaming assessment strategies when requested
+    if args.first().map(String::as_str) == Some("bench-scan") {
+        return bench::run_cli(&config, &args[1..]).await;
+    }
+
+    // Evaluate the guardrails against a labeled prompt dataset when requested
+    if args.first().map(String::as_str) == Some("evaluate") {
+        let panw_api_key = vault.watch(&config.security.api_key).await?;
+        let state = build_app_state(&config, policy_file, panw_api_key)?;
+        return evaluate::run_cli(&state.security_client, &args[1..]).await;
+    }
 
     // Initialize logging
     setup_logging(&config.server.debug_level);
+    match &config_file {
+        Some((path, config::ConfigSource::Argument)) => {
+            info!("Using configuration file {} given with --config", path)
+        }
+        Some((path, config::ConfigSource::SearchPath)) => {
+            info!("Using configuration file {} found in the search path", path)
+        }
+        None => info!(
+            "No configuration file found in {}; using environment variables only",
+            config::config_search_path()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+
+    // Export security events from here on
+    security_events::init(&config.security_events)?;
+
+    // Notify alerts from here on
+    notifications::init(&config)?;
+
+    // Migrate the state files to the current schema before any store loads them
+    for (store, path, from, to) in StoreFiles::new(policy_file, &config).migrate()? {
+        info!("Migrated {} store {} from schema version {} to {}", store, path, from, to);
+    }
 
     // Create application state
-    let state = build_app_state(
-        config.ollama.base_url,
-        config.security
-    )?;
+    let panw_api_key = vault.watch(&config.security.api_key).await?;
+    let state = build_app_state(&config, policy_file, panw_api_key)?;
     info!("Application state initialized successfully");
+    state.proxy_capabilities.log_banner();
+
+    // Run scheduled prompts in the background
+    scheduler::spawn(state.clone());
+
+    // Probe the PANW security channel in the background
+    health::spawn_security_probe(state.health.clone());
+
+    // Probe the Ollama servers in the background to keep the rotation healthy
+    ollama::spawn_backend_probes(
+        state.ollama_client.clone(),
+        Duration::from_secs(config.ollama.probe_interval_secs),
+    );
+
+    // Check the Ollama version to adjust requests to it
+    ollama::spawn_version_checks(
+        state.ollama_client.clone(),
+        Duration::from_secs(config.ollama.version_check_interval_secs),
+    );
+
+    // Prune transcript sessions past their retention period in the background
+    if let Some(transcripts) = &state.transcripts {
+        transcripts::spawn_prune(transcripts.clone(), config.transcripts.retention_days);
+    }
+
+    // Keep the health checker to flip readiness on shutdown
+    let health = state.health.clone();
+
+    // Keep the connection registry to register accepted connections
+    let connections = state.connections.clone();
 
     // Build router with all the Ollama API endpoints
     let app = build_router(state);
     info!("Router configured with all endpoints");
 
+    // Read the TLS key from Vault when referenced, then keep the secrets up to date
+    let tls_key = match &config.server.tls_key_file {
+        Some(key_file) if vault::is_reference(key_file) => Some(vault.watch(key_file).await?),
+        _ => None,
+    };
+    vault::spawn_refresh(vault);
+
     // Start the server
     info!("Starting server with configuration: {:?}", config.server);
-    start_server(app, &config.server).await?;
+    let shutdown_delay = Duration::from_secs(config.health.shutdown_delay_secs);
+    start_server(
+        app,
+        &config.server,
+        tls_key,
+        connections,
+        shutdown_signal(health, shutdown_delay),
+    )
+    .await?;
 
     Ok(())
 }
@@ -187,6 +898,35 @@ fn setup_logging(debug_level_str: &str) {
     );
 }
 
+/// Removes the configuration file option from the command line arguments.
+///
+/// Accepts `--config <path>` and `--config=<path>` anywhere in the arguments.
+///
+/// # Returns
+///
+/// * `Ok(Some(path))` - The configuration file given
+/// * `Ok(None)` - If the option is absent
+/// * `Err` - If the option lacks its path
+fn take_config_arg(args: &mut Vec<String>) -> Result<Option<String>, config::ConfigError> {
+    let Some(index) = args
+        .iter()
+        .position(|arg| arg == "--config" || arg.starts_with("--config="))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    let path = match arg.strip_prefix("--config=") {
+        Some(path) => Some(path.to_string()),
+        None => (index < args.len()).then(|| args.remove(index)),
+    };
+    match path.filter(|path| !path.is_empty()) {
+        Some(path) => Ok(Some(path)),
+        None => Err(config::ConfigError::ValidationError(
+            "--config requires the path of a configuration file".into(),
+        )),
+    }
+}
+
 /// Builds the application state with configured clients.
 ///
 /// Creates and initializes the application state containing clients
@@ -195,36 +935,140 @@ fn setup_logging(debug_level_str: &str) {
 /// # Arguments
 ///
 /// * `config` - The application configuration
+/// * `config_file` - Path of the configuration file, backed up with the stores
+/// * `panw_api_key` - PANW API key, kept up to date when it is read from Vault
 ///
 /// # Returns
 ///
 /// * `Ok(AppState)` - Initialized application state
 /// * `Err` - If client creation or initialization fails
 fn build_app_state(
-    ollama_base_url: String,
-    security_config: config::SecurityConfig
+    config: &config::Config,
+    config_file: &str,
+    panw_api_key: SharedSecret,
 ) -> Result<AppState, Box<dyn std::error::Error>> {
     info!("Building application state with configured clients");
 
+    // Load the demo scenario scripting latencies
+    let scenario = if config.demo.scenario_file.is_empty() {
+        None
+    } else {
+        let scenario = Scenario::load(&config.demo.scenario_file)?;
+        info!("Loaded demo scenario from {}", config.demo.scenario_file);
+        Some(Arc::new(scenario))
+    };
+
     // Create Ollama client
-    let ollama_client = OllamaClient::new(ollama_base_url.clone());
-    info!(
-        "Created Ollama client with base URL: {}",
-        ollama_base_url
-    );
+    let ollama_client = match config.ollama.provider {
+        config::OllamaProvider::Http => {
+            info!(
+                "Created Ollama client with base URL: {}",
+                config.ollama.base_url
+            );
+            if !config.ollama.backends.is_empty() {
+                info!(
+                    "Spreading requests over further Ollama servers: {}",
+                    config.ollama.backends.join(", ")
+                );
+            }
+            for route in &config.ollama.model_routes {
+                info!(
+                    "Routing requests for {} to Ollama servers: {}",
+                    route.model,
+                    route.backends.join(", ")
+                );
+            }
+            let headers = ollama::upstream_headers(&config.ollama)?;
+            if !headers.is_empty() {
+                info!(
+                    "Sending {} configured headers with requests to Ollama",
+                    headers.len()
+                );
+            }
+            OllamaClient::new(
+                config.ollama.base_url.clone(),
+                &config.ollama.backends,
+                &config.ollama.model_routes,
+            )
+            .with_http_client(&config.ollama.http_client, headers)?
+            .with_retry(&config.ollama.retry)
+        }
+        config::OllamaProvider::Mock => {
+            info!(
+                "Created mock Ollama backend with seed {} serving {}",
+                config.ollama.mock.seed,
+                config.ollama.mock.models.join(", ")
+            );
+            OllamaClient::mock(MockOllama::new(
+                config.ollama.mock.clone(),
+                scenario.clone(),
+            ))
+        }
+    };
 
     // Create security client
-    let security_client = SecurityClient::new(security_config);
+    let mut security_client = SecurityClient::new(config.security.clone());
+    security_client.with_api_key(panw_api_key);
+    security_client.with_http_client(&config.security.http_client)?;
+    security_client.with_brandings(TenantBrandings::new(&config.branding));
+    if let Some(scenario) = scenario {
+        security_client.with_scenario(scenario);
+    }
+    if config.security.uses(config::SecurityProvider::LlamaGuard) {
+        security_client.with_llama_guard(LlamaGuardClient::new(
+            &config.security.llama_guard,
+            ollama_client.clone(),
+        ));
+    }
 
     info!(
         "Created security client with base URL: {}",
         security_client.base_url()
     );
 
+    // Create manifest signing service
+    let provenance = ProvenanceService::new(&config.provenance)?;
+    let scheduler = Scheduler::new(&config.scheduler)?;
+    let transcripts = config
+        .transcripts
+        .enabled
+        .then(|| Transcripts::new(&config.transcripts))
+        .transpose()?;
+
     // Build the application state using the builder pattern
     let state = AppState::builder()
         .with_ollama_client(ollama_client)
         .with_security_client(security_client)
+        .with_models_config(config.models.clone())
+        .with_admin_config(config.admin.clone())
+        .with_maintenance_config(config.maintenance.clone())
+        .with_kill_switch_config(config.kill_switch.clone())
+        .with_dedup_config(config.dedup.clone())
+        .with_idempotency_config(config.idempotency.clone())
+        .with_provenance(provenance)
+        .with_postprocess_config(config.postprocess.clone())
+        .with_summarize_config(config.summarize.clone())
+        .with_batch_config(config.batch.clone())
+        .with_jobs_config(config.jobs.clone())
+        .with_scheduler(scheduler)
+        .with_templates_config(config.templates.clone())
+        .with_health_config(config.health.clone())
+        .with_metrics_config(config.metrics.clone())
+        .with_context_config(config.context.clone())
+        .with_capabilities_config(config.capabilities.clone())
+        .with_auto_pull_config(config.auto_pull.clone())
+        .with_client_auth_config(config.client_auth.clone())
+        .with_usage_config(config.usage.clone())
+        .with_transcripts(transcripts)
+        .with_store_files(StoreFiles::new(config_file, config))
+        .with_rate_limit_config(config.rate_limit.clone())
+        .with_access_windows_config(config.access_windows.clone())
+        .with_concurrency_config(config.concurrency.clone())
+        .with_timeout_config(config.timeouts.clone())
+        .with_feature_flags_config(config.feature_flags.clone())
+        .with_proxy_capabilities(ProxyCapabilities::describe(config))
+        .with_compat_config(config.compat.clone())
+        .with_passthrough_config(config.passthrough.clone())
         .build()?;
 
     Ok(state)
@@ -247,8 +1091,50 @@ fn build_router(state: AppState) -> Router {
     // Group endpoints by functionality
     let generation_routes = Router::new()
         .route("/api/generate", post(generate::handle_generate))
+        .route("/api/generate/batch", post(batch::handle_generate_batch))
         .route("/api/chat", post(chat::handle_chat))
-        .route("/api/embeddings", post(embeddings::handle_embeddings));
+        .route("/api/summarize", post(summarize::handle_summarize))
+        .route("/v1/completions", post(openai::handle_completions))
+        .route(
+            "/api/templates/{name}/generate",
+            post(handlers::templates::handle_run_template),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            compat::apply_shims,
+        ))
+        .route_layer(middleware::from_fn(branding::serve_block_page))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            provenance::record_provenance,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            concurrency::limit_generations,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            dedup::dedup_guard,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            verdict_debug::attach_verdict_debug,
+        ))
+        .route("/api/embeddings", post(embeddings::handle_embeddings))
+        .route("/v1/embeddings", post(openai::handle_embeddings));
+
+    let job_routes = Router::new()
+        .route("/api/jobs", post(handlers::jobs::handle_create_job))
+        .route("/api/jobs/{id}", get(handlers::jobs::handle_get_job));
+
+    let template_routes = Router::new()
+        .route("/api/templates", get(handlers::templates::handle_list_templates))
+        .route(
+            "/api/templates/{name}",
+            get(handlers::templates::handle_get_template)
+                .put(handlers::templates::handle_put_template)
+                .delete(handlers::templates::handle_delete_template),
+        );
 
     let model_routes = Router::new()
         .route("/api/tags", get(models::handle_list_models))
@@ -257,48 +1143,285 @@ fn build_router(state: AppState) -> Router {
         .route("/api/copy", post(models::handle_copy_model))
         .route("/api/delete", post(models::handle_delete_model))
         .route("/api/pull", post(models::handle_pull_model))
-        .route("/api/push", post(models::handle_push_model));
+        .route("/api/push", post(models::handle_push_model))
+        .route(
+            "/api/capabilities",
+            get(handlers::capabilities::handle_capabilities),
+        );
 
-    let utility_routes = Router::new().route("/api/version", get(version::handle_version));
+    let utility_routes = Router::new()
+        .route("/api/version", get(version::handle_version))
+        .route("/healthz", get(handlers::health::handle_healthz))
+        .route("/livez", get(handlers::health::handle_livez))
+        .route("/readyz", get(handlers::health::handle_readyz))
+        .route("/metrics", get(handlers::metrics::handle_metrics))
+        .route(
+            "/api/provenance/{request_id}",
+            get(handlers::provenance::handle_get_manifest),
+        )
+        .route("/auth/token", post(handlers::auth::handle_issue_token))
+        .route("/api/me/usage", get(handlers::me::handle_my_usage))
+        .route("/api/me/violations", get(handlers::me::handle_my_violations))
+        .route(
+            "/api/proxy/capabilities",
+            get(handlers::proxy::handle_capabilities),
+        );
+
+    let admin_routes = Router::new()
+        .route(
+            "/admin/maintenance",
+            get(admin::handle_get_maintenance)
+                .put(admin::handle_set_maintenance)
+                .delete(admin::handle_clear_maintenance),
+        )
+        .route(
+            "/admin/kill-switch",
+            get(admin::handle_get_kill_switch)
+                .put(admin::handle_activate_kill_switch)
+                .delete(admin::handle_lift_kill_switch),
+        )
+        .route("/admin/flags", get(admin::handle_list_flags))
+        .route(
+            "/admin/flags/{name}",
+            put(admin::handle_set_flag).delete(admin::handle_clear_flag),
+        )
+        .route("/admin/schedules", get(admin::handle_list_schedules))
+        .route("/admin/usage", get(admin::handle_usage_report))
+        .route("/admin/stores", get(admin::handle_verify_stores))
+        .route("/admin/backup", get(admin::handle_backup))
+        .route("/admin/cache/{hash}", delete(admin::handle_invalidate_cache))
+        .route("/admin/panw-responses", get(admin::handle_list_panw_responses))
+        .route("/admin/connections", get(admin::handle_list_connections))
+        .route(
+            "/admin/connections/{id}",
+            delete(admin::handle_terminate_connection),
+        )
+        .route(
+            "/admin/scan-reports/{report_id}",
+            get(admin::handle_get_scan_report),
+        )
+        .route(
+            "/admin/scan-results/{scan_id}",
+            get(admin::handle_get_scan_result),
+        )
+        .route(
+            "/admin/keys",
+            get(admin::handle_list_keys).post(admin::handle_create_key),
+        )
+        .route("/admin/keys/{id}", delete(admin::handle_revoke_key))
+        .route("/admin/keys/{id}/rotate", post(admin::handle_rotate_key))
+        .route(
+            "/admin/schedules/{name}",
+            put(admin::handle_put_schedule).delete(admin::handle_delete_schedule),
+        );
 
     // Combine all routes
     Router::new()
         .merge(generation_routes)
+        .merge(job_routes)
+        .merge(template_routes)
         .merge(model_routes)
         .merge(utility_routes)
+        .merge(admin_routes)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            passthrough::pass_headers,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            timeout::enforce_timeouts,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), usage::record_usage))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::enforce_rate_limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            access_windows::enforce_access_windows,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            client_auth::apply_app_user,
+        ))
+        .layer(middleware::from_fn(key_scopes::enforce_scopes))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            client_auth::require_client_key,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_lockout::enforce_lockout,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance::maintenance_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            kill_switch::kill_switch_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_metrics::record_request_metrics,
+        ))
+        .layer(middleware::from_fn(transaction::assign_transaction_id))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            connections::record_connection_request,
+        ))
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
 
 /// Starts the HTTP server with the configured router.
 ///
-/// Binds to the configured address and port and starts serving requests.
+/// Binds to the configured address and port and starts serving requests,
+/// over HTTPS when a TLS certificate and key are configured.
 ///
 /// # Arguments
 ///
 /// * `app` - The configured Axum router
 /// * `server_config` - Server configuration settings
+/// * `tls_key` - TLS private key read from Vault instead of the key file, if any
+/// * `connections` - Registry of the accepted connections
+/// * `shutdown` - Future completing when the server should shut down gracefully
 ///
 /// # Returns
 ///
 /// * `Ok(())` - If the server starts and runs successfully
-/// * `Err` - If binding fails or the server encounters an error
+/// * `Err` - If binding fails, the certificate cannot be loaded or the server encounters an error
 async fn start_server(
     app: Router,
     server_config: &config::ServerConfig,
+    tls_key: Option<SharedSecret>,
+    connections: ConnectionRegistry,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let addr = SocketAddr::new(IpAddr::from_str(&server_config.host)?, server_config.port);
 
+    if let (Some(cert_file), Some(key_file)) =
+        (&server_config.tls_cert_file, &server_config.tls_key_file)
+    {
+        let mut files = TlsFiles::new(
+            cert_file,
+            key_file,
+            server_config.tls_client_ca_file.as_ref(),
+        );
+        if let Some(tls_key) = tls_key {
+            files = files.with_key_secret(tls_key);
+        }
+        return start_tls_server(app, addr, files, server_config, connections, shutdown).await;
+    }
+
     info!("Binding server to {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await?;
+    let listener = TrackedListener::new(listener, connections);
     info!("Server started successfully on {}", addr);
 
     info!("Waiting for incoming connections...");
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
+    axum::serve(listener, TrackedMakeService::new(app))
+    .with_graceful_shutdown(shutdown)
     .await?;
 
+    info!("Server shut down");
     Ok(())
 }
+
+/// Starts the HTTPS server and reloads its certificate when the files change.
+///
+/// Clients must present a certificate signed by the configured CAs when client
+/// certificate authentication is enabled.
+///
+/// # Arguments
+///
+/// * `app` - The configured Axum router
+/// * `addr` - Address to bind to
+/// * `files` - Certificate and key files to serve
+/// * `server_config` - Server configuration settings
+/// * `connections` - Registry of the accepted connections
+/// * `shutdown` - Future completing when the server should shut down gracefully
+async fn start_tls_server(
+    app: Router,
+    addr: SocketAddr,
+    files: TlsFiles,
+    server_config: &config::ServerConfig,
+    connections: ConnectionRegistry,
+    shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tls_config = files.load()?;
+    tls::spawn_reload(
+        tls_config.clone(),
+        files,
+        Duration::from_secs(server_config.tls_reload_interval_secs),
+    );
+
+    // Drain in-flight requests once the shutdown signal completes
+    let handle = axum_server::Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        shutdown_handle.graceful_shutdown(None);
+    });
+
+    info!("Server starting with TLS on {}", addr);
+    info!("Waiting for incoming connections...");
+    axum_server::bind(addr)
+        .acceptor(TrackingAcceptor::new(
+            ClientIdentityAcceptor::new(tls_config),
+            connections,
+        ))
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await?;
+
+    info!("Server shut down");
+    Ok(())
+}
+
+/// Waits for a shutdown signal and marks the proxy as not ready.
+///
+/// Completes after the configured delay, giving orchestrators time to notice
+/// the failing readiness check and stop routing traffic before the server
+/// stops accepting connections and drains in-flight requests.
+///
+/// # Arguments
+///
+/// * `health` - Health checker whose readiness flips on shutdown
+/// * `delay` - How long to keep serving after readiness has flipped
+async fn shutdown_signal(health: HealthChecker, delay: Duration) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!(
+        "Shutdown signal received, reporting not ready for {}s before draining",
+        delay.as_secs()
+    );
+    health.begin_shutdown();
+    tokio::time::sleep(delay).await;
+}
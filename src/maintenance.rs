@@ -0,0 +1,225 @@
+// Read-only and maintenance mode handling.
+//
+// This module decides which operating mode is in effect for incoming requests
+// and rejects the requests that the active mode does not allow.
+//
+// # Mode Resolution
+//
+// The effective mode is resolved in order of precedence:
+// 1. An override set at runtime through the admin API
+// 2. A scheduled window from the configuration covering the current time
+// 3. The configured default mode
+//
+// Only new requests are rejected; streams that were already in flight when a
+// restricted mode became active are allowed to drain normally.
+use crate::config::{MaintenanceConfig, OperatingMode};
+use crate::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use tracing::{info, warn};
+
+// Default message returned while the proxy is in maintenance mode.
+const DEFAULT_MAINTENANCE_MESSAGE: &str =
+    "The service is undergoing maintenance. Please try again later.";
+
+// Default message returned for rejected requests in read-only mode.
+const DEFAULT_READ_ONLY_MESSAGE: &str =
+    "The service is in read-only mode. Model management is temporarily unavailable.";
+
+// Model management endpoints that modify the models installed in Ollama.
+const MODEL_WRITE_PATHS: [&str; 5] = [
+    "/api/create",
+    "/api/copy",
+    "/api/delete",
+    "/api/pull",
+    "/api/push",
+];
+
+// Where the currently effective mode comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModeSource {
+    Override,
+    Schedule,
+    Config,
+}
+
+// The operating mode in effect at a given point in time.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveMode {
+    // Mode that applies to new requests
+    pub mode: OperatingMode,
+
+    // Where the mode comes from
+    pub source: ModeSource,
+
+    // Message returned to rejected clients
+    pub message: String,
+
+    // When the mode is expected to end, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+// A mode set at runtime through the admin API.
+#[derive(Debug, Clone)]
+struct ModeOverride {
+    mode: OperatingMode,
+    message: Option<String>,
+}
+
+// Resolves the effective operating mode from configuration and runtime overrides.
+//
+// Cloning the controller is cheap and all clones share the same override.
+#[derive(Clone)]
+pub struct MaintenanceController {
+    // Configured default mode and scheduled windows
+    config: MaintenanceConfig,
+
+    // Mode set through the admin API, taking precedence over the configuration
+    mode_override: Arc<RwLock<Option<ModeOverride>>>,
+}
+
+impl MaintenanceController {
+    // Creates a controller for the given maintenance configuration.
+    pub fn new(config: MaintenanceConfig) -> Self {
+        Self {
+            config,
+            mode_override: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    // Sets a runtime override for the operating mode.
+    pub fn set_override(&self, mode: OperatingMode, message: Option<String>) {
+        info!("Operating mode overridden to {:?}", mode);
+        *self.mode_override.write().unwrap_or_else(|e| e.into_inner()) =
+            Some(ModeOverride { mode, message });
+    }
+
+    // Clears the runtime override, returning to the configured schedule.
+    pub fn clear_override(&self) {
+        info!("Operating mode override cleared");
+        *self.mode_override.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    // Returns the operating mode in effect right now.
+    pub fn effective_mode(&self) -> EffectiveMode {
+        self.effective_mode_at(Utc::now())
+    }
+
+    // Returns the operating mode in effect at the given time.
+    fn effective_mode_at(&self, now: DateTime<Utc>) -> EffectiveMode {
+        let mode_override = self
+            .mode_override
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(mode_override) = mode_override {
+            return EffectiveMode {
+                mode: mode_override.mode,
+                source: ModeSource::Override,
+                message: self.message_for(mode_override.mode, mode_override.message.as_deref()),
+                until: None,
+            };
+        }
+
+        if let Some(window) = self
+            .config
+            .windows
+            .iter()
+            .find(|window| window.start <= now && now < window.end)
+        {
+            return EffectiveMode {
+                mode: window.mode,
+                source: ModeSource::Schedule,
+                message: self.message_for(window.mode, window.message.as_deref()),
+                until: Some(window.end),
+            };
+        }
+
+        EffectiveMode {
+            mode: self.config.mode,
+            source: ModeSource::Config,
+            message: self.message_for(self.config.mode, None),
+            until: None,
+        }
+    }
+
+    // Picks the message for a mode, preferring a specific message over the configured one.
+    fn message_for(&self, mode: OperatingMode, specific: Option<&str>) -> String {
+        if let Some(message) = specific.filter(|m| !m.is_empty()) {
+            return message.to_string();
+        }
+        if !self.config.message.is_empty() {
+            return self.config.message.clone();
+        }
+        match mode {
+            OperatingMode::ReadOnly => DEFAULT_READ_ONLY_MESSAGE.to_string(),
+            _ => DEFAULT_MAINTENANCE_MESSAGE.to_string(),
+        }
+    }
+}
+
+// Returns whether a request is rejected under the given mode.
+//
+// Admin endpoints always stay reachable so that the mode can be lifted, and the
+// liveness endpoint so that orchestrators do not restart the proxy.
+fn is_restricted(mode: OperatingMode, method: &Method, path: &str) -> bool {
+    if path.starts_with("/admin") || path == "/livez" {
+        return false;
+    }
+
+    match mode {
+        OperatingMode::Normal => false,
+        OperatingMode::ReadOnly => *method == Method::POST && MODEL_WRITE_PATHS.contains(&path),
+        OperatingMode::Maintenance => true,
+    }
+}
+
+// Middleware rejecting requests that the effective operating mode does not allow.
+//
+// Rejected requests receive a 503 with the configured message and, when the end of
+// a scheduled window is known, a `Retry-After` header.
+pub async fn maintenance_guard(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let effective = state.maintenance.effective_mode();
+    if !is_restricted(effective.mode, request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    warn!(
+        "Rejected {} {} while in {:?} mode",
+        request.method(),
+        request.uri().path(),
+        effective.mode
+    );
+
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+    let body = Json(json!({
+        "error": effective.message,
+        "status": status.as_u16(),
+        "mode": effective.mode,
+    }));
+    let mut response = (status, body).into_response();
+
+    if let Some(until) = effective.until {
+        let retry_after = (until - Utc::now()).num_seconds().max(1);
+        if let Ok(value) = retry_after.to_string().parse() {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+    }
+
+    response
+}
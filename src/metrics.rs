@@ -0,0 +1,227 @@
+// Process-wide metrics exposed in the Prometheus text format.
+//
+// Metrics are recorded through the free functions in this module from wherever
+// the measured event happens, including inside response streams, and rendered
+// by the `/metrics` endpoint.
+//
+// # Usage
+//
+// ```rust
+// metrics::increment_counter(
+//     "panw_secret_findings_total",
+//     "Secrets found in model responses by the local scanner",
+//     &[("kind", "jwt"), ("action", "mask")],
+// );
+//
+// metrics::observe_histogram(
+//     "panw_http_request_duration_seconds",
+//     "Time until the response headers were sent",
+//     &[("route", "/api/chat")],
+//     0.42,
+// );
+// ```
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+// A counter metric and its values per label set.
+struct CounterFamily {
+    // Description rendered as the HELP line
+    help: &'static str,
+
+    // Values keyed by rendered label set
+    values: BTreeMap<String, u64>,
+}
+
+// A gauge metric and its values per label set.
+struct GaugeFamily {
+    // Description rendered as the HELP line
+    help: &'static str,
+
+    // Values keyed by rendered label set
+    values: BTreeMap<String, f64>,
+}
+
+// Upper bounds, in seconds, of the buckets of every histogram.
+const HISTOGRAM_BUCKETS: &[f64] = &[
+    0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0,
+];
+
+// Observations of a single histogram series.
+#[derive(Default)]
+struct Histogram {
+    // Observations per bucket, not cumulative; the last entry counts values above all bounds
+    buckets: Vec<u64>,
+
+    // Sum of all observed values
+    sum: f64,
+
+    // Number of observations
+    count: u64,
+}
+
+// A histogram metric and its series per label set.
+struct HistogramFamily {
+    // Description rendered as the HELP line
+    help: &'static str,
+
+    // Series keyed by rendered label set
+    values: BTreeMap<String, Histogram>,
+}
+
+// Registry of all metrics recorded by this process.
+#[derive(Default)]
+struct Registry {
+    counters: BTreeMap<&'static str, CounterFamily>,
+    gauges: BTreeMap<&'static str, GaugeFamily>,
+    histograms: BTreeMap<&'static str, HistogramFamily>,
+}
+
+// Returns the process-wide registry.
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+// Renders a label set as `{name="value",...}`, or an empty string without labels.
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let rendered: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, escaped)
+        })
+        .collect();
+    format!("{{{}}}", rendered.join(","))
+}
+
+// Increments a counter by one.
+//
+// # Arguments
+//
+// * `name` - Metric name, following Prometheus naming conventions
+// * `help` - Description of the metric
+// * `labels` - Label names and values identifying the series
+pub fn increment_counter(name: &'static str, help: &'static str, labels: &[(&str, &str)]) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let family = registry
+        .counters
+        .entry(name)
+        .or_insert_with(|| CounterFamily {
+            help,
+            values: BTreeMap::new(),
+        });
+    *family.values.entry(render_labels(labels)).or_insert(0) += 1;
+}
+
+// Sets a gauge to the given value.
+//
+// # Arguments
+//
+// * `name` - Metric name, following Prometheus naming conventions
+// * `help` - Description of the metric
+// * `labels` - Label names and values identifying the series
+// * `value` - Current value of the gauge
+pub fn set_gauge(name: &'static str, help: &'static str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let family = registry
+        .gauges
+        .entry(name)
+        .or_insert_with(|| GaugeFamily {
+            help,
+            values: BTreeMap::new(),
+        });
+    family.values.insert(render_labels(labels), value);
+}
+
+// Records an observation in a histogram.
+//
+// # Arguments
+//
+// * `name` - Metric name, following Prometheus naming conventions
+// * `help` - Description of the metric
+// * `labels` - Label names and values identifying the series
+// * `value` - Observed value in seconds
+pub fn observe_histogram(
+    name: &'static str,
+    help: &'static str,
+    labels: &[(&str, &str)],
+    value: f64,
+) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let family = registry
+        .histograms
+        .entry(name)
+        .or_insert_with(|| HistogramFamily {
+            help,
+            values: BTreeMap::new(),
+        });
+    let histogram = family.values.entry(render_labels(labels)).or_default();
+    if histogram.buckets.is_empty() {
+        histogram.buckets = vec![0; HISTOGRAM_BUCKETS.len() + 1];
+    }
+
+    let bucket = HISTOGRAM_BUCKETS
+        .iter()
+        .position(|bound| value <= *bound)
+        .unwrap_or(HISTOGRAM_BUCKETS.len());
+    histogram.buckets[bucket] += 1;
+    histogram.sum += value;
+    histogram.count += 1;
+}
+
+// Adds the `le` label of a histogram bucket to a rendered label set.
+fn bucket_labels(labels: &str, bound: &str) -> String {
+    match labels.strip_suffix('}') {
+        Some(labels) => format!("{},le=\"{}\"}}", labels, bound),
+        None => format!("{{le=\"{}\"}}", bound),
+    }
+}
+
+// Renders all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let mut output = String::new();
+
+    for (name, family) in &registry.counters {
+        let _ = writeln!(output, "# HELP {} {}", name, family.help);
+        let _ = writeln!(output, "# TYPE {} counter", name);
+        for (labels, value) in &family.values {
+            let _ = writeln!(output, "{}{} {}", name, labels, value);
+        }
+    }
+
+    for (name, family) in &registry.gauges {
+        let _ = writeln!(output, "# HELP {} {}", name, family.help);
+        let _ = writeln!(output, "# TYPE {} gauge", name);
+        for (labels, value) in &family.values {
+            let _ = writeln!(output, "{}{} {}", name, labels, value);
+        }
+    }
+
+    for (name, family) in &registry.histograms {
+        let _ = writeln!(output, "# HELP {} {}", name, family.help);
+        let _ = writeln!(output, "# TYPE {} histogram", name);
+        for (labels, histogram) in &family.values {
+            let mut cumulative = 0;
+            for (bound, observations) in HISTOGRAM_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += observations;
+                let bucket = bucket_labels(labels, &bound.to_string());
+                let _ = writeln!(output, "{}_bucket{} {}", name, bucket, cumulative);
+            }
+            let bucket = bucket_labels(labels, "+Inf");
+            let _ = writeln!(output, "{}_bucket{} {}", name, bucket, histogram.count);
+            let _ = writeln!(output, "{}_sum{} {}", name, labels, histogram.sum);
+            let _ = writeln!(output, "{}_count{} {}", name, labels, histogram.count);
+        }
+    }
+
+    output
+}
@@ -0,0 +1,129 @@
+// Schema versions and migrations of the local state files.
+//
+// State files are written as `{"schema_version": N, "data": ...}`, where the
+// data is the store's own JSON. Files written before versioning hold the data
+// alone and count as version 1. Every store has a list of migrations, and its
+// current version is one more than the number of migrations, so adding a
+// migration to the list is all it takes to change a store's schema.
+//
+// # Startup
+//
+// Before any store is loaded, state files of an older version are migrated
+// step by step and rewritten, encrypted when encryption is configured. The
+// proxy refuses to start if a state file has a newer version than it
+// supports, instead of ignoring the file and overwriting it on the next
+// change. Contents read from backups and by `store verify` are migrated in
+// memory the same way.
+use serde::Serialize;
+use serde_json::Value;
+
+// Rewrites the data of a store from one schema version to the next.
+//
+// Holds a description of the change and the function applying it.
+type Migration = (&'static str, fn(Value) -> Result<Value, String>);
+
+// Migrations of the prompt template library, in order.
+const TEMPLATES: &[Migration] = &[];
+
+// Migrations of the scan budget state, in order.
+const BUDGET: &[Migration] = &[];
+
+// Migrations of the managed client keys, in order.
+const KEYS: &[Migration] = &[];
+
+// Migrations of the kill switch state, in order.
+const KILL_SWITCH: &[Migration] = &[];
+
+// Versioned state file contents.
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    // Schema version of the data
+    schema_version: u32,
+    // Data of the store
+    data: &'a T,
+}
+
+// Returns the migrations of a store; migration `i` upgrades version `i + 1`.
+fn migrations(store: &str) -> &'static [Migration] {
+    match store {
+        "templates" => TEMPLATES,
+        "budget" => BUDGET,
+        "keys" => KEYS,
+        "kill_switch" => KILL_SWITCH,
+        _ => &[],
+    }
+}
+
+// Returns the schema version the store is written in.
+pub fn current_version(store: &str) -> u32 {
+    migrations(store).len() as u32 + 1
+}
+
+// Splits decrypted state file contents into their schema version and data.
+//
+// # Errors
+//
+// Returns an error if the contents are not JSON.
+fn split(contents: &str) -> Result<(u32, Value), String> {
+    let mut value: Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+    let version = value
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .filter(|_| value.get("data").is_some())
+        .map(|version| u32::try_from(version).unwrap_or(u32::MAX));
+    match (version, value.as_object_mut()) {
+        (Some(version), Some(object)) => {
+            let data = object.remove("data").unwrap_or_default();
+            Ok((version, data))
+        }
+        _ => Ok((1, value)),
+    }
+}
+
+// Returns the schema version of decrypted state file contents.
+//
+// # Errors
+//
+// Returns an error if the contents are not JSON.
+pub fn version_of(contents: &str) -> Result<u32, String> {
+    split(contents).map(|(version, _)| version)
+}
+
+// Returns the data of decrypted state file contents in the current schema of
+// the store, migrating data of an older version.
+//
+// # Errors
+//
+// Returns an error if the contents are not JSON, have a newer version than
+// this proxy supports, or a migration fails.
+pub fn decode(store: &str, contents: &str) -> Result<Value, String> {
+    let (version, mut data) = split(contents)?;
+    let current = current_version(store);
+    if version > current {
+        return Err(format!(
+            "schema version {} is newer than version {} supported by this proxy",
+            version, current
+        ));
+    }
+    for (description, apply) in migrations(store)
+        .iter()
+        .skip(version.saturating_sub(1) as usize)
+    {
+        data = apply(data).map_err(|e| format!("migration \"{}\" failed: {}", description, e))?;
+    }
+    Ok(data)
+}
+
+// Returns state file contents holding the data in the current schema of the
+// store.
+//
+// # Errors
+//
+// Returns an error if the data cannot be serialized.
+pub fn encode<T: Serialize>(store: &str, data: &T) -> Result<String, String> {
+    serde_json::to_string_pretty(&Versioned {
+        schema_version: current_version(store),
+        data,
+    })
+    .map_err(|e| e.to_string())
+}
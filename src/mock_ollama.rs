@@ -0,0 +1,352 @@
+// Built-in mock of the Ollama API.
+//
+// With `ollama.provider: mock` the proxy answers model requests itself instead
+// of forwarding them to an Ollama server, so demos and tests of the security
+// pipeline run without GPUs or model downloads.
+//
+// # Determinism
+//
+// Responses are drawn from a fixed word list with a random generator seeded
+// from the configured seed, the model and the prompt. The same request always
+// produces the same tokens, while different prompts produce different ones.
+//
+// # Triggers
+//
+// When a prompt contains a configured trigger phrase, the trigger's response
+// text is embedded in the middle of the generated tokens. Pointing a trigger at
+// text the security profile blocks exercises blocking of responses, including
+// streams cut off halfway.
+//
+// # Responses
+//
+// Responses are built as `reqwest::Response`s, so the rest of the proxy cannot
+// tell them from those of a real server. Streams are paced at the configured
+// token rate, or as scripted by the demo scenario.
+use crate::config::MockOllamaConfig;
+use crate::ollama::with_tag;
+use crate::scenario::Scenario;
+use axum::http;
+use bytes::Bytes;
+use chrono::Utc;
+use futures_util::stream;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::{Response, StatusCode};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Version reported by the mock.
+const MOCK_VERSION: &str = "0.0.0-mock";
+
+// Context window reported for every model.
+const CONTEXT_LENGTH: u64 = 8192;
+
+// Length of the generated embeddings.
+const EMBEDDING_LENGTH: usize = 64;
+
+// Words generated responses are made of.
+const WORDS: &str = "the model answers with a short and friendly reply about your question \
+     which covers several points in detail including examples from practice \
+     that show how this works when data flows through each step of pipeline \
+     so results stay consistent across runs while every token is generated \
+     locally for testing";
+
+// Mock Ollama backend generating deterministic responses.
+pub struct MockOllama {
+    config: MockOllamaConfig,
+
+    // Demo scenario scripting the pace of responses, if any
+    scenario: Option<Arc<Scenario>>,
+}
+
+impl MockOllama {
+    // Creates a mock with the given settings.
+    pub fn new(config: MockOllamaConfig, scenario: Option<Arc<Scenario>>) -> Self {
+        Self { config, scenario }
+    }
+
+    // Answers a request to an Ollama API endpoint.
+    //
+    // # Arguments
+    //
+    // * `endpoint` - The API endpoint called (e.g., "/api/chat")
+    // * `body` - The JSON request body, for POST requests
+    pub async fn respond(&self, endpoint: &str, body: Option<&Value>) -> Response {
+        let null = Value::Null;
+        let body = body.unwrap_or(&null);
+        match endpoint {
+            "/api/generate" | "/api/chat" => self.generate(endpoint, body).await,
+            "/api/embeddings" | "/api/embed" => self.embed(body),
+            "/api/show" => self.show(body),
+            "/api/tags" => json_response(StatusCode::OK, &self.tags()),
+            "/api/ps" => json_response(StatusCode::OK, &json!({ "models": [] })),
+            "/api/version" => json_response(StatusCode::OK, &json!({ "version": MOCK_VERSION })),
+            "/api/pull" | "/api/push" | "/api/create" | "/api/copy" | "/api/delete" => {
+                json_response(StatusCode::OK, &json!({ "status": "success" }))
+            }
+            _ => error_response(StatusCode::NOT_FOUND, "404 page not found"),
+        }
+    }
+
+    // Generates a completion or chat response, streamed unless disabled.
+    async fn generate(&self, endpoint: &str, body: &Value) -> Response {
+        let model = model_name(body);
+        if !self.knows(&model) {
+            return model_not_found(&model);
+        }
+
+        let chat = endpoint == "/api/chat";
+        let prompt = if chat {
+            body["messages"]
+                .as_array()
+                .map(|messages| {
+                    messages
+                        .iter()
+                        .filter_map(|message| message["content"].as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .unwrap_or_default()
+        } else {
+            body["prompt"].as_str().unwrap_or_default().to_string()
+        };
+        let tokens = self.tokens(&model, &prompt);
+        let eval_count = tokens.len();
+        let delays = self.chunk_delays(&prompt, eval_count + 1);
+
+        let chunk = move |content: &str, done: bool| {
+            let mut chunk = json!({
+                "model": model,
+                "created_at": Utc::now().to_rfc3339(),
+                "done": done,
+            });
+            if chat {
+                chunk["message"] = json!({ "role": "assistant", "content": content });
+            } else {
+                chunk["response"] = json!(content);
+            }
+            if done {
+                chunk["done_reason"] = json!("stop");
+                chunk["prompt_eval_count"] = json!(prompt.split_whitespace().count());
+                chunk["eval_count"] = json!(eval_count);
+            }
+            chunk
+        };
+
+        if body["stream"].as_bool() == Some(false) {
+            tokio::time::sleep(delays.iter().sum()).await;
+            return json_response(StatusCode::OK, &chunk(&tokens.concat(), true));
+        }
+
+        let chunks =
+            tokens
+                .into_iter()
+                .map(Some)
+                .chain([None])
+                .zip(delays)
+                .map(move |(token, delay)| {
+                    let line = match token {
+                        Some(token) => chunk(&token, false),
+                        None => chunk("", true),
+                    };
+                    (delay, format!("{}\n", line))
+                });
+        let body = stream::unfold(chunks, |mut chunks| async move {
+            let (delay, line) = chunks.next()?;
+            tokio::time::sleep(delay).await;
+            Some((Ok::<_, Infallible>(Bytes::from(line)), chunks))
+        });
+
+        let response = http::Response::builder()
+            .status(StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, "application/x-ndjson")
+            .body(reqwest::Body::wrap_stream(body))
+            .expect("mock response is valid");
+        Response::from(response)
+    }
+
+    // Generates a deterministic embedding of the prompt.
+    fn embed(&self, body: &Value) -> Response {
+        let model = model_name(body);
+        if !self.knows(&model) {
+            return model_not_found(&model);
+        }
+
+        let input = body["prompt"]
+            .as_str()
+            .or_else(|| body["input"].as_str())
+            .unwrap_or_default();
+        let mut rng = self.rng(&model, input);
+        let embedding: Vec<f32> = (0..EMBEDDING_LENGTH)
+            .map(|_| rng.gen_range(-1.0..1.0))
+            .collect();
+        json_response(StatusCode::OK, &json!({ "embedding": embedding }))
+    }
+
+    // Describes a model.
+    fn show(&self, body: &Value) -> Response {
+        let model = model_name(body);
+        if !self.knows(&model) {
+            return model_not_found(&model);
+        }
+
+        json_response(
+            StatusCode::OK,
+            &json!({
+                "modelfile": format!("FROM {}", model),
+                "parameters": "",
+                "template": "{{ .Prompt }}",
+                "details": model_details(),
+                "model_info": {
+                    "general.architecture": "mock",
+                    "mock.context_length": CONTEXT_LENGTH,
+                    "mock.embedding_length": EMBEDDING_LENGTH,
+                },
+                "capabilities": ["completion", "embedding"],
+            }),
+        )
+    }
+
+    // Lists the configured models.
+    fn tags(&self) -> Value {
+        let models: Vec<Value> = self
+            .config
+            .models
+            .iter()
+            .map(|name| {
+                let name = with_tag(name);
+                json!({
+                    "name": name,
+                    "model": name,
+                    "modified_at": "1970-01-01T00:00:00Z",
+                    "size": 0,
+                    "digest": format!("{:x}", Sha256::digest(name.as_bytes())),
+                    "details": model_details(),
+                })
+            })
+            .collect();
+        json!({ "models": models })
+    }
+
+    // Returns whether a model is configured, with or without its tag.
+    fn knows(&self, model: &str) -> bool {
+        let model = with_tag(model);
+        self.config
+            .models
+            .iter()
+            .any(|name| with_tag(name) == model)
+    }
+
+    // Generates the tokens of a response, with triggered texts in the middle.
+    fn tokens(&self, model: &str, prompt: &str) -> Vec<String> {
+        let words: Vec<&str> = WORDS.split_whitespace().collect();
+        let mut rng = self.rng(model, prompt);
+        let mut tokens: Vec<String> = (0..self.config.response_tokens)
+            .map(|index| {
+                let word = words.choose(&mut rng).copied().unwrap_or_default();
+                if index == 0 {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_uppercase().chain(chars).collect())
+                        .unwrap_or_default()
+                } else {
+                    format!(" {}", word)
+                }
+            })
+            .collect();
+        if let Some(last) = tokens.last_mut() {
+            last.push('.');
+        }
+
+        let prompt = prompt.to_lowercase();
+        let triggered: Vec<String> = self
+            .config
+            .triggers
+            .iter()
+            .filter(|trigger| prompt.contains(&trigger.phrase.to_lowercase()))
+            .flat_map(|trigger| trigger.response.split_whitespace())
+            .map(|word| format!(" {}", word))
+            .collect();
+        let middle = tokens.len() / 2;
+        tokens.splice(middle..middle, triggered);
+        tokens
+    }
+
+    // Returns the random generator of a request.
+    fn rng(&self, model: &str, prompt: &str) -> StdRng {
+        let digest =
+            Sha256::digest(format!("{}:{}:{}", self.config.seed, model, prompt).as_bytes());
+        StdRng::from_seed(digest.into())
+    }
+
+    // Returns the delays before each of the chunks of a response to a prompt.
+    //
+    // Scripted delays apply from the first chunk; otherwise chunks follow each
+    // other at the configured token rate.
+    fn chunk_delays(&self, prompt: &str, chunks: usize) -> Vec<Duration> {
+        if let Some(script) = self
+            .scenario
+            .as_ref()
+            .and_then(|scenario| scenario.token_delays(prompt))
+        {
+            return (0..chunks)
+                .map(|index| Duration::from_millis(script[index.min(script.len() - 1)]))
+                .collect();
+        }
+
+        let delay = if self.config.tokens_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / self.config.tokens_per_second)
+        } else {
+            Duration::ZERO
+        };
+        (0..chunks)
+            .map(|index| if index == 0 { Duration::ZERO } else { delay })
+            .collect()
+    }
+}
+
+// Returns the model named in a request body.
+fn model_name(body: &Value) -> String {
+    body["model"]
+        .as_str()
+        .or_else(|| body["name"].as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Returns the details reported for every model.
+fn model_details() -> Value {
+    json!({
+        "format": "gguf",
+        "family": "mock",
+        "families": ["mock"],
+        "parameter_size": "0B",
+        "quantization_level": "none",
+    })
+}
+
+// Builds the error returned for models that are not configured.
+fn model_not_found(model: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        &format!("model '{}' not found", model),
+    )
+}
+
+// Builds an error response in Ollama's format.
+fn error_response(status: StatusCode, message: &str) -> Response {
+    json_response(status, &json!({ "error": message }))
+}
+
+// Builds a JSON response.
+fn json_response(status: StatusCode, body: &Value) -> Response {
+    let response = http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .expect("mock response is valid");
+    Response::from(response)
+}
@@ -0,0 +1,232 @@
+// Built-in mock of the PANW AI Runtime API.
+//
+// The mock serves the scan endpoints the proxy uses on a local port, so the
+// security pipeline can be exercised and measured without a PANW tenant or
+// API key. Scans are answered as benign after the configured latency:
+//
+// - synchronous scans are answered after the latency
+// - asynchronous scans are acknowledged at once and their results reported as
+//   complete once the latency has passed since submission
+//
+// The latency of each scan is the base latency plus a random jitter of up to
+// the configured amount. Scans and polls are counted, so callers can compare
+// how many requests a workload took. Tests can start the mock with a marker,
+// blocking the scans of content that holds it.
+use axum::{
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+// Requests answered by the mock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockPanwCounts {
+    // Synchronous scans
+    pub sync_scans: u64,
+
+    // Submitted asynchronous scans
+    pub async_scans: u64,
+
+    // Polls for asynchronous scan results
+    pub polls: u64,
+}
+
+// Shared state of the mock server.
+#[derive(Default)]
+struct MockState {
+    latency: Duration,
+    jitter: Duration,
+    sync_scans: AtomicU64,
+    async_scans: AtomicU64,
+    polls: AtomicU64,
+
+    // Text marking content whose scans are answered as malicious
+    block_marker: Option<String>,
+
+    // When each pending asynchronous scan completes and whether it blocks, by scan ID
+    pending: Mutex<HashMap<String, (Instant, bool)>>,
+}
+
+impl MockState {
+    // Returns the latency of a scan, including its jitter.
+    fn scan_latency(&self) -> Duration {
+        let jitter = self.jitter.as_millis() as u64;
+        if jitter == 0 {
+            return self.latency;
+        }
+        self.latency + Duration::from_millis(rand::thread_rng().gen_range(0..=jitter))
+    }
+
+    // Returns whether a scan request holds the block marker.
+    fn blocks(&self, request: &Value) -> bool {
+        self.block_marker
+            .as_deref()
+            .is_some_and(|marker| request.to_string().contains(marker))
+    }
+}
+
+// Mock PANW API answering every scan as benign.
+pub struct MockPanw {
+    // Base URL of the running mock
+    base_url: String,
+
+    state: Arc<MockState>,
+}
+
+// Query of a poll for asynchronous scan results.
+#[derive(Deserialize)]
+struct ResultsQuery {
+    #[serde(default)]
+    scan_ids: String,
+}
+
+impl MockPanw {
+    // Starts the mock on a free local port.
+    //
+    // # Arguments
+    //
+    // * `latency` - How long each scan takes
+    // * `jitter` - Largest random latency added to each scan
+    //
+    // # Errors
+    //
+    // Returns an error if no local port can be bound.
+    pub async fn start(latency: Duration, jitter: Duration) -> std::io::Result<Self> {
+        Self::serve(MockState {
+            latency,
+            jitter,
+            ..MockState::default()
+        })
+        .await
+    }
+
+    // Starts the mock blocking the scans of content that holds a marker.
+    //
+    // # Arguments
+    //
+    // * `latency` - How long each scan takes
+    // * `marker` - Text marking content to block
+    #[cfg(test)]
+    pub async fn start_blocking(latency: Duration, marker: &str) -> std::io::Result<Self> {
+        Self::serve(MockState {
+            latency,
+            block_marker: Some(marker.to_string()),
+            ..MockState::default()
+        })
+        .await
+    }
+
+    // Serves the scan endpoints on a free local port.
+    async fn serve(state: MockState) -> std::io::Result<Self> {
+        let state = Arc::new(state);
+        let app = Router::new()
+            .route("/v1/scan/sync/request", post(sync_scan))
+            .route("/v1/scan/async/request", post(async_scan))
+            .route("/v1/scan/results", get(scan_results))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(Self { base_url, state })
+    }
+
+    // Returns the base URL of the mock, to be used as the PANW base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Returns the requests answered so far.
+    pub fn counts(&self) -> MockPanwCounts {
+        MockPanwCounts {
+            sync_scans: self.state.sync_scans.load(Ordering::Relaxed),
+            async_scans: self.state.async_scans.load(Ordering::Relaxed),
+            polls: self.state.polls.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Handler for synchronous scans (POST /v1/scan/sync/request)
+async fn sync_scan(State(state): State<Arc<MockState>>, Json(request): Json<Value>) -> Json<Value> {
+    state.sync_scans.fetch_add(1, Ordering::Relaxed);
+    tokio::time::sleep(state.scan_latency()).await;
+    Json(scan_result(
+        &uuid::Uuid::new_v4().to_string(),
+        state.blocks(&request),
+    ))
+}
+
+// Handler for submitted asynchronous scans (POST /v1/scan/async/request)
+async fn async_scan(
+    State(state): State<Arc<MockState>>,
+    Json(request): Json<Value>,
+) -> Json<Value> {
+    state.async_scans.fetch_add(1, Ordering::Relaxed);
+    let scan_id = uuid::Uuid::new_v4().to_string();
+    let completes_at = Instant::now() + state.scan_latency();
+    state
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(scan_id.clone(), (completes_at, state.blocks(&request)));
+
+    Json(json!({
+        "received": chrono::Utc::now().to_rfc3339(),
+        "scan_id": scan_id,
+        "report_id": format!("R{}", scan_id),
+    }))
+}
+
+// Handler for polls of asynchronous scan results (GET /v1/scan/results)
+async fn scan_results(
+    State(state): State<Arc<MockState>>,
+    Query(query): Query<ResultsQuery>,
+) -> Json<Value> {
+    state.polls.fetch_add(1, Ordering::Relaxed);
+    let mut pending = state.pending.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    let results: Vec<Value> = query
+        .scan_ids
+        .split(',')
+        .filter_map(|scan_id| {
+            let (completes_at, blocked) = *pending.get(scan_id)?;
+            if now < completes_at {
+                return Some(json!({ "req_id": 1, "status": "pending", "scan_id": scan_id }));
+            }
+            pending.remove(scan_id);
+            Some(json!({
+                "req_id": 1,
+                "status": "complete",
+                "scan_id": scan_id,
+                "result": scan_result(scan_id, blocked),
+            }))
+        })
+        .collect();
+    Json(Value::Array(results))
+}
+
+// Returns a scan result allowing the content, or blocking it as malicious.
+fn scan_result(scan_id: &str, blocked: bool) -> Value {
+    let (category, action) = if blocked {
+        ("malicious", "block")
+    } else {
+        ("benign", "allow")
+    };
+    json!({
+        "report_id": format!("R{}", scan_id),
+        "scan_id": scan_id,
+        "category": category,
+        "action": action,
+    })
+}
@@ -0,0 +1,211 @@
+// Automatic pulls of allowed models on first use.
+//
+// In a fresh lab the first request for a model usually fails with a raw Ollama
+// "model not found" error. With automatic pulls enabled, a request for a model
+// that is on the allow list but not installed starts a pull in the background
+// instead, and is answered with 425 Too Early and a `Retry-After` header. Later
+// requests for the same model report the pull's progress until it finishes.
+//
+// Models that are not on the allow list, and all models while the proxy is not
+// in normal operating mode, are left to Ollama as before.
+use crate::capabilities::CapabilityCache;
+use crate::config::AutoPullConfig;
+use crate::handlers::ApiError;
+use crate::key_scopes::model_matches;
+use crate::ollama::{OllamaClient, OllamaError};
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+
+// Progress of a running pull.
+#[derive(Debug, Clone, Default)]
+struct PullProgress {
+    // Latest status line reported by Ollama
+    status: String,
+
+    // Bytes downloaded of the current layer
+    completed: u64,
+
+    // Size of the current layer
+    total: u64,
+}
+
+impl PullProgress {
+    // Describes the progress for clients waiting on the pull.
+    fn describe(&self) -> String {
+        match self.completed.saturating_mul(100).checked_div(self.total) {
+            Some(percent) => format!("{} ({}%)", self.status, percent),
+            None if self.status.is_empty() => "starting".to_string(),
+            None => self.status.clone(),
+        }
+    }
+}
+
+// Starts and tracks automatic pulls of missing models.
+//
+// Cloning the puller is cheap and all clones share the same running pulls.
+#[derive(Clone)]
+pub struct ModelPuller {
+    // Whether missing models are pulled at all
+    enabled: bool,
+
+    // Models that may be pulled; a trailing `*` matches any suffix
+    allowed_models: Vec<String>,
+
+    // Seconds clients are asked to wait before retrying
+    retry_after_secs: u64,
+
+    // Running pulls keyed by model name
+    pulls: Arc<Mutex<HashMap<String, PullProgress>>>,
+}
+
+impl ModelPuller {
+    // Creates the puller from the configuration.
+    pub fn new(config: &AutoPullConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            allowed_models: config.allowed_models.clone(),
+            retry_after_secs: config.retry_after_secs,
+            pulls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Ensures that an allowed model is installed, starting a pull if it is missing.
+    //
+    // # Arguments
+    //
+    // * `ollama` - Client used to check for and pull the model
+    // * `capabilities` - Capability cache, which knows installed models
+    // * `model` - Name of the requested model
+    // * `may_pull` - Whether the operating mode allows model writes
+    //
+    // # Errors
+    //
+    // Returns a model pulling error while a pull of the model is running.
+    pub async fn ensure_available(
+        &self,
+        ollama: &OllamaClient,
+        capabilities: &CapabilityCache,
+        model: &str,
+        may_pull: bool,
+    ) -> Result<(), ApiError> {
+        if !self.enabled || !self.is_allowed(model) {
+            return Ok(());
+        }
+
+        if let Some(progress) = self.progress(model) {
+            return Err(self.pulling(model, &progress));
+        }
+
+        if capabilities.get(ollama, model).await.is_some() || !may_pull {
+            return Ok(());
+        }
+
+        // Only a definite "not found" starts a pull; other failures surface as usual
+        match ollama.forward("/api/show", &json!({ "name": model })).await {
+            Err(OllamaError::ApiError { status, .. }) if status == StatusCode::NOT_FOUND => {}
+            _ => return Ok(()),
+        }
+
+        let progress = {
+            let mut pulls = self.pulls.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(progress) = pulls.get(model) {
+                progress.clone()
+            } else {
+                info!("Model {} is missing, pulling it automatically", model);
+                pulls.insert(model.to_string(), PullProgress::default());
+                self.spawn_pull(ollama.clone(), model.to_string());
+                PullProgress::default()
+            }
+        };
+        Err(self.pulling(model, &progress))
+    }
+
+    // Returns whether a model may be pulled automatically.
+    fn is_allowed(&self, model: &str) -> bool {
+        self.allowed_models
+            .iter()
+            .any(|allowed| model_matches(allowed, model))
+    }
+
+    // Returns the progress of a running pull of the model.
+    fn progress(&self, model: &str) -> Option<PullProgress> {
+        let pulls = self.pulls.lock().unwrap_or_else(|e| e.into_inner());
+        pulls.get(model).cloned()
+    }
+
+    // Builds the error returned while the model is being pulled.
+    fn pulling(&self, model: &str, progress: &PullProgress) -> ApiError {
+        ApiError::ModelPulling {
+            message: format!(
+                "Model {} is being pulled: {}. Retry in {} seconds",
+                model,
+                progress.describe(),
+                self.retry_after_secs
+            ),
+            retry_after_secs: self.retry_after_secs,
+        }
+    }
+
+    // Pulls the model in the background, tracking its progress until it finishes.
+    fn spawn_pull(&self, ollama: OllamaClient, model: String) {
+        let pulls = self.pulls.clone();
+
+        tokio::spawn(async move {
+            let request = json!({ "name": model, "stream": true });
+            let result = match ollama.stream("/api/pull", &request).await {
+                Ok(stream) => {
+                    let mut stream = Box::pin(stream);
+                    let mut pending: Vec<u8> = Vec::new();
+                    let mut failure = None;
+
+                    while let Some(chunk) = stream.next().await {
+                        let chunk = match chunk {
+                            Ok(chunk) => chunk,
+                            Err(e) => {
+                                failure = Some(e.to_string());
+                                break;
+                            }
+                        };
+                        pending.extend_from_slice(&chunk);
+
+                        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                            let line: Vec<u8> = pending.drain(..=end).collect();
+                            let Ok(update) = serde_json::from_slice::<Value>(&line) else {
+                                continue;
+                            };
+                            if let Some(error) = update["error"].as_str() {
+                                failure = Some(error.to_string());
+                                continue;
+                            }
+
+                            let mut pulls = pulls.lock().unwrap_or_else(|e| e.into_inner());
+                            if let Some(progress) = pulls.get_mut(&model) {
+                                progress.status =
+                                    update["status"].as_str().unwrap_or_default().to_string();
+                                progress.completed = update["completed"].as_u64().unwrap_or(0);
+                                progress.total = update["total"].as_u64().unwrap_or(0);
+                            }
+                        }
+                    }
+
+                    failure.map_or(Ok(()), Err)
+                }
+                Err(e) => Err(e.to_string()),
+            };
+
+            match result {
+                Ok(()) => info!("Automatic pull of {} finished", model),
+                Err(e) => warn!("Automatic pull of {} failed: {}", model, e),
+            }
+            debug!("Removing pull state of {}", model);
+            pulls
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&model);
+        });
+    }
+}
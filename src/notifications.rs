@@ -0,0 +1,347 @@
+// Notification of operational alerts.
+//
+// Subsystems raise alerts through this module instead of posting to their own
+// webhooks. Each alert has a severity, and is sent to the channels of every
+// alert rule matching its name and severity:
+//
+// - `scan_budget_exhausted`: the monthly scan budget was reached (`high`)
+// - `scheduled_prompt_failed`: a scheduled prompt run failed (`medium`)
+// - `lockout`: a source was locked out after repeated authentication
+//   failures (`high`)
+// - `failover`: an Ollama server was taken out of or back into rotation
+//   (`medium`)
+// - `policy_change`: settings were changed through the admin API (`medium`)
+// - `kill_switch`: the kill switch was activated or lifted (`critical`)
+//
+// # Channels
+//
+// - `webhook`: the alert is posted as a JSON object with its `event`,
+//   `severity`, `summary`, `timestamp` and details
+// - `smtp`: the alert is emailed with its summary as subject
+// - `pagerduty`: an event is triggered through the PagerDuty Events API v2,
+//   with `critical` alerts as `critical`, `high` alerts as `error` and
+//   `medium` alerts as `warning`
+//
+// The alert webhooks of the scan budget and the scheduler are kept as webhook
+// channels notified of their own alert.
+//
+// # Delivery
+//
+// Alerts are sent in the background and delivery is best effort: failures are
+// logged and counted in `panw_notification_failures_total` by channel, and
+// sent notifications in `panw_notifications_total` by alert and channel.
+use crate::config::{
+    AlertRule, Config, EventSeverity, NotificationChannelConfig, NotificationChannelKind,
+    SmtpConfig,
+};
+use crate::http_client;
+use crate::metrics;
+use crate::smtp;
+use chrono::{SecondsFormat, Utc};
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use tracing::{debug, warn};
+
+// Notifier installed at startup; alerts are only logged by their subsystem when not installed.
+static NOTIFIER: OnceLock<Notifier> = OnceLock::new();
+
+// Default URL of the PagerDuty Events API v2.
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+// Alerts that can be notified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    // The monthly scan budget was reached
+    ScanBudgetExhausted,
+    // A scheduled prompt run failed
+    ScheduledPromptFailed,
+    // A source was locked out after repeated authentication failures
+    Lockout,
+    // An Ollama server was taken out of or back into rotation
+    Failover,
+    // Settings were changed through the admin API
+    PolicyChange,
+    // The kill switch was activated or lifted
+    KillSwitch,
+}
+
+impl AlertKind {
+    // Returns the name of the alert in rules and notifications.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ScanBudgetExhausted => "scan_budget_exhausted",
+            Self::ScheduledPromptFailed => "scheduled_prompt_failed",
+            Self::Lockout => "lockout",
+            Self::Failover => "failover",
+            Self::PolicyChange => "policy_change",
+            Self::KillSwitch => "kill_switch",
+        }
+    }
+
+    // Returns the severity of the alert.
+    fn severity(self) -> EventSeverity {
+        match self {
+            Self::KillSwitch => EventSeverity::Critical,
+            Self::ScanBudgetExhausted | Self::Lockout => EventSeverity::High,
+            Self::ScheduledPromptFailed | Self::Failover | Self::PolicyChange => {
+                EventSeverity::Medium
+            }
+        }
+    }
+}
+
+// Destination of notifications.
+enum Channel {
+    // JSON object posted to a URL
+    Webhook { url: String },
+    // Email sent through an SMTP server
+    Smtp(SmtpConfig),
+    // Event triggered through the PagerDuty Events API
+    PagerDuty { url: String, routing_key: String },
+}
+
+impl Channel {
+    fn new(config: &NotificationChannelConfig) -> Self {
+        match config.kind {
+            NotificationChannelKind::Webhook => Self::Webhook {
+                url: config.url.clone(),
+            },
+            NotificationChannelKind::Smtp => Self::Smtp(config.smtp.clone()),
+            NotificationChannelKind::Pagerduty => Self::PagerDuty {
+                url: if config.url.is_empty() {
+                    PAGERDUTY_EVENTS_URL.to_string()
+                } else {
+                    config.url.clone()
+                },
+                routing_key: config.routing_key.clone(),
+            },
+        }
+    }
+
+    // Sends a notification of an alert.
+    async fn send(
+        &self,
+        client: &Client,
+        kind: AlertKind,
+        summary: &str,
+        notification: &Value,
+    ) -> Result<(), String> {
+        let request = match self {
+            Self::Webhook { url } => client.post(url).json(notification),
+            Self::PagerDuty { url, routing_key } => {
+                let severity = match kind.severity() {
+                    EventSeverity::Critical => "critical",
+                    EventSeverity::High => "error",
+                    EventSeverity::Medium => "warning",
+                    EventSeverity::Low | EventSeverity::Info => "info",
+                };
+                client.post(url).json(&json!({
+                    "routing_key": routing_key,
+                    "event_action": "trigger",
+                    "payload": {
+                        "summary": summary,
+                        "source": "panw-api-ollama",
+                        "severity": severity,
+                        "component": kind.as_str(),
+                        "timestamp": notification["timestamp"],
+                        "custom_details": notification,
+                    },
+                }))
+            }
+            Self::Smtp(config) => {
+                let subject = format!(
+                    "[panw-api-ollama] {} alert: {}",
+                    kind.severity().as_str(),
+                    summary
+                );
+                let body = serde_json::to_string_pretty(notification).unwrap_or_default();
+                return smtp::send(config, &subject, &format!("{}\n\n{}\n", summary, body)).await;
+            }
+        };
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err(format!("returned {}", response.status())),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+// Rule sending matching alerts to channels.
+struct Rule {
+    // Alerts the rule applies to; empty applies to every alert
+    alerts: Vec<String>,
+
+    // Lowest severity of alerts the rule applies to
+    min_severity: EventSeverity,
+
+    // Names of the channels the alerts are sent to
+    channels: Vec<String>,
+}
+
+impl Rule {
+    fn new(config: &AlertRule) -> Self {
+        Self {
+            alerts: config.alerts.clone(),
+            min_severity: config.min_severity,
+            channels: config.channels.clone(),
+        }
+    }
+
+    // Returns whether the rule applies to an alert.
+    fn matches(&self, kind: AlertKind) -> bool {
+        kind.severity() >= self.min_severity
+            && (self.alerts.is_empty() || self.alerts.iter().any(|alert| alert == kind.as_str()))
+    }
+}
+
+// Sends alerts to the channels of the rules matching them.
+struct Notifier {
+    // Channels by name
+    channels: HashMap<String, Arc<Channel>>,
+
+    // Rules in configuration order
+    rules: Vec<Rule>,
+
+    // HTTP client used for webhooks and PagerDuty
+    client: Client,
+}
+
+// Installs the notifier for all subsequent alerts.
+//
+// The alert webhooks of the scan budget and the scheduler are added as channels
+// notified of their own alert. Does nothing when no rule is configured.
+//
+// # Errors
+//
+// Returns an error if the HTTP client cannot be created.
+pub fn init(config: &Config) -> Result<(), String> {
+    let mut channels: HashMap<String, Arc<Channel>> = config
+        .notifications
+        .channels
+        .iter()
+        .map(|channel| (channel.name.clone(), Arc::new(Channel::new(channel))))
+        .collect();
+    let mut rules: Vec<Rule> = config.notifications.rules.iter().map(Rule::new).collect();
+
+    let legacy_webhooks = [
+        (
+            AlertKind::ScanBudgetExhausted,
+            &config.security.budget.alert_webhook,
+        ),
+        (
+            AlertKind::ScheduledPromptFailed,
+            &config.scheduler.alert_webhook,
+        ),
+    ];
+    for (kind, url) in legacy_webhooks {
+        if url.is_empty() {
+            continue;
+        }
+        let name = format!("{}_webhook", kind.as_str());
+        channels.insert(
+            name.clone(),
+            Arc::new(Channel::Webhook { url: url.clone() }),
+        );
+        rules.push(Rule {
+            alerts: vec![kind.as_str().to_string()],
+            min_severity: EventSeverity::Info,
+            channels: vec![name],
+        });
+    }
+
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let client = http_client::builder(
+        &config.notifications.http_client,
+        Some(http_client::DEFAULT_WEBHOOK_REQUEST_TIMEOUT),
+    )
+    .build()
+    .map_err(|e| format!("notification client: {}", e))?;
+    let _ = NOTIFIER.set(Notifier {
+        channels,
+        rules,
+        client,
+    });
+    Ok(())
+}
+
+// Notifies the channels of the rules matching an alert.
+//
+// # Arguments
+//
+// * `kind` - The alert
+// * `summary` - Human-readable summary of the alert
+// * `details` - Further fields of the alert
+pub fn notify(kind: AlertKind, summary: &str, details: &[(&str, Value)]) {
+    let Some(notifier) = NOTIFIER.get() else {
+        return;
+    };
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+
+    let mut names: Vec<&String> = notifier
+        .rules
+        .iter()
+        .filter(|rule| rule.matches(kind))
+        .flat_map(|rule| &rule.channels)
+        .collect();
+    names.sort();
+    names.dedup();
+    if names.is_empty() {
+        return;
+    }
+
+    let mut object = Map::new();
+    object.insert("event".to_string(), Value::from(kind.as_str()));
+    object.insert(
+        "severity".to_string(),
+        Value::from(kind.severity().as_str()),
+    );
+    object.insert("summary".to_string(), Value::from(summary));
+    object.insert(
+        "timestamp".to_string(),
+        Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+    );
+    for (field, value) in details {
+        if !value.is_null() {
+            object.insert(field.to_string(), value.clone());
+        }
+    }
+    let notification = Arc::new(Value::Object(object));
+    let summary: Arc<str> = Arc::from(summary);
+
+    for name in names {
+        let Some(channel) = notifier.channels.get(name).cloned() else {
+            continue;
+        };
+        let client = notifier.client.clone();
+        let name = name.clone();
+        let notification = notification.clone();
+        let summary = summary.clone();
+        runtime.spawn(async move {
+            match channel.send(&client, kind, &summary, &notification).await {
+                Ok(()) => {
+                    debug!("{} alert sent to {}", kind.as_str(), name);
+                    metrics::increment_counter(
+                        "panw_notifications_total",
+                        "Alert notifications sent",
+                        &[("alert", kind.as_str()), ("channel", &name)],
+                    );
+                }
+                Err(e) => {
+                    warn!("Failed to send {} alert to {}: {}", kind.as_str(), name, e);
+                    metrics::increment_counter(
+                        "panw_notification_failures_total",
+                        "Alert notifications that could not be sent",
+                        &[("channel", &name)],
+                    );
+                }
+            }
+        });
+    }
+}
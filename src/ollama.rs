@@ -10,12 +10,70 @@
 // - Handles both streaming and non-streaming responses
 // - Processes and transforms API errors into structured types
 // - Manages HTTP connection details
+//
+// With the mock provider, requests are answered by the built-in mock backend
+// instead of an Ollama server.
+//
+// # Failover
+//
+// When further Ollama servers are configured, requests are spread over all
+// servers in rotation. A background probe takes servers that stop answering
+// out of rotation until they recover, and a request whose connection to a
+// server fails is retried on the next one. Servers out of rotation are still
+// tried as a last resort when no healthy server is left.
+//
+// # Model Routing
+//
+// Model routes send the requests for matching models to their own servers,
+// such as large models to a GPU server and embedding models to a CPU server.
+// The first route whose pattern matches the request's model decides; requests
+// for other models, and requests naming no model, go to the servers above.
+// The servers of a route share its requests in rotation with the same failover.
+//
+// # Upstream Authentication
+//
+// For servers behind an authenticating gateway, configured headers, a bearer
+// token or basic authentication credentials are sent with every request to
+// every server, including the health probes.
+//
+// # Retries
+//
+// Requests that fail transiently, because the connection to every server
+// failed or was reset or because a server answered with a retryable status
+// such as 502 or 503, are retried with exponential backoff, so brief Ollama
+// restarts do not surface as errors. Timeouts are not retried, since the
+// server may still be generating.
+//
+// # Version Compatibility
+//
+// The version of the server at the base URL is checked at startup and on the
+// configured interval, and requests are adjusted to known differences between
+// versions; see `ollama_compat`. The servers are expected to run the same
+// version.
+use crate::config::{HttpClientConfig, ModelRoute, OllamaConfig, OllamaRetryConfig};
+use crate::http_client;
+use crate::key_scopes::model_matches;
+use crate::metrics;
+use crate::mock_ollama::MockOllama;
+use crate::notifications::{self, AlertKind};
+use crate::ollama_compat::OllamaCompat;
+use crate::passthrough;
+use crate::security_events::{self, EventType};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::Bytes;
-use futures_util::Stream;
+use futures_util::{future, Stream};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
 use reqwest::{Client, Response, StatusCode};
 use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
+
+// How long a probe of an Ollama server may take before it counts as unhealthy.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
 
 // Errors that can occur when interacting with the Ollama API.
 //
@@ -42,10 +100,137 @@ pub enum OllamaError {
     ConfigError(String),
 }
 
+// Ollama server requests are forwarded to.
+struct Backend {
+    // Base URL of the server
+    base_url: String,
+
+    // Whether the server is in rotation
+    healthy: AtomicBool,
+}
+
+impl Backend {
+    fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    // Takes the server into or out of rotation.
+    fn set_healthy(&self, healthy: bool) {
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            let message = if healthy {
+                format!("Ollama server {} is back in rotation", self.base_url)
+            } else {
+                format!("Ollama server {} taken out of rotation", self.base_url)
+            };
+            if healthy {
+                info!("{}", message);
+            } else {
+                warn!("{}", message);
+            }
+            security_events::publish(
+                EventType::Failover,
+                &message,
+                &[
+                    ("backend", json!(self.base_url)),
+                    ("in_rotation", json!(healthy)),
+                ],
+            );
+            notifications::notify(
+                AlertKind::Failover,
+                &message,
+                &[
+                    ("backend", json!(self.base_url)),
+                    ("in_rotation", json!(healthy)),
+                ],
+            );
+        }
+        metrics::set_gauge(
+            "panw_ollama_backend_up",
+            "Whether the Ollama server is in rotation (1) or not (0)",
+            &[("backend", &self.base_url)],
+            if healthy { 1.0 } else { 0.0 },
+        );
+    }
+}
+
+// Servers answering requests for the models matching a pattern.
+struct Route {
+    // Model name; a trailing `*` matches any suffix
+    model: String,
+
+    // Positions of the route's servers among all servers
+    servers: Vec<usize>,
+}
+
+impl Route {
+    // Returns whether the route applies to requests for a model.
+    fn matches(&self, model: &str) -> bool {
+        model_matches(&self.model, model)
+    }
+}
+
+// Retries of requests that fail transiently.
+#[derive(Debug, Default)]
+struct RetryPolicy {
+    // Retries after the first attempt
+    max_retries: u32,
+
+    // Delay before the first retry, doubled for every further retry
+    initial_backoff: Duration,
+
+    // Longest delay between retries
+    max_backoff: Duration,
+
+    // Response statuses that are retried
+    retry_on_status: Vec<StatusCode>,
+}
+
+impl RetryPolicy {
+    fn new(config: &OllamaRetryConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            retry_on_status: config
+                .retry_on_status
+                .iter()
+                .filter_map(|status| StatusCode::from_u16(*status).ok())
+                .collect(),
+        }
+    }
+
+    // Returns why a failed request may be retried, or `None` if it may not.
+    fn reason(&self, error: &OllamaError) -> Option<&'static str> {
+        match error {
+            OllamaError::RequestError(e)
+                if (e.is_connect() || e.is_request()) && !e.is_timeout() =>
+            {
+                Some("connection")
+            }
+            OllamaError::ApiError { status, .. } if self.retry_on_status.contains(status) => {
+                Some("status")
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the delay before the given retry, counting from zero.
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
 // Client for interacting with the Ollama API.
 //
 // This client provides methods for sending requests to Ollama endpoints
 // and handles the transformation of responses into appropriate formats.
+//
+// Cloning the client is cheap and all clones share the same rotation.
 #[derive(Clone)]
 pub struct OllamaClient {
     // HTTP client for making API requests
@@ -53,6 +238,27 @@ pub struct OllamaClient {
 
     // Base URL for the Ollama API service
     base_url: String,
+
+    // Servers requests are forwarded to, the one at the base URL first
+    backends: Arc<Vec<Backend>>,
+
+    // Positions of the servers answering requests no route applies to
+    pool: Arc<Vec<usize>>,
+
+    // Routes of specific models to their own servers, in order of precedence
+    routes: Arc<Vec<Route>>,
+
+    // Position of the server the next request starts at
+    next: Arc<AtomicUsize>,
+
+    // Retries of requests that fail transiently
+    retry: Arc<RetryPolicy>,
+
+    // Mock backend answering requests in place of the service, if enabled
+    mock: Option<Arc<MockOllama>>,
+
+    // Compatibility with the version of the servers
+    compat: OllamaCompat,
 }
 
 impl OllamaClient {
@@ -65,16 +271,92 @@ impl OllamaClient {
     // # Arguments
     //
     // * `base_url` - The base URL of the Ollama API service (e.g., "http://localhost:11434")
+    // * `backends` - Base URLs of further Ollama servers sharing the requests
+    // * `routes` - Servers answering requests for specific models instead
     //
     // # Example
     //
     // ```
-    // let client = OllamaClient::new("http://localhost:11434");
+    // let client = OllamaClient::new("http://localhost:11434".to_string(), &[], &[]);
     // ```
-    pub fn new(base_url: String) -> Self {
+    pub fn new(base_url: String, backends: &[String], routes: &[ModelRoute]) -> Self {
+        // Servers shared by the pool and routes, or by several routes, are
+        // kept once so that their health is tracked once
+        let mut servers: Vec<Backend> = Vec::new();
+        let mut position = |url: &String| {
+            let url = url.trim_end_matches('/');
+            match servers.iter().position(|server| server.base_url == url) {
+                Some(index) => index,
+                None => {
+                    servers.push(Backend::new(url));
+                    servers.len() - 1
+                }
+            }
+        };
+        let pool = std::iter::once(&base_url)
+            .chain(backends)
+            .map(&mut position)
+            .collect();
+        let routes = routes
+            .iter()
+            .map(|route| Route {
+                model: route.model.clone(),
+                servers: route.backends.iter().map(&mut position).collect(),
+            })
+            .collect();
         Self {
             client: Client::new(),
             base_url,
+            backends: Arc::new(servers),
+            pool: Arc::new(pool),
+            routes: Arc::new(routes),
+            next: Arc::new(AtomicUsize::new(0)),
+            retry: Arc::new(RetryPolicy::default()),
+            mock: None,
+            compat: OllamaCompat::default(),
+        }
+    }
+
+    // Sends requests to the Ollama servers with the given client settings and
+    // headers.
+    //
+    // # Arguments
+    //
+    // * `config` - Timeouts and connection pooling of the HTTP client
+    // * `headers` - Headers sent with every request
+    //
+    // # Errors
+    //
+    // Returns an error if the HTTP client cannot be created
+    pub fn with_http_client(
+        mut self,
+        config: &HttpClientConfig,
+        headers: HeaderMap,
+    ) -> Result<Self, OllamaError> {
+        self.client = http_client::builder(config, None)
+            .default_headers(headers)
+            .build()?;
+        Ok(self)
+    }
+
+    // Retries requests that fail transiently as configured.
+    pub fn with_retry(mut self, config: &OllamaRetryConfig) -> Self {
+        self.retry = Arc::new(RetryPolicy::new(config));
+        self
+    }
+
+    // Creates a client answered by the mock backend instead of an Ollama service.
+    pub fn mock(mock: MockOllama) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: "mock".to_string(),
+            backends: Arc::new(Vec::new()),
+            pool: Arc::new(Vec::new()),
+            routes: Arc::new(Vec::new()),
+            next: Arc::new(AtomicUsize::new(0)),
+            retry: Arc::new(RetryPolicy::default()),
+            mock: Some(Arc::new(mock)),
+            compat: OllamaCompat::default(),
         }
     }
 
@@ -82,8 +364,26 @@ impl OllamaClient {
     // Public API Methods
     //--------------------------------------------------------------------------
 
+    // Returns the base URL of the Ollama API service.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Returns whether requests are answered by the mock backend.
+    pub fn is_mock(&self) -> bool {
+        self.mock.is_some()
+    }
+
+    // Returns the compatibility state with the version of the servers.
+    pub fn compat(&self) -> &OllamaCompat {
+        &self.compat
+    }
+
     // Forwards a POST request to the specified Ollama API endpoint.
     //
+    // The request goes to the servers of the route matching the model named in
+    // the body, if any. The body is adjusted to the version of the servers.
+    //
     // # Arguments
     //
     // * `endpoint` - The API endpoint to call (e.g., "/api/chat")
@@ -101,8 +401,20 @@ impl OllamaClient {
         endpoint: &str,
         body: &T,
     ) -> Result<Response, OllamaError> {
-        self.forward_request(endpoint, |url| self.client.post(url).json(body))
-            .await
+        if let Some(mock) = &self.mock {
+            return check_status(mock.respond(endpoint, Some(&to_value(body))).await).await;
+        }
+        let model = if self.routes.is_empty() {
+            None
+        } else {
+            model_of(&to_value(body))
+        };
+        let adjusted = self.compat.adjust(endpoint, body);
+        self.forward_request(endpoint, model.as_deref(), |url| match &adjusted {
+            Some(adjusted) => self.client.post(url).json(adjusted),
+            None => self.client.post(url).json(body),
+        })
+        .await
     }
 
     // Forwards a GET request to the specified Ollama API endpoint.
@@ -119,7 +431,10 @@ impl OllamaClient {
     //
     // Returns an error if the request fails or the API returns an error status
     pub async fn forward_get(&self, endpoint: &str) -> Result<Response, OllamaError> {
-        self.forward_request(endpoint, |url| self.client.get(url))
+        if let Some(mock) = &self.mock {
+            return check_status(mock.respond(endpoint, None).await).await;
+        }
+        self.forward_request(endpoint, None, |url| self.client.get(url))
             .await
     }
 
@@ -145,9 +460,7 @@ impl OllamaClient {
         endpoint: &str,
         body: &T,
     ) -> Result<impl Stream<Item = Result<Bytes, reqwest::Error>>, OllamaError> {
-        let response = self
-            .forward_request(endpoint, |url| self.client.post(url).json(body))
-            .await?;
+        let response = self.forward(endpoint, body).await?;
         Ok(response.bytes_stream())
     }
 
@@ -157,9 +470,15 @@ impl OllamaClient {
 
     // Generic method to handle both GET and POST requests, reducing code duplication.
     //
+    // Requests are sent to the servers in rotation order; when the connection
+    // to a server fails, the server is taken out of rotation and the request is
+    // retried on the next one. Requests that fail transiently on every server
+    // are retried after a backoff.
+    //
     // # Arguments
     //
     // * `endpoint` - The API endpoint to call
+    // * `model` - The model the request is for, selecting the route it takes
     // * `request_builder` - A function that configures the request
     //
     // # Returns
@@ -172,30 +491,265 @@ impl OllamaClient {
     async fn forward_request<F>(
         &self,
         endpoint: &str,
+        model: Option<&str>,
         request_builder: F,
     ) -> Result<Response, OllamaError>
     where
-        F: FnOnce(&str) -> reqwest::RequestBuilder,
+        F: Fn(&str) -> reqwest::RequestBuilder,
     {
-        let url = format!("{}{}", self.base_url, endpoint);
-        debug!("Forwarding request to {}", url);
+        let route = model.and_then(|model| self.routes.iter().find(|route| route.matches(model)));
+        let servers = match route {
+            Some(route) => {
+                debug!("Routing request for {} to its own servers", route.model);
+                &route.servers
+            }
+            None => &self.pool,
+        };
 
-        let response = request_builder(&url).send().await.map_err(|e| {
-            error!("Request to Ollama API failed: {}", e);
-            OllamaError::RequestError(e)
-        })?;
+        let mut retry = 0;
+        loop {
+            let result = self
+                .send_to_servers(endpoint, servers, &request_builder)
+                .await;
+            let reason = match &result {
+                Err(e) if retry < self.retry.max_retries => self.retry.reason(e),
+                _ => None,
+            };
+            let Some(reason) = reason else {
+                return result;
+            };
+
+            let backoff = self.retry.backoff(retry);
+            retry += 1;
+            warn!(
+                "Retrying request to {} in {:?} ({} of {}) after a transient failure",
+                endpoint, backoff, retry, self.retry.max_retries
+            );
+            metrics::increment_counter(
+                "panw_ollama_retries_total",
+                "Ollama requests retried after a transient failure, by reason",
+                &[("reason", reason)],
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    // Sends a request to the given servers in rotation order until one answers.
+    async fn send_to_servers<F>(
+        &self,
+        endpoint: &str,
+        servers: &[usize],
+        request_builder: &F,
+    ) -> Result<Response, OllamaError>
+    where
+        F: Fn(&str) -> reqwest::RequestBuilder,
+    {
+        let mut last_error = None;
+        for backend in self.rotation(servers) {
+            let url = format!("{}{}", backend.base_url, endpoint);
+            debug!("Forwarding request to {}", url);
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let message = response
-                .text()
+            let request = request_builder(&url).headers(passthrough::request_headers());
+            match request.send().await {
+                Ok(response) => {
+                    backend.set_healthy(true);
+                    passthrough::record_response_headers(response.headers());
+                    return check_status(response).await;
+                }
+                Err(e) if e.is_connect() => {
+                    warn!(
+                        "Connection to Ollama server {} failed: {}",
+                        backend.base_url, e
+                    );
+                    backend.set_healthy(false);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    error!("Request to Ollama API failed: {}", e);
+                    return Err(OllamaError::RequestError(e));
+                }
+            }
+        }
+
+        match last_error {
+            Some(e) => {
+                error!("Request to Ollama API failed on every server: {}", e);
+                Err(OllamaError::RequestError(e))
+            }
+            None => Err(OllamaError::ConfigError(
+                "No Ollama server configured".to_string(),
+            )),
+        }
+    }
+
+    // Returns the given servers in the order the next request tries them.
+    //
+    // Healthy servers take turns in going first; servers out of rotation come last.
+    fn rotation(&self, servers: &[usize]) -> Vec<&Backend> {
+        let count = servers.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let (healthy, unhealthy): (Vec<&Backend>, Vec<&Backend>) = (0..count)
+            .map(|offset| &self.backends[servers[(start + offset) % count]])
+            .partition(|backend| backend.healthy.load(Ordering::Relaxed));
+        healthy.into_iter().chain(unhealthy).collect()
+    }
+
+    // Probes every server and takes it into or out of rotation accordingly.
+    async fn probe_backends(&self) {
+        future::join_all(self.backends.iter().map(|backend| async move {
+            let healthy = self
+                .client
+                .get(format!("{}/api/version", backend.base_url))
+                .timeout(PROBE_TIMEOUT)
+                .send()
                 .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            error!("Ollama API error: {} - {}", status, message);
-            return Err(OllamaError::ApiError { status, message });
+                .is_ok_and(|response| response.status().is_success());
+            backend.set_healthy(healthy);
+        }))
+        .await;
+    }
+
+    // Checks the version of the server at the base URL and records its compatibility.
+    async fn check_version(&self) {
+        let Some(backend) = self.backends.first() else {
+            return;
+        };
+        let result = async {
+            self.client
+                .get(format!("{}/api/version", backend.base_url))
+                .timeout(PROBE_TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Value>()
+                .await
+        }
+        .await;
+        match result {
+            Ok(body) => match body.get("version").and_then(Value::as_str) {
+                Some(version) => self.compat.record_version(version),
+                None => self
+                    .compat
+                    .record_error("Version missing from response".to_string()),
+            },
+            Err(e) => self.compat.record_error(e.to_string()),
         }
+    }
+}
 
-        debug!("Successfully received response from Ollama API");
-        Ok(response)
+// Starts the background task checking the version of the Ollama servers at
+// startup and then on the given interval.
+//
+// The version is not checked when the mock backend answers requests.
+pub fn spawn_version_checks(client: OllamaClient, interval: Duration) {
+    if client.is_mock() {
+        return;
     }
+
+    tokio::spawn(async move {
+        client.check_version().await;
+        if interval.is_zero() {
+            return;
+        }
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            client.check_version().await;
+        }
+    });
+}
+
+// Starts the background task probing the Ollama servers on the given interval.
+//
+// Probes only run when requests are spread over several servers.
+pub fn spawn_backend_probes(client: OllamaClient, interval: Duration) {
+    if client.backends.len() < 2 || interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            client.probe_backends().await;
+        }
+    });
+}
+
+// Builds the headers sent with every request to the Ollama servers.
+//
+// Credentials are marked sensitive so that they are never logged.
+//
+// # Errors
+//
+// Returns an error if a configured header name or value is invalid
+pub fn upstream_headers(config: &OllamaConfig) -> Result<HeaderMap, OllamaError> {
+    let invalid = |name: &str| OllamaError::ConfigError(format!("Invalid Ollama header {}", name));
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.headers {
+        let mut value = HeaderValue::from_str(value).map_err(|_| invalid(name))?;
+        value.set_sensitive(true);
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid(name))?,
+            value,
+        );
+    }
+
+    let authorization = if !config.bearer_token.is_empty() {
+        Some(format!("Bearer {}", config.bearer_token))
+    } else if !config.basic_auth_user.is_empty() {
+        let credentials = format!("{}:{}", config.basic_auth_user, config.basic_auth_password);
+        Some(format!("Basic {}", STANDARD.encode(credentials)))
+    } else {
+        None
+    };
+    if let Some(authorization) = authorization {
+        let mut value =
+            HeaderValue::from_str(&authorization).map_err(|_| invalid("Authorization"))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    Ok(headers)
+}
+
+// Turns responses with an error status into an `ApiError`.
+async fn check_status(response: Response) -> Result<Response, OllamaError> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let message = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        error!("Ollama API error: {} - {}", status, message);
+        return Err(OllamaError::ApiError { status, message });
+    }
+
+    debug!("Successfully received response from Ollama API");
+    Ok(response)
+}
+
+// Returns the model a request body is for.
+fn model_of(body: &Value) -> Option<String> {
+    body.get("model")
+        .or_else(|| body.get("name"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+// Appends the implicit `:latest` tag to model names without a tag.
+pub(crate) fn with_tag(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("{}:latest", name)
+    }
+}
+
+// Converts a request body to JSON for the mock backend or for routing.
+fn to_value<T: Serialize + ?Sized>(body: &T) -> Value {
+    serde_json::to_value(body).unwrap_or(Value::Null)
 }
@@ -0,0 +1,257 @@
+// Compatibility with the version of the connected Ollama servers.
+//
+// The Ollama API changes between releases, and the proxy is deployed next to
+// servers that are upgraded on their own schedule. The version reported by
+// `GET /api/version` is checked at startup and on the configured interval,
+// and classified against the range of versions the proxy was tested with:
+//
+// - `compatible`: within the tested range
+// - `older_than_tested`: older than the oldest tested version; requests are
+//   still forwarded, but may be rejected by the server
+// - `newer_than_tested`: newer than the newest tested version; requests are
+//   still forwarded, but the API may have changed in ways the proxy does not
+//   know about
+// - `unknown`: the version has not been checked yet, could not be read or is
+//   not recognized, or the mock backend answers in place of Ollama
+//
+// A change of the status is logged once, as a warning when the version is
+// outside the tested range, and the status is reported by `/readyz`. It does
+// not affect readiness.
+//
+// # Adjustments
+//
+// Requests are adjusted to known differences between versions:
+//
+// - `model_field`: model management requests name the model in `model` on
+//   versions that read it, instead of the deprecated `name` that older
+//   versions expect
+//
+// While the version is unknown, requests are sent unchanged.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+// Oldest Ollama version the proxy was tested with.
+const OLDEST_TESTED: Version = Version(0, 3, 0);
+
+// Newest Ollama version the proxy was tested with.
+const NEWEST_TESTED: Version = Version(0, 12, 6);
+
+// First version reading the model from `model` in model management requests.
+const MODEL_FIELD_SINCE: Version = Version(0, 5, 0);
+
+// Endpoints of model management requests naming the model in `name` or `model`.
+const MODEL_MANAGEMENT_ENDPOINTS: [&str; 5] = [
+    "/api/show",
+    "/api/create",
+    "/api/delete",
+    "/api/pull",
+    "/api/push",
+];
+
+// Release version of an Ollama server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(u64, u64, u64);
+
+impl Version {
+    // Parses a version such as `0.12.6`, `v0.5.1` or `0.6.0-rc1`.
+    //
+    // Pre-release and build suffixes are ignored, and missing minor or patch
+    // numbers count as 0.
+    //
+    // # Returns
+    //
+    // The version, or `None` if the value is not a release version
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().trim_start_matches('v');
+        let release = value.split(['-', '+']).next()?;
+        let mut numbers = release.split('.').map(|number| number.parse::<u64>());
+        let major = numbers.next()?.ok()?;
+        let minor = numbers.next().transpose().ok()?.unwrap_or(0);
+        let patch = numbers.next().transpose().ok()?.unwrap_or(0);
+        if numbers.next().is_some() {
+            return None;
+        }
+        Some(Self(major, minor, patch))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+// How the connected Ollama version relates to the versions the proxy was tested with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompatibilityStatus {
+    Compatible,
+    OlderThanTested,
+    NewerThanTested,
+    Unknown,
+}
+
+// Compatibility of the connected Ollama version, as reported by `/readyz`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Compatibility {
+    // How the version relates to the tested versions
+    pub status: CompatibilityStatus,
+
+    // Version as reported by Ollama
+    pub version: Option<String>,
+
+    // Range of versions the proxy was tested with
+    pub tested_versions: String,
+
+    // Adjustments applied to requests for this version
+    pub adjustments: Vec<&'static str>,
+
+    // When the version was last checked
+    pub checked_at: Option<DateTime<Utc>>,
+
+    // Why the version could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Default for Compatibility {
+    fn default() -> Self {
+        Self {
+            status: CompatibilityStatus::Unknown,
+            version: None,
+            tested_versions: format!("{} - {}", OLDEST_TESTED, NEWEST_TESTED),
+            adjustments: Vec::new(),
+            checked_at: None,
+            error: None,
+        }
+    }
+}
+
+// Compatibility state shared by the Ollama client and the health checker.
+//
+// Cloning is cheap and all clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct OllamaCompat {
+    // Latest compatibility and the parsed version it was derived from
+    state: Arc<Mutex<(Compatibility, Option<Version>)>>,
+}
+
+impl OllamaCompat {
+    // Returns the compatibility according to the latest version check.
+    pub fn compatibility(&self) -> Compatibility {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+            .clone()
+    }
+
+    // Records the version reported by Ollama, logging when the status changes.
+    pub fn record_version(&self, reported: &str) {
+        let Some(version) = Version::parse(reported) else {
+            // Requests are sent unchanged to a server whose version is not understood
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.0.version.as_deref() != Some(reported) {
+                warn!("Ollama reports unrecognized version {:?}", reported);
+            }
+            *state = (
+                Compatibility {
+                    version: Some(reported.to_string()),
+                    checked_at: Some(Utc::now()),
+                    error: Some("Unrecognized version".to_string()),
+                    ..Compatibility::default()
+                },
+                None,
+            );
+            return;
+        };
+
+        let status = if version < OLDEST_TESTED {
+            CompatibilityStatus::OlderThanTested
+        } else if version > NEWEST_TESTED {
+            CompatibilityStatus::NewerThanTested
+        } else {
+            CompatibilityStatus::Compatible
+        };
+        let mut adjustments = Vec::new();
+        if version >= MODEL_FIELD_SINCE {
+            adjustments.push("model_field");
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.1 != Some(version) {
+            match status {
+                CompatibilityStatus::Compatible => {
+                    info!("Connected to Ollama {}", version)
+                }
+                CompatibilityStatus::OlderThanTested => warn!(
+                    "Ollama {} is older than {}, the oldest version this proxy was tested \
+                     with; requests may be rejected",
+                    version, OLDEST_TESTED
+                ),
+                _ => warn!(
+                    "Ollama {} is newer than {}, the newest version this proxy was tested \
+                     with; API changes may not be handled",
+                    version, NEWEST_TESTED
+                ),
+            }
+        }
+        state.0 = Compatibility {
+            status,
+            version: Some(reported.to_string()),
+            adjustments,
+            checked_at: Some(Utc::now()),
+            ..Compatibility::default()
+        };
+        state.1 = Some(version);
+    }
+
+    // Records a failed version check.
+    //
+    // A version read earlier stays in effect, so a briefly unreachable server
+    // keeps its adjustments.
+    pub fn record_error(&self, error: String) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.1.is_some() {
+            state.0.checked_at = Some(Utc::now());
+            state.0.error = Some(error);
+            return;
+        }
+        if state.0.error.as_ref() != Some(&error) {
+            warn!("Failed to check the Ollama version: {}", error);
+        }
+        state.0 = Compatibility {
+            checked_at: Some(Utc::now()),
+            error: Some(error),
+            ..Compatibility::default()
+        };
+    }
+
+    // Adjusts a request body for the connected version.
+    //
+    // Bodies are only serialized when an adjustment applies to the endpoint.
+    //
+    // # Returns
+    //
+    // The adjusted body, or `None` if the body is sent unchanged
+    pub fn adjust<T: Serialize + ?Sized>(&self, endpoint: &str, body: &T) -> Option<Value> {
+        let version = self.state.lock().unwrap_or_else(|e| e.into_inner()).1?;
+
+        if version >= MODEL_FIELD_SINCE && MODEL_MANAGEMENT_ENDPOINTS.contains(&endpoint) {
+            let Ok(Value::Object(mut object)) = serde_json::to_value(body) else {
+                return None;
+            };
+            if object.contains_key("model") {
+                return None;
+            }
+            let name = object.remove("name")?;
+            object.insert("model".to_string(), name);
+            return Some(Value::Object(object));
+        }
+        None
+    }
+}
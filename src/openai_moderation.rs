@@ -0,0 +1,155 @@
+// OpenAI Moderation API as an alternative security backend.
+//
+// With the `openai_moderation` security provider, prompts and responses are
+// assessed by OpenAI's moderation endpoint instead of the PANW AI Runtime API.
+// Its verdicts are mapped onto the PANW scan response, so the rest of the
+// security pipeline, including caching, the circuit breaker, the failure mode
+// and security events, works unchanged:
+//
+// - content flagged in a blocking category is blocked as harmful content,
+//   reported as `toxic_content` in the prompt or response findings
+// - the moderation ID becomes the report ID, and the moderation model the
+//   profile name
+// - text and code of a scan are moderated together, and the grounding
+//   context is not moderated
+//
+// The moderation API has no masking, reports or asynchronous scans, so content
+// is never masked, the admin scan report endpoints cannot fetch its results,
+// and scans of streamed responses scanned asynchronously are sent as regular
+// moderation requests.
+use crate::config::OpenAiModerationConfig;
+use crate::types::{ScanRequest, ScanResponse};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::debug;
+
+// Response of the moderation endpoint.
+#[derive(Debug, Deserialize)]
+pub struct ModerationResponse {
+    // Identifier of the moderation
+    #[serde(default)]
+    id: String,
+
+    // Verdicts of the moderated inputs
+    results: Vec<ModerationResult>,
+}
+
+// Verdict of a single moderated input.
+#[derive(Debug, Deserialize)]
+struct ModerationResult {
+    // Whether the input was flagged in any category
+    flagged: bool,
+
+    // Whether the input was flagged, by category
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+// Client of the OpenAI Moderation API.
+#[derive(Debug, Clone)]
+pub struct ModerationClient {
+    // Base URL of the OpenAI API
+    base_url: String,
+
+    // API key for the OpenAI API
+    api_key: String,
+
+    // Moderation model
+    model: String,
+
+    // Categories that block content; empty blocks every flagged category
+    block_categories: Vec<String>,
+}
+
+impl ModerationClient {
+    // Creates a client with the configured endpoint, key and blocking categories.
+    pub fn new(config: &OpenAiModerationConfig) -> Self {
+        Self {
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+            block_categories: config.block_categories.clone(),
+        }
+    }
+
+    // Returns the base URL of the OpenAI API.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Builds the moderation request for the content of a scan.
+    pub fn request(&self, client: &Client, payload: &ScanRequest) -> RequestBuilder {
+        let input = payload
+            .contents
+            .iter()
+            .flat_map(|content| {
+                [
+                    &content.prompt,
+                    &content.code_prompt,
+                    &content.response,
+                    &content.code_response,
+                ]
+            })
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        client
+            .post(format!("{}/v1/moderations", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&json!({ "model": self.model, "input": input }))
+    }
+
+    // Maps a moderation response onto a PANW scan response for the same scan.
+    pub fn scan_response(
+        &self,
+        payload: &ScanRequest,
+        moderation: ModerationResponse,
+    ) -> ScanResponse {
+        let is_prompt = payload
+            .contents
+            .iter()
+            .any(|content| content.prompt.is_some() || content.code_prompt.is_some());
+        let blocked: Vec<&str> = moderation
+            .results
+            .iter()
+            .filter(|result| result.flagged)
+            .flat_map(|result| &result.categories)
+            .filter(|(category, flagged)| **flagged && self.blocks(category))
+            .map(|(category, _)| category.as_str())
+            .collect();
+        if !blocked.is_empty() {
+            debug!("OpenAI moderation flagged {}", blocked.join(", "));
+        }
+
+        let mut response = ScanResponse::default_safe_response();
+        response.report_id = moderation.id;
+        response.scan_id = uuid::Uuid::new_v4();
+        response.tr_id = Some(payload.tr_id.clone());
+        response.profile_name = Some(self.model.clone());
+        if !blocked.is_empty() {
+            response.category = "malicious".to_string();
+            response.action = "block".to_string();
+            if is_prompt {
+                response.prompt_detected.toxic_content = true;
+            } else {
+                response.response_detected.toxic_content = true;
+            }
+        }
+        response
+    }
+
+    // Returns whether content flagged in a category is blocked.
+    fn blocks(&self, category: &str) -> bool {
+        self.block_categories.is_empty()
+            || self.block_categories.iter().any(|blocking| {
+                category == blocking
+                    || category
+                        .strip_prefix(blocking.as_str())
+                        .is_some_and(|sub| sub.starts_with('/'))
+            })
+    }
+}
@@ -0,0 +1,131 @@
+// Passing of selected headers between clients and Ollama.
+//
+// The proxy builds its own requests to Ollama and its own responses to
+// clients, so headers of either side are dropped by default. Integrations that
+// depend on them, such as Ollama plugins reading tracing or tenant headers,
+// list the headers to pass on:
+//
+// - client request headers on the request allowlist are forwarded with every
+//   Ollama request made for the client's request
+// - Ollama response headers on the response allowlist are returned to the
+//   client, unless the proxy sets the same header itself
+//
+// # Scope
+//
+// Headers are exchanged for the duration of the client's request. Ollama
+// requests made outside of one, such as health probes, scheduled prompts and
+// background jobs, carry no client headers.
+use crate::config::PassthroughConfig;
+use crate::AppState;
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+tokio::task_local! {
+    // Headers exchanged for the client request being processed
+    static EXCHANGE: Exchange;
+}
+
+// Headers exchanged between a client request and the Ollama requests made for it.
+#[derive(Clone)]
+struct Exchange {
+    // Allowed client headers forwarded to Ollama
+    request: HeaderMap,
+
+    // Allowed Ollama response headers returned to the client
+    response: Arc<Mutex<HeaderMap>>,
+
+    // Names of the Ollama response headers that are returned
+    response_allowed: Arc<Vec<HeaderName>>,
+}
+
+// Allowlists of the headers passed between clients and Ollama.
+//
+// Cloning the allowlists is cheap and all clones share the same lists.
+#[derive(Clone, Default)]
+pub struct HeaderPassthrough {
+    // Client request headers forwarded to Ollama
+    request: Arc<Vec<HeaderName>>,
+
+    // Ollama response headers returned to clients
+    response: Arc<Vec<HeaderName>>,
+}
+
+impl HeaderPassthrough {
+    // Creates the allowlists for the given configuration.
+    pub fn new(config: &PassthroughConfig) -> Self {
+        let names = |headers: &[String]| {
+            headers
+                .iter()
+                .filter_map(|name| HeaderName::try_from(name.as_str()).ok())
+                .collect()
+        };
+        Self {
+            request: Arc::new(names(&config.request_headers)),
+            response: Arc::new(names(&config.response_headers)),
+        }
+    }
+
+    // Returns whether any header is passed through.
+    fn is_enabled(&self) -> bool {
+        !self.request.is_empty() || !self.response.is_empty()
+    }
+}
+
+// Returns the client headers to forward with an Ollama request.
+pub fn request_headers() -> HeaderMap {
+    EXCHANGE
+        .try_with(|exchange| exchange.request.clone())
+        .unwrap_or_default()
+}
+
+// Keeps the allowed headers of an Ollama response for the client's response.
+pub fn record_response_headers(headers: &HeaderMap) {
+    let _ = EXCHANGE.try_with(|exchange| {
+        let mut response = exchange.response.lock().unwrap_or_else(|e| e.into_inner());
+        for name in exchange.response_allowed.iter() {
+            if let Some(value) = headers.get(name) {
+                response.insert(name.clone(), value.clone());
+            }
+        }
+    });
+}
+
+// Middleware passing the allowed headers between the client and Ollama.
+pub async fn pass_headers(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let passthrough = &state.header_passthrough;
+    if !passthrough.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let mut forwarded = HeaderMap::new();
+    for name in passthrough.request.iter() {
+        for value in request.headers().get_all(name) {
+            forwarded.append(name.clone(), value.clone());
+        }
+    }
+    if !forwarded.is_empty() {
+        debug!("Forwarding {} client headers to Ollama", forwarded.len());
+    }
+
+    let exchange = Exchange {
+        request: forwarded,
+        response: Arc::new(Mutex::new(HeaderMap::new())),
+        response_allowed: passthrough.response.clone(),
+    };
+    let returned = exchange.response.clone();
+    let mut response = EXCHANGE.scope(exchange, next.run(request)).await;
+
+    let returned = std::mem::take(&mut *returned.lock().unwrap_or_else(|e| e.into_inner()));
+    for (name, value) in returned.iter() {
+        if !response.headers().contains_key(name) {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
@@ -0,0 +1,171 @@
+// Local masking of personal data in prompts.
+//
+// Some content must never leave the host, not even to be scanned. With PII
+// masking enabled, prompts are masked locally before they are scanned or sent
+// to Ollama; each match is replaced with a `[REDACTED:<entity>]` marker and
+// the masked prompt is used for the rest of the request.
+//
+// # Detected Entities
+//
+// - `email`: email addresses
+// - `phone`: phone numbers in international (`+49 30 1234567`) or North
+//   American (`(555) 123-4567`) notation
+// - `ssn`: US social security numbers in `123-45-6789` notation, excluding
+//   numbers that are never issued
+// - `credit_card`: payment card numbers of 13 to 19 digits, optionally
+//   grouped with spaces or dashes, that pass the Luhn check
+// - `iban`: international bank account numbers, optionally grouped with
+//   spaces, that pass the mod-97 check
+//
+// Card and account numbers failing their check digits are left alone, which
+// keeps order numbers and similar digit runs intact. Masked entities are
+// logged and counted in `panw_pii_masked_total` by `entity`.
+use crate::config::{PiiConfig, PiiEntity};
+use crate::metrics;
+use regex::{Captures, Regex};
+use tracing::info;
+
+// Patterns of the entity types in masking order; card and account numbers go
+// before phone numbers so their digit groups are not taken for one.
+const ENTITIES: [(PiiEntity, &str); 6] = [
+    (
+        PiiEntity::Email,
+        r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b",
+    ),
+    (
+        PiiEntity::Iban,
+        r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b",
+    ),
+    (PiiEntity::CreditCard, r"\b\d(?:[ -]?\d){12,18}\b"),
+    (PiiEntity::Ssn, r"\b(\d{3})-(\d{2})-(\d{4})\b"),
+    (
+        PiiEntity::Phone,
+        r"\+\d{1,3}[ .-]?(?:\(\d{1,4}\)[ .-]?)?\d{1,4}(?:[ .-]?\d{2,4}){1,4}\b",
+    ),
+    (
+        PiiEntity::Phone,
+        r"(?:\(\d{3}\) ?|\b\d{3}[ .-])\d{3}[ .-]\d{4}\b",
+    ),
+];
+
+// Masks personal data of the configured types in text.
+pub struct PiiMasker {
+    // Patterns of the configured entity types, in masking order
+    patterns: Vec<(PiiEntity, Regex)>,
+}
+
+impl PiiMasker {
+    // Compiles the patterns of the configured entity types.
+    pub fn new(config: &PiiConfig) -> Self {
+        Self {
+            patterns: ENTITIES
+                .iter()
+                .filter(|(entity, _)| config.entities.contains(entity))
+                .map(|(entity, pattern)| (*entity, Regex::new(pattern).expect("valid PII pattern")))
+                .collect(),
+        }
+    }
+
+    // Replaces personal data in the text with `[REDACTED:<entity>]` markers.
+    //
+    // # Returns
+    //
+    // The masked text and the entity types that were masked
+    pub fn mask(&self, text: &str) -> (String, Vec<PiiEntity>) {
+        let mut masked = text.to_string();
+        let mut found = Vec::new();
+
+        for (entity, pattern) in &self.patterns {
+            let mut matched = false;
+            let replaced = pattern.replace_all(&masked, |captures: &Captures| {
+                if is_valid(*entity, captures) {
+                    matched = true;
+                    format!("[REDACTED:{}]", entity.as_str())
+                } else {
+                    captures[0].to_string()
+                }
+            });
+            if matched {
+                masked = replaced.into_owned();
+                if !found.contains(entity) {
+                    found.push(*entity);
+                }
+            }
+        }
+
+        (masked, found)
+    }
+}
+
+// Checks a candidate match beyond its pattern.
+fn is_valid(entity: PiiEntity, captures: &Captures) -> bool {
+    let candidate = &captures[0];
+    match entity {
+        PiiEntity::Email => true,
+        PiiEntity::Phone => {
+            let digits = candidate.chars().filter(char::is_ascii_digit).count();
+            (7..=15).contains(&digits)
+        }
+        PiiEntity::Ssn => {
+            let area = &captures[1];
+            area != "000"
+                && area != "666"
+                && !area.starts_with('9')
+                && &captures[2] != "00"
+                && &captures[3] != "0000"
+        }
+        PiiEntity::CreditCard => luhn(candidate),
+        PiiEntity::Iban => iban_checksum(candidate),
+    }
+}
+
+// Returns whether a card number passes the Luhn check.
+fn luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match (index % 2, digit * 2) {
+            (0, _) => *digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+// Returns whether an account number passes the IBAN mod-97 check.
+fn iban_checksum(candidate: &str) -> bool {
+    let iban: Vec<char> = candidate.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+    let mut remainder = 0u32;
+    for c in iban[4..].iter().chain(&iban[..4]) {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+// Records and logs personal data masked in a prompt.
+pub fn record_findings(entities: &[PiiEntity]) {
+    for entity in entities {
+        info!("Masked {} in prompt before scanning", entity.as_str());
+        metrics::increment_counter(
+            "panw_pii_masked_total",
+            "Prompts with personal data masked by the local PII stage",
+            &[("entity", entity.as_str())],
+        );
+    }
+}
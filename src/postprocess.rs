@@ -0,0 +1,416 @@
+// Post-processing of model responses after security assessment.
+//
+// Rewrites applied here never influence the security verdict: they run on
+// content that has already been assessed as safe, just before it is handed to
+// the client, in both the streaming and the non-streaming paths.
+//
+// # Filters
+//
+// The configured filters run in order:
+//
+// - `strip_markdown_images`: removes `![alt](url)` and `![alt][ref]` images
+// - `strip_html`: removes HTML tags and comments, and script and style blocks
+//   together with their contents
+// - `collapse_whitespace`: collapses runs of spaces and tabs into one space and
+//   more than two consecutive newlines into two
+// - `strip_leakage`: removes leaked system prompts, see below
+//
+// # Leakage
+//
+// Models sometimes echo their system prompt, or the chat template turn holding
+// it, back to the user. The `strip_leakage` filter removes text matching the
+// configured leakage patterns, or the built-in patterns for the system turns of
+// the Llama 2, ChatML and Llama 3 templates and for lines announcing a system
+// prompt. System prompts the proxy itself sends, such as the summarization
+// prompt, are removed whenever they are echoed verbatim. Removals are counted
+// in `panw_leakage_stripped_total`.
+//
+// Streamed responses are checked batch by batch. When leaked text runs to the
+// end of a batch, the rest of its line is removed from the next batch as well.
+//
+// # URL Rewriting
+//
+// Links suggested by a model can be rewritten to reduce click-through risk:
+//
+// - `defang`: `https://example.com/a` becomes `hxxps://example[.]com/a`
+// - `redirect`: the URL is wrapped in an operator-provided safe-redirect
+//   service, replacing `{url}` in the template with the percent-encoded URL
+//
+// # Length Limit
+//
+// Responses longer than the configured maximum are cut off and end with the
+// truncation notice; streamed content after the cut-off is dropped.
+//
+// # Streaming
+//
+// Streamed responses are processed batch by batch. State that spans batches,
+// such as an unterminated script block or the number of characters already
+// sent, is kept by the caller in a `PostProcessState`.
+use crate::config::{PostProcessConfig, ResponseFilter, UrlRewriteMode};
+use crate::metrics;
+use regex::{Captures, Regex};
+use std::sync::Arc;
+use tracing::debug;
+
+// Pattern matching URLs in response text.
+const URL_PATTERN: &str = r#"\b(?i:https?|ftp)://[^\s<>"'()\[\]{}`]+"#;
+
+// Pattern matching inline and reference-style markdown images.
+const MARKDOWN_IMAGE_PATTERN: &str = r"!\[[^\]\n]*\](?:\([^)\n]*\)|\[[^\]\n]*\])";
+
+// Pattern matching a script or style block, or its beginning when the closing tag is missing.
+const HTML_BLOCK_PATTERN: &str = r"(?is)<(?:script|style)\b[^>]*>(?:.*?</(?:script|style)\s*>|.*)";
+
+// Pattern matching the remainder of a script or style block up to and including its closing tag.
+const HTML_BLOCK_END_PATTERN: &str = r"(?is)^.*?</(?:script|style)\s*>";
+
+// Pattern matching HTML comments and tags.
+const HTML_TAG_PATTERN: &str = r"(?s)<!--.*?-->|</?[A-Za-z][A-Za-z0-9-]*(?:\s[^<>]*)?/?>";
+
+// Trailing characters treated as sentence punctuation rather than part of a URL.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?'];
+
+// Placeholder replaced with the encoded URL in redirect templates.
+const URL_PLACEHOLDER: &str = "{url}";
+
+// Patterns matching leaked system prompts unless others are configured.
+const DEFAULT_LEAKAGE_PATTERNS: [&str; 4] = [
+    // Llama 2 system block
+    r"(?s)<<SYS>>.*?(?:<</SYS>>|\z)",
+    // ChatML system turn
+    r"(?s)<\|im_start\|>\s*system\b.*?(?:<\|im_end\|>|\z)",
+    // Llama 3 system turn
+    r"(?s)<\|start_header_id\|>\s*system\s*<\|end_header_id\|>.*?(?:<\|eot_id\|>|\z)",
+    // Line announcing the system prompt
+    r"(?im)^[ \t]*(?:my |the )?system (?:prompt|instructions|message)[ \t]*:.*$\n?",
+];
+
+// Compiled patterns used by the post-processor.
+struct Patterns {
+    url: Regex,
+    markdown_image: Regex,
+    html_block: Regex,
+    html_block_end: Regex,
+    html_tag: Regex,
+}
+
+// Post-processing state carried between the batches of a streamed response.
+//
+// Non-streaming responses use a fresh default state.
+#[derive(Debug, Default)]
+pub struct PostProcessState {
+    // Whether a script or style block continues in the next batch
+    html_block_open: bool,
+
+    // Whether the last character sent was a collapsed space or tab
+    after_space: bool,
+
+    // Number of consecutive newlines sent last
+    newline_run: usize,
+
+    // Number of characters sent so far
+    sent_chars: usize,
+
+    // Whether the response has been cut off at the maximum length
+    truncated: bool,
+
+    // Whether leaked text runs on to the end of its line in the next batch
+    leak_line_open: bool,
+}
+
+// Applies the configured rewrites to assessed response text.
+//
+// Cloning the post-processor is cheap.
+#[derive(Clone)]
+pub struct ResponsePostProcessor {
+    // Filters applied in order
+    filters: Vec<ResponseFilter>,
+
+    // How URLs are rewritten
+    url_mode: UrlRewriteMode,
+
+    // Safe-redirect template used in redirect mode
+    redirect_template: String,
+
+    // Maximum number of characters per response, 0 for unlimited
+    max_length: usize,
+
+    // Notice appended when a response is cut off
+    truncation_notice: String,
+
+    // Patterns matching leaked system prompts
+    leakage: Arc<Vec<Regex>>,
+
+    // Compiled patterns
+    patterns: Arc<Patterns>,
+}
+
+impl ResponsePostProcessor {
+    // Creates a post-processor for the given configuration.
+    pub fn new(config: &PostProcessConfig) -> Self {
+        let compile = |pattern| Regex::new(pattern).expect("valid post-processing pattern");
+
+        Self {
+            filters: config.filters.clone(),
+            url_mode: config.url_mode,
+            redirect_template: config.url_redirect_template.clone(),
+            max_length: config.max_length,
+            truncation_notice: config.truncation_notice.clone(),
+            leakage: Arc::new(if config.leakage_patterns.is_empty() {
+                DEFAULT_LEAKAGE_PATTERNS.iter().copied().map(compile).collect()
+            } else {
+                // Validated with the configuration
+                config
+                    .leakage_patterns
+                    .iter()
+                    .filter_map(|pattern| Regex::new(pattern).ok())
+                    .collect()
+            }),
+            patterns: Arc::new(Patterns {
+                url: compile(URL_PATTERN),
+                markdown_image: compile(MARKDOWN_IMAGE_PATTERN),
+                html_block: compile(HTML_BLOCK_PATTERN),
+                html_block_end: compile(HTML_BLOCK_END_PATTERN),
+                html_tag: compile(HTML_TAG_PATTERN),
+            }),
+        }
+    }
+
+    // Adds system prompts sent by the proxy, removed by the `strip_leakage`
+    // filter when a model echoes them. Whitespace may differ in the echo.
+    pub fn with_system_prompts(mut self, prompts: &[&str]) -> Self {
+        let mut leakage = self.leakage.as_ref().clone();
+        for prompt in prompts {
+            let words: Vec<String> = prompt.split_whitespace().map(regex::escape).collect();
+            if words.is_empty() {
+                continue;
+            }
+            if let Ok(pattern) = Regex::new(&format!("(?i){}", words.join(r"\s+"))) {
+                leakage.push(pattern);
+            }
+        }
+        self.leakage = Arc::new(leakage);
+        self
+    }
+
+    // Applies all configured rewrites to a piece of response text.
+    //
+    // # Arguments
+    //
+    // * `text` - Text to process
+    // * `state` - State carried over from earlier batches of the same response
+    //
+    // # Returns
+    //
+    // The rewritten text, or None if nothing was changed
+    pub fn process(&self, text: &str, state: &mut PostProcessState) -> Option<String> {
+        let mut processed = text.to_string();
+
+        for filter in &self.filters {
+            processed = match filter {
+                ResponseFilter::StripMarkdownImages => self
+                    .patterns
+                    .markdown_image
+                    .replace_all(&processed, "")
+                    .into_owned(),
+                ResponseFilter::StripHtml => self.strip_html(&processed, state),
+                ResponseFilter::CollapseWhitespace => collapse_whitespace(&processed, state),
+                ResponseFilter::StripLeakage => self.strip_leakage(&processed, state),
+            };
+        }
+
+        if let Some(rewritten) = self.rewrite_urls(&processed) {
+            processed = rewritten;
+        }
+
+        processed = self.enforce_max_length(processed, state);
+
+        (processed != text).then_some(processed)
+    }
+
+    // Removes HTML tags, comments and script and style blocks.
+    fn strip_html(&self, text: &str, state: &mut PostProcessState) -> String {
+        let mut stripped = text.to_string();
+
+        if state.html_block_open {
+            match self.patterns.html_block_end.find(&stripped) {
+                Some(end) => {
+                    stripped.replace_range(..end.end(), "");
+                    state.html_block_open = false;
+                }
+                None => return String::new(),
+            }
+        }
+
+        // A block running to the end of the text continues in the next batch
+        state.html_block_open = self
+            .patterns
+            .html_block
+            .find_iter(&stripped)
+            .last()
+            .is_some_and(|m| !self.patterns.html_block_end.is_match(m.as_str()));
+        stripped = self.patterns.html_block.replace_all(&stripped, "").into_owned();
+
+        self.patterns.html_tag.replace_all(&stripped, "").into_owned()
+    }
+
+    // Removes text matching the leakage patterns.
+    fn strip_leakage(&self, text: &str, state: &mut PostProcessState) -> String {
+        let mut stripped = text.to_string();
+
+        if state.leak_line_open {
+            match stripped.find('\n') {
+                Some(end) => {
+                    stripped.replace_range(..end, "");
+                    state.leak_line_open = false;
+                }
+                None => return String::new(),
+            }
+        }
+
+        let mut removed = 0;
+        for pattern in self.leakage.iter() {
+            let ends: Vec<usize> = pattern
+                .find_iter(&stripped)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.end())
+                .collect();
+            let Some(last_end) = ends.last() else {
+                continue;
+            };
+            removed += ends.len();
+            state.leak_line_open |= *last_end == stripped.len() && !stripped.ends_with('\n');
+            stripped = pattern.replace_all(&stripped, "").into_owned();
+        }
+
+        if removed > 0 {
+            debug!("Removed {} leaked system prompts from a response", removed);
+            for _ in 0..removed {
+                metrics::increment_counter(
+                    "panw_leakage_stripped_total",
+                    "Leaked system prompts removed from responses",
+                    &[],
+                );
+            }
+        }
+        stripped
+    }
+
+    // Cuts the text off once the response exceeds the maximum length.
+    fn enforce_max_length(&self, text: String, state: &mut PostProcessState) -> String {
+        if self.max_length == 0 {
+            return text;
+        }
+        if state.truncated {
+            return String::new();
+        }
+
+        let remaining = self.max_length.saturating_sub(state.sent_chars);
+        match text.char_indices().nth(remaining) {
+            Some((cut, _)) => {
+                state.truncated = true;
+                state.sent_chars = self.max_length;
+                format!("{}{}", &text[..cut], self.truncation_notice)
+            }
+            None => {
+                state.sent_chars += text.chars().count();
+                text
+            }
+        }
+    }
+
+    // Rewrites all URLs in the text according to the configured mode.
+    fn rewrite_urls(&self, text: &str) -> Option<String> {
+        if self.url_mode == UrlRewriteMode::None || !self.patterns.url.is_match(text) {
+            return None;
+        }
+
+        let rewritten = self.patterns.url.replace_all(text, |caps: &Captures| {
+            let matched = &caps[0];
+            let url = matched.trim_end_matches(TRAILING_PUNCTUATION);
+            let trailing = &matched[url.len()..];
+            let url = match self.url_mode {
+                UrlRewriteMode::Defang => defang_url(url),
+                UrlRewriteMode::Redirect => self.redirect_url(url),
+                UrlRewriteMode::None => url.to_string(),
+            };
+            format!("{}{}", url, trailing)
+        });
+
+        Some(rewritten.into_owned())
+    }
+
+    // Wraps a URL in the configured safe-redirect service.
+    fn redirect_url(&self, url: &str) -> String {
+        let encoded = percent_encode(url);
+        if self.redirect_template.contains(URL_PLACEHOLDER) {
+            self.redirect_template.replace(URL_PLACEHOLDER, &encoded)
+        } else {
+            format!("{}{}", self.redirect_template, encoded)
+        }
+    }
+}
+
+// Collapses runs of spaces and tabs into one space, and more than two
+// consecutive newlines into two.
+fn collapse_whitespace(text: &str, state: &mut PostProcessState) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            ' ' | '\t' => {
+                if !state.after_space {
+                    collapsed.push(' ');
+                }
+                state.after_space = true;
+            }
+            '\n' => {
+                state.newline_run += 1;
+                if state.newline_run <= 2 {
+                    collapsed.push('\n');
+                }
+                state.after_space = false;
+            }
+            '\r' => {}
+            _ => {
+                collapsed.push(c);
+                state.after_space = false;
+                state.newline_run = 0;
+            }
+        }
+    }
+
+    collapsed
+}
+
+// Converts a URL to its defanged form, e.g. `hxxps://example[.]com/path`.
+fn defang_url(url: &str) -> String {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+
+    let scheme = match scheme.to_ascii_lowercase().as_str() {
+        "http" => "hxxp".to_string(),
+        "https" => "hxxps".to_string(),
+        "ftp" => "fxp".to_string(),
+        other => other.to_string(),
+    };
+
+    let host_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (host, path) = rest.split_at(host_end);
+
+    format!("{}://{}{}", scheme, host.replace('.', "[.]"), path)
+}
+
+// Percent-encodes everything except unreserved URL characters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() * 3);
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
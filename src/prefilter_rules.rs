@@ -0,0 +1,138 @@
+// Local regex and keyword pre-filter rules.
+//
+// Much traffic is obviously benign or obviously unacceptable, and a scan adds
+// latency and uses quota either way. Operators define rules matching content
+// with regular expressions and keywords, each deciding what happens to the
+// content it matches before any scan:
+//
+// - `block`: the content is blocked without a scan, reported as a topic
+//   violation
+// - `allow`: the content is allowed without a scan; allow rules only match
+//   when a pattern matches, or a keyword is, the whole trimmed content, so a
+//   greeting rule cannot wave through a longer prompt starting with a greeting
+// - `deep_scan`: the content is scanned afresh even for trusted clients,
+//   without reusing a cached or concurrent verdict
+//
+// When several rules match, a block beats a deep scan, which beats an allow.
+// The language policy and the toxicity pre-filter run first, so an allow rule
+// never overrides them. Matches are logged and counted in
+// `panw_prefilter_rule_matches_total` by `rule` and `action`.
+use crate::config::{PrefilterAction, PrefilterRule, PrefilterRulesConfig, PrefilterStage};
+use crate::metrics;
+use regex::Regex;
+use tracing::debug;
+
+// A compiled rule.
+struct Rule {
+    // Name reported in logs and metrics
+    name: String,
+
+    // What happens to matching content
+    action: PrefilterAction,
+
+    // Content the rule applies to
+    applies_to: PrefilterStage,
+
+    // Patterns and keywords as regular expressions
+    matchers: Vec<Regex>,
+}
+
+impl Rule {
+    // Compiles a validated rule.
+    fn new(config: &PrefilterRule) -> Self {
+        let whole = config.action == PrefilterAction::Allow;
+        let mut sources: Vec<String> = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                if whole {
+                    format!(r"\A(?:{})\z", pattern)
+                } else {
+                    pattern.clone()
+                }
+            })
+            .collect();
+        if !config.keywords.is_empty() {
+            let keywords = config
+                .keywords
+                .iter()
+                .map(|keyword| {
+                    let words: Vec<String> =
+                        keyword.split_whitespace().map(regex::escape).collect();
+                    words.join(r"\s+")
+                })
+                .collect::<Vec<_>>()
+                .join("|");
+            sources.push(if whole {
+                format!(r"(?i)\A(?:{})[.!?]*\z", keywords)
+            } else {
+                format!(r"(?i)\b(?:{})\b", keywords)
+            });
+        }
+        Self {
+            name: config.name.clone(),
+            action: config.action,
+            applies_to: config.applies_to,
+            matchers: sources
+                .iter()
+                .filter_map(|source| Regex::new(source).ok())
+                .collect(),
+        }
+    }
+
+    // Returns whether the rule matches trimmed content.
+    fn matches(&self, content: &str, is_prompt: bool) -> bool {
+        let applies = match self.applies_to {
+            PrefilterStage::Prompts => is_prompt,
+            PrefilterStage::Responses => !is_prompt,
+            PrefilterStage::Both => true,
+        };
+        applies && self.matchers.iter().any(|matcher| matcher.is_match(content))
+    }
+}
+
+// Matches content against the configured pre-filter rules.
+pub struct PrefilterRules {
+    // Rules in configuration order
+    rules: Vec<Rule>,
+}
+
+impl PrefilterRules {
+    // Compiles the configured rules.
+    pub fn new(config: &PrefilterRulesConfig) -> Self {
+        Self {
+            rules: config.rules.iter().map(Rule::new).collect(),
+        }
+    }
+
+    // Decides content by the rules matching it.
+    //
+    // # Returns
+    //
+    // The strongest action of the matching rules, or `None` if no rule matches
+    pub fn check(&self, content: &str, is_prompt: bool) -> Option<PrefilterAction> {
+        let content = content.trim();
+        let matched = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(content, is_prompt))
+            .max_by_key(|rule| rule.action)?;
+        let action = match matched.action {
+            PrefilterAction::Allow => "allow",
+            PrefilterAction::DeepScan => "deep_scan",
+            PrefilterAction::Block => "block",
+        };
+        debug!(
+            "Pre-filter rule {} matched the {}, deciding {}",
+            matched.name,
+            if is_prompt { "prompt" } else { "response" },
+            action
+        );
+        metrics::increment_counter(
+            "panw_prefilter_rule_matches_total",
+            "Content decided by a local pre-filter rule",
+            &[("rule", matched.name.as_str()), ("action", action)],
+        );
+        Some(matched.action)
+    }
+}
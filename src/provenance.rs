@@ -0,0 +1,360 @@
+// Signed provenance manifests for generated responses.
+//
+// When enabled, every generation response is assigned a request ID, returned in
+// the `X-Request-Id` header, and a detached manifest describing the response is
+// signed with an Ed25519 key once the response has been fully delivered. The
+// request ID is the transaction ID of the request, so manifests can be looked
+// up by the ID a client supplied.
+//
+// # Manifest Contents
+//
+// - Request ID, endpoint and model
+// - SHA-256 hash of the exact response body sent to the client
+// - PANW scan report IDs of all assessments performed for the request
+// - Timestamps for when the request was received and the response completed
+//
+// # Verification
+//
+// The signed manifest returned by `GET /api/provenance/{request_id}` carries the
+// manifest as readable JSON, the exact signed bytes as base64 `payload`, the
+// base64 `signature` over those bytes and the base64 `public_key` to verify it.
+//
+// Manifests are kept in memory for the configured retention period.
+use crate::config::ProvenanceConfig;
+use crate::handlers::ApiError;
+use crate::transaction::TransactionId;
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body, BodyDataStream},
+    extract::{Request, State},
+    http::HeaderValue,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{pkcs8::DecodePrivateKey, Signer, SigningKey};
+use futures_util::Stream;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+// Largest request body read to determine the model of a request.
+const MAX_REQUEST_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+// Response header carrying the request ID.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Signature algorithm reported in signed manifests.
+const SIGNATURE_ALGORITHM: &str = "Ed25519";
+
+// Errors raised while setting up manifest signing.
+#[derive(Debug, Error)]
+pub enum ProvenanceError {
+    // The configured signing key could not be loaded
+    #[error("Failed to load provenance signing key from {0}: {1}")]
+    KeyFile(String, String),
+}
+
+// Report IDs of the PANW scans performed for a single request.
+//
+// Cloning the trail is cheap and all clones record into the same list.
+#[derive(Clone, Default)]
+pub struct ScanTrail {
+    report_ids: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScanTrail {
+    // Records the report ID of a completed scan.
+    pub fn record(&self, report_id: &str) {
+        if report_id.is_empty() {
+            return;
+        }
+        self.report_ids
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(report_id.to_string());
+    }
+
+    // Returns the report IDs recorded so far.
+    fn report_ids(&self) -> Vec<String> {
+        self.report_ids
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+// Provenance details of a single generated response.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseManifest {
+    // Request ID returned to the client in the X-Request-Id header
+    pub request_id: String,
+
+    // Endpoint that produced the response
+    pub endpoint: String,
+
+    // Model requested by the client
+    pub model: String,
+
+    // Hex-encoded SHA-256 hash of the response body
+    pub content_sha256: String,
+
+    // PANW scan report IDs of the assessments performed for the request
+    pub scan_report_ids: Vec<String>,
+
+    // When the proxy received the request
+    pub request_received_at: DateTime<Utc>,
+
+    // When the last byte of the response was delivered
+    pub response_completed_at: DateTime<Utc>,
+}
+
+// A manifest together with its detached signature.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedManifest {
+    // The manifest in readable form
+    pub manifest: ResponseManifest,
+
+    // Base64 of the exact JSON bytes that were signed
+    pub payload: String,
+
+    // Signature algorithm
+    pub algorithm: &'static str,
+
+    // Base64 signature over the payload bytes
+    pub signature: String,
+
+    // Base64 public key verifying the signature
+    pub public_key: String,
+}
+
+// A signed manifest waiting to be retrieved.
+struct StoredManifest {
+    manifest: SignedManifest,
+    expires_at: Instant,
+}
+
+// Signs response manifests and keeps them retrievable by request ID.
+//
+// Cloning the service is cheap and all clones share the same manifests.
+#[derive(Clone)]
+pub struct ProvenanceService {
+    // Key used to sign manifests; `None` when provenance is disabled
+    signing_key: Option<Arc<SigningKey>>,
+
+    // How long signed manifests are kept
+    retention: Duration,
+
+    // Signed manifests keyed by request ID
+    manifests: Arc<Mutex<HashMap<String, StoredManifest>>>,
+}
+
+impl ProvenanceService {
+    // Creates the service for the given configuration.
+    //
+    // Without a configured key file an ephemeral key is generated, so manifests
+    // signed before a restart can no longer be verified against the new key.
+    //
+    // # Errors
+    //
+    // Returns an error if the configured PKCS#8 PEM key file cannot be loaded.
+    pub fn new(config: &ProvenanceConfig) -> Result<Self, ProvenanceError> {
+        if !config.enabled {
+            return Ok(Self::disabled());
+        }
+
+        let signing_key = if config.signing_key_file.is_empty() {
+            warn!("No provenance signing key configured, using an ephemeral key");
+            SigningKey::generate(&mut rand::rngs::OsRng)
+        } else {
+            SigningKey::read_pkcs8_pem_file(&config.signing_key_file).map_err(|e| {
+                ProvenanceError::KeyFile(config.signing_key_file.clone(), e.to_string())
+            })?
+        };
+
+        info!(
+            "Signing response manifests with public key {}",
+            BASE64.encode(signing_key.verifying_key().as_bytes())
+        );
+
+        Ok(Self {
+            signing_key: Some(Arc::new(signing_key)),
+            retention: Duration::from_secs(config.retention_secs),
+            manifests: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    // Creates a service that does not sign or store manifests.
+    pub fn disabled() -> Self {
+        Self {
+            signing_key: None,
+            retention: Duration::ZERO,
+            manifests: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns whether manifests are produced.
+    pub fn is_enabled(&self) -> bool {
+        self.signing_key.is_some()
+    }
+
+    // Returns the signed manifest for a request, if it is still retained.
+    pub fn get(&self, request_id: &str) -> Option<SignedManifest> {
+        let manifests = self.manifests.lock().unwrap_or_else(|e| e.into_inner());
+        manifests
+            .get(request_id)
+            .filter(|stored| stored.expires_at > Instant::now())
+            .map(|stored| stored.manifest.clone())
+    }
+
+    // Signs a manifest and stores it under its request ID.
+    //
+    // Expired manifests are pruned on every call.
+    fn record(&self, manifest: ResponseManifest) {
+        let Some(signing_key) = &self.signing_key else {
+            return;
+        };
+
+        let payload = match serde_json::to_vec(&manifest) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to serialize response manifest: {}", e);
+                return;
+            }
+        };
+        let signature = signing_key.sign(&payload);
+
+        let request_id = manifest.request_id.clone();
+        let signed = SignedManifest {
+            manifest,
+            payload: BASE64.encode(&payload),
+            algorithm: SIGNATURE_ALGORITHM,
+            signature: BASE64.encode(signature.to_bytes()),
+            public_key: BASE64.encode(signing_key.verifying_key().as_bytes()),
+        };
+
+        let now = Instant::now();
+        let mut manifests = self.manifests.lock().unwrap_or_else(|e| e.into_inner());
+        manifests.retain(|_, stored| stored.expires_at > now);
+        manifests.insert(
+            request_id.clone(),
+            StoredManifest {
+                manifest: signed,
+                expires_at: now + self.retention,
+            },
+        );
+
+        debug!("Signed response manifest for request {}", request_id);
+    }
+}
+
+// Response body that forwards chunks unchanged while hashing them.
+//
+// Once the inner body ends, the manifest is completed, signed and stored.
+struct HashingBody {
+    inner: BodyDataStream,
+    hasher: Sha256,
+    manifest: Option<ResponseManifest>,
+    trail: ScanTrail,
+    service: ProvenanceService,
+}
+
+impl Stream for HashingBody {
+    type Item = Result<Bytes, axum::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_next(cx);
+
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => this.hasher.update(chunk),
+            Poll::Ready(Some(Err(_))) => {
+                // A truncated response gets no manifest
+                this.manifest = None;
+            }
+            Poll::Ready(None) => {
+                if let Some(mut manifest) = this.manifest.take() {
+                    manifest.content_sha256 =
+                        format!("{:x}", std::mem::take(&mut this.hasher).finalize());
+                    manifest.scan_report_ids = this.trail.report_ids();
+                    manifest.response_completed_at = Utc::now();
+                    this.service.record(manifest);
+                }
+            }
+            Poll::Pending => {}
+        }
+
+        poll
+    }
+}
+
+// Middleware assigning request IDs and producing signed manifests for responses.
+//
+// A `ScanTrail` is added to the request extensions so that handlers can collect
+// the report IDs of the scans they perform.
+pub async fn record_provenance(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.provenance.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let request_id = match request.extensions().get::<TransactionId>() {
+        Some(TransactionId(Some(id))) => id.clone(),
+        _ => Uuid::new_v4().to_string(),
+    };
+    let request_received_at = Utc::now();
+    let endpoint = request.uri().path().to_string();
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ApiError::BadRequest(format!("Failed to read request body: {}", e))
+                .into_response();
+        }
+    };
+    let model = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|value| value.get("model")?.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let trail = ScanTrail::default();
+    let mut request = Request::from_parts(parts, Body::from(body));
+    request.extensions_mut().insert(trail.clone());
+
+    let response = next.run(request).await;
+    let (mut parts, body) = response.into_parts();
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        parts.headers.insert(REQUEST_ID_HEADER, value);
+    }
+
+    let hashing = HashingBody {
+        inner: body.into_data_stream(),
+        hasher: Sha256::new(),
+        manifest: Some(ResponseManifest {
+            request_id,
+            endpoint,
+            model,
+            content_sha256: String::new(),
+            scan_report_ids: Vec::new(),
+            request_received_at,
+            response_completed_at: request_received_at,
+        }),
+        trail,
+        service: state.provenance.clone(),
+    };
+
+    Response::from_parts(parts, Body::from_stream(hashing))
+}
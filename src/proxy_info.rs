@@ -0,0 +1,335 @@
+// Self-description of the proxy's enabled subsystems.
+//
+// Deployments differ widely in which optional subsystems they enable. The
+// description built here is logged as a banner at startup and served at
+// `GET /api/proxy/capabilities`, so client tooling and support scripts can
+// adapt to a deployment's feature set without reading its configuration.
+//
+// # Secrets
+//
+// The description only says which subsystems are enabled and how, never with
+// which keys, tokens or endpoints.
+use crate::config::{Config, FailureMode, OllamaProvider, SecurityProvider, StreamingStrategy};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::info;
+
+// How streamed responses are assessed before they reach the client.
+const STREAMING_STRATEGY: &str = "assess_before_release";
+
+// How streamed responses are assessed when scanned asynchronously.
+const ASYNC_STREAMING_STRATEGY: &str = "release_within_risk_window";
+
+// Enabled subsystems of the proxy.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyCapabilities {
+    // Version of the proxy
+    pub version: &'static str,
+
+    // Model backends requests are forwarded to
+    pub providers: Vec<&'static str>,
+
+    // Backend assessing prompts and responses: `panw`, `openai_moderation`,
+    // `bedrock_guardrails`, `azure_content_safety`, `llama_guard`, or `fan_out`
+    // when several backends scan in parallel
+    pub security_provider: &'static str,
+
+    // Backends scanning in parallel, when several assess content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security_fan_out: Option<FanOutCapabilities>,
+
+    // Client authentication
+    pub auth: AuthCapabilities,
+
+    // Handling of streamed responses
+    pub streaming: StreamingCapabilities,
+
+    // Replays of earlier responses instead of new generations
+    pub caching: CachingCapabilities,
+
+    // Records kept about requests and scans
+    pub audit: AuditCapabilities,
+
+    // Optional subsystems by name and whether they are enabled
+    pub features: BTreeMap<&'static str, bool>,
+}
+
+// Client authentication settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthCapabilities {
+    // Whether requests must carry credentials
+    pub required: bool,
+
+    // Accepted credentials: `api_key`, `basic`, `jwt`, `access_token`,
+    // `client_certificate` and `trusted_proxy`
+    pub modes: Vec<&'static str>,
+}
+
+// Backends scanning the same content in parallel.
+#[derive(Debug, Clone, Serialize)]
+pub struct FanOutCapabilities {
+    // Backends scanning every prompt and response
+    pub providers: Vec<&'static str>,
+
+    // Policy combining their verdicts: `any_block`, `all_block` or `weighted`
+    pub policy: &'static str,
+}
+
+// Streaming settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamingCapabilities {
+    // Chunks are held back until the content they carry has been assessed, or
+    // released while up to the risk window of content awaits its verdict
+    pub strategy: &'static str,
+
+    // Characters released before their verdict when scanning asynchronously
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub risk_window_chars: Option<usize>,
+
+    // Strategies of models streamed differently: `sentence`, `code` or `windowed`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, &'static str>,
+
+    // Longest duration of a streamed response in seconds (0 = unlimited)
+    pub timeout_secs: u64,
+}
+
+// Response replay settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct CachingCapabilities {
+    // Identical requests within the window receive the first response
+    pub dedup: bool,
+
+    // Responses are replayed for repeated `Idempotency-Key` headers
+    pub idempotency: bool,
+}
+
+// Record keeping settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditCapabilities {
+    // Where usage and violation records are kept
+    pub backend: &'static str,
+
+    // Signed response manifests are produced
+    pub provenance: bool,
+
+    // Usage reports are exported with privacy protections
+    pub private_usage_reports: bool,
+}
+
+impl ProxyCapabilities {
+    // Describes the subsystems enabled by the configuration.
+    pub fn describe(config: &Config) -> Self {
+        let client_auth = &config.client_auth;
+        let mut modes = Vec::new();
+        if client_auth.enabled
+            && (!client_auth.keys.is_empty() || !client_auth.key_state_file.is_empty())
+        {
+            modes.push("api_key");
+        }
+        if client_auth.enabled && client_auth.basic_auth {
+            modes.push("basic");
+        }
+        if client_auth.enabled && client_auth.jwt.enabled {
+            modes.push("jwt");
+        }
+        if client_auth.enabled && client_auth.access_tokens.enabled {
+            modes.push("access_token");
+        }
+        if config.server.tls_client_ca_file.is_some() {
+            modes.push("client_certificate");
+        }
+        // Proxy identities are accepted alongside, not instead of, credentials
+        let required = !modes.is_empty();
+        if client_auth.trusted_proxy.enabled {
+            modes.push("trusted_proxy");
+        }
+
+        let security = &config.security;
+        let features = BTreeMap::from([
+            ("access_windows", !config.access_windows.policies.is_empty()),
+            ("admin_api", !config.admin.token.is_empty()),
+            ("auth_lockout", client_auth.lockout.enabled),
+            ("auto_pull", config.auto_pull.enabled),
+            ("circuit_breaker", security.circuit_breaker.enabled),
+            ("compat_shims", config.compat.enabled),
+            ("concurrency_limit", config.concurrency.max_in_flight > 0),
+            ("context_window_check", config.context.enabled),
+            ("demo_scenario", !config.demo.scenario_file.is_empty()),
+            (
+                "contextual_grounding",
+                !security.contextual_grounding.is_empty(),
+            ),
+            ("fail_open", security.failure_mode != FailureMode::Closed),
+            ("feature_flags", !config.feature_flags.is_empty()),
+            (
+                "header_passthrough",
+                !config.passthrough.request_headers.is_empty()
+                    || !config.passthrough.response_headers.is_empty(),
+            ),
+            (
+                "key_scopes",
+                client_auth
+                    .keys
+                    .iter()
+                    .any(|key| !key.scopes.is_empty() || !key.models.is_empty()),
+            ),
+            ("language_policy", security.language.enabled),
+            (
+                "model_concurrency_limit",
+                config.concurrency.max_in_flight_per_model > 0
+                    || config
+                        .concurrency
+                        .model_limits
+                        .iter()
+                        .any(|limit| limit.max_in_flight > 0),
+            ),
+            ("model_routing", !config.ollama.model_routes.is_empty()),
+            (
+                "notifications",
+                !config.notifications.rules.is_empty()
+                    || !security.budget.alert_webhook.is_empty()
+                    || !config.scheduler.alert_webhook.is_empty(),
+            ),
+            ("ollama_failover", !config.ollama.backends.is_empty()),
+            ("payload_decoding", security.decode_payloads.enabled),
+            ("pii_masking", security.pii.enabled),
+            ("prefilter_rules", !security.prefilter_rules.rules.is_empty()),
+            ("rate_limit", config.rate_limit.enabled),
+            ("response_capture", security.response_capture.enabled),
+            (
+                "response_postprocessing",
+                !config.postprocess.filters.is_empty(),
+            ),
+            ("scan_budget", security.budget.monthly_cap > 0),
+            (
+                "scan_bypass",
+                !security.scan_bypass.keys.is_empty() || !security.scan_bypass.networks.is_empty(),
+            ),
+            ("scan_cache", security.scan_cache.enabled),
+            ("scheduled_prompts", !config.scheduler.schedules.is_empty()),
+            ("scheduler_leader_election", config.scheduler.leader_election.enabled),
+            ("secret_scanning", security.secrets.enabled),
+            (
+                "security_events",
+                !config.security_events.webhook_url.is_empty()
+                    || !config.security_events.syslog_address.is_empty(),
+            ),
+            ("state_encryption", !config.encryption.keys.is_empty()),
+            ("tls", config.server.tls_cert_file.is_some()),
+            ("toxicity_filter", security.toxicity.enabled),
+            ("vault_secrets", !config.vault.address.is_empty()),
+        ]);
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            providers: vec![match config.ollama.provider {
+                OllamaProvider::Http => "ollama",
+                OllamaProvider::Mock => "mock",
+            }],
+            security_provider: if security.fan_out.providers.is_empty() {
+                security.provider.as_str()
+            } else {
+                "fan_out"
+            },
+            security_fan_out: (!security.fan_out.providers.is_empty()).then(|| {
+                FanOutCapabilities {
+                    providers: security
+                        .fan_out
+                        .providers
+                        .iter()
+                        .map(SecurityProvider::as_str)
+                        .collect(),
+                    policy: security.fan_out.policy.as_str(),
+                }
+            }),
+            auth: AuthCapabilities {
+                required,
+                modes,
+            },
+            streaming: StreamingCapabilities {
+                strategy: if security.async_streaming.enabled {
+                    ASYNC_STREAMING_STRATEGY
+                } else {
+                    STREAMING_STRATEGY
+                },
+                risk_window_chars: security
+                    .async_streaming
+                    .enabled
+                    .then_some(security.async_streaming.risk_window_chars),
+                models: config
+                    .models
+                    .streaming
+                    .iter()
+                    .map(|streaming| {
+                        let strategy = match streaming.strategy {
+                            StreamingStrategy::Sentence => "sentence",
+                            StreamingStrategy::Code => "code",
+                            StreamingStrategy::Windowed => "windowed",
+                        };
+                        (streaming.model.clone(), strategy)
+                    })
+                    .collect(),
+                timeout_secs: config.timeouts.stream_timeout_secs,
+            },
+            caching: CachingCapabilities {
+                dedup: config.dedup.enabled,
+                idempotency: config.idempotency.enabled,
+            },
+            audit: AuditCapabilities {
+                backend: "memory",
+                provenance: config.provenance.enabled,
+                private_usage_reports: config.usage.report_min_count > 0
+                    || config.usage.report_noise_epsilon > 0.0,
+            },
+            features,
+        }
+    }
+
+    // Logs the description as a startup banner.
+    pub fn log_banner(&self) {
+        let enabled: Vec<&str> = self
+            .features
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(name, _)| *name)
+            .collect();
+
+        info!(
+            "panw-api-ollama v{} proxying to {}, assessed by {}",
+            self.version,
+            self.providers.join(", "),
+            match &self.security_fan_out {
+                Some(fan_out) => format!(
+                    "{} in parallel ({})",
+                    fan_out.providers.join(", "),
+                    fan_out.policy
+                ),
+                None => self.security_provider.to_string(),
+            }
+        );
+        info!(
+            "Client authentication: {}",
+            if self.auth.required {
+                self.auth.modes.join(", ")
+            } else {
+                "none".to_string()
+            }
+        );
+        info!(
+            "Streaming: {}, dedup: {}, idempotency: {}, provenance: {}",
+            self.streaming.strategy,
+            self.caching.dedup,
+            self.caching.idempotency,
+            self.audit.provenance
+        );
+        info!(
+            "Enabled features: {}",
+            if enabled.is_empty() {
+                "none".to_string()
+            } else {
+                enabled.join(", ")
+            }
+        );
+    }
+}
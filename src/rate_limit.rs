@@ -0,0 +1,165 @@
+// Per-client request rate limiting.
+//
+// Each client gets a token bucket per route that refills at the configured
+// sustained rate and holds up to the configured burst. A request takes one
+// token; requests arriving at an empty bucket are rejected with 429 and a
+// `Retry-After` header telling the client when the next token is available.
+// This protects both the Ollama backend and the PANW scan quota from a single
+// misbehaving client.
+//
+// # Clients
+//
+// Clients are identified by their authenticated identity (API key, JWT or
+// client certificate) and otherwise by their IP address. The health checks
+// and the admin API are never limited.
+//
+// # Memory
+//
+// Buckets that have refilled completely carry no state worth keeping and are
+// dropped once the number of tracked buckets exceeds `MAX_BUCKETS`.
+use crate::client_auth::ClientIdentity;
+use crate::config::RateLimitConfig;
+use crate::handlers::ApiError;
+use crate::metrics;
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+// Paths that are never rate limited.
+const EXEMPT_PATHS: [&str; 3] = ["/healthz", "/livez", "/readyz"];
+
+// Number of tracked buckets above which full buckets are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+// Tokens of one client on one route.
+struct Bucket {
+    // Tokens currently available
+    tokens: f64,
+
+    // When the tokens were last refilled
+    refilled_at: Instant,
+}
+
+// Enforces the configured rate limits.
+//
+// Cloning the limiter is cheap and all clones share the same buckets.
+#[derive(Clone)]
+pub struct RateLimiter {
+    // Default limit and per-route overrides
+    config: Arc<RateLimitConfig>,
+
+    // Buckets keyed by route and client
+    buckets: Arc<Mutex<HashMap<(String, String), Bucket>>>,
+}
+
+impl RateLimiter {
+    // Creates a limiter with empty buckets.
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Returns the sustained rate per second and the burst of a route.
+    fn limit(&self, route: &str) -> (f64, f64) {
+        let (requests_per_minute, burst) = self
+            .config
+            .routes
+            .iter()
+            .find(|limit| limit.route == route)
+            .map_or(
+                (self.config.requests_per_minute, self.config.burst),
+                |limit| (limit.requests_per_minute, limit.burst),
+            );
+        (f64::from(requests_per_minute) / 60.0, f64::from(burst))
+    }
+
+    // Takes a token for a request of the client on the route.
+    //
+    // # Returns
+    //
+    // `None` when the request may proceed, or the number of seconds until the
+    // next token is available.
+    fn acquire(&self, route: &str, client: &str) -> Option<u64> {
+        let (rate, burst) = self.limit(route);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= MAX_BUCKETS {
+            buckets.retain(|(route, _), bucket| {
+                let (rate, burst) = self.limit(route);
+                let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+                bucket.tokens + elapsed * rate < burst
+            });
+        }
+
+        let bucket = buckets
+            .entry((route.to_string(), client.to_string()))
+            .or_insert(Bucket {
+                tokens: burst,
+                refilled_at: now,
+            });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.refilled_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            Some(((1.0 - bucket.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+// Middleware rejecting requests of clients that exceed their rate limit.
+pub async fn enforce_rate_limit(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if !state.rate_limiter.config.enabled
+        || path.starts_with("/admin")
+        || EXEMPT_PATHS.contains(&path)
+    {
+        return next.run(request).await;
+    }
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let client = match request.extensions().get::<ClientIdentity>() {
+        Some(ClientIdentity(Some(identity))) => identity.clone(),
+        _ => request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map_or_else(|| "unknown".to_string(), |info| info.0.ip().to_string()),
+    };
+
+    if let Some(retry_after_secs) = state.rate_limiter.acquire(&route, &client) {
+        warn!("Rate limit exceeded by {} on {}", client, route);
+        metrics::increment_counter(
+            "panw_rate_limited_requests_total",
+            "Requests rejected by the per-client rate limit",
+            &[("route", route.as_str())],
+        );
+        return ApiError::RateLimited {
+            message: format!("Rate limit exceeded, retry in {} s", retry_after_secs),
+            retry_after_secs,
+        }
+        .into_response();
+    }
+
+    next.run(request).await
+}
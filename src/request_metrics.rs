@@ -0,0 +1,196 @@
+// Per-route request metrics labeled by tenant and API key.
+//
+// Every request is counted in `panw_http_requests_total` and its latency is
+// recorded in `panw_http_request_duration_seconds`. Both are labeled with the
+// matched route template, the method, the tenant and a key identifier, so
+// per-customer dashboards can be built without exposing raw keys.
+//
+// # Labels
+//
+// - `route` is the route template (e.g. `/api/jobs/{id}`), or `unmatched` for
+//   unknown paths, so path parameters do not create new series.
+// - `tenant` is taken from the configured tenant header.
+// - `key` is the first 12 hex digits of the SHA-256 hash of the bearer token.
+//
+// Requests without a tenant or key are labeled `none`. Only the configured
+// number of distinct tenants and keys get their own label; further values are
+// reported as `other` so a misbehaving client cannot blow up the number of series.
+//
+// # Latency
+//
+// Latency is measured until the response headers are sent. For streaming
+// responses this is the time to the first chunk, not the full stream duration.
+use crate::config::MetricsConfig;
+use crate::metrics;
+use crate::AppState;
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Label value for requests without a tenant or key.
+const NO_LABEL: &str = "none";
+
+// Label value for tenants or keys beyond the cardinality cap.
+const OVERFLOW_LABEL: &str = "other";
+
+// Number of hex digits of the key hash used as the key label.
+const KEY_ID_LENGTH: usize = 12;
+
+// Distinct label values seen so far.
+#[derive(Default)]
+struct SeenLabels {
+    tenants: HashSet<String>,
+    keys: HashSet<String>,
+}
+
+// Derives the tenant and key labels of requests, enforcing the cardinality caps.
+//
+// Cloning the labeler is cheap and all clones share the same seen labels.
+#[derive(Clone)]
+pub struct RequestLabeler {
+    // Request header naming the tenant
+    tenant_header: String,
+
+    // Maximum number of distinct tenant labels
+    max_tenants: usize,
+
+    // Maximum number of distinct key labels
+    max_keys: usize,
+
+    // Label values that have been handed out
+    seen: Arc<Mutex<SeenLabels>>,
+}
+
+impl RequestLabeler {
+    // Creates a labeler with the configured header and caps.
+    pub fn new(config: &MetricsConfig) -> Self {
+        Self {
+            tenant_header: config.tenant_header.clone(),
+            max_tenants: config.max_tenants,
+            max_keys: config.max_keys,
+            seen: Arc::new(Mutex::new(SeenLabels::default())),
+        }
+    }
+
+    // Returns the tenant named by the tenant header of a request.
+    fn tenant<'a>(&self, headers: &'a HeaderMap) -> Option<&'a str> {
+        headers
+            .get(self.tenant_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|tenant| !tenant.is_empty())
+    }
+
+    // Returns the tenant and key labels of a request.
+    fn labels(&self, headers: &HeaderMap) -> (String, String) {
+        let tenant = self.tenant(headers);
+        let key_id = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(key_id);
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let tenant = capped(&mut seen.tenants, self.max_tenants, tenant);
+        let key = capped(&mut seen.keys, self.max_keys, key_id.as_deref());
+        (tenant, key)
+    }
+}
+
+// Tenant of a request, as named by the tenant header.
+//
+// Extracting the tenant never fails; requests without the header have no tenant.
+#[derive(Debug, Clone, Default)]
+pub struct Tenant(pub Option<String>);
+
+impl FromRequestParts<AppState> for Tenant {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Tenant(
+            state
+                .request_labeler
+                .tenant(&parts.headers)
+                .map(str::to_string),
+        ))
+    }
+}
+
+// Hashes an API key into the identifier used as its label.
+pub fn key_id(key: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(key.as_bytes()));
+    digest[..KEY_ID_LENGTH].to_string()
+}
+
+// Returns the label for a value, or the overflow label once the cap is reached.
+fn capped(seen: &mut HashSet<String>, max: usize, value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return NO_LABEL.to_string();
+    };
+
+    if seen.contains(value) {
+        return value.to_string();
+    }
+    if seen.len() >= max {
+        return OVERFLOW_LABEL.to_string();
+    }
+    seen.insert(value.to_string());
+    value.to_string()
+}
+
+// Middleware recording the request counter and latency histogram of every request.
+pub async fn record_request_metrics(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let method = request.method().to_string();
+    let (tenant, key) = state.request_labeler.labels(request.headers());
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let status = response.status().as_u16().to_string();
+    metrics::increment_counter(
+        "panw_http_requests_total",
+        "HTTP requests handled by the proxy",
+        &[
+            ("route", route.as_str()),
+            ("method", method.as_str()),
+            ("status", status.as_str()),
+            ("tenant", tenant.as_str()),
+            ("key", key.as_str()),
+        ],
+    );
+    metrics::observe_histogram(
+        "panw_http_request_duration_seconds",
+        "Time until the response headers were sent",
+        &[
+            ("route", route.as_str()),
+            ("method", method.as_str()),
+            ("tenant", tenant.as_str()),
+            ("key", key.as_str()),
+        ],
+        elapsed,
+    );
+
+    response
+}
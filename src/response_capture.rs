@@ -0,0 +1,278 @@
+// Capture of redacted PANW responses for troubleshooting.
+//
+// Raw PANW responses can carry the scanned content, such as masked prompts and
+// responses, so they are not written to the logs. When a response cannot be
+// understood, operators still need to see what PANW returned, so when enabled
+// a sample of responses is kept in memory and listed at
+// `GET /admin/panw-responses`:
+//
+// - responses with an error status and responses whose body cannot be parsed
+//   are always captured
+// - successfully parsed responses are captured at the configured sample rate
+// - at most the configured number of responses is captured per minute, and
+//   only the most recent captures are kept
+//
+// # Redaction
+//
+// In JSON bodies, only identifiers, verdicts, timestamps and error messages
+// keep their text; every other string is replaced by its length, so the shape
+// of the response is kept while scanned content is not. Numbers and booleans
+// are kept. Other bodies, such as error pages of intermediate proxies, are
+// kept as text with credentials masked. Bodies are cut to the configured
+// length after redaction.
+//
+// # Metrics
+//
+// Captures are counted in `panw_response_captures_total` by `reason`
+// (`error_status`, `parse_error` or `sampled`), and responses skipped by the
+// rate limit in `panw_response_captures_skipped_total`.
+use crate::config::ResponseCaptureConfig;
+use crate::metrics;
+use crate::secrets::SecretScanner;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Fields whose text is kept in captured JSON bodies.
+const KEPT_FIELDS: [&str; 18] = [
+    "action",
+    "allowed_topics",
+    "blocked_topics",
+    "category",
+    "code",
+    "completed_at",
+    "created_at",
+    "detection_service",
+    "error",
+    "message",
+    "profile_id",
+    "profile_name",
+    "received",
+    "report_id",
+    "scan_id",
+    "status",
+    "tr_id",
+    "verdict",
+];
+
+// Length of the rate limit window.
+const WINDOW: Duration = Duration::from_secs(60);
+
+// Why a response was captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureReason {
+    // PANW returned an error status
+    ErrorStatus,
+    // The body could not be parsed
+    ParseError,
+    // The response was picked by sampling
+    Sampled,
+}
+
+impl CaptureReason {
+    // Returns the name of the reason in captures and metric labels.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::ErrorStatus => "error_status",
+            Self::ParseError => "parse_error",
+            Self::Sampled => "sampled",
+        }
+    }
+}
+
+// A captured PANW response.
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedResponse {
+    // When the response was received
+    pub captured_at: DateTime<Utc>,
+
+    // `error_status`, `parse_error` or `sampled`
+    pub reason: &'static str,
+
+    // HTTP status of the response
+    pub status: u16,
+
+    // Why the body could not be parsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    // Length of the original body in bytes
+    pub body_bytes: usize,
+
+    // Whether the redacted body was cut to the configured length
+    pub truncated: bool,
+
+    // Redacted body
+    pub body: String,
+}
+
+// Captures and the rate limit window.
+#[derive(Default)]
+struct Captures {
+    // Most recent captures, oldest first
+    entries: VecDeque<CapturedResponse>,
+
+    // When the current rate limit window started
+    window_start: Option<Instant>,
+
+    // Captures made in the current window
+    in_window: u32,
+}
+
+// Keeps a redacted sample of PANW responses.
+//
+// Cloning is cheap and all clones share the same captures.
+#[derive(Clone)]
+pub struct ResponseCapture {
+    // Capacity, rate limit, sample rate and body length
+    config: Arc<ResponseCaptureConfig>,
+
+    // Scanner masking credentials in bodies that are not JSON
+    scanner: Arc<SecretScanner>,
+
+    // Captured responses
+    captures: Arc<Mutex<Captures>>,
+}
+
+impl ResponseCapture {
+    // Creates the capture from the configuration.
+    pub fn new(config: &ResponseCaptureConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            scanner: Arc::new(SecretScanner::new()),
+            captures: Arc::new(Mutex::new(Captures::default())),
+        }
+    }
+
+    // Captures a response PANW answered with an error status.
+    pub fn record_error_status(&self, status: u16, body: &str) {
+        self.capture(CaptureReason::ErrorStatus, status, body, None);
+    }
+
+    // Captures a response whose body could not be parsed.
+    pub fn record_parse_error(&self, status: u16, body: &str, error: &str) {
+        self.capture(
+            CaptureReason::ParseError,
+            status,
+            body,
+            Some(error.to_string()),
+        );
+    }
+
+    // Captures a successfully parsed response at the configured sample rate.
+    pub fn sample(&self, status: u16, body: &str) {
+        let percent = self.config.sample_percent;
+        if percent > 0 && rand::thread_rng().gen_range(0..100) < percent {
+            self.capture(CaptureReason::Sampled, status, body, None);
+        }
+    }
+
+    // Returns the captured responses, most recent first.
+    pub fn list(&self) -> Vec<CapturedResponse> {
+        let captures = self.captures.lock().unwrap_or_else(|e| e.into_inner());
+        captures.entries.iter().rev().cloned().collect()
+    }
+
+    // Redacts and keeps a response unless the rate limit is reached.
+    fn capture(&self, reason: CaptureReason, status: u16, body: &str, error: Option<String>) {
+        let now = Instant::now();
+        {
+            let mut captures = self.captures.lock().unwrap_or_else(|e| e.into_inner());
+            if captures
+                .window_start
+                .is_none_or(|start| now.duration_since(start) >= WINDOW)
+            {
+                captures.window_start = Some(now);
+                captures.in_window = 0;
+            }
+            if captures.in_window >= self.config.max_per_minute {
+                metrics::increment_counter(
+                    "panw_response_captures_skipped_total",
+                    "PANW responses not captured because of the rate limit",
+                    &[],
+                );
+                return;
+            }
+            captures.in_window += 1;
+        }
+
+        let (body_text, truncated) = truncate(self.redact(body), self.config.max_body_bytes);
+        let capture = CapturedResponse {
+            captured_at: Utc::now(),
+            reason: reason.as_str(),
+            status,
+            error,
+            body_bytes: body.len(),
+            truncated,
+            body: body_text,
+        };
+
+        let mut captures = self.captures.lock().unwrap_or_else(|e| e.into_inner());
+        while captures.entries.len() >= self.config.capacity {
+            captures.entries.pop_front();
+        }
+        captures.entries.push_back(capture);
+        metrics::increment_counter(
+            "panw_response_captures_total",
+            "PANW responses captured for troubleshooting",
+            &[("reason", reason.as_str())],
+        );
+    }
+
+    // Returns the body with scanned content removed.
+    fn redact(&self, body: &str) -> String {
+        match serde_json::from_str::<Value>(body) {
+            Ok(mut value) => {
+                redact_value(&mut value, false);
+                value.to_string()
+            }
+            Err(_) => self.scanner.mask(body, &mut false).0,
+        }
+    }
+}
+
+// Replaces strings not held by a kept field with their length.
+//
+// # Arguments
+//
+// * `value` - JSON value to redact in place
+// * `kept` - Whether the value is held by a field in `KEPT_FIELDS`
+fn redact_value(value: &mut Value, kept: bool) {
+    match value {
+        Value::String(text) if !kept => {
+            *value = Value::from(format!("[redacted: {} chars]", text.chars().count()));
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact_value(item, kept);
+            }
+        }
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                redact_value(field, KEPT_FIELDS.contains(&name.as_str()));
+            }
+        }
+        _ => {}
+    }
+}
+
+// Cuts text to at most `max_bytes` bytes on a character boundary.
+//
+// # Returns
+//
+// The text and whether it was cut
+fn truncate(mut text: String, max_bytes: usize) -> (String, bool) {
+    if text.len() <= max_bytes {
+        return (text, false);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+}
@@ -0,0 +1,180 @@
+// Scan bypass for trusted clients.
+//
+// Internal jobs such as synthetic data generation send large volumes of
+// content that does not need a PANW verdict, but would otherwise use up the
+// scan budget. Requests authenticated with a trusted client key, or coming
+// from a trusted network, are not scanned by PANW:
+//
+// - client keys are trusted by id; only requests authenticated with the key
+//   itself count, not JWTs or access tokens carrying the same subject
+// - networks are matched against the address of the connecting peer, so a
+//   reverse proxy in front of the proxy makes all its clients trusted
+// - the local checks, such as the secret scanner and the toxicity filter,
+//   still run
+//
+// Bypassed requests are still audited: each is logged, counted and exported
+// as a `scan_bypass` security event, and `verdict_debug` reports
+// `scan_bypass` as the deciding check.
+//
+// # Metrics
+//
+// Bypassed requests are counted in `panw_scan_bypass_requests_total` by
+// `reason` (`key` or `network`).
+use crate::client_auth::Credential;
+use crate::config::ScanBypassConfig;
+use crate::metrics;
+use crate::security_events::{self, EventType};
+use crate::transaction::TransactionId;
+use crate::AppState;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use serde_json::json;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tracing::info;
+
+// Network of trusted source addresses.
+#[derive(Debug, Clone, Copy)]
+pub struct Network {
+    address: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    // Parses a network in CIDR notation, or a single address.
+    //
+    // # Returns
+    //
+    // The network, or `None` if the value is not a valid network
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (address, prefix) = match value.split_once('/') {
+            Some((address, prefix)) => {
+                (address.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?))
+            }
+            None => (value.parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Self { address, prefix })
+    }
+
+    // Returns whether an address belongs to the network.
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Client keys and networks whose requests skip PANW scanning.
+//
+// Cloning is cheap and all clones share the same lists.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedClients {
+    // Ids of trusted client keys
+    keys: Arc<HashSet<String>>,
+
+    // Trusted source networks
+    networks: Arc<Vec<Network>>,
+}
+
+impl TrustedClients {
+    // Creates the trusted clients from the configuration, skipping invalid networks.
+    pub fn new(config: &ScanBypassConfig) -> Self {
+        Self {
+            keys: Arc::new(config.keys.iter().cloned().collect()),
+            networks: Arc::new(
+                config
+                    .networks
+                    .iter()
+                    .filter_map(|network| Network::parse(network))
+                    .collect(),
+            ),
+        }
+    }
+
+    // Returns why a request is trusted: `key` when it was authenticated with a
+    // trusted client key, `network` when it comes from a trusted network.
+    fn reason(&self, key: Option<&str>, ip: Option<IpAddr>) -> Option<&'static str> {
+        if key.is_some_and(|key| self.keys.contains(key)) {
+            return Some("key");
+        }
+        ip.filter(|ip| self.networks.iter().any(|network| network.contains(*ip)))
+            .map(|_| "network")
+    }
+}
+
+// Trusted client a request comes from, if any.
+//
+// Extracting the bypass never fails. A request from a trusted client is logged
+// and exported as a `scan_bypass` security event when it is extracted.
+#[derive(Debug, Clone, Default)]
+pub struct ScanBypass(pub Option<String>);
+
+impl FromRequestParts<AppState> for ScanBypass {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let key = parts
+            .extensions
+            .get::<Credential>()
+            .filter(|credential| credential.kind == "api_key")
+            .map(|credential| credential.subject.clone());
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip());
+        let Some(reason) = state
+            .security_client
+            .trusted_clients()
+            .reason(key.as_deref(), ip)
+        else {
+            return Ok(Self(None));
+        };
+
+        let client = match reason {
+            "key" => key.clone().unwrap_or_default(),
+            _ => ip.map(|ip| ip.to_string()).unwrap_or_default(),
+        };
+        info!(
+            "Skipping PANW scans of {} {} for trusted client {}",
+            parts.method,
+            parts.uri.path(),
+            client
+        );
+        metrics::increment_counter(
+            "panw_scan_bypass_requests_total",
+            "Requests from trusted clients that skipped PANW scanning",
+            &[("reason", reason)],
+        );
+        let transaction = parts
+            .extensions
+            .get::<TransactionId>()
+            .and_then(|id| id.0.clone());
+        security_events::publish(
+            EventType::ScanBypass,
+            &format!("PANW scans skipped for trusted client {}", client),
+            &[
+                ("client", json!(key)),
+                ("ip", json!(ip.map(|ip| ip.to_string()))),
+                ("path", json!(parts.uri.path())),
+                ("transaction_id", json!(transaction)),
+            ],
+        );
+        Ok(Self(Some(client)))
+    }
+}
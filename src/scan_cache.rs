@@ -0,0 +1,245 @@
+// Reuse of PANW verdicts for repeated identical content.
+//
+// System prompts, canned greetings and retried requests send the same content
+// to PANW over and over. When the cache is enabled, the verdict of a scan is
+// kept for a configured time and reused for identical content instead of
+// making another API call.
+//
+// # Matching
+//
+// Content is identified by a SHA-256 hash of the scanned content, the security
+// profile it was scanned with and whether it is a prompt or a response, so the
+// content itself is not kept in memory. Failed scans are never cached.
+//
+// # Eviction
+//
+// Verdicts expire after the configured TTL. Once the cache holds the maximum
+// number of verdicts, the least recently used one is evicted.
+//
+// Verdicts are also linked to the hash of the request body they were cached
+// for, so `DELETE /admin/cache/{hash}` drops them together with the responses
+// retained for duplicate suppression. Requests sent with the admin token and
+// an `X-Scan-Bypass-Cache: true` header never reuse a cached verdict; their
+// fresh verdicts replace the cached ones.
+//
+// # Metrics
+//
+// Lookups are counted in `panw_scan_cache_lookups_total` by `result` (`hit`
+// or `miss`), and the number of cached verdicts is reported in
+// `panw_scan_cache_entries`.
+use crate::config::ScanCacheConfig;
+use crate::metrics;
+use crate::security::Assessment;
+use crate::types::Content;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Hash identifying scanned content.
+pub type CacheKey = [u8; 32];
+
+// A cached verdict.
+struct Entry {
+    // Assessment built from the scan
+    assessment: Assessment,
+
+    // When the verdict was cached
+    stored_at: Instant,
+
+    // Position of the entry's last use in `Entries::recency`
+    last_used: u64,
+
+    // Hashes of the request bodies the verdict was cached for
+    requests: Vec<String>,
+}
+
+// Cached verdicts and the order they were last used in.
+#[derive(Default)]
+struct Entries {
+    // Verdicts by content hash
+    by_key: HashMap<CacheKey, Entry>,
+
+    // Content hashes by position of their last use, least recent first
+    recency: BTreeMap<u64, CacheKey>,
+
+    // Position given to the next use
+    next_use: u64,
+
+    // Content hashes by the hash of the request body they were cached for
+    by_request: HashMap<String, HashSet<CacheKey>>,
+}
+
+impl Entries {
+    // Returns the position of a use after all earlier ones.
+    fn next_position(&mut self) -> u64 {
+        let position = self.next_use;
+        self.next_use += 1;
+        position
+    }
+
+    // Marks an entry as used most recently.
+    fn touch(&mut self, key: &CacheKey) {
+        let position = self.next_position();
+        if let Some(entry) = self.by_key.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = position;
+            self.recency.insert(position, *key);
+        }
+    }
+
+    // Removes an entry.
+    fn remove(&mut self, key: &CacheKey) {
+        let Some(entry) = self.by_key.remove(key) else {
+            return;
+        };
+        self.recency.remove(&entry.last_used);
+        for request in &entry.requests {
+            if let Some(keys) = self.by_request.get_mut(request) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.by_request.remove(request);
+                }
+            }
+        }
+    }
+}
+
+// LRU cache of verdicts with a TTL.
+//
+// Cloning the cache is cheap and all clones share the same verdicts.
+#[derive(Clone)]
+pub struct ScanCache {
+    // Largest number of cached verdicts
+    max_entries: usize,
+
+    // How long a verdict is reused
+    ttl: Duration,
+
+    // Cached verdicts
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl ScanCache {
+    // Creates the cache from the configuration.
+    pub fn new(config: &ScanCacheConfig) -> Self {
+        Self {
+            max_entries: config.max_entries,
+            ttl: Duration::from_secs(config.ttl_secs),
+            entries: Arc::new(Mutex::new(Entries::default())),
+        }
+    }
+
+    // Returns the key identifying content scanned with a profile.
+    //
+    // # Arguments
+    //
+    // * `content` - Content sent to PANW
+    // * `profile` - Security profile the content is scanned with
+    // * `is_prompt` - Whether the content is a prompt rather than a response
+    pub fn key(content: &Content, profile: &str, is_prompt: bool) -> CacheKey {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(content).unwrap_or_default());
+        hasher.update([0]);
+        hasher.update(profile.as_bytes());
+        hasher.update([0, u8::from(is_prompt)]);
+        hasher.finalize().into()
+    }
+
+    // Returns the cached verdict for the key, if it has not expired.
+    pub fn get(&self, key: &CacheKey) -> Option<Assessment> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let fresh = entries
+            .by_key
+            .get(key)
+            .map(|entry| entry.stored_at.elapsed() < self.ttl);
+        let assessment = match fresh {
+            Some(true) => {
+                entries.touch(key);
+                entries
+                    .by_key
+                    .get(key)
+                    .map(|entry| entry.assessment.clone())
+            }
+            Some(false) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+
+        let result = if assessment.is_some() { "hit" } else { "miss" };
+        metrics::increment_counter(
+            "panw_scan_cache_lookups_total",
+            "Lookups of cached PANW verdicts",
+            &[("result", result)],
+        );
+        record_size(&entries);
+        assessment
+    }
+
+    // Caches the verdict for the key, evicting the least recently used
+    // verdict when the cache is full.
+    //
+    // # Arguments
+    //
+    // * `key` - Key of the scanned content
+    // * `assessment` - Assessment built from the scan
+    // * `request` - Hash of the body of the request the content was scanned for, if known
+    pub fn insert(&self, key: CacheKey, assessment: Assessment, request: Option<&str>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(&key);
+        while entries.by_key.len() >= self.max_entries {
+            let Some(&oldest) = entries.recency.values().next() else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+
+        let position = entries.next_position();
+        entries.recency.insert(position, key);
+        let requests: Vec<String> = request.map(str::to_string).into_iter().collect();
+        for request in &requests {
+            entries
+                .by_request
+                .entry(request.clone())
+                .or_default()
+                .insert(key);
+        }
+        entries.by_key.insert(
+            key,
+            Entry {
+                assessment,
+                stored_at: Instant::now(),
+                last_used: position,
+                requests,
+            },
+        );
+        record_size(&entries);
+    }
+
+    // Drops the verdicts cached for a request body.
+    //
+    // # Returns
+    //
+    // The number of verdicts dropped
+    pub fn invalidate(&self, request: &str) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let keys = entries.by_request.remove(request).unwrap_or_default();
+        for key in &keys {
+            entries.remove(key);
+        }
+        record_size(&entries);
+        keys.len()
+    }
+}
+
+// Reports the number of cached verdicts.
+fn record_size(entries: &Entries) {
+    metrics::set_gauge(
+        "panw_scan_cache_entries",
+        "PANW verdicts currently cached",
+        &[],
+        entries.by_key.len() as f64,
+    );
+}
@@ -0,0 +1,124 @@
+// Scripted latencies for reproducible demos.
+//
+// Demos of streaming block and mask behavior depend on timing: a verdict that
+// arrives while tokens are still streaming looks different from one that
+// arrives after the last token. A scenario file scripts these timings so a demo
+// plays out the same way every time.
+//
+// # Scenario Files
+//
+// ```yaml
+// tokens:
+//   - prompt_contains: "secret plan"
+//     delays_ms: [900, 40, 40, 250, 40]
+//   - delays_ms: [300, 60]
+// verdicts:
+//   - stage: response
+//     content_contains: "bad"
+//     delay_ms: 1500
+// ```
+//
+// Token rules pace the responses of the mock Ollama backend. The first rule
+// whose phrase occurs in the prompt applies, and a rule without a phrase
+// matches every prompt. Its delays are waited before each streamed chunk in
+// turn, the first being the time to the first token; the last delay repeats for
+// the rest of the stream. Prompts no rule matches keep the configured token
+// rate.
+//
+// Verdict rules delay PANW scans of the given stage (`prompt`, `response` or
+// `any`) whose content contains the phrase, again first match wins. The delay
+// is waited before the scan is sent, so it applies in front of the real PANW
+// API as well.
+use serde::Deserialize;
+use std::fs;
+use std::time::Duration;
+
+// Latencies scripted by a scenario file.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Scenario {
+    // Pacing of mock responses, first match wins
+    #[serde(default)]
+    tokens: Vec<TokenRule>,
+
+    // Delays of PANW verdicts, first match wins
+    #[serde(default)]
+    verdicts: Vec<VerdictRule>,
+}
+
+// Pacing of the responses to matching prompts.
+#[derive(Debug, Clone, Deserialize)]
+struct TokenRule {
+    // Phrase looked for in the prompt, ignoring case; empty matches every prompt
+    #[serde(default)]
+    prompt_contains: String,
+
+    // Delays before successive chunks; the last one repeats
+    delays_ms: Vec<u64>,
+}
+
+// Delay of the verdicts on matching content.
+#[derive(Debug, Clone, Deserialize)]
+struct VerdictRule {
+    // Scans the rule applies to
+    #[serde(default)]
+    stage: ScanStage,
+
+    // Phrase looked for in the scanned content, ignoring case; empty matches all content
+    #[serde(default)]
+    content_contains: String,
+
+    // Delay before the scan is sent
+    delay_ms: u64,
+}
+
+// Scans a verdict rule applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ScanStage {
+    #[default]
+    Any,
+    Prompt,
+    Response,
+}
+
+impl Scenario {
+    // Loads a scenario file.
+    //
+    // # Errors
+    //
+    // Returns an error if the file cannot be read or is not a valid scenario.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let scenario: Scenario =
+            serde_yml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+        if scenario.tokens.iter().any(|rule| rule.delays_ms.is_empty()) {
+            return Err(format!("{}: token rules need at least one delay", path));
+        }
+        Ok(scenario)
+    }
+
+    // Returns the scripted delays before the chunks of a response, if a rule matches.
+    pub fn token_delays(&self, prompt: &str) -> Option<&[u64]> {
+        let prompt = prompt.to_lowercase();
+        self.tokens
+            .iter()
+            .find(|rule| prompt.contains(&rule.prompt_contains.to_lowercase()))
+            .map(|rule| rule.delays_ms.as_slice())
+    }
+
+    // Returns the delay before a scan of the given content.
+    pub fn verdict_delay(&self, content: &str, is_prompt: bool) -> Duration {
+        let content = content.to_lowercase();
+        self.verdicts
+            .iter()
+            .find(|rule| {
+                let stage_matches = match rule.stage {
+                    ScanStage::Any => true,
+                    ScanStage::Prompt => is_prompt,
+                    ScanStage::Response => !is_prompt,
+                };
+                stage_matches && content.contains(&rule.content_contains.to_lowercase())
+            })
+            .map_or(Duration::ZERO, |rule| Duration::from_millis(rule.delay_ms))
+    }
+}
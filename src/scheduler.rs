@@ -0,0 +1,384 @@
+// Scheduled prompt execution.
+//
+// Prompts defined in the configuration or through the admin API are run on a
+// cron schedule, for example to generate a nightly report in the lab. Every
+// run goes through the full security pipeline: the prompt is scanned, a
+// response is generated and the response is scanned before the result is
+// posted to the schedule's webhook.
+//
+// # Failures
+//
+// A run fails when the pipeline returns an error or the result cannot be
+// delivered. Failures are logged, counted in the
+// `panw_scheduled_prompt_failures_total` metric and notified as
+// `scheduled_prompt_failed` alerts. Blocked prompts or responses are not failures;
+// their verdict is delivered to the schedule's webhook like any other result.
+//
+// # Timing
+//
+// Cron expressions are evaluated in UTC. The scheduler wakes at the next due
+// run, and at least once a minute to pick up schedules changed at runtime.
+//
+// # Replicas
+//
+// With leader election enabled, only the replica holding the leader lease runs
+// due schedules; see the `leader` module.
+use crate::config::{ConfigError, ScheduledPrompt, SchedulerConfig};
+use crate::handlers::generate::{blocked_prompt, generate_assessed, GenerationResult};
+use crate::handlers::utils::scan_verdict;
+use crate::http_client;
+use crate::leader::LeaderLease;
+use crate::metrics;
+use crate::notifications::{self, AlertKind};
+use crate::types::{GenerateRequest, ScanVerdict};
+use crate::AppState;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+// Longest the scheduler sleeps before looking for changed schedules.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+// Outcome of a scheduled run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    // The prompt and the response passed security checks
+    Completed,
+    // The prompt or the response was blocked
+    Blocked,
+    // The run failed or its result could not be delivered
+    Failed,
+}
+
+// Summary of the most recent run of a schedule.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleRun {
+    // When the run started
+    pub started_at: DateTime<Utc>,
+
+    // Outcome of the run
+    pub status: RunStatus,
+
+    // Why the run failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// A schedule as reported by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScheduleStatus {
+    // The schedule definition
+    #[serde(flatten)]
+    pub definition: ScheduledPrompt,
+
+    // When the schedule runs next
+    pub next_run: Option<DateTime<Utc>>,
+
+    // The most recent run, if any
+    pub last_run: Option<ScheduleRun>,
+}
+
+// A registered schedule.
+struct Entry {
+    definition: ScheduledPrompt,
+    schedule: cron::Schedule,
+    last_run: Option<ScheduleRun>,
+}
+
+impl Entry {
+    // Returns the schedule as reported by the admin API.
+    fn status(&self) -> ScheduleStatus {
+        ScheduleStatus {
+            definition: self.definition.clone(),
+            next_run: self.schedule.upcoming(Utc).next(),
+            last_run: self.last_run.clone(),
+        }
+    }
+}
+
+// Registry of scheduled prompts.
+//
+// Cloning the scheduler is cheap and all clones share the same schedules.
+#[derive(Clone)]
+pub struct Scheduler {
+    // HTTP client used for result webhooks
+    client: Client,
+
+    // Schedules keyed by name
+    entries: Arc<Mutex<BTreeMap<String, Entry>>>,
+
+    // Lease electing the replica that runs schedules, when leader election is enabled
+    leader: Option<LeaderLease>,
+}
+
+impl Scheduler {
+    // Creates the scheduler with the schedules from the configuration.
+    //
+    // # Errors
+    //
+    // Returns an error if the HTTP client cannot be created
+    pub fn new(config: &SchedulerConfig) -> Result<Self, reqwest::Error> {
+        let client = http_client::builder(
+            &config.http_client,
+            Some(http_client::DEFAULT_WEBHOOK_REQUEST_TIMEOUT),
+        )
+        .build()?;
+        let scheduler = Self {
+            client,
+            entries: Arc::new(Mutex::new(BTreeMap::new())),
+            leader: config
+                .leader_election
+                .enabled
+                .then(|| LeaderLease::new(&config.leader_election)),
+        };
+
+        for definition in &config.schedules {
+            if let Err(e) = scheduler.upsert(definition.clone()) {
+                warn!("Skipping schedule {}: {}", definition.name, e);
+            }
+        }
+
+        Ok(scheduler)
+    }
+
+    // Returns all schedules ordered by name.
+    pub fn list(&self) -> Vec<ScheduleStatus> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.values().map(Entry::status).collect()
+    }
+
+    // Adds a schedule or replaces the schedule with the same name.
+    //
+    // # Errors
+    //
+    // Returns an error if the schedule is incomplete or its cron expression is invalid.
+    pub fn upsert(&self, definition: ScheduledPrompt) -> Result<ScheduleStatus, ConfigError> {
+        definition.validate()?;
+        let schedule = definition.schedule()?;
+
+        let entry = Entry {
+            definition,
+            schedule,
+            last_run: None,
+        };
+        let status = entry.status();
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(entry.definition.name.clone(), entry);
+        info!("Registered schedule {}", status.definition.name);
+        Ok(status)
+    }
+
+    // Removes a schedule, returning whether it existed.
+    pub fn remove(&self, name: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.remove(name).is_some()
+    }
+
+    // Returns the schedules with a run due after `from` and no later than `to`.
+    fn due(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<ScheduledPrompt> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .values()
+            .filter(|entry| entry.schedule.after(&from).next().is_some_and(|at| at <= to))
+            .map(|entry| entry.definition.clone())
+            .collect()
+    }
+
+    // Returns the earliest upcoming run across all schedules.
+    fn next_run(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .values()
+            .filter_map(|entry| entry.schedule.after(&after).next())
+            .min()
+    }
+
+    // Records the outcome of a run.
+    fn record(&self, name: &str, run: ScheduleRun) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.get_mut(name) {
+            entry.last_run = Some(run);
+        }
+    }
+
+    // Logs, counts and notifies the alert for a failed run.
+    fn fire_alert(&self, definition: &ScheduledPrompt, started_at: DateTime<Utc>, reason: &str) {
+        error!("Scheduled prompt {} failed: {}", definition.name, reason);
+        metrics::increment_counter(
+            "panw_scheduled_prompt_failures_total",
+            "Scheduled prompt runs that failed or could not be delivered",
+            &[("schedule", definition.name.as_str())],
+        );
+
+        notifications::notify(
+            AlertKind::ScheduledPromptFailed,
+            &format!("Scheduled prompt {} failed", definition.name),
+            &[
+                ("schedule", json!(definition.name)),
+                ("started_at", json!(started_at)),
+                ("error", json!(reason)),
+            ],
+        );
+    }
+}
+
+// Result of a run through the security pipeline.
+enum PipelineResult {
+    Completed(String),
+    Blocked(ScanVerdict),
+    Failed(String),
+}
+
+// Starts the background task running due schedules.
+pub fn spawn(state: AppState) {
+    if let Some(leader) = &state.scheduler.leader {
+        leader.spawn();
+    }
+
+    tokio::spawn(async move {
+        let mut last_check = Utc::now();
+
+        loop {
+            let now = Utc::now();
+            let max_wake = now + chrono::Duration::from_std(MAX_SLEEP).unwrap_or_default();
+            let wake = state
+                .scheduler
+                .next_run(now)
+                .map_or(max_wake, |next| next.min(max_wake));
+            tokio::time::sleep((wake - now).to_std().unwrap_or_default()).await;
+
+            let now = Utc::now();
+            let due = state.scheduler.due(last_check, now);
+            last_check = now;
+            if !due.is_empty()
+                && state
+                    .scheduler
+                    .leader
+                    .as_ref()
+                    .is_some_and(|leader| !leader.is_leader())
+            {
+                debug!("Skipping {} due schedules on a replica that is not the leader", due.len());
+                continue;
+            }
+
+            if !due.is_empty() && state.kill_switch.is_active() {
+                warn!("Skipping {} due schedules while the kill switch is active", due.len());
+                continue;
+            }
+
+            for definition in due {
+                let state = state.clone();
+                tokio::spawn(async move { run_schedule(&state, definition).await });
+            }
+        }
+    });
+}
+
+// Runs a schedule through the security pipeline and delivers the result.
+async fn run_schedule(state: &AppState, definition: ScheduledPrompt) {
+    let started_at = Utc::now();
+    info!("Running scheduled prompt {}", definition.name);
+
+    let mut request = GenerateRequest {
+        model: definition.model.clone(),
+        prompt: definition.prompt.clone(),
+        system: definition.system.clone(),
+        template: None,
+        context: None,
+        stream: Some(false),
+        raw: None,
+        format: None,
+        options: None,
+        images: None,
+    };
+
+    let result = match blocked_prompt(&state.security_client, &mut request).await {
+        Ok(Some(assessment)) => PipelineResult::Blocked(scan_verdict(
+            "prompt",
+            &assessment,
+            &state.security_client.branding(),
+        )),
+        Ok(None) => match generate_assessed(state, &state.security_client, &request).await {
+            Ok(GenerationResult::Blocked(_, assessment)) => {
+                PipelineResult::Blocked(scan_verdict(
+                    "response",
+                    &assessment,
+                    &state.security_client.branding(),
+                ))
+            }
+            Ok(GenerationResult::Passed { response, .. }) => PipelineResult::Completed(response),
+            Err(e) => PipelineResult::Failed(e.to_string()),
+        },
+        Err(e) => PipelineResult::Failed(e.to_string()),
+    };
+
+    let mut payload = json!({
+        "event": "scheduled_prompt_result",
+        "schedule": definition.name,
+        "model": definition.model,
+        "started_at": started_at,
+        "completed_at": Utc::now(),
+    });
+    let (mut status, mut failure) = match result {
+        PipelineResult::Completed(response) => {
+            payload["status"] = json!(RunStatus::Completed);
+            payload["response"] = json!(response);
+            (RunStatus::Completed, None)
+        }
+        PipelineResult::Blocked(verdict) => {
+            payload["status"] = json!(RunStatus::Blocked);
+            payload["verdict"] = json!(verdict);
+            (RunStatus::Blocked, None)
+        }
+        PipelineResult::Failed(error) => {
+            payload["status"] = json!(RunStatus::Failed);
+            payload["error"] = json!(error);
+            (RunStatus::Failed, Some(error))
+        }
+    };
+
+    let delivery = state
+        .scheduler
+        .client
+        .post(&definition.webhook)
+        .json(&payload)
+        .send()
+        .await;
+    let delivery_error = match delivery {
+        Ok(response) if response.status().is_success() => None,
+        Ok(response) => Some(format!("result webhook returned {}", response.status())),
+        Err(e) => Some(format!("failed to deliver result: {}", e)),
+    };
+    if let Some(delivery_error) = delivery_error {
+        status = RunStatus::Failed;
+        failure = Some(match failure {
+            Some(error) => format!("{}; {}", error, delivery_error),
+            None => delivery_error,
+        });
+    }
+
+    if let Some(reason) = &failure {
+        state.scheduler.fire_alert(&definition, started_at, reason);
+    } else {
+        info!(
+            "Scheduled prompt {} finished with status {:?}",
+            definition.name, status
+        );
+    }
+
+    state.scheduler.record(
+        &definition.name,
+        ScheduleRun {
+            started_at,
+            status,
+            error: failure,
+        },
+    );
+}
@@ -0,0 +1,169 @@
+// Local scanner for high-confidence credentials in model responses.
+//
+// PANW DLP already inspects responses, but leaked credentials are the most
+// damaging class of leak, so responses are additionally checked locally for a
+// small set of patterns that almost never produce false positives.
+//
+// # Detected Secrets
+//
+// - AWS access key IDs and secret access key assignments
+// - PEM private key blocks
+// - JSON Web Tokens
+//
+// # Streaming
+//
+// Streamed responses are masked batch by batch. A private key block that is
+// still open at the end of a batch is tracked by the caller, so that the rest
+// of the block is masked in the following batches until its END line.
+use crate::config::SecretAction;
+use crate::metrics;
+use regex::Regex;
+use tracing::warn;
+
+// Pattern matching a PEM private key block, or its beginning when the END line is missing.
+const PRIVATE_KEY_PATTERN: &str = r"-----BEGIN (?:[A-Z0-9]+ )*PRIVATE KEY-----(?:(?s:.)*?-----END (?:[A-Z0-9]+ )*PRIVATE KEY-----|(?s:.)*)";
+
+// Pattern matching the remainder of a private key block up to and including its END line.
+const PRIVATE_KEY_END_PATTERN: &str = r"^(?s:.)*?-----END (?:[A-Z0-9]+ )*PRIVATE KEY-----";
+
+// Kind of secret detected in a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    AwsAccessKeyId,
+    AwsSecretAccessKey,
+    PrivateKey,
+    Jwt,
+}
+
+impl SecretKind {
+    // Returns the name used in masks, logs and metric labels.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SecretKind::AwsAccessKeyId => "aws_access_key_id",
+            SecretKind::AwsSecretAccessKey => "aws_secret_access_key",
+            SecretKind::PrivateKey => "private_key",
+            SecretKind::Jwt => "jwt",
+        }
+    }
+}
+
+// Detects and masks secrets in text.
+pub struct SecretScanner {
+    // Patterns for every detected secret kind
+    patterns: Vec<(SecretKind, Regex)>,
+
+    // Pattern closing a private key block opened in an earlier batch
+    private_key_end: Regex,
+}
+
+impl SecretScanner {
+    // Compiles the secret patterns.
+    pub fn new() -> Self {
+        let patterns = [
+            (SecretKind::PrivateKey, PRIVATE_KEY_PATTERN),
+            (
+                SecretKind::AwsAccessKeyId,
+                r"\b(?:AKIA|ASIA|AGPA|AIDA|AROA|ANPA|ANVA|AIPA)[A-Z0-9]{16}\b",
+            ),
+            (
+                SecretKind::AwsSecretAccessKey,
+                r#"(?i)aws_?secret_?access_?key["']?\s*[:=]\s*["']?[A-Za-z0-9/+=]{40}"#,
+            ),
+            (
+                SecretKind::Jwt,
+                r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]{8,}",
+            ),
+        ];
+
+        Self {
+            patterns: patterns
+                .into_iter()
+                .map(|(kind, pattern)| (kind, Regex::new(pattern).expect("valid secret pattern")))
+                .collect(),
+            private_key_end: Regex::new(PRIVATE_KEY_END_PATTERN).expect("valid secret pattern"),
+        }
+    }
+
+    // Returns the distinct kinds of secrets found in the text.
+    pub fn find(&self, text: &str) -> Vec<SecretKind> {
+        self.patterns
+            .iter()
+            .filter(|(_, pattern)| pattern.is_match(text))
+            .map(|(kind, _)| *kind)
+            .collect()
+    }
+
+    // Replaces every secret in the text with a `[REDACTED:<kind>]` marker.
+    //
+    // # Arguments
+    //
+    // * `text` - Text to mask
+    // * `open_block` - Whether a private key block is open from an earlier batch; updated
+    //   to reflect whether one is still open at the end of this text
+    //
+    // # Returns
+    //
+    // The masked text and the kinds of secrets that were masked
+    pub fn mask(&self, text: &str, open_block: &mut bool) -> (String, Vec<SecretKind>) {
+        let mut masked = text.to_string();
+        let mut found = Vec::new();
+
+        if *open_block {
+            found.push(SecretKind::PrivateKey);
+            match self.private_key_end.find(&masked) {
+                Some(end) => {
+                    masked.replace_range(..end.end(), &redaction(SecretKind::PrivateKey));
+                    *open_block = false;
+                }
+                None => return (redaction(SecretKind::PrivateKey), found),
+            }
+        }
+
+        for (kind, pattern) in &self.patterns {
+            if !pattern.is_match(&masked) {
+                continue;
+            }
+            if *kind == SecretKind::PrivateKey {
+                // A block running to the end of the text continues in the next batch
+                *open_block = pattern
+                    .find_iter(&masked)
+                    .last()
+                    .is_some_and(|m| !self.private_key_end.is_match(m.as_str()));
+            }
+            masked = pattern.replace_all(&masked, redaction(*kind)).into_owned();
+            if !found.contains(kind) {
+                found.push(*kind);
+            }
+        }
+
+        (masked, found)
+    }
+}
+
+impl Default for SecretScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Returns the marker replacing a secret of the given kind.
+fn redaction(kind: SecretKind) -> String {
+    format!("[REDACTED:{}]", kind.as_str())
+}
+
+// Records and logs secrets found in a response.
+pub fn record_findings(kinds: &[SecretKind], action: SecretAction) {
+    let action = match action {
+        SecretAction::Mask => "mask",
+        SecretAction::Block => "block",
+    };
+
+    for kind in kinds {
+        warn!("Secret scanner found {} in response, action={}", kind.as_str(), action);
+        metrics::increment_counter(
+            "panw_secret_findings_total",
+            "Secrets found in model responses by the local secret scanner",
+            &[("kind", kind.as_str()), ("action", action)],
+        );
+    }
+}
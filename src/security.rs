@@ -33,12 +33,52 @@
 // }
 // ```
 use crate::{
-    config::SecurityConfig,
-    types::{AiProfile, Content, Metadata, ScanRequest, ScanResponse},
+    azure_content_safety::ContentSafetyClient,
+    bedrock_guardrails::GuardrailsClient,
+    branding::{Branding, TenantBrandings},
+    budget::{BudgetDecision, ScanBudget},
+    circuit_breaker::CircuitBreaker,
+    encoded_payloads::PayloadDecoder,
+    config::{
+        AsyncStreamingConfig, DegradedMode, FailureMode, HttpClientConfig, PrefilterAction,
+        SecretAction,
+        FanOutConfig, SecurityConfig, SecurityProvider, VerdictPolicy,
+    },
+    http_client,
+    language::LanguagePolicy,
+    llama_guard::LlamaGuardClient,
+    provenance::ScanTrail,
+    metrics,
+    ollama::with_tag,
+    openai_moderation::ModerationClient,
+    pii::{self, PiiMasker},
+    prefilter_rules::PrefilterRules,
+    response_capture::ResponseCapture,
+    scan_bypass::TrustedClients,
+    scan_cache::{CacheKey, ScanCache},
+    scenario::Scenario,
+    secrets::{self, SecretScanner},
+    security_events::{self, EventType},
+    toxicity::ToxicityFilter,
+    transcripts::{Entry, Transcripts},
+    types::{
+        AiProfile, AsyncScanObject, AsyncScanResponse, Content, Metadata, ProviderVerdict,
+        ScanIdResult, ScanRequest, ScanResponse,
+    },
+    usage::UsageLedger,
+    vault::SharedSecret,
+    verdict_debug::{AssessmentTrace, VerdictTrail},
 };
-use reqwest::Client;
-use std::time::Instant;
+use axum::http::HeaderMap;
+use reqwest::{Client, RequestBuilder};
+use serde_json::json;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use thiserror::Error;
+use tokio::sync::{watch, Semaphore};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
@@ -92,11 +132,40 @@ pub enum SecurityError {
     #[error("Content blocked by PANW AI security policy: {0}")]
     BlockedContent(String),
 
+    // Monthly scan budget exhausted while the degraded mode blocks traffic
+    #[error("Monthly PANW scan budget exhausted")]
+    BudgetExhausted,
+
+    // PANW API failing while the circuit breaker's open mode blocks traffic
+    #[error("PANW API unavailable, circuit breaker open")]
+    CircuitOpen,
+
+    // Provider answered with a server error, or rate limited a provider
+    // without retry information
+    #[error("Security provider unavailable - Status {0}: {1}")]
+    ProviderUnavailable(reqwest::StatusCode, String),
+
     // Generic assessment error for other cases
     #[error("PANW security assessment error: {0}")]
     AssessmentError(String),
 }
 
+impl SecurityError {
+    // Returns whether the error shows that the PANW API is unavailable.
+    //
+    // Timeouts, connection errors, server errors and rate limiting by the
+    // provider count as outages. Any other answer shows that the API is
+    // reachable, and errors of the proxy itself, such as an unconfigured
+    // provider or an asynchronous scan that did not settle in time, are not
+    // outages either.
+    pub fn is_outage(&self) -> bool {
+        matches!(
+            self,
+            Self::RequestError(_) | Self::ProviderUnavailable(..) | Self::TooManyRequests(..)
+        )
+    }
+}
+
 // Represents the result of a security assessment from PANW AI Runtime API.
 //
 // This struct contains the outcome of evaluating content against Palo Alto Networks' security policies,
@@ -120,6 +189,44 @@ pub struct Assessment {
 
     // Complete findings from the PANW AI security scan
     pub details: ScanResponse,
+
+    // Check that decided the verdict (e.g., "panw", "toxicity_filter")
+    pub decided_by: &'static str,
+}
+
+impl Assessment {
+    // Returns a description of every finding that contributed to the verdict.
+    pub fn reasons(&self) -> Vec<&'static str> {
+        let prompt = &self.details.prompt_detected;
+        let response = &self.details.response_detected;
+        [
+            (prompt.url_cats, "Prompt contains malicious URLs"),
+            (prompt.dlp, "Prompt contains sensitive information"),
+            (prompt.injection, "Prompt contains injection threats"),
+            (prompt.toxic_content, "Prompt contains harmful content"),
+            (prompt.malicious_code, "Prompt contains malicious code"),
+            (prompt.agent, "Prompt contains any Agent related threats"),
+            (
+                prompt.topic_violation,
+                "Prompt contains any content violates topic guardrails",
+            ),
+            (response.url_cats, "Response contains malicious URLs"),
+            (response.dlp, "Response contains sensitive information"),
+            (response.db_security, "Response contains database security threats"),
+            (response.toxic_content, "Response contains harmful content"),
+            (response.malicious_code, "Response contains malicious code"),
+            (response.agent, "Response contains any Agent related threats"),
+            (response.ungrounded, "Response contains any ungrounded content"),
+            (
+                response.topic_violation,
+                "Response contains any content violates topic guardrails",
+            ),
+        ]
+        .into_iter()
+        .filter(|(detected, _)| *detected)
+        .map(|(_, reason)| reason)
+        .collect()
+    }
 }
 
 // Client for performing security assessments using the PANW AI Runtime API.
@@ -134,12 +241,24 @@ pub struct SecurityClient {
     // Base URL for the PANW API service
     base_url: String,
 
-    // API key for authenticating with PANW services
-    api_key: String,
+    // API key for authenticating with PANW services, refreshed from Vault when referenced
+    api_key: SharedSecret,
 
     // Security profile name to use for assessments
     profile_name: String,
 
+    // Profile selected for the current request, overriding the model's and the default (optional)
+    selected_profile: Option<String>,
+
+    // Profiles used for requests to specific models, keyed by tagged model name
+    model_profiles: Arc<HashMap<String, String>>,
+
+    // Request header through which clients select an allowed profile (optional)
+    profile_header: Option<String>,
+
+    // Profiles clients may select through the profile header
+    allowed_profiles: Arc<HashSet<String>>,
+
     // Application name for telemetry and audit
     app_name: String,
 
@@ -151,6 +270,284 @@ pub struct SecurityClient {
 
     // Default context for grounding LLM responses. When not empty, grounding is enabled
     contextual_grounding_context: String,
+
+    // Paces scan requests while the PANW API is rate limiting this client
+    throttle: ScanThrottle,
+
+    // Monthly scan budget shared by all clones of this client
+    budget: ScanBudget,
+
+    // Collects report IDs of scans performed for the current request (optional)
+    scan_trail: Option<ScanTrail>,
+
+    // Transaction ID of the current request, sent as the tr_id of its scans (optional)
+    transaction_id: Option<String>,
+
+    // Collects explanations of the assessments for the current request (optional)
+    verdict_trail: Option<VerdictTrail>,
+
+    // Transcripts assessed content is recorded in (optional)
+    transcripts: Option<Transcripts>,
+
+    // Session the current request belongs to in transcripts (optional)
+    session: Option<String>,
+
+    // Local scanner for secrets in responses (optional)
+    secret_scanner: Option<Arc<SecretScanner>>,
+
+    // Action taken when the local scanner finds a secret
+    secret_action: SecretAction,
+
+    // Local language detection and policy (optional)
+    language_policy: Option<Arc<LanguagePolicy>>,
+
+    // Local profanity and toxicity pre-filter (optional)
+    toxicity_filter: Option<Arc<ToxicityFilter>>,
+
+    // Local regex and keyword rules deciding content before it is scanned (optional)
+    prefilter_rules: Option<Arc<PrefilterRules>>,
+
+    // Local masking of personal data in prompts (optional)
+    pii_masker: Option<Arc<PiiMasker>>,
+
+    // Local decoding of encoded payloads in prompts for scanning (optional)
+    payload_decoder: Option<Arc<PayloadDecoder>>,
+
+    // Tenant of the current request, selecting its toxicity word list and branding (optional)
+    tenant: Option<String>,
+
+    // Branding of block messages, resolved per tenant
+    brandings: TenantBrandings,
+
+    // Per-client usage counts shared by all clones of this client (optional)
+    usage: Option<UsageLedger>,
+
+    // Identity of the client of the current request, for usage accounting (optional)
+    client_identity: Option<String>,
+
+    // Demo scenario delaying verdicts (optional)
+    scenario: Option<Arc<Scenario>>,
+
+    // Verdicts of recent scans shared by all clones of this client (optional)
+    scan_cache: Option<ScanCache>,
+
+    // Hash of the body of the current request, linking its cached verdicts (optional)
+    request_hash: Option<String>,
+
+    // Whether the current request must not reuse cached verdicts
+    bypass_scan_cache: bool,
+
+    // Scans in progress shared by all clones of this client
+    in_flight: InFlightScans,
+
+    // Settings of asynchronous scanning of streamed responses
+    async_streaming: AsyncStreamingConfig,
+
+    // Whether scans are submitted to the asynchronous scan API
+    async_scans: bool,
+
+    // Whether streamed responses end with a summary of their scans and verdict
+    stream_summary: bool,
+
+    // Redacted PANW responses shared by all clones of this client (optional)
+    response_capture: Option<ResponseCapture>,
+
+    // Circuit breaker shared by all clones of this client (optional)
+    circuit_breaker: Option<CircuitBreaker>,
+
+    // Whether content is passed through when a scan fails during an outage
+    failure_mode: FailureMode,
+
+    // Client keys and networks whose requests skip PANW scanning
+    trusted_clients: TrustedClients,
+
+    // Trusted client of the current request, whose content is not scanned (optional)
+    scan_bypass: Option<String>,
+
+    // OpenAI Moderation API assessing content in place of PANW (optional)
+    moderation: Option<ModerationClient>,
+
+    // Bedrock guardrail assessing content in place of PANW (optional)
+    guardrails: Option<GuardrailsClient>,
+
+    // Azure AI Content Safety assessing content in place of PANW (optional)
+    content_safety: Option<ContentSafetyClient>,
+
+    // Llama Guard model served by Ollama assessing content in place of PANW (optional)
+    llama_guard: Option<LlamaGuardClient>,
+
+    // Backend assessing content when no providers scan in parallel
+    provider: SecurityProvider,
+
+    // Providers scanning the same content in parallel and how their verdicts combine
+    fan_out: Arc<FanOutConfig>,
+}
+
+// Scans in progress, keyed by the hash of their content.
+//
+// Identical content submitted while a scan of it is in progress, which is
+// common with shared system prompts, waits for that scan and shares its verdict
+// instead of making another PANW call. If the scan fails or is cancelled, the
+// waiting requests scan the content themselves, so a failure is never shared.
+#[derive(Clone, Default)]
+struct InFlightScans {
+    // Receivers of the verdicts of the scans in progress
+    scans: Arc<Mutex<HashMap<CacheKey, watch::Receiver<Option<Assessment>>>>>,
+}
+
+// Role of a request in the scan of its content.
+enum Flight {
+    // No scan of the content is in progress; the request scans it
+    Leader(FlightGuard),
+
+    // A scan of the content is in progress; the request waits for its verdict
+    Follower(watch::Receiver<Option<Assessment>>),
+}
+
+// Shares the verdict of a scan and ends the scan when dropped.
+struct FlightGuard {
+    // Content hash of the scan
+    key: CacheKey,
+
+    // Scans in progress
+    scans: InFlightScans,
+
+    // Sends the verdict to waiting requests
+    verdict: watch::Sender<Option<Assessment>>,
+}
+
+impl InFlightScans {
+    // Joins the scan of the content with the given hash, starting it if none
+    // is in progress.
+    fn join(&self, key: CacheKey) -> Flight {
+        let mut scans = self.scans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(receiver) = scans.get(&key) {
+            return Flight::Follower(receiver.clone());
+        }
+
+        let (verdict, receiver) = watch::channel(None);
+        scans.insert(key, receiver);
+        Flight::Leader(FlightGuard {
+            key,
+            scans: self.clone(),
+            verdict,
+        })
+    }
+}
+
+impl Flight {
+    // Waits for the verdict of the leading scan.
+    //
+    // # Returns
+    //
+    // The shared verdict, or `None` if the leading scan failed or was cancelled
+    async fn wait(mut receiver: watch::Receiver<Option<Assessment>>) -> Option<Assessment> {
+        let verdict = receiver.wait_for(Option::is_some).await.ok()?;
+        verdict.clone()
+    }
+}
+
+impl FlightGuard {
+    // Shares the verdict with the requests waiting for it.
+    fn publish(&self, assessment: &Assessment) {
+        self.verdict.send_replace(Some(assessment.clone()));
+    }
+}
+
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        let mut scans = self.scans.scans.lock().unwrap_or_else(|e| e.into_inner());
+        scans.remove(&self.key);
+    }
+}
+
+// Paces PANW scan requests while the API is returning 429 responses.
+//
+// After a 429, requests queue behind a single permit and wait out the advertised
+// retry interval instead of hammering the API. The throttle is shared by all
+// clones of the security client and is lifted by the first successful scan.
+#[derive(Clone)]
+struct ScanThrottle {
+    // Point in time before which no scan request should be sent
+    resume_at: Arc<Mutex<Option<tokio::time::Instant>>>,
+
+    // Serializes requests while throttled so that only one probes the API at a time
+    pacing: Arc<Semaphore>,
+
+    // Longest time a scan may spend waiting before the rate limit error is surfaced
+    max_wait: Duration,
+}
+
+impl ScanThrottle {
+    // Creates a throttle that lets scans wait at most `max_wait`.
+    fn new(max_wait: Duration) -> Self {
+        Self {
+            resume_at: Arc::new(Mutex::new(None)),
+            pacing: Arc::new(Semaphore::new(1)),
+            max_wait,
+        }
+    }
+
+    // Returns when requests may resume, if the API is currently rate limiting.
+    fn resume_at(&self) -> Option<tokio::time::Instant> {
+        let resume_at = *self.resume_at.lock().unwrap_or_else(|e| e.into_inner());
+        resume_at.filter(|at| *at > tokio::time::Instant::now())
+    }
+
+    // Holds back requests for the given retry interval.
+    fn pause_for(&self, wait: Duration) {
+        let until = tokio::time::Instant::now() + wait;
+        let mut resume_at = self.resume_at.lock().unwrap_or_else(|e| e.into_inner());
+        if resume_at.is_none_or(|at| at < until) {
+            warn!(
+                "PANW API rate limit reached, pacing scan requests for {} s",
+                wait.as_secs()
+            );
+            *resume_at = Some(until);
+        }
+    }
+
+    // Lifts the throttle after a successful request.
+    fn clear(&self) {
+        let mut resume_at = self.resume_at.lock().unwrap_or_else(|e| e.into_inner());
+        if resume_at.take().is_some() {
+            info!("PANW API rate limit lifted, resuming normal scan requests");
+        }
+    }
+}
+
+// Response of the PANW AI Runtime API.
+struct ApiReply {
+    // HTTP status of the response
+    status: reqwest::StatusCode,
+
+    // Seconds to wait before retrying, from the `Retry-After` header
+    retry_after_secs: Option<u32>,
+
+    // Raw response body
+    body: String,
+}
+
+// Parses a `Retry-After` header given in seconds or as an HTTP date.
+fn parse_retry_after(value: &str) -> Option<u32> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse() {
+        return Some(secs);
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (at.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_seconds();
+    Some(secs.clamp(0, i64::from(u32::MAX)) as u32)
+}
+
+// Converts a PANW retry interval and unit into a duration.
+fn retry_after_duration(interval: u32, unit: &str) -> Duration {
+    let seconds = match unit.trim_end_matches('s') {
+        "minute" => u64::from(interval) * 60,
+        "hour" => u64::from(interval) * 3_600,
+        "day" => u64::from(interval) * 86_400,
+        _ => u64::from(interval),
+    };
+    Duration::from_secs(seconds.max(1))
 }
 
 impl Content {
@@ -268,15 +665,96 @@ impl SecurityClient {
     // * `app_name` - Name of the application using this security client
     // * `app_user` - Identifier for the user or context within the application
     pub fn new(config: SecurityConfig) -> Self {
+        let moderation = config
+            .uses(SecurityProvider::OpenaiModeration)
+            .then(|| ModerationClient::new(&config.openai_moderation));
+        let guardrails = config
+            .uses(SecurityProvider::BedrockGuardrails)
+            .then(|| GuardrailsClient::new(&config.bedrock_guardrails));
+        let content_safety = config
+            .uses(SecurityProvider::AzureContentSafety)
+            .then(|| ContentSafetyClient::new(&config.azure_content_safety));
         Self {
             client: Client::new(),
             base_url: config.base_url,
-            api_key: config.api_key,
+            api_key: SharedSecret::new(config.api_key),
             profile_name: config.profile_name,
+            selected_profile: None,
+            model_profiles: Arc::new(
+                config
+                    .model_profiles
+                    .iter()
+                    .map(|mapping| (with_tag(&mapping.model), mapping.profile.clone()))
+                    .collect(),
+            ),
+            profile_header: (!config.profile_header.is_empty()).then_some(config.profile_header),
+            allowed_profiles: Arc::new(config.allowed_profiles.into_iter().collect()),
             app_name: config.app_name,
             app_user: config.app_user,
             contextual_grounding_context: config.contextual_grounding,
             user_ip: None,
+            throttle: ScanThrottle::new(Duration::from_secs(config.rate_limit_max_wait_secs)),
+            budget: ScanBudget::new(config.budget),
+            scan_trail: None,
+            transaction_id: None,
+            verdict_trail: None,
+            transcripts: None,
+            session: None,
+            secret_scanner: config
+                .secrets
+                .enabled
+                .then(|| Arc::new(SecretScanner::new())),
+            secret_action: config.secrets.action,
+            language_policy: config
+                .language
+                .enabled
+                .then(|| Arc::new(LanguagePolicy::new(&config.language))),
+            toxicity_filter: config
+                .toxicity
+                .enabled
+                .then(|| Arc::new(ToxicityFilter::new(&config.toxicity))),
+            prefilter_rules: (!config.prefilter_rules.rules.is_empty())
+                .then(|| Arc::new(PrefilterRules::new(&config.prefilter_rules))),
+            pii_masker: config
+                .pii
+                .enabled
+                .then(|| Arc::new(PiiMasker::new(&config.pii))),
+            payload_decoder: config
+                .decode_payloads
+                .enabled
+                .then(|| Arc::new(PayloadDecoder::new(&config.decode_payloads))),
+            tenant: None,
+            brandings: TenantBrandings::default(),
+            usage: None,
+            client_identity: None,
+            scenario: None,
+            scan_cache: config
+                .scan_cache
+                .enabled
+                .then(|| ScanCache::new(&config.scan_cache)),
+            in_flight: InFlightScans::default(),
+            async_streaming: config.async_streaming,
+            async_scans: false,
+            stream_summary: config.stream_summary,
+            response_capture: config
+                .response_capture
+                .enabled
+                .then(|| ResponseCapture::new(&config.response_capture)),
+            circuit_breaker: config
+                .circuit_breaker
+                .enabled
+                .then(|| CircuitBreaker::new(&config.circuit_breaker)),
+            failure_mode: config.failure_mode,
+            trusted_clients: TrustedClients::new(&config.scan_bypass),
+            scan_bypass: None,
+            request_hash: None,
+            bypass_scan_cache: false,
+            moderation,
+            guardrails,
+            content_safety,
+            llama_guard: None,
+            provider: config.provider,
+            fan_out: Arc::new(config.fan_out),
         }
     }
 
@@ -285,8 +763,20 @@ impl SecurityClient {
     //--------------------------------------------------------------------------
 
     /// Returns the base URL of the security service
+    ///
+    /// With several providers scanning in parallel, this is the URL of the first.
     pub fn base_url(&self) -> &str {
-        &self.base_url
+        let provider = self.fan_out.providers.first().unwrap_or(&self.provider);
+        let base_url = match provider {
+            SecurityProvider::Panw => None,
+            SecurityProvider::OpenaiModeration => self.moderation.as_ref().map(|c| c.base_url()),
+            SecurityProvider::BedrockGuardrails => self.guardrails.as_ref().map(|c| c.base_url()),
+            SecurityProvider::AzureContentSafety => {
+                self.content_safety.as_ref().map(|c| c.base_url())
+            }
+            SecurityProvider::LlamaGuard => self.llama_guard.as_ref().map(|c| c.base_url()),
+        };
+        base_url.unwrap_or(&self.base_url)
     }
 
     /// Sets the user IP address for subsequent security assessments
@@ -299,6 +789,305 @@ impl SecurityClient {
         self
     }
 
+    /// Records the report IDs of subsequent scans into the given trail
+    ///
+    /// # Arguments
+    ///
+    /// * `trail` - The scan trail of the request being processed
+    pub fn with_scan_trail(&mut self, trail: ScanTrail) -> &mut Self {
+        self.scan_trail = Some(trail);
+        self
+    }
+
+    /// Sends the given transaction ID as the tr_id of subsequent scans
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The transaction ID of the request being processed
+    pub fn with_transaction_id(&mut self, id: impl Into<String>) -> &mut Self {
+        self.transaction_id = Some(id.into());
+        self
+    }
+
+    /// Records explanations of subsequent assessments into the given trail
+    ///
+    /// # Arguments
+    ///
+    /// * `trail` - The verdict trail of the request being processed
+    pub fn with_verdict_trail(&mut self, trail: VerdictTrail) -> &mut Self {
+        self.verdict_trail = Some(trail);
+        self
+    }
+
+    /// Records subsequent assessments in the given transcripts
+    ///
+    /// # Arguments
+    ///
+    /// * `transcripts` - The transcripts shared by all requests
+    pub fn with_transcripts(&mut self, transcripts: Transcripts) -> &mut Self {
+        self.transcripts = Some(transcripts);
+        self
+    }
+
+    /// Records subsequent assessments under the given transcript session
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session named by the request being processed
+    pub fn with_session(&mut self, session: impl Into<String>) -> &mut Self {
+        self.session = Some(session.into());
+        self
+    }
+
+    /// Reports the given client identity as the app user of subsequent assessments
+    /// and accounts their scans and violations to it
+    ///
+    /// # Arguments
+    ///
+    /// * `app_user` - The identity of the client making the request
+    pub fn with_app_user(&mut self, app_user: impl Into<String>) -> &mut Self {
+        self.app_user = app_user.into();
+        self.client_identity = Some(self.app_user.clone());
+        self
+    }
+
+    /// Authenticates with the given API key instead of the configured one
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - The API key, kept up to date when it is read from Vault
+    pub fn with_api_key(&mut self, api_key: SharedSecret) -> &mut Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// Sends requests to PANW with the given client settings
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Timeouts and connection pooling of the HTTP client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created
+    pub fn with_http_client(
+        &mut self,
+        config: &HttpClientConfig,
+    ) -> Result<&mut Self, SecurityError> {
+        self.client =
+            http_client::builder(config, Some(http_client::DEFAULT_SECURITY_REQUEST_TIMEOUT))
+                .build()?;
+        Ok(self)
+    }
+
+    /// Counts scans and violations of identified clients in the given ledger
+    ///
+    /// # Arguments
+    ///
+    /// * `usage` - The usage ledger shared by all requests
+    pub fn with_usage_ledger(&mut self, usage: UsageLedger) -> &mut Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Uses the given security profile for subsequent assessments
+    ///
+    /// # Arguments
+    ///
+    /// * `profile_name` - The profile selected for the client making the request
+    pub fn with_profile_name(&mut self, profile_name: impl Into<String>) -> &mut Self {
+        self.selected_profile = Some(profile_name.into());
+        self
+    }
+
+    /// Returns the profile a request selects through the profile header, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns a message naming the profile if it is not one of the allowed profiles.
+    pub fn requested_profile(&self, headers: &HeaderMap) -> Result<Option<String>, String> {
+        let Some(header) = &self.profile_header else {
+            return Ok(None);
+        };
+        let Some(profile) = headers
+            .get(header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|profile| !profile.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        if !self.allowed_profiles.contains(profile) {
+            return Err(format!("Security profile {} is not allowed", profile));
+        }
+        debug!("Request selected security profile {}", profile);
+        Ok(Some(profile.to_string()))
+    }
+
+    // Returns the profile scanning content of a model: the profile selected for
+    // the request, else the model's, else the default.
+    fn profile_for(&self, model_name: &str) -> &str {
+        self.selected_profile
+            .as_deref()
+            .or_else(|| {
+                self.model_profiles
+                    .get(&with_tag(model_name))
+                    .map(String::as_str)
+            })
+            .unwrap_or(&self.profile_name)
+    }
+
+    /// Applies the word lists and branding of the given tenant to subsequent assessments
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - The tenant of the request being processed
+    pub fn with_tenant(&mut self, tenant: impl Into<String>) -> &mut Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Brands block messages with the given per-tenant brandings
+    ///
+    /// # Arguments
+    ///
+    /// * `brandings` - The brandings shared by all requests
+    pub fn with_brandings(&mut self, brandings: TenantBrandings) -> &mut Self {
+        self.brandings = brandings;
+        self
+    }
+
+    /// Returns the branding of the current tenant
+    pub fn branding(&self) -> Arc<Branding> {
+        self.brandings.resolve(self.tenant.as_deref())
+    }
+
+    /// Delays verdicts as scripted by the given demo scenario
+    ///
+    /// # Arguments
+    ///
+    /// * `scenario` - The scenario shared by all requests
+    pub fn with_scenario(&mut self, scenario: Arc<Scenario>) -> &mut Self {
+        self.scenario = Some(scenario);
+        self
+    }
+
+    /// Assesses content with a Llama Guard model served by Ollama instead of PANW
+    ///
+    /// # Arguments
+    ///
+    /// * `llama_guard` - Client classifying content through the Ollama client
+    pub fn with_llama_guard(&mut self, llama_guard: LlamaGuardClient) -> &mut Self {
+        self.llama_guard = Some(llama_guard);
+        self
+    }
+
+    /// Returns the asynchronous streaming settings, if asynchronous streaming is enabled
+    pub fn async_streaming(&self) -> Option<&AsyncStreamingConfig> {
+        self.async_streaming.enabled.then_some(&self.async_streaming)
+    }
+
+    /// Returns whether streamed responses end with a summary of their scans and verdict
+    pub fn stream_summary(&self) -> bool {
+        self.stream_summary
+    }
+
+    /// Returns the configured risk window of asynchronous streaming, even when it is disabled
+    pub fn async_risk_window(&self) -> usize {
+        self.async_streaming.risk_window_chars
+    }
+
+    /// Submits subsequent scans to the asynchronous scan API instead of the synchronous one
+    pub fn with_async_scans(&mut self) -> &mut Self {
+        self.async_scans = true;
+        self
+    }
+
+    /// Returns the client keys and networks whose requests skip PANW scanning
+    pub fn trusted_clients(&self) -> &TrustedClients {
+        &self.trusted_clients
+    }
+
+    /// Skips PANW scans of subsequent assessments for a trusted client
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The trusted client making the request
+    pub fn with_scan_bypass(&mut self, client: impl Into<String>) -> &mut Self {
+        self.scan_bypass = Some(client.into());
+        self
+    }
+
+    /// Links the verdicts cached for subsequent assessments to a request body
+    ///
+    /// # Arguments
+    ///
+    /// * `body_hash` - Hex-encoded SHA-256 of the request body
+    /// * `bypass` - Whether cached verdicts must not be reused for the request
+    pub fn with_request_hash(&mut self, body_hash: impl Into<String>, bypass: bool) -> &mut Self {
+        self.request_hash = Some(body_hash.into());
+        self.bypass_scan_cache = bypass;
+        self
+    }
+
+    /// Returns whether verdicts are cached, so requests are linked to them
+    pub fn caches_verdicts(&self) -> bool {
+        self.scan_cache.is_some()
+    }
+
+    /// Drops the verdicts cached for a request body
+    ///
+    /// # Returns
+    ///
+    /// The number of verdicts dropped
+    pub fn invalidate_cached_verdicts(&self, body_hash: &str) -> usize {
+        self.scan_cache
+            .as_ref()
+            .map_or(0, |cache| cache.invalidate(body_hash))
+    }
+
+    /// Returns the captured PANW responses, if response capture is enabled
+    pub fn response_capture(&self) -> Option<&ResponseCapture> {
+        self.response_capture.as_ref()
+    }
+
+    /// Fetches the threat report of a scan from PANW
+    ///
+    /// # Arguments
+    ///
+    /// * `report_id` - Report ID of the scan, as found in verdicts and logs
+    ///
+    /// # Returns
+    ///
+    /// The report as returned by PANW
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecurityError::NotFound` if PANW has no report with the ID, or
+    /// another security provider is in use
+    pub async fn fetch_report(&self, report_id: &str) -> Result<serde_json::Value, SecurityError> {
+        self.fetch_by_id("reports", "report_ids", report_id).await
+    }
+
+    /// Fetches the result of a scan from PANW
+    ///
+    /// # Arguments
+    ///
+    /// * `scan_id` - Scan ID of the scan
+    ///
+    /// # Returns
+    ///
+    /// The scan result as returned by PANW
+    ///
+    /// # Errors
+    ///
+    /// Returns `SecurityError::NotFound` if PANW has no scan with the ID, or
+    /// another security provider is in use
+    pub async fn fetch_result(&self, scan_id: &str) -> Result<serde_json::Value, SecurityError> {
+        self.fetch_by_id("results", "scan_ids", scan_id).await
+    }
+
     // Performs a security assessment on the provided content using PANW AI Runtime API.
     //
     // # Arguments
@@ -325,25 +1114,45 @@ impl SecurityClient {
         // Optimization: Skip assessment for empty content
         if content.trim().is_empty() {
             debug!("Skipping PANW assessment for empty content");
-            return Ok(self.create_safe_assessment());
+            let result = Ok(self.create_safe_assessment());
+            self.explain(&result, model_name, is_prompt, start_time);
+            return result;
+        }
+
+        // Personal data in prompts never leaves the host, not even to be scanned
+        let pii_masked = self.mask_prompt(content, is_prompt);
+        let content = pii_masked.as_deref().unwrap_or(content);
+
+        // Content decided by the local pre-filters is not sent to PANW
+        let rule = self.check_rules(content, is_prompt);
+        if let Some(assessment) = self.prefilter(content, is_prompt, rule) {
+            self.record_violation(&assessment, is_prompt);
+            let result = Ok(Self::keep_pii_masked(assessment, pii_masked.as_deref()));
+            self.explain(&result, model_name, is_prompt, start_time);
+            self.transcribe(&[content], &result, model_name, is_prompt);
+            return result;
         }
 
         // Prepare content for assessment
         let content_obj = self.prepare_content(content, is_prompt)?;
         debug!("Prepared content for PANW assessment: {:#?}", content_obj);
 
-        // Create and send the request payload
-        let payload = self.create_scan_request(content_obj, model_name);
-        let scan_result = self.send_security_request(&payload).await?;
-
-        // Process results
-        let result = self.process_scan_result(scan_result);
+        // Scan the content and process results
+        let deep = rule == Some(PrefilterAction::DeepScan);
+        let mut result = self.scan(content_obj, model_name, deep).await;
+        if !is_prompt {
+            result = result.map(|assessment| self.block_secrets(content, assessment));
+        }
+        if let Some(masked) = &pii_masked {
+            result = result.map(|assessment| Self::keep_pii_masked(assessment, Some(masked)));
+        }
 
         let elapsed_time = start_time.elapsed();
         let content_type = if is_prompt { "prompt" } else { "response" };
 
         match &result {
             Ok(assessment) => {
+                self.record_violation(assessment, is_prompt);
                 if assessment.is_safe {
                     if assessment.is_masked {
                         info!(
@@ -376,89 +1185,622 @@ impl SecurityClient {
             }
         }
 
-        result
+        self.explain(&result, model_name, is_prompt, start_time);
+        self.transcribe(&[content], &result, model_name, is_prompt);
+        result
+    }
+
+    // Performs a security assessment that includes both text and code content.
+    //
+    // # Arguments
+    //
+    // * `text_content` - The regular text content to assess
+    // * `code_content` - The code block content to assess
+    // * `model_name` - Name of the AI model associated with this content
+    // * `is_prompt` - If `true`, content is treated as a prompt to an AI; if `false`, as an AI response
+    //
+    // # Returns
+    //
+    // Security assessment results
+    //
+    // # Errors
+    //
+    // Returns error if assessment fails or content is blocked by security policy
+    pub async fn assess_content_with_code(
+        &self,
+        text_content: &str,
+        code_content: &str,
+        model_name: &str,
+        is_prompt: bool,
+    ) -> Result<Assessment, SecurityError> {
+        let start_time = Instant::now();
+
+        // Skip assessment for empty content
+        if text_content.trim().is_empty() && code_content.trim().is_empty() {
+            debug!("Skipping PANW assessment for empty text and code content");
+            let result = Ok(self.create_safe_assessment());
+            self.explain(&result, model_name, is_prompt, start_time);
+            return result;
+        }
+
+        // Personal data in prompts never leaves the host, not even to be scanned
+        let text_masked = self.mask_prompt(text_content, is_prompt);
+        let code_masked = self.mask_prompt(code_content, is_prompt);
+        let text_content = text_masked.as_deref().unwrap_or(text_content);
+        let code_content = code_masked.as_deref().unwrap_or(code_content);
+        let combined = format!("{}\n{}", text_content, code_content);
+        let pii_masked = (text_masked.is_some() || code_masked.is_some()).then_some(&combined);
+
+        // Content decided by the local pre-filters is not sent to PANW
+        let rule = self.check_rules(&combined, is_prompt);
+        if let Some(assessment) = self.prefilter(text_content, is_prompt, rule) {
+            self.record_violation(&assessment, is_prompt);
+            let result = Ok(Self::keep_pii_masked(assessment, pii_masked.map(String::as_str)));
+            self.explain(&result, model_name, is_prompt, start_time);
+            self.transcribe(&[text_content, code_content], &result, model_name, is_prompt);
+            return result;
+        }
+
+        // Create Content object directly without extracting code blocks
+        let content_obj = if is_prompt {
+            Content::builder()
+                .with_prompt(text_content.to_string())
+                .with_code_prompt(self.append_decoded_payloads(&combined, code_content.to_string()))
+                .build()
+                .map_err(|e| SecurityError::AssessmentError(e.to_string()))?
+        } else {
+            Content::builder()
+                .with_response(text_content.to_string())
+                .with_code_response(code_content.to_string())
+                .build()
+                .map_err(|e| SecurityError::AssessmentError(e.to_string()))?
+        };
+
+        // Scan the content and process results
+        let deep = rule == Some(PrefilterAction::DeepScan);
+        let mut result = self.scan(content_obj, model_name, deep).await;
+        if !is_prompt {
+            result = result.map(|assessment| self.block_secrets(&combined, assessment));
+        }
+        if let Some(masked) = pii_masked {
+            result = result.map(|assessment| Self::keep_pii_masked(assessment, Some(masked)));
+        }
+
+        let elapsed_time = start_time.elapsed();
+        let content_type = if is_prompt { "prompt" } else { "response" };
+
+        match &result {
+            Ok(assessment) => {
+                self.record_violation(assessment, is_prompt);
+                if assessment.is_safe {
+                    info!(
+                        "Security assessment with code completed in {} ms - {} passed security assessment",
+                        elapsed_time.as_millis(), content_type
+                    );
+                } else {
+                    warn!(
+                        "Security assessment with code completed in {} ms - {} failed security assessment: category={}, action={}",
+                        elapsed_time.as_millis(), content_type, assessment.category, assessment.action
+                    );
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Security assessment with code failed in {} ms - error: {}",
+                    elapsed_time.as_millis(),
+                    e
+                );
+            }
+        }
+
+        self.explain(&result, model_name, is_prompt, start_time);
+        self.transcribe(&[text_content, code_content], &result, model_name, is_prompt);
+        result
+    }
+
+    // Scans prepared content with the PANW AI Runtime API, honouring the scan budget.
+    //
+    // Verdicts cached for identical content are reused without a scan, and identical
+    // content already being scanned shares that scan's verdict. Once the monthly scan
+    // budget is exhausted, or while the circuit breaker is open, the configured mode
+    // decides whether the content is rejected or passed through without a PANW scan.
+    // When the scan fails because the PANW API is unavailable, the failure mode
+    // decides the same. Content of trusted clients is passed through unscanned,
+    // unless a deep scan is forced, which also bypasses cached and concurrent scans.
+    //
+    // # Arguments
+    //
+    // * `content_obj` - Content object to scan
+    // * `model_name` - Name of the AI model associated with this content
+    // * `deep` - Whether a pre-filter rule forces a fresh scan
+    //
+    // # Returns
+    //
+    // Assessment built from the scan results
+    async fn scan(
+        &self,
+        content_obj: Content,
+        model_name: &str,
+        deep: bool,
+    ) -> Result<Assessment, SecurityError> {
+        if let Some(client) = self.scan_bypass.as_ref().filter(|_| !deep) {
+            debug!("Skipping PANW scan for trusted client {}", client);
+            let mut assessment = self.create_safe_assessment();
+            assessment.decided_by = "scan_bypass";
+            return Ok(assessment);
+        }
+
+        let is_prompt = content_obj.prompt.is_some() || content_obj.code_prompt.is_some();
+        let key = ScanCache::key(&content_obj, self.profile_for(model_name), is_prompt);
+        let cached = self
+            .scan_cache
+            .as_ref()
+            .filter(|_| !deep && !self.bypass_scan_cache)
+            .and_then(|cache| cache.get(&key));
+        if let Some(mut assessment) = cached {
+            debug!("Reusing cached verdict for identical content");
+            assessment.decided_by = "scan_cache";
+            if let Some(trail) = &self.scan_trail {
+                trail.record(&assessment.details.report_id);
+            }
+            return Ok(assessment);
+        }
+
+        // Requests whose leading scan failed scan the content themselves
+        let flight = match self.in_flight.join(key) {
+            _ if deep => None,
+            Flight::Leader(guard) => Some(guard),
+            Flight::Follower(receiver) => match Flight::wait(receiver).await {
+                Some(assessment) => {
+                    debug!("Sharing the verdict of a concurrent scan of identical content");
+                    metrics::increment_counter(
+                        "panw_scans_coalesced_total",
+                        "Scans answered by a concurrent scan of identical content",
+                        &[],
+                    );
+                    if let Some(trail) = &self.scan_trail {
+                        trail.record(&assessment.details.report_id);
+                    }
+                    return Ok(assessment);
+                }
+                None => None,
+            },
+        };
+
+        if let Some(mode) = self.circuit_breaker.as_ref().and_then(CircuitBreaker::check) {
+            if mode == DegradedMode::Block {
+                return Err(SecurityError::CircuitOpen);
+            }
+            let mut assessment = self.fail_open(&SecurityError::CircuitOpen, is_prompt);
+            assessment.decided_by = "circuit_breaker";
+            return Ok(assessment);
+        }
+
+        match self.budget.reserve() {
+            BudgetDecision::Scan => {}
+            BudgetDecision::Degraded(DegradedMode::Block) => {
+                return Err(SecurityError::BudgetExhausted);
+            }
+            BudgetDecision::Degraded(mode) => {
+                warn!("Scan budget exhausted, skipping PANW scan in {:?} mode", mode);
+                let mut assessment = self.create_safe_assessment();
+                assessment.decided_by = "scan_budget";
+                return Ok(assessment);
+            }
+        }
+
+        if let Some(scenario) = &self.scenario {
+            let text = [
+                &content_obj.prompt,
+                &content_obj.response,
+                &content_obj.code_prompt,
+                &content_obj.code_response,
+            ]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+            let delay = scenario.verdict_delay(&text, is_prompt);
+            if !delay.is_zero() {
+                debug!("Demo scenario delays verdict by {} ms", delay.as_millis());
+                tokio::time::sleep(delay).await;
+            }
+        }
+
+        let payload = self.create_scan_request(content_obj, model_name);
+        let scan_result = if self.fan_out.providers.is_empty() {
+            self.scan_with(self.provider, &payload).await
+        } else {
+            self.fan_out_scan(&payload).await
+        };
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.record(&scan_result);
+        }
+        let scan_result = match scan_result {
+            Err(e) if e.is_outage() && self.failure_mode != FailureMode::Closed => {
+                return Ok(self.fail_open(&e, is_prompt));
+            }
+            scan_result => scan_result?,
+        };
+        let assessment = self.process_scan_result(scan_result)?;
+
+        if let Some(trail) = &self.scan_trail {
+            trail.record(&assessment.details.report_id);
+        }
+        if let (Some(usage), Some(client)) = (&self.usage, &self.client_identity) {
+            usage.record_scan(client);
+        }
+        if let Some(cache) = &self.scan_cache {
+            cache.insert(key, assessment.clone(), self.request_hash.as_deref());
+        }
+        if let Some(guard) = &flight {
+            guard.publish(&assessment);
+        }
+
+        Ok(assessment)
+    }
+
+    // Passes content through unscanned after a scan failed during an outage, or was skipped
+    // by the open circuit breaker, exporting a `fail_open` security event if the failure
+    // mode asks for an audit record.
+    fn fail_open(&self, error: &SecurityError, is_prompt: bool) -> Assessment {
+        let direction = if is_prompt { "prompt" } else { "response" };
+        warn!("PANW scan failed, passing {} through unscanned: {}", direction, error);
+        metrics::increment_counter(
+            "panw_scans_failed_open_total",
+            "Prompts and responses passed through unscanned because the PANW scan failed",
+            &[("direction", direction)],
+        );
+
+        if self.failure_mode == FailureMode::OpenLog {
+            security_events::publish(
+                EventType::FailOpen,
+                &format!("{} passed through unscanned", direction),
+                &[
+                    ("direction", json!(direction)),
+                    ("error", json!(error.to_string())),
+                    ("transaction_id", json!(self.transaction_id)),
+                    ("client", json!(self.client_identity)),
+                    ("tenant", json!(self.tenant)),
+                ],
+            );
+        }
+
+        let mut assessment = self.create_safe_assessment();
+        assessment.decided_by = "failure_mode";
+        assessment
+    }
+
+    // Records a violation of the request's client if the assessment blocks the content, and
+    // publishes a security event if it blocks or masks the content.
+    fn record_violation(&self, assessment: &Assessment, is_prompt: bool) {
+        self.publish_event(assessment, is_prompt);
+        if assessment.is_safe {
+            return;
+        }
+        if let (Some(usage), Some(client)) = (&self.usage, &self.client_identity) {
+            usage.record_violation(client, assessment, is_prompt);
+        }
+    }
+
+    // Publishes a security event if the assessment blocks or masks the content.
+    fn publish_event(&self, assessment: &Assessment, is_prompt: bool) {
+        let (event, verb) = match (assessment.is_safe, assessment.is_masked) {
+            (false, _) => (EventType::Block, "blocked"),
+            (true, true) => (EventType::Mask, "masked"),
+            (true, false) => return,
+        };
+        let direction = if is_prompt { "prompt" } else { "response" };
+        let report_id = &assessment.details.report_id;
+        let providers = &assessment.details.provider_verdicts;
+        security_events::publish(
+            event,
+            &format!("{} {} ({})", direction, verb, assessment.category),
+            &[
+                ("direction", json!(direction)),
+                ("category", json!(assessment.category)),
+                ("action", json!(assessment.action)),
+                ("decided_by", json!(assessment.decided_by)),
+                ("report_id", json!((!report_id.is_empty()).then_some(report_id))),
+                ("providers", json!((!providers.is_empty()).then_some(providers))),
+                ("transaction_id", json!(self.transaction_id)),
+                ("client", json!(self.client_identity)),
+                ("tenant", json!(self.tenant)),
+            ],
+        );
+    }
+
+    // Records assessed content and its verdict in the transcript of the request's
+    // session, if transcripts are enabled.
+    fn transcribe(
+        &self,
+        parts: &[&str],
+        result: &Result<Assessment, SecurityError>,
+        model_name: &str,
+        is_prompt: bool,
+    ) {
+        let Some(transcripts) = &self.transcripts else {
+            return;
+        };
+        let content = parts
+            .iter()
+            .copied()
+            .filter(|part| !part.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let (verdict, detections, report_id, providers) = match result {
+            Ok(assessment) => (
+                if !assessment.is_safe {
+                    "block"
+                } else if assessment.is_masked {
+                    "mask"
+                } else {
+                    "allow"
+                },
+                assessment.reasons(),
+                Some(assessment.details.report_id.as_str()).filter(|id| !id.is_empty()),
+                assessment.details.provider_verdicts.as_slice(),
+            ),
+            Err(_) => ("error", Vec::new(), None, &[][..]),
+        };
+        transcripts.record(Entry {
+            session: self
+                .session
+                .as_deref()
+                .or(self.transaction_id.as_deref())
+                .unwrap_or("unknown"),
+            is_prompt,
+            model: model_name,
+            client: self.client_identity.as_deref(),
+            tenant: self.tenant.as_deref(),
+            transaction_id: self.transaction_id.as_deref(),
+            content: &content,
+            verdict,
+            detections,
+            report_id,
+            providers,
+        });
+    }
+
+    // Records the explanation of an assessment if the request's verdicts are explained.
+    fn explain(
+        &self,
+        result: &Result<Assessment, SecurityError>,
+        model_name: &str,
+        is_prompt: bool,
+        start_time: Instant,
+    ) {
+        let Some(trail) = &self.verdict_trail else {
+            return;
+        };
+
+        let (decided_by, blocked) = match result {
+            Ok(assessment) => (assessment.decided_by, !assessment.is_safe),
+            Err(SecurityError::BudgetExhausted) => ("scan_budget", true),
+            Err(_) => ("panw", true),
+        };
+        let mut trace = AssessmentTrace {
+            stage: if is_prompt { "prompt" } else { "response" },
+            model: model_name.to_string(),
+            profile: self.profile_for(model_name).to_string(),
+            policy_path: self.policy_path(decided_by, blocked, is_prompt),
+            decided_by,
+            action: String::new(),
+            category: String::new(),
+            masked: false,
+            detections: Vec::new(),
+            report_id: None,
+            error: None,
+            latency_ms: start_time.elapsed().as_millis() as u64,
+        };
+        match result {
+            Ok(assessment) => {
+                trace.action = assessment.action.clone();
+                trace.category = assessment.category.clone();
+                trace.masked = assessment.is_masked;
+                trace.detections = assessment.reasons();
+                trace.report_id = Some(assessment.details.report_id.clone())
+                    .filter(|report_id| !report_id.is_empty());
+            }
+            Err(e) => trace.error = Some(e.to_string()),
+        }
+        trail.record(trace);
+    }
+
+    // Returns the checks run for content, in order, up to the one that blocked it
+    // or, for a pre-filter rule, decided it.
+    fn policy_path(
+        &self,
+        decided_by: &'static str,
+        blocked: bool,
+        is_prompt: bool,
+    ) -> Vec<&'static str> {
+        if decided_by == "empty_content" {
+            return vec![decided_by];
+        }
+
+        let scanner = match decided_by {
+            "scan_budget" | "scan_cache" | "failure_mode" | "scan_bypass" => decided_by,
+            _ => "panw",
+        };
+        let checks = [
+            ("language_policy", self.language_policy.is_some()),
+            ("toxicity_filter", self.toxicity_filter.is_some()),
+            ("prefilter_rule", self.prefilter_rules.is_some()),
+            (scanner, true),
+            (
+                "secret_scanner",
+                !is_prompt
+                    && self.secret_scanner.is_some()
+                    && self.secret_action == SecretAction::Block,
+            ),
+        ];
+        let mut path = Vec::new();
+        for (check, runs) in checks {
+            if runs {
+                path.push(check);
+            }
+            if check == decided_by && (blocked || check == "prefilter_rule") {
+                break;
+            }
+        }
+        path
     }
 
-    // Performs a security assessment that includes both text and code content.
+    // Masks secrets found by the local secret scanner in response text.
     //
     // # Arguments
     //
-    // * `text_content` - The regular text content to assess
-    // * `code_content` - The code block content to assess
-    // * `model_name` - Name of the AI model associated with this content
-    // * `is_prompt` - If `true`, content is treated as a prompt to an AI; if `false`, as an AI response
+    // * `text` - Response text to mask
+    // * `open_block` - Whether a private key block is still open from earlier streamed text
     //
     // # Returns
     //
-    // Security assessment results
+    // The masked text if the scanner is enabled in mask mode and found a secret, None otherwise
+    pub fn mask_secrets(&self, text: &str, open_block: &mut bool) -> Option<String> {
+        let scanner = self.secret_scanner.as_ref()?;
+        if self.secret_action != SecretAction::Mask {
+            return None;
+        }
+
+        let (masked, found) = scanner.mask(text, open_block);
+        if found.is_empty() {
+            return None;
+        }
+
+        secrets::record_findings(&found, self.secret_action);
+        Some(masked)
+    }
+
+    // Masks personal data of the configured types in a prompt.
     //
-    // # Errors
+    // # Returns
     //
-    // Returns error if assessment fails or content is blocked by security policy
-    pub async fn assess_content_with_code(
-        &self,
-        text_content: &str,
-        code_content: &str,
-        model_name: &str,
-        is_prompt: bool,
-    ) -> Result<Assessment, SecurityError> {
-        let start_time = Instant::now();
+    // The masked prompt if PII masking is enabled and found personal data, None otherwise
+    pub fn mask_pii(&self, text: &str) -> Option<String> {
+        let (masked, found) = self.pii_masker.as_ref()?.mask(text);
+        if found.is_empty() {
+            return None;
+        }
 
-        // Skip assessment for empty content
-        if text_content.trim().is_empty() && code_content.trim().is_empty() {
-            debug!("Skipping PANW assessment for empty text and code content");
-            return Ok(self.create_safe_assessment());
+        pii::record_findings(&found);
+        Some(masked)
+    }
+
+    // Masks personal data in a prompt before any local check or scan sees it; every
+    // assessment path starts with this step.
+    //
+    // # Returns
+    //
+    // The masked prompt, or None for responses and prompts without personal data
+    fn mask_prompt(&self, content: &str, is_prompt: bool) -> Option<String> {
+        is_prompt.then(|| self.mask_pii(content)).flatten()
+    }
+
+    // Makes an allowed prompt assessment carry the prompt with its personal data
+    // masked, unless the scan masked it further.
+    fn keep_pii_masked(mut assessment: Assessment, masked: Option<&str>) -> Assessment {
+        if let Some(masked) = masked {
+            if assessment.is_safe && !assessment.is_masked {
+                assessment.final_content = masked.to_string();
+                assessment.is_masked = true;
+            }
         }
+        assessment
+    }
 
-        // Create Content object directly without extracting code blocks
-        let content_obj = if is_prompt {
-            Content::builder()
-                .with_prompt(text_content.to_string())
-                .with_code_prompt(code_content.to_string())
-                .build()
-                .map_err(|e| SecurityError::AssessmentError(e.to_string()))?
-        } else {
-            Content::builder()
-                .with_response(text_content.to_string())
-                .with_code_response(code_content.to_string())
-                .build()
-                .map_err(|e| SecurityError::AssessmentError(e.to_string()))?
+    // Blocks an otherwise safe response assessment if the local scanner finds a secret.
+    //
+    // Only applies when the secret scanner is enabled in block mode; findings are
+    // reported as sensitive data in the response.
+    fn block_secrets(&self, content: &str, mut assessment: Assessment) -> Assessment {
+        let Some(scanner) = &self.secret_scanner else {
+            return assessment;
         };
+        if !assessment.is_safe || self.secret_action != SecretAction::Block {
+            return assessment;
+        }
 
-        // Create and send the request payload
-        let payload = self.create_scan_request(content_obj, model_name);
-        let scan_result = self.send_security_request(&payload).await?;
+        let found = scanner.find(content);
+        if found.is_empty() {
+            return assessment;
+        }
 
-        // Process results
-        let result = self.process_scan_result(scan_result);
+        secrets::record_findings(&found, self.secret_action);
+        assessment.is_safe = false;
+        assessment.category = "malicious".to_string();
+        assessment.action = "block".to_string();
+        assessment.details.response_detected.dlp = true;
+        assessment.decided_by = "secret_scanner";
+        assessment
+    }
 
-        let elapsed_time = start_time.elapsed();
-        let content_type = if is_prompt { "prompt" } else { "response" };
+    // Decides content by the local pre-filter rules, if any are configured.
+    fn check_rules(&self, content: &str, is_prompt: bool) -> Option<PrefilterAction> {
+        self.prefilter_rules
+            .as_ref()
+            .and_then(|rules| rules.check(content, is_prompt))
+    }
 
-        match &result {
-            Ok(assessment) => {
-                if assessment.is_safe {
-                    info!(
-                        "Security assessment with code completed in {} ms - {} passed security assessment",
-                        elapsed_time.as_millis(), content_type
-                    );
-                } else {
-                    warn!(
-                        "Security assessment with code completed in {} ms - {} failed security assessment: category={}, action={}",
-                        elapsed_time.as_millis(), content_type, assessment.category, assessment.action
-                    );
-                }
-            }
-            Err(e) => {
-                error!(
-                    "Security assessment with code failed in {} ms - error: {}",
-                    elapsed_time.as_millis(),
-                    e
-                );
+    // Checks content against the local language policy, toxicity pre-filter and
+    // pre-filter rules.
+    //
+    // # Arguments
+    //
+    // * `content` - Content to check
+    // * `is_prompt` - Whether the content is a prompt
+    // * `rule` - Action of the pre-filter rules matching the content
+    //
+    // # Returns
+    //
+    // A blocked assessment if any of them blocks the content, an allowed one if
+    // an allow rule decides it, None if the content is to be scanned
+    fn prefilter(
+        &self,
+        content: &str,
+        is_prompt: bool,
+        rule: Option<PrefilterAction>,
+    ) -> Option<Assessment> {
+        let language_blocked = self
+            .language_policy
+            .as_ref()
+            .is_some_and(|policy| policy.check(content, is_prompt));
+        let toxic = !language_blocked
+            && self
+                .toxicity_filter
+                .as_ref()
+                .is_some_and(|filter| filter.check(content, self.tenant.as_deref(), is_prompt));
+        let rule_blocked = rule == Some(PrefilterAction::Block);
+        if !language_blocked && !toxic && !rule_blocked {
+            if rule != Some(PrefilterAction::Allow) {
+                return None;
             }
+            let mut assessment = self.create_safe_assessment();
+            assessment.decided_by = "prefilter_rule";
+            return Some(assessment);
         }
 
-        result
+        let mut assessment = self.create_safe_assessment();
+        assessment.is_safe = false;
+        assessment.category = "malicious".to_string();
+        assessment.action = "block".to_string();
+        assessment.decided_by = if language_blocked {
+            "language_policy"
+        } else if toxic {
+            "toxicity_filter"
+        } else {
+            "prefilter_rule"
+        };
+        let topic_violation = language_blocked || rule_blocked;
+        if is_prompt {
+            assessment.details.prompt_detected.topic_violation = topic_violation;
+            assessment.details.prompt_detected.toxic_content = toxic;
+        } else {
+            assessment.details.response_detected.topic_violation = topic_violation;
+            assessment.details.response_detected.toxic_content = toxic;
+        }
+        Some(assessment)
     }
 
     //--------------------------------------------------------------------------
@@ -476,6 +1818,7 @@ impl SecurityClient {
             final_content: String::new(),
             is_masked: false,
             details: ScanResponse::default_safe_response(),
+            decided_by: "empty_content",
         }
     }
 
@@ -537,6 +1880,24 @@ impl SecurityClient {
         code_content
     }
 
+    // Appends the text decoded from encoded payloads in a prompt to its code
+    // content, so instructions or secrets smuggled in an encoding are scanned too.
+    fn append_decoded_payloads(&self, prompt: &str, code: String) -> String {
+        let Some(decoder) = &self.payload_decoder else {
+            return code;
+        };
+        let decoded = decoder.decode(prompt);
+        if decoded.is_empty() {
+            return code;
+        }
+        Some(code)
+            .filter(|code| !code.is_empty())
+            .into_iter()
+            .chain(decoded.into_iter().map(|(_, text)| text))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     // Prepares a Content object for PANW assessment based on the provided text.
     //
     // # Arguments
@@ -548,8 +1909,11 @@ impl SecurityClient {
     //
     // Structured Content object ready for assessment
     fn prepare_content(&self, content: &str, is_prompt: bool) -> Result<Content, SecurityError> {
-        // Extract any code blocks
-        let code_blocks = self.extract_code_blocks(content);
+        // Extract any code blocks, along with decoded payloads of prompts
+        let mut code_blocks = self.extract_code_blocks(content);
+        if is_prompt {
+            code_blocks = self.append_decoded_payloads(content, code_blocks);
+        }
         let has_code = !code_blocks.is_empty();
 
         // Remove code blocks from the main content to avoid duplication
@@ -663,6 +2027,7 @@ impl SecurityClient {
             final_content,
             is_masked,
             details: scan_result,
+            decided_by: "panw",
         };
 
         Ok(assessment)
@@ -680,9 +2045,12 @@ impl SecurityClient {
     // * `model_name` - Name of the AI model associated with this content
     fn create_scan_request(&self, content_obj: Content, model_name: &str) -> ScanRequest {
         ScanRequest {
-            tr_id: Uuid::new_v4().to_string(),
+            tr_id: self
+                .transaction_id
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
             ai_profile: AiProfile {
-                profile_name: self.profile_name.clone(),
+                profile_name: self.profile_for(model_name).to_string(),
             },
             metadata: Metadata {
                 app_name: self.app_name.to_string(),
@@ -694,6 +2062,154 @@ impl SecurityClient {
         }
     }
 
+    // Scans the content of a scan request with one provider.
+    //
+    // # Arguments
+    //
+    // * `provider` - Backend assessing the content
+    // * `payload` - The scan whose content is assessed
+    //
+    // # Returns
+    //
+    // The provider's verdict as a scan response
+    async fn scan_with(
+        &self,
+        provider: SecurityProvider,
+        payload: &ScanRequest,
+    ) -> Result<ScanResponse, SecurityError> {
+        let not_configured = || {
+            SecurityError::AssessmentError(format!(
+                "Security provider {} is not configured",
+                provider.as_str()
+            ))
+        };
+        match provider {
+            SecurityProvider::Panw if self.async_scans => self.send_async_request(payload).await,
+            SecurityProvider::Panw => self.send_security_request(payload).await,
+            SecurityProvider::OpenaiModeration => {
+                let moderation = self.moderation.as_ref().ok_or_else(not_configured)?;
+                self.send_moderation_request(moderation, payload).await
+            }
+            SecurityProvider::BedrockGuardrails => {
+                let guardrails = self.guardrails.as_ref().ok_or_else(not_configured)?;
+                self.send_guardrail_request(guardrails, payload).await
+            }
+            SecurityProvider::AzureContentSafety => {
+                let content_safety = self.content_safety.as_ref().ok_or_else(not_configured)?;
+                self.send_content_safety_request(content_safety, payload).await
+            }
+            SecurityProvider::LlamaGuard => {
+                let llama_guard = self.llama_guard.as_ref().ok_or_else(not_configured)?;
+                llama_guard.classify(payload).await
+            }
+        }
+    }
+
+    // Scans the content of a scan request with all providers of the fan-out in
+    // parallel and combines their verdicts with the configured policy.
+    //
+    // The scan fails when any provider fails. A blocked scan is reported with the
+    // response of the first provider that blocked, an allowed one with the
+    // response of the first provider that allowed, preferring one that masked
+    // data. The verdicts of all providers are attached to the response.
+    //
+    // # Arguments
+    //
+    // * `payload` - The scan whose content is assessed
+    async fn fan_out_scan(&self, payload: &ScanRequest) -> Result<ScanResponse, SecurityError> {
+        let providers = &self.fan_out.providers;
+        let responses = futures_util::future::join_all(
+            providers
+                .iter()
+                .map(|provider| self.scan_with(*provider, payload)),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let verdicts: Vec<ProviderVerdict> = providers
+            .iter()
+            .zip(&responses)
+            .map(|(provider, response)| ProviderVerdict {
+                provider: provider.as_str().to_string(),
+                action: response.action.clone(),
+                category: response.category.clone(),
+                report_id: response.report_id.clone(),
+            })
+            .collect();
+        for verdict in &verdicts {
+            debug!(
+                "Security provider {} decided {} ({})",
+                verdict.provider, verdict.action, verdict.category
+            );
+            metrics::increment_counter(
+                "panw_fan_out_verdicts_total",
+                "Verdicts of the security providers scanning in parallel",
+                &[
+                    ("provider", verdict.provider.as_str()),
+                    ("action", verdict.action.as_str()),
+                ],
+            );
+        }
+
+        let blocks = |response: &ScanResponse| response.action == "block";
+        let blocked = match self.fan_out.policy {
+            VerdictPolicy::AnyBlock => responses.iter().any(blocks),
+            VerdictPolicy::AllBlock => responses.iter().all(blocks),
+            VerdictPolicy::Weighted => {
+                let weight = |provider: &SecurityProvider| {
+                    self.fan_out
+                        .weights
+                        .get(provider.as_str())
+                        .copied()
+                        .unwrap_or(1.0)
+                };
+                let total: f64 = providers.iter().map(weight).sum();
+                let blocking: f64 = providers
+                    .iter()
+                    .zip(&responses)
+                    .filter(|(_, response)| blocks(response))
+                    .map(|(provider, _)| weight(provider))
+                    .sum();
+                total > 0.0 && blocking / total >= self.fan_out.threshold
+            }
+        };
+
+        let masks = |response: &ScanResponse| {
+            !response.prompt_masked_data.data.is_empty()
+                || !response.response_masked_data.data.is_empty()
+        };
+        let chosen = if blocked {
+            responses.iter().position(blocks)
+        } else {
+            responses
+                .iter()
+                .position(|response| !blocks(response) && masks(response))
+                .or_else(|| responses.iter().position(|response| !blocks(response)))
+        };
+        let mut response = responses
+            .into_iter()
+            .nth(chosen.unwrap_or(0))
+            .unwrap_or_else(ScanResponse::default_safe_response);
+        if chosen.is_none() {
+            // Only reached when zero weights allow content every provider blocked
+            response.action = "allow".to_string();
+            response.category = "benign".to_string();
+        }
+        info!(
+            "Security fan-out ({}) {} content: {}",
+            self.fan_out.policy.as_str(),
+            if blocked { "blocked" } else { "allowed" },
+            verdicts
+                .iter()
+                .map(|verdict| format!("{} {}", verdict.provider, verdict.action))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        response.provider_verdicts = verdicts;
+        Ok(response)
+    }
+
     // Sends a security assessment request to the PANW AI Runtime API and processes the response.
     //
     // # Arguments
@@ -707,80 +2223,342 @@ impl SecurityClient {
         &self,
         payload: &ScanRequest,
     ) -> Result<ScanResponse, SecurityError> {
-        let (status, body_text) = self.make_api_request(payload).await?;
-        self.parse_api_response(status, body_text)
+        self.with_backoff(|| async {
+            let reply = self.make_api_request(payload).await?;
+            self.parse_api_response(reply)
+        })
+        .await
     }
 
-    // Makes an HTTP request to the PANW AI Runtime API.
+    // Sends a moderation request to the OpenAI Moderation API and maps its verdict
+    // onto a scan response.
     //
     // # Arguments
     //
-    // * `payload` - The request payload to send
+    // * `moderation` - Client of the OpenAI Moderation API
+    // * `payload` - The scan whose content is moderated
+    async fn send_moderation_request(
+        &self,
+        moderation: &ModerationClient,
+        payload: &ScanRequest,
+    ) -> Result<ScanResponse, SecurityError> {
+        self.with_backoff(|| async {
+            let reply = self.send(moderation.request(&self.client, payload)).await?;
+            let response = self.parse_api_response(reply)?;
+            Ok(moderation.scan_response(payload, response))
+        })
+        .await
+    }
+
+    // Applies the Bedrock guardrail to the content of a scan and maps its verdict
+    // onto a scan response.
+    //
+    // # Arguments
+    //
+    // * `guardrails` - Client of the Bedrock ApplyGuardrail API
+    // * `payload` - The scan whose content is assessed
+    async fn send_guardrail_request(
+        &self,
+        guardrails: &GuardrailsClient,
+        payload: &ScanRequest,
+    ) -> Result<ScanResponse, SecurityError> {
+        self.with_backoff(|| async {
+            let reply = self.send(guardrails.request(&self.client, payload)).await?;
+            let response = self.parse_api_response(reply)?;
+            Ok(guardrails.scan_response(payload, response))
+        })
+        .await
+    }
+
+    // Analyzes the content of a scan with Azure AI Content Safety and maps its
+    // severities onto a scan response.
+    //
+    // # Arguments
+    //
+    // * `content_safety` - Client of the Content Safety text analysis API
+    // * `payload` - The scan whose content is analyzed
+    async fn send_content_safety_request(
+        &self,
+        content_safety: &ContentSafetyClient,
+        payload: &ScanRequest,
+    ) -> Result<ScanResponse, SecurityError> {
+        self.with_backoff(|| async {
+            let mut analyses = Vec::new();
+            for request in content_safety.requests(&self.client, payload) {
+                let reply = self.send(request).await?;
+                analyses.push(self.parse_api_response(reply)?);
+            }
+            Ok(content_safety.scan_response(payload, analyses))
+        })
+        .await
+    }
+
+    // Sends a request to the PANW AI Runtime API, waiting out rate limiting.
+    //
+    // While the API is rate limiting, requests are queued and paced according to the
+    // retry interval advertised in the 429 response body or its `Retry-After` header.
+    // The rate limit error is only returned once waiting would exceed the configured
+    // maximum wait.
+    //
+    // # Arguments
+    //
+    // * `send` - Sends the request and parses its response; called again for every retry
+    async fn with_backoff<T, F, Fut>(&self, send: F) -> Result<T, SecurityError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, SecurityError>>,
+    {
+        let deadline = tokio::time::Instant::now() + self.throttle.max_wait;
+
+        loop {
+            // While throttled, queue behind a single permit and wait out the retry interval
+            let _permit = match self.throttle.resume_at() {
+                Some(resume_at) => {
+                    if resume_at > deadline {
+                        let remaining = resume_at - tokio::time::Instant::now();
+                        return Err(SecurityError::TooManyRequests(
+                            remaining.as_secs().max(1) as u32,
+                            "second".to_string(),
+                        ));
+                    }
+                    let permit = self.throttle.pacing.acquire().await.map_err(|e| {
+                        SecurityError::AssessmentError(format!("Scan queue closed: {}", e))
+                    })?;
+                    if let Some(resume_at) = self.throttle.resume_at() {
+                        debug!("Waiting for PANW rate limit to lift before scanning");
+                        tokio::time::sleep_until(resume_at).await;
+                    }
+                    Some(permit)
+                }
+                None => None,
+            };
+
+            match send().await {
+                Err(SecurityError::TooManyRequests(interval, unit)) => {
+                    let wait = retry_after_duration(interval, &unit);
+                    self.throttle.pause_for(wait);
+                    if tokio::time::Instant::now() + wait > deadline {
+                        return Err(SecurityError::TooManyRequests(interval, unit));
+                    }
+                }
+                result => {
+                    if result.is_ok() {
+                        self.throttle.clear();
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Submits a scan to the PANW asynchronous scan API and polls for its result.
+    //
+    // The scan is submitted as a batch of one. Its result is polled at the configured
+    // interval until the scan completes or the settle timeout passes.
+    //
+    // # Arguments
+    //
+    // * `payload` - The request payload to submit
     //
     // # Returns
     //
-    // Status code and response body from the API
-    async fn make_api_request(
+    // Scan response of the completed scan
+    async fn send_async_request(
         &self,
         payload: &ScanRequest,
-    ) -> Result<(reqwest::StatusCode, String), SecurityError> {
+    ) -> Result<ScanResponse, SecurityError> {
+        let endpoint = format!("{}/v1/scan/async/request", self.base_url);
+        debug!("Submitting asynchronous security assessment to: {}", endpoint);
+
+        let batch = [AsyncScanObject {
+            req_id: 1,
+            scan_req: payload.clone(),
+        }];
+        let submitted: AsyncScanResponse = self
+            .with_backoff(|| async {
+                let reply = self.execute(self.client.post(&endpoint).json(&batch)).await?;
+                self.parse_api_response(reply)
+            })
+            .await?;
+        debug!(
+            "Asynchronous scan {} submitted with report {}",
+            submitted.scan_id, submitted.report_id
+        );
+
+        let interval = Duration::from_millis(self.async_streaming.poll_interval_ms);
+        let timeout = Duration::from_secs(self.async_streaming.settle_timeout_secs);
+        let deadline = Instant::now() + timeout;
+        let endpoint = format!(
+            "{}/v1/scan/results?scan_ids={}",
+            self.base_url, submitted.scan_id
+        );
+        loop {
+            tokio::time::sleep(interval).await;
+            let results: Vec<ScanIdResult> = self
+                .with_backoff(|| async {
+                    let reply = self.execute(self.client.get(&endpoint)).await?;
+                    self.parse_api_response(reply)
+                })
+                .await?;
+            let completed = results
+                .into_iter()
+                .find(|result| result.scan_id == submitted.scan_id && result.req_id == 1)
+                .filter(|result| result.status == "complete")
+                .and_then(|result| result.result);
+            if let Some(scan_result) = completed {
+                return Ok(scan_result);
+            }
+            if Instant::now() >= deadline {
+                return Err(SecurityError::AssessmentError(format!(
+                    "Asynchronous scan {} did not complete within {} s",
+                    submitted.scan_id,
+                    timeout.as_secs()
+                )));
+            }
+        }
+    }
+
+    // Looks up a single scan report or result by ID.
+    //
+    // # Arguments
+    //
+    // * `resource` - `reports` or `results`
+    // * `parameter` - Query parameter taking the ID
+    // * `id` - ID to look up
+    //
+    // # Returns
+    //
+    // The entry PANW returned for the ID
+    async fn fetch_by_id(
+        &self,
+        resource: &str,
+        parameter: &str,
+        id: &str,
+    ) -> Result<serde_json::Value, SecurityError> {
+        let uses_panw = if self.fan_out.providers.is_empty() {
+            self.provider == SecurityProvider::Panw
+        } else {
+            self.fan_out.providers.contains(&SecurityProvider::Panw)
+        };
+        if !uses_panw {
+            return Err(SecurityError::NotFound);
+        }
+        let endpoint = format!("{}/v1/scan/{}", self.base_url, resource);
+        debug!("Fetching PANW scan {} for {}", resource, id);
+
+        let entries: Vec<serde_json::Value> = self
+            .with_backoff(|| async {
+                let reply = self
+                    .execute(self.client.get(&endpoint).query(&[(parameter, id)]))
+                    .await?;
+                self.parse_api_response(reply)
+            })
+            .await?;
+        entries.into_iter().next().ok_or(SecurityError::NotFound)
+    }
+
+    // Makes an HTTP request to the PANW AI Runtime API.
+    //
+    // # Arguments
+    //
+    // * `payload` - The request payload to send
+    //
+    // # Returns
+    //
+    // Status code, retry interval and response body from the API
+    async fn make_api_request(&self, payload: &ScanRequest) -> Result<ApiReply, SecurityError> {
         let endpoint = format!("{}/v1/scan/sync/request", self.base_url);
         debug!("Sending security assessment request to: {}", endpoint);
 
-        let response = self
-            .client
-            .post(&endpoint)
-            .header("Content-Type", "application/json")
-            .header("x-pan-token", &self.api_key)
-            .json(payload)
-            .send()
-            .await
-            .map_err(|e| {
-                error!("PANW security assessment request failed: {}", e);
-                SecurityError::RequestError(e)
-            })?;
+        self.execute(
+            self.client
+                .post(&endpoint)
+                .header("Content-Type", "application/json")
+                .json(payload),
+        )
+        .await
+    }
+
+    // Sends a request to the PANW AI Runtime API, authenticated with the API key.
+    //
+    // # Arguments
+    //
+    // * `request` - The request to send
+    //
+    // # Returns
+    //
+    // Status code, retry interval and response body from the API
+    async fn execute(&self, request: RequestBuilder) -> Result<ApiReply, SecurityError> {
+        self.send(request.header("x-pan-token", self.api_key.get())).await
+    }
+
+    // Sends an authenticated request to the security service.
+    //
+    // # Returns
+    //
+    // Status code, retry interval and response body from the service
+    async fn send(&self, request: RequestBuilder) -> Result<ApiReply, SecurityError> {
+        let response = request.send().await.map_err(|e| {
+            error!("Security assessment request failed: {}", e);
+            SecurityError::RequestError(e)
+        })?;
 
         let status = response.status();
-        let body_text = response.text().await.map_err(|e| {
-            error!("Failed to read PANW response body: {}", e);
+        let retry_after_secs = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().await.map_err(|e| {
+            error!("Failed to read security assessment response body: {}", e);
             SecurityError::RequestError(e)
         })?;
 
-        Ok((status, body_text))
+        Ok(ApiReply {
+            status,
+            retry_after_secs,
+            body,
+        })
     }
 
     // Parses the PANW AI Runtime API response and handles different status codes.
     //
     // # Arguments
     //
-    // * `status` - The HTTP status code from the API response
-    // * `body_text` - The raw response body text
+    // * `reply` - Status code, retry interval and raw body of the API response
     //
     // # Returns
     //
-    // Parsed scan response object
-    fn parse_api_response(
-        &self,
-        status: reqwest::StatusCode,
-        body_text: String,
-    ) -> Result<ScanResponse, SecurityError> {
-        // Log the raw response in debug mode
-        debug!("PANW API response status: {}", status);
-        debug!("Raw PANW response body:\n{}", body_text);
+    // Parsed response object
+    fn parse_api_response<T: DeserializeOwned>(&self, reply: ApiReply) -> Result<T, SecurityError> {
+        let ApiReply {
+            status,
+            retry_after_secs,
+            body: body_text,
+        } = reply;
+
+        // Raw bodies can carry scanned content, so they are only kept redacted
+        debug!("PANW API response status: {} ({} bytes)", status, body_text.len());
 
         // Handle error status codes based on OpenAPI specification
         if !status.is_success() {
-            error!("PANW security assessment error: {} - {}", status, body_text);
+            if let Some(capture) = &self.response_capture {
+                capture.record_error_status(status.as_u16(), &body_text);
+            }
 
-            // Parse error response if possible
+            // Raw bodies can echo scanned content, so only the reported error message is
+            // logged; the body itself is kept by the response capture, redacted
             let error_details = match serde_json::from_str::<serde_json::Value>(&body_text) {
                 Ok(v) => v
                     .pointer("/error/message")
                     .and_then(|m| m.as_str())
                     .map(String::from)
-                    .unwrap_or_else(|| body_text.clone()),
-                Err(_) => body_text.clone(),
+                    .unwrap_or_else(|| {
+                        format!("response of {} bytes without an error message", body_text.len())
+                    }),
+                Err(e) => format!("unparseable response of {} bytes: {}", body_text.len(), e),
             };
+            error!("PANW security assessment error: {} - {}", status, error_details);
 
             return match status.as_u16() {
                 400 => Err(SecurityError::BadRequest(error_details)),
@@ -791,7 +2569,7 @@ impl SecurityClient {
                 413 => Err(SecurityError::RequestTooLarge),
                 415 => Err(SecurityError::UnsupportedMediaType),
                 429 => {
-                    // Try to parse retry information
+                    // Try to parse retry information, falling back to the Retry-After header
                     let retry_after = serde_json::from_str::<serde_json::Value>(&body_text)
                         .ok()
                         .and_then(|v| {
@@ -800,7 +2578,8 @@ impl SecurityClient {
                                 let unit = r.get("unit")?.as_str()?;
                                 Some((interval, unit.to_string()))
                             })
-                        });
+                        })
+                        .or_else(|| retry_after_secs.map(|secs| (secs, "second".to_string())));
 
                     if let Some((interval, unit)) = retry_after {
                         Err(SecurityError::TooManyRequests(interval, unit))
@@ -809,6 +2588,9 @@ impl SecurityClient {
                         // Default retry after 60 seconds if not specified
                     }
                 }
+                _ if status.is_server_error() => {
+                    Err(SecurityError::ProviderUnavailable(status, error_details))
+                }
                 _ => Err(SecurityError::AssessmentError(format!(
                     "Status {}: {}",
                     status, error_details
@@ -817,9 +2599,21 @@ impl SecurityClient {
         }
 
         // Parse JSON response
-        serde_json::from_str(&body_text).map_err(|e| {
-            error!("Failed to parse PANW security assessment response: {}", e);
+        let parsed = serde_json::from_str(&body_text).map_err(|e| {
+            error!(
+                "Failed to parse PANW security assessment response ({}, {} bytes): {}",
+                status,
+                body_text.len(),
+                e
+            );
+            if let Some(capture) = &self.response_capture {
+                capture.record_parse_error(status.as_u16(), &body_text, &e.to_string());
+            }
             SecurityError::JsonError(e)
-        })
+        });
+        if let (Ok(_), Some(capture)) = (&parsed, &self.response_capture) {
+            capture.sample(status.as_u16(), &body_text);
+        }
+        parsed
     }
 }
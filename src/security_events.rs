@@ -0,0 +1,305 @@
+// Security events exported to SIEM systems.
+//
+// Security teams collect what the proxy decides in their SIEM. When a webhook
+// or a syslog collector is configured, the following events are exported as
+// flat JSON objects:
+//
+// - `block`: a prompt or response was blocked (default severity `high`)
+// - `mask`: sensitive data in a prompt or response was masked (`low`)
+// - `fail_open`: a prompt or response was passed through unscanned because
+//   the PANW API failed (`high`)
+// - `scan_bypass`: a request from a trusted client skipped PANW scanning
+//   (`info`)
+// - `auth_failure`: a request was rejected for missing or invalid
+//   credentials (`low`)
+// - `lockout`: a source was locked out after repeated authentication
+//   failures (`high`)
+// - `failover`: an Ollama server was taken out of or back into rotation
+//   (`medium`)
+// - `policy_change`: the operating mode, a feature flag, a schedule or a client
+//   key was changed through the admin API (`medium`)
+// - `access_denied`: a request was rejected outside the time windows of an
+//   access policy (`low`)
+// - `kill_switch`: the kill switch was activated or lifted (`critical`)
+// - `impersonation`: an admin sent a request as a client with `X-Act-As-User`
+//   (`medium`)
+//
+// # Schema Mapping
+//
+// The severity of every event type can be overridden, and every field can be
+// renamed, so that events arrive in the SIEM's own schema without parsing
+// glue downstream.
+//
+// # Delivery
+//
+// Webhook events are posted in the background; syslog events are sent over
+// UDP as RFC 5424 messages with the JSON object as their message. Delivery is
+// best effort: failures are logged and counted in
+// `panw_security_event_failures_total`, and exported events in
+// `panw_security_events_total` by `event_type` and `severity`.
+use crate::config::{EventSeverity, SecurityEventsConfig};
+use crate::http_client;
+use crate::metrics;
+use chrono::{SecondsFormat, Utc};
+use reqwest::Client;
+use serde_json::{Map, Value};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+// Exporter installed at startup; events are dropped when not installed.
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+// Syslog facility of exported events (local0).
+const SYSLOG_FACILITY: u8 = 16;
+
+// Types of exported security events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    // A prompt or response was blocked
+    Block,
+    // Sensitive data in a prompt or response was masked
+    Mask,
+    // A prompt or response was passed through unscanned because PANW failed
+    FailOpen,
+    // A request from a trusted client skipped PANW scanning
+    ScanBypass,
+    // A request was rejected for missing or invalid credentials
+    AuthFailure,
+    // A source was locked out after repeated authentication failures
+    Lockout,
+    // An Ollama server was taken out of or back into rotation
+    Failover,
+    // Settings were changed through the admin API
+    PolicyChange,
+    // A request was rejected outside the time windows of an access policy
+    AccessDenied,
+    // The kill switch was activated or lifted
+    KillSwitch,
+    // An admin sent a request as a client
+    Impersonation,
+}
+
+impl EventType {
+    // Returns the name of the event type in exported events.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Mask => "mask",
+            Self::FailOpen => "fail_open",
+            Self::ScanBypass => "scan_bypass",
+            Self::AuthFailure => "auth_failure",
+            Self::Lockout => "lockout",
+            Self::Failover => "failover",
+            Self::PolicyChange => "policy_change",
+            Self::AccessDenied => "access_denied",
+            Self::KillSwitch => "kill_switch",
+            Self::Impersonation => "impersonation",
+        }
+    }
+
+    // Returns the severity of the event type unless overridden.
+    fn default_severity(self) -> EventSeverity {
+        match self {
+            Self::KillSwitch => EventSeverity::Critical,
+            Self::Block | Self::FailOpen | Self::Lockout => EventSeverity::High,
+            Self::Failover | Self::PolicyChange | Self::Impersonation => EventSeverity::Medium,
+            Self::Mask | Self::AuthFailure | Self::AccessDenied => EventSeverity::Low,
+            Self::ScanBypass => EventSeverity::Info,
+        }
+    }
+}
+
+// Delivers security events to the configured destinations.
+struct Exporter {
+    // Destinations, severities and field names
+    config: SecurityEventsConfig,
+
+    // HTTP client used for the webhook
+    client: Client,
+
+    // Socket sending syslog messages and the collector's address, if configured
+    syslog: Option<(UdpSocket, SocketAddr)>,
+
+    // Host name reported in syslog messages
+    hostname: String,
+}
+
+impl Exporter {
+    // Returns the severity of an event type.
+    fn severity(&self, event: EventType) -> EventSeverity {
+        self.config
+            .severities
+            .get(event.as_str())
+            .copied()
+            .unwrap_or_else(|| event.default_severity())
+    }
+
+    // Returns the exported name of a field.
+    fn field_name<'a>(&'a self, field: &'a str) -> &'a str {
+        self.config
+            .field_names
+            .get(field)
+            .map(String::as_str)
+            .unwrap_or(field)
+    }
+
+    // Sends an event as an RFC 5424 message to the syslog collector.
+    fn send_syslog(&self, event: EventType, severity: EventSeverity, body: &str) {
+        let Some((socket, collector)) = &self.syslog else {
+            return;
+        };
+
+        let code = match severity {
+            EventSeverity::Critical => 2,
+            EventSeverity::High => 3,
+            EventSeverity::Medium => 4,
+            EventSeverity::Low => 5,
+            EventSeverity::Info => 6,
+        };
+        let message = format!(
+            "<{}>1 {} {} panw-api-ollama - {} - {}",
+            SYSLOG_FACILITY * 8 + code,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            event.as_str(),
+            body
+        );
+        if let Err(e) = socket.send_to(message.as_bytes(), collector) {
+            warn!("Failed to send security event to syslog: {}", e);
+            record_failure("syslog");
+        }
+    }
+
+    // Posts an event to the webhook in the background.
+    fn send_webhook(&self, payload: Value) {
+        if self.config.webhook_url.is_empty() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+
+        let client = self.client.clone();
+        let url = self.config.webhook_url.clone();
+        runtime.spawn(async move {
+            match client.post(&url).json(&payload).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Security event delivered to webhook");
+                }
+                Ok(response) => {
+                    warn!("Security event webhook returned {}", response.status());
+                    record_failure("webhook");
+                }
+                Err(e) => {
+                    warn!("Failed to deliver security event: {}", e);
+                    record_failure("webhook");
+                }
+            }
+        });
+    }
+}
+
+// Installs the exporter for all subsequent events.
+//
+// Does nothing when neither a webhook nor a syslog collector is configured.
+//
+// # Errors
+//
+// Returns an error if the syslog collector cannot be resolved, the socket
+// cannot be opened or the HTTP client cannot be created.
+pub fn init(config: &SecurityEventsConfig) -> Result<(), String> {
+    if config.webhook_url.is_empty() && config.syslog_address.is_empty() {
+        return Ok(());
+    }
+
+    let syslog = if config.syslog_address.is_empty() {
+        None
+    } else {
+        let collector = config
+            .syslog_address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("cannot resolve syslog collector {}", config.syslog_address))?;
+        let bind = if collector.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(bind)
+            .and_then(|socket| socket.set_nonblocking(true).map(|_| socket))
+            .map_err(|e| format!("security event syslog socket: {}", e))?;
+        Some((socket, collector))
+    };
+
+    let client = http_client::builder(
+        &config.http_client,
+        Some(http_client::DEFAULT_WEBHOOK_REQUEST_TIMEOUT),
+    )
+    .build()
+    .map_err(|e| format!("security event webhook client: {}", e))?;
+    let _ = EXPORTER.set(Exporter {
+        config: config.clone(),
+        client,
+        syslog,
+        hostname: std::env::var("HOSTNAME")
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "-".to_string()),
+    });
+    Ok(())
+}
+
+// Exports a security event.
+//
+// # Arguments
+//
+// * `event` - Type of the event
+// * `message` - Human-readable summary of the event
+// * `fields` - Further fields of the event, by the names listed in
+//   `SECURITY_EVENT_FIELDS`
+pub fn publish(event: EventType, message: &str, fields: &[(&str, Value)]) {
+    let Some(exporter) = EXPORTER.get() else {
+        return;
+    };
+
+    let severity = exporter.severity(event);
+    let mut object = Map::new();
+    let mut insert = |field: &str, value: Value| {
+        object.insert(exporter.field_name(field).to_string(), value);
+    };
+    insert(
+        "timestamp",
+        Value::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+    );
+    insert("event_type", Value::from(event.as_str()));
+    insert("severity", Value::from(severity.as_str()));
+    insert("message", Value::from(message));
+    for (field, value) in fields {
+        if !value.is_null() {
+            insert(field, value.clone());
+        }
+    }
+
+    metrics::increment_counter(
+        "panw_security_events_total",
+        "Security events exported to SIEM systems",
+        &[
+            ("event_type", event.as_str()),
+            ("severity", severity.as_str()),
+        ],
+    );
+    let payload = Value::Object(object);
+    exporter.send_syslog(event, severity, &payload.to_string());
+    exporter.send_webhook(payload);
+}
+
+// Counts a failed delivery.
+fn record_failure(destination: &str) {
+    metrics::increment_counter(
+        "panw_security_event_failures_total",
+        "Security events that could not be delivered",
+        &[("destination", destination)],
+    );
+}
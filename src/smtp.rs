@@ -0,0 +1,222 @@
+// Minimal SMTP client for email notifications.
+//
+// Alert emails are plain text messages submitted to a single SMTP server, one
+// message per connection:
+//
+// - the connection is plain, upgraded with STARTTLS, or TLS from the start
+// - the server certificate is verified against the configured certificate
+//   authorities, or the system bundle
+// - when a user name is configured, the client authenticates with AUTH PLAIN
+//
+// Any reply other than the expected one aborts the delivery, and the whole
+// exchange is bounded by a timeout.
+use crate::config::{SmtpConfig, SmtpSecurity};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use rustls::pki_types::{pem::PemObject, CertificateDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+// Certificate authorities trusted when no CA file is configured.
+const SYSTEM_CA_BUNDLE: &str = "/etc/ssl/certs/ca-certificates.crt";
+
+// Longest time a delivery may take.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Connection to the server, plain or encrypted.
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+// Sends a plain text email to the configured recipients.
+//
+// # Errors
+//
+// Returns a description of the failure if the message was not accepted.
+pub async fn send(config: &SmtpConfig, subject: &str, body: &str) -> Result<(), String> {
+    tokio::time::timeout(DELIVERY_TIMEOUT, deliver(config, subject, body))
+        .await
+        .map_err(|_| "timed out".to_string())?
+}
+
+// Submits the message through a new connection to the server.
+async fn deliver(config: &SmtpConfig, subject: &str, body: &str) -> Result<(), String> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .map_err(|e| format!("cannot connect to {}:{}: {}", config.host, config.port, e))?;
+    let connection: Box<dyn Connection> = match config.security {
+        SmtpSecurity::Tls => Box::new(encrypt(config, tcp).await?),
+        _ => Box::new(tcp),
+    };
+    let mut session = Session::new(connection);
+    session.expect(220).await?;
+
+    let ehlo = format!("EHLO {}", hostname());
+    session.command(&ehlo, 250).await?;
+    if config.security == SmtpSecurity::Starttls {
+        session.command("STARTTLS", 220).await?;
+        let connection = session.reader.into_inner();
+        session = Session::new(Box::new(encrypt(config, connection).await?));
+        session.command(&ehlo, 250).await?;
+    }
+
+    if !config.username.is_empty() {
+        let credentials = STANDARD.encode(format!("\0{}\0{}", config.username, config.password));
+        session
+            .command(&format!("AUTH PLAIN {}", credentials), 235)
+            .await?;
+    }
+    session
+        .command(&format!("MAIL FROM:<{}>", config.from), 250)
+        .await?;
+    for recipient in &config.to {
+        session
+            .command(&format!("RCPT TO:<{}>", recipient), 250)
+            .await?;
+    }
+    session.command("DATA", 354).await?;
+    session.write(&message(config, subject, body)).await?;
+    session.expect(250).await?;
+    let _ = session.command("QUIT", 221).await;
+    Ok(())
+}
+
+// Wraps a connection in TLS, verifying the server's certificate.
+async fn encrypt<S: Connection>(
+    config: &SmtpConfig,
+    stream: S,
+) -> Result<tokio_rustls::client::TlsStream<S>, String> {
+    let ca_file = if config.ca_file.is_empty() {
+        SYSTEM_CA_BUNDLE
+    } else {
+        config.ca_file.as_str()
+    };
+    let invalid = |e: &dyn std::fmt::Display| format!("invalid CA file {}: {}", ca_file, e);
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_file).map_err(|e| invalid(&e))? {
+        roots
+            .add(cert.map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+    }
+
+    let tls = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(config.host.clone())
+        .map_err(|e| format!("invalid SMTP host {}: {}", config.host, e))?;
+    TlsConnector::from(Arc::new(tls))
+        .connect(name, stream)
+        .await
+        .map_err(|e| format!("TLS handshake failed: {}", e))
+}
+
+// Builds the message, ending with the line that terminates the DATA command.
+fn message(config: &SmtpConfig, subject: &str, body: &str) -> String {
+    let subject = if subject.is_ascii() {
+        subject.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", STANDARD.encode(subject))
+    };
+    let mut message = format!(
+        "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+         MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Transfer-Encoding: 8bit\r\n\r\n",
+        config.from,
+        config
+            .to
+            .iter()
+            .map(|recipient| format!("<{}>", recipient))
+            .collect::<Vec<_>>()
+            .join(", "),
+        subject,
+        Utc::now().to_rfc2822(),
+        uuid::Uuid::new_v4(),
+        hostname()
+    );
+    // Lines starting with a dot are escaped so they do not end the message
+    for line in body.lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push_str(".\r\n");
+    message
+}
+
+// Returns the host name the client introduces itself with.
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+// Command and reply exchange with the server.
+struct Session {
+    reader: BufReader<Box<dyn Connection>>,
+}
+
+impl Session {
+    fn new(connection: Box<dyn Connection>) -> Self {
+        Self {
+            reader: BufReader::new(connection),
+        }
+    }
+
+    // Sends a command and checks its reply.
+    async fn command(&mut self, command: &str, expected: u16) -> Result<(), String> {
+        self.write(&format!("{}\r\n", command)).await?;
+        self.expect(expected).await
+    }
+
+    // Writes raw data to the server.
+    async fn write(&mut self, data: &str) -> Result<(), String> {
+        let stream = self.reader.get_mut();
+        stream
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|e| format!("write failed: {}", e))?;
+        stream
+            .flush()
+            .await
+            .map_err(|e| format!("write failed: {}", e))
+    }
+
+    // Reads a reply, which may span several lines, and checks that it is of
+    // the expected class.
+    async fn expect(&mut self, expected: u16) -> Result<(), String> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("read failed: {}", e))?;
+            if read == 0 {
+                return Err("connection closed by server".to_string());
+            }
+            reply.push_str(line.trim_end());
+            // The last line of a reply has a space, or nothing, after its code
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+            reply.push(' ');
+        }
+
+        let code: u16 = reply
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0);
+        if code / 100 != expected / 100 {
+            return Err(format!("unexpected reply: {}", reply));
+        }
+        Ok(())
+    }
+}
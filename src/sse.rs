@@ -0,0 +1,132 @@
+// Server-sent event framing for OpenAI-compatible streaming responses.
+//
+// Ollama streams newline-delimited JSON, and so does `SecurityAssessedStream`.
+// OpenAI clients expect server-sent events instead: every chunk is sent as a
+// `data: {...}` event followed by a blank line, and the stream ends with a
+// `data: [DONE]` event. `SseStream` sits on top of the assessed stream and
+// re-frames each NDJSON line, after translating it into the OpenAI chunk
+// format, as an event.
+//
+// # Line Buffering
+//
+// The inner stream may split lines across chunks. Incomplete lines are kept
+// until the rest arrives; whatever remains when the inner stream ends, such as
+// a single JSON object without a trailing newline, is framed before the final
+// `[DONE]` event.
+use bytes::Bytes;
+use futures_util::{ready, Stream};
+use pin_project::pin_project;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tracing::warn;
+
+// Content type of server-sent event responses.
+pub const SSE_CONTENT_TYPE: &str = "text/event-stream";
+
+// Event terminating an OpenAI stream.
+const DONE_EVENT: &[u8] = b"data: [DONE]\n\n";
+
+// Stream re-framing NDJSON chunks as server-sent events.
+//
+// Each complete line is parsed as JSON and passed to the translation function;
+// the returned value is sent as a `data:` event. Lines that are not valid JSON,
+// or for which the translation returns `None`, are skipped.
+#[pin_project]
+pub struct SseStream<S, F> {
+    #[pin]
+    inner: S,
+
+    // Translates an NDJSON chunk into the event payload
+    translate: F,
+
+    // Bytes of an incomplete line waiting for the next chunk
+    pending: Vec<u8>,
+
+    // Whether the final `[DONE]` event has been sent
+    finished: bool,
+}
+
+impl<S, F> SseStream<S, F>
+where
+    F: FnMut(&serde_json::Value) -> Option<serde_json::Value>,
+{
+    // Wraps an NDJSON stream, translating each chunk with the given function.
+    pub fn new(inner: S, translate: F) -> Self {
+        Self {
+            inner,
+            translate,
+            pending: Vec::new(),
+            finished: false,
+        }
+    }
+}
+
+// Frames every line of the given NDJSON bytes as a server-sent event.
+fn frame_lines<F>(lines: &[u8], translate: &mut F) -> Vec<u8>
+where
+    F: FnMut(&serde_json::Value) -> Option<serde_json::Value>,
+{
+    let mut events = Vec::new();
+    for line in lines.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
+        let json = match serde_json::from_slice::<serde_json::Value>(line) {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("Skipping unparseable stream chunk: {}", e);
+                continue;
+            }
+        };
+        let Some(payload) = translate(&json) else {
+            continue;
+        };
+        if let Ok(payload) = serde_json::to_vec(&payload) {
+            events.extend_from_slice(b"data: ");
+            events.extend_from_slice(&payload);
+            events.extend_from_slice(b"\n\n");
+        }
+    }
+    events
+}
+
+impl<S, E, F> Stream for SseStream<S, F>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    F: FnMut(&serde_json::Value) -> Option<serde_json::Value>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            if *this.finished {
+                return Poll::Ready(None);
+            }
+
+            match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => {
+                    this.pending.extend_from_slice(&chunk);
+                    // Only frame complete lines; the rest waits for the next chunk
+                    let Some(end) = this.pending.iter().rposition(|b| *b == b'\n') else {
+                        continue;
+                    };
+                    let complete: Vec<u8> = this.pending.drain(..=end).collect();
+                    let events = frame_lines(&complete, this.translate);
+                    if !events.is_empty() {
+                        return Poll::Ready(Some(Ok(Bytes::from(events))));
+                    }
+                }
+                Some(Err(e)) => {
+                    *this.finished = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => {
+                    *this.finished = true;
+                    let mut events = frame_lines(this.pending, this.translate);
+                    this.pending.clear();
+                    events.extend_from_slice(DONE_EVENT);
+                    return Poll::Ready(Some(Ok(Bytes::from(events))));
+                }
+            }
+        }
+    }
+}
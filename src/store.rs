@@ -0,0 +1,627 @@
+// Maintenance, backup and restore of the local state files.
+//
+// The proxy persists the prompt template library, the monthly scan budget, the
+// client keys managed through the admin API and the kill switch in small JSON
+// state files, and appends conversation transcripts to a JSONL file.
+// This module verifies and exports them, either from the command line while
+// the server is stopped:
+//
+// ```text
+// panw-api-ollama store verify
+// panw-api-ollama store export templates
+// panw-api-ollama store prune-older-than 90
+// ```
+//
+// or through `GET /admin/stores` while it is running. The JSON state files are
+// rewritten in full on every change, so they never need vacuuming. Transcripts
+// only grow; `store prune-older-than` removes the sessions whose last record
+// is older than the given number of days, as the proxy does on its own when
+// `TRANSCRIPTS_RETENTION_DAYS` is set.
+//
+// # Backup and Restore
+//
+// A backup is a single JSON archive holding the configuration file (the
+// policy) and every state file, for migrating a proxy to a new host. Backups
+// are taken with `store backup <file>` or `GET /admin/backup`, and restored
+// with `store restore <file>` while the server is stopped, since the state is
+// only loaded at startup. Restoring writes each file to the path configured on
+// the new host after checking that its contents are valid, replacing it
+// through a temporary file so that a crash never leaves it half written.
+//
+// Secrets in the configuration file, such as API keys, tokens, passwords and
+// encryption keys, are replaced with a placeholder in the backup, so that a
+// client with the admin scope cannot read the proxy's own credentials through
+// `GET /admin/backup`. Vault references are kept. Restoring the configuration
+// keeps the secrets of the configuration file already on the new host, and
+// leaves out secrets it does not have, to be set through environment
+// variables instead.
+//
+// The provenance signing key and encryption key files are deliberately not
+// part of the archive; copy them separately if the new host must use the same
+// keys.
+//
+// # Schema Migrations
+//
+// State files carry a schema version (see `migrations`); transcripts do not. At startup, and with
+// `store migrate`, files of an older version are migrated and rewritten, and
+// files of a newer version stop the proxy from starting. Backups restored on
+// a newer proxy are migrated the next time it starts.
+//
+// # Encryption
+//
+// Encrypted state files are backed up as they are, so the new host needs the
+// same encryption keys; restored files are encrypted with its active key.
+// `store rekey` rewrites every state file with the active key after a key
+// rotation, and encrypts files that were written before encryption was
+// enabled, which the proxy refuses to read once keys are configured.
+// Transcript records are rewritten one by one.
+use crate::config::Config;
+use crate::{
+    budget, encryption, key_store, kill_switch, migrations, templates, transcripts, vault,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_yml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+
+// Names of the stores, in the order they are reported.
+const STORES: [&str; 5] = ["templates", "budget", "keys", "kill_switch", "transcripts"];
+
+// Name of the transcript store, which holds one record per line.
+const TRANSCRIPTS: &str = "transcripts";
+
+// Name of the configuration file in backups.
+const POLICY: &str = "policy";
+
+// Version of the backup archive format.
+const BACKUP_VERSION: u32 = 1;
+
+// Fields of the configuration file holding secrets.
+const SECRET_FIELDS: [&str; 16] = [
+    "api_key",
+    "token",
+    "bearer_token",
+    "basic_auth_password",
+    "password",
+    "key",
+    "secret_access_key",
+    "session_token",
+    "signing_secret",
+    "hmac_key",
+    "routing_key",
+    "role_id",
+    "secret_id",
+    "webhook",
+    "alert_webhook",
+    "webhook_url",
+];
+
+// Fields of the configuration file whose values are all secrets.
+const SECRET_MAPS: [&str; 1] = ["headers"];
+
+// Placeholder replacing secrets in backups.
+const REDACTED: &str = "<redacted>";
+
+// Result of verifying a single store.
+#[derive(Debug, Serialize)]
+pub struct StoreStatus {
+    // Name of the store
+    pub store: &'static str,
+    // State file of the store; empty when the store is kept in memory only
+    pub path: String,
+    // `ok`, `missing` or `invalid`
+    pub status: &'static str,
+    // Number of records in the file, when it is valid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub records: Option<usize>,
+    // Schema version of the file, when it exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<u32>,
+    // Reason the file is invalid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Archive holding the configuration and state files of a proxy.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+    // Version of the archive format
+    pub version: u32,
+    // When the backup was taken
+    pub created_at: DateTime<Utc>,
+    // File contents keyed by store name; missing files are left out
+    pub files: BTreeMap<String, String>,
+}
+
+// Configuration and state files of the stores.
+#[derive(Debug, Clone, Default)]
+pub struct StoreFiles {
+    // Configuration file
+    policy: String,
+
+    // Prompt template library
+    templates: String,
+
+    // Monthly scan budget
+    budget: String,
+
+    // Managed client keys
+    keys: String,
+
+    // Kill switch activation
+    kill_switch: String,
+
+    // Conversation transcripts
+    transcripts: String,
+}
+
+impl StoreFiles {
+    // Takes the state files from the configuration.
+    //
+    // # Arguments
+    //
+    // * `config_file` - Path of the configuration file
+    // * `config` - Configuration naming the state files
+    pub fn new(config_file: &str, config: &Config) -> Self {
+        Self {
+            policy: config_file.to_string(),
+            templates: config.templates.state_file.clone(),
+            budget: config.security.budget.state_file.clone(),
+            keys: config.client_auth.key_state_file.clone(),
+            kill_switch: config.kill_switch.state_file.clone(),
+            transcripts: config.transcripts.file.clone(),
+        }
+    }
+
+    // Returns the file of the named store.
+    fn path(&self, store: &str) -> Option<&str> {
+        match store {
+            POLICY => Some(&self.policy),
+            "templates" => Some(&self.templates),
+            "budget" => Some(&self.budget),
+            "keys" => Some(&self.keys),
+            "kill_switch" => Some(&self.kill_switch),
+            TRANSCRIPTS => Some(&self.transcripts),
+            _ => None,
+        }
+    }
+
+    // Verifies every store.
+    pub fn verify(&self) -> Vec<StoreStatus> {
+        STORES
+            .iter()
+            .map(|&store| {
+                let path = self.path(store).unwrap_or_default();
+                let result = match store {
+                    "templates" => templates::verify_state_file(path),
+                    "keys" => key_store::verify_state_file(path),
+                    "kill_switch" => kill_switch::verify_state_file(path),
+                    TRANSCRIPTS => transcripts::verify_state_file(path),
+                    _ => budget::verify_state_file(path),
+                };
+                let (status, records, error) = match result {
+                    Ok(Some(records)) => ("ok", Some(records), None),
+                    Ok(None) => ("missing", None, None),
+                    Err(e) => ("invalid", None, Some(e)),
+                };
+                let schema_version = encryption::read(path)
+                    .ok()
+                    .filter(|_| store != TRANSCRIPTS)
+                    .and_then(|contents| migrations::version_of(&contents).ok());
+                StoreStatus {
+                    store,
+                    path: path.to_string(),
+                    status,
+                    records,
+                    schema_version,
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    // Collects the configuration and state files into a backup.
+    //
+    // # Errors
+    //
+    // Returns an error if an existing file cannot be read.
+    pub fn backup(&self) -> Result<Backup, String> {
+        let mut files = BTreeMap::new();
+        for store in std::iter::once(POLICY).chain(STORES) {
+            let path = self.path(store).unwrap_or_default();
+            if path.is_empty() || !std::path::Path::new(path).exists() {
+                continue;
+            }
+            let mut contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            if store == POLICY {
+                let mut policy: Value =
+                    serde_yml::from_str(&contents).map_err(|e| format!("{}: {}", path, e))?;
+                redact(&mut policy);
+                contents = serde_yml::to_string(&policy).map_err(|e| e.to_string())?;
+            }
+            files.insert(store.to_string(), contents);
+        }
+
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            created_at: Utc::now(),
+            files,
+        })
+    }
+
+    // Writes the files of a backup to the configured paths.
+    //
+    // Every file is checked before any is written, so an invalid backup leaves
+    // the existing files untouched. The secrets of the configuration file are
+    // taken from the configuration file being replaced.
+    //
+    // # Returns
+    //
+    // The stores and paths that were written
+    //
+    // # Errors
+    //
+    // Returns an error if the backup is invalid or a file cannot be written.
+    pub fn restore(&self, backup: &Backup) -> Result<Vec<(String, String)>, String> {
+        if backup.version != BACKUP_VERSION {
+            return Err(format!("unsupported backup version {}", backup.version));
+        }
+
+        let mut targets = Vec::new();
+        for (store, contents) in &backup.files {
+            let path = self
+                .path(store)
+                .ok_or_else(|| format!("unknown store {} in backup", store))?;
+            if path.is_empty() {
+                return Err(format!("{} store has no state file on this host", store));
+            }
+            let contents = match store.as_str() {
+                POLICY => self.unredact_policy(contents),
+                TRANSCRIPTS => transcripts::reseal_contents(contents),
+                _ => encryption::reseal(contents),
+            }
+            .map_err(|e| format!("invalid {} in backup: {}", store, e))?;
+            let contents = contents.as_str();
+            match store.as_str() {
+                POLICY => serde_yml::from_str::<Config>(contents)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                "templates" => templates::verify_contents(contents).map(|_| ()),
+                "keys" => key_store::verify_contents(contents).map(|_| ()),
+                "kill_switch" => kill_switch::verify_contents(contents).map(|_| ()),
+                TRANSCRIPTS => transcripts::verify_contents(contents).map(|_| ()),
+                _ => budget::verify_contents(contents).map(|_| ()),
+            }
+            .map_err(|e| format!("invalid {} in backup: {}", store, e))?;
+            targets.push((store.clone(), path.to_string(), contents.to_string()));
+        }
+
+        // Keep a running proxy from appending to transcripts being replaced
+        let _lock = targets
+            .iter()
+            .find(|(store, _, _)| store == TRANSCRIPTS)
+            .map(|(_, path, _)| {
+                transcripts::lock_file(path).map_err(|e| format!("{}: {}", path, e))
+            })
+            .transpose()?;
+        targets
+            .into_iter()
+            .map(|(store, path, contents)| {
+                write_atomically(&path, &contents).map_err(|e| format!("{}: {}", path, e))?;
+                Ok((store, path))
+            })
+            .collect()
+    }
+
+    // Fills the redacted secrets of a backed up configuration file with those
+    // of the configuration file on this host.
+    //
+    // # Errors
+    //
+    // Returns an error if either configuration file cannot be parsed.
+    fn unredact_policy(&self, contents: &str) -> Result<String, String> {
+        let mut policy: Value = serde_yml::from_str(contents).map_err(|e| e.to_string())?;
+        let live = match fs::read_to_string(&self.policy) {
+            Ok(live) => Some(
+                serde_yml::from_str::<Value>(&live)
+                    .map_err(|e| format!("{}: {}", self.policy, e))?,
+            ),
+            Err(_) => None,
+        };
+        unredact(&mut policy, live.as_ref());
+        serde_yml::to_string(&policy).map_err(|e| e.to_string())
+    }
+
+    // Migrates every state file of an older schema version to the current one.
+    //
+    // # Returns
+    //
+    // The stores and paths that were migrated, with the versions they were
+    // migrated from and to
+    //
+    // # Errors
+    //
+    // Returns an error if a file cannot be read, has a newer schema version
+    // than this proxy supports, or cannot be migrated or written.
+    pub fn migrate(&self) -> Result<Vec<(String, String, u32, u32)>, String> {
+        let mut migrated = Vec::new();
+        for store in STORES.into_iter().filter(|&store| store != TRANSCRIPTS) {
+            let path = self.path(store).unwrap_or_default();
+            if path.is_empty() || !std::path::Path::new(path).exists() {
+                continue;
+            }
+            let contents = encryption::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            let version = migrations::version_of(&contents)
+                .map_err(|e| format!("{} store {}: {}", store, path, e))?;
+            let current = migrations::current_version(store);
+            if version == current {
+                continue;
+            }
+            let data = migrations::decode(store, &contents)
+                .map_err(|e| format!("{} store {}: {}", store, path, e))?;
+            migrations::encode(store, &data)
+                .and_then(|contents| encryption::write(path, &contents))
+                .map_err(|e| format!("{}: {}", path, e))?;
+            migrated.push((store.to_string(), path.to_string(), version, current));
+        }
+        Ok(migrated)
+    }
+
+    // Rewrites every state file with the active encryption key.
+    //
+    // # Returns
+    //
+    // The stores and paths that were rewritten
+    //
+    // # Errors
+    //
+    // Returns an error if a file cannot be decrypted or written.
+    pub fn rekey(&self) -> Result<Vec<(String, String)>, String> {
+        let mut rewritten = Vec::new();
+        for store in STORES {
+            let path = self.path(store).unwrap_or_default();
+            if path.is_empty() || !std::path::Path::new(path).exists() {
+                continue;
+            }
+            if store == TRANSCRIPTS {
+                transcripts::rekey_file(path).map_err(|e| format!("{}: {}", path, e))?;
+            } else {
+                fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|contents| encryption::reseal(&contents))
+                    .and_then(|sealed| write_atomically(path, &sealed).map_err(|e| e.to_string()))
+                    .map_err(|e| format!("{}: {}", path, e))?;
+            }
+            rewritten.push((store.to_string(), path.to_string()));
+        }
+        Ok(rewritten)
+    }
+
+    // Removes the transcript sessions whose last record is older than the
+    // given number of days.
+    //
+    // # Returns
+    //
+    // The number of records and sessions removed
+    //
+    // # Errors
+    //
+    // Returns an error if the transcript file cannot be read or rewritten.
+    pub fn prune(&self, days: u64) -> Result<(usize, usize), String> {
+        transcripts::prune_file(&self.transcripts, days)
+            .map(|(records, sessions)| (records, sessions.len()))
+            .map_err(|e| format!("{}: {}", self.transcripts, e))
+    }
+}
+
+// Replaces the secrets in a configuration file with a placeholder.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Mapping(fields) => {
+            for (name, value) in fields.iter_mut() {
+                let name = name.as_str().unwrap_or_default();
+                match value {
+                    Value::Mapping(secrets) if SECRET_MAPS.contains(&name) => secrets
+                        .iter_mut()
+                        .for_each(|(_, secret)| redact_secret(secret)),
+                    _ if SECRET_FIELDS.contains(&name) => redact_secret(value),
+                    _ => redact(value),
+                }
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+// Replaces a secret with a placeholder unless it is empty or a Vault reference.
+fn redact_secret(value: &mut Value) {
+    if value
+        .as_str()
+        .is_some_and(|secret| !secret.is_empty() && !vault::is_reference(secret))
+    {
+        *value = Value::String(REDACTED.to_string());
+    }
+}
+
+// Replaces the placeholders in a redacted configuration file with the secrets
+// at the same place in the live configuration file, and leaves out secrets
+// that it does not have.
+//
+// List items with an `id`, such as client and encryption keys, are matched by
+// id, other list items by position.
+fn unredact(value: &mut Value, live: Option<&Value>) {
+    match value {
+        Value::Mapping(fields) => {
+            let mut missing = Vec::new();
+            for (name, value) in fields.iter_mut() {
+                let live = live.and_then(|live| live.get(name));
+                if value.as_str() == Some(REDACTED) {
+                    match live.filter(|live| live.as_str() != Some(REDACTED)) {
+                        Some(live) => *value = live.clone(),
+                        None => missing.push(name.clone()),
+                    }
+                } else {
+                    unredact(value, live);
+                }
+            }
+            for name in missing {
+                fields.remove(&name);
+            }
+        }
+        Value::Sequence(items) => {
+            let live_items = live.and_then(Value::as_sequence);
+            for (index, item) in items.iter_mut().enumerate() {
+                let live = live_items.and_then(|live_items| match item.get("id").cloned() {
+                    Some(id) => live_items.iter().find(|live| live.get("id") == Some(&id)),
+                    None => live_items.get(index),
+                });
+                unredact(item, live);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Replaces a file with new contents without ever leaving it half written.
+//
+// The contents are written and synced to a temporary file next to the file,
+// which is then renamed over it.
+//
+// # Errors
+//
+// Returns an error if the temporary file cannot be written or renamed.
+pub fn write_atomically(path: &str, contents: &str) -> std::io::Result<()> {
+    let temp_path = format!("{}.tmp", path);
+    let result = fs::File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// Runs a `store` subcommand.
+//
+// # Arguments
+//
+// * `files` - Configuration and state files of the stores
+// * `args` - Arguments following `store`
+//
+// # Errors
+//
+// Returns an error if the subcommand is unknown or fails.
+pub fn run_cli(files: &StoreFiles, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["verify"] => {
+            let statuses = files.verify();
+            for status in &statuses {
+                match (&status.records, &status.error) {
+                    (Some(records), _) => {
+                        let version = status
+                            .schema_version
+                            .map(|version| format!(", schema version {}", version))
+                            .unwrap_or_default();
+                        println!(
+                            "{}: ok, {} records{} ({})",
+                            status.store, records, version, status.path
+                        )
+                    }
+                    (_, Some(error)) => {
+                        println!("{}: invalid ({}): {}", status.store, status.path, error)
+                    }
+                    _ => println!("{}: no state file", status.store),
+                }
+            }
+            if statuses.iter().any(|status| status.status == "invalid") {
+                return Err("one or more stores are invalid".into());
+            }
+            Ok(())
+        }
+        ["export", store] => {
+            let path = files
+                .path(store)
+                .filter(|_| STORES.contains(store))
+                .ok_or_else(|| {
+                    format!(
+                        "unknown store {}; expected one of {}",
+                        store,
+                        STORES.join(", ")
+                    )
+                })?;
+            if path.is_empty() {
+                return Err(format!("{} store has no state file", store).into());
+            }
+            if *store == TRANSCRIPTS {
+                print!(
+                    "{}",
+                    transcripts::export_contents(&fs::read_to_string(path)?)?
+                );
+                return Ok(());
+            }
+            let contents = encryption::read(path)?;
+            let value: serde_json::Value = serde_json::from_str(&contents)?;
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            Ok(())
+        }
+        ["backup", file] => {
+            let backup = files.backup()?;
+            fs::write(file, serde_json::to_string_pretty(&backup)?)?;
+            println!(
+                "Backed up {} to {}",
+                backup.files.keys().cloned().collect::<Vec<_>>().join(", "),
+                file
+            );
+            Ok(())
+        }
+        ["restore", file] => {
+            let backup: Backup = serde_json::from_str(&fs::read_to_string(file)?)?;
+            for (store, path) in files.restore(&backup)? {
+                println!("Restored {} to {}", store, path);
+            }
+            Ok(())
+        }
+        ["migrate"] => {
+            for (store, path, from, to) in files.migrate()? {
+                println!(
+                    "Migrated {} ({}) from schema version {} to {}",
+                    store, path, from, to
+                );
+            }
+            Ok(())
+        }
+        ["rekey"] => {
+            for (store, path) in files.rekey()? {
+                println!("Rewrote {} ({})", store, path);
+            }
+            Ok(())
+        }
+        ["prune-older-than", days] => {
+            let days: u64 = days
+                .parse()
+                .map_err(|_| format!("invalid number of days {}", days))?;
+            let (records, sessions) = files.prune(days)?;
+            println!(
+                "Pruned {} transcript sessions with {} records older than {} days",
+                sessions, records, days
+            );
+            Ok(())
+        }
+        _ => Err("usage: store verify | \
+                  store export <templates|budget|keys|kill_switch|transcripts> | \
+                  store backup <file> | store restore <file> | store migrate | store rekey | \
+                  store prune-older-than <days>"
+            .into()),
+    }
+}
@@ -1,5 +1,10 @@
 use crate::{
-    handlers::utils::{format_security_violation_message, log_llm_metrics},
+    branding::Branding,
+    config::{ModelStreaming, StreamingStrategy},
+    handlers::utils::log_llm_metrics,
+    metrics,
+    ollama::with_tag,
+    postprocess::{PostProcessState, ResponsePostProcessor},
     security::{Assessment, SecurityClient},
     types::{StreamError, Content},
 };
@@ -7,13 +12,95 @@ use bytes::Bytes;
 use futures_util::{ready, Future, Stream};
 use pin_project::pin_project;
 use std::{
+    collections::VecDeque,
     pin::Pin,
     task::{Context, Poll},
 };
+use tracing::{debug, warn};
+
+/// Field of the chunk summarizing a finished stream.
+pub const SUMMARY_FIELD: &str = "verdict_summary";
 
 // Type alias for complex assessment future to improve readability
 type AssessmentFuture = Pin<Box<dyn Future<Output = Result<Assessment, StreamError>> + Send>>;
 
+/// An asynchronous assessment whose chunks may be released before it settles.
+struct SettlingScan {
+    /// The assessment, resolving once the scan result has been polled
+    future: AssessmentFuture,
+    /// Characters of content submitted up to and including this assessment
+    covers: usize,
+}
+
+/// Lifecycle states of a security-assessed stream.
+///
+/// Exactly one state is active at a time, which replaces the set of interacting
+/// flags the stream used to track. The allowed transitions are:
+///
+/// ```text
+/// Accumulating       -> AwaitingAssessment | Draining | Blocked | Finished
+/// AwaitingAssessment -> Releasing | Accumulating | Blocked | Finished
+/// Releasing          -> Accumulating | Blocked | Finished
+/// Draining           -> Blocked | Finished
+/// Blocked, Finished  -> (terminal)
+/// ```
+///
+/// With asynchronous scanning, assessments settle while the stream accumulates
+/// and releases, so a blocking verdict or a failed scan can end the stream from
+/// any non-terminal state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamState {
+    /// Reading upstream chunks into the pending buffer until a boundary is reached
+    Accumulating,
+    /// A security assessment is in flight for the pending chunks
+    AwaitingAssessment,
+    /// Assessed chunks are queued and handed to the consumer before reading on
+    Releasing,
+    /// Content was blocked; the blocked message has been emitted
+    Blocked,
+    /// The upstream has ended; the tail is settled and queued chunks are flushed
+    Draining,
+    /// The stream has completed and its terminal `done` chunk has been emitted
+    Finished,
+}
+
+impl StreamState {
+    /// Returns whether moving from this state to `next` is a legal transition.
+    fn can_transition_to(self, next: StreamState) -> bool {
+        use StreamState::*;
+        matches!(
+            (self, next),
+            (Accumulating, AwaitingAssessment)
+                | (Accumulating, Draining)
+                | (Accumulating, Blocked)
+                | (Accumulating, Finished)
+                | (AwaitingAssessment, Releasing)
+                | (AwaitingAssessment, Accumulating)
+                | (AwaitingAssessment, Blocked)
+                | (AwaitingAssessment, Finished)
+                | (Releasing, Accumulating)
+                | (Releasing, Blocked)
+                | (Releasing, Finished)
+                | (Draining, Blocked)
+                | (Draining, Finished)
+        )
+    }
+
+    /// Returns whether the stream emits nothing further in this state.
+    fn is_terminal(self) -> bool {
+        matches!(self, StreamState::Blocked | StreamState::Finished)
+    }
+}
+
+/// Shape of the NDJSON chunks produced by the upstream Ollama endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkFormat {
+    /// `/api/chat` chunks carrying a `message` object
+    Chat,
+    /// `/api/generate` chunks carrying a `response` string
+    Generate,
+}
+
 /// Buffer for stream content that handles parsing, accumulation, and code extraction.
 ///
 /// This struct maintains separate buffers for text and code content, tracks code block boundaries,
@@ -24,21 +111,25 @@ struct StreamBuffer {
     code_buffer: String,
     in_code_block: bool,
     read_pos: usize,
-    output_buffer: Vec<Bytes>,        // General output buffer
-    text_buffer_complete: Vec<Bytes>, // Buffer for complete text responses
-    code_buffer_complete: Vec<Bytes>, // Buffer for complete code blocks
-    pending_buffer: Vec<Bytes>,       // Buffer for content waiting for assessment
+    pending_buffer: Vec<Bytes>, // Buffer for content waiting for assessment
+    ready_buffer: VecDeque<Bytes>, // Assessed content waiting to be handed to the consumer
     assessment_window: usize,
     sentence_boundary_chars: &'static [char],
+    paragraph_batches: bool, // Whether text is only assessed at blank lines
     last_was_boundary: bool,
-    waiting_for_assessment: bool, // Flag indicating we're waiting for assessment
-    has_complete_text: bool,      // Flag indicating we have complete text
-    has_complete_code: bool,      // Flag indicating we have complete code
-    batch_ready: bool,            // Flag indicating a batch is ready to send
-    accumulating: bool,           // Flag indicating we're accumulating chunks
-    blocked: bool,                // Flag indicating content has been blocked
+    state: StreamState,
+    format: ChunkFormat,        // Chunk shape used when synthesizing terminal chunks
+    terminal_received: bool,    // Whether the upstream sent its own `done: true` chunk
     last_assessed_text_pos: usize, // Position in text buffer that has already been assessed
     last_assessed_code_pos: usize, // Position in code buffer that has already been assessed
+    secret_block_open: bool,    // Whether a masked private key block continues in the next batch
+    postprocess_state: PostProcessState, // Post-processing state carried between released batches
+    submitted_chars: usize,     // Characters submitted for asynchronous assessment so far
+    settled_chars: usize,       // Characters covered by a safe asynchronous verdict
+    released_chars: usize,      // Characters released before their asynchronous verdict
+    scans: usize,               // Assessments of the stream's content that completed
+    masked_segments: usize,     // Released batches in which secrets were masked
+    scan_failed: bool,          // Whether an assessment of the stream's content failed
 }
 
 impl StreamBuffer {
@@ -51,34 +142,57 @@ impl StreamBuffer {
         const ASSESSMENT_WINDOW: usize = 100_000;
         const TEXT_INITIAL_CAPACITY: usize = ASSESSMENT_WINDOW / 10; // 10% of max assessment window
         const VEC_INITIAL_CAPACITY: usize = 8; // Default small vector capacity
-        
+
         Self {
             text_buffer: String::with_capacity(TEXT_INITIAL_CAPACITY),
             code_buffer: String::with_capacity(TEXT_INITIAL_CAPACITY),
             in_code_block: false,
             read_pos: 0,
-            output_buffer: Vec::with_capacity(VEC_INITIAL_CAPACITY),
-            text_buffer_complete: Vec::with_capacity(VEC_INITIAL_CAPACITY),
-            code_buffer_complete: Vec::with_capacity(VEC_INITIAL_CAPACITY),
             pending_buffer: Vec::with_capacity(VEC_INITIAL_CAPACITY),
+            ready_buffer: VecDeque::with_capacity(VEC_INITIAL_CAPACITY),
             assessment_window: ASSESSMENT_WINDOW,
             sentence_boundary_chars: &['\n'],
+            paragraph_batches: false,
             last_was_boundary: false,
-            waiting_for_assessment: false,
-            has_complete_text: false,
-            has_complete_code: false,
-            batch_ready: false,
-            accumulating: false,
-            blocked: false,
+            state: StreamState::Accumulating,
+            format: ChunkFormat::Chat,
+            terminal_received: false,
             last_assessed_text_pos: 0,
             last_assessed_code_pos: 0,
+            secret_block_open: false,
+            postprocess_state: PostProcessState::default(),
+            submitted_chars: 0,
+            settled_chars: 0,
+            released_chars: 0,
+            scans: 0,
+            masked_segments: 0,
+            scan_failed: false,
+        }
+    }
+
+    /// Moves the stream into `next`, logging any transition the state machine does not allow.
+    ///
+    /// # Arguments
+    ///
+    /// * `next` - The state to move into
+    fn transition(&mut self, next: StreamState) {
+        if !self.state.can_transition_to(next) {
+            warn!(
+                "Unexpected stream state transition: {:?} -> {:?}",
+                self.state, next
+            );
+            debug_assert!(false, "illegal stream state transition");
         }
+        debug!("Stream state: {:?} -> {:?}", self.state, next);
+        self.state = next;
     }
 
     /// Processes a string chunk from the stream, parsing it as JSON and extracting content.
     ///
     /// This method parses Ollama's JSON response chunks, identifies and separates regular text
     /// from code blocks, and maintains the state of code block detection between chunks.
+    /// The content is read from `message.content` of `/api/chat` chunks or from `response`
+    /// of `/api/generate` chunks.
     ///
     /// # Arguments
     ///
@@ -86,7 +200,10 @@ impl StreamBuffer {
     fn process(&mut self, chunk: &str) {
         // Parse Ollama's JSON response chunk
         if let Ok(json) = serde_json::from_str::<serde_json::Value>(chunk) {
-            if let Some(content) = json["message"]["content"].as_str() {
+            let content = json["message"]["content"]
+                .as_str()
+                .or_else(|| json["response"].as_str());
+            if let Some(content) = content {
                 // Look for code block markers in the incoming content
                 if content.contains("```") {
                     // Contains a code block marker, need special processing
@@ -186,9 +303,6 @@ impl StreamBuffer {
                     let remaining = &buffer_copy[pos + 3..];
                     self.text_buffer.push_str(remaining);
                 }
-
-                // Mark that we have a complete code block
-                self.has_complete_code = true;
             } else {
                 // Start of a code block
                 // Extract content before the marker
@@ -303,7 +417,9 @@ impl StreamBuffer {
         // Check for semantic boundaries in text
         if new_text_content {
             let last_char = self.text_buffer.chars().last().unwrap_or(' ');
-            if self.sentence_boundary_chars.contains(&last_char)
+            let at_boundary = self.sentence_boundary_chars.contains(&last_char)
+                && (!self.paragraph_batches || self.text_buffer.ends_with("\n\n"));
+            if at_boundary
                 && self.text_buffer.len() > 15
                 && !self.last_was_boundary
             {
@@ -337,6 +453,30 @@ impl StreamBuffer {
         // If not safe, we keep buffers as is to potentially modify them
     }
 
+    /// Marks the unassessed content as submitted for an asynchronous assessment.
+    ///
+    /// The assessed positions advance right away, so the next assessment is only
+    /// triggered by content arriving after this one.
+    ///
+    /// # Returns
+    ///
+    /// The number of characters submitted so far, including this submission
+    fn submit(&mut self) -> usize {
+        self.submitted_chars += self.text_buffer.len().saturating_sub(self.last_assessed_text_pos)
+            + self.code_buffer.len().saturating_sub(self.last_assessed_code_pos);
+        self.commit(true);
+        self.submitted_chars
+    }
+
+    /// Returns whether the submitted content lacking a safe verdict fits the risk window.
+    ///
+    /// # Arguments
+    ///
+    /// * `risk_window` - Largest number of characters released before their verdict
+    fn within_risk_window(&self, risk_window: usize) -> bool {
+        self.submitted_chars.saturating_sub(self.settled_chars) <= risk_window
+    }
+
     /// Adds a chunk to the pending buffer for later assessment.
     ///
     /// This method stores chunks that are waiting for security assessment before
@@ -347,134 +487,54 @@ impl StreamBuffer {
     /// * `bytes` - The raw bytes to store in the pending buffer
     fn buffer_pending_chunk(&mut self, bytes: Bytes) {
         self.pending_buffer.push(bytes);
-        self.waiting_for_assessment = true;
     }
 
-    /// Moves content from the pending buffer to the appropriate destination buffer
-    /// once security assessment is complete.
+    /// Moves all pending chunks into the ready queue as a single combined batch.
     ///
-    /// This method determines whether the pending content contains code blocks and
-    /// routes it to either the code buffer or text buffer accordingly.
-    fn release_pending_chunks(&mut self) {
-        // First, find what kind of content we have in pending buffer
-        let mut has_code = false;
-
-        for bytes in &self.pending_buffer {
-            if let Ok(chunk) = std::str::from_utf8(bytes) {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(chunk) {
-                    if let Some(content) = json["message"]["content"].as_str() {
-                        if content.contains("```") || self.in_code_block {
-                            has_code = true;
-                            break;
-                        }
-                    }
-                }
-            }
-        }
-
-        // Move pending chunks to the appropriate buffer based on content type
-        if has_code {
-            // Move to code buffer
-            for chunk in self.pending_buffer.drain(..) {
-                self.code_buffer_complete.push(chunk);
-            }
-            self.has_complete_code = true;
-        } else {
-            // Move to text buffer
-            for chunk in self.pending_buffer.drain(..) {
-                self.text_buffer_complete.push(chunk);
-            }
-            self.has_complete_text = true;
-        }
-
-        // Mark the content as ready to send
-        self.mark_batch_ready();
-        self.waiting_for_assessment = false;
-    }
-
-    /// Marks the current batch of content as ready to be returned.
+    /// Only called once the pending content has been assessed as safe, or when the
+    /// pending chunks carry no content that still requires assessment. Secrets found
+    /// by the local secret scanner are masked and the configured post-processing is
+    /// applied to the batch before it is queued.
+    ///
+    /// # Arguments
     ///
-    /// This method is called when either text or code content has been completed
-    /// and is ready to be sent to the consumer.
-    fn mark_batch_ready(&mut self) {
-        // If we have completed code blocks or text, mark the batch as ready
-        if self.has_complete_code || self.has_complete_text {
-            self.batch_ready = true;
-            self.accumulating = false;
+    /// * `security_client` - Client providing the local secret masking
+    /// * `post_processor` - Rewrites applied to assessed content
+    fn release_pending_chunks(
+        &mut self,
+        security_client: &SecurityClient,
+        post_processor: &ResponsePostProcessor,
+    ) {
+        if self.pending_buffer.is_empty() {
+            return;
         }
-    }
 
-    /// Creates a single response from all accumulated content in the relevant buffer.
-    ///
-    /// Combines chunks from either code, text, or general output buffers into a single
-    /// response, prioritizing code content if available.
-    ///
-    /// # Returns
-    ///
-    /// Some(Bytes) if there is content to return, None otherwise
-    fn create_complete_response(&mut self) -> Option<Bytes> {
         // Pre-calculate the total buffer size needed to avoid reallocations
-        let total_size = if self.has_complete_code {
-            self.code_buffer_complete
-                .iter()
-                .map(|b| b.len())
-                .sum::<usize>()
-        } else if self.has_complete_text {
-            self.text_buffer_complete
-                .iter()
-                .map(|b| b.len())
-                .sum::<usize>()
-        } else {
-            self.output_buffer.iter().map(|b| b.len()).sum::<usize>()
-        };
-
-        // Pre-allocate with the right size
+        let total_size = self.pending_buffer.iter().map(|b| b.len()).sum::<usize>();
         let mut combined_data = Vec::with_capacity(total_size);
-
-        // If we have complete code, prioritize that
-        if self.has_complete_code {
-            // Combine all code chunks
-            for chunk in self.code_buffer_complete.drain(..) {
-                combined_data.extend_from_slice(&chunk);
-            }
-            self.has_complete_code = false;
-        } else if self.has_complete_text {
-            // Combine all text chunks
-            for chunk in self.text_buffer_complete.drain(..) {
-                combined_data.extend_from_slice(&chunk);
-            }
-            self.has_complete_text = false;
-        } else {
-            // Combine all general output chunks
-            for chunk in self.output_buffer.drain(..) {
-                combined_data.extend_from_slice(&chunk);
-            }
+        for chunk in self.pending_buffer.drain(..) {
+            combined_data.extend_from_slice(&chunk);
         }
 
-        self.batch_ready = false;
-
-        if !combined_data.is_empty() {
-            Some(Bytes::from(combined_data))
-        } else {
-            None
-        }
+        let secret_block_open = &mut self.secret_block_open;
+        let postprocess_state = &mut self.postprocess_state;
+        let masked_segments = &mut self.masked_segments;
+        let batch = rewrite_batch(combined_data, self.format, |text| {
+            let masked = security_client.mask_secrets(text, secret_block_open);
+            *masked_segments += usize::from(masked.is_some());
+            let current = masked.as_deref().unwrap_or(text);
+            post_processor.process(current, postprocess_state).or(masked)
+        });
+        self.ready_buffer.push_back(Bytes::from(batch));
     }
 
-    /// Returns the next complete chunk if a batch is ready.
-    ///
-    /// This method only returns content when a complete batch is ready, as indicated
-    /// by the batch_ready flag.
+    /// Returns the next assessed batch that is ready to be handed to the consumer.
     ///
     /// # Returns
     ///
-    /// Some(Bytes) if a complete batch is ready, None otherwise
-    fn get_next_chunk(&mut self) -> Option<Bytes> {
-        if self.batch_ready {
-            return self.create_complete_response();
-        }
-
-        // Not returning individual chunks - accumulate until batch is ready
-        None
+    /// Some(Bytes) if a batch is ready, None otherwise
+    fn next_ready_chunk(&mut self) -> Option<Bytes> {
+        self.ready_buffer.pop_front()
     }
 }
 
@@ -491,47 +551,204 @@ where
     #[pin]
     inner: S,
     security_client: SecurityClient,
+    post_processor: ResponsePostProcessor,
     model_name: String,
     buffer: StreamBuffer,
     assessment_fut: Option<AssessmentFuture>,
-    finished: bool,
-    retry_count: u32,
     is_prompt: bool,
+    risk_window: Option<usize>,  // Characters released ahead of their asynchronous verdict
+    settling: Vec<SettlingScan>, // Asynchronous assessments of released or releasable chunks
+    summary_pending: bool,       // Whether the verdict summary is still to be sent
+    deferred_error: Option<StreamError>, // Error ending the stream, sent after the summary
+}
+
+/// Builds a terminal NDJSON chunk (`done: true`) in the shape of the upstream endpoint.
+///
+/// # Arguments
+///
+/// * `format` - The chunk format used by the upstream endpoint
+/// * `model_name` - Model name reported in the chunk
+/// * `content` - Text content carried by the chunk
+/// * `done_reason` - Why the stream ended (e.g. "blocked", "truncated")
+///
+/// # Returns
+///
+/// Bytes containing a newline-terminated JSON object
+fn create_terminal_chunk(
+    format: ChunkFormat,
+    model_name: &str,
+    content: String,
+    done_reason: &str,
+) -> Bytes {
+    let mut chunk = serde_json::json!({
+        "model": model_name,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "done": true,
+        "done_reason": done_reason
+    });
+    match format {
+        ChunkFormat::Chat => {
+            chunk["message"] = serde_json::json!({ "role": "assistant", "content": content });
+        }
+        ChunkFormat::Generate => chunk["response"] = serde_json::json!(content),
+    }
+
+    let mut bytes = serde_json::to_vec(&chunk).unwrap_or_else(|_| content.into_bytes());
+    bytes.push(b'\n');
+    Bytes::from(bytes)
+}
+
+/// Builds the chunk summarizing the scans and the overall verdict of a finished stream.
+///
+/// The verdict is `block` if content was blocked, `error` if an assessment failed,
+/// `mask` if secrets were masked and `allow` otherwise. The summary carries no `done`
+/// field, since it is sent right before the stream's terminal chunk.
+///
+/// # Arguments
+///
+/// * `buffer` - The buffer of the finished stream
+/// * `model_name` - Model name reported in the chunk
+///
+/// # Returns
+///
+/// Bytes containing a newline-terminated JSON object
+fn create_summary_chunk(buffer: &StreamBuffer, model_name: &str) -> Bytes {
+    let verdict = if buffer.state == StreamState::Blocked {
+        "block"
+    } else if buffer.scan_failed {
+        "error"
+    } else if buffer.masked_segments > 0 {
+        "mask"
+    } else {
+        "allow"
+    };
+    let chunk = serde_json::json!({
+        "model": model_name,
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        SUMMARY_FIELD: {
+            "verdict": verdict,
+            "scans": buffer.scans,
+            "masked_segments": buffer.masked_segments,
+        }
+    });
+
+    let mut bytes = serde_json::to_vec(&chunk).unwrap_or_default();
+    bytes.push(b'\n');
+    Bytes::from(bytes)
+}
+
+/// Returns the offset of the terminal (`done: true`) chunk a batch ends with, if any.
+fn terminal_offset(batch: &[u8]) -> Option<usize> {
+    let body = batch.strip_suffix(b"\n").unwrap_or(batch);
+    let start = body.iter().rposition(|b| *b == b'\n').map_or(0, |index| index + 1);
+    let json = serde_json::from_slice::<serde_json::Value>(&body[start..]).ok()?;
+    json["done"].as_bool().unwrap_or(false).then_some(start)
+}
+
+/// Returns the text content carried by an NDJSON chunk.
+fn chunk_content(json: &serde_json::Value, format: ChunkFormat) -> Option<&str> {
+    match format {
+        ChunkFormat::Chat => json["message"]["content"].as_str(),
+        ChunkFormat::Generate => json["response"].as_str(),
+    }
+}
+
+/// Rewrites the text content of a released batch of NDJSON chunks.
+///
+/// The text of all content-bearing chunks is joined so that matches split across
+/// chunks are found. When the text is rewritten, those chunks are replaced by a
+/// single chunk carrying the new text in place of the last of them; all other
+/// lines are kept as they are.
+///
+/// # Arguments
+///
+/// * `batch` - The combined bytes of the released chunks
+/// * `format` - The chunk format used by the upstream endpoint
+/// * `rewrite` - Returns the rewritten text, or None to keep the batch unchanged
+///
+/// # Returns
+///
+/// The rewritten batch, or the original batch if nothing was rewritten
+fn rewrite_batch(
+    batch: Vec<u8>,
+    format: ChunkFormat,
+    rewrite: impl FnOnce(&str) -> Option<String>,
+) -> Vec<u8> {
+    let lines: Vec<&[u8]> = batch
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut text = String::new();
+    let mut content_lines = Vec::new();
+    let mut last_content: Option<(usize, serde_json::Value)> = None;
+    for (index, line) in lines.iter().enumerate() {
+        if let Ok(json) = serde_json::from_slice::<serde_json::Value>(line) {
+            if let Some(content) = chunk_content(&json, format).filter(|c| !c.is_empty()) {
+                text.push_str(content);
+                content_lines.push(index);
+                last_content = Some((index, json));
+            }
+        }
+    }
+
+    let Some((last_index, mut template)) = last_content else {
+        return batch;
+    };
+    let Some(rewritten) = rewrite(&text) else {
+        return batch;
+    };
+
+    match format {
+        ChunkFormat::Chat => template["message"]["content"] = serde_json::json!(rewritten),
+        ChunkFormat::Generate => template["response"] = serde_json::json!(rewritten),
+    }
+    let merged = match serde_json::to_vec(&template) {
+        Ok(merged) => merged,
+        Err(_) => return batch,
+    };
+
+    let mut output = Vec::with_capacity(batch.len());
+    for (index, line) in lines.iter().enumerate() {
+        if index == last_index {
+            output.extend_from_slice(&merged);
+        } else if !content_lines.contains(&index) {
+            output.extend_from_slice(line);
+        } else {
+            continue;
+        }
+        output.push(b'\n');
+    }
+    output
 }
 
 /// Creates a formatted response for blocked content.
 ///
 /// This function generates a standardized message indicating that content has been
 /// blocked by the security assessment system, including the category, action details,
-/// and specific detection information.
+/// and specific detection information. The response doubles as the stream's terminal chunk.
 ///
 /// # Arguments
 ///
 /// * `assessment` - The complete security assessment result
+/// * `format` - The chunk format used by the upstream endpoint
+/// * `branding` - Branding of the block message and its synthetic model name
 ///
 /// # Returns
 ///
 /// Bytes containing the formatted blocked content message
-fn create_blocked_response(assessment: &Assessment) -> Bytes {
+fn create_blocked_response(
+    assessment: &Assessment,
+    format: ChunkFormat,
+    branding: &Branding,
+) -> Bytes {
     // Format a JSON response that looks like a normal LLM response but contains our blocked message
-    let blocked_json = serde_json::json!({
-        "model": "security-filter", // Could be customized if needed
-        "created_at": chrono::Utc::now().to_rfc3339(),
-        "message": {
-            "role": "assistant",
-            "content": format_security_violation_message(assessment)
-        },
-        "done": true
-    });
-
-    // Convert to bytes
-    Bytes::from(serde_json::to_vec(&blocked_json).unwrap_or_else(|_| {
-        format!(
-            "BLOCKED - Category: {}, Action: {}",
-            assessment.category, assessment.action
-        )
-        .into_bytes()
-    }))
+    create_terminal_chunk(
+        format,
+        branding.model_name(),
+        branding.violation_message(assessment),
+        "blocked",
+    )
 }
 
 /// Creates a future that will perform security assessment on buffered content.
@@ -594,81 +811,185 @@ where
     ///
     /// * `inner` - The inner stream to wrap, which produces bytes
     /// * `security_client` - Client for performing security assessments
+    /// * `post_processor` - Rewrites applied to assessed content before release
     /// * `model_name` - Name of the AI model being used
     /// * `is_prompt` - Whether this stream contains prompt (true) or response (false) content
+    /// * `model_streaming` - Streaming strategies of individual models; models without one
+    ///   follow the asynchronous streaming settings of the security client
     ///
     /// # Returns
     ///
     /// A new SecurityAssessedStream instance
     pub fn new(
         inner: S,
-        security_client: SecurityClient,
+        mut security_client: SecurityClient,
+        post_processor: ResponsePostProcessor,
         model_name: String,
         is_prompt: bool,
+        model_streaming: &[ModelStreaming],
     ) -> Self {
+        let mut buffer = StreamBuffer::new();
+        let model = with_tag(&model_name);
+        let streaming = model_streaming
+            .iter()
+            .find(|streaming| with_tag(&streaming.model) == model);
+        let risk_window = match streaming {
+            Some(streaming) => {
+                debug!("Streaming {} with the {:?} strategy", model_name, streaming.strategy);
+                if let Some(window) = streaming.assessment_window_chars {
+                    buffer.assessment_window = window;
+                }
+                buffer.paragraph_batches = streaming.strategy == StreamingStrategy::Code;
+                (streaming.strategy == StreamingStrategy::Windowed).then(|| {
+                    streaming
+                        .risk_window_chars
+                        .unwrap_or_else(|| security_client.async_risk_window())
+                })
+            }
+            None => security_client
+                .async_streaming()
+                .map(|config| config.risk_window_chars),
+        };
+        if risk_window.is_some() {
+            security_client.with_async_scans();
+        }
+
         Self {
             inner,
-            security_client,
+            post_processor,
             model_name,
-            buffer: StreamBuffer::new(),
+            buffer,
             assessment_fut: None,
-            finished: false,
-            retry_count: 0,
             is_prompt,
+            risk_window,
+            settling: Vec::new(),
+            summary_pending: security_client.stream_summary(),
+            deferred_error: None,
+            security_client,
         }
     }
 
+    /// Polls the asynchronous assessments of submitted content.
+    ///
+    /// Safe verdicts widen the settled part of the stream. A blocking verdict or a
+    /// failed scan ends the stream, discarding everything not yet handed to the
+    /// consumer; chunks released within the risk window have already been forwarded.
+    ///
+    /// # Arguments
+    ///
+    /// * `settling` - The asynchronous assessments in flight
+    /// * `buffer` - The buffer whose settled position is advanced
+    /// * `security_client` - Client providing the branding of the blocked message
+    /// * `cx` - Task context for waking
+    ///
+    /// # Returns
+    ///
+    /// Some item ending the stream if content was blocked or could not be verified
+    fn poll_settling(
+        settling: &mut Vec<SettlingScan>,
+        buffer: &mut StreamBuffer,
+        security_client: &SecurityClient,
+        cx: &mut Context<'_>,
+    ) -> Option<Result<Bytes, StreamError>> {
+        let mut index = 0;
+        while index < settling.len() {
+            let Poll::Ready(result) = settling[index].future.as_mut().poll(cx) else {
+                index += 1;
+                continue;
+            };
+            let scan = settling.swap_remove(index);
+            buffer.scans += 1;
+            buffer.scan_failed |= result.is_err();
+            if let Ok(assessment) = &result {
+                if assessment.is_safe {
+                    buffer.settled_chars = buffer.settled_chars.max(scan.covers);
+                    continue;
+                }
+            }
+
+            settling.clear();
+            buffer.pending_buffer.clear();
+            buffer.ready_buffer.clear();
+            let forwarded = buffer.released_chars.saturating_sub(buffer.settled_chars);
+            if forwarded > 0 {
+                warn!(
+                    "Stream ended by a verdict after forwarding {} unverified characters",
+                    forwarded
+                );
+                metrics::increment_counter(
+                    "panw_stream_unverified_releases_total",
+                    "Streams ended by a verdict after content within the risk window was forwarded",
+                    &[],
+                );
+            }
+            return Some(match result {
+                Ok(assessment) => {
+                    buffer.transition(StreamState::Blocked);
+                    Ok(create_blocked_response(
+                        &assessment,
+                        buffer.format,
+                        &security_client.branding(),
+                    ))
+                }
+                Err(e) => {
+                    // Fail closed: content that could not be verified is not released further
+                    buffer.transition(StreamState::Finished);
+                    Err(e)
+                }
+            });
+        }
+        None
+    }
+
     /// Processes the results of a security assessment on buffered content.
     ///
     /// This method handles what happens after a security assessment is completed,
-    /// either passing content through if it's safe or blocking it if it's unsafe.
+    /// either queueing the pending content for release if it's safe or blocking it
+    /// if it's unsafe.
     ///
     /// # Arguments
     ///
     /// * `assessment` - The security assessment result
     /// * `buffer` - The buffer containing content that was assessed
-    /// * `assessment_fut` - The future that produced the assessment (will be cleared)
-    /// * `retry_count` - Counter for assessment retry attempts
+    /// * `security_client` - Client providing the local secret masking and branding
+    /// * `post_processor` - Rewrites applied to assessed content before release
     ///
     /// # Returns
     ///
-    /// Some(Result) if a response should be sent immediately, None if processing should continue
+    /// Some(Bytes) with the blocked message if the content was blocked, None otherwise
     fn process_assessment_result(
         assessment: Assessment,
         buffer: &mut StreamBuffer,
-        assessment_fut: &mut Option<AssessmentFuture>,
-        retry_count: &mut u32,
-    ) -> Option<Result<Bytes, StreamError>> {
-        // Important: Always clear the future after processing to avoid "resumed after completion" panic
-        *assessment_fut = None;
-
+        security_client: &SecurityClient,
+        post_processor: &ResponsePostProcessor,
+    ) -> Option<Bytes> {
+        buffer.scans += 1;
         if !assessment.is_safe {
-            let blocked = create_blocked_response(&assessment);
-            *retry_count = 0;
             // Clear the pending buffer since we're not going to send these chunks
             buffer.pending_buffer.clear();
-            buffer.waiting_for_assessment = false;
-            buffer.accumulating = false;
-            buffer.blocked = true;
-            return Some(Ok(blocked));
-        }
-
-        // Don't try to send content if the buffer is empty
-        if buffer.text_buffer.is_empty()
-            && buffer.code_buffer.is_empty()
-            && buffer.pending_buffer.is_empty()
-        {
-            buffer.commit(true);
-            return None;
+            buffer.transition(StreamState::Blocked);
+            return Some(create_blocked_response(
+                &assessment,
+                buffer.format,
+                &security_client.branding(),
+            ));
         }
 
-        // Mark the content as safe by updating the read position and clearing code buffer
+        // Mark the content as safe by updating the assessed positions
         buffer.commit(true);
 
         // Release all pending chunks now that assessment is complete
-        buffer.release_pending_chunks();
+        buffer.release_pending_chunks(security_client, post_processor);
+
+        // While draining, the queued chunks are flushed by the draining state itself
+        if buffer.state == StreamState::AwaitingAssessment {
+            if buffer.ready_buffer.is_empty() {
+                buffer.transition(StreamState::Accumulating);
+            } else {
+                buffer.transition(StreamState::Releasing);
+            }
+        }
 
-        // We don't return a result here - we'll let the chunks flow through via get_next_chunk
         None
     }
 
@@ -685,10 +1006,6 @@ where
     /// * `security_client` - Client for performing security assessments
     /// * `model_name` - Name of the AI model being used
     /// * `is_prompt` - Whether this is prompt or response content
-    ///
-    /// # Returns
-    ///
-    /// Some(Result) if a response should be sent immediately, None if processing should continue
     fn process_stream_chunk(
         bytes: Bytes,
         buffer: &mut StreamBuffer,
@@ -696,11 +1013,16 @@ where
         security_client: &SecurityClient,
         model_name: &str,
         is_prompt: bool,
-    ) -> Option<Result<Bytes, StreamError>> {
+    ) {
+        // Chunks that are not valid UTF-8 are still held back until the next assessment
         if let Ok(chunk) = std::str::from_utf8(&bytes) {
             // Check if this is the final chunk containing LLM metrics
             if let Ok(json) = serde_json::from_str::<serde_json::Value>(chunk) {
+                if json.get("response").is_some() {
+                    buffer.format = ChunkFormat::Generate;
+                }
                 if json.get("done").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    buffer.terminal_received = true;
                     // Use the shared utility function to log metrics
                     log_llm_metrics(&json, true);
                 }
@@ -711,121 +1033,71 @@ where
 
             // Call detect_code_blocks to find and handle code block markers
             buffer.detect_code_blocks();
-
-            // Always buffer the chunk while we determine if assessment is needed
-            buffer.buffer_pending_chunk(bytes);
-
-            // Check if we need to trigger an assessment
-            if buffer.get_assessable_chunk(is_prompt).is_some() {
-                *assessment_fut = Some(create_security_assessment_future(
-                    buffer,
-                    security_client,
-                    model_name,
-                    is_prompt,
-                ));
-                // We're already buffering chunks - set the waiting flag
-                buffer.waiting_for_assessment = true;
-                return None;
-            }
-
-            // If we're not waiting for assessment, we should still assess this content
-            // before sending it, so we'll create an assessment future anyway
-            if !buffer.waiting_for_assessment {
-                // Always perform some level of assessment before sending content
-                buffer.waiting_for_assessment = true;
-                *assessment_fut = Some(create_security_assessment_future(
-                    buffer,
-                    security_client,
-                    model_name,
-                    is_prompt,
-                ));
-            }
-
-            return None;
         }
 
-        // If we couldn't process as UTF-8, add to pending buffer to be safe
+        // Always buffer the chunk while we determine if assessment is needed
         buffer.buffer_pending_chunk(bytes);
 
-        // If we're not waiting for assessment, trigger one anyway for safety
-        if !buffer.waiting_for_assessment {
-            buffer.waiting_for_assessment = true;
+        // Check if we need to trigger an assessment
+        if buffer.get_assessable_chunk(is_prompt).is_some() {
             *assessment_fut = Some(create_security_assessment_future(
                 buffer,
                 security_client,
                 model_name,
                 is_prompt,
             ));
+            buffer.transition(StreamState::AwaitingAssessment);
         }
-
-        None
     }
 
     /// Handles the end of a stream by performing a final assessment if needed.
     ///
     /// When the input stream ends, this method checks if there's any remaining content
-    /// that needs security assessment before the stream can complete.
+    /// that needs security assessment before the stream can complete. The assessed
+    /// positions are deliberately left untouched here: they only advance once the
+    /// final assessment resolves as safe (see `StreamBuffer::commit`), so a failed
+    /// final assessment can never mark the tail as verified.
     ///
     /// # Arguments
     ///
     /// * `buffer` - The buffer containing any remaining content
     /// * `assessment_fut` - Optional future for pending assessments
     /// * `security_client` - Client for performing security assessments
+    /// * `post_processor` - Rewrites applied to assessed content before release
     /// * `model_name` - Name of the AI model being used
     /// * `is_prompt` - Whether this is prompt or response content
-    ///
-    /// # Returns
-    ///
-    /// Some(Result) if a final response should be sent, None if processing should continue
     fn process_stream_end(
         buffer: &mut StreamBuffer,
         assessment_fut: &mut Option<AssessmentFuture>,
         security_client: &SecurityClient,
+        post_processor: &ResponsePostProcessor,
         model_name: &str,
         is_prompt: bool,
-    ) -> Option<Result<Bytes, StreamError>> {
+    ) {
         // Check if there's any new content since the last assessment
         let new_text_content = buffer.text_buffer.len() > buffer.last_assessed_text_pos;
         let new_code_content = buffer.code_buffer.len() > buffer.last_assessed_code_pos;
 
-        // Only trigger final assessment if we have new content
         if new_text_content || new_code_content {
-            // Only log the new portions of text/code that will be assessed
-            if new_text_content {
-                &buffer.text_buffer[buffer.last_assessed_text_pos..]
-            } else {
-                ""
-            };
-
-            if new_code_content {
-                &buffer.code_buffer[buffer.last_assessed_code_pos..]
-            } else {
-                ""
-            };
-
-            // Create assessment future for the new content
+            // The unassessed tail must be verified before any pending chunk is released
             *assessment_fut = Some(create_security_assessment_future(
                 buffer,
                 security_client,
                 model_name,
                 is_prompt,
             ));
-
-            // Update tracking positions to avoid reassessing this content
-            buffer.last_assessed_text_pos = buffer.text_buffer.len();
-            buffer.last_assessed_code_pos = buffer.code_buffer.len();
-
-            None
         } else {
-            // No new content to assess
-            None
+            // Pending chunks without new content (e.g. the final metrics chunk) carry
+            // nothing left to assess, so they can be released as they are
+            buffer.release_pending_chunks(security_client, post_processor);
         }
     }
 
     /// Implementation of the Stream::poll_next method.
     ///
-    /// This method handles the stream polling logic, checking for buffered chunks,
-    /// processing pending assessments, and handling the inner stream's data.
+    /// This method drives the stream state machine: it settles in-flight assessments,
+    /// hands released chunks to the consumer, and reads from the inner stream only
+    /// while accumulating.
     ///
     /// # Arguments
     ///
@@ -844,101 +1116,154 @@ where
     {
         let mut this = self.project();
 
-        // Check if content has been blocked, if so we should stop processing and close the stream
-        if this.buffer.blocked {
-            *this.finished = true;
-            return Poll::Ready(None);
-        }
-
-        // First check if we have any buffered chunks ready to return
-        if let Some(bytes) = this.buffer.get_next_chunk() {
-            return Poll::Ready(Some(Ok(bytes)));
-        }
-
         loop {
-            if *this.finished {
+            if this.buffer.state.is_terminal() {
                 return Poll::Ready(None);
             }
 
-            // Process pending security assessments
+            if let Some(item) = Self::poll_settling(
+                this.settling,
+                this.buffer,
+                this.security_client,
+                cx,
+            ) {
+                return Poll::Ready(Some(item));
+            }
+
+            // Settle any in-flight assessment before anything else
             if let Some(fut) = this.assessment_fut.as_mut() {
                 match fut.as_mut().poll(cx) {
                     Poll::Ready(Ok(assessment)) => {
-                        if let Some(result) = Self::process_assessment_result(
+                        // Always clear the future after completion to avoid polling it again
+                        *this.assessment_fut = None;
+                        if let Some(blocked) = Self::process_assessment_result(
                             assessment,
                             this.buffer,
-                            this.assessment_fut,
-                            this.retry_count,
+                            this.security_client,
+                            this.post_processor,
                         ) {
-                            // If content has been blocked, return the blocked message
-                            // and mark the stream as finished on the next poll
-                            if this.buffer.blocked {
-                                return Poll::Ready(Some(result));
-                            }
-                            return Poll::Ready(Some(result));
-                        }
-                        // After processing assessment, check if we have buffered chunks to return
-                        if let Some(bytes) = this.buffer.get_next_chunk() {
-                            return Poll::Ready(Some(Ok(bytes)));
+                            return Poll::Ready(Some(Ok(blocked)));
                         }
+                        continue;
                     }
                     Poll::Ready(Err(e)) => {
-                        this.assessment_fut.take();
+                        // Fail closed: content that could not be verified is never released
+                        *this.assessment_fut = None;
+                        this.buffer.scans += 1;
+                        this.buffer.scan_failed = true;
+                        this.buffer.pending_buffer.clear();
+                        this.buffer.ready_buffer.clear();
+                        this.buffer.transition(StreamState::Finished);
                         return Poll::Ready(Some(Err(e)));
                     }
                     Poll::Pending => return Poll::Pending,
                 }
             }
 
-            // Process incoming stream chunks
-            match ready!(this.inner.as_mut().poll_next(cx)) {
-                Some(Ok(bytes)) => {
-                    Self::process_stream_chunk(
-                        bytes,
+            match this.buffer.state {
+                StreamState::Releasing => {
+                    if let Some(bytes) = this.buffer.next_ready_chunk() {
+                        return Poll::Ready(Some(Ok(bytes)));
+                    }
+                    this.buffer.transition(StreamState::Accumulating);
+                }
+                StreamState::Draining => {
+                    if let Some(bytes) = this.buffer.next_ready_chunk() {
+                        return Poll::Ready(Some(Ok(bytes)));
+                    }
+
+                    // Earlier asynchronous assessments settle before the tail is assessed
+                    if !this.settling.is_empty() {
+                        return Poll::Pending;
+                    }
+
+                    Self::process_stream_end(
                         this.buffer,
                         this.assessment_fut,
                         this.security_client,
+                        this.post_processor,
                         this.model_name,
                         *this.is_prompt,
                     );
 
-                    // After processing the chunk, check if we have any completed content to return
-                    if this.assessment_fut.is_some() {
-                        // If we started an assessment, wait for it to complete
-                        cx.waker().wake_by_ref();
-                        return Poll::Pending;
-                    } else if let Some(bytes) = this.buffer.get_next_chunk() {
-                        // If we have a chunk ready to return, return it
-                        return Poll::Ready(Some(Ok(bytes)));
+                    // Settle the final assessment or flush the released tail first
+                    if this.assessment_fut.is_none() && this.buffer.ready_buffer.is_empty() {
+                        this.buffer.transition(StreamState::Finished);
+                        if !this.buffer.terminal_received {
+                            // The upstream was truncated; clients waiting for `done` would hang
+                            warn!("Upstream stream ended without a terminal chunk, synthesizing one");
+                            return Poll::Ready(Some(Ok(create_terminal_chunk(
+                                this.buffer.format,
+                                this.model_name,
+                                String::new(),
+                                "truncated",
+                            ))));
+                        }
+                        return Poll::Ready(None);
                     }
-                    // Otherwise continue processing more chunks
-                    continue;
                 }
-                Some(Err(e)) => {
-                    return Poll::Ready(Some(Err(StreamError::NetworkError(e.to_string()))));
+                StreamState::AwaitingAssessment => {
+                    // Submitted chunks are released once they fit the risk window
+                    if let Some(risk_window) = *this.risk_window {
+                        // The upstream terminal chunk is held back until every window
+                        // scan has settled, so a late block cannot follow it
+                        if this.buffer.terminal_received {
+                            this.buffer.transition(StreamState::Accumulating);
+                            continue;
+                        }
+                        if this.buffer.within_risk_window(risk_window) {
+                            this.buffer.released_chars = this.buffer.submitted_chars;
+                            this.buffer
+                                .release_pending_chunks(this.security_client, this.post_processor);
+                            if this.buffer.ready_buffer.is_empty() {
+                                this.buffer.transition(StreamState::Accumulating);
+                            } else {
+                                this.buffer.transition(StreamState::Releasing);
+                            }
+                            continue;
+                        }
+                        if !this.settling.is_empty() {
+                            return Poll::Pending;
+                        }
+                    }
+
+                    // Awaiting without a future in flight cannot make progress
+                    warn!("Stream was awaiting assessment with no assessment in flight");
+                    this.buffer.transition(StreamState::Finished);
+                    return Poll::Ready(None);
                 }
-                None => {
-                    // Final assessment on stream end
-                    if let Some(result) = Self::process_stream_end(
-                        this.buffer,
-                        this.assessment_fut,
-                        this.security_client,
-                        this.model_name,
-                        *this.is_prompt,
-                    ) {
-                        return Poll::Ready(Some(result));
-                    } else if this.assessment_fut.is_some() {
-                        // If we started a final assessment, wait for it to complete
-                        cx.waker().wake_by_ref();
-                        return Poll::Pending;
-                    } else if let Some(bytes) = this.buffer.get_next_chunk() {
-                        // Try to return any remaining buffered chunks
-                        return Poll::Ready(Some(Ok(bytes)));
-                    } else {
-                        *this.finished = true;
-                        return Poll::Ready(None);
+                StreamState::Accumulating => {
+                    match ready!(this.inner.as_mut().poll_next(cx)) {
+                        Some(Ok(bytes)) => {
+                            Self::process_stream_chunk(
+                                bytes,
+                                this.buffer,
+                                this.assessment_fut,
+                                this.security_client,
+                                this.model_name,
+                                *this.is_prompt,
+                            );
+
+                            // Asynchronous assessments settle while their chunks are released
+                            if this.risk_window.is_some() {
+                                if let Some(future) = this.assessment_fut.take() {
+                                    let covers = this.buffer.submit();
+                                    this.settling.push(SettlingScan { future, covers });
+                                }
+                            }
+                        }
+                        Some(Err(e)) => {
+                            // The error item is the terminal chunk; unassessed content is dropped
+                            this.buffer.pending_buffer.clear();
+                            this.buffer.transition(StreamState::Finished);
+                            return Poll::Ready(Some(Err(StreamError::NetworkError(
+                                e.to_string(),
+                            ))));
+                        }
+                        None => this.buffer.transition(StreamState::Draining),
                     }
                 }
+                StreamState::Blocked | StreamState::Finished => return Poll::Ready(None),
             }
         }
     }
@@ -963,7 +1288,386 @@ where
     /// # Returns
     ///
     /// Poll indicating whether an item is ready or pending
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.poll_next_impl(cx)
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(e) = self.as_mut().project().deferred_error.take() {
+            return Poll::Ready(Some(Err(e)));
+        }
+        let item = ready!(self.as_mut().poll_next_impl(cx));
+
+        // The verdict summary precedes the stream's terminal chunk, which stays the only
+        // chunk marked as `done`
+        let this = self.project();
+        if !*this.summary_pending {
+            return Poll::Ready(item);
+        }
+        let item = match item {
+            Some(Ok(bytes)) => match terminal_offset(&bytes) {
+                Some(offset) => {
+                    let summary = create_summary_chunk(this.buffer, this.model_name);
+                    let mut batch = Vec::with_capacity(bytes.len() + summary.len());
+                    batch.extend_from_slice(&bytes[..offset]);
+                    batch.extend_from_slice(&summary);
+                    batch.extend_from_slice(&bytes[offset..]);
+                    Ok(Bytes::from(batch))
+                }
+                None => return Poll::Ready(Some(Ok(bytes))),
+            },
+            Some(Err(e)) => {
+                // Errors are turned into the terminal chunk by the consumer
+                *this.deferred_error = Some(e);
+                Ok(create_summary_chunk(this.buffer, this.model_name))
+            }
+            None => Ok(create_summary_chunk(this.buffer, this.model_name)),
+        };
+        *this.summary_pending = false;
+        Poll::Ready(Some(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PostProcessConfig, SecurityConfig};
+    use crate::mock_panw::MockPanw;
+    use futures_util::{stream, StreamExt};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    // Text marking content the mock PANW API blocks.
+    const MARKER: &str = "forbidden";
+
+    fn chat_chunk(content: &str, done: bool) -> Bytes {
+        let chunk = json!({
+            "model": "llama3",
+            "message": { "role": "assistant", "content": content },
+            "done": done,
+        });
+        Bytes::from(format!("{}\n", chunk))
+    }
+
+    fn generate_chunk(response: &str, done: bool) -> Bytes {
+        let chunk = json!({ "model": "llama3", "response": response, "done": done });
+        Bytes::from(format!("{}\n", chunk))
+    }
+
+    // Returns a client scanning with PANW at `base_url`, with `settings` added to the
+    // security configuration.
+    fn security_client(base_url: &str, settings: Value) -> SecurityClient {
+        let mut config = json!({
+            "base_url": base_url,
+            "api_key": "test",
+            "profile_name": "test",
+            "app_name": "test",
+            "app_user": "test",
+        });
+        if let (Some(config), Value::Object(settings)) = (config.as_object_mut(), settings) {
+            config.extend(settings);
+        }
+        SecurityClient::new(serde_json::from_value::<SecurityConfig>(config).unwrap())
+    }
+
+    fn async_streaming() -> Value {
+        json!({
+            "async_streaming": {
+                "enabled": true,
+                "risk_window_chars": 1000,
+                "poll_interval_ms": 10,
+            },
+        })
+    }
+
+    // Streams the chunks through the assessment and returns the emitted lines, with
+    // errors as `None`.
+    async fn assess(chunks: Vec<Bytes>, security_client: SecurityClient) -> Vec<Option<Value>> {
+        let (lines, _) = assess_states(chunks, security_client).await;
+        lines.into_iter().map(|(_, line)| line).collect()
+    }
+
+    // Streams the chunks through the assessment and returns the emitted lines, with
+    // errors as `None`, each paired with the state the stream was in once it was
+    // emitted, and the state the stream ended in.
+    async fn assess_states(
+        chunks: Vec<Bytes>,
+        security_client: SecurityClient,
+    ) -> (Vec<(StreamState, Option<Value>)>, StreamState) {
+        let inner = stream::iter(chunks.into_iter().map(Ok::<_, reqwest::Error>));
+        let mut assessed = std::pin::pin!(SecurityAssessedStream::new(
+            inner,
+            security_client,
+            ResponsePostProcessor::new(&PostProcessConfig::default()),
+            "llama3".to_string(),
+            false,
+            &[],
+        ));
+        let mut lines = Vec::new();
+        while let Some(item) = assessed.next().await {
+            let state = assessed.as_ref().get_ref().buffer.state;
+            match item {
+                Ok(bytes) => lines.extend(
+                    bytes
+                        .split(|b| *b == b'\n')
+                        .filter(|line| !line.is_empty())
+                        .map(|line| (state, Some(serde_json::from_slice(line).unwrap()))),
+                ),
+                Err(_) => lines.push((state, None)),
+            }
+        }
+        (lines, assessed.as_ref().get_ref().buffer.state)
+    }
+
+    // Returns the content of every emitted chat line, with errors as `None`.
+    fn contents(lines: &[(StreamState, Option<Value>)]) -> Vec<Option<&str>> {
+        lines
+            .iter()
+            .map(|(_, line)| {
+                line.as_ref()
+                    .and_then(|line| line["message"]["content"].as_str())
+            })
+            .collect()
+    }
+
+    // Returns the state the stream was in once each line was emitted.
+    fn states(lines: &[(StreamState, Option<Value>)]) -> Vec<StreamState> {
+        lines.iter().map(|(state, _)| *state).collect()
+    }
+
+    fn done_chunks(lines: &[Option<Value>]) -> Vec<&Value> {
+        lines
+            .iter()
+            .flatten()
+            .filter(|line| line["done"] == json!(true))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn terminal_chunk_waits_for_a_blocking_window_scan() {
+        let mock = MockPanw::start_blocking(Duration::from_millis(100), MARKER)
+            .await
+            .unwrap();
+        // The terminal chunk completes the last line, submitting its window scan
+        let chunks = vec![
+            chat_chunk("The first line is fine", false),
+            chat_chunk(".\n", false),
+            chat_chunk(&format!("The last line is {}", MARKER), false),
+            chat_chunk(".\n", true),
+        ];
+
+        let lines = assess(chunks, security_client(mock.base_url(), async_streaming())).await;
+
+        let done = done_chunks(&lines);
+        assert_eq!(done.len(), 1, "{:?}", lines);
+        assert_eq!(done[0]["done_reason"], "blocked");
+        assert_eq!(lines.last().unwrap().as_ref(), Some(done[0]));
+    }
+
+    // Base URL on which no PANW API listens, failing every scan.
+    const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+    // Chunks ending in a tail that is only assessed once the upstream has ended.
+    fn chunks_with_tail(tail: &str) -> Vec<Bytes> {
+        vec![
+            chat_chunk("The tail ", false),
+            chat_chunk(tail, false),
+            chat_chunk("", true),
+        ]
+    }
+
+    #[tokio::test]
+    async fn failed_tail_assessment_fails_closed() {
+        let lines = assess(
+            chunks_with_tail("is never verified"),
+            security_client(UNREACHABLE, json!({ "failure_mode": "fail_closed" })),
+        )
+        .await;
+
+        // Only the error is emitted; neither the tail nor the upstream terminal chunk is
+        assert_eq!(lines, vec![None]);
+    }
+
+    #[tokio::test]
+    async fn failed_tail_assessment_fails_open() {
+        let lines = assess(
+            chunks_with_tail("passes unscanned"),
+            security_client(UNREACHABLE, json!({ "failure_mode": "fail_open" })),
+        )
+        .await;
+
+        let contents: Vec<_> = lines
+            .iter()
+            .map(|line| {
+                line.as_ref().unwrap()["message"]["content"]
+                    .as_str()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(contents, vec!["The tail ", "passes unscanned", ""]);
+        let done = done_chunks(&lines);
+        assert_eq!(done.len(), 1);
+        assert!(done[0].get("done_reason").is_none());
+    }
+
+    #[tokio::test]
+    async fn blocked_tail_is_replaced_by_the_blocked_message() {
+        let mock = MockPanw::start_blocking(Duration::ZERO, MARKER)
+            .await
+            .unwrap();
+        let lines = assess(
+            chunks_with_tail(&format!("is {}", MARKER)),
+            security_client(mock.base_url(), json!({})),
+        )
+        .await;
+
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        let blocked = lines[0].as_ref().unwrap();
+        assert_eq!(blocked["done"], true);
+        assert_eq!(blocked["done_reason"], "blocked");
+        assert!(!blocked["message"]["content"]
+            .as_str()
+            .unwrap()
+            .contains(MARKER));
+    }
+
+    #[test]
+    fn generate_chunks_are_read_from_response() {
+        let mut buffer = StreamBuffer::new();
+        buffer.process(r#"{"model":"llama3","response":"Some ","done":false}"#);
+        buffer.process(r#"{"model":"llama3","response":"text","done":false}"#);
+
+        assert_eq!(buffer.text_buffer, "Some text");
+    }
+
+    #[tokio::test]
+    async fn generate_stream_is_scanned() {
+        let mock = MockPanw::start_blocking(Duration::ZERO, MARKER)
+            .await
+            .unwrap();
+        let chunks = vec![
+            generate_chunk("The first line is fine", false),
+            generate_chunk(".\n", false),
+            generate_chunk(&format!("The next line is {}", MARKER), false),
+            generate_chunk(".\n", false),
+            generate_chunk("", true),
+        ];
+
+        let lines = assess(chunks, security_client(mock.base_url(), json!({}))).await;
+
+        let done = done_chunks(&lines);
+        assert_eq!(done.len(), 1, "{:?}", lines);
+        assert_eq!(done[0]["done_reason"], "blocked");
+        assert!(done[0]["response"].is_string());
+        assert!(lines.iter().flatten().all(|line| !line["response"]
+            .as_str()
+            .unwrap_or_default()
+            .contains(MARKER)));
+    }
+
+    // Every state, for checking the transition table exhaustively.
+    const STATES: [StreamState; 6] = [
+        StreamState::Accumulating,
+        StreamState::AwaitingAssessment,
+        StreamState::Releasing,
+        StreamState::Blocked,
+        StreamState::Draining,
+        StreamState::Finished,
+    ];
+
+    // Chunks of a chat response whose second line is blocked when it holds `marker`.
+    fn two_line_chunks(marker: &str) -> Vec<Bytes> {
+        vec![
+            chat_chunk("The first line is fine", false),
+            chat_chunk(".\n", false),
+            chat_chunk(&format!("The second line is {}", marker), false),
+            chat_chunk(".\n", false),
+            chat_chunk("", true),
+        ]
+    }
+
+    #[tokio::test]
+    async fn blocked_stream_releases_assessed_lines_then_ends_blocked() {
+        let mock = MockPanw::start_blocking(Duration::ZERO, MARKER)
+            .await
+            .unwrap();
+        let (lines, end) = assess_states(
+            two_line_chunks(MARKER),
+            security_client(mock.base_url(), json!({})),
+        )
+        .await;
+
+        assert_eq!(
+            states(&lines),
+            vec![
+                StreamState::Releasing,
+                StreamState::Releasing,
+                StreamState::Blocked
+            ]
+        );
+        let contents = contents(&lines);
+        assert_eq!(contents[..2], [Some("The first line is fine"), Some(".\n")]);
+        assert!(!contents[2].unwrap().contains(MARKER));
+        let blocked = lines[2].1.as_ref().unwrap();
+        assert_eq!(blocked["done"], true);
+        assert_eq!(blocked["done_reason"], "blocked");
+        assert_eq!(end, StreamState::Blocked);
+    }
+
+    #[tokio::test]
+    async fn failed_assessment_ends_the_stream_with_an_error() {
+        let (lines, end) = assess_states(
+            two_line_chunks("fine"),
+            security_client(UNREACHABLE, json!({ "failure_mode": "fail_closed" })),
+        )
+        .await;
+
+        // The first batch is never released; the error is the only item
+        assert_eq!(lines, vec![(StreamState::Finished, None)]);
+        assert_eq!(end, StreamState::Finished);
+    }
+
+    #[tokio::test]
+    async fn completed_stream_drains_its_tail_and_finishes() {
+        let mock = MockPanw::start_blocking(Duration::ZERO, MARKER)
+            .await
+            .unwrap();
+        let mut chunks = two_line_chunks("fine");
+        chunks.insert(4, chat_chunk("An unterminated tail", false));
+        let (lines, end) = assess_states(chunks, security_client(mock.base_url(), json!({}))).await;
+
+        assert_eq!(
+            states(&lines),
+            vec![
+                StreamState::Releasing,
+                StreamState::Releasing,
+                StreamState::Releasing,
+                StreamState::Releasing,
+                StreamState::Draining,
+                StreamState::Draining,
+            ]
+        );
+        assert_eq!(
+            contents(&lines),
+            vec![
+                Some("The first line is fine"),
+                Some(".\n"),
+                Some("The second line is fine"),
+                Some(".\n"),
+                Some("An unterminated tail"),
+                Some(""),
+            ]
+        );
+        let done = lines.last().unwrap().1.as_ref().unwrap();
+        assert_eq!(done["done"], true);
+        assert!(done.get("done_reason").is_none());
+        assert_eq!(end, StreamState::Finished);
+    }
+
+    #[test]
+    fn only_blocked_and_finished_are_terminal() {
+        for state in STATES {
+            let terminal = matches!(state, StreamState::Blocked | StreamState::Finished);
+            assert_eq!(state.is_terminal(), terminal, "{:?}", state);
+            if terminal {
+                assert!(STATES.iter().all(|next| !state.can_transition_to(*next)));
+            }
+        }
     }
 }
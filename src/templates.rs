@@ -0,0 +1,194 @@
+// Library of approved prompt templates.
+//
+// Templates centralize approved prompts for less technical users: an operator
+// saves a prompt with `{{variable}}` placeholders once, and users run it by
+// supplying only the variable values.
+//
+// # Scanning
+//
+// - The template text and system message are scanned when the template is
+//   saved, and templates that fail the scan are rejected.
+// - The substituted values are scanned when the template is run; the response
+//   is scanned like any other generated response.
+//
+// # Persistence
+//
+// Saved templates are written to the configured state file, encrypted when
+// state file encryption is configured, and loaded again at startup.
+use crate::config::TemplatesConfig;
+use crate::encryption;
+use crate::migrations;
+use crate::types::PromptTemplate;
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+// Pattern matching a `{{variable}}` placeholder.
+const PLACEHOLDER_PATTERN: &str = r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}";
+
+// Stores prompt templates and substitutes their variables.
+//
+// Cloning the store is cheap and all clones share the same templates.
+#[derive(Clone)]
+pub struct TemplateStore {
+    // File persisting the templates; empty to keep them in memory only
+    state_file: String,
+
+    // Compiled placeholder pattern
+    placeholder: Arc<Regex>,
+
+    // Templates keyed by name
+    templates: Arc<Mutex<BTreeMap<String, PromptTemplate>>>,
+}
+
+impl TemplateStore {
+    // Creates the store, loading persisted templates from the state file.
+    pub fn new(config: &TemplatesConfig) -> Self {
+        let templates = load_templates(&config.state_file);
+        if !templates.is_empty() {
+            info!("Loaded {} prompt templates", templates.len());
+        }
+
+        Self {
+            state_file: config.state_file.clone(),
+            placeholder: Arc::new(Regex::new(PLACEHOLDER_PATTERN).expect("valid placeholder pattern")),
+            templates: Arc::new(Mutex::new(templates)),
+        }
+    }
+
+    // Returns all templates ordered by name.
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        let templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        templates.values().cloned().collect()
+    }
+
+    // Returns the template with the given name.
+    pub fn get(&self, name: &str) -> Option<PromptTemplate> {
+        let templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        templates.get(name).cloned()
+    }
+
+    // Returns the distinct variable names used in a template text, in order of appearance.
+    pub fn variables(&self, template: &str) -> Vec<String> {
+        let mut variables: Vec<String> = Vec::new();
+        for captures in self.placeholder.captures_iter(template) {
+            let name = &captures[1];
+            if !variables.iter().any(|existing| existing == name) {
+                variables.push(name.to_string());
+            }
+        }
+        variables
+    }
+
+    // Saves a template, replacing any template with the same name.
+    pub fn save(&self, template: PromptTemplate) {
+        let mut templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        templates.insert(template.name.clone(), template);
+        save_templates(&self.state_file, &templates);
+    }
+
+    // Removes a template, returning whether it existed.
+    pub fn remove(&self, name: &str) -> bool {
+        let mut templates = self.templates.lock().unwrap_or_else(|e| e.into_inner());
+        let removed = templates.remove(name).is_some();
+        if removed {
+            save_templates(&self.state_file, &templates);
+        }
+        removed
+    }
+
+    // Substitutes variable values into a template.
+    //
+    // # Errors
+    //
+    // Returns the names of the variables that have no value.
+    pub fn render(
+        &self,
+        template: &PromptTemplate,
+        values: &HashMap<String, String>,
+    ) -> Result<String, Vec<String>> {
+        let missing: Vec<String> = template
+            .variables
+            .iter()
+            .filter(|name| !values.contains_key(*name))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+
+        let rendered = self
+            .placeholder
+            .replace_all(&template.template, |captures: &Captures| {
+                values.get(&captures[1]).cloned().unwrap_or_default()
+            });
+        Ok(rendered.into_owned())
+    }
+}
+
+// Loads the persisted templates, starting empty if the file is missing or unreadable.
+fn load_templates(path: &str) -> BTreeMap<String, PromptTemplate> {
+    if path.is_empty() {
+        return BTreeMap::new();
+    }
+
+    match fs::read_to_string(path) {
+        Ok(contents) => encryption::open(&contents)
+            .and_then(|contents| migrations::decode("templates", &contents))
+            .and_then(|data| serde_json::from_value(data).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Ignoring invalid prompt template file {}: {}", path, e);
+                BTreeMap::new()
+            }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+// Checks that the state file holds valid templates.
+//
+// # Returns
+//
+// The number of stored templates, or `None` if no state file exists
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or is not a valid template library.
+pub fn verify_state_file(path: &str) -> Result<Option<usize>, String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    verify_contents(&contents).map(Some)
+}
+
+// Checks that state file contents hold valid templates.
+//
+// # Returns
+//
+// The number of templates
+//
+// # Errors
+//
+// Returns an error if the contents are not a valid template library.
+pub fn verify_contents(contents: &str) -> Result<usize, String> {
+    let data = migrations::decode("templates", &encryption::open(contents)?)?;
+    let templates: BTreeMap<String, PromptTemplate> =
+        serde_json::from_value(data).map_err(|e| e.to_string())?;
+    Ok(templates.len())
+}
+
+// Persists the templates so they survive restarts.
+fn save_templates(path: &str, templates: &BTreeMap<String, PromptTemplate>) {
+    if path.is_empty() {
+        return;
+    }
+
+    let result = migrations::encode("templates", templates)
+        .and_then(|contents| encryption::write(path, &contents));
+
+    if let Err(e) = result {
+        warn!("Failed to persist prompt templates to {}: {}", path, e);
+    }
+}
@@ -0,0 +1,105 @@
+// Per-route request timeouts.
+//
+// Requests that take longer than the configured timeout are aborted: the
+// handler is dropped, which cancels the upstream Ollama request and any PANW
+// scan still pending, and the client receives a 504 error instead of waiting
+// indefinitely on a stalled backend.
+//
+// # Streaming
+//
+// Streaming and non-streaming responses have separate timeouts. The request
+// timeout bounds the time until the response starts, which for a streaming
+// response is the time until the first chunk. The stream timeout bounds the
+// whole exchange of a streaming response, measured from the arrival of the
+// request; once it passes the stream is cut off and ends with a final error
+// chunk, since the status code has already been sent. A response is treated as
+// streaming when its length is not known up front.
+use crate::config::TimeoutConfig;
+use crate::cutoff::cut_off_stream;
+use crate::handlers::ApiError;
+use crate::metrics;
+use crate::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::warn;
+
+// Returns the request and stream timeout of a route; zero disables a timeout.
+fn timeouts(config: &TimeoutConfig, route: &str) -> (Duration, Duration) {
+    let (request, stream) = config
+        .routes
+        .iter()
+        .find(|timeout| timeout.route == route)
+        .map_or(
+            (config.request_timeout_secs, config.stream_timeout_secs),
+            |timeout| (timeout.request_timeout_secs, timeout.stream_timeout_secs),
+        );
+    (Duration::from_secs(request), Duration::from_secs(stream))
+}
+
+// Counts a request that ran into its timeout.
+fn record_timeout(route: &str, phase: &'static str) {
+    metrics::increment_counter(
+        "panw_request_timeouts_total",
+        "Requests aborted because they exceeded their timeout",
+        &[("route", route), ("phase", phase)],
+    );
+}
+
+// Middleware aborting requests and streams that exceed their route's timeouts.
+pub async fn enforce_timeouts(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let (request_timeout, stream_timeout) = timeouts(&state.timeout_config, &route);
+    let started = Instant::now();
+
+    let response = if request_timeout.is_zero() {
+        next.run(request).await
+    } else {
+        match tokio::time::timeout(request_timeout, next.run(request)).await {
+            Ok(response) => response,
+            Err(_) => {
+                warn!(
+                    "Request to {} timed out after {} s",
+                    route,
+                    request_timeout.as_secs()
+                );
+                record_timeout(&route, "request");
+                return ApiError::Timeout(format!(
+                    "Request timed out after {} s",
+                    request_timeout.as_secs()
+                ))
+                .into_response();
+            }
+        }
+    };
+
+    if stream_timeout.is_zero() {
+        return response;
+    }
+
+    // Cut the stream off at the deadline
+    let deadline = started + stream_timeout;
+    cut_off_stream(response, async move {
+        tokio::time::sleep_until(deadline).await;
+        warn!(
+            "Stream of {} timed out after {} s",
+            route,
+            stream_timeout.as_secs()
+        );
+        record_timeout(&route, "stream");
+        let message = format!("Stream timed out after {} s", stream_timeout.as_secs());
+        (message, StatusCode::GATEWAY_TIMEOUT)
+    })
+}
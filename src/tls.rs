@@ -0,0 +1,250 @@
+// Native TLS termination for the listener.
+//
+// When a certificate and key are configured, the proxy serves HTTPS directly
+// instead of relying on a separate reverse proxy. Both files are PEM encoded;
+// the certificate file may hold the full chain.
+//
+// # Client Certificates
+//
+// When a client CA bundle is configured as well, the listener requires mutual
+// TLS: only clients presenting a certificate signed by one of the CAs complete
+// the handshake. The common name of a validated client certificate is attached
+// to every request on the connection as its `ClientIdentity` and reported to
+// PANW as the `app_user`.
+//
+// # Reloading
+//
+// The files are checked for changes on an interval and the certificate is
+// swapped in place when any of them changes, so renewed certificates are
+// picked up without a restart. New connections use the new certificate while
+// existing connections keep theirs. A reload that fails, for example because
+// only one of the two files has been replaced so far, keeps the previous
+// certificate and is retried on the next check.
+//
+// When the private key is read from Vault instead of a file, it is reloaded
+// whenever the refreshed secret changes.
+use crate::client_auth::ClientIdentity;
+use crate::vault::SharedSecret;
+use axum::{middleware::AddExtension, Extension};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures_util::future::BoxFuture;
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::server::TlsStream;
+use tower::Layer;
+use tracing::{debug, info, warn};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+// Certificate, key and client CA files of the listener.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    // PEM certificate chain
+    cert_file: PathBuf,
+
+    // PEM private key
+    key_file: PathBuf,
+
+    // PEM bundle of CAs for client certificates (optional)
+    client_ca_file: Option<PathBuf>,
+
+    // PEM private key read from Vault instead of `key_file` (optional)
+    key_secret: Option<SharedSecret>,
+}
+
+impl TlsFiles {
+    // Creates the file set.
+    pub fn new(
+        cert_file: impl Into<PathBuf>,
+        key_file: impl Into<PathBuf>,
+        client_ca_file: Option<impl Into<PathBuf>>,
+    ) -> Self {
+        Self {
+            cert_file: cert_file.into(),
+            key_file: key_file.into(),
+            client_ca_file: client_ca_file.map(Into::into),
+            key_secret: None,
+        }
+    }
+
+    // Uses a private key read from Vault instead of the key file.
+    pub fn with_key_secret(mut self, key_secret: SharedSecret) -> Self {
+        self.key_secret = Some(key_secret);
+        self
+    }
+
+    // Loads the certificate, key and client CAs into a server configuration.
+    //
+    // # Errors
+    //
+    // Returns an error if any of the files cannot be read or parsed.
+    pub fn load(&self) -> io::Result<RustlsConfig> {
+        Ok(RustlsConfig::from_config(Arc::new(self.server_config()?)))
+    }
+
+    // Builds the rustls configuration from the files.
+    fn server_config(&self) -> io::Result<ServerConfig> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_file)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| invalid(&self.cert_file, e))?;
+        let key = match &self.key_secret {
+            Some(secret) => PrivateKeyDer::from_pem_slice(secret.get().as_bytes()),
+            None => PrivateKeyDer::from_pem_file(&self.key_file),
+        }
+        .map_err(|e| invalid(&self.key_file, e))?;
+
+        let builder = ServerConfig::builder();
+        let builder = match &self.client_ca_file {
+            Some(ca_file) => {
+                let mut roots = RootCertStore::empty();
+                for cert in
+                    CertificateDer::pem_file_iter(ca_file).map_err(|e| invalid(ca_file, e))?
+                {
+                    let cert = cert.map_err(|e| invalid(ca_file, e))?;
+                    roots.add(cert).map_err(|e| invalid(ca_file, e))?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| invalid(ca_file, e))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| invalid(&self.cert_file, e))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    // Returns the modification times of all files and the version of the key
+    // secret, if they can be read.
+    fn modified(&self) -> Option<(Vec<SystemTime>, u64)> {
+        let mut times = vec![modified(&self.cert_file)?];
+        if self.key_secret.is_none() {
+            times.push(modified(&self.key_file)?);
+        }
+        if let Some(ca_file) = &self.client_ca_file {
+            times.push(modified(ca_file)?);
+        }
+        let key_version = self.key_secret.as_ref().map_or(0, SharedSecret::version);
+        Some((times, key_version))
+    }
+}
+
+// Reloads the certificate in the background whenever its files change.
+//
+// # Arguments
+//
+// * `config` - Server configuration the reloaded certificate is swapped into
+// * `files` - Certificate, key and client CA files to watch
+// * `interval` - How often the files are checked for changes
+pub fn spawn_reload(config: RustlsConfig, files: TlsFiles, interval: Duration) {
+    tokio::spawn(async move {
+        let mut loaded = files.modified();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let current = files.modified();
+            if current.is_none() || current == loaded {
+                continue;
+            }
+            debug!("TLS certificate files changed, reloading");
+
+            match files.server_config() {
+                Ok(server_config) => {
+                    config.reload_from_config(Arc::new(server_config));
+                    info!(
+                        "Reloaded TLS certificate from {}",
+                        files.cert_file.display()
+                    );
+                    loaded = current;
+                }
+                Err(e) => warn!(
+                    "Failed to reload TLS certificate, keeping the previous one: {}",
+                    e
+                ),
+            }
+        }
+    });
+}
+
+// Acceptor completing the TLS handshake and attaching the client identity.
+#[derive(Clone)]
+pub struct ClientIdentityAcceptor {
+    // Acceptor performing the handshake
+    inner: RustlsAcceptor,
+}
+
+impl ClientIdentityAcceptor {
+    // Creates an acceptor serving the given configuration.
+    pub fn new(config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for ClientIdentityAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientIdentity>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let common_name = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| common_name(cert));
+            if let Some(name) = &common_name {
+                debug!("Accepted client certificate for {}", name);
+            }
+
+            Ok((
+                stream,
+                Extension(ClientIdentity(common_name)).layer(service),
+            ))
+        })
+    }
+}
+
+// Returns the subject common name of a DER encoded certificate.
+fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = X509Certificate::from_der(cert.as_ref()).ok()?;
+    let name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+    Some(name.to_string())
+}
+
+// Returns the modification time of a file.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Builds the error returned for an unusable TLS file.
+fn invalid(path: &Path, error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), error),
+    )
+}
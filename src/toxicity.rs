@@ -0,0 +1,196 @@
+// Local profanity and toxicity pre-filter.
+//
+// Prompts and responses are matched against an embedded word list before PANW
+// is consulted. Obviously abusive traffic is blocked without spending scan
+// quota, and the filter still provides minimal protection while PANW is
+// unavailable or skipped in `local_only` degraded mode.
+//
+// # Word Lists
+//
+// Every term has a severity. Only matches at or above the configured minimum
+// severity trigger the configured action, so the embedded low-severity terms
+// can be ignored where mild profanity is acceptable. Tenants can add their own
+// terms, or replace the shared lists entirely, keyed by the tenant header.
+//
+// # Matching
+//
+// Text is lowercased, common character substitutions such as `@` for `a` and
+// `0` for `o` are undone, and the result is split into words. A term matches
+// when its words appear consecutively, so "class" never matches "ass".
+use crate::config::{ToxicTerm, ToxicityAction, ToxicityConfig, ToxicitySeverity};
+use crate::metrics;
+use std::collections::HashMap;
+use tracing::warn;
+
+// Embedded word list, one `<severity> <term>` per line.
+const EMBEDDED_TERMS: &str = include_str!("toxicity_terms.txt");
+
+// Compiled terms of a word list.
+#[derive(Default)]
+struct WordList {
+    // Single-word terms
+    words: HashMap<String, ToxicitySeverity>,
+
+    // Multi-word terms as their words
+    phrases: Vec<(Vec<String>, ToxicitySeverity)>,
+}
+
+impl WordList {
+    // Adds a term, keeping the higher severity of duplicate terms.
+    fn add(&mut self, term: &str, severity: ToxicitySeverity) {
+        let words = tokenize(term);
+        match words.len() {
+            0 => {}
+            1 => {
+                let entry = self.words.entry(words[0].clone()).or_insert(severity);
+                *entry = (*entry).max(severity);
+            }
+            _ => self.phrases.push((words, severity)),
+        }
+    }
+
+    // Adds configured terms.
+    fn extend(&mut self, terms: &[ToxicTerm]) {
+        for term in terms {
+            self.add(&term.term, term.severity);
+        }
+    }
+
+    // Returns the highest severity of the terms found in the words.
+    fn highest(&self, words: &[String]) -> Option<ToxicitySeverity> {
+        let single = words
+            .iter()
+            .filter_map(|word| self.words.get(word))
+            .copied();
+        let phrases = self
+            .phrases
+            .iter()
+            .filter(|(phrase, _)| words.windows(phrase.len()).any(|window| window == phrase))
+            .map(|(_, severity)| *severity);
+        single.chain(phrases).max()
+    }
+}
+
+// Matches content against the shared and tenant-specific word lists.
+pub struct ToxicityFilter {
+    // Embedded and configured terms shared by all tenants
+    shared: WordList,
+
+    // Terms of individual tenants
+    tenants: HashMap<String, (WordList, bool)>,
+
+    // Action taken when a term at or above `min_severity` matches
+    action: ToxicityAction,
+
+    // Lowest severity that triggers the action
+    min_severity: ToxicitySeverity,
+}
+
+impl ToxicityFilter {
+    // Compiles the embedded word list and the configured terms.
+    pub fn new(config: &ToxicityConfig) -> Self {
+        let mut shared = WordList::default();
+        for line in EMBEDDED_TERMS.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((severity, term)) = line.split_once(' ') {
+                let severity = severity.parse().expect("valid embedded toxicity severity");
+                shared.add(term, severity);
+            }
+        }
+        shared.extend(&config.terms);
+
+        let tenants = config
+            .tenants
+            .iter()
+            .map(|(tenant, tenant_config)| {
+                let mut list = WordList::default();
+                list.extend(&tenant_config.terms);
+                (tenant.clone(), (list, tenant_config.replace_defaults))
+            })
+            .collect();
+
+        Self {
+            shared,
+            tenants,
+            action: config.action,
+            min_severity: config.min_severity,
+        }
+    }
+
+    // Matches the content against the word lists of the tenant.
+    //
+    // # Arguments
+    //
+    // * `content` - Text of the prompt or response
+    // * `tenant` - Tenant of the request, if known
+    // * `is_prompt` - Whether the content is a prompt rather than a response
+    //
+    // # Returns
+    //
+    // Whether the content should be blocked
+    pub fn check(&self, content: &str, tenant: Option<&str>, is_prompt: bool) -> bool {
+        let words = tokenize(content);
+        let tenant_list = tenant.and_then(|tenant| self.tenants.get(tenant));
+
+        let shared = match tenant_list {
+            Some((_, true)) => None,
+            _ => self.shared.highest(&words),
+        };
+        let own = tenant_list.and_then(|(list, _)| list.highest(&words));
+        let Some(severity) = shared
+            .max(own)
+            .filter(|severity| *severity >= self.min_severity)
+        else {
+            return false;
+        };
+
+        let direction = if is_prompt { "prompt" } else { "response" };
+        let action = match self.action {
+            ToxicityAction::Flag => "flag",
+            ToxicityAction::Block => "block",
+        };
+        warn!(
+            "Toxicity pre-filter matched {} with {} severity, tenant={}, action={}",
+            direction,
+            severity.as_str(),
+            tenant.unwrap_or("none"),
+            action
+        );
+        metrics::increment_counter(
+            "panw_toxicity_matches_total",
+            "Prompts and responses matched by the local toxicity pre-filter",
+            &[
+                ("direction", direction),
+                ("severity", severity.as_str()),
+                ("action", action),
+            ],
+        );
+        self.action == ToxicityAction::Block
+    }
+}
+
+// Lowercases text, undoes common character substitutions and splits it into words.
+fn tokenize(text: &str) -> Vec<String> {
+    let normalized: String = text
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            '@' | '4' => 'a',
+            '3' => 'e',
+            '1' => 'i',
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' => 't',
+            c => c,
+        })
+        .collect();
+
+    normalized
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
@@ -0,0 +1,93 @@
+// Transaction IDs correlating client requests with PANW scans.
+//
+// Every request is assigned a transaction ID, which is sent as the `tr_id` of
+// the PANW scans performed for it and returned in the `X-Request-Id` and
+// `X-TR-Id` response headers. Clients that already trace their requests can
+// supply the ID themselves in either header, so the scan reports in the PANW
+// console can be found by the ID of the client's own logs.
+//
+// # Accepted IDs
+//
+// Supplied IDs must be 1 to 128 characters of letters, digits, `-`, `_`, `.`
+// and `:`. Requests with a missing or invalid ID are assigned a random UUID
+// instead.
+use crate::AppState;
+use axum::{
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderMap, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+// Headers carrying the transaction ID, in order of precedence.
+const TRANSACTION_ID_HEADERS: [&str; 2] = ["x-request-id", "x-tr-id"];
+
+// Longest accepted client-supplied transaction ID.
+const MAX_TRANSACTION_ID_LENGTH: usize = 128;
+
+// Transaction ID of a request, assigned by the `assign_transaction_id` middleware.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionId(pub Option<String>);
+
+impl FromRequestParts<AppState> for TransactionId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<TransactionId>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// Returns whether a client-supplied transaction ID is acceptable.
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_TRANSACTION_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// Returns the transaction ID supplied by the client, if any.
+fn supplied(headers: &HeaderMap) -> Option<String> {
+    let value = TRANSACTION_ID_HEADERS
+        .iter()
+        .find_map(|name| headers.get(*name))?;
+    match value.to_str() {
+        Ok(id) if is_valid(id.trim()) => Some(id.trim().to_string()),
+        _ => {
+            warn!("Ignoring invalid client-supplied transaction ID");
+            None
+        }
+    }
+}
+
+// Middleware assigning transaction IDs to requests and echoing them in responses.
+pub async fn assign_transaction_id(mut request: Request, next: Next) -> Response {
+    let id = supplied(request.headers()).unwrap_or_else(|| Uuid::new_v4().to_string());
+    debug!(
+        "Assigned transaction ID {} to {} {}",
+        id,
+        request.method(),
+        request.uri().path()
+    );
+    request
+        .extensions_mut()
+        .insert(TransactionId(Some(id.clone())));
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        for name in TRANSACTION_ID_HEADERS {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+    response
+}
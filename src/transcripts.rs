@@ -0,0 +1,677 @@
+// Tamper-evident conversation transcripts.
+//
+// When the proxy is the system of record for disputes over blocked content,
+// the stored conversation must be trustworthy. With transcripts enabled, every
+// assessed prompt and response is appended to the transcript file as a JSON
+// line holding the content, its verdict and the request it belongs to. When
+// several security providers scan in parallel, the verdict of each is recorded
+// as well.
+//
+// # Sessions
+//
+// Records are grouped into sessions, named by the configured session header
+// (`X-Session-Id` by default) so the turns of a conversation form one session.
+// Requests without the header form a session of their own, named by their
+// transaction ID. Session names follow the rules of transaction IDs.
+//
+// # Hash Chain
+//
+// The records of a session are numbered and chained: each record carries the
+// hash of the previous record of its session, and its own hash over its
+// contents including that link. `transcripts verify` recomputes every chain,
+// so a record that was altered, removed, inserted or reordered breaks its
+// session's chain:
+//
+// ```text
+// panw-api-ollama transcripts verify
+// panw-api-ollama transcripts verify <session>
+// ```
+//
+// Removing the last records of a session, or a whole session, leaves no gap
+// in the file itself. The verification therefore reports the head hash of
+// every session; keeping the head of a disputed session elsewhere, such as in
+// the ticket of the dispute, detects a later truncation too. After a restart
+// the chains continue from the heads found in the file.
+//
+// Without `TRANSCRIPTS_HMAC_KEY` the hash is a plain SHA-256, which only
+// detects accidental damage and careless edits: anyone able to write the file
+// can rewrite a whole session and recompute its chain. With the key set, the
+// hash is an HMAC-SHA256 under it, so rewriting a session takes the key as
+// well. The key must be set before the first record is written and kept
+// afterwards; records hashed under another key, or without one, fail the
+// verification.
+//
+// # Retention
+//
+// With a retention period configured, sessions whose last record is older
+// than the period are removed from the file at startup and then every hour,
+// or with `store prune-older-than <days>`. Sessions are pruned as a whole, so
+// the chains of the remaining sessions stay intact.
+//
+// The running proxy holds a lock on `<file>.lock` for as long as it appends to
+// the file. `store prune-older-than`, `store rekey` and `store restore` take
+// the same lock before rewriting the file and refuse to run while the proxy
+// holds it, since records appended during the rewrite would be lost and the
+// proxy would keep chaining onto removed sessions. A second proxy configured
+// with the same file fails to start for the same reason.
+//
+// # Encryption
+//
+// Transcripts hold the assessed content in the clear unless state file
+// encryption is configured, in which case every record is sealed on its own.
+// Records that cannot be written are logged and counted in
+// `panw_transcript_write_failures_total`; the request itself is not affected.
+use crate::bedrock_guardrails::hmac_sha256;
+use crate::client_auth::to_hex;
+use crate::config::TranscriptsConfig;
+use crate::transaction;
+use crate::types::ProviderVerdict;
+use crate::{encryption, metrics, store, AppState};
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+// How often sessions past their retention period are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+// Link of the first record of a session.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Record of an assessed prompt or response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Record {
+    // Session the record belongs to
+    session: String,
+
+    // Position of the record in its session, starting at 1
+    seq: u64,
+
+    // When the content was assessed
+    timestamp: String,
+
+    // `prompt` or `response`
+    stage: String,
+
+    // Model the content was sent to or generated by
+    model: String,
+
+    // Client identity of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<String>,
+
+    // Tenant of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+
+    // Transaction ID of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transaction_id: Option<String>,
+
+    // Content as assessed
+    content: String,
+
+    // `allow`, `mask`, `block` or `error`
+    verdict: String,
+
+    // Reasons of a block or mask
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    detections: Vec<String>,
+
+    // Scan report of the verdict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report_id: Option<String>,
+
+    // Verdicts of the providers when several scanned the content
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    providers: Vec<ProviderVerdict>,
+
+    // Hash of the previous record of the session
+    prev_hash: String,
+
+    // Hash of this record, over all other fields
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    hash: String,
+}
+
+impl Record {
+    // Computes the hash of the record over all fields but the hash itself,
+    // keyed with the HMAC key unless it is empty.
+    fn digest(&self, hmac_key: &str) -> String {
+        let mut unhashed = self.clone();
+        unhashed.hash.clear();
+        let json = serde_json::to_string(&unhashed).unwrap_or_default();
+        if hmac_key.is_empty() {
+            to_hex(&Sha256::digest(json.as_bytes()))
+        } else {
+            to_hex(&hmac_sha256(hmac_key.as_bytes(), &json))
+        }
+    }
+}
+
+// Assessment to record in a transcript.
+pub struct Entry<'a> {
+    // Session named by the request, or its transaction ID
+    pub session: &'a str,
+
+    // Whether the content is a prompt rather than a response
+    pub is_prompt: bool,
+
+    // Model, client, tenant and transaction of the request
+    pub model: &'a str,
+    pub client: Option<&'a str>,
+    pub tenant: Option<&'a str>,
+    pub transaction_id: Option<&'a str>,
+
+    // Content as assessed
+    pub content: &'a str,
+
+    // `allow`, `mask`, `block` or `error`, with the reasons of a block or mask
+    pub verdict: &'static str,
+    pub detections: Vec<&'static str>,
+
+    // Scan report of the verdict
+    pub report_id: Option<&'a str>,
+
+    // Verdicts of the providers when several scanned the content
+    pub providers: &'a [ProviderVerdict],
+}
+
+// Appends assessed content to the transcript file, chained by session.
+//
+// Cloning is cheap and all clones share the same chains.
+#[derive(Clone)]
+pub struct Transcripts {
+    // Sequence number and hash of the last record of every session
+    heads: Arc<Mutex<HashMap<String, (u64, String)>>>,
+
+    // File the records are appended to
+    file: String,
+
+    // Lock on the file, held for as long as the proxy appends to it
+    _lock: Arc<File>,
+
+    // Key of the record hashes, or empty for plain hashes
+    hmac_key: String,
+
+    // Request header naming the session
+    session_header: String,
+}
+
+impl Transcripts {
+    // Opens the transcript file, continuing the chains of the sessions in it.
+    //
+    // # Errors
+    //
+    // Returns an error if the file is locked by another process, such as
+    // another proxy or a store command rewriting it.
+    pub fn new(config: &TranscriptsConfig) -> Result<Self, String> {
+        let lock = lock_file(&config.file)
+            .map_err(|e| format!("transcript file {}: {}", config.file, e))?;
+        let mut heads = HashMap::new();
+        if let Ok(contents) = fs::read_to_string(&config.file) {
+            for (index, line) in contents.lines().enumerate() {
+                match parse_line(line) {
+                    Ok(record) => {
+                        heads.insert(record.session, (record.seq, record.hash));
+                    }
+                    Err(e) => warn!(
+                        "Skipping invalid record on line {} of transcript file {}: {}",
+                        index + 1,
+                        config.file,
+                        e
+                    ),
+                }
+            }
+            info!(
+                "Continuing {} transcript sessions from {}",
+                heads.len(),
+                config.file
+            );
+        }
+        Ok(Self {
+            heads: Arc::new(Mutex::new(heads)),
+            file: config.file.clone(),
+            _lock: Arc::new(lock),
+            hmac_key: config.hmac_key.clone(),
+            session_header: config.session_header.clone(),
+        })
+    }
+
+    // Appends an assessment to the chain of its session.
+    pub fn record(&self, entry: Entry<'_>) {
+        let mut heads = self.heads.lock().unwrap_or_else(|e| e.into_inner());
+        let (seq, prev_hash) = heads
+            .get(entry.session)
+            .map(|(seq, hash)| (seq + 1, hash.clone()))
+            .unwrap_or_else(|| (1, GENESIS_HASH.to_string()));
+        let mut record = Record {
+            session: entry.session.to_string(),
+            seq,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            stage: if entry.is_prompt {
+                "prompt"
+            } else {
+                "response"
+            }
+            .to_string(),
+            model: entry.model.to_string(),
+            client: entry.client.map(str::to_string),
+            tenant: entry.tenant.map(str::to_string),
+            transaction_id: entry.transaction_id.map(str::to_string),
+            content: entry.content.to_string(),
+            verdict: entry.verdict.to_string(),
+            detections: entry.detections.iter().map(|d| d.to_string()).collect(),
+            report_id: entry.report_id.map(str::to_string),
+            providers: entry.providers.to_vec(),
+            prev_hash,
+            hash: String::new(),
+        };
+        record.hash = record.digest(&self.hmac_key);
+
+        let result = serde_json::to_string(&record)
+            .map_err(|e| e.to_string())
+            .and_then(|line| encryption::seal(&line))
+            .and_then(|line| {
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.file)
+                    .and_then(|mut file| writeln!(file, "{}", line))
+                    .map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => {
+                heads.insert(record.session, (record.seq, record.hash));
+            }
+            Err(e) => {
+                error!(
+                    "Failed to append record {} of session {} to transcript file {}: {}",
+                    seq, entry.session, self.file, e
+                );
+                metrics::increment_counter(
+                    "panw_transcript_write_failures_total",
+                    "Transcript records that could not be written",
+                    &[],
+                );
+            }
+        }
+    }
+
+    // Removes the sessions whose last record is older than the retention
+    // period, so that new records of a pruned session start a new chain.
+    //
+    // Runs under the lock of the chains, so no record is appended meanwhile.
+    fn prune(&self, retention_days: u64) {
+        let mut heads = self.heads.lock().unwrap_or_else(|e| e.into_inner());
+        match remove_expired(&self.file, retention_days) {
+            Ok((records, sessions)) => {
+                if !sessions.is_empty() {
+                    info!(
+                        "Pruned {} transcript sessions with {} records older than {} days from {}",
+                        sessions.len(),
+                        records,
+                        retention_days,
+                        self.file
+                    );
+                }
+                for session in sessions {
+                    heads.remove(&session);
+                }
+            }
+            Err(e) => error!("Failed to prune transcript file {}: {}", self.file, e),
+        }
+    }
+}
+
+// Starts the background task pruning sessions past their retention period at
+// startup and then every hour.
+//
+// Nothing is pruned when no retention period is configured.
+pub fn spawn_prune(transcripts: Transcripts, retention_days: u64) {
+    if retention_days == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            transcripts.prune(retention_days);
+        }
+    });
+}
+
+// Session named by a request, when transcripts are enabled.
+#[derive(Debug, Clone, Default)]
+pub struct Session(pub Option<String>);
+
+impl FromRequestParts<AppState> for Session {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(transcripts) = &state.transcripts else {
+            return Ok(Session(None));
+        };
+        let session = parts
+            .headers
+            .get(transcripts.session_header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|session| {
+                let valid = transaction::is_valid(session);
+                if !valid {
+                    warn!("Ignoring invalid transcript session name");
+                }
+                valid
+            })
+            .map(str::to_string);
+        Ok(Session(session))
+    }
+}
+
+// Reads a record from a line of the transcript file.
+fn parse_line(line: &str) -> Result<Record, String> {
+    serde_json::from_str(&encryption::open(line)?).map_err(|e| e.to_string())
+}
+
+// Takes the lock guarding the transcript file against concurrent writers.
+//
+// The lock is held until the returned file is closed, or the process exits.
+//
+// # Errors
+//
+// Returns an error if another process holds the lock or the lock file cannot
+// be created.
+pub fn lock_file(path: &str) -> Result<File, String> {
+    let lock_path = format!("{}.lock", path);
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| format!("cannot open lock file {}: {}", lock_path, e))?;
+    match lock.try_lock() {
+        Ok(()) => Ok(lock),
+        Err(fs::TryLockError::WouldBlock) => {
+            Err("in use by a running proxy; stop it first".to_string())
+        }
+        Err(fs::TryLockError::Error(e)) => Err(format!("cannot lock {}: {}", lock_path, e)),
+    }
+}
+
+// Removes the sessions whose last record is older than the given number of
+// days from the transcript file.
+//
+// The file is rewritten through a temporary file, so it is never left half
+// written. Unreadable records are kept.
+//
+// # Returns
+//
+// The number of records removed and the sessions they belonged to
+//
+// # Errors
+//
+// Returns an error if the file is in use by a running proxy, or cannot be
+// read or rewritten.
+pub fn prune_file(path: &str, days: u64) -> Result<(usize, Vec<String>), String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok((0, Vec::new()));
+    }
+    let _lock = lock_file(path)?;
+    remove_expired(path, days)
+}
+
+// Removes the expired sessions from the transcript file, with the caller
+// holding its lock.
+fn remove_expired(path: &str, days: u64) -> Result<(usize, Vec<String>), String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let cutoff = Utc::now() - chrono::Duration::days(i64::try_from(days).unwrap_or(i64::MAX));
+
+    let mut last_seen: HashMap<String, DateTime<Utc>> = HashMap::new();
+    let records: Vec<_> = contents
+        .lines()
+        .map(|line| (line, parse_line(line).ok()))
+        .collect();
+    for record in records.iter().filter_map(|(_, record)| record.as_ref()) {
+        let Ok(timestamp) = DateTime::parse_from_rfc3339(&record.timestamp) else {
+            continue;
+        };
+        let timestamp = timestamp.with_timezone(&Utc);
+        last_seen
+            .entry(record.session.clone())
+            .and_modify(|last| *last = (*last).max(timestamp))
+            .or_insert(timestamp);
+    }
+    let expired: Vec<String> = last_seen
+        .into_iter()
+        .filter(|(_, last)| *last < cutoff)
+        .map(|(session, _)| session)
+        .collect();
+    if expired.is_empty() {
+        return Ok((0, expired));
+    }
+
+    let mut kept = String::new();
+    let mut removed = 0;
+    for (line, record) in &records {
+        if record
+            .as_ref()
+            .is_some_and(|record| expired.contains(&record.session))
+        {
+            removed += 1;
+            continue;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    store::write_atomically(path, &kept).map_err(|e| e.to_string())?;
+    Ok((removed, expired))
+}
+
+// Rewrites every record of the transcript file with the active encryption key.
+//
+// # Errors
+//
+// Returns an error if the file is in use by a running proxy, a record cannot
+// be decrypted or the file cannot be rewritten.
+pub fn rekey_file(path: &str) -> Result<(), String> {
+    let _lock = lock_file(path)?;
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    store::write_atomically(path, &reseal_contents(&contents)?).map_err(|e| e.to_string())
+}
+
+// Encrypts every record of transcript file contents with the active key,
+// accepting unencrypted records.
+//
+// # Errors
+//
+// Returns an error naming the first record that cannot be decrypted.
+pub fn reseal_contents(contents: &str) -> Result<String, String> {
+    let mut rewritten = String::new();
+    for (index, line) in contents.lines().enumerate() {
+        let sealed = encryption::reseal(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        rewritten.push_str(&sealed);
+        rewritten.push('\n');
+    }
+    Ok(rewritten)
+}
+
+// Checks that the transcript file exists and every record in it is readable.
+//
+// # Returns
+//
+// The number of records, or `None` if there is no file
+//
+// # Errors
+//
+// Returns an error if the file cannot be read or holds an unreadable record.
+pub fn verify_state_file(path: &str) -> Result<Option<usize>, String> {
+    if path.is_empty() || !std::path::Path::new(path).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    verify_contents(&contents).map(Some)
+}
+
+// Checks that every record of transcript file contents is readable.
+//
+// Hash chains are checked by `transcripts verify`.
+//
+// # Returns
+//
+// The number of records
+//
+// # Errors
+//
+// Returns an error naming the first unreadable record.
+pub fn verify_contents(contents: &str) -> Result<usize, String> {
+    for (index, line) in contents.lines().enumerate() {
+        parse_line(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+    }
+    Ok(contents.lines().count())
+}
+
+// Returns the records of transcript file contents, decrypted, one per line.
+//
+// # Errors
+//
+// Returns an error naming the first unreadable record.
+pub fn export_contents(contents: &str) -> Result<String, String> {
+    let mut exported = String::new();
+    for (index, line) in contents.lines().enumerate() {
+        let record = parse_line(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        exported.push_str(&serde_json::to_string(&record).map_err(|e| e.to_string())?);
+        exported.push('\n');
+    }
+    Ok(exported)
+}
+
+// State of a session's chain during verification.
+#[derive(Default)]
+struct Chain {
+    // Number of records
+    records: u64,
+
+    // Hash of the last record
+    head: String,
+
+    // First break found in the chain
+    error: Option<String>,
+}
+
+// Verifies the hash chains of the transcript file.
+//
+// # Arguments
+//
+// * `config` - Transcript settings naming the file and the HMAC key
+// * `args` - Arguments following `transcripts`
+//
+// # Errors
+//
+// Returns an error if the subcommand is unknown, the file cannot be read, or
+// a chain is broken.
+pub fn run_cli(
+    config: &TranscriptsConfig,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let only = match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["verify"] => None,
+        ["verify", session] => Some(session.to_string()),
+        _ => return Err("usage: transcripts verify [<session>]".into()),
+    };
+
+    let contents = fs::read_to_string(&config.file)
+        .map_err(|e| format!("cannot read transcript file {}: {}", config.file, e))?;
+    let mut chains: BTreeMap<String, Chain> = BTreeMap::new();
+    let mut unreadable = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let record = match parse_line(line) {
+            Ok(record) => record,
+            Err(e) => {
+                unreadable.push(format!("line {}: {}", line_number, e));
+                continue;
+            }
+        };
+        if only.as_ref().is_some_and(|only| *only != record.session) {
+            continue;
+        }
+
+        let chain = chains.entry(record.session.clone()).or_default();
+        chain.records += 1;
+        if chain.error.is_none() {
+            let (expected_seq, expected_prev) = if chain.head.is_empty() {
+                (1, GENESIS_HASH)
+            } else {
+                (chain.records, chain.head.as_str())
+            };
+            chain.error = if record.digest(&config.hmac_key) != record.hash {
+                Some(format!(
+                    "record {} on line {} was altered",
+                    record.seq, line_number
+                ))
+            } else if record.seq != expected_seq || record.prev_hash != expected_prev {
+                Some(format!(
+                    "record {} on line {} does not follow record {}; records were removed, \
+                     inserted or reordered",
+                    record.seq,
+                    line_number,
+                    expected_seq - 1
+                ))
+            } else {
+                None
+            };
+        }
+        chain.head = record.hash;
+    }
+
+    if let Some(only) = &only {
+        if !chains.contains_key(only) {
+            return Err(format!("no records of session {} in {}", only, config.file).into());
+        }
+    }
+    for (session, chain) in &chains {
+        match &chain.error {
+            None => println!(
+                "{}: ok, {} records, head {}",
+                session, chain.records, chain.head
+            ),
+            Some(error) => println!("{}: broken: {}", session, error),
+        }
+    }
+    for line in &unreadable {
+        println!("unreadable {}", line);
+    }
+
+    let broken = chains
+        .values()
+        .filter(|chain| chain.error.is_some())
+        .count();
+    if broken > 0 || !unreadable.is_empty() {
+        return Err(format!(
+            "{} broken sessions and {} unreadable records in {}",
+            broken,
+            unreadable.len(),
+            config.file
+        )
+        .into());
+    }
+    Ok(())
+}
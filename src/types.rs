@@ -58,6 +58,10 @@ pub struct GenerateRequest {
     /// Optional model-specific parameters
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<Value>,
+
+    /// Optional base64-encoded images for vision models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
 }
 
 /// Response from an Ollama text generation request.
@@ -83,6 +87,215 @@ pub struct GenerateResponse {
     pub done: bool,
 }
 
+/// Request to generate responses for a batch of prompts.
+///
+/// Every prompt is processed like a non-streaming generate request with the
+/// same model, system message and options.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchGenerateRequest {
+    /// Name of the Ollama model to use for generation
+    pub model: String,
+
+    /// The prompts to generate responses for
+    pub prompts: Vec<String>,
+
+    /// Optional system message to guide model behavior
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// Optional model-specific parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Value>,
+}
+
+/// Outcome of a single prompt in a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemStatus {
+    /// The prompt and the response passed security checks
+    Allowed,
+
+    /// The prompt or the response was blocked
+    Blocked,
+
+    /// The prompt could not be processed
+    Failed,
+}
+
+/// Security verdict of blocked content in batch and job results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanVerdict {
+    /// Whether the prompt or the response was blocked ("prompt" or "response")
+    pub stage: String,
+
+    /// Security category assigned to the content
+    pub category: String,
+
+    /// Action taken by the security policy
+    pub action: String,
+
+    /// Security message describing the violation
+    pub message: String,
+}
+
+/// Result of a single prompt in a batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchGenerateResult {
+    /// Position of the prompt in the request
+    pub index: usize,
+
+    /// Outcome of the prompt
+    pub status: BatchItemStatus,
+
+    /// The generated response, for allowed items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+
+    /// The security verdict, for blocked items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<ScanVerdict>,
+
+    /// The error message, for failed items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of a batch generation request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchGenerateResponse {
+    /// Name of the model used for generation
+    pub model: String,
+
+    /// Timestamp when the response was created
+    pub created_at: String,
+
+    /// One result per prompt, in request order
+    pub results: Vec<BatchGenerateResult>,
+}
+
+/// Lifecycle state of an asynchronous generation job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// The job is waiting to be processed
+    Queued,
+
+    /// The job is being scanned or generated
+    Running,
+
+    /// The prompt and the response passed security checks
+    Completed,
+
+    /// The prompt or the response was blocked
+    Blocked,
+
+    /// The job could not be processed
+    Failed,
+}
+
+impl JobStatus {
+    /// Returns whether the job has finished processing.
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+/// State and result of an asynchronous generation job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResponse {
+    /// Identifier used to fetch the job
+    pub id: String,
+
+    /// Current state of the job
+    pub status: JobStatus,
+
+    /// When the job was submitted
+    pub created_at: DateTime<Utc>,
+
+    /// When the job finished processing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+
+    /// The generate response, for completed jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+
+    /// The security verdict, for blocked jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verdict: Option<ScanVerdict>,
+
+    /// The error message, for failed jobs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A saved prompt template.
+///
+/// Templates contain `{{variable}}` placeholders that are substituted with
+/// caller-supplied values when the template is run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    /// Unique name of the template
+    pub name: String,
+
+    /// Optional description shown to users picking a template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Optional default model used when running the template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Optional system message to guide model behavior
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+
+    /// The prompt text with `{{variable}}` placeholders
+    pub template: String,
+
+    /// Names of the variables used in the template
+    #[serde(default)]
+    pub variables: Vec<String>,
+
+    /// When the template was last saved
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Request to save a prompt template.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SaveTemplateRequest {
+    /// Optional description shown to users picking a template
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Optional default model used when running the template
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Optional system message to guide model behavior
+    #[serde(default)]
+    pub system: Option<String>,
+
+    /// The prompt text with `{{variable}}` placeholders
+    pub template: String,
+}
+
+/// Request to run a saved prompt template.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunTemplateRequest {
+    /// Model overriding the template's default model
+    #[serde(default)]
+    pub model: Option<String>,
+
+    /// Values substituted for the template's variables
+    #[serde(default)]
+    pub variables: std::collections::HashMap<String, String>,
+
+    /// Optional model-specific parameters
+    #[serde(default)]
+    pub options: Option<Value>,
+}
+
 /// Request parameters for chat-based interactions with Ollama models.
 ///
 /// This struct encapsulates all parameters needed for a multi-turn conversation
@@ -119,6 +332,10 @@ pub struct Message {
 
     /// The actual text content of the message
     pub content: String,
+
+    /// Optional base64-encoded images for vision models
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
 }
 
 /// Response from an Ollama chat request.
@@ -139,6 +356,42 @@ pub struct ChatResponse {
     pub done: bool,
 }
 
+/// Request to summarize a conversation or document.
+///
+/// Exactly one of `messages` and `text` is expected; when both are given the
+/// conversation is summarized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizeRequest {
+    /// Optional model overriding the configured summarization model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Conversation to summarize
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<Message>>,
+
+    /// Document to summarize
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    /// Optional model-specific parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Value>,
+}
+
+/// Response containing the summary of a conversation or document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummarizeResponse {
+    /// Name of the model that produced the summary
+    pub model: String,
+
+    /// Timestamp when the response was created
+    pub created_at: String,
+
+    /// The summary, or the security message when the content was blocked
+    pub summary: String,
+}
+
 /// Request parameters for generating text embeddings with Ollama models.
 ///
 /// Text embeddings are vector representations of text that capture semantic meaning,
@@ -219,6 +472,159 @@ pub struct VersionResponse {
     pub version: String,
 }
 
+//------------------------------------------------------------------------------
+// OpenAI-Compatible API Types
+//------------------------------------------------------------------------------
+
+/// A value the OpenAI API accepts either as a single string or as a list of strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StringOrList {
+    /// A single string
+    One(String),
+
+    /// A list of strings
+    Many(Vec<String>),
+}
+
+impl StringOrList {
+    /// Returns the contained strings as a list.
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            StringOrList::One(value) => vec![value],
+            StringOrList::Many(values) => values,
+        }
+    }
+}
+
+/// Request parameters for the legacy OpenAI completions API.
+///
+/// Only the parameters that have an Ollama equivalent are supported; all other
+/// fields sent by clients are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionRequest {
+    /// Name of the Ollama model to use for generation
+    pub model: String,
+
+    /// The prompt to complete; only a single prompt per request is supported
+    pub prompt: StringOrList,
+
+    /// Optional maximum number of tokens to generate
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+
+    /// Optional sampling temperature
+    #[serde(default)]
+    pub temperature: Option<f32>,
+
+    /// Optional nucleus sampling probability
+    #[serde(default)]
+    pub top_p: Option<f32>,
+
+    /// Optional sequences at which generation stops
+    #[serde(default)]
+    pub stop: Option<StringOrList>,
+
+    /// Optional seed for reproducible sampling
+    #[serde(default)]
+    pub seed: Option<i64>,
+
+    /// Optional flag to stream the completion as server-sent events
+    #[serde(default)]
+    pub stream: Option<bool>,
+}
+
+/// A single completion choice in an OpenAI completions response.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionChoice {
+    /// The generated text
+    pub text: String,
+
+    /// Position of the choice in the response
+    pub index: u32,
+
+    /// Log probabilities, which are never reported
+    pub logprobs: Option<Value>,
+
+    /// Why generation stopped ("stop" or "length"), or None while streaming
+    pub finish_reason: Option<String>,
+}
+
+/// Token usage reported in OpenAI responses.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompletionUsage {
+    /// Number of tokens in the prompt
+    pub prompt_tokens: u64,
+
+    /// Number of generated tokens
+    pub completion_tokens: u64,
+
+    /// Sum of prompt and completion tokens
+    pub total_tokens: u64,
+}
+
+/// Response, or streamed chunk, of the OpenAI completions API.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionResponse {
+    /// Identifier of the completion
+    pub id: String,
+
+    /// Object type, always "text_completion"
+    pub object: &'static str,
+
+    /// Unix timestamp when the completion was created
+    pub created: i64,
+
+    /// Name of the model that generated the completion
+    pub model: String,
+
+    /// The generated completions
+    pub choices: Vec<CompletionChoice>,
+
+    /// Token usage, reported once generation is complete
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<CompletionUsage>,
+}
+
+/// Request parameters for the OpenAI embeddings API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAiEmbeddingsRequest {
+    /// Name of the Ollama embedding model to use
+    pub model: String,
+
+    /// The text, or list of texts, to generate embeddings for
+    pub input: StringOrList,
+}
+
+/// A single embedding in an OpenAI embeddings response.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenAiEmbedding {
+    /// Object type, always "embedding"
+    pub object: &'static str,
+
+    /// Position of the input this embedding belongs to
+    pub index: usize,
+
+    /// Vector of floating-point values representing the text embedding
+    pub embedding: Vec<f32>,
+}
+
+/// Response of the OpenAI embeddings API.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenAiEmbeddingsResponse {
+    /// Object type, always "list"
+    pub object: &'static str,
+
+    /// One embedding per input, in input order
+    pub data: Vec<OpenAiEmbedding>,
+
+    /// Name of the model that generated the embeddings
+    pub model: String,
+
+    /// Token usage, which Ollama does not report for embeddings
+    pub usage: CompletionUsage,
+}
+
 //------------------------------------------------------------------------------
 // PANW Security Types
 //------------------------------------------------------------------------------
@@ -305,6 +711,27 @@ pub struct ScanResponse {
     /// Optional timestamp when assessment was completed
     #[serde(default)]
     pub completed_at: Option<DateTime<Utc>>,
+
+    /// Verdicts of the individual providers when several scanned the content
+    #[serde(skip)]
+    pub provider_verdicts: Vec<ProviderVerdict>,
+}
+
+/// Verdict of one of several providers scanning the same content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderVerdict {
+    /// Name of the provider
+    pub provider: String,
+
+    /// Action the provider decided on, `allow` or `block`
+    pub action: String,
+
+    /// Category the provider assigned
+    pub category: String,
+
+    /// Report of the provider's scan
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub report_id: String,
 }
 
 impl ScanResponse {
@@ -334,10 +761,53 @@ impl ScanResponse {
             response_detection_details: ResponseDetectionDetails::default(),
             created_at: None,
             completed_at: None,
+            provider_verdicts: Vec::new(),
         }
     }
 }
 
+/// Scan submitted to the PANW asynchronous scan API.
+///
+/// The asynchronous API accepts a batch of scan requests and returns a scan ID
+/// under which their results are polled.
+#[derive(Debug, Clone, Serialize)]
+pub struct AsyncScanObject {
+    /// Identifier of the scan within the submitted batch
+    pub req_id: u32,
+
+    /// The scan request itself
+    pub scan_req: ScanRequest,
+}
+
+/// Acknowledgement of a batch submitted to the PANW asynchronous scan API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AsyncScanResponse {
+    /// UUID under which the results of the batch are polled
+    pub scan_id: uuid::Uuid,
+
+    /// Unique identifier for the assessment report
+    #[serde(default)]
+    pub report_id: String,
+}
+
+/// Result of a scan submitted to the PANW asynchronous scan API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanIdResult {
+    /// Identifier of the scan within its batch
+    #[serde(default)]
+    pub req_id: u32,
+
+    /// Status of the scan ("pending" or "complete")
+    pub status: String,
+
+    /// UUID of the batch the scan was submitted in
+    pub scan_id: uuid::Uuid,
+
+    /// Scan results, present once the scan is complete
+    #[serde(default)]
+    pub result: Option<ScanResponse>,
+}
+
 /// AI security profile configuration for PANW security scans.
 ///
 /// Specifies which security profile should be used when evaluating content.
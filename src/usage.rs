@@ -0,0 +1,253 @@
+// Per-client usage accounting and privacy-preserving usage reports.
+//
+// Requests, PANW scans and security violations are counted per client identity,
+// as established by an API key, JWT or client certificate. Requests without an
+// identity are not counted. Counts are kept in memory and reset on restart.
+//
+// # Self-Service
+//
+// Clients can read their own counts and the reasons of their most recent
+// violations, so end users can find out why a request was blocked without
+// contacting an admin. Violation records never contain the blocked content,
+// scan report IDs or policy details.
+//
+// # Privacy
+//
+// Usage reports are exported through the admin API. To allow sharing them more
+// broadly without exposing the behaviour of individual clients, two protections
+// can be configured:
+//
+// - A minimum count (k-anonymity threshold): clients with fewer requests than
+//   the threshold are left out of the report and only counted as suppressed.
+// - Laplace noise: every count is perturbed with noise calibrated to the
+//   configured epsilon, so a single request does not measurably change the
+//   report. Smaller epsilons add more noise.
+//
+// The threshold is applied to the noisy request count, so whether a client
+// appears in the report does not reveal its exact count either.
+use crate::client_auth::ClientIdentity;
+use crate::config::UsageConfig;
+use crate::security::Assessment;
+use crate::AppState;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+// Number of recent violations kept per client.
+const RECENT_VIOLATIONS: usize = 20;
+
+// Usage counts of a single client.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientUsage {
+    // Identity of the client
+    pub client: String,
+    // Requests made by the client
+    pub requests: u64,
+    // Prompts and responses scanned by PANW on behalf of the client
+    pub scans: u64,
+    // Prompts and responses blocked by a security policy
+    pub violations: u64,
+}
+
+// Sanitized record of a blocked prompt or response.
+#[derive(Debug, Clone, Serialize)]
+pub struct ViolationRecord {
+    // When the content was blocked
+    pub timestamp: DateTime<Utc>,
+    // Whether a prompt or a response was blocked
+    pub direction: &'static str,
+    // Security category assigned to the content
+    pub category: String,
+    // Findings that led to the block
+    pub reasons: Vec<&'static str>,
+}
+
+// Counts and recent violations of a single client.
+#[derive(Default)]
+struct ClientRecord {
+    // Usage counts
+    usage: ClientUsage,
+    // Most recent violations, oldest first
+    recent: VecDeque<ViolationRecord>,
+}
+
+// Usage report, with the configured privacy protections applied unless exact.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    // Usage of the clients included in the report, ordered by client
+    pub clients: Vec<ClientUsage>,
+    // Number of clients left out for falling below the minimum count
+    pub suppressed_clients: usize,
+    // Minimum request count applied, 0 when no threshold was applied
+    pub min_count: u64,
+    // Epsilon of the noise added to the counts, 0 when no noise was added
+    pub noise_epsilon: f64,
+}
+
+// Counts usage per client and builds usage reports.
+//
+// Cloning the ledger is cheap and all clones share the same counts.
+#[derive(Clone)]
+pub struct UsageLedger {
+    // Privacy protections applied to reports
+    config: UsageConfig,
+
+    // Usage counts by client identity
+    clients: Arc<Mutex<HashMap<String, ClientRecord>>>,
+}
+
+impl UsageLedger {
+    // Creates an empty ledger.
+    pub fn new(config: &UsageConfig) -> Self {
+        Self {
+            config: config.clone(),
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Counts a request made by the client.
+    pub fn record_request(&self, client: &str) {
+        self.update(client, |record| record.usage.requests += 1);
+    }
+
+    // Counts a PANW scan performed on behalf of the client.
+    pub fn record_scan(&self, client: &str) {
+        self.update(client, |record| record.usage.scans += 1);
+    }
+
+    // Counts a security violation of the client and keeps its sanitized record.
+    //
+    // # Arguments
+    //
+    // * `client` - Identity of the client
+    // * `assessment` - Assessment that blocked the content
+    // * `is_prompt` - Whether a prompt rather than a response was blocked
+    pub fn record_violation(&self, client: &str, assessment: &Assessment, is_prompt: bool) {
+        let violation = ViolationRecord {
+            timestamp: Utc::now(),
+            direction: if is_prompt { "prompt" } else { "response" },
+            category: assessment.category.clone(),
+            reasons: assessment.reasons(),
+        };
+        self.update(client, |record| {
+            record.usage.violations += 1;
+            if record.recent.len() == RECENT_VIOLATIONS {
+                record.recent.pop_front();
+            }
+            record.recent.push_back(violation);
+        });
+    }
+
+    // Returns the exact usage counts of the client.
+    pub fn usage_of(&self, client: &str) -> ClientUsage {
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients
+            .get(client)
+            .map(|record| record.usage.clone())
+            .unwrap_or_else(|| ClientUsage {
+                client: client.to_string(),
+                ..ClientUsage::default()
+            })
+    }
+
+    // Returns the most recent violations of the client, newest first.
+    pub fn violations_of(&self, client: &str) -> Vec<ViolationRecord> {
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        clients
+            .get(client)
+            .map(|record| record.recent.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // Applies an update to the record of the client.
+    fn update(&self, client: &str, update: impl FnOnce(&mut ClientRecord)) {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        let record = clients
+            .entry(client.to_string())
+            .or_insert_with(|| ClientRecord {
+                usage: ClientUsage {
+                    client: client.to_string(),
+                    ..ClientUsage::default()
+                },
+                ..ClientRecord::default()
+            });
+        update(record);
+    }
+
+    // Builds a usage report.
+    //
+    // # Arguments
+    //
+    // * `exact` - Whether to report exact counts, skipping the privacy protections
+    pub fn report(&self, exact: bool) -> UsageReport {
+        let mut usage: Vec<ClientUsage> = self
+            .clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|record| record.usage.clone())
+            .collect();
+        usage.sort_by(|a, b| a.client.cmp(&b.client));
+
+        if exact {
+            return UsageReport {
+                clients: usage,
+                suppressed_clients: 0,
+                min_count: 0,
+                noise_epsilon: 0.0,
+            };
+        }
+
+        let epsilon = self.config.report_noise_epsilon;
+        let mut rng = rand::thread_rng();
+        let mut noisy = |count: u64| {
+            if epsilon > 0.0 {
+                (count as f64 + laplace(&mut rng, 1.0 / epsilon))
+                    .round()
+                    .max(0.0) as u64
+            } else {
+                count
+            }
+        };
+
+        let total = usage.len();
+        let clients: Vec<ClientUsage> = usage
+            .into_iter()
+            .map(|usage| ClientUsage {
+                requests: noisy(usage.requests),
+                scans: noisy(usage.scans),
+                violations: noisy(usage.violations),
+                client: usage.client,
+            })
+            .filter(|usage| usage.requests >= self.config.report_min_count)
+            .collect();
+
+        UsageReport {
+            suppressed_clients: total - clients.len(),
+            clients,
+            min_count: self.config.report_min_count,
+            noise_epsilon: epsilon,
+        }
+    }
+}
+
+// Middleware counting requests of identified clients.
+pub async fn record_usage(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(ClientIdentity(Some(client))) = request.extensions().get::<ClientIdentity>() {
+        state.usage.record_request(client);
+    }
+    next.run(request).await
+}
+
+// Draws a sample from a Laplace distribution centred on zero.
+fn laplace(rng: &mut impl Rng, scale: f64) -> f64 {
+    let u: f64 = rng.gen_range(-0.5..0.5);
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
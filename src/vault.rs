@@ -0,0 +1,317 @@
+// Secret resolution from HashiCorp Vault.
+//
+// Secrets need not be configured in plain text: any setting that accepts a
+// secret can instead hold a reference of the form `vault:<path>#<field>`,
+// naming a field of a secret in the configured KV version 2 engine. The PANW
+// API key, the TLS private key (`tls_key_file`) and the state file encryption
+// keys accept references.
+//
+// # Authentication
+//
+// The proxy authenticates with a configured token or, for deployments that
+// must not hold long-lived tokens, with an AppRole role ID and secret ID. An
+// AppRole token is obtained on first use and obtained again when Vault rejects
+// it, so expired tokens are replaced without a restart.
+//
+// # Refresh
+//
+// Referenced secrets are read again on the configured interval, and changed
+// values take effect without a restart: the PANW API key on the next scan and
+// the TLS key on the next certificate reload check. A read that fails keeps
+// the previous value. State file encryption keys are only read at startup,
+// since rotating them requires `store rekey` anyway.
+use crate::config::VaultConfig;
+use crate::http_client;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+// Prefix of secret references.
+const REFERENCE_PREFIX: &str = "vault:";
+
+// Errors that can occur while resolving a secret.
+#[derive(Debug, Error)]
+pub enum VaultError {
+    // Reference is malformed or Vault is not configured
+    #[error("Invalid secret reference {0}")]
+    InvalidReference(String),
+
+    // Vault could not be reached or rejected the request
+    #[error("Vault request failed: {0}")]
+    RequestFailed(String),
+
+    // Secret or field does not exist
+    #[error("Secret {0} not found in Vault")]
+    NotFound(String),
+}
+
+// Returns whether a configured value is a Vault reference.
+pub fn is_reference(value: &str) -> bool {
+    value.starts_with(REFERENCE_PREFIX)
+}
+
+// Secret value that is kept up to date by the refresh task.
+//
+// Cloning the secret is cheap and all clones share the same value.
+#[derive(Clone)]
+pub struct SharedSecret {
+    // Current value
+    value: Arc<RwLock<String>>,
+
+    // Incremented whenever the value changes
+    version: Arc<AtomicU64>,
+}
+
+impl SharedSecret {
+    // Creates a secret holding a fixed value.
+    pub fn new(value: impl Into<String>) -> Self {
+        Self {
+            value: Arc::new(RwLock::new(value.into())),
+            version: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Returns the current value.
+    pub fn get(&self) -> String {
+        self.value.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Returns the number of times the value has changed.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    // Replaces the value, returning whether it changed.
+    fn set(&self, value: String) -> bool {
+        let mut current = self.value.write().unwrap_or_else(|e| e.into_inner());
+        if *current == value {
+            return false;
+        }
+        *current = value;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+}
+
+impl fmt::Debug for SharedSecret {
+    // Never prints the value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedSecret(redacted)")
+    }
+}
+
+// Reads secrets from Vault and keeps the referenced ones up to date.
+//
+// Cloning the client is cheap and all clones share the same token and
+// watched secrets.
+#[derive(Clone)]
+pub struct VaultClient {
+    // Vault address, authentication and refresh settings
+    config: Arc<VaultConfig>,
+
+    // HTTP client used to talk to Vault
+    client: Client,
+
+    // Token obtained with AppRole, if any
+    token: Arc<Mutex<Option<String>>>,
+
+    // Secrets refreshed in the background, keyed by reference
+    watched: Arc<Mutex<Vec<(String, SharedSecret)>>>,
+}
+
+impl VaultClient {
+    // Creates a client for the configured Vault server.
+    //
+    // # Errors
+    //
+    // Returns an error if the HTTP client cannot be created
+    pub fn new(config: &VaultConfig) -> Result<Self, reqwest::Error> {
+        let client = http_client::builder(
+            &config.http_client,
+            Some(http_client::DEFAULT_VAULT_REQUEST_TIMEOUT),
+        )
+        .build()?;
+        Ok(Self {
+            config: Arc::new(config.clone()),
+            client,
+            token: Arc::new(Mutex::new(None)),
+            watched: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    // Resolves a configured value once.
+    //
+    // Values that are not references are returned unchanged.
+    //
+    // # Errors
+    //
+    // Returns an error if the referenced secret cannot be read.
+    pub async fn resolve(&self, value: &str) -> Result<String, VaultError> {
+        if !is_reference(value) {
+            return Ok(value.to_string());
+        }
+        self.read(value).await
+    }
+
+    // Resolves a configured value and keeps it up to date.
+    //
+    // Values that are not references never change.
+    //
+    // # Errors
+    //
+    // Returns an error if the referenced secret cannot be read.
+    pub async fn watch(&self, value: &str) -> Result<SharedSecret, VaultError> {
+        let secret = SharedSecret::new(self.resolve(value).await?);
+        if is_reference(value) {
+            self.watched
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push((value.to_string(), secret.clone()));
+        }
+        Ok(secret)
+    }
+
+    // Reads a referenced field, logging in again once if the token was rejected.
+    async fn read(&self, reference: &str) -> Result<String, VaultError> {
+        let (path, field) = reference
+            .strip_prefix(REFERENCE_PREFIX)
+            .and_then(|reference| reference.split_once('#'))
+            .filter(|(path, field)| !path.is_empty() && !field.is_empty())
+            .ok_or_else(|| VaultError::InvalidReference(reference.to_string()))?;
+        if self.config.address.is_empty() {
+            return Err(VaultError::InvalidReference(format!(
+                "{} (no Vault address configured)",
+                reference
+            )));
+        }
+
+        let url = format!(
+            "{}/v1/{}/data/{}",
+            self.config.address.trim_end_matches('/'),
+            self.config.kv_mount,
+            path.trim_start_matches('/')
+        );
+        debug!("Reading secret {} from Vault", reference);
+
+        let mut response = self.send(self.client.get(&url)).await?;
+        if response.status() == StatusCode::FORBIDDEN && self.uses_approle() {
+            debug!("Vault token rejected, logging in again");
+            self.token.lock().unwrap_or_else(|e| e.into_inner()).take();
+            response = self.send(self.client.get(&url)).await?;
+        }
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(VaultError::NotFound(reference.to_string()));
+        }
+
+        let body: Value = response
+            .error_for_status()
+            .map_err(|e| VaultError::RequestFailed(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| VaultError::RequestFailed(e.to_string()))?;
+        body.pointer("/data/data")
+            .and_then(|data| data.get(field))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| VaultError::NotFound(reference.to_string()))
+    }
+
+    // Sends a request with the Vault token and namespace.
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, VaultError> {
+        let mut request = request.header("X-Vault-Token", self.token().await?);
+        if !self.config.namespace.is_empty() {
+            request = request.header("X-Vault-Namespace", &self.config.namespace);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| VaultError::RequestFailed(e.to_string()))
+    }
+
+    // Returns whether the client authenticates with AppRole.
+    fn uses_approle(&self) -> bool {
+        self.config.token.is_empty()
+    }
+
+    // Returns the configured token or logs in with AppRole.
+    async fn token(&self) -> Result<String, VaultError> {
+        if !self.uses_approle() {
+            return Ok(self.config.token.clone());
+        }
+        if let Some(token) = self.token.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+            return Ok(token);
+        }
+
+        let url = format!(
+            "{}/v1/auth/{}/login",
+            self.config.address.trim_end_matches('/'),
+            self.config.approle_mount
+        );
+        let mut request = self.client.post(&url).json(&json!({
+            "role_id": self.config.role_id,
+            "secret_id": self.config.secret_id,
+        }));
+        if !self.config.namespace.is_empty() {
+            request = request.header("X-Vault-Namespace", &self.config.namespace);
+        }
+        let body: Value = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| VaultError::RequestFailed(format!("AppRole login: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| VaultError::RequestFailed(format!("AppRole login: {}", e)))?;
+        let token = body
+            .pointer("/auth/client_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| VaultError::RequestFailed("AppRole login returned no token".into()))?
+            .to_string();
+
+        info!("Logged in to Vault with AppRole");
+        *self.token.lock().unwrap_or_else(|e| e.into_inner()) = Some(token.clone());
+        Ok(token)
+    }
+}
+
+// Refreshes the watched secrets in the background.
+//
+// Does nothing when no secrets are watched or refreshing is disabled.
+pub fn spawn_refresh(vault: VaultClient) {
+    let watched = vault
+        .watched
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    if watched.is_empty() || vault.config.refresh_secs == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(vault.config.refresh_secs));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            for (reference, secret) in &watched {
+                match vault.read(reference).await {
+                    Ok(value) => {
+                        if secret.set(value) {
+                            info!("Secret {} changed in Vault, using the new value", reference);
+                        }
+                    }
+                    Err(e) => warn!(
+                        "Failed to refresh {}, keeping the previous value: {}",
+                        reference, e
+                    ),
+                }
+            }
+        }
+    });
+}
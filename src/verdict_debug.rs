@@ -0,0 +1,247 @@
+// Explanations of assessment decisions for diagnosing verdicts.
+//
+// When content is or is not blocked unexpectedly, the verdict alone does not
+// say why. Requests sent with the admin token and an `X-Debug-Verdict: true`
+// header receive a `verdict_debug` object describing every assessment made
+// for them:
+//
+// - the checks run in order (`policy_path`) and the one that decided the
+//   verdict (`decided_by`): `language_policy`, `toxicity_filter`, `panw`,
+//   `scan_cache`, `secret_scanner`, `scan_budget` or `empty_content`
+// - the PANW profile, report ID and detections that fired
+// - the latency of each assessment and their total
+// - whether the response was replayed from the duplicate or idempotency cache
+//   (`cache`), in which case no assessments were made for the request
+//
+// # Placement
+//
+// The object is added as a top-level field of JSON responses, and of the final
+// chunk of streamed responses. Server-sent event streams are left unchanged.
+// When client authentication is enabled the admin token is only accepted
+// together with `X-Act-As-User`, so debug requests are sent as a client.
+use crate::dedup::{DUPLICATE_HEADER, IDEMPOTENT_REPLAY_HEADER};
+use crate::handlers::admin::require_admin;
+use crate::handlers::ApiError;
+use crate::sse::SSE_CONTENT_TYPE;
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Request, State},
+    http::header::{CONTENT_LENGTH, CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+// Request header asking for verdict explanations.
+const DEBUG_VERDICT_HEADER: &str = "x-debug-verdict";
+
+// Field of the response carrying the explanations.
+const DEBUG_FIELD: &str = "verdict_debug";
+
+// Largest non-streamed response body extended with the explanations.
+const MAX_RESPONSE_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+// Explanation of a single assessment.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssessmentTrace {
+    // Whether a prompt or a response was assessed
+    pub stage: &'static str,
+
+    // Model the content was sent to or generated by
+    pub model: String,
+
+    // PANW security profile the content was scanned with
+    pub profile: String,
+
+    // Checks run, in order, up to the one that decided the verdict
+    pub policy_path: Vec<&'static str>,
+
+    // Check that decided the verdict
+    pub decided_by: &'static str,
+
+    // Action and category of the verdict
+    pub action: String,
+    pub category: String,
+
+    // Whether the content was masked instead of blocked
+    pub masked: bool,
+
+    // Detections that fired
+    pub detections: Vec<&'static str>,
+
+    // PANW report ID, if the content was scanned by PANW
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_id: Option<String>,
+
+    // Error that failed the assessment, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    // Duration of the assessment
+    pub latency_ms: u64,
+}
+
+// Explanations of the assessments made for a single request.
+//
+// Cloning the trail is cheap and all clones record into the same list.
+#[derive(Clone, Default)]
+pub struct VerdictTrail {
+    assessments: Arc<Mutex<Vec<AssessmentTrace>>>,
+}
+
+impl VerdictTrail {
+    // Records the explanation of a completed assessment.
+    pub fn record(&self, trace: AssessmentTrace) {
+        self.assessments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(trace);
+    }
+
+    // Builds the explanations attached to the response.
+    fn explain(&self, cache: &'static str) -> Value {
+        let assessments = self
+            .assessments
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        serde_json::to_value(VerdictDebug {
+            cache,
+            scan_latency_ms: assessments.iter().map(|trace| trace.latency_ms).sum(),
+            assessments,
+        })
+        .unwrap_or_default()
+    }
+}
+
+// Explanations attached to a response.
+#[derive(Serialize)]
+struct VerdictDebug {
+    // `duplicate` or `idempotent_replay` for replayed responses, `miss` otherwise
+    cache: &'static str,
+
+    // Total duration of the assessments
+    scan_latency_ms: u64,
+
+    assessments: Vec<AssessmentTrace>,
+}
+
+// Middleware attaching verdict explanations to responses of admin requests
+// that ask for them.
+pub async fn attach_verdict_debug(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let requested = request
+        .headers()
+        .get(DEBUG_VERDICT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"));
+    if !requested {
+        return next.run(request).await;
+    }
+    if require_admin(&state, request.headers()).is_err() {
+        return ApiError::Unauthorized(format!(
+            "{} requires the admin token",
+            DEBUG_VERDICT_HEADER
+        ))
+        .into_response();
+    }
+
+    debug!(
+        "Explaining verdicts of {} {}",
+        request.method(),
+        request.uri().path()
+    );
+    let trail = VerdictTrail::default();
+    request.extensions_mut().insert(trail.clone());
+
+    let response = next.run(request).await;
+    let cache = if response.headers().contains_key(DUPLICATE_HEADER) {
+        "duplicate"
+    } else if response.headers().contains_key(IDEMPOTENT_REPLAY_HEADER) {
+        "idempotent_replay"
+    } else {
+        "miss"
+    };
+    let is_sse = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(SSE_CONTENT_TYPE));
+    if is_sse {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+
+    // Complete bodies are extended as a whole
+    if body.size_hint().exact().is_some() {
+        let bytes = match to_bytes(body, MAX_RESPONSE_BODY_BYTES).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return ApiError::InternalError(format!("Failed to read response body: {}", e))
+                    .into_response();
+            }
+        };
+        let body = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(Value::Object(mut object)) => {
+                object.insert(DEBUG_FIELD.to_string(), trail.explain(cache));
+                Body::from(Value::Object(object).to_string())
+            }
+            _ => Body::from(bytes),
+        };
+        return Response::from_parts(parts, body);
+    }
+
+    // Streams are split into lines and the final chunk is extended once the
+    // assessments of the content before it have been recorded
+    let lines = stream::unfold(
+        (body.into_data_stream(), BytesMut::new(), false),
+        move |(mut inner, mut buffer, mut finished)| {
+            let trail = trail.clone();
+            async move {
+                loop {
+                    if let Some(end) = buffer.iter().position(|&byte| byte == b'\n') {
+                        let line = buffer.split_to(end + 1).freeze();
+                        let line = extend_final_chunk(line, &trail, cache);
+                        return Some((Ok(line), (inner, buffer, finished)));
+                    }
+                    if finished {
+                        if buffer.is_empty() {
+                            return None;
+                        }
+                        let line = extend_final_chunk(buffer.split().freeze(), &trail, cache);
+                        return Some((Ok(line), (inner, buffer, finished)));
+                    }
+                    match inner.next().await {
+                        Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                        Some(Err(e)) => return Some((Err(e), (inner, buffer, true))),
+                        None => finished = true,
+                    }
+                }
+            }
+        },
+    );
+    Response::from_parts(parts, Body::from_stream(lines))
+}
+
+// Adds the explanations to a streamed line if it is the final chunk.
+fn extend_final_chunk(line: Bytes, trail: &VerdictTrail, cache: &'static str) -> Bytes {
+    match serde_json::from_slice::<Value>(&line) {
+        Ok(Value::Object(mut chunk)) if chunk.get("done") == Some(&Value::Bool(true)) => {
+            chunk.insert(DEBUG_FIELD.to_string(), trail.explain(cache));
+            let newline = if line.ends_with(b"\n") { "\n" } else { "" };
+            Bytes::from(format!("{}{}", Value::Object(chunk), newline))
+        }
+        _ => line,
+    }
+}